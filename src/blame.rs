@@ -0,0 +1,339 @@
+//! Showing who last changed a line with `:blame`.
+//!
+//! `git blame` is run in the background, since it can take a while in large repositories. The
+//! buffer's contents are passed to git, so that lines are blamed correctly even if the buffer has
+//! unsaved changes.
+
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use futures::channel::mpsc;
+use futures::{future, SinkExt};
+use log::*;
+use thiserror::Error;
+use tokio::io::{self, AsyncWriteExt};
+use tokio::process::Command;
+
+/// The commit hash that git reports for lines that haven't been committed.
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+/// The number of characters of the commit hash that are shown.
+const SHORT_HASH_LEN: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to run git: {0}")]
+    Io(#[source] io::Error),
+
+    #[error("git blame failed: {0}")]
+    Failed(String),
+
+    #[error("could not parse output of git blame")]
+    Parse,
+}
+
+/// The commit that last changed a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blame {
+    /// The full hash of the commit, or `None` if the line hasn't been committed.
+    pub commit: Option<String>,
+
+    pub author: String,
+
+    /// The date of the commit in the author's time zone, as `YYYY-MM-DD`.
+    pub date: String,
+
+    /// The first line of the commit message.
+    pub summary: String,
+}
+
+impl Display for Blame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.commit {
+            Some(commit) => write!(
+                f,
+                "{} {}, {} · {}",
+                &commit[..SHORT_HASH_LEN],
+                self.author,
+                self.date,
+                self.summary
+            ),
+            None => write!(f, "Not committed yet"),
+        }
+    }
+}
+
+/// The blame for a line of a buffer.
+#[derive(Debug)]
+pub struct Report {
+    /// The path of the blamed buffer.
+    pub path: PathBuf,
+
+    /// The version of the buffer that was blamed.
+    pub version: u32,
+
+    /// The zero-indexed line that was blamed.
+    pub line: usize,
+
+    pub blame: Result<Blame, Error>,
+}
+
+/// Blames a line of a buffer in the background, sending a report once git exits.
+///
+/// `text` is the current contents of the buffer, which may differ from the file on disk.
+pub fn spawn(
+    path: PathBuf,
+    version: u32,
+    line: usize,
+    text: String,
+    mut report_sender: mpsc::Sender<Report>,
+) {
+    tokio::spawn(async move {
+        let blame = run(&path, line, text).await;
+
+        let report = Report {
+            path,
+            version,
+            line,
+            blame,
+        };
+
+        if report_sender.send(report).await.is_err() {
+            info!("editor exited before blame finished");
+        }
+    });
+}
+
+/// Runs `git blame` for a zero-indexed line of a file, whose contents are `text`.
+async fn run(path: &Path, line: usize, text: String) -> Result<Blame, Error> {
+    let mut command = Command::new("git");
+    command
+        .arg("blame")
+        .arg("--porcelain")
+        .arg("-L")
+        .arg(format!("{},+1", line + 1))
+        .arg("--contents")
+        .arg("-")
+        .arg("--")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(dir) = path.parent() {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn().map_err(Error::Io)?;
+
+    let mut stdin = child.stdin.take().expect("stdin was not piped");
+    let write_stdin = async move {
+        if let Err(e) = stdin.write_all(text.as_bytes()).await {
+            warn!("unable to write to git: {}", e);
+        }
+    };
+
+    let (_, output) = future::join(write_stdin, child.wait_with_output()).await;
+    let output = output.map_err(Error::Io)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .next()
+            .map(String::from)
+            .unwrap_or_else(|| output.status.to_string());
+        return Err(Error::Failed(reason));
+    }
+
+    parse_porcelain(&String::from_utf8_lossy(&output.stdout)).ok_or(Error::Parse)
+}
+
+/// Parses the output of `git blame --porcelain` for a single line.
+fn parse_porcelain(output: &str) -> Option<Blame> {
+    let mut lines = output.lines();
+    let commit = lines.next()?.split_whitespace().next()?;
+
+    let mut author = None;
+    let mut time = None;
+    let mut tz = None;
+    let mut summary = None;
+
+    // The header ends with the contents of the line, which is prefixed with a tab.
+    for line in lines.take_while(|line| !line.starts_with('\t')) {
+        let (key, value) = match line.find(' ') {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            None => (line, ""),
+        };
+
+        match key {
+            "author" => author = Some(value),
+            "author-time" => time = value.parse::<i64>().ok(),
+            "author-tz" => tz = parse_tz(value),
+            "summary" => summary = Some(value),
+            _ => (),
+        }
+    }
+
+    if commit == UNCOMMITTED {
+        return Some(Blame {
+            commit: None,
+            author: author?.to_owned(),
+            date: String::new(),
+            summary: String::new(),
+        });
+    }
+
+    Some(Blame {
+        commit: Some(commit.to_owned()),
+        author: author?.to_owned(),
+        date: format_date(time? + tz.unwrap_or(0)),
+        summary: summary.unwrap_or_default().to_owned(),
+    })
+}
+
+/// Parses a time zone offset such as `-0700` into seconds.
+fn parse_tz(tz: &str) -> Option<i64> {
+    let sign = match tz.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours = tz.get(1..3)?.parse::<i64>().ok()?;
+    let minutes = tz.get(3..5)?.parse::<i64>().ok()?;
+
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Formats seconds since the Unix epoch as a `YYYY-MM-DD` date.
+fn format_date(timestamp: i64) -> String {
+    // Converts days since the epoch to a civil date, following
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let days = timestamp.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::Command;
+
+    use indoc::indoc;
+
+    use super::{format_date, parse_porcelain, parse_tz, run, Blame};
+
+    #[test]
+    fn parse_committed_line() {
+        let output = indoc! {"
+            4f1d3a9c2b7e8f60a1b2c3d4e5f60718293a4b5c 12 3 1
+            author Jane Doe
+            author-mail <jane@example.com>
+            author-time 1589342400
+            author-tz -0700
+            committer Jane Doe
+            committer-mail <jane@example.com>
+            committer-time 1589342400
+            committer-tz -0700
+            summary Fix the frobnicator
+            filename src/lib.rs
+            \tlet x = 1;
+        "};
+
+        let blame = parse_porcelain(output).unwrap();
+        assert_eq!(
+            blame,
+            Blame {
+                commit: Some(String::from("4f1d3a9c2b7e8f60a1b2c3d4e5f60718293a4b5c")),
+                author: String::from("Jane Doe"),
+                date: String::from("2020-05-12"),
+                summary: String::from("Fix the frobnicator"),
+            }
+        );
+        assert_eq!(
+            blame.to_string(),
+            "4f1d3a9c Jane Doe, 2020-05-12 · Fix the frobnicator"
+        );
+    }
+
+    #[test]
+    fn parse_uncommitted_line() {
+        let output = indoc! {"
+            0000000000000000000000000000000000000000 1 1 1
+            author Not Committed Yet
+            author-time 1589342400
+            author-tz +0000
+            summary Version of src/lib.rs from src/lib.rs
+            \tnew line
+        "};
+
+        assert_eq!(
+            parse_porcelain(output).unwrap().to_string(),
+            "Not committed yet"
+        );
+        assert_eq!(parse_porcelain(""), None);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(-86_400), "1969-12-31");
+        assert_eq!(parse_tz("+0530"), Some(19_800));
+        assert_eq!(parse_tz("-0700"), Some(-25_200));
+        assert_eq!(parse_tz("0700"), None);
+    }
+
+    #[tokio::test]
+    async fn blame_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .env("GIT_AUTHOR_NAME", "Jane Doe")
+                .env("GIT_AUTHOR_EMAIL", "jane@example.com")
+                .env("GIT_AUTHOR_DATE", "2020-05-12T12:00:00+00:00")
+                .env("GIT_COMMITTER_NAME", "Jane Doe")
+                .env("GIT_COMMITTER_EMAIL", "jane@example.com")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "first\n").unwrap();
+        git(&["init", "--quiet"]);
+        git(&["add", "file.txt"]);
+        git(&["commit", "--quiet", "--message", "Add file"]);
+
+        let text = String::from("first\nsecond\n");
+
+        let blame = run(&path, 0, text.clone()).await.unwrap();
+        assert_eq!(blame.author, "Jane Doe");
+        assert_eq!(blame.date, "2020-05-12");
+        assert_eq!(blame.summary, "Add file");
+
+        let blame = run(&path, 1, text).await.unwrap();
+        assert_eq!(blame.commit, None);
+
+        assert!(run(Path::new("/nonexistent/file.txt"), 0, String::new())
+            .await
+            .is_err());
+    }
+}
@@ -1,31 +1,73 @@
 //! Text editing buffers and buffer management.
 
 use std::cmp;
+use std::convert::TryFrom;
 use std::env;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
+use encoding_rs::{Encoding, UTF_8};
 use euclid::{Point2D, Rect};
+use futures::future::{self, FutureExt};
 use futures::stream::{self, StreamExt, TryStreamExt};
-use itertools::Itertools;
 use log::*;
-use lsp_types::{TextDocumentItem, VersionedTextDocumentIdentifier};
-use tokio::fs::{self, File};
-use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tokio_stream::wrappers::LinesStream;
+use lsp_types::{
+    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    VersionedTextDocumentIdentifier,
+};
+use tokio::fs;
+use tokio::io;
+use tokio::task;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
 
 use crate::lsp::ToUri;
+use crate::options::{self, BufferOptions, WindowOptions};
 use crate::syntax::Syntax;
-use crate::ui::{Bounds, Color, Context, Coordinates, Drawable};
+use crate::ui::{Bounds, Color, Context, Coordinates, Drawable, Style};
 
+mod columns;
+mod diagnostics;
+mod directory;
 mod edit;
+mod encoding;
+mod file_format;
+mod fold;
+mod help;
+mod hex;
 mod highlight;
+mod indent;
+mod line_numbers;
+mod loader;
+mod log_view;
 mod motion;
+mod search;
+mod selection;
+mod shading;
+mod snippet;
+mod statistics;
 mod storage;
+mod terminal;
 mod units;
-
+mod url;
+mod wrap;
+
+pub use edit::Edit;
+pub use file_format::FileFormat;
+pub use highlight::Symbol;
+pub use indent::Indent;
+pub use motion::{Motion, Scroll};
+pub use search::{Direction, SearchOptions, SearchResult};
+pub use snippet::Snippet;
+
+use columns::column_of;
+use diagnostics::Diagnostics;
+use encoding::Decoded;
 use highlight::Highlighter;
 use motion::Cursor;
+use snippet::TabStops;
 use storage::Storage;
+use units::{ByteIndex, CharPosition};
 
 /// Unit for buffer-internal positions and lengths.
 pub struct BufferSpace;
@@ -41,6 +83,9 @@ pub type Offset = euclid::Vector2D<isize, BufferSpace>;
 /// This area is endpoint-exclusive.
 pub type Span = Rect<usize, BufferSpace>;
 
+/// Files larger than this many bytes are opened without highlighting or language servers.
+const LARGE_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
 /// Container for all open buffers.
 ///
 /// Also keeps track of which buffer is considered the current (or active) buffer.
@@ -50,7 +95,8 @@ pub struct Buffers {
 }
 
 impl Buffers {
-    pub async fn from_paths(paths: Vec<PathBuf>, bounds: Bounds) -> io::Result<Self> {
+    /// Opens buffers for paths, showing them as hex dumps if `binary` is true.
+    pub async fn from_paths(paths: Vec<PathBuf>, bounds: Bounds, binary: bool) -> io::Result<Self> {
         let mut buffers = if paths.is_empty() {
             Buffers {
                 buffers: vec![Buffer::new()],
@@ -66,7 +112,11 @@ impl Buffers {
                         }
                     }
 
-                    Buffer::open(path).await
+                    if binary {
+                        Buffer::open_binary(path).await
+                    } else {
+                        Buffer::open(path).await
+                    }
                 })
                 .try_collect()
                 .await?;
@@ -96,6 +146,184 @@ impl Buffers {
     pub fn current_mut(&mut self) -> &mut Buffer {
         &mut self.buffers[self.current]
     }
+
+    /// Changes the area of the screen that the active buffer is drawn in, scrolling it to keep the
+    /// cursor visible. Does nothing if the size of the area hasn't changed.
+    pub fn set_bounds(&mut self, bounds: Bounds) {
+        let size = bounds.to_rect().to_usize().cast_unit().size;
+        let buffer = self.current_mut();
+        let column = buffer.cursor_column();
+
+        match &mut buffer.viewport {
+            Some(viewport) if viewport.size != size => {
+                viewport.size = size;
+
+                // A larger viewport scrolls back, so that it doesn't show blank lines past the end
+                // of the buffer, or columns left of the cursor that no longer need to be hidden.
+                viewport.origin.y = cmp::min(
+                    viewport.origin.y,
+                    buffer.storage.lines().saturating_sub(size.height),
+                );
+                viewport.origin.x =
+                    cmp::min(viewport.origin.x, (column + 1).saturating_sub(size.width));
+            }
+            _ => return,
+        }
+
+        buffer.move_offset(Offset::zero());
+    }
+
+    /// Adds a buffer and makes it the active buffer.
+    ///
+    /// The new buffer takes over the viewport and window options of the previously active buffer.
+    pub fn push(&mut self, buffer: Buffer) {
+        self.buffers.push(buffer);
+        self.focus_index(self.buffers.len() - 1);
+    }
+
+    /// Adds a buffer without making it the active buffer, returning it.
+    pub fn add(&mut self, buffer: Buffer) -> &Buffer {
+        self.buffers.push(buffer);
+        self.buffers.last().expect("buffer was just added")
+    }
+
+    /// Makes the buffer for a path the active buffer, opening it if there isn't one already.
+    ///
+    /// The buffer takes over the viewport and window options of the previously active buffer. The
+    /// path must be absolute. The boolean is `true` if a new buffer was opened.
+    pub async fn focus(&mut self, path: &Path) -> io::Result<(&mut Buffer, bool)> {
+        let (index, opened) = match self.index_of(path) {
+            Some(index) => (index, false),
+            None => {
+                self.buffers.push(Buffer::open(path.to_owned()).await?);
+                (self.buffers.len() - 1, true)
+            }
+        };
+
+        self.focus_index(index);
+
+        Ok((self.current_mut(), opened))
+    }
+
+    /// Makes the buffer at an index the active buffer.
+    ///
+    /// The buffer takes over the viewport and window options of the previously active buffer.
+    pub fn focus_index(&mut self, index: usize) {
+        if index != self.current {
+            let viewport = self.current_mut().hide();
+            let window = self.current().window;
+            self.show(index, viewport, window);
+        }
+    }
+
+    /// Makes the buffer at an index the active buffer, showing it in a viewport of the same size.
+    ///
+    /// The buffer is scrolled back to where it was when it was last hidden, and then as little as
+    /// needed to show the cursor.
+    fn show(&mut self, index: usize, viewport: Option<Span>, window: WindowOptions) {
+        let buffer = &mut self.buffers[index];
+        buffer.viewport = viewport.map(|viewport| Span::new(buffer.hidden_origin, viewport.size));
+        buffer.window = window;
+        buffer.move_offset(Offset::zero());
+        self.current = index;
+    }
+
+    /// Makes the buffer after the active buffer active, continuing from the first after the last.
+    pub fn focus_next(&mut self) {
+        self.focus_index((self.current + 1) % self.buffers.len());
+    }
+
+    /// Makes the buffer before the active buffer active, continuing from the last before the
+    /// first.
+    pub fn focus_previous(&mut self) {
+        let len = self.buffers.len();
+        self.focus_index((self.current + len - 1) % len);
+    }
+
+    /// Closes the buffer at an index, returning it.
+    ///
+    /// If the active buffer is closed, the buffer after it becomes active and takes over its
+    /// viewport and window options. Closing the only buffer leaves an empty buffer in its place.
+    pub fn remove(&mut self, index: usize) -> Buffer {
+        if self.buffers.len() == 1 {
+            self.buffers.push(Buffer::new());
+        }
+
+        let mut buffer = self.buffers.remove(index);
+
+        if index < self.current {
+            self.current -= 1;
+        } else if index == self.current {
+            let viewport = buffer.hide();
+            self.show(
+                cmp::min(index, self.buffers.len() - 1),
+                viewport,
+                buffer.window,
+            );
+        }
+
+        buffer
+    }
+
+    /// Waits for the active buffer's syntax tree to be updated by a background parse, or for more
+    /// of a large file to be read into its buffer.
+    ///
+    /// Never completes if nothing is happening in the background.
+    pub async fn background_work(&mut self) {
+        let current = self.current;
+        let work = self
+            .buffers
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, buffer)| {
+                // Large files aren't highlighted, so they're never parsed.
+                if buffer.loader.is_some() {
+                    Some(buffer.load_chunk().boxed())
+                } else if i == current {
+                    Some(buffer.parsed().boxed())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if work.is_empty() {
+            future::pending().await
+        } else {
+            future::select_all(work).await;
+        }
+    }
+
+    /// Returns the index of the buffer for a path, if it's open.
+    pub fn index_of(&self, path: &Path) -> Option<usize> {
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.path.as_deref() == Some(path))
+    }
+
+    /// Returns the buffer for a path, if it's open.
+    pub fn get_mut(&mut self, path: &Path) -> Option<&mut Buffer> {
+        self.buffers
+            .iter_mut()
+            .find(|buffer| buffer.path.as_deref() == Some(path))
+    }
+
+    /// Returns the buffer for a path, opening a hidden buffer for it if there isn't one already.
+    ///
+    /// The path must be absolute. The boolean is `true` if a new buffer was opened.
+    pub async fn get_or_open(&mut self, path: &Path) -> io::Result<(&mut Buffer, bool)> {
+        match self.index_of(path) {
+            Some(index) => Ok((&mut self.buffers[index], false)),
+            None => {
+                let buffer = Buffer::open(path.to_owned()).await?;
+                self.buffers.push(buffer);
+                Ok((
+                    self.buffers.last_mut().expect("buffer was just pushed"),
+                    true,
+                ))
+            }
+        }
+    }
 }
 
 impl<'a> IntoIterator for &'a Buffers {
@@ -107,22 +335,87 @@ impl<'a> IntoIterator for &'a Buffers {
     }
 }
 
+impl<'a> IntoIterator for &'a mut Buffers {
+    type Item = &'a mut Buffer;
+    type IntoIter = std::slice::IterMut<'a, Buffer>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.buffers.iter_mut()
+    }
+}
+
 /// An in-memory view of a file.
 pub struct Buffer {
     /// The file path that this buffer represents.
     path: Option<PathBuf>,
 
+    /// Whether the buffer lists the entries of the directory at `path`, instead of containing a
+    /// file.
+    directory: bool,
+
+    /// The name of the help document that the buffer shows, which can't be edited.
+    help: Option<&'static str>,
+
+    /// The terminal emulator, if the buffer shows a program running in a terminal.
+    terminal: Option<terminal::Emulator>,
+
+    /// The log file that the buffer follows, if it shows the editor's log.
+    log: Option<log_view::LogFollower>,
+
+    /// The working directory of the buffer, if it was changed with `:lcd`.
+    local_dir: Option<PathBuf>,
+
+    /// The encoding of the file, which the buffer's contents are decoded from and encoded to.
+    encoding: &'static Encoding,
+
+    /// Whether the file starts with a byte order mark. The mark isn't part of the buffer's contents.
+    bom: bool,
+
+    /// The line ending of the file. Lines are always separated by `\n` in the buffer itself.
+    file_format: FileFormat,
+
+    /// The bytes of the file as they were last read or written, if the buffer shows the file as a
+    /// hex dump.
+    binary: Option<Vec<u8>>,
+
+    /// Whether the file is too large to be highlighted or sent to language servers.
+    large: bool,
+
+    /// The rest of the file, if it's large and is still being read.
+    loader: Option<loader::Loader>,
+
+    /// Whether the file ends with a newline. Files without one are written back without one.
+    eol: bool,
+
     /// Buffer contents.
     storage: Storage,
 
-    /// The version of the document. Increases after each edit, including undo/redo.
+    /// The version of the document. Increases after each edit or transaction, including undo/redo.
     version: u32,
 
+    /// The version of the document when it was last read from or written to disk.
+    saved_version: u32,
+
+    /// Whether the open transaction has made any edits, or `None` if there is no open
+    /// transaction.
+    transaction: Option<bool>,
+
     /// The cursor position within the buffer.
     ///
     /// The on-screen cursor location is determined by offsetting this position with the viewport.
     cursor: Cursor,
 
+    /// Whether the cursor may be after the last character of its line, as in insert mode. The
+    /// editor turns this off in normal mode.
+    cursor_past_end: bool,
+
+    /// Where the selection started in visual mode, or `None` if nothing is selected. The cursor is
+    /// the other end.
+    selection_anchor: Option<Position>,
+
+    /// The tab stops of the snippet being filled in.
+    tab_stops: TabStops,
+
     /// Syntax associated with the buffer.
     ///
     /// `None` if unknown or plain-text.
@@ -135,54 +428,317 @@ pub struct Buffer {
     ///
     /// `None` if the buffer is hidden.
     viewport: Option<Span>,
+
+    /// Where the viewport was scrolled to when the buffer was hidden, so that it's shown the same
+    /// way again.
+    hidden_origin: Position,
+
+    /// The options of the window showing the buffer, which move with the viewport.
+    window: WindowOptions,
+
+    /// The buffer-local options.
+    options: BufferOptions,
+
+    /// How the file was indented when it was opened, which overrides the options.
+    indent: Option<Indent>,
+
+    /// Errors and warnings about the buffer's contents.
+    diagnostics: Diagnostics,
 }
 
 impl Buffer {
     pub fn new() -> Self {
         Buffer {
             path: None,
+            directory: false,
+            help: None,
+            terminal: None,
+            log: None,
+            local_dir: None,
+            encoding: UTF_8,
+            bom: false,
+            file_format: FileFormat::Unix,
+            eol: true,
+            binary: None,
+            large: false,
+            loader: None,
+            transaction: None,
             cursor: Cursor::default(),
+            cursor_past_end: true,
+            selection_anchor: None,
+            tab_stops: TabStops::default(),
             storage: Storage::new(),
             version: 0,
+            saved_version: 0,
             syntax: None,
             highlighter: None,
             viewport: None,
+            hidden_origin: Position::zero(),
+            window: WindowOptions::default(),
+            options: options::buffer_defaults(None),
+            indent: None,
+            diagnostics: Diagnostics::default(),
         }
     }
 
+    /// Changes the syntax of the buffer, applying the options set for its language. The detected
+    /// indentation still takes precedence.
     pub fn set_syntax(&mut self, syntax: Option<Syntax>) {
         self.syntax = syntax;
-        self.highlighter = syntax.map(Highlighter::new);
+        self.highlighter = syntax.and_then(load_highlighter);
+        options::apply_language(syntax, &mut self.options);
+
+        if let Some(indent) = self.indent {
+            indent.apply(&mut self.options);
+        }
     }
 
     /// Open a new buffer containing the contents of the given path. The path must be absolute.
+    ///
+    /// If the path is a directory, the buffer lists its entries. Binary files are shown as a hex
+    /// dump.
     pub async fn open(path: PathBuf) -> io::Result<Self> {
         info!("creating buffer for {}", path.display());
 
         assert!(path.is_absolute(), "path must be absolute");
 
-        let lines = if fs::metadata(&path).await.is_ok() {
-            let reader = BufReader::new(File::open(&path).await?);
-            LinesStream::new(reader.lines()).try_collect().await?
-        } else {
-            info!("{} does not exist", path.display());
-            vec![String::new()]
+        let bytes = match fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_dir() => return Buffer::open_directory(path).await,
+            Ok(metadata) if metadata.len() > LARGE_FILE_SIZE => {
+                info!("{} is large, opening without highlighting", path.display());
+                return Buffer::open_large(path).await;
+            }
+            Ok(_) => {
+                let bytes = fs::read(&path).await?;
+                if hex::is_binary(&bytes) {
+                    info!("{} is binary, opening as a hex dump", path.display());
+                    return Ok(Buffer::from_binary(path, bytes));
+                }
+
+                Some(bytes)
+            }
+            Err(_) => {
+                info!("{} does not exist", path.display());
+                None
+            }
         };
 
-        info!("read {} lines", lines.len());
+        // Decoding the file, splitting it into lines and detecting its syntax and indentation take
+        // long enough for large files that they run on a blocking thread, rather than holding up
+        // the other tasks on the worker.
+        let buffer = task::spawn_blocking(move || Buffer::from_file(path, bytes))
+            .await
+            .expect("reading a file panicked");
+
+        Ok(buffer)
+    }
+
+    /// Creates a buffer from the contents of its file, or an empty buffer if the file doesn't
+    /// exist yet.
+    fn from_file(path: PathBuf, bytes: Option<Vec<u8>>) -> Self {
+        let decoded = match bytes {
+            Some(bytes) => encoding::decode(&bytes),
+            None => Decoded {
+                text: String::new(),
+                encoding: UTF_8,
+                bom: false,
+            },
+        };
+
+        let Decoded {
+            text,
+            encoding,
+            bom,
+        } = decoded;
+        let lines = text.lines().map(String::from).collect::<Vec<_>>();
+        let file_format = FileFormat::detect(&text);
+        let eol = text.is_empty() || text.ends_with('\n');
 
-        let syntax = Syntax::identify(&path);
+        info!(
+            "read {} lines as {} ({})",
+            lines.len(),
+            encoding.name(),
+            file_format
+        );
+
+        let syntax = Syntax::identify(&path, lines.iter().map(String::as_str));
         info!("syntax identified: {:?}", syntax);
 
-        Ok(Buffer {
+        let mut options = options::buffer_defaults(syntax);
+        let indent = Indent::detect(lines.iter().map(String::as_str));
+        if let Some(indent) = indent {
+            info!("indentation detected: {}", indent);
+            indent.apply(&mut options);
+        }
+
+        Buffer {
             cursor: Cursor::default(),
+            cursor_past_end: true,
+            selection_anchor: None,
+            tab_stops: TabStops::default(),
             storage: lines.into(),
             version: 0,
+            saved_version: 0,
             path: Some(path),
+            directory: false,
+            help: None,
+            terminal: None,
+            log: None,
+            local_dir: None,
+            encoding,
+            bom,
+            file_format,
+            eol,
+            binary: None,
+            large: false,
+            loader: None,
+            transaction: None,
             syntax,
-            highlighter: syntax.map(Highlighter::new),
+            highlighter: syntax.and_then(load_highlighter),
             viewport: None,
-        })
+            hidden_origin: Position::zero(),
+            window: WindowOptions::default(),
+            options,
+            indent,
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Writes the contents of the buffer to its file.
+    pub async fn save(&mut self) -> io::Result<()> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "buffer has no file name"))?;
+
+        self.write_to(&path).await
+    }
+
+    /// Writes the contents of the buffer to a file, creating it if it doesn't exist.
+    ///
+    /// The file is written with the buffer's line ending, in the encoding that it was read with,
+    /// starting with a byte order mark if it had one. Hex dumps are converted back to bytes.
+    pub async fn write_to(&mut self, path: &Path) -> io::Result<()> {
+        info!("writing buffer to {}", path.display());
+        self.check_loaded()?;
+
+        if self.binary.is_some() {
+            self.write_binary(path).await?;
+            self.saved_version = self.version;
+            return Ok(());
+        }
+
+        let mut text = self.text();
+        if self.file_format != FileFormat::Unix {
+            text = text.replace('\n', self.file_format.line_ending());
+        }
+        fs::write(path, encoding::encode(&text, self.encoding, self.bom)?).await?;
+        self.saved_version = self.version;
+        Ok(())
+    }
+
+    /// The encoding that the buffer's file is read and written with.
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// Returns whether the buffer's file is too large to be highlighted or sent to language
+    /// servers.
+    pub fn is_large(&self) -> bool {
+        self.large
+    }
+
+    /// Returns the line ending that the buffer's file is written with.
+    pub fn file_format(&self) -> FileFormat {
+        self.file_format
+    }
+
+    /// Changes the line ending that the buffer's file is written with, marking the buffer as
+    /// modified if it is different.
+    pub fn set_file_format(&mut self, file_format: FileFormat) {
+        if file_format == self.file_format {
+            return;
+        }
+
+        self.file_format = file_format;
+
+        match &mut self.transaction {
+            Some(edited) => *edited = true,
+            None => self.version += 1,
+        }
+    }
+
+    /// Returns whether the buffer's file is written with a byte order mark.
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// Changes whether the buffer's file is written with a byte order mark.
+    ///
+    /// The mark is only written for Unicode encodings.
+    pub fn set_bom(&mut self, bom: bool) {
+        self.bom = bom;
+    }
+
+    /// The file path that this buffer represents, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Changes the path that this buffer represents, identifying its syntax again.
+    ///
+    /// The path must be absolute.
+    pub fn set_path(&mut self, path: PathBuf) {
+        assert!(path.is_absolute(), "path must be absolute");
+
+        let syntax = if self.large {
+            None
+        } else {
+            Syntax::identify(&path, self.storage.iter_lines())
+        };
+        if syntax != self.syntax {
+            self.set_syntax(syntax);
+        }
+
+        self.path = Some(path);
+    }
+
+    /// The working directory of the buffer, if it differs from the editor's.
+    pub fn local_dir(&self) -> Option<&Path> {
+        self.local_dir.as_deref()
+    }
+
+    /// Changes the working directory of the buffer. The directory must be absolute.
+    pub fn set_local_dir(&mut self, dir: PathBuf) {
+        assert!(dir.is_absolute(), "directory must be absolute");
+        self.local_dir = Some(dir);
+    }
+
+    /// The version of the document, which increases after each edit.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns whether the buffer has been edited since it was last read or written.
+    pub fn is_modified(&self) -> bool {
+        self.version != self.saved_version
+    }
+
+    /// Returns the contents of the buffer, as they would be written to its file.
+    ///
+    /// Lines are separated by `\n`, whatever the buffer's file format. The text ends with a newline
+    /// unless the buffer's file didn't end with one.
+    pub fn text(&self) -> String {
+        let mut text = self.storage.to_string();
+        if !self.eol {
+            text.pop();
+        }
+        text
+    }
+
+    /// Returns an iterator over the lines of the buffer, starting from a zero-indexed line.
+    pub fn lines_from(&self, line: usize) -> impl Iterator<Item = &str> {
+        self.storage.iter_lines().skip(line)
     }
 
     pub fn to_text_document_item(&self) -> Option<TextDocumentItem> {
@@ -194,7 +750,13 @@ impl Buffer {
                 .into_language_id()
                 .to_owned(),
             version: self.version.into(),
-            text: self.storage.to_string(),
+            text: self.text(),
+        })
+    }
+
+    pub fn to_text_document_identifier(&self) -> Option<TextDocumentIdentifier> {
+        Some(TextDocumentIdentifier {
+            uri: self.path.as_ref()?.to_uri(),
         })
     }
 
@@ -205,14 +767,209 @@ impl Buffer {
         })
     }
 
+    /// Returns the cursor's position in the buffer's document, for requests about the text at the
+    /// cursor.
+    pub fn to_text_document_position(&self) -> Option<TextDocumentPositionParams> {
+        let byte = self.storage.line_to_byte(self.cursor.y()) + ByteIndex::new(self.cursor.x());
+        let position = self.storage.byte_to_char_position(byte);
+
+        Some(TextDocumentPositionParams {
+            text_document: self.to_text_document_identifier()?,
+            position: lsp_types::Position::new(position.y as u64, position.x as u64),
+        })
+    }
+
+    /// Returns the position in the buffer of a position from a language server, which counts
+    /// characters instead of bytes. Positions past the end of a line are at the end of the line.
+    pub fn position_from_lsp(&self, position: lsp_types::Position) -> Position {
+        let byte = self.storage.char_to_byte(CharPosition::new(
+            usize::try_from(position.character).unwrap_or(usize::MAX),
+            usize::try_from(position.line).unwrap_or(usize::MAX),
+        ));
+
+        self.storage.position_of_byte(byte).cast_unit()
+    }
+
+    /// Waits for the buffer's syntax tree to be updated by a background parse.
+    ///
+    /// Never completes if the buffer is not being parsed in the background.
+    pub async fn parsed(&mut self) {
+        match &mut self.highlighter {
+            Some(highlighter) => highlighter.parsed().await,
+            None => future::pending().await,
+        }
+    }
+
+    /// Returns the names of the functions and classes enclosing the cursor, from outermost to
+    /// innermost.
+    pub fn breadcrumbs(&self) -> Vec<String> {
+        match &self.highlighter {
+            Some(highlighter) => highlighter.breadcrumbs(self, self.cursor()),
+            None => vec![],
+        }
+    }
+
+    /// Returns the named functions and classes in the buffer, in order.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        match &self.highlighter {
+            Some(highlighter) => highlighter.symbols(self),
+            None => vec![],
+        }
+    }
+
+    /// Returns the position of the cursor within the buffer.
+    pub fn cursor(&self) -> Position {
+        Position::new(self.cursor.x(), self.cursor.y())
+    }
+
+    /// Returns the zero-indexed line of the cursor.
+    pub fn cursor_line(&self) -> usize {
+        self.cursor.y()
+    }
+
+    /// Returns the identifier under the cursor, if any.
+    pub fn identifier_at_cursor(&self) -> Option<&str> {
+        let line = self.storage.line(self.cursor.y());
+        let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+
+        let x = self.cursor.x().min(line.len());
+        let start = line[..x]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_identifier(c))
+            .last()
+            .map_or(x, |(index, _)| index);
+        let end = line[x..]
+            .find(|c| !is_identifier(c))
+            .map_or(line.len(), |index| x + index);
+
+        Some(&line[start..end]).filter(|identifier| !identifier.is_empty())
+    }
+
+    /// Returns the part of the identifier under the cursor that is before it, which may be empty.
+    pub fn identifier_before_cursor(&self) -> &str {
+        let line = self.storage.line(self.cursor.y());
+        let x = self.cursor.x().min(line.len());
+
+        let start = line[..x]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(x, |(index, _)| index);
+
+        &line[start..x]
+    }
+
+    /// Styles the text within a range that is inside the viewport.
+    ///
+    /// Lines within the range are styled to their end. At least one cell is styled on each line,
+    /// even if it is empty, unless the range ends at the start of the line.
+    pub fn style_range(&self, ctx: &mut Context<'_>, range: Range<Position>, style: Style) {
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => return,
+        };
+
+        let Range { start, end } = range;
+
+        let mut last_row = cmp::min(end.y, viewport.max_y().saturating_sub(1));
+        if end.x == 0 && end.y > start.y && last_row == end.y {
+            last_row -= 1;
+        }
+
+        let display_lines = self.display_lines();
+        let first_row = cmp::max(start.y, viewport.min_y());
+        for row in first_row..=cmp::min(last_row, self.storage.lines().saturating_sub(1)) {
+            let line = self.storage.line(row);
+            let line_len = column_of(line, line.len());
+
+            let start_x = if row == start.y {
+                column_of(line, start.x)
+            } else {
+                0
+            };
+            let end_x = if row == end.y {
+                column_of(line, end.x)
+            } else {
+                line_len
+            };
+
+            // Always cover at least one cell, even if the line is empty.
+            let start_x = cmp::min(start_x, line_len.saturating_sub(1));
+            let end_x = cmp::max(cmp::min(end_x, line_len), start_x + 1);
+
+            let origin = ctx.bounds.min;
+            for (y, columns) in display_lines.spans(row, start_x..end_x) {
+                let y = origin.y + y as u16;
+                let bounds = Bounds::new(
+                    Coordinates::new(origin.x + columns.start as u16, y),
+                    Coordinates::new(origin.x + columns.end as u16, y + 1),
+                )
+                .intersection(&ctx.bounds);
+
+                if !bounds.is_empty() {
+                    ctx.screen.apply_style(bounds, style);
+                }
+            }
+        }
+    }
+
+    /// Returns the options of the window showing the buffer.
+    pub fn window_options(&self) -> WindowOptions {
+        self.window
+    }
+
+    pub fn set_window_options(&mut self, options: WindowOptions) {
+        self.window = options;
+    }
+
+    pub fn buffer_options(&self) -> BufferOptions {
+        self.options
+    }
+
+    pub fn set_buffer_options(&mut self, options: BufferOptions) {
+        self.options = options;
+    }
+
+    /// Hides the buffer, returning its viewport. Where the viewport was scrolled to is remembered
+    /// for when the buffer is shown again.
+    fn hide(&mut self) -> Option<Span> {
+        let viewport = self.viewport.take();
+        if let Some(viewport) = viewport {
+            self.hidden_origin = viewport.origin;
+        }
+        viewport
+    }
+
     /// Returns the cursor position relative to the viewport.
+    ///
+    /// When lines are wrapped, a cursor after the end of a line that fills its last display line
+    /// is shown in the last column of that display line, and a cursor past the bottom of a line
+    /// that is taller than the viewport is shown in the bottom-right corner.
     pub fn cursor_position(&self) -> Position {
         let viewport = self
             .viewport
             .expect("attempted to determine cursor position for hidden buffer");
 
+        if self.window.wrap {
+            let display_lines = self.display_lines();
+            let column = self.cursor_column();
+            let last_column = viewport.width().saturating_sub(1);
+
+            return display_lines
+                .position(self.cursor.y(), column)
+                .or_else(|| {
+                    let (row, columns) = display_lines.spans(self.cursor.y(), 0..column).last()?;
+                    Some(Position::new(cmp::min(columns.end, last_column), row))
+                })
+                .unwrap_or_else(|| {
+                    Position::new(last_column, viewport.height().saturating_sub(1))
+                });
+        }
+
         Position::new(
-            self.cursor.x() - viewport.min_x(),
+            self.cursor_column() - viewport.min_x(),
             self.cursor.y() - viewport.min_y(),
         )
     }
@@ -228,12 +985,34 @@ impl<'a> From<&'a str> for Buffer {
     fn from(s: &str) -> Self {
         Buffer {
             cursor: Cursor::default(),
+            cursor_past_end: true,
+            selection_anchor: None,
+            tab_stops: TabStops::default(),
             syntax: None,
             storage: Storage::from(s),
             version: 0,
+            saved_version: 0,
             path: None,
+            directory: false,
+            help: None,
+            terminal: None,
+            log: None,
+            local_dir: None,
+            encoding: UTF_8,
+            bom: false,
+            file_format: FileFormat::Unix,
+            eol: true,
+            binary: None,
+            large: false,
+            loader: None,
+            transaction: None,
             highlighter: None,
             viewport: None,
+            hidden_origin: Position::zero(),
+            window: WindowOptions::default(),
+            options: options::buffer_defaults(None),
+            indent: None,
+            diagnostics: Diagnostics::default(),
         }
     }
 }
@@ -245,35 +1024,100 @@ impl Drawable for Buffer {
             None => return,
         };
 
-        let tilde = String::from("~");
+        self.draw_shading(ctx);
 
-        for (row, line) in self
-            .storage
-            .iter_lines()
-            .skip(viewport.min_y())
-            .pad_using(viewport.height(), |_| &tilde)
-            .enumerate()
-            .take(viewport.height())
-        {
-            // FIXME: Naively assumes ASCII.
-            if viewport.min_x() < line.len() {
-                let max = cmp::min(viewport.max_x(), line.len());
-                let line = &line[viewport.min_x()..max];
-                ctx.screen.write(Coordinates::new(0, row as u16), line);
+        let origin = ctx.bounds.min;
+        let display_lines = self.display_lines();
+
+        for (row, display_line) in display_lines.iter().enumerate() {
+            let line = self.storage.line(display_line.line);
+            let (offset, line) = visible_slice(line, display_line.columns.clone());
+            if !line.is_empty() {
+                ctx.screen.write(
+                    Coordinates::new(origin.x + offset as u16, origin.y + row as u16),
+                    line,
+                );
             }
         }
 
-        for row in (self.storage.lines() - viewport.min_y())..ctx.bounds.height().into() {
+        let rows = cmp::min(viewport.height(), ctx.bounds.height().into());
+        for row in display_lines.rows()..rows {
+            let y = origin.y + row as u16;
+            ctx.screen.write(Coordinates::new(origin.x, y), "~");
+
             let bounds = Bounds::new(
-                Coordinates::new(0, row as u16),
-                Coordinates::new(1, row as u16 + 1),
+                Coordinates::new(origin.x, y),
+                Coordinates::new(origin.x + 1, y + 1),
             );
-
             ctx.screen.apply_color(bounds, Color::BLUE);
         }
 
         if let Some(highlighter) = &self.highlighter {
-            highlighter.highlight(&mut ctx.screen, &self);
+            highlighter.highlight(ctx.screen, origin, self, &display_lines);
+        }
+
+        self.draw_selection(ctx);
+        self.draw_placeholder(ctx);
+        self.draw_diagnostics(ctx);
+    }
+}
+
+/// Returns the part of a line within a range of screen columns, and the number of columns after
+/// the start of the range that it begins at.
+///
+/// Wide characters that are cut off by either end of the range are left out, so the part may
+/// begin a column late. Only the characters before the end of the range are examined. If the
+/// characters before the range are printable ASCII, as in minified code, they take up a column
+/// each and are skipped without being segmented, so lines scrolled far to the right stay fast to
+/// draw.
+fn visible_slice(line: &str, columns: Range<usize>) -> (usize, &str) {
+    let skip = cmp::min(columns.start, line.len());
+    let skip = if line.as_bytes()[..skip]
+        .iter()
+        .all(|&byte| byte == b' ' || byte.is_ascii_graphic())
+    {
+        // The range may start at a combining mark, which belongs to the character before it.
+        let mut cursor = GraphemeCursor::new(skip, line.len(), true);
+        match cursor.is_boundary(line, 0) {
+            Ok(true) => skip,
+            _ => cursor.prev_boundary(line, 0).ok().flatten().unwrap_or(0),
+        }
+    } else {
+        0
+    };
+
+    let mut column = skip;
+    let mut start = None;
+    let mut end = line.len();
+
+    for (index, grapheme) in line[skip..].grapheme_indices(true) {
+        let index = skip + index;
+        let width = grapheme.width();
+        if column + width > columns.end {
+            end = index;
+            break;
+        }
+
+        if start.is_none() && column >= columns.start {
+            start = Some((column - columns.start, index));
+        }
+
+        column += width;
+    }
+
+    match start {
+        Some((offset, start)) => (offset, &line[start..end]),
+        None => (0, ""),
+    }
+}
+
+/// Creates a highlighter for a syntax, logging an error if its grammar could not be loaded.
+fn load_highlighter(syntax: Syntax) -> Option<Highlighter> {
+    match Highlighter::new(syntax) {
+        Ok(highlighter) => Some(highlighter),
+        Err(e) => {
+            error!("unable to load grammar for {:?}: {}", syntax, e);
+            None
         }
     }
 }
@@ -285,13 +1129,18 @@ mod tests {
     use euclid::rect;
     use indoc::indoc;
 
-    use crate::ui::{Bounds, Context, Drawable, Screen, Size};
+    use crate::syntax::Syntax;
+    use crate::ui::{Bounds, Context, Coordinates, Drawable, Screen, Size};
 
-    use super::{Buffer, Buffers, Cursor, Position, Span, Storage};
+    #[cfg(feature = "lang-python")]
+    use super::Symbol;
+    use super::{
+        visible_slice, Buffer, Buffers, Cursor, FileFormat, Offset, Position, Span, Storage,
+    };
 
     #[tokio::test]
     async fn buffers_open_existing_path() {
-        let buffers = Buffers::from_paths(vec![PathBuf::from("src/lib.rs")], Bounds::zero())
+        let buffers = Buffers::from_paths(vec![PathBuf::from("src/lib.rs")], Bounds::zero(), false)
             .await
             .unwrap();
 
@@ -301,9 +1150,13 @@ mod tests {
 
     #[tokio::test]
     async fn buffers_open_new_path() {
-        let buffers = Buffers::from_paths(vec![PathBuf::from("does_not_exist.rs")], Bounds::zero())
-            .await
-            .unwrap();
+        let buffers = Buffers::from_paths(
+            vec![PathBuf::from("does_not_exist.rs")],
+            Bounds::zero(),
+            false,
+        )
+        .await
+        .unwrap();
 
         let current = buffers.current();
 
@@ -312,6 +1165,237 @@ mod tests {
         assert_eq!(current.storage, Storage::new());
     }
 
+    #[tokio::test]
+    async fn open_and_write_latin1() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin1.txt");
+        std::fs::write(&path, b"caf\xe9\n").unwrap();
+
+        let mut buffer = Buffer::open(path.clone()).await.unwrap();
+        assert_eq!(buffer.storage.to_string(), "café\n");
+        assert_eq!(buffer.encoding(), encoding_rs::WINDOWS_1252);
+
+        buffer.insert_str("un ");
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"un caf\xe9\n");
+    }
+
+    #[tokio::test]
+    async fn preserve_missing_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("noeol.txt");
+        std::fs::write(&path, "a\nb").unwrap();
+
+        let mut buffer = Buffer::open(path.clone()).await.unwrap();
+        buffer.syntax = Some(Syntax::Rust);
+        assert_eq!(buffer.text(), "a\nb");
+        assert_eq!(buffer.to_text_document_item().unwrap().text, "a\nb");
+
+        buffer.insert('c');
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ca\nb");
+
+        std::fs::write(&path, "a\n").unwrap();
+        let buffer = Buffer::open(path).await.unwrap();
+        assert_eq!(buffer.text(), "a\n");
+    }
+
+    #[tokio::test]
+    async fn convert_line_endings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dos.txt");
+        std::fs::write(&path, "a\r\nb\r\n").unwrap();
+
+        let mut buffer = Buffer::open(path.clone()).await.unwrap();
+        assert_eq!(buffer.file_format(), FileFormat::Dos);
+        assert_eq!(buffer.text(), "a\nb\n");
+
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\r\nb\r\n");
+
+        buffer.set_file_format(FileFormat::Unix);
+        assert!(buffer.is_modified());
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\n");
+    }
+
+    #[tokio::test]
+    async fn preserve_bom() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bom.txt");
+        std::fs::write(&path, "\u{feff}a\n").unwrap();
+
+        let mut buffer = Buffer::open(path.clone()).await.unwrap();
+        assert!(buffer.has_bom());
+        assert_eq!(buffer.text(), "a\n");
+
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "\u{feff}a\n");
+
+        buffer.set_bom(false);
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn visible_slice_of_line() {
+        assert_eq!(visible_slice("hello", 1..3), (0, "el"));
+        assert_eq!(visible_slice("hello", 3..10), (0, "lo"));
+        assert_eq!(visible_slice("hello", 6..10), (0, ""));
+        assert_eq!(visible_slice("a台北b", 2..7), (1, "北b"));
+        assert_eq!(visible_slice("a台北b", 1..6), (0, "台北b"));
+        assert_eq!(visible_slice("a台北b", 1..4), (0, "台"));
+        assert_eq!(visible_slice("a台北b", 2..3), (0, ""));
+        assert_eq!(visible_slice("e\u{301}f", 1..2), (0, "f"));
+        assert_eq!(visible_slice("ae\u{301}f", 2..4), (0, "f"));
+        assert_eq!(visible_slice("ab台北", 3..6), (1, "北"));
+    }
+
+    #[test]
+    fn visible_slice_of_long_line() {
+        let line = format!("{}台b", "a".repeat(1_000_000));
+        assert_eq!(visible_slice(&line, 999_999..1_000_003), (0, "a台b"));
+        assert_eq!(visible_slice(&line, 1_000_001..1_000_010), (1, "b"));
+        assert_eq!(visible_slice(&line, 2_000_000..2_000_010), (0, ""));
+    }
+
+    #[tokio::test]
+    async fn buffers_push() {
+        let mut buffers = Buffers::from_paths(
+            vec![],
+            Bounds::new(Coordinates::zero(), Coordinates::new(4, 3)),
+            false,
+        )
+        .await
+        .unwrap();
+        buffers.current_mut().viewport = Some(rect(2, 2, 4, 3));
+
+        buffers.push(Buffer::from("scratch"));
+
+        assert_eq!(buffers.current().storage.to_string(), "scratch\n");
+        assert_eq!(buffers.current().viewport, Some(rect(0, 0, 4, 3)));
+        assert_eq!(buffers.buffers[0].viewport, None);
+    }
+
+    #[tokio::test]
+    async fn buffers_focus() {
+        let mut buffers = Buffers::from_paths(
+            vec![PathBuf::from("src/lib.rs")],
+            Bounds::new(Coordinates::zero(), Coordinates::new(4, 3)),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let lib = buffers.current().path.clone().unwrap();
+        let main = lib.with_file_name("main.rs");
+
+        let (_, opened) = buffers.focus(&main).await.unwrap();
+        assert!(opened);
+        assert_eq!(buffers.current().path(), Some(main.as_path()));
+        assert_eq!(buffers.current().viewport, Some(rect(0, 0, 4, 3)));
+        assert_eq!(buffers.buffers[0].viewport, None);
+
+        let (_, opened) = buffers.focus(&lib).await.unwrap();
+        assert!(!opened);
+        assert_eq!(buffers.current().path(), Some(lib.as_path()));
+        assert_eq!(buffers.buffers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn buffers_focus_scrolled() {
+        let mut buffers = Buffers::from_paths(
+            vec![],
+            Bounds::new(Coordinates::zero(), Coordinates::new(4, 3)),
+            false,
+        )
+        .await
+        .unwrap();
+        let text = (0..60).map(|i| format!("{}\n", i)).collect::<String>();
+        buffers.push(Buffer::from(text.as_str()));
+        buffers.current_mut().cursor = Cursor::at(0, 50);
+        buffers.current_mut().move_offset(Offset::zero());
+
+        let viewport = buffers.current().viewport;
+        let position = buffers.current().cursor_position();
+        assert_eq!(viewport.unwrap().min_y(), 48);
+
+        buffers.focus_next();
+        assert_eq!(buffers.current().viewport, Some(rect(0, 0, 4, 3)));
+        buffers.focus_previous();
+        assert_eq!(buffers.current().viewport, viewport);
+        assert_eq!(buffers.current().cursor_position(), position);
+
+        // The cursor is shown even if it moved while the buffer was hidden.
+        buffers.focus_next();
+        buffers.buffers[1].cursor = Cursor::at(0, 10);
+        buffers.focus_previous();
+        assert_eq!(buffers.current().viewport.unwrap().min_y(), 9);
+        assert_eq!(buffers.current().cursor_position(), Position::new(0, 1));
+
+        buffers.focus_next();
+        buffers.remove(0);
+        assert_eq!(buffers.current().viewport.unwrap().min_y(), 9);
+    }
+
+    #[tokio::test]
+    async fn buffers_remove() {
+        let mut buffers = Buffers::from_paths(
+            vec![],
+            Bounds::new(Coordinates::zero(), Coordinates::new(4, 3)),
+            false,
+        )
+        .await
+        .unwrap();
+        buffers.push(Buffer::from("a"));
+        buffers.push(Buffer::from("b"));
+        buffers.focus_index(1);
+
+        buffers.remove(0);
+        assert_eq!(buffers.current().text(), "a\n");
+
+        let removed = buffers.remove(0);
+        assert!(removed.viewport.is_none());
+        assert_eq!(buffers.current().text(), "b\n");
+        assert_eq!(buffers.current().viewport, Some(rect(0, 0, 4, 3)));
+
+        buffers.remove(0);
+        assert_eq!(buffers.buffers.len(), 1);
+        assert_eq!(buffers.current().text(), "\n");
+        assert_eq!(buffers.current().viewport, Some(rect(0, 0, 4, 3)));
+    }
+
+    #[tokio::test]
+    async fn buffers_get_or_open() {
+        let mut buffers =
+            Buffers::from_paths(vec![PathBuf::from("src/lib.rs")], Bounds::zero(), false)
+                .await
+                .unwrap();
+
+        let path = buffers.current().path.clone().unwrap();
+        let (_, opened) = buffers.get_or_open(&path).await.unwrap();
+        assert!(!opened);
+
+        let (buffer, opened) = buffers
+            .get_or_open(&path.with_file_name("main.rs"))
+            .await
+            .unwrap();
+        assert!(opened);
+        assert!(buffer.viewport.is_none());
+        assert_eq!(buffers.current().path(), Some(path.as_path()));
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn set_path_identifies_syntax() {
+        let mut buffer = Buffer::new();
+
+        buffer.set_path(PathBuf::from("/tmp/foo.rs"));
+
+        assert_eq!(buffer.syntax, Some(Syntax::Rust));
+        assert!(buffer.highlighter.is_some());
+    }
+
     #[test]
     fn draw_empty_buffer() {
         let mut buffer = Buffer::new();
@@ -418,4 +1502,196 @@ mod tests {
 
         assert_eq!(buffer.cursor_position(), Position::zero());
     }
+
+    #[test]
+    fn draw_wrapped_lines() {
+        let mut buffer = Buffer::from(indoc! {"
+            abcdefg
+            hi
+        "});
+        buffer.window.wrap = true;
+        buffer.window.number = true;
+        buffer.viewport = Some(rect(0, 0, 3, 4));
+        buffer.cursor = Cursor::at(7, 0);
+
+        let mut screen = Screen::new(Size::new(3, 4));
+        buffer.draw(&mut Context {
+            bounds: Bounds::from_size(screen.size),
+            screen: &mut screen,
+        });
+
+        let rows = (0..4)
+            .map(|y| (0..3).map(|x| screen[(y, x)].c.unwrap_or(' ')).collect())
+            .collect::<Vec<String>>();
+        assert_eq!(rows, vec!["abc", "def", "g  ", "hi "]);
+        assert_eq!(buffer.cursor_position(), Position::new(1, 2));
+
+        buffer.cursor = Cursor::at(6, 0);
+        assert_eq!(buffer.cursor_position(), Position::new(0, 2));
+    }
+
+    #[test]
+    fn identifier_at_cursor() {
+        let mut buffer = Buffer::from("let é_1 = foo::bar();\n");
+
+        buffer.cursor = Cursor::at(6, 0);
+        assert_eq!(buffer.identifier_at_cursor(), Some("é_1"));
+
+        buffer.cursor = Cursor::at(12, 0);
+        assert_eq!(buffer.identifier_at_cursor(), Some("foo"));
+        assert_eq!(buffer.identifier_before_cursor(), "f");
+
+        buffer.cursor = Cursor::at(17, 0);
+        assert_eq!(buffer.identifier_at_cursor(), Some("bar"));
+
+        buffer.cursor = Cursor::at(9, 0);
+        assert_eq!(buffer.identifier_at_cursor(), None);
+        assert_eq!(buffer.identifier_before_cursor(), "");
+    }
+
+    #[tokio::test]
+    async fn write_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.txt");
+
+        let mut buffer = Buffer::from("foo\nbar\n");
+        buffer.insert('x');
+        assert!(buffer.is_modified());
+
+        buffer.write_to(&path).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "xfoo\nbar\n");
+        assert!(!buffer.is_modified());
+    }
+
+    #[tokio::test]
+    async fn save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("saved.txt");
+
+        let mut buffer = Buffer::from("foo\n");
+        assert!(buffer.save().await.is_err());
+
+        buffer.set_path(path.clone());
+        buffer.insert('x');
+        buffer.save().await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "xfoo\n");
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn lsp_positions() {
+        let mut buffer = Buffer::from("aé\nb\n");
+        buffer.set_path(PathBuf::from("/tmp/foo.txt"));
+        buffer.set_cursor_past_end(true);
+        buffer.jump_to(Position::new(3, 0));
+
+        let position = buffer.to_text_document_position().unwrap().position;
+        assert_eq!(position, lsp_types::Position::new(0, 2));
+
+        assert_eq!(
+            buffer.position_from_lsp(lsp_types::Position::new(0, 2)),
+            Position::new(3, 0)
+        );
+        assert_eq!(
+            buffer.position_from_lsp(lsp_types::Position::new(1, 5)),
+            Position::new(1, 1)
+        );
+    }
+
+    #[test]
+    fn set_bounds() {
+        let text = (0..100).map(|i| format!("{}\n", i)).collect::<String>();
+        let mut buffer = Buffer::from(text.as_str());
+        buffer.viewport = Some(Span::from_size(Size::new(80, 20).cast().cast_unit()));
+        buffer.jump_to(Position::new(0, 90));
+
+        let mut buffers = Buffers {
+            buffers: vec![buffer],
+            current: 0,
+        };
+
+        // Shrinking the viewport keeps the cursor visible.
+        buffers.set_bounds(Bounds::from_size(Size::new(80, 5)));
+        let viewport = buffers.current().viewport.unwrap();
+        assert!(viewport.min_y() <= 90 && 90 < viewport.max_y());
+
+        // Growing it doesn't show lines past the end.
+        buffers.set_bounds(Bounds::from_size(Size::new(80, 40)));
+        let buffer = buffers.current();
+        let viewport = buffer.viewport.unwrap();
+        assert_eq!(viewport.max_y(), buffer.storage.lines());
+        assert_eq!(viewport.height(), 40);
+    }
+
+    #[test]
+    fn focus_next_and_previous() {
+        let mut buffers = Buffers {
+            buffers: vec![Buffer::from("a"), Buffer::from("b"), Buffer::from("c")],
+            current: 0,
+        };
+
+        buffers.focus_previous();
+        assert_eq!(buffers.current().text(), "c\n");
+        buffers.focus_next();
+        assert_eq!(buffers.current().text(), "a\n");
+        buffers.focus_next();
+        assert_eq!(buffers.current().text(), "b\n");
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn breadcrumbs() {
+        let mut buffer = Buffer::from(indoc! {"
+            mod outer {
+                impl Foo {
+                    fn bar() {
+                        let x = 1;
+                    }
+                }
+            }
+        "});
+        buffer.set_syntax(Some(Syntax::Rust));
+
+        assert_eq!(buffer.breadcrumbs(), vec![String::from("outer")]);
+
+        buffer.cursor = Cursor::at(12, 3);
+        assert_eq!(buffer.breadcrumbs(), vec!["outer", "Foo", "bar"]);
+
+        buffer.cursor = Cursor::at(0, 7);
+        assert!(buffer.breadcrumbs().is_empty());
+    }
+
+    #[cfg(feature = "lang-python")]
+    #[test]
+    fn symbols() {
+        let mut buffer = Buffer::from(indoc! {"
+            class Foo:
+                @property
+                def bar(self):
+                    pass
+
+            def baz():
+                pass
+        "});
+        assert!(buffer.symbols().is_empty());
+
+        buffer.set_syntax(Some(Syntax::Python));
+
+        let symbol = |name: &str, x, y, depth| Symbol {
+            name: String::from(name),
+            position: Position::new(x, y),
+            depth,
+        };
+
+        assert_eq!(
+            buffer.symbols(),
+            vec![
+                symbol("Foo", 0, 0, 0),
+                symbol("bar", 4, 1, 1),
+                symbol("baz", 0, 5, 0),
+            ]
+        );
+    }
 }
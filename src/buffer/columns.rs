@@ -0,0 +1,70 @@
+//! Converting between byte offsets in a line and the screen columns that they're drawn in.
+//!
+//! The cursor is stored as a byte offset into its line, so that edits can use it directly. The
+//! viewport, and the column that the cursor remembers while moving up and down, are measured in
+//! screen columns instead, since full-width characters take two columns and combining characters
+//! take none.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::Buffer;
+
+/// Returns the screen column that the text at a byte offset of a line starts in.
+///
+/// Offsets past the end of the line are treated as the end of the line.
+pub fn column_of(line: &str, x: usize) -> usize {
+    let mut x = x.min(line.len());
+    while !line.is_char_boundary(x) {
+        x -= 1;
+    }
+
+    line[..x].width()
+}
+
+/// Returns the byte offset of the grapheme cluster drawn in a screen column of a line, or the end
+/// of the line if the line is narrower.
+pub fn byte_of_column(line: &str, column: usize) -> usize {
+    let mut start = 0;
+    for (index, grapheme) in line.grapheme_indices(true) {
+        start += grapheme.width();
+        if column < start {
+            return index;
+        }
+    }
+
+    line.len()
+}
+
+impl Buffer {
+    /// Returns the screen column of the cursor within its line.
+    pub fn cursor_column(&self) -> usize {
+        column_of(self.storage.line(self.cursor.y()), self.cursor.x())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{byte_of_column, column_of};
+
+    #[test]
+    fn columns() {
+        let line = "a台北e\u{301}b";
+
+        assert_eq!(column_of(line, 0), 0);
+        assert_eq!(column_of(line, 1), 1);
+        assert_eq!(column_of(line, 4), 3);
+        assert_eq!(column_of(line, 7), 5);
+        assert_eq!(column_of(line, 8), 6);
+        assert_eq!(column_of(line, 10), 6);
+        assert_eq!(column_of(line, 100), 7);
+
+        assert_eq!(byte_of_column(line, 0), 0);
+        assert_eq!(byte_of_column(line, 1), 1);
+        assert_eq!(byte_of_column(line, 2), 1);
+        assert_eq!(byte_of_column(line, 4), 4);
+        assert_eq!(byte_of_column(line, 5), 7);
+        assert_eq!(byte_of_column(line, 6), 10);
+        assert_eq!(byte_of_column(line, 7), 11);
+    }
+}
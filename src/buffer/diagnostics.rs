@@ -0,0 +1,278 @@
+//! Diagnostics reported about the contents of a buffer, such as errors and warnings from linters.
+
+use std::collections::BTreeMap;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::columns::column_of;
+use super::{Buffer, Position};
+use crate::theme;
+use crate::ui::{Bounds, Context, Coordinates, Style};
+
+/// Marks lines with diagnostics, drawn after the end of the line.
+///
+/// Diagnostics with an empty range, or at the end of a line, may not cover any visible text, so the
+/// sign ensures that they can be noticed.
+const SIGN: &str = "●";
+
+/// The diagnostics of a buffer, grouped by the tool that reported them.
+///
+/// Each tool replaces its own diagnostics wholesale, so results from a linter don't clobber those
+/// from a language server.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    sources: BTreeMap<String, Vec<Diagnostic>>,
+}
+
+impl Diagnostics {
+    fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.sources.values().flatten()
+    }
+}
+
+/// Returns the style for a diagnostic in the active color scheme.
+fn style(diagnostic: &Diagnostic) -> Option<Style> {
+    // Diagnostics without a severity are treated as errors.
+    let name = match diagnostic.severity {
+        Some(DiagnosticSeverity::Error) | None => "diagnostic.error",
+        Some(DiagnosticSeverity::Warning) => "diagnostic.warning",
+        Some(DiagnosticSeverity::Information) => "diagnostic.information",
+        Some(DiagnosticSeverity::Hint) => "diagnostic.hint",
+    };
+
+    theme::current().style(name)
+}
+
+/// Returns the severity of a diagnostic for sorting, where lower values are more severe.
+fn severity_rank(diagnostic: &Diagnostic) -> DiagnosticSeverity {
+    diagnostic.severity.unwrap_or(DiagnosticSeverity::Error)
+}
+
+impl Buffer {
+    /// Replaces the diagnostics reported by a source, such as a linter.
+    pub fn set_diagnostics(&mut self, source: &str, diagnostics: Vec<Diagnostic>) {
+        if diagnostics.is_empty() {
+            self.diagnostics.sources.remove(source);
+        } else {
+            self.diagnostics
+                .sources
+                .insert(source.to_owned(), diagnostics);
+        }
+    }
+
+    /// Returns the diagnostics of the buffer from all sources.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    /// Returns the most severe diagnostic that starts on the cursor's line.
+    pub fn diagnostic_at_cursor(&self) -> Option<&Diagnostic> {
+        let y = self.cursor.y();
+
+        self.diagnostics()
+            .filter(|diagnostic| diagnostic.range.start.line as usize == y)
+            .min_by_key(|diagnostic| (severity_rank(diagnostic), diagnostic.range.start.character))
+    }
+
+    /// Styles the text covered by diagnostics within the viewport, and draws a sign after each
+    /// line that a diagnostic starts on.
+    ///
+    /// Diagnostics with an empty range are drawn over the character they start at.
+    pub(super) fn draw_diagnostics(&self, ctx: &mut Context<'_>) {
+        for diagnostic in self.diagnostics() {
+            let style = match style(diagnostic) {
+                Some(style) => style,
+                None => continue,
+            };
+
+            let start = &diagnostic.range.start;
+            let end = &diagnostic.range.end;
+
+            self.style_range(
+                ctx,
+                Position::new(start.character as usize, start.line as usize)
+                    ..Position::new(end.character as usize, end.line as usize),
+                style,
+            );
+        }
+
+        self.draw_signs(ctx);
+    }
+
+    /// Draws a sign one cell past the end of each line in the viewport that a diagnostic starts
+    /// on, in the style of its most severe diagnostic.
+    fn draw_signs(&self, ctx: &mut Context<'_>) {
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => return,
+        };
+
+        let mut signs = BTreeMap::new();
+        for diagnostic in self.diagnostics() {
+            let row = diagnostic.range.start.line as usize;
+            if row < viewport.min_y() || row >= viewport.max_y() || row >= self.storage.lines() {
+                continue;
+            }
+
+            let sign = signs.entry(row).or_insert(diagnostic);
+            if severity_rank(diagnostic) < severity_rank(sign) {
+                *sign = diagnostic;
+            }
+        }
+
+        let display_lines = self.display_lines();
+        for (row, diagnostic) in signs {
+            let style = match style(diagnostic) {
+                Some(style) => style,
+                None => continue,
+            };
+
+            let line = self.storage.line(row);
+            let position = match display_lines.position(row, column_of(line, line.len()) + 1) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            let x = ctx.bounds.min.x + position.x as u16;
+            let y = ctx.bounds.min.y + position.y as u16;
+            let position = Coordinates::new(x, y);
+            let bounds =
+                Bounds::new(position, Coordinates::new(x + 1, y + 1)).intersection(&ctx.bounds);
+            if bounds.is_empty() {
+                continue;
+            }
+
+            ctx.screen.write(position, SIGN);
+            ctx.screen.apply_style(
+                bounds,
+                Style {
+                    underline: false,
+                    ..style
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::rect;
+    use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+    use crate::buffer::{Buffer, Cursor};
+    use crate::ui::{Bounds, Context, Drawable, Screen, Size};
+
+    fn diagnostic(
+        line: u64,
+        start: u64,
+        end: u64,
+        severity: Option<DiagnosticSeverity>,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            severity,
+            message: String::from(message),
+            ..Diagnostic::default()
+        }
+    }
+
+    #[test]
+    fn diagnostics_by_source() {
+        let mut buffer = Buffer::from("a\nb\n");
+
+        buffer.set_diagnostics("lint", vec![diagnostic(0, 0, 1, None, "first")]);
+        buffer.set_diagnostics("server", vec![diagnostic(1, 0, 1, None, "second")]);
+        assert_eq!(buffer.diagnostics().count(), 2);
+
+        buffer.set_diagnostics("lint", vec![]);
+        assert_eq!(
+            buffer
+                .diagnostics()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["second"]
+        );
+    }
+
+    #[test]
+    fn diagnostic_at_cursor() {
+        let mut buffer = Buffer::from("let x = y;\nfoo();\n");
+        buffer.set_diagnostics(
+            "lint",
+            vec![
+                diagnostic(0, 4, 5, Some(DiagnosticSeverity::Warning), "unused"),
+                diagnostic(0, 8, 9, Some(DiagnosticSeverity::Error), "undefined"),
+            ],
+        );
+
+        assert_eq!(
+            buffer.diagnostic_at_cursor().map(|d| d.message.as_str()),
+            Some("undefined")
+        );
+
+        buffer.cursor = Cursor::at(0, 1);
+        assert!(buffer.diagnostic_at_cursor().is_none());
+    }
+
+    #[test]
+    fn draw_diagnostics() {
+        let mut buffer = Buffer::from("let x = y;\n\n");
+        buffer.viewport = Some(rect(0, 0, 10, 2));
+        buffer.set_diagnostics(
+            "lint",
+            vec![
+                diagnostic(0, 8, 9, Some(DiagnosticSeverity::Error), "undefined"),
+                diagnostic(1, 0, 0, Some(DiagnosticSeverity::Warning), "empty line"),
+            ],
+        );
+
+        let mut screen = Screen::new(Size::new(10, 2));
+        buffer.draw(&mut Context {
+            bounds: Bounds::from_size(screen.size),
+            screen: &mut screen,
+        });
+
+        let underlined = |row| {
+            (0..10)
+                .filter(|&col| screen[(row, col)].underline)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(underlined(0), vec![8]);
+        assert_eq!(underlined(1), vec![0]);
+    }
+
+    #[test]
+    fn draw_signs() {
+        let mut buffer = Buffer::from("let x = y;\nfoo\n\nbar\n");
+        buffer.viewport = Some(rect(0, 0, 12, 4));
+        buffer.set_diagnostics(
+            "lint",
+            vec![
+                diagnostic(0, 10, 10, Some(DiagnosticSeverity::Warning), "end of line"),
+                diagnostic(1, 0, 3, Some(DiagnosticSeverity::Hint), "hint"),
+                diagnostic(1, 0, 0, Some(DiagnosticSeverity::Error), "error"),
+                diagnostic(2, 0, 0, Some(DiagnosticSeverity::Information), "empty line"),
+            ],
+        );
+
+        let mut screen = Screen::new(Size::new(12, 4));
+        buffer.draw(&mut Context {
+            bounds: Bounds::from_size(screen.size),
+            screen: &mut screen,
+        });
+
+        let signs = |row| {
+            (0..12)
+                .filter(|&col| screen[(row, col)].c == Some('●'))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(signs(0), vec![11]);
+        assert_eq!(signs(1), vec![4]);
+        assert_eq!(signs(2), vec![1]);
+        assert!(signs(3).is_empty());
+
+        assert!(screen[(1, 4)].color.is_some());
+        assert!(!screen[(1, 4)].underline);
+    }
+}
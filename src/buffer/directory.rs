@@ -0,0 +1,146 @@
+//! Buffers that list the entries of a directory.
+//!
+//! Like netrw, opening a directory shows one entry per line. The first line is `../`, for the
+//! parent directory, followed by directories with a trailing slash and then files. Pressing Enter
+//! on an entry opens it.
+
+use std::path::{Path, PathBuf};
+
+use encoding_rs::UTF_8;
+use tokio::fs;
+use tokio::io;
+
+use crate::options::{self, WindowOptions};
+
+use super::{Buffer, Cursor, Diagnostics, FileFormat, Position, TabStops};
+
+/// The line that refers to the parent directory.
+const PARENT: &str = "../";
+
+impl Buffer {
+    /// Opens a buffer listing the entries of a directory. The path must be absolute.
+    pub(super) async fn open_directory(path: PathBuf) -> io::Result<Self> {
+        // Remove `.` components, so that paths of entries compare equal to the paths of buffers.
+        let path = path.components().collect::<PathBuf>();
+
+        let lines = read_entries(&path).await?;
+
+        Ok(Buffer {
+            cursor: Cursor::default(),
+            cursor_past_end: true,
+            selection_anchor: None,
+            tab_stops: TabStops::default(),
+            storage: lines.into(),
+            version: 0,
+            saved_version: 0,
+            path: Some(path),
+            directory: true,
+            help: None,
+            terminal: None,
+            log: None,
+            local_dir: None,
+            encoding: UTF_8,
+            bom: false,
+            file_format: FileFormat::Unix,
+            eol: true,
+            binary: None,
+            large: false,
+            loader: None,
+            transaction: None,
+            syntax: None,
+            highlighter: None,
+            viewport: None,
+            hidden_origin: Position::zero(),
+            window: WindowOptions::default(),
+            options: options::buffer_defaults(None),
+            indent: None,
+            diagnostics: Diagnostics::default(),
+        })
+    }
+
+    /// Returns whether the buffer lists the entries of a directory.
+    pub fn is_directory(&self) -> bool {
+        self.directory
+    }
+
+    /// Returns the path of the entry on the cursor's line, if the buffer lists a directory.
+    pub fn entry_at_cursor(&self) -> Option<PathBuf> {
+        if !self.directory {
+            return None;
+        }
+
+        let directory = self.path.as_deref()?;
+
+        match self.storage.iter_lines().nth(self.cursor.y())? {
+            "" => None,
+            PARENT => directory.parent().map(Path::to_owned),
+            name => Some(directory.join(name.trim_end_matches('/'))),
+        }
+    }
+}
+
+/// Returns the lines listing a directory.
+async fn read_entries(path: &Path) -> io::Result<Vec<String>> {
+    let mut directories = vec![];
+    let mut files = vec![];
+
+    let mut entries = fs::read_dir(path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        // Follow symlinks, so that links to directories are listed as directories.
+        match fs::metadata(entry.path()).await {
+            Ok(metadata) if metadata.is_dir() => directories.push(format!("{}/", name)),
+            _ => files.push(name),
+        }
+    }
+
+    directories.sort();
+    files.sort();
+
+    let mut lines = vec![];
+    if path.parent().is_some() {
+        lines.push(String::from(PARENT));
+    }
+    lines.extend(directories);
+    lines.extend(files);
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::buffer::Buffer;
+
+    #[tokio::test]
+    async fn open_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+
+        let mut buffer = Buffer::open(dir.path().join(".")).await.unwrap();
+        assert!(buffer.is_directory());
+        assert_eq!(buffer.path(), Some(dir.path()));
+        assert_eq!(buffer.text(), "../\nsrc/\nCargo.toml\nREADME.md\n");
+
+        assert_eq!(buffer.entry_at_cursor().as_deref(), dir.path().parent());
+        buffer.move_down();
+        assert_eq!(buffer.entry_at_cursor(), Some(dir.path().join("src")));
+        buffer.move_down();
+        assert_eq!(
+            buffer.entry_at_cursor(),
+            Some(dir.path().join("Cargo.toml"))
+        );
+
+        let root = Buffer::open(Path::new("/").to_owned()).await.unwrap();
+        assert!(!root.text().starts_with("../"));
+
+        let file = Buffer::open(dir.path().join("README.md")).await.unwrap();
+        assert!(!file.is_directory());
+        assert_eq!(file.entry_at_cursor(), None);
+    }
+}
@@ -1,13 +1,17 @@
 //! Functions and structures for applying edits to a buffer.
 
+use std::cmp;
 use std::convert::TryFrom;
 use std::ops::Range;
 
-use lsp_types::TextDocumentContentChangeEvent;
+use lsp_types::{TextDocumentContentChangeEvent, TextEdit};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::buffer::units::{ByteIndex, CharPosition};
 
-use super::Buffer;
+use super::motion::Motion;
+use super::search::find_matches;
+use super::{Buffer, Position};
 
 /// An edit that can be applied to a buffer.
 #[derive(Debug)]
@@ -15,6 +19,9 @@ pub struct Edit {
     pub range: Range<ByteIndex>,
     pub character_range: Range<CharPosition>,
     pub new_text: String,
+
+    /// The text that was replaced.
+    pub old_text: String,
 }
 
 impl Edit {
@@ -47,23 +54,28 @@ impl Edit {
 impl Buffer {
     /// Returns the byte index of the current cursor position.
     fn byte_at_cursor(&self) -> ByteIndex {
-        let mut byte = 0;
-
-        for line in self.storage.iter_lines().take(self.cursor.y()) {
-            byte += line.len() + 1;
-        }
-
-        byte += self.cursor.x();
+        self.byte_at(Position::new(self.cursor.x(), self.cursor.y()))
+    }
 
-        ByteIndex::new(byte)
+    /// Returns the byte index of a position.
+    fn byte_at(&self, pos: Position) -> ByteIndex {
+        self.storage.line_to_byte(pos.y) + ByteIndex::new(pos.x)
     }
 
     /// Inserts a character at the current cursor position.
     ///
     /// Returns an `Edit` representing the change.
     pub fn insert(&mut self, c: char) -> Edit {
+        self.insert_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Inserts a string at the current cursor position, leaving the cursor after it.
+    ///
+    /// The string is inserted as a single edit, no matter how many characters or lines it
+    /// contains. Returns an `Edit` representing the change.
+    pub fn insert_str(&mut self, text: &str) -> Edit {
         let byte = self.byte_at_cursor();
-        let edit = self.edit(byte..byte, c.to_string());
+        let edit = self.edit(byte..byte, text.to_owned());
 
         let pos = self.storage.position_of_byte(edit.new_end());
         self.cursor.set_x(pos.x);
@@ -72,7 +84,42 @@ impl Buffer {
         edit
     }
 
+    /// Inserts a tab at the cursor, or spaces up to the next tab stop if `expandtab` is set.
+    pub fn insert_tab(&mut self) -> Edit {
+        if !self.options.expandtab {
+            return self.insert('\t');
+        }
+
+        let tabstop = cmp::max(self.options.tabstop, 1);
+        let spaces = tabstop - self.cursor.x() % tabstop;
+        self.insert_str(&" ".repeat(spaces))
+    }
+
+    /// Replaces the `len` bytes before the cursor on its line with text, leaving the cursor after
+    /// the text.
+    pub fn replace_before_cursor(&mut self, len: usize, text: &str) -> Edit {
+        let end = self.byte_at_cursor();
+        let start = end - ByteIndex::new(cmp::min(len, self.cursor.x()));
+        let edit = self.edit(start..end, text.to_owned());
+
+        let pos = self.storage.position_of_byte(edit.new_end());
+        self.cursor.set_x(pos.x);
+        self.cursor.set_y(pos.y);
+
+        edit
+    }
+
+    /// Deletes the placeholder of a snippet that the cursor was moved to, if nothing has been
+    /// edited since, so that the text typed next replaces it.
+    pub fn delete_placeholder(&mut self) -> Option<Edit> {
+        let range = self.selected_placeholder()?;
+        Some(self.edit(range, String::new()))
+    }
+
     /// Delete the character immediately preceding the cursor.
+    ///
+    /// Characters are grapheme clusters, so a combining character is deleted together with the
+    /// character that it modifies. At the beginning of a line, the preceding newline is deleted.
     pub fn delete(&mut self) -> Option<Edit> {
         let end = self.byte_at_cursor();
 
@@ -80,8 +127,13 @@ impl Buffer {
             return None;
         }
 
-        // FIXME: Naively assumes ASCII
-        let start = end - ByteIndex::new(1);
+        let line = self.storage.line(self.cursor.y());
+        let len = line[..self.cursor.x()]
+            .graphemes(true)
+            .next_back()
+            .map_or(1, str::len);
+
+        let start = end - ByteIndex::new(len);
         let edit = self.edit(start..end, String::new());
 
         let pos = self.storage.position_of_byte(start);
@@ -91,10 +143,418 @@ impl Buffer {
         Some(edit)
     }
 
+    /// Deletes the word before the cursor, along with any whitespace between it and the cursor.
+    ///
+    /// Words are runs of alphanumeric characters and underscores, or runs of other non-blank
+    /// characters. At the beginning of a line, the preceding newline is deleted.
+    pub fn delete_word_backward(&mut self) -> Option<Edit> {
+        if self.cursor.x() == 0 {
+            return self.delete();
+        }
+
+        let class = |c: char| {
+            if c.is_whitespace() {
+                0
+            } else if c.is_alphanumeric() || c == '_' {
+                1
+            } else {
+                2
+            }
+        };
+
+        let line = self.storage.line(self.cursor.y());
+        let before = line[..self.cursor.x()].trim_end();
+        let start = match before.chars().next_back() {
+            Some(last) => before
+                .char_indices()
+                .rev()
+                .take_while(|&(_, c)| class(c) == class(last))
+                .last()
+                .map_or(before.len(), |(index, _)| index),
+            None => 0,
+        };
+
+        Some(self.replace_before_cursor(self.cursor.x() - start, ""))
+    }
+
+    /// Deletes the text between the start of the line and the cursor. At the beginning of a line,
+    /// the preceding newline is deleted.
+    pub fn delete_to_line_start(&mut self) -> Option<Edit> {
+        if self.cursor.x() == 0 {
+            return self.delete();
+        }
+
+        Some(self.replace_before_cursor(self.cursor.x(), ""))
+    }
+
+    /// Delete the character under the cursor, leaving the cursor in place.
+    ///
+    /// Like `delete`, characters are grapheme clusters. Returns `None` if the cursor is at the end
+    /// of the line.
+    pub fn delete_forward(&mut self) -> Option<Edit> {
+        let line = self.storage.line(self.cursor.y());
+        let len = line[self.cursor.x()..].graphemes(true).next()?.len();
+
+        let start = self.byte_at_cursor();
+        Some(self.edit(start..start + ByteIndex::new(len), String::new()))
+    }
+
+    /// Deletes the text between the cursor and the target of a motion, exclusive of the target.
+    ///
+    /// Returns `None` if the motion has no target.
+    pub fn delete_motion(&mut self, motion: Motion) -> Option<Edit> {
+        let range = self.motion_range(motion)?;
+        let edit = self.edit(range.clone(), String::new());
+
+        let pos = self.storage.position_of_byte(range.start);
+        self.cursor.set_x(pos.x);
+        self.cursor.set_y(pos.y);
+
+        Some(edit)
+    }
+
+    /// Returns the text between the cursor and the target of a motion, exclusive of the target,
+    /// and the range that it covers.
+    ///
+    /// Returns `None` if the motion has no target.
+    pub fn yank_motion(&self, motion: Motion) -> Option<(String, Range<Position>)> {
+        let range = self.motion_range(motion)?;
+        let text = self.storage.text_in(range.start.0..range.end.0);
+
+        let start = self.storage.position_of_byte(range.start);
+        let end = self.storage.position_of_byte(range.end);
+        Some((
+            text,
+            Position::new(start.x, start.y)..Position::new(end.x, end.y),
+        ))
+    }
+
+    /// Deletes the selected text and deselects it, leaving the cursor at the start of the
+    /// selection.
+    ///
+    /// Returns `None` if nothing is selected.
+    pub fn delete_selection(&mut self) -> Option<Edit> {
+        let range = self.selection_bytes()?;
+        let start = self.selection()?.start;
+        self.clear_selection();
+
+        let edit = self.edit(range, String::new());
+        self.cursor.set_x(start.x);
+        self.cursor.set_y(start.y);
+
+        Some(edit)
+    }
+
+    /// Returns the selected text and the range that it covers.
+    ///
+    /// Returns `None` if nothing is selected.
+    pub fn yank_selection(&self) -> Option<(String, Range<Position>)> {
+        let range = self.selection_bytes()?;
+        Some((
+            self.storage.text_in(range.start.0..range.end.0),
+            self.selection()?,
+        ))
+    }
+
+    /// Returns the text of the cursor's line, including its newline, and the range that it covers.
+    pub fn yank_line(&self) -> (String, Range<Position>) {
+        let y = self.cursor.y();
+        let line = self.storage.line(y);
+        (
+            format!("{}\n", line),
+            Position::new(0, y)..Position::new(line.len(), y),
+        )
+    }
+
+    /// Inserts text before the cursor, or above the cursor's line if the text is made of whole
+    /// lines. The cursor is left at the start of the pasted text.
+    pub fn paste(&mut self, text: &str, linewise: bool) -> Edit {
+        let pos = if linewise {
+            Position::new(0, self.cursor.y())
+        } else {
+            Position::new(self.cursor.x(), self.cursor.y())
+        };
+
+        let byte = self.byte_at(pos);
+        let edit = self.edit(byte..byte, text.to_owned());
+
+        self.cursor.set_x(pos.x);
+        self.cursor.set_y(pos.y);
+
+        edit
+    }
+
+    /// Inserts text after the character under the cursor, or below the cursor's line if the text
+    /// is made of whole lines. The cursor is left at the start of the pasted text.
+    pub fn paste_after(&mut self, text: &str, linewise: bool) -> Edit {
+        let y = self.cursor.y();
+        let line = self.storage.line(y);
+
+        let (pos, edit) = if linewise && y + 1 < self.storage.lines() {
+            let byte = self.storage.line_to_byte(y + 1);
+            (
+                Position::new(0, y + 1),
+                self.edit(byte..byte, text.to_owned()),
+            )
+        } else if linewise {
+            // There's no line after the last line to paste before, so the lines are pasted at the
+            // end of the last line, with the newline moved to their start.
+            let byte = self.byte_at(Position::new(line.len(), y));
+            let text = format!("\n{}", text.strip_suffix('\n').unwrap_or(text));
+            (Position::new(0, y + 1), self.edit(byte..byte, text))
+        } else {
+            let x = self.cursor.x();
+            let x = x + line[x..].graphemes(true).next().map_or(0, str::len);
+            let byte = self.byte_at(Position::new(x, y));
+            (Position::new(x, y), self.edit(byte..byte, text.to_owned()))
+        };
+
+        self.cursor.set_x(pos.x);
+        self.cursor.set_y(pos.y);
+
+        edit
+    }
+
+    /// Deletes the cursor's line, leaving the cursor at the start of the line that takes its place.
+    ///
+    /// Returns the text of the line, including its newline, and the edit.
+    pub fn delete_line(&mut self) -> (String, Edit) {
+        let y = self.cursor.y();
+        let start = self.storage.line_to_byte(y);
+        let len = ByteIndex::new(self.storage.line(y).len());
+        let text = format!("{}\n", self.storage.line(y));
+
+        let range = if y + 1 < self.storage.lines() {
+            start..self.storage.line_to_byte(y + 1)
+        } else if y > 0 {
+            // The last line has no newline after it to delete, so the one before it is deleted.
+            start - ByteIndex::new(1)..start + len
+        } else {
+            start..start + len
+        };
+        let edit = self.edit(range, String::new());
+
+        self.cursor.set_y(cmp::min(y, self.storage.lines() - 1));
+        self.cursor.set_x(0);
+
+        (text, edit)
+    }
+
+    /// Returns the byte range between the cursor and the target of a motion, exclusive of the
+    /// target.
+    fn motion_range(&self, motion: Motion) -> Option<Range<ByteIndex>> {
+        let target = self.byte_at(self.motion_target(motion)?);
+        let cursor = self.byte_at_cursor();
+
+        Some(if target < cursor {
+            target..cursor
+        } else {
+            cursor..target
+        })
+    }
+
+    /// Applies a set of LSP text edits to the buffer.
+    ///
+    /// As in a `WorkspaceEdit`, the ranges of all edits refer to the document before any of them
+    /// are applied. The edits are applied from the end of the document backwards so that each
+    /// range remains valid. The returned `Edit`s are in the order that they were applied.
+    pub fn apply_text_edits(&mut self, mut text_edits: Vec<TextEdit>) -> Vec<Edit> {
+        text_edits
+            .sort_by_key(|text_edit| (text_edit.range.start.line, text_edit.range.start.character));
+
+        let edits = self.transaction(|buffer| {
+            text_edits
+                .into_iter()
+                .rev()
+                .map(|text_edit| {
+                    let to_byte = |position: lsp_types::Position| {
+                        buffer.storage.char_to_byte(CharPosition::new(
+                            usize::try_from(position.character).unwrap_or(usize::MAX),
+                            usize::try_from(position.line).unwrap_or(usize::MAX),
+                        ))
+                    };
+
+                    let range = to_byte(text_edit.range.start)..to_byte(text_edit.range.end);
+                    buffer.edit(range, text_edit.new_text)
+                })
+                .collect()
+        });
+
+        self.clamp_cursor();
+
+        edits
+    }
+
+    /// Removes whitespace from the end of every line, returning the edits.
+    pub fn trim_trailing_whitespace(&mut self) -> Vec<Edit> {
+        let edits = self.transaction(|buffer| {
+            (0..buffer.storage.lines())
+                .filter_map(|line| {
+                    let text = buffer.storage.line(line);
+                    let trimmed_len = text.trim_end().len();
+                    if trimmed_len == text.len() {
+                        return None;
+                    }
+
+                    let start = buffer.storage.line_to_byte(line);
+                    let range =
+                        start + ByteIndex::new(trimmed_len)..start + ByteIndex::new(text.len());
+                    Some(buffer.edit(range, String::new()))
+                })
+                .collect()
+        });
+
+        self.clamp_cursor();
+
+        edits
+    }
+
+    /// Replaces each match of a pattern on the given lines, returning the edits. Patterns are
+    /// matched literally, and matches don't overlap.
+    ///
+    /// The replacements are made in a single transaction, so that they're undone at once.
+    pub fn replace_in_lines(
+        &mut self,
+        lines: &[usize],
+        pattern: &str,
+        replacement: &str,
+    ) -> Vec<Edit> {
+        if pattern.is_empty() {
+            return vec![];
+        }
+
+        let edits = self.transaction(|buffer| {
+            let mut edits = vec![];
+            let line_count = buffer.storage.lines();
+
+            // Each line is replaced from its end, so that the earlier matches don't move.
+            for &line in lines.iter().filter(|&&line| line < line_count) {
+                let mut columns = vec![];
+                for x in find_matches(buffer.storage.line(line), pattern, false) {
+                    if columns
+                        .last()
+                        .map_or(true, |&last| x >= last + pattern.len())
+                    {
+                        columns.push(x);
+                    }
+                }
+
+                let start = buffer.storage.line_to_byte(line);
+                for x in columns.into_iter().rev() {
+                    let range =
+                        start + ByteIndex::new(x)..start + ByteIndex::new(x + pattern.len());
+                    edits.push(buffer.edit(range, replacement.to_owned()));
+                }
+            }
+
+            edits
+        });
+
+        self.clamp_cursor();
+
+        edits
+    }
+
+    /// Makes the buffer's file end with a newline when it is written, if it didn't already.
+    ///
+    /// The storage always ends with a newline, so only language servers need to be told about the
+    /// change. Returns the edit that they should be sent, if any.
+    pub fn ensure_final_newline(&mut self) -> Option<Edit> {
+        if self.eol {
+            return None;
+        }
+
+        self.eol = true;
+
+        let end = ByteIndex::new(self.storage.len() - 1);
+        let position = self.storage.byte_to_char_position(end);
+
+        match &mut self.transaction {
+            Some(edited) => *edited = true,
+            None => self.version += 1,
+        }
+
+        Some(Edit {
+            range: end..end,
+            character_range: position..position,
+            new_text: String::from("\n"),
+            old_text: String::new(),
+        })
+    }
+
+    /// Replaces the contents of the buffer with new text, such as the output of a formatter,
+    /// returning the edit. Only the part between the common prefix and suffix of the old and new
+    /// text is replaced.
+    ///
+    /// Whether the file ends with a newline is left alone, since `ensure_final_newline` decides
+    /// that.
+    pub fn replace_text(&mut self, text: &str) -> Option<Edit> {
+        let old = self.storage.to_string();
+        let mut new = text.to_owned();
+        if !new.ends_with('\n') {
+            new.push('\n');
+        }
+
+        let mut prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+
+        if prefix == old.len() && prefix == new.len() {
+            return None;
+        }
+
+        let mut suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(cmp::min(old.len(), new.len()) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(old.len() - suffix) {
+            suffix -= 1;
+        }
+
+        let range = ByteIndex::new(prefix)..ByteIndex::new(old.len() - suffix);
+        let edit = self.edit(range, new[prefix..new.len() - suffix].to_owned());
+
+        self.clamp_cursor();
+
+        Some(edit)
+    }
+
+    /// Makes several edits as a single change, returning the edits made by `f`.
+    ///
+    /// The buffer's version is incremented once for the whole transaction rather than for each
+    /// edit, so the returned edits should be sent to language servers in a single `didChange`
+    /// notification, in order. Transactions may be nested, in which case the outermost
+    /// transaction is the change.
+    pub fn transaction<F>(&mut self, f: F) -> Vec<Edit>
+    where
+        F: FnOnce(&mut Buffer) -> Vec<Edit>,
+    {
+        if self.transaction.is_some() {
+            return f(self);
+        }
+
+        self.transaction = Some(false);
+        let edits = f(self);
+
+        if let Some(true) = self.transaction.take() {
+            self.version += 1;
+        }
+
+        edits
+    }
+
     /// Replaces a byte range in the storage with a new string, and constructs an `Edit` that
     /// represents that change.
     ///
-    /// - The buffer's version is incremented.
+    /// - The buffer's version is incremented, unless a transaction is open.
     /// - The buffer's highlighter is notified of the edit.
     fn edit(&mut self, range: Range<ByteIndex>, new_text: String) -> Edit {
         let start_position = self.storage.position_of_byte(range.start);
@@ -102,10 +562,15 @@ impl Buffer {
 
         let character_range = self.storage.byte_to_char_position(range.start)
             ..self.storage.byte_to_char_position(range.end);
+        let old_text = self.storage.text_in(range.start.0..range.end.0);
 
         self.storage
             .replace_range(range.start.0..range.end.0, &new_text);
-        self.version += 1;
+
+        match &mut self.transaction {
+            Some(edited) => *edited = true,
+            None => self.version += 1,
+        }
 
         let new_end_position = self
             .storage
@@ -115,12 +580,15 @@ impl Buffer {
             range,
             character_range,
             new_text,
+            old_text,
         };
 
         if let Some(highlighter) = &mut self.highlighter {
             highlighter.edit(&edit, start_position, old_end_position, new_end_position);
         }
 
+        self.tab_stops.edit(&edit);
+
         edit
     }
 }
@@ -128,8 +596,13 @@ impl Buffer {
 #[cfg(test)]
 mod tests {
     use indoc::indoc;
+    use lsp_types::{Position, Range, TextEdit};
 
-    use crate::buffer::{Buffer, Cursor};
+    #[cfg(feature = "lang-rust")]
+    use crate::buffer::Motion;
+    use crate::buffer::{Buffer, Cursor, Position as BufferPosition};
+    #[cfg(feature = "lang-rust")]
+    use crate::syntax::Syntax;
 
     use super::ByteIndex;
 
@@ -174,6 +647,77 @@ mod tests {
         assert_eq!(buf.cursor.y(), 1);
     }
 
+    #[test]
+    fn insert_tab() {
+        let mut buf = Buffer::from("ab");
+        buf.cursor = Cursor::at(2, 0);
+
+        buf.insert_tab();
+        assert_eq!(buf.storage.to_string(), "ab\t\n");
+
+        buf.options.expandtab = true;
+        buf.options.tabstop = 4;
+        buf.insert_tab();
+        assert_eq!(buf.storage.to_string(), "ab\t \n");
+        buf.insert_tab();
+        assert_eq!(buf.storage.to_string(), "ab\t     \n");
+        assert_eq!(buf.cursor.x(), 8);
+    }
+
+    #[test]
+    fn replace_before_cursor() {
+        let mut buf = Buffer::from("let fo = 1;");
+        buf.cursor = Cursor::at(6, 0);
+
+        let edit = buf.replace_before_cursor(2, "foo");
+        assert_eq!(buf.storage.to_string(), "let foo = 1;\n");
+        assert_eq!(edit.old_text, "fo");
+        assert_eq!(buf.cursor.x(), 7);
+
+        buf.replace_before_cursor(3, "");
+        assert_eq!(buf.storage.to_string(), "let  = 1;\n");
+        assert_eq!(buf.cursor.x(), 4);
+    }
+
+    #[test]
+    fn delete_word_backward() {
+        let mut buf = Buffer::from("let foo_bar = baz(1);  \nx");
+        buf.cursor = Cursor::at(23, 0);
+
+        let delete = |buf: &mut Buffer| {
+            buf.delete_word_backward();
+            buf.storage.line(0).to_owned()
+        };
+
+        assert_eq!(delete(&mut buf), "let foo_bar = baz(1");
+        assert_eq!(delete(&mut buf), "let foo_bar = baz(");
+        assert_eq!(delete(&mut buf), "let foo_bar = baz");
+        assert_eq!(delete(&mut buf), "let foo_bar = ");
+        assert_eq!(delete(&mut buf), "let foo_bar ");
+        assert_eq!(delete(&mut buf), "let ");
+        assert_eq!(delete(&mut buf), "");
+        assert!(buf.delete_word_backward().is_none());
+
+        buf.cursor = Cursor::at(0, 1);
+        delete(&mut buf);
+        assert_eq!(buf.storage.to_string(), "x\n");
+    }
+
+    #[test]
+    fn delete_to_line_start() {
+        let mut buf = Buffer::from("foo\n    bar baz");
+        buf.cursor = Cursor::at(8, 1);
+
+        let edit = buf.delete_to_line_start().unwrap();
+        assert_eq!(edit.old_text, "    bar ");
+        assert_eq!(buf.storage.to_string(), "foo\nbaz\n");
+        assert_eq!(buf.cursor.x(), 0);
+
+        buf.delete_to_line_start();
+        assert_eq!(buf.storage.to_string(), "foobaz\n");
+        assert_eq!((buf.cursor.x(), buf.cursor.y()), (3, 0));
+    }
+
     #[test]
     fn delete_at_middle_of_line() {
         let mut buf = Buffer::from("abc");
@@ -186,6 +730,37 @@ mod tests {
         assert_eq!(buf.cursor.y(), 0);
     }
 
+    #[test]
+    fn delete_grapheme_clusters() {
+        let mut buf = Buffer::from("ae\u{301}台");
+        buf.cursor.set_x(buf.storage.line(0).len());
+
+        buf.delete();
+        assert_eq!(buf.storage.to_string(), "ae\u{301}\n");
+        assert_eq!(buf.cursor.x(), 4);
+
+        buf.delete();
+        assert_eq!(buf.storage.to_string(), "a\n");
+        assert_eq!(buf.cursor.x(), 1);
+    }
+
+    #[test]
+    fn delete_forward() {
+        let mut buf = Buffer::from("ae\u{301}台");
+        buf.cursor.set_x(1);
+
+        buf.delete_forward();
+        assert_eq!(buf.storage.to_string(), "a台\n");
+        assert_eq!(buf.cursor.x(), 1);
+
+        buf.delete_forward();
+        assert_eq!(buf.storage.to_string(), "a\n");
+        assert_eq!(buf.cursor.x(), 1);
+
+        assert!(buf.delete_forward().is_none());
+        assert_eq!(buf.storage.to_string(), "a\n");
+    }
+
     #[test]
     fn delete_beginning_of_line() {
         let mut buf = Buffer::from("a\nb");
@@ -199,6 +774,145 @@ mod tests {
         assert_eq!(buf.cursor.y(), 0);
     }
 
+    #[test]
+    fn apply_text_edits() {
+        let mut buf = Buffer::from(indoc! {"
+            use foo::bar;
+            use foo::baz;
+        "});
+
+        let edits = buf.apply_text_edits(vec![
+            TextEdit::new(
+                Range::new(Position::new(0, 4), Position::new(0, 7)),
+                String::from("qux"),
+            ),
+            TextEdit::new(
+                Range::new(Position::new(1, 4), Position::new(1, 7)),
+                String::from("qux"),
+            ),
+        ]);
+
+        assert_eq!(buf.storage.to_string(), "use qux::bar;\nuse qux::baz;\n");
+        assert_eq!(buf.version, 1);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].range.start, ByteIndex::new(18));
+    }
+
+    #[test]
+    fn insert_str() {
+        let mut buffer = Buffer::from("fn main() {}\n");
+        buffer.cursor = Cursor::at(11, 0);
+        let version = buffer.version;
+
+        let edit = buffer.insert_str("\n    println!(\"héllo\");\n");
+
+        assert_eq!(
+            buffer.storage.to_string(),
+            "fn main() {\n    println!(\"héllo\");\n}\n"
+        );
+        assert_eq!(buffer.version, version + 1);
+        assert_eq!(edit.range, ByteIndex::new(11)..ByteIndex::new(11));
+        assert_eq!((buffer.cursor.x(), buffer.cursor.y()), (0, 2));
+    }
+
+    #[test]
+    fn trim_trailing_whitespace() {
+        let mut buffer = Buffer::from("a  \nb\n\t\nc \t");
+        buffer.cursor = Cursor::at(2, 3);
+
+        let edits = buffer.trim_trailing_whitespace();
+
+        assert_eq!(buffer.storage.to_string(), "a\nb\n\nc\n");
+        assert_eq!(edits.len(), 3);
+        assert_eq!(buffer.version, 1);
+        assert_eq!((buffer.cursor.x(), buffer.cursor.y()), (1, 3));
+
+        assert!(buffer.trim_trailing_whitespace().is_empty());
+        assert_eq!(buffer.version, 1);
+    }
+
+    #[test]
+    fn ensure_final_newline() {
+        let mut buffer = Buffer::from("a\nbc");
+        buffer.eol = false;
+        assert_eq!(buffer.text(), "a\nbc");
+
+        let edit = buffer.ensure_final_newline().unwrap();
+        assert_eq!(buffer.text(), "a\nbc\n");
+        assert_eq!(buffer.version, 1);
+
+        let event = edit.to_text_document_content_change_event();
+        assert_eq!(
+            event.range,
+            Some(Range::new(Position::new(1, 2), Position::new(1, 2)))
+        );
+        assert_eq!(event.text, "\n");
+
+        assert!(buffer.ensure_final_newline().is_none());
+    }
+
+    #[test]
+    fn replace_text() {
+        let mut buffer = Buffer::from("fn main() {\nlet x=1;\n}\n");
+        buffer.cursor = Cursor::at(4, 1);
+
+        let edit = buffer
+            .replace_text("fn main() {\n    let x = 1;\n}")
+            .unwrap();
+        assert_eq!(
+            buffer.storage.to_string(),
+            "fn main() {\n    let x = 1;\n}\n"
+        );
+        assert_eq!(edit.old_text, "let x=");
+        assert_eq!(edit.new_text, "    let x = ");
+        assert_eq!(buffer.cursor(), BufferPosition::new(4, 1));
+
+        assert!(buffer
+            .replace_text("fn main() {\n    let x = 1;\n}\n")
+            .is_none());
+
+        let mut buffer = Buffer::from("aé\n");
+        let edit = buffer.replace_text("aè\n").unwrap();
+        assert_eq!(edit.old_text, "é");
+        assert_eq!(edit.new_text, "è");
+    }
+
+    #[test]
+    fn replace_in_lines() {
+        let mut buf = Buffer::from(indoc! {"
+            foo foo
+            foo
+            aaa
+        "});
+
+        let edits = buf.replace_in_lines(&[0, 2, 5], "foo", "bar");
+        assert_eq!(edits.len(), 2);
+        assert_eq!(buf.storage.to_string(), "bar bar\nfoo\naaa\n");
+        assert_eq!(buf.version, 1);
+
+        buf.replace_in_lines(&[2], "aa", "b");
+        assert_eq!(buf.storage.to_string(), "bar bar\nfoo\nba\n");
+    }
+
+    #[test]
+    fn transaction() {
+        let mut buffer = Buffer::from("b\n");
+
+        let edits = buffer.transaction(|buffer| {
+            let mut edits = vec![buffer.insert('a')];
+            edits.extend(buffer.transaction(|buffer| vec![buffer.insert_str("\n")]));
+            edits.push(buffer.insert('c'));
+            edits
+        });
+
+        assert_eq!(buffer.storage.to_string(), "a\ncb\n");
+        assert_eq!(edits.len(), 3);
+        assert_eq!(buffer.version, 1);
+
+        assert!(buffer.transaction(|_| vec![]).is_empty());
+        assert_eq!(buffer.version, 1);
+    }
+
     #[test]
     fn delete_beginning_of_buffer() {
         let mut buf = Buffer::new();
@@ -207,4 +921,120 @@ mod tests {
 
         assert!(edit.is_none());
     }
+
+    #[test]
+    fn yank_and_paste() {
+        let mut buffer = Buffer::from(indoc! {"
+            abc
+            def
+        "});
+        buffer.cursor = Cursor::at(1, 1);
+
+        let (line, range) = buffer.yank_line();
+        assert_eq!(line, "def\n");
+        assert_eq!(range, BufferPosition::new(0, 1)..BufferPosition::new(3, 1));
+
+        let edit = buffer.paste(&line, true);
+        assert_eq!(edit.range, ByteIndex::new(4)..ByteIndex::new(4));
+        assert_eq!(buffer.storage.to_string(), "abc\ndef\ndef\n");
+        assert_eq!(buffer.cursor.x(), 0);
+
+        buffer.paste("xy", false);
+        assert_eq!(buffer.storage.to_string(), "abc\nxydef\ndef\n");
+        assert_eq!(buffer.cursor.y(), 1);
+    }
+
+    #[test]
+    fn delete_line_and_paste_after() {
+        let mut buffer = Buffer::from(indoc! {"
+            abc
+            def
+        "});
+
+        let (line, edit) = buffer.delete_line();
+        assert_eq!(line, "abc\n");
+        assert_eq!(edit.range, ByteIndex::new(0)..ByteIndex::new(4));
+        assert_eq!(buffer.storage.to_string(), "def\n");
+
+        buffer.paste_after(&line, true);
+        assert_eq!(buffer.storage.to_string(), "def\nabc\n");
+        assert_eq!((buffer.cursor.x(), buffer.cursor.y()), (0, 1));
+
+        buffer.cursor = Cursor::at(0, 0);
+        buffer.paste_after(&line, true);
+        assert_eq!(buffer.storage.to_string(), "def\nabc\nabc\n");
+
+        buffer.paste_after("xy", false);
+        assert_eq!(buffer.storage.to_string(), "def\naxybc\nabc\n");
+        assert_eq!((buffer.cursor.x(), buffer.cursor.y()), (1, 1));
+
+        buffer.cursor = Cursor::at(1, 2);
+        let (line, _) = buffer.delete_line();
+        assert_eq!(line, "abc\n");
+        assert_eq!(buffer.storage.to_string(), "def\naxybc\n");
+        assert_eq!(buffer.cursor.y(), 1);
+
+        let mut buffer = Buffer::from("abc");
+        buffer.delete_line();
+        assert_eq!(buffer.storage.to_string(), "\n");
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn delete_motion() {
+        let mut buffer = Buffer::from(indoc! {"
+            fn foo() {}
+
+            fn bar() {}
+
+            fn baz() {}
+        "});
+        buffer.set_syntax(Some(Syntax::Rust));
+
+        assert_eq!(
+            buffer.yank_motion(Motion::NextDefinition).unwrap(),
+            (
+                String::from("fn foo() {}\n\n"),
+                BufferPosition::new(0, 0)..BufferPosition::new(0, 2)
+            )
+        );
+
+        let edit = buffer.delete_motion(Motion::NextDefinition).unwrap();
+        assert_eq!(edit.range, ByteIndex::new(0)..ByteIndex::new(13));
+        assert_eq!(edit.old_text, "fn foo() {}\n\n");
+        assert_eq!(buffer.storage.to_string(), "fn bar() {}\n\nfn baz() {}\n");
+
+        buffer.cursor = Cursor::at(0, 2);
+        buffer.delete_motion(Motion::PreviousDefinition).unwrap();
+        assert_eq!(buffer.storage.to_string(), "fn baz() {}\n");
+        assert_eq!(buffer.cursor.y(), 0);
+
+        assert!(buffer.delete_motion(Motion::PreviousDefinition).is_none());
+    }
+
+    #[test]
+    fn delete_selection() {
+        let mut buffer = Buffer::from(indoc! {"
+            Lorem ipsum
+            dolor sit amet
+        "});
+        assert!(buffer.delete_selection().is_none());
+
+        buffer.cursor = Cursor::at(6, 0);
+        buffer.start_selection();
+        buffer.cursor = Cursor::at(5, 1);
+        assert_eq!(
+            buffer.yank_selection().unwrap(),
+            (
+                String::from("ipsum\ndolor "),
+                BufferPosition::new(6, 0)..BufferPosition::new(6, 1)
+            )
+        );
+
+        let edit = buffer.delete_selection().unwrap();
+        assert_eq!(edit.old_text, "ipsum\ndolor ");
+        assert_eq!(buffer.storage.to_string(), "Lorem sit amet\n");
+        assert_eq!((buffer.cursor.x(), buffer.cursor.y()), (6, 0));
+        assert!(buffer.selection().is_none());
+    }
 }
@@ -0,0 +1,172 @@
+//! Decoding files that aren't UTF-8, and encoding them again when they're written.
+//!
+//! Buffers are always edited as UTF-8. When a file is opened, its encoding is detected from its
+//! byte order mark or, failing that, guessed from its contents. The buffer remembers the encoding
+//! and whether the file had a byte order mark, so that the file is written back the way it was
+//! read. The byte order mark itself is never part of the buffer's contents.
+
+use std::borrow::Cow;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use tokio::io;
+
+/// The byte order mark, as a UTF-16 code unit.
+const BOM: u16 = 0xfeff;
+
+/// The decoded contents of a file.
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    /// The text of the file, without its byte order mark.
+    pub text: String,
+
+    /// The encoding that the file was decoded from.
+    pub encoding: &'static Encoding,
+
+    /// Whether the file started with a byte order mark.
+    pub bom: bool,
+}
+
+/// Decodes the contents of a file.
+///
+/// UTF-16 is only detected if the file starts with a byte order mark. Files that are valid UTF-8
+/// are always decoded as UTF-8; other encodings, such as Latin-1 or Shift-JIS, are guessed.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return Decoded {
+            text: text.into_owned(),
+            encoding,
+            bom: true,
+        };
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Decoded {
+            text: text.to_owned(),
+            encoding: UTF_8,
+            bom: false,
+        };
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, false);
+
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    Decoded {
+        text: text.into_owned(),
+        encoding,
+        bom: false,
+    }
+}
+
+/// Encodes text for writing to a file, starting with a byte order mark if `bom` is true.
+///
+/// Only Unicode encodings have a byte order mark, so `bom` is ignored for other encodings. Returns
+/// an error if the text contains characters that the encoding can't represent.
+pub fn encode<'a>(
+    text: &'a str,
+    encoding: &'static Encoding,
+    bom: bool,
+) -> io::Result<Cow<'a, [u8]>> {
+    let bom = if bom { Some(BOM) } else { None };
+
+    // encoding_rs only decodes UTF-16, so it is encoded by hand.
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+        for unit in bom.into_iter().chain(text.encode_utf16()) {
+            if encoding == UTF_16LE {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        return Ok(Cow::Owned(bytes));
+    }
+
+    if encoding == UTF_8 && bom.is_some() {
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        bytes.extend_from_slice("\u{feff}".as_bytes());
+        bytes.extend_from_slice(text.as_bytes());
+        return Ok(Cow::Owned(bytes));
+    }
+
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("text cannot be encoded as {}", encoding.name()),
+        ));
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use super::{decode, encode, Decoded};
+
+    fn decoded(text: &str, encoding: &'static Encoding, bom: bool) -> Decoded {
+        Decoded {
+            text: String::from(text),
+            encoding,
+            bom,
+        }
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(
+            decode("naïve\n".as_bytes()),
+            decoded("naïve\n", UTF_8, false)
+        );
+        assert_eq!(
+            encode("naïve\n", UTF_8, false).unwrap(),
+            "naïve\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn utf8_bom() {
+        let bytes = "\u{feff}hi\n".as_bytes();
+        assert_eq!(decode(bytes), decoded("hi\n", UTF_8, true));
+        assert_eq!(encode("hi\n", UTF_8, true).unwrap(), bytes);
+        assert_eq!(encode("hi\n", UTF_8, false).unwrap(), "hi\n".as_bytes());
+    }
+
+    #[test]
+    fn latin1() {
+        let bytes = b"na\xefve caf\xe9\n";
+
+        let Decoded { text, encoding, .. } = decode(bytes);
+        assert_eq!(text, "naïve café\n");
+        assert_eq!(encoding, WINDOWS_1252);
+
+        assert_eq!(encode(&text, encoding, false).unwrap(), &bytes[..]);
+        assert_eq!(encode(&text, encoding, true).unwrap(), &bytes[..]);
+        assert!(encode("台北", encoding, false).is_err());
+    }
+
+    #[test]
+    fn utf16() {
+        let le = b"\xff\xfeh\x00i\x00\n\x00";
+        assert_eq!(decode(le), decoded("hi\n", UTF_16LE, true));
+        assert_eq!(encode("hi\n", UTF_16LE, true).unwrap(), &le[..]);
+        assert_eq!(encode("hi\n", UTF_16LE, false).unwrap(), &le[2..]);
+
+        let be = b"\xfe\xff\x00h\x00i\x00\n";
+        assert_eq!(decode(be), decoded("hi\n", UTF_16BE, true));
+        assert_eq!(encode("hi\n", UTF_16BE, true).unwrap(), &be[..]);
+    }
+
+    #[test]
+    fn shift_jis() {
+        let text = "これは日本語のテキストです。文字コードを推測します。\n";
+        let (bytes, _, _) = SHIFT_JIS.encode(text);
+
+        assert_eq!(decode(&bytes), decoded(text, SHIFT_JIS, false));
+    }
+}
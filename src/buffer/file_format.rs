@@ -0,0 +1,82 @@
+//! Line endings of files.
+//!
+//! Buffers always separate lines with `\n`. The line ending of a file is detected when it is
+//! opened, and restored when it is written.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// The line ending of a file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileFormat {
+    /// Lines end with `\n`.
+    Unix,
+
+    /// Lines end with `\r\n`.
+    Dos,
+}
+
+impl FileFormat {
+    /// Detects the line ending of a file from its first line.
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(index) if text[..index].ends_with('\r') => FileFormat::Dos,
+            _ => FileFormat::Unix,
+        }
+    }
+
+    /// Returns the characters that end each line.
+    pub fn line_ending(self) -> &'static str {
+        match self {
+            FileFormat::Unix => "\n",
+            FileFormat::Dos => "\r\n",
+        }
+    }
+}
+
+impl Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileFormat::Unix => "unix",
+            FileFormat::Dos => "dos",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("unknown file format: {0}")]
+pub struct UnknownFileFormat(String);
+
+impl FromStr for FileFormat {
+    type Err = UnknownFileFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(FileFormat::Unix),
+            "dos" => Ok(FileFormat::Dos),
+            _ => Err(UnknownFileFormat(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileFormat;
+
+    #[test]
+    fn detect() {
+        assert_eq!(FileFormat::detect("a\r\nb\n"), FileFormat::Dos);
+        assert_eq!(FileFormat::detect("a\nb\r\n"), FileFormat::Unix);
+        assert_eq!(FileFormat::detect("a\r"), FileFormat::Unix);
+        assert_eq!(FileFormat::detect(""), FileFormat::Unix);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("dos".parse(), Ok(FileFormat::Dos));
+        assert_eq!(FileFormat::Unix.to_string(), "unix");
+        assert!("mac".parse::<FileFormat>().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Foldable regions of a buffer.
+//!
+//! Language servers can report folding ranges, but the editor doesn't request them yet. Instead,
+//! folds are derived from the syntax tree using the grammar's `folds.scm` query, which captures
+//! nodes that may be folded with `@fold`.
+
+use euclid::vec2;
+use tree_sitter::Node;
+
+use super::Buffer;
+
+/// A range of lines that may be folded.
+///
+/// Like LSP folding ranges, both lines are zero-indexed and inclusive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Fold {
+    pub start_row: usize,
+    pub end_row: usize,
+}
+
+impl Fold {
+    /// Returns the fold covering a node, or `None` if the node is contained within a single line.
+    pub fn from_node(node: Node) -> Option<Self> {
+        let start = node.start_position();
+        let end = node.end_position();
+
+        // Nodes that include their trailing newline end at the start of the following line.
+        let end_row = if end.column == 0 && end.row > start.row {
+            end.row - 1
+        } else {
+            end.row
+        };
+
+        if end_row > start.row {
+            Some(Fold {
+                start_row: start.row,
+                end_row,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Sorts folds by their first line, keeping only the outermost fold that starts on each line.
+pub fn normalize(folds: &mut Vec<Fold>) {
+    folds.sort_by(|a, b| {
+        a.start_row
+            .cmp(&b.start_row)
+            .then(b.end_row.cmp(&a.end_row))
+    });
+    folds.dedup_by_key(|fold| fold.start_row);
+}
+
+impl Buffer {
+    /// Returns the foldable regions of the buffer, ordered by their first line.
+    pub fn folds(&self) -> Vec<Fold> {
+        match &self.highlighter {
+            Some(highlighter) => highlighter.folds(self),
+            None => vec![],
+        }
+    }
+
+    /// Moves the cursor down to the start of the next fold.
+    pub fn move_to_next_fold(&mut self) {
+        let y = self.cursor.y();
+
+        if let Some(fold) = self.folds().into_iter().find(|fold| fold.start_row > y) {
+            self.move_offset(vec2(0, (fold.start_row - y) as isize));
+        }
+    }
+
+    /// Moves the cursor up to the end of the previous fold.
+    pub fn move_to_previous_fold(&mut self) {
+        let y = self.cursor.y();
+
+        if let Some(end_row) = self
+            .folds()
+            .into_iter()
+            .map(|fold| fold.end_row)
+            .filter(|&end_row| end_row < y)
+            .max()
+        {
+            self.move_offset(vec2(0, -((y - end_row) as isize)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "lang-rust")]
+    use indoc::indoc;
+
+    use crate::buffer::Buffer;
+    #[cfg(feature = "lang-rust")]
+    use crate::buffer::Cursor;
+    #[cfg(feature = "lang-rust")]
+    use crate::syntax::Syntax;
+
+    use super::{normalize, Fold};
+
+    #[cfg(feature = "lang-rust")]
+    fn rust_buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::from(text);
+        buffer.set_syntax(Some(Syntax::Rust));
+        buffer
+    }
+
+    #[test]
+    fn normalize_keeps_outermost_fold() {
+        let mut folds = vec![
+            Fold {
+                start_row: 2,
+                end_row: 3,
+            },
+            Fold {
+                start_row: 0,
+                end_row: 2,
+            },
+            Fold {
+                start_row: 0,
+                end_row: 5,
+            },
+        ];
+
+        normalize(&mut folds);
+
+        assert_eq!(
+            folds,
+            vec![
+                Fold {
+                    start_row: 0,
+                    end_row: 5,
+                },
+                Fold {
+                    start_row: 2,
+                    end_row: 3,
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn folds_from_syntax_tree() {
+        let buffer = rust_buffer(indoc! {"
+            use std::{
+                fs,
+                io,
+            };
+
+            fn main() {
+                if true {
+                    println!();
+                }
+            }
+
+            struct Unit;
+        "});
+
+        assert_eq!(
+            buffer.folds(),
+            vec![
+                Fold {
+                    start_row: 0,
+                    end_row: 3,
+                },
+                Fold {
+                    start_row: 5,
+                    end_row: 9,
+                },
+                Fold {
+                    start_row: 6,
+                    end_row: 8,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_folds_without_syntax() {
+        let buffer = Buffer::from("fn main() {\n}\n");
+
+        assert!(buffer.folds().is_empty());
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn move_between_folds() {
+        let mut buffer = rust_buffer(indoc! {"
+            fn foo() {
+                bar();
+            }
+
+            fn baz() {
+                quux();
+            }
+        "});
+
+        buffer.move_to_next_fold();
+        assert_eq!(buffer.cursor.y(), 4);
+
+        buffer.move_to_next_fold();
+        assert_eq!(buffer.cursor.y(), 4);
+
+        buffer.move_to_previous_fold();
+        assert_eq!(buffer.cursor.y(), 2);
+
+        buffer.cursor = Cursor::at(0, 1);
+        buffer.move_to_previous_fold();
+        assert_eq!(buffer.cursor.y(), 1);
+    }
+}
@@ -0,0 +1,48 @@
+//! Buffers that show help documents.
+//!
+//! Help buffers can't be edited. Pressing Enter on a reference to a tag follows it.
+
+use crate::help::{self, Doc};
+
+use super::{Buffer, Buffers, Storage};
+
+impl Buffer {
+    /// Creates a buffer showing a help document.
+    fn from_help(doc: Doc) -> Self {
+        Buffer {
+            storage: Storage::from(doc.text),
+            help: Some(doc.name),
+            ..Buffer::new()
+        }
+    }
+
+    /// Returns the name of the help document that the buffer shows, if any.
+    pub fn help_doc(&self) -> Option<&'static str> {
+        self.help
+    }
+
+    /// Returns the tag referred to under the cursor, if the buffer shows a help document.
+    pub fn tag_at_cursor(&self) -> Option<&str> {
+        self.help?;
+
+        let line = self.storage.iter_lines().nth(self.cursor.y())?;
+        help::reference_at(line, self.cursor.x())
+    }
+}
+
+impl Buffers {
+    /// Makes the buffer showing a help document the active buffer, opening it if there isn't one
+    /// already.
+    pub fn focus_help(&mut self, doc: Doc) -> &mut Buffer {
+        match self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.help == Some(doc.name))
+        {
+            Some(index) => self.focus_index(index),
+            None => self.push(Buffer::from_help(doc)),
+        }
+
+        self.current_mut()
+    }
+}
@@ -0,0 +1,246 @@
+//! Editing binary files as a hex dump.
+//!
+//! Like `xxd`, binary files are shown with one line per sixteen bytes: the offset of the first
+//! byte, the bytes in hexadecimal, and the bytes as ASCII, with `.` for bytes that aren't
+//! printable. Either pane may be edited in place. When the buffer is written, the lines are
+//! converted back to bytes and the dump is redrawn, so that the panes agree again.
+
+use std::cmp;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tokio::io;
+
+use super::{Buffer, Storage};
+
+/// The number of bytes shown on each line.
+const BYTES_PER_LINE: usize = 16;
+
+/// The column that the ASCII pane starts at: the offset, two bytes per hex digit pair with a space
+/// after every other pair, and another space.
+const ASCII_COLUMN: usize = 10 + BYTES_PER_LINE * 5 / 2 + 1;
+
+impl Buffer {
+    /// Opens a buffer showing the contents of the given path as a hex dump. The path must be
+    /// absolute.
+    ///
+    /// Directories are listed as usual.
+    pub async fn open_binary(path: PathBuf) -> io::Result<Self> {
+        assert!(path.is_absolute(), "path must be absolute");
+
+        let bytes = match fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_dir() => return Buffer::open_directory(path).await,
+            Ok(_) => fs::read(&path).await?,
+            Err(_) => vec![],
+        };
+
+        Ok(Buffer::from_binary(path, bytes))
+    }
+
+    /// Creates a buffer showing the bytes of a file as a hex dump.
+    pub(super) fn from_binary(path: PathBuf, bytes: Vec<u8>) -> Self {
+        Buffer {
+            storage: Storage::from(dump(&bytes).as_str()),
+            path: Some(path),
+            binary: Some(bytes),
+            ..Buffer::new()
+        }
+    }
+
+    /// Returns whether the buffer shows its file as a hex dump.
+    pub fn is_binary(&self) -> bool {
+        self.binary.is_some()
+    }
+
+    /// Converts the hex dump back to bytes and writes them to a file, then redraws the dump.
+    pub(super) async fn write_binary(&mut self, path: &Path) -> io::Result<()> {
+        let original = self.binary.as_deref().unwrap_or_default();
+        let bytes = parse(self.storage.iter_lines(), original)?;
+        fs::write(path, &bytes).await?;
+
+        self.storage = Storage::from(dump(&bytes).as_str());
+        self.binary = Some(bytes);
+        self.clamp_cursor();
+
+        Ok(())
+    }
+}
+
+/// Returns whether the contents of a file should be shown as a hex dump rather than as text.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+/// Returns the lines of the hex dump of some bytes, each ending with a newline.
+fn dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        dump.push_str(&format!("{:08x}: ", line * BYTES_PER_LINE));
+
+        for i in 0..BYTES_PER_LINE {
+            match chunk.get(i) {
+                Some(byte) => dump.push_str(&format!("{:02x}", byte)),
+                None => dump.push_str("  "),
+            }
+
+            if i % 2 == 1 {
+                dump.push(' ');
+            }
+        }
+
+        dump.push(' ');
+        dump.extend(chunk.iter().map(|&byte| display(byte)));
+        dump.push('\n');
+    }
+
+    dump
+}
+
+/// Returns the character that a byte is shown as in the ASCII pane.
+fn display(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        char::from(byte)
+    } else {
+        '.'
+    }
+}
+
+/// Converts the lines of a hex dump back to bytes.
+///
+/// Each line is compared with the bytes that it was dumped from. A byte changed in the hex pane
+/// takes priority over the ASCII pane; otherwise, a printable character changed in the ASCII pane
+/// replaces its byte. The ASCII pane is only read while the line keeps its original layout, since
+/// its columns no longer line up with the bytes once bytes are added or removed. Empty lines are
+/// ignored, and the offsets only identify the original bytes, so lines may be added or removed.
+fn parse<'a>(lines: impl Iterator<Item = &'a str>, original: &[u8]) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+
+    for (row, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {} of the hex dump is invalid", row + 1),
+            )
+        };
+
+        let colon = line.find(':').ok_or_else(invalid)?;
+        let offset = usize::from_str_radix(&line[..colon], 16).map_err(|_| invalid())?;
+
+        // The hex pane ends at the first double space, like `xxd -r`.
+        let rest = &line[colon + 1..];
+        let rest = rest.strip_prefix(' ').unwrap_or(rest);
+        let hex = match rest.find("  ") {
+            Some(index) => &rest[..index],
+            None => rest,
+        };
+        let digits = hex.chars().filter(|c| *c != ' ').collect::<Vec<_>>();
+        if digits.len() % 2 != 0 {
+            return Err(invalid());
+        }
+
+        let len = digits.len() / 2;
+        let original_len = cmp::min(original.len().saturating_sub(offset), BYTES_PER_LINE);
+        let ascii = if len == original_len && line.chars().count() == ASCII_COLUMN + len {
+            line.chars().skip(ASCII_COLUMN).collect()
+        } else {
+            vec![]
+        };
+
+        for (i, pair) in digits.chunks(2).enumerate() {
+            let pair = pair.iter().collect::<String>();
+            let byte = u8::from_str_radix(&pair, 16).map_err(|_| invalid())?;
+
+            bytes.push(match original.get(offset + i) {
+                Some(&original) if byte == original => match ascii.get(i) {
+                    Some(&c) if c != display(original) && (c.is_ascii_graphic() || c == ' ') => {
+                        c as u8
+                    }
+                    _ => original,
+                },
+                _ => byte,
+            });
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+
+    use super::{dump, is_binary, parse};
+
+    const BYTES: &[u8] = b"Hello, world!\n\x00\x01\xffabc";
+
+    #[test]
+    fn detect_binary() {
+        assert!(is_binary(BYTES));
+        assert!(!is_binary(b"Hello, world!\n"));
+    }
+
+    #[test]
+    fn dump_bytes() {
+        assert_eq!(
+            dump(BYTES),
+            "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 0001  Hello, world!...\n\
+             00000010: ff61 6263                                .abc\n"
+        );
+        assert_eq!(dump(b""), "");
+    }
+
+    #[test]
+    fn parse_unchanged() {
+        let dump = dump(BYTES);
+        assert_eq!(parse(dump.lines(), BYTES).unwrap(), BYTES);
+    }
+
+    #[test]
+    fn parse_edits() {
+        // The hex pane changes the first byte, and the ASCII pane changes the last.
+        let lines = [
+            "00000000: 4a65 6c6c 6f2c 2077 6f72 6c64 210a 0001  Hello, world!...",
+            "00000010: ff61 6263                                .abC",
+        ];
+        assert_eq!(
+            parse(lines.iter().copied(), BYTES).unwrap(),
+            b"Jello, world!\n\x00\x01\xffabC"
+        );
+
+        // Bytes may be appended in the hex pane.
+        let lines = ["00000010: ff61 6263 64                             .abc"];
+        assert_eq!(parse(lines.iter().copied(), BYTES).unwrap(), b"\xffabcd");
+
+        assert!(parse(["garbage"].iter().copied(), BYTES).is_err());
+        assert!(parse(["00000000: 4"].iter().copied(), BYTES).is_err());
+    }
+
+    #[tokio::test]
+    async fn open_and_write_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, BYTES).unwrap();
+
+        let mut buffer = Buffer::open(path.clone()).await.unwrap();
+        assert!(buffer.is_binary());
+        assert_eq!(buffer.text(), dump(BYTES));
+
+        // The offset is only used to find the original bytes.
+        buffer.insert('0');
+        buffer.write_to(&path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), BYTES);
+        assert_eq!(buffer.text(), dump(BYTES));
+        assert!(!buffer.is_modified());
+
+        let text = dir.path().join("text.txt");
+        std::fs::write(&text, "Hello").unwrap();
+        let buffer = Buffer::open_binary(text).await.unwrap();
+        assert!(buffer.is_binary());
+        assert_eq!(buffer.text(), dump(b"Hello"));
+    }
+}
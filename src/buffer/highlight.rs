@@ -14,100 +14,132 @@
 
 use std::cell::RefCell;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
-use lazy_static::lazy_static;
+use futures::future;
 use log::*;
-use maplit::hashmap;
-use tree_sitter::{InputEdit, Parser, Point, Query, QueryCursor, Range, Tree};
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Query, QueryCursor, Range, Tree};
 
 use crate::buffer::units::BytePosition;
+use crate::grammar::{Grammar, LoadError};
 use crate::syntax::Syntax;
-use crate::ui::{Bounds, Color, Coordinates, Screen};
-
-use super::{edit::Edit, Buffer, Span};
-
-lazy_static! {
-    static ref DEFAULT_THEME: HashMap<&'static str, Color> = hashmap! {
-        "attribute" => Color::new(0xff, 0x00, 0x00),
-        "comment" => Color::new(0x4e, 0x4e, 0x4e),
-        "constant" => Color::new(0x00, 0x87, 0x87),
-        "escape" => Color::new(0xff, 0xd7, 0x00),
-        "function" => Color::new(0xff, 0x87, 0x00),
-        "function.macro" => Color::new(0xff, 0x00, 0x00),
-        "keyword" => Color::new(0xff, 0xff, 0x00),
-        "label" => Color::new(0xff, 0xff, 0x00),
-        "number" => Color::new(0x00, 0x87, 0x87),
-        "operator" => Color::new(0xff, 0xff, 0x00),
-        "string" => Color::new(0x5f, 0x87, 0xd7),
-        "type" => Color::new(0x00, 0xff, 0x00),
-    };
-}
+use crate::theme::{self, ColorScheme};
+use crate::ui::{Bounds, Coordinates, Screen, Style};
+
+use super::columns::{byte_of_column, column_of};
+use super::fold::{self, Fold};
+use super::wrap::DisplayLines;
+use super::{edit::Edit, Buffer, Position};
+
+mod cache;
+mod parse;
+
+use cache::{HighlightCache, Highlights};
+use parse::{ParseState, PendingParse, BACKGROUND_PARSE_THRESHOLD};
+
+/// The maximum number of nested language injections to highlight.
+///
+/// This prevents queries that inject a language into itself from recursing forever.
+const MAX_INJECTION_DEPTH: usize = 8;
+
+/// Lines longer than this many bytes, such as those of minified files, are only highlighted within
+/// the viewport. Their highlights aren't cached, since they depend on the horizontal scroll.
+const LONG_LINE_LENGTH: usize = 1024;
 
+/// The styles of a highlight query's captures in a color scheme.
 pub struct Theme {
-    /// Map of capture index to associated color, if any.
-    colors: Vec<Option<Color>>,
+    /// The color scheme that the styles were taken from.
+    scheme: &'static str,
+
+    /// Map of capture index to associated style, if any.
+    styles: Vec<Option<Style>>,
 }
 
 impl Theme {
-    pub fn new(capture_names: &[String]) -> Self {
-        let theme = &DEFAULT_THEME;
-
+    pub fn new(capture_names: &[String], scheme: &ColorScheme) -> Self {
         Self {
-            colors: capture_names
+            scheme: scheme.name,
+            styles: capture_names
                 .iter()
                 .map(|name| {
-                    if let Some(color) = theme.get(name.as_str()) {
-                        return Some(*color);
-                    }
-
-                    for (pos, _) in name.rmatch_indices('.') {
-                        let fallback_name = &name[..pos];
+                    let style = scheme.style(name);
 
-                        if let Some(color) = theme.get(&fallback_name) {
-                            info!("no color for {}, falling back to {}", name, &fallback_name);
-                            return Some(*color);
-                        }
+                    if style.is_none() {
+                        info!("no style for {} in {}", name, scheme.name);
                     }
 
-                    info!("no color for {}", name);
-
-                    None
+                    style
                 })
                 .collect(),
         }
     }
 
-    pub fn color_for(&self, capture_index: usize) -> Option<Color> {
-        self.colors[capture_index]
+    pub fn style_for(&self, capture_index: usize) -> Option<Style> {
+        self.styles[capture_index]
     }
 }
 
 pub struct Highlighter {
+    language: Language,
     parser: RefCell<Parser>,
     query: Query,
-    old_tree: Option<Tree>,
-    theme: Theme,
+
+    /// The syntax tree of the entire buffer.
+    parse_state: RefCell<ParseState>,
+
+    /// Highlights of lines that have already been highlighted.
+    cache: RefCell<HighlightCache>,
+    theme: RefCell<Theme>,
+
+    /// Query identifying regions of the buffer that should be highlighted as another language.
+    injection_query: Option<Query>,
+
+    /// Highlighters for languages injected into this one, created on demand. `None` if the
+    /// grammar for the language could not be loaded.
+    injected: RefCell<HashMap<Syntax, Option<Highlighter>>>,
+
+    /// Query identifying scopes, definitions and references of local variables.
+    locals_query: Option<Query>,
+
+    /// Query identifying foldable regions.
+    folds_query: Option<Query>,
+
+    /// Query identifying functions and classes.
+    textobjects_query: Option<Query>,
 }
 
 impl Highlighter {
-    pub fn new(language: Syntax) -> Self {
-        let (language, query) = tree_sitter_highlight_config(language);
+    pub fn new(syntax: Syntax) -> Result<Self, LoadError> {
+        let Grammar {
+            language,
+            highlights: query,
+            injections: injection_query,
+            locals: locals_query,
+            folds: folds_query,
+            textobjects: textobjects_query,
+        } = Grammar::load(syntax)?;
 
         let mut parser = Parser::new();
         parser
             .set_language(language)
-            .expect("incompatible tree-sitter version");
+            .map_err(LoadError::Incompatible)?;
 
-        let theme = Theme::new(query.capture_names());
+        let theme = RefCell::new(Theme::new(query.capture_names(), theme::current()));
 
-        Highlighter {
+        Ok(Highlighter {
+            language,
             query,
             parser: RefCell::new(parser),
-            old_tree: None,
+            parse_state: RefCell::new(ParseState::default()),
+            cache: RefCell::new(HighlightCache::default()),
             theme,
-        }
+            injection_query,
+            injected: RefCell::new(HashMap::new()),
+            locals_query,
+            folds_query,
+            textobjects_query,
+        })
     }
 
     /// Notifies the highlighter that the underlying text has been edited.
@@ -118,172 +150,893 @@ impl Highlighter {
         old_end_position: BytePosition,
         new_end_position: BytePosition,
     ) {
-        if let Some(tree) = &mut self.old_tree {
-            tree.edit(&InputEdit {
-                start_byte: edit.range.start.0,
-                old_end_byte: edit.range.end.0,
-                new_end_byte: edit.range.start.0 + edit.new_text.len(),
-                start_position: Point {
-                    row: start_position.y,
-                    column: start_position.x,
-                },
-                old_end_position: Point {
-                    row: old_end_position.y,
-                    column: old_end_position.x,
-                },
-                new_end_position: Point {
-                    row: new_end_position.y,
-                    column: new_end_position.x,
-                },
-            });
+        let cache = self.cache.get_mut();
+        cache.edit(start_position.y, old_end_position.y, new_end_position.y);
+
+        // Definitions and injections may affect the highlighting of any following line.
+        if self.locals_query.is_some() || self.injection_query.is_some() {
+            cache.invalidate_from(start_position.y);
         }
+
+        self.parse_state.get_mut().edit(InputEdit {
+            start_byte: edit.range.start.0,
+            old_end_byte: edit.range.end.0,
+            new_end_byte: edit.range.start.0 + edit.new_text.len(),
+            start_position: Point {
+                row: start_position.y,
+                column: start_position.x,
+            },
+            old_end_position: Point {
+                row: old_end_position.y,
+                column: old_end_position.x,
+            },
+            new_end_position: Point {
+                row: new_end_position.y,
+                column: new_end_position.x,
+            },
+        });
     }
 
-    /// Apply syntax highlighting from buffer to the screen.
-    pub fn highlight(&self, screen: &mut Screen, buffer: &Buffer) {
-        debug!("starting highlighting");
+    /// Waits for a background parse of the buffer to finish.
+    ///
+    /// Never completes if the buffer is not being parsed in the background.
+    pub async fn parsed(&mut self) {
+        let state = self.parse_state.get_mut();
 
-        let tree = self.parser.borrow_mut().parse_with(
-            &mut |_, point| {
-                buffer
-                    .storage
-                    .slice_at(BytePosition::new(point.column, point.row))
-            },
-            self.old_tree.as_ref(),
-        );
+        let result = match &mut state.pending {
+            Some(pending) => (&mut pending.result).await,
+            None => future::pending().await,
+        };
 
-        let tree = match tree {
+        if state.finish(result.unwrap_or(None)) {
+            self.cache.get_mut().clear();
+        }
+    }
+
+    /// Apply syntax highlighting from buffer to the screen.
+    ///
+    /// Only lines that have not been highlighted since they were last edited are highlighted
+    /// again.
+    /// The viewport is drawn with its top-left corner at `origin`, showing `display_lines`.
+    pub fn highlight(
+        &self,
+        screen: &mut Screen,
+        origin: Coordinates,
+        buffer: &Buffer,
+        display_lines: &DisplayLines,
+    ) {
+        let tree = match self.buffer_tree(buffer) {
             Some(tree) => tree,
             None => return,
         };
 
-        let mut cursor = QueryCursor::new();
+        let rows = match (display_lines.iter().next(), display_lines.iter().last()) {
+            (Some(first), Some(last)) => first.line..last.line + 1,
+            _ => return,
+        };
 
-        let viewport = buffer
-            .viewport
-            .expect("attempted to highlight hidden buffer");
+        let mut cache = self.cache.borrow_mut();
+
+        if self.update_theme() {
+            cache.clear();
+        }
+
+        // Highlights of long lines, which are computed separately so that the lines around them
+        // don't need to be highlighted across their whole length.
+        let mut windows = HashMap::new();
+        let mut short_rows_start = rows.start;
+
+        for row in rows.start..=rows.end {
+            if row < rows.end && buffer.storage.line(row).len() <= LONG_LINE_LENGTH {
+                continue;
+            }
+
+            if let Some(missing_rows) = cache.missing_rows(short_rows_start..row) {
+                debug!("highlighting lines {:?}", missing_rows);
+
+                let mut highlights = Highlights::new(missing_rows);
+                self.highlight_tree(&mut highlights, buffer, &tree, 0);
+                cache.insert(highlights);
+            }
+
+            if row < rows.end {
+                let line = buffer.storage.line(row);
+                let columns = display_lines
+                    .iter()
+                    .filter(|display_line| display_line.line == row)
+                    .map(|display_line| display_line.columns.clone());
+                let start = columns.clone().map(|columns| columns.start).min();
+                let end = columns.map(|columns| columns.end).max();
+                let mut highlights = Highlights::window(
+                    row,
+                    byte_of_column(line, start.unwrap_or(0))
+                        ..byte_of_column(line, end.unwrap_or(0)),
+                );
+                self.highlight_tree(&mut highlights, buffer, &tree, 0);
+                windows.insert(row, highlights.into_first_line());
+            }
 
-        let (start, end) = span_to_points(viewport);
+            short_rows_start = row + 1;
+        }
+
+        for row in rows {
+            let line = match windows.get(&row) {
+                Some(line) => Some(line.as_slice()),
+                None => cache.get(row),
+            };
+
+            let text = buffer.storage.line(row);
+            for highlight in line.unwrap_or_default() {
+                let start_x = column_of(text, highlight.start);
+                let end_x = column_of(text, highlight.end);
+
+                for (y, columns) in display_lines.spans(row, start_x..end_x) {
+                    let y = origin.y + u16::try_from(y).expect("viewport outside screen bounds");
+                    let start_x = u16::try_from(columns.start)
+                        .expect("attempted to draw outside screen bounds");
+                    let end_x = u16::try_from(columns.end)
+                        .expect("attempted to draw outside screen bounds");
+
+                    screen.apply_style(
+                        Bounds::new(
+                            Coordinates::new(origin.x + start_x, y),
+                            Coordinates::new(origin.x + end_x, y + 1),
+                        ),
+                        highlight.style,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Highlights the given ranges of the buffer, which contain a language injected into another.
+    ///
+    /// `depth` is the number of injections that the ranges are nested within.
+    fn highlight_ranges(
+        &self,
+        highlights: &mut Highlights,
+        buffer: &Buffer,
+        ranges: &[Range],
+        depth: usize,
+    ) {
+        self.update_theme();
+
+        if let Some(tree) = self.parse(buffer, ranges, None) {
+            self.highlight_tree(highlights, buffer, &tree, depth);
+        }
+    }
+
+    /// Switches to the active color scheme, if it has changed.
+    ///
+    /// Returns `true` if the theme was changed.
+    fn update_theme(&self) -> bool {
+        let scheme = theme::current();
+        let mut theme = self.theme.borrow_mut();
+
+        if theme.scheme == scheme.name {
+            return false;
+        }
+
+        *theme = Theme::new(self.query.capture_names(), scheme);
+        true
+    }
+
+    /// Computes the highlights of a syntax tree, including any injected languages.
+    fn highlight_tree(
+        &self,
+        highlights: &mut Highlights,
+        buffer: &Buffer,
+        tree: &Tree,
+        depth: usize,
+    ) {
+        let mut cursor = QueryCursor::new();
+
+        let (start, end) = highlights.points();
         cursor.set_point_range(start, end);
 
+        let locals = self.resolve_locals(buffer, tree, end);
+        let mut definition_styles = HashMap::new();
+
+        // The node most recently colored as a reference to a local definition.
+        let mut resolved_reference = None;
+
         let captures_query = cursor.captures(&self.query, tree.root_node(), |node| {
-            let Range {
-                start_point,
-                end_point,
-                ..
-            } = node.range();
-            let start = BytePosition::new(start_point.column, start_point.row);
-            let end = BytePosition::new(end_point.column, end_point.row);
-            &buffer.storage[start..end]
+            node_text(buffer, node)
         });
 
-        for (m, _) in captures_query {
-            for capture in m.captures {
-                let range = capture.node.range();
-                let index = capture.index as usize;
+        for (m, capture_index) in captures_query {
+            let capture = m.captures[capture_index];
+            let node = capture.node;
+            let range = node.range();
+            let index = capture.index as usize;
 
-                let color = self.theme.color_for(index);
+            if resolved_reference == Some(node.id())
+                || !locals.satisfies_predicates(&self.query, m.pattern_index, node)
+            {
+                continue;
+            }
 
-                if log_enabled!(log::Level::Debug) {
-                    // The capture range may span across lines, so we can't use the storage's
-                    // `Index` implementation.
-                    let text = &buffer.storage.to_string()[range.start_byte..range.end_byte];
+            // References to local variables are styled the same as their definition.
+            let definition_style = locals.references.get(&node.id()).and_then(|&definition| {
+                *definition_styles
+                    .entry(definition.id)
+                    .or_insert_with(|| self.definition_style(buffer, tree, &locals, definition))
+            });
 
-                    debug!(
-                        "capture={} color={:?} text={:?}",
-                        self.query.capture_names()[index],
-                        color,
-                        text,
-                    );
-                }
+            if definition_style.is_some() {
+                resolved_reference = Some(node.id());
+            }
+
+            let style = definition_style.or_else(|| self.theme.borrow().style_for(index));
+
+            if log_enabled!(log::Level::Debug) {
+                // The capture range may span across lines, so we can't use the storage's
+                // `Index` implementation.
+                let text = &buffer.storage.to_string()[range.start_byte..range.end_byte];
+
+                debug!(
+                    "capture={} style={:?} text={:?}",
+                    self.query.capture_names()[index],
+                    style,
+                    text,
+                );
+            }
+
+            if let Some(style) = style {
+                highlights.add(range, style);
+            }
+        }
+
+        self.highlight_injections(highlights, buffer, tree, depth);
+    }
+
+    /// Returns the foldable regions of the buffer, ordered by their first line.
+    pub fn folds(&self, buffer: &Buffer) -> Vec<Fold> {
+        let (query, tree) = match (&self.folds_query, self.buffer_tree(buffer)) {
+            (Some(query), Some(tree)) => (query, tree),
+            _ => return vec![],
+        };
+
+        let mut cursor = QueryCursor::new();
+
+        let mut folds = cursor
+            .captures(query, tree.root_node(), |node| node_text(buffer, node))
+            .filter_map(|(m, capture_index)| Fold::from_node(m.captures[capture_index].node))
+            .collect();
+
+        fold::normalize(&mut folds);
+        folds
+    }
 
-                if let Some(color) = color {
-                    highlight_range(screen, viewport, range, color);
+    /// Returns the start positions of the functions and classes in the buffer, in order.
+    ///
+    /// Definitions nested within a function, such as closures, are skipped. A definition that
+    /// immediately wraps another, such as a decorated Python function, is only included once.
+    pub fn definitions(&self, buffer: &Buffer) -> Vec<Position> {
+        let mut function_end = 0;
+        let mut positions = vec![];
+
+        for object in self.text_objects(buffer) {
+            if object.start_byte < function_end || object.wrapped {
+                continue;
+            }
+
+            if object.kind == TextObjectKind::Function {
+                function_end = object.end_byte;
+            }
+
+            positions.push(Position::new(
+                object.start_position.column,
+                object.start_position.row,
+            ));
+        }
+
+        positions
+    }
+
+    /// Returns the names of the functions and classes enclosing a position, from outermost to
+    /// innermost.
+    pub fn breadcrumbs(&self, buffer: &Buffer, position: Position) -> Vec<String> {
+        let point = Point::new(position.y, position.x);
+
+        self.text_objects(buffer)
+            .into_iter()
+            .filter(|object| {
+                !object.wrapped && object.start_position <= point && point < object.end_position
+            })
+            .filter_map(|object| object.name)
+            .collect()
+    }
+
+    /// Returns the named functions and classes in the buffer, in order.
+    pub fn symbols(&self, buffer: &Buffer) -> Vec<Symbol> {
+        // The end bytes of the symbols enclosing the current one.
+        let mut enclosing: Vec<usize> = vec![];
+        let mut symbols = vec![];
+
+        for object in self.text_objects(buffer) {
+            let name = match object.name {
+                Some(name) if !object.wrapped => name,
+                _ => continue,
+            };
+
+            while matches!(enclosing.last(), Some(&end) if end <= object.start_byte) {
+                enclosing.pop();
+            }
+
+            symbols.push(Symbol {
+                name,
+                position: Position::new(object.start_position.column, object.start_position.row),
+                depth: enclosing.len(),
+            });
+            enclosing.push(object.end_byte);
+        }
+
+        symbols
+    }
+
+    /// Returns the functions and classes captured by the textobjects query, ordered by their start,
+    /// with enclosing objects first. Nodes captured more than once are only included once.
+    fn text_objects(&self, buffer: &Buffer) -> Vec<TextObject> {
+        let (query, tree) = match (&self.textobjects_query, self.buffer_tree(buffer)) {
+            (Some(query), Some(tree)) => (query, tree),
+            _ => return vec![],
+        };
+
+        let kinds = textobject_kinds(query);
+        let mut cursor = QueryCursor::new();
+
+        let mut nodes = cursor
+            .captures(query, tree.root_node(), |node| node_text(buffer, node))
+            .filter_map(|(m, capture_index)| {
+                let capture = m.captures[capture_index];
+                Some((kinds[capture.index as usize]?, capture.node))
+            })
+            .collect::<Vec<_>>();
+
+        nodes.sort_by_key(|(_, node)| (node.start_byte(), cmp::Reverse(node.end_byte())));
+        nodes.dedup_by_key(|(_, node)| node.id());
+
+        let ids = nodes
+            .iter()
+            .map(|(_, node)| node.id())
+            .collect::<HashSet<_>>();
+
+        nodes
+            .into_iter()
+            .map(|(kind, node)| TextObject {
+                kind,
+                wrapped: matches!(node.parent(), Some(parent) if ids.contains(&parent.id())),
+                start_byte: node.start_byte(),
+                end_byte: node.end_byte(),
+                start_position: node.start_position(),
+                end_position: node.end_position(),
+                name: definition_name(buffer, node),
+            })
+            .collect()
+    }
+
+    /// Returns the syntax tree of the entire buffer.
+    ///
+    /// Large buffers are parsed in the background, so the tree may not reflect recent edits, or may
+    /// not be available at all until the first parse finishes.
+    fn buffer_tree(&self, buffer: &Buffer) -> Option<Tree> {
+        let mut state = self.parse_state.borrow_mut();
+        let mut cache = self.cache.borrow_mut();
+
+        if state.poll() {
+            cache.clear();
+        }
+
+        if state.needs_parse() {
+            if buffer.storage.len() < BACKGROUND_PARSE_THRESHOLD {
+                if let Some(tree) = self.parse(buffer, &[], state.tree.as_ref()) {
+                    match &state.tree {
+                        Some(old_tree) => {
+                            for range in old_tree.changed_ranges(&tree) {
+                                cache.invalidate(range.start_point.row..range.end_point.row + 1);
+                            }
+                        }
+                        None => cache.clear(),
+                    }
+
+                    state.set_tree(tree);
                 }
+            } else if state.pending.is_none() {
+                state.pending = Some(PendingParse::start(
+                    self.language,
+                    buffer.storage.to_string(),
+                    state.tree.clone(),
+                ));
             }
         }
 
-        debug!("finished highlighting");
+        state.tree.clone()
     }
-}
 
-/// Highlights a tree-sitter range on the screen.
-fn highlight_range(screen: &mut Screen, viewport: Span, range: Range, color: Color) {
-    debug!("highlighting range {:?}", range);
+    /// Parses the given ranges of the buffer on the current thread. If no ranges are given, the
+    /// entire buffer is parsed.
+    fn parse(&self, buffer: &Buffer, ranges: &[Range], old_tree: Option<&Tree>) -> Option<Tree> {
+        let mut parser = self.parser.borrow_mut();
+
+        if let Err(e) = parser.set_included_ranges(ranges) {
+            warn!("invalid highlight ranges: {:?}", e);
+            return None;
+        }
 
-    // Split the range into rectangular areas per-line.
-    for y in range.start_point.row..=cmp::min(range.end_point.row, viewport.max_y() - 1) {
-        let mut start_x = if y == range.start_point.row {
-            cmp::max(range.start_point.column, viewport.min_x())
-        } else {
-            0
+        parser.parse_with(
+            &mut |_, point| {
+                buffer
+                    .storage
+                    .slice_at(BytePosition::new(point.column, point.row))
+            },
+            old_tree,
+        )
+    }
+
+    /// Finds the definitions of local variables and the references to them, from the start of the
+    /// buffer to the given point.
+    ///
+    /// References are resolved to the innermost enclosing definition of the same name that
+    /// precedes them, following tree-sitter's `locals.scm` conventions.
+    fn resolve_locals(&self, buffer: &Buffer, tree: &Tree, end: Point) -> Locals {
+        let mut locals = Locals::default();
+
+        let query = match &self.locals_query {
+            Some(query) => query,
+            None => return locals,
         };
-        start_x = start_x.saturating_sub(viewport.min_x());
 
-        let mut end_x = if y == range.end_point.row {
-            cmp::min(range.end_point.column, viewport.max_x())
-        } else {
-            viewport.max_x()
+        let capture_index = |name: &str| {
+            query
+                .capture_names()
+                .iter()
+                .position(|capture_name| capture_name == name)
+        };
+
+        let scope_index = capture_index("local.scope");
+        let definition_index = capture_index("local.definition");
+        let reference_index = capture_index("local.reference");
+
+        // The outermost scope contains global definitions.
+        let mut scopes = vec![Scope {
+            end_byte: usize::MAX,
+            inherits: false,
+            definitions: HashMap::new(),
+        }];
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_point_range(Point::new(0, 0), end);
+
+        for (m, capture_index) in
+            cursor.captures(query, tree.root_node(), |node| node_text(buffer, node))
+        {
+            let capture = m.captures[capture_index];
+            let node = capture.node;
+            let index = Some(capture.index as usize);
+
+            while scopes.len() > 1 && scopes[scopes.len() - 1].end_byte <= node.start_byte() {
+                scopes.pop();
+            }
+
+            // Names of locals never span multiple lines.
+            let name = if node.start_position().row == node.end_position().row {
+                Some(node_text(buffer, node))
+            } else {
+                None
+            };
+
+            if index == scope_index {
+                let inherits = !query
+                    .property_settings(m.pattern_index)
+                    .iter()
+                    .any(|property| {
+                        &*property.key == "local.scope-inherits"
+                            && property.value.as_deref() == Some("false")
+                    });
+
+                scopes.push(Scope {
+                    end_byte: node.end_byte(),
+                    inherits,
+                    definitions: HashMap::new(),
+                });
+            } else if index == definition_index {
+                if let Some(name) = name {
+                    locals.definitions.insert(node.id());
+                    scopes
+                        .last_mut()
+                        .expect("missing outermost scope")
+                        .definitions
+                        .insert(name, Definition::from(node));
+                }
+            } else if index == reference_index && !locals.definitions.contains(&node.id()) {
+                if let Some(name) = name {
+                    for scope in scopes.iter().rev() {
+                        if let Some(&definition) = scope.definitions.get(name) {
+                            locals.references.insert(node.id(), definition);
+                            break;
+                        }
+
+                        if !scope.inherits {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        locals
+    }
+
+    /// Returns the style of the definition of a local variable, which may be outside of the
+    /// viewport.
+    fn definition_style(
+        &self,
+        buffer: &Buffer,
+        tree: &Tree,
+        locals: &Locals,
+        definition: Definition,
+    ) -> Option<Style> {
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(definition.start_byte, definition.end_byte);
+
+        cursor
+            .captures(&self.query, tree.root_node(), |node| {
+                node_text(buffer, node)
+            })
+            .map(|(m, capture_index)| (m.pattern_index, m.captures[capture_index]))
+            .filter(|(pattern_index, capture)| {
+                capture.node.id() == definition.id
+                    && locals.satisfies_predicates(&self.query, *pattern_index, capture.node)
+            })
+            .filter_map(|(_, capture)| self.theme.borrow().style_for(capture.index as usize))
+            .last()
+    }
+
+    /// Highlights regions of the buffer that contain another language, such as fenced code blocks
+    /// in Markdown, using that language's grammar.
+    ///
+    /// Injected regions are highlighted after the host language, so their colors take precedence.
+    /// Injected languages may contain injections themselves, up to a limited depth.
+    fn highlight_injections(
+        &self,
+        highlights: &mut Highlights,
+        buffer: &Buffer,
+        tree: &Tree,
+        depth: usize,
+    ) {
+        let query = match &self.injection_query {
+            Some(query) => query,
+            None => return,
         };
-        end_x = end_x.saturating_sub(viewport.min_x());
 
-        // If we're told to start highlighting at the edge of the viewport, bail out.
-        // TODO: Is this a bug in tree-sitter?
-        if start_x == end_x {
+        if depth >= MAX_INJECTION_DEPTH {
+            warn!("injections nested too deeply, not highlighting");
             return;
         }
 
-        let start_x = u16::try_from(start_x).expect("attempted to draw outside screen bounds");
-        let end_x = u16::try_from(end_x).expect("attempted to draw outside screen bounds");
-        let y = u16::try_from(y.saturating_sub(viewport.min_y()))
-            .expect("viewport outside screen bounds");
-        let highlight_bounds = Bounds::new(
-            Coordinates::new(start_x as u16, y),
-            Coordinates::new(end_x as u16, y + 1),
-        );
+        // Older grammars use `@language` and `@content` instead of the `injection.` prefix.
+        let capture_index = |name: &str| {
+            query.capture_names().iter().position(|capture_name| {
+                capture_name == name || capture_name.trim_start_matches("injection.") == name
+            })
+        };
 
-        screen.apply_color(highlight_bounds, color);
+        let language_index = capture_index("language");
+        let content_index = match capture_index("content") {
+            Some(content_index) => content_index,
+            None => return,
+        };
+
+        let mut cursor = QueryCursor::new();
+        let (start, end) = highlights.points();
+        cursor.set_point_range(start, end);
+
+        // Injections are parsed separately, unless the pattern specifies that all of its matches
+        // should be combined into a single document.
+        let mut combined: HashMap<(usize, Syntax), Vec<Range>> = HashMap::new();
+        let mut separate = vec![];
+
+        for m in cursor.matches(query, tree.root_node(), |node| node_text(buffer, node)) {
+            let capture_node = |index| {
+                m.captures
+                    .iter()
+                    .find(|capture| capture.index as usize == index)
+                    .map(|capture| capture.node)
+            };
+
+            let content = match capture_node(content_index) {
+                Some(content) => content,
+                None => continue,
+            };
+
+            let properties = query.property_settings(m.pattern_index);
+            let property = |key: &str| properties.iter().find(|property| &*property.key == key);
+
+            // The language may be captured from the buffer text, or fixed by the pattern.
+            let language = language_index
+                .and_then(capture_node)
+                .map(|node| node_text(buffer, node))
+                .or_else(|| property("injection.language").and_then(|p| p.value.as_deref()));
+
+            let language = match language {
+                Some(language) => language,
+                None => continue,
+            };
+
+            let syntax = match Syntax::from_name(language) {
+                Some(syntax) => syntax,
+                None => {
+                    debug!("no grammar for injected language {:?}", language);
+                    continue;
+                }
+            };
+
+            let ranges = content_ranges(content, property("injection.include-children").is_some());
+
+            if property("injection.combined").is_some() {
+                combined
+                    .entry((m.pattern_index, syntax))
+                    .or_default()
+                    .extend(ranges);
+            } else {
+                separate.push((syntax, ranges));
+            }
+        }
+
+        let injections = combined
+            .into_iter()
+            .map(|((_, syntax), ranges)| (syntax, ranges))
+            .chain(separate);
+
+        let mut injected = self.injected.borrow_mut();
+
+        for (syntax, mut ranges) in injections {
+            // Included ranges must be ordered and may not overlap.
+            ranges.sort_by_key(|range| range.start_byte);
+            ranges.dedup_by(|range, prev| range.start_byte < prev.end_byte);
+
+            if ranges.is_empty() {
+                continue;
+            }
+
+            let highlighter =
+                injected
+                    .entry(syntax)
+                    .or_insert_with(|| match Highlighter::new(syntax) {
+                        Ok(highlighter) => Some(highlighter),
+                        Err(e) => {
+                            error!("unable to load injected grammar for {:?}: {}", syntax, e);
+                            None
+                        }
+                    });
+
+            if let Some(highlighter) = highlighter {
+                highlighter.highlight_ranges(highlights, buffer, &ranges, depth + 1);
+            }
+        }
     }
 }
 
-fn tree_sitter_highlight_config(language: Syntax) -> (tree_sitter::Language, Query) {
-    use Syntax::*;
+/// The kinds of objects captured by a textobjects query, such as `@function.around`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TextObjectKind {
+    Function,
+    Class,
+}
+
+/// A named function or class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+
+    /// The start of the definition.
+    pub position: Position,
+
+    /// The number of symbols that enclose this one.
+    pub depth: usize,
+}
+
+/// A function or class captured by a textobjects query.
+struct TextObject {
+    kind: TextObjectKind,
+
+    /// Whether the object's parent is also a text object, such as the function of a decorated
+    /// Python function.
+    wrapped: bool,
+
+    start_byte: usize,
+    end_byte: usize,
+    start_position: Point,
+    end_position: Point,
+    name: Option<String>,
+}
+
+/// Local variables found by a highlighter's locals query.
+#[derive(Default)]
+struct Locals {
+    /// IDs of nodes that define local variables.
+    definitions: HashSet<usize>,
+
+    /// Map of the IDs of nodes that reference local variables to their definitions.
+    references: HashMap<usize, Definition>,
+}
+
+impl Locals {
+    fn is_local(&self, node: Node<'_>) -> bool {
+        self.definitions.contains(&node.id()) || self.references.contains_key(&node.id())
+    }
 
-    match language {
-        JavaScript => tree_sitter_languages::javascript(),
-        Rust => tree_sitter_languages::rust(),
+    /// Returns whether a node satisfies any `#is? local` or `#is-not? local` predicates of a
+    /// highlight pattern.
+    fn satisfies_predicates(&self, query: &Query, pattern_index: usize, node: Node<'_>) -> bool {
+        query
+            .property_predicates(pattern_index)
+            .iter()
+            .filter(|(property, _)| &*property.key == "local")
+            .all(|(_, is_positive)| self.is_local(node) == *is_positive)
     }
 }
 
-fn span_to_points(span: Span) -> (Point, Point) {
-    (
-        Point::new(span.min_y(), span.min_x()),
-        Point::new(span.max_y() - 1, span.max_x()),
-    )
+/// A lexical scope that local variables may be defined in.
+struct Scope<'a> {
+    end_byte: usize,
+
+    /// Whether references may resolve to definitions in enclosing scopes.
+    inherits: bool,
+
+    definitions: HashMap<&'a str, Definition>,
 }
 
-#[cfg(test)]
-mod tests {
-    use euclid::{rect, size2};
-    use indoc::indoc;
-    use tree_sitter::Point;
+/// The node that defines a local variable.
+#[derive(Debug, Clone, Copy)]
+struct Definition {
+    id: usize,
+    start_byte: usize,
+    end_byte: usize,
+}
 
-    use crate::buffer::{Buffer, Span};
-    use crate::ui::{Bounds, Color, Context, Drawable, Screen, Size};
+impl From<Node<'_>> for Definition {
+    fn from(node: Node<'_>) -> Self {
+        Definition {
+            id: node.id(),
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+        }
+    }
+}
 
-    use super::{span_to_points, Syntax, Theme};
+/// Maps the capture indices of a textobjects query to the kind of object that they capture.
+fn textobject_kinds(query: &Query) -> Vec<Option<TextObjectKind>> {
+    query
+        .capture_names()
+        .iter()
+        .map(|name| match name.as_str() {
+            "function.around" => Some(TextObjectKind::Function),
+            "class.around" => Some(TextObjectKind::Class),
+            _ => None,
+        })
+        .collect()
+}
 
-    #[test]
-    fn points_from_span() {
-        let span = Span::from_size(size2(2, 1));
-        let (min, max) = span_to_points(span);
+/// Returns the name of a function or class definition.
+///
+/// Grammars differ in where the name is found. It may be a field of the definition itself, nested
+/// within a declarator (as in C), or within a child node (as in JavaScript variable declarations).
+fn definition_name(buffer: &Buffer, node: Node) -> Option<String> {
+    const NAME_FIELDS: &[&str] = &["name", "declarator", "type", "definition"];
+
+    let mut node = node;
+
+    // Limit the search in case of unexpected syntax.
+    for _ in 0..4 {
+        let child = NAME_FIELDS
+            .iter()
+            .find_map(|field| node.child_by_field_name(field))
+            .or_else(|| node.named_child(0))?;
+
+        if child.named_child_count() == 0 {
+            if child.start_position().row != child.end_position().row {
+                return None;
+            }
+
+            return Some(node_text(buffer, child).to_owned()).filter(|name| !name.is_empty());
+        }
+
+        node = child;
+    }
+
+    None
+}
+
+/// Returns the text of a node that fits on a single line.
+fn node_text<'a>(buffer: &'a Buffer, node: Node<'_>) -> &'a str {
+    let Range {
+        start_point,
+        end_point,
+        ..
+    } = node.range();
+    let start = BytePosition::new(start_point.column, start_point.row);
+    let end = BytePosition::new(end_point.column, end_point.row);
+    &buffer.storage[start..end]
+}
+
+/// Returns the ranges of an injected node that should be parsed as the injected language.
+///
+/// Unless `include_children` is set, the ranges of the node's named children are excluded, since
+/// they are usually part of the host language (e.g., interpolations within a template string).
+fn content_ranges(node: Node<'_>, include_children: bool) -> Vec<Range> {
+    let mut range = node.range();
 
-        assert_eq!(min, Point::new(0, 0));
-        assert_eq!(max, Point::new(0, 2));
+    if include_children {
+        return vec![range];
     }
 
+    let mut ranges = vec![];
+
+    for child in (0..node.named_child_count()).filter_map(|i| node.named_child(i)) {
+        if child.start_byte() > range.start_byte {
+            ranges.push(Range {
+                start_byte: range.start_byte,
+                start_point: range.start_point,
+                end_byte: child.start_byte(),
+                end_point: child.start_position(),
+            });
+        }
+
+        range.start_byte = child.end_byte();
+        range.start_point = child.end_position();
+    }
+
+    if range.end_byte > range.start_byte {
+        ranges.push(range);
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "lang-javascript", feature = "lang-rust"))]
+    use euclid::{rect, size2};
+    #[cfg(feature = "lang-rust")]
+    use futures::executor;
+    #[cfg(any(feature = "lang-javascript", feature = "lang-rust"))]
+    use indoc::indoc;
+    #[cfg(any(feature = "lang-javascript", feature = "lang-rust"))]
+    use tree_sitter::Parser;
+
+    use crate::theme;
+    use crate::ui::{Color, Style};
+    #[cfg(any(
+        feature = "lang-javascript",
+        feature = "lang-rust",
+        all(
+            feature = "lang-bash",
+            feature = "lang-c",
+            feature = "lang-cpp",
+            feature = "lang-go",
+            feature = "lang-json",
+            feature = "lang-python",
+            feature = "lang-toml",
+            feature = "lang-typescript"
+        )
+    ))]
+    use crate::{
+        buffer::{Buffer, Span},
+        syntax::Syntax,
+        ui::{Bounds, Context, Drawable, Screen, Size},
+    };
+
+    #[cfg(feature = "lang-javascript")]
+    use super::content_ranges;
+    use super::Theme;
+    #[cfg(feature = "lang-rust")]
+    use super::{Grammar, BACKGROUND_PARSE_THRESHOLD, LONG_LINE_LENGTH};
+
     // TODO: it might be better to just unit test highlight_range directly...
 
+    #[cfg(feature = "lang-rust")]
     #[test]
     fn highlight_large_buffer() {
         let mut buffer = Buffer::from(indoc! {r#"
@@ -304,6 +1057,7 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(feature = "lang-rust")]
     #[test]
     fn highlight_left_viewport_edge() {
         let mut buffer = Buffer::from(indoc! {"
@@ -324,6 +1078,7 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(feature = "lang-javascript")]
     #[test]
     fn highlight_right_viewport_edge() {
         let mut buffer = Buffer::from(r"parse('\n');");
@@ -342,6 +1097,7 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(feature = "lang-rust")]
     #[test]
     fn highlight_at_edge_of_screen() {
         let mut buffer = Buffer::from("impl Debug for Foo {}");
@@ -360,6 +1116,7 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(feature = "lang-javascript")]
     #[test]
     fn highlight_through_edge_of_screen() {
         let mut buffer = Buffer::from("'long string literal'");
@@ -378,6 +1135,7 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(feature = "lang-javascript")]
     #[test]
     fn highlight_multiline_comment() {
         let mut buffer = Buffer::from(indoc! {r#"
@@ -416,6 +1174,7 @@ mod tests {
         assert_eq!(ctx.screen[(0, 3)].c, Some('I'));
     }
 
+    #[cfg(feature = "lang-javascript")]
     #[test]
     fn highlight_shifted_viewport() {
         let mut buffer = Buffer::from(indoc! {"
@@ -436,6 +1195,7 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(feature = "lang-javascript")]
     #[test]
     fn highlight_through_viewport_bottom() {
         let mut buffer = Buffer::from(indoc! {"
@@ -459,14 +1219,380 @@ mod tests {
         buffer.draw(&mut ctx);
     }
 
+    #[cfg(all(
+        feature = "lang-bash",
+        feature = "lang-c",
+        feature = "lang-cpp",
+        feature = "lang-go",
+        feature = "lang-json",
+        feature = "lang-python",
+        feature = "lang-toml",
+        feature = "lang-typescript"
+    ))]
+    #[test]
+    fn highlight_bundled_languages() {
+        let cases = [
+            (Syntax::Bash, "echo \"$HOME\""),
+            (Syntax::C, "int main(void) { return 0; }"),
+            (Syntax::Cpp, "int main() { return 0; }"),
+            (Syntax::Go, "func main() { return }"),
+            (Syntax::Python, "def main(): return"),
+            (Syntax::TypeScript, "let x: number = 1;"),
+            (Syntax::Tsx, "let x = <div />;"),
+            (Syntax::Toml, "a = 1"),
+            (Syntax::Json, "{\"a\": 1}"),
+        ];
+
+        for &(syntax, text) in &cases {
+            let mut buffer = Buffer::from(text);
+            buffer.set_syntax(Some(syntax));
+
+            let size = Size::new(30, 1);
+            buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+            let mut screen = Screen::new(size);
+
+            let mut ctx = Context {
+                bounds: Bounds::from_size(screen.size),
+                screen: &mut screen,
+            };
+
+            buffer.draw(&mut ctx);
+
+            assert!(
+                ctx.screen[(0, 0)].color.is_some() || ctx.screen[(0, 4)].color.is_some(),
+                "{:?} was not highlighted",
+                syntax
+            );
+        }
+    }
+
+    #[cfg(all(feature = "lang-markdown", feature = "lang-rust"))]
+    #[test]
+    fn highlight_markdown_code_block() {
+        let mut buffer = Buffer::from(indoc! {"
+            # Example
+
+            ```rust
+            fn main() {}
+            ```
+        "});
+
+        let size = Size::new(20, 5);
+
+        buffer.set_syntax(Some(Syntax::Markdown));
+        buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        assert_eq!(ctx.screen[(3, 0)].c, Some('f'));
+        assert_eq!(ctx.screen[(3, 0)].color, Some(Color::new(0xff, 0xff, 0x00)));
+        assert_eq!(ctx.screen[(3, 3)].color, Some(Color::new(0xff, 0x87, 0x00)));
+    }
+
+    #[cfg(all(
+        feature = "lang-html",
+        feature = "lang-javascript",
+        feature = "lang-css"
+    ))]
+    #[test]
+    fn highlight_html_script_and_style() {
+        let mut buffer = Buffer::from(indoc! {"
+            <script>
+            let x;
+            </script>
+            <style>
+            a { width: 10px; }
+            </style>
+        "});
+
+        let size = Size::new(20, 6);
+
+        buffer.set_syntax(Some(Syntax::Html));
+        buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        // `let` is highlighted as a JavaScript keyword.
+        assert_eq!(ctx.screen[(1, 0)].c, Some('l'));
+        assert_eq!(ctx.screen[(1, 0)].color, Some(Color::new(0xff, 0xff, 0x00)));
+
+        // `10` is highlighted as a CSS number.
+        assert_eq!(ctx.screen[(4, 11)].c, Some('1'));
+        assert_eq!(
+            ctx.screen[(4, 11)].color,
+            Some(Color::new(0x00, 0x87, 0x87))
+        );
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn highlight_rust_macro_injection() {
+        let mut buffer = Buffer::from(indoc! {"
+            m! { fn f() {} }
+        "});
+
+        let size = Size::new(20, 1);
+
+        buffer.set_syntax(Some(Syntax::Rust));
+        buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        // The macro body is parsed as Rust, so `fn` is highlighted as a keyword.
+        assert_eq!(ctx.screen[(0, 5)].c, Some('f'));
+        assert_eq!(ctx.screen[(0, 5)].color, Some(Color::new(0xff, 0xff, 0x00)));
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn highlight_long_line() {
+        let line = format!(
+            "static S: &str = \"{}\"; fn g() {{}}",
+            "x".repeat(LONG_LINE_LENGTH)
+        );
+        let mut buffer = Buffer::from(format!("fn f() {{}}\n{}\n", line).as_str());
+
+        let size = Size::new(10, 2);
+
+        buffer.set_syntax(Some(Syntax::Rust));
+        buffer.viewport = Some(rect(line.find("fn g").unwrap() - 3, 0, 10, 2));
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        // Only the visible part of the long line is highlighted.
+        assert_eq!(ctx.screen[(1, 3)].c, Some('f'));
+        assert_eq!(ctx.screen[(1, 3)].color, Some(Color::new(0xff, 0xff, 0x00)));
+
+        let highlighter = buffer.highlighter.as_ref().unwrap();
+        assert!(highlighter.cache.borrow().get(0).is_some());
+        assert!(highlighter.cache.borrow().get(1).is_none());
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn reuse_tree_after_edit() {
+        let mut buffer = Buffer::from("fn main() {}\n");
+
+        let size = Size::new(12, 1);
+
+        buffer.set_syntax(Some(Syntax::Rust));
+        buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        let highlighter = buffer.highlighter.as_ref().unwrap();
+        assert!(highlighter.parse_state.borrow().tree.is_some());
+
+        // `fn` is no longer a keyword once it becomes part of an identifier.
+        buffer.insert('x');
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        assert_eq!(ctx.screen[(0, 1)].c, Some('f'));
+        assert_ne!(ctx.screen[(0, 1)].color, Some(Color::new(0xff, 0xff, 0x00)));
+
+        // The edited tree must match a fresh parse of the new text.
+        let mut parser = Parser::new();
+        parser
+            .set_language(Grammar::load(Syntax::Rust).unwrap().language)
+            .unwrap();
+        let expected = parser.parse("xfn main() {}\n", None).unwrap();
+
+        let state = buffer.highlighter.as_ref().unwrap().parse_state.borrow();
+        assert_eq!(
+            state.tree.as_ref().unwrap().root_node().to_sexp(),
+            expected.root_node().to_sexp()
+        );
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn parse_large_buffer_in_background() {
+        let text = "fn f() {}\n".repeat(BACKGROUND_PARSE_THRESHOLD / 10 + 1);
+        let mut buffer = Buffer::from(text.as_str());
+
+        let size = Size::new(10, 1);
+
+        buffer.set_syntax(Some(Syntax::Rust));
+        buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+        let keyword = Some(Color::new(0xff, 0xff, 0x00));
+
+        let mut screen = Screen::new(size);
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+        buffer.draw(&mut ctx);
+
+        assert!(buffer
+            .highlighter
+            .as_ref()
+            .unwrap()
+            .parse_state
+            .borrow()
+            .pending
+            .is_some());
+
+        executor::block_on(buffer.highlighter.as_mut().unwrap().parsed());
+
+        let mut screen = Screen::new(size);
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+        buffer.draw(&mut ctx);
+
+        assert_eq!(ctx.screen[(0, 0)].c, Some('f'));
+        assert_eq!(ctx.screen[(0, 0)].color, keyword);
+
+        // Edits are reflected in the old tree until the buffer is parsed again.
+        buffer.insert(' ');
+
+        let mut screen = Screen::new(size);
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+        buffer.draw(&mut ctx);
+
+        assert_eq!(ctx.screen[(0, 1)].c, Some('f'));
+        assert_eq!(ctx.screen[(0, 1)].color, keyword);
+    }
+
+    #[cfg(feature = "lang-javascript")]
+    #[test]
+    fn highlight_local_references() {
+        let mut buffer = Buffer::from(indoc! {"
+            function f(require) {
+              return require;
+            }
+            require;
+        "});
+
+        let size = Size::new(20, 3);
+
+        buffer.set_syntax(Some(Syntax::JavaScript));
+        buffer.viewport = Some(Span::from_size(size.cast().cast_unit()));
+
+        let mut screen = Screen::new(size);
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        let parameter = Some(Color::new(0xd7, 0x87, 0xff));
+
+        // The parameter shadows the builtin, so references to it are colored like the parameter.
+        assert_eq!(ctx.screen[(0, 11)].c, Some('r'));
+        assert_eq!(ctx.screen[(0, 11)].color, parameter);
+        assert_eq!(ctx.screen[(1, 9)].c, Some('r'));
+        assert_eq!(ctx.screen[(1, 9)].color, parameter);
+
+        // Definitions are resolved even if they are scrolled out of view.
+        buffer.viewport = Some(rect(0, 1, 20, 3));
+
+        let mut ctx = Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        };
+
+        buffer.draw(&mut ctx);
+
+        assert_eq!(ctx.screen[(0, 9)].c, Some('r'));
+        assert_eq!(ctx.screen[(0, 9)].color, parameter);
+
+        // Outside of the function, `require` is the builtin.
+        assert_eq!(ctx.screen[(2, 0)].c, Some('r'));
+        assert_eq!(ctx.screen[(2, 0)].color, Some(Color::new(0xff, 0x87, 0x00)));
+    }
+
+    #[cfg(feature = "lang-javascript")]
+    #[test]
+    fn injection_excludes_children() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(tree_sitter_languages::javascript())
+            .unwrap();
+
+        let tree = parser.parse("`a${b}c`", None).unwrap();
+        let template = tree
+            .root_node()
+            .named_descendant_for_byte_range(0, 8)
+            .unwrap();
+        assert_eq!(template.kind(), "template_string");
+
+        let ranges = content_ranges(template, false);
+        let bytes = ranges
+            .iter()
+            .map(|range| (range.start_byte, range.end_byte))
+            .collect::<Vec<_>>();
+        assert_eq!(bytes, vec![(0, 2), (6, 8)]);
+
+        let ranges = content_ranges(template, true);
+        assert_eq!(ranges, vec![template.range()]);
+    }
+
     #[test]
     fn theme_capture_name_fallback() {
-        let theme = Theme::new(&[
+        let capture_names = [
             String::from("function"),
             String::from("function.method"),
             String::from("function.builtin.static"),
-        ]);
-        assert_eq!(theme.color_for(1), Some(Color::new(0xff, 0x87, 0x00)));
-        assert_eq!(theme.color_for(2), Some(Color::new(0xff, 0x87, 0x00)));
+        ];
+        let theme = Theme::new(
+            &capture_names,
+            theme::color_scheme(theme::DEFAULT_COLOR_SCHEME).unwrap(),
+        );
+
+        let function = Some(Style::fg(Color::new(0xff, 0x87, 0x00)));
+        assert_eq!(theme.style_for(1), function);
+        assert_eq!(theme.style_for(2), function);
     }
 }
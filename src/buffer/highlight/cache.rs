@@ -0,0 +1,292 @@
+//! Caching highlights per line.
+//!
+//! Running queries is the most expensive part of highlighting, so the styles of each line are
+//! remembered until the line is affected by an edit. Scrolling through a buffer without editing it
+//! only runs queries for lines that haven't been seen yet.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tree_sitter::{Point, Range as TreeRange};
+
+use crate::ui::Style;
+
+/// A styled span of byte columns within a line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineHighlight {
+    pub start: usize,
+
+    /// The end column, exclusive. May extend past the end of the line.
+    pub end: usize,
+
+    pub style: Style,
+}
+
+/// Highlights computed for a range of lines.
+pub struct Highlights {
+    rows: Range<usize>,
+
+    /// The columns that highlights are being computed for. Highlights outside of these columns are
+    /// discarded.
+    columns: Range<usize>,
+
+    lines: Vec<Vec<LineHighlight>>,
+}
+
+impl Highlights {
+    pub fn new(rows: Range<usize>) -> Self {
+        Highlights {
+            lines: vec![vec![]; rows.len()],
+            rows,
+            columns: 0..usize::MAX,
+        }
+    }
+
+    /// Creates highlights for some of the columns of a single line.
+    ///
+    /// This is used for lines that are too long to highlight entirely.
+    pub fn window(row: usize, columns: Range<usize>) -> Self {
+        Highlights {
+            rows: row..row + 1,
+            columns,
+            lines: vec![vec![]],
+        }
+    }
+
+    /// Returns the points delimiting the text that highlights are being computed for.
+    pub fn points(&self) -> (Point, Point) {
+        if self.columns.end == usize::MAX {
+            (Point::new(self.rows.start, 0), Point::new(self.rows.end, 0))
+        } else {
+            (
+                Point::new(self.rows.start, self.columns.start),
+                Point::new(self.rows.end - 1, self.columns.end),
+            )
+        }
+    }
+
+    /// Returns the highlights of the first line.
+    pub fn into_first_line(self) -> Vec<LineHighlight> {
+        self.lines.into_iter().next().unwrap_or_default()
+    }
+
+    /// Styles a range of the buffer, splitting it into highlights for each line that it covers.
+    ///
+    /// Later highlights take precedence over earlier ones.
+    pub fn add(&mut self, range: TreeRange, style: Style) {
+        let start_row = cmp::max(range.start_point.row, self.rows.start);
+        let end_row = cmp::min(range.end_point.row + 1, self.rows.end);
+
+        for row in start_row..end_row {
+            let start = if row == range.start_point.row {
+                range.start_point.column
+            } else {
+                0
+            };
+
+            let end = if row == range.end_point.row {
+                range.end_point.column
+            } else {
+                usize::MAX
+            };
+
+            let start = cmp::max(start, self.columns.start);
+            let end = cmp::min(end, self.columns.end);
+
+            if start < end {
+                self.lines[row - self.rows.start].push(LineHighlight { start, end, style });
+            }
+        }
+    }
+}
+
+/// Highlights of each line of a buffer, for lines that have been highlighted since they were last
+/// edited.
+#[derive(Debug, Default)]
+pub struct HighlightCache {
+    lines: HashMap<usize, Vec<LineHighlight>>,
+}
+
+impl HighlightCache {
+    /// Returns the highlights of a line, if they have been computed.
+    pub fn get(&self, row: usize) -> Option<&[LineHighlight]> {
+        self.lines.get(&row).map(Vec::as_slice)
+    }
+
+    /// Returns the smallest range of lines that covers every line in `rows` that is not cached.
+    pub fn missing_rows(&self, rows: Range<usize>) -> Option<Range<usize>> {
+        let mut missing = rows.filter(|row| !self.lines.contains_key(row));
+
+        let start = missing.next()?;
+        let end = missing.next_back().unwrap_or(start) + 1;
+
+        Some(start..end)
+    }
+
+    pub fn insert(&mut self, highlights: Highlights) {
+        for (row, line) in highlights.rows.zip(highlights.lines) {
+            self.lines.insert(row, line);
+        }
+    }
+
+    /// Updates the cache for an edit that replaced the lines from `start_row` to `old_end_row`
+    /// (inclusive) with the lines from `start_row` to `new_end_row`.
+    ///
+    /// The edited lines are discarded, and the lines after them are moved to their new positions.
+    pub fn edit(&mut self, start_row: usize, old_end_row: usize, new_end_row: usize) {
+        self.lines = self
+            .lines
+            .drain()
+            .filter_map(|(row, line)| {
+                if row < start_row {
+                    Some((row, line))
+                } else if row > old_end_row {
+                    Some((row - old_end_row + new_end_row, line))
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Discards the highlights of the given lines.
+    pub fn invalidate(&mut self, rows: Range<usize>) {
+        self.lines.retain(|row, _| !rows.contains(row));
+    }
+
+    /// Discards the highlights of all lines starting at `row`.
+    pub fn invalidate_from(&mut self, row: usize) {
+        self.lines.retain(|&cached_row, _| cached_row < row);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::{Point, Range};
+
+    use crate::ui::{Color, Style};
+
+    use super::{HighlightCache, Highlights, LineHighlight};
+
+    const RED: Style = Style::fg(Color::new(0xff, 0x00, 0x00));
+
+    fn cached(rows: std::ops::Range<usize>) -> HighlightCache {
+        let mut cache = HighlightCache::default();
+        cache.insert(Highlights::new(rows));
+        cache
+    }
+
+    #[test]
+    fn points_from_rows() {
+        let (min, max) = Highlights::new(2..4).points();
+
+        assert_eq!(min, Point::new(2, 0));
+        assert_eq!(max, Point::new(4, 0));
+    }
+
+    #[test]
+    fn window() {
+        let mut highlights = Highlights::window(1, 10..20);
+        assert_eq!(highlights.points(), (Point::new(1, 10), Point::new(1, 20)));
+
+        highlights.add(
+            Range {
+                start_byte: 0,
+                end_byte: 40,
+                start_point: Point::new(0, 5),
+                end_point: Point::new(1, 15),
+            },
+            RED,
+        );
+
+        assert_eq!(
+            highlights.into_first_line(),
+            vec![LineHighlight {
+                start: 10,
+                end: 15,
+                style: RED
+            }]
+        );
+    }
+
+    #[test]
+    fn split_multiline_range() {
+        let mut highlights = Highlights::new(1..3);
+        highlights.add(
+            Range {
+                start_byte: 2,
+                end_byte: 12,
+                start_point: Point::new(0, 2),
+                end_point: Point::new(2, 3),
+            },
+            RED,
+        );
+
+        assert_eq!(
+            highlights.lines,
+            vec![
+                vec![LineHighlight {
+                    start: 0,
+                    end: usize::MAX,
+                    style: RED
+                }],
+                vec![LineHighlight {
+                    start: 0,
+                    end: 3,
+                    style: RED
+                }],
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_rows() {
+        let mut cache = cached(2..4);
+        cache.insert(Highlights::new(6..7));
+
+        assert_eq!(cache.missing_rows(2..4), None);
+        assert_eq!(cache.missing_rows(0..3), Some(0..2));
+        assert_eq!(cache.missing_rows(3..8), Some(4..8));
+        assert_eq!(cache.missing_rows(3..5), Some(4..5));
+    }
+
+    #[test]
+    fn edit_shifts_following_lines() {
+        let mut cache = cached(0..10);
+
+        // Replace lines 2-3 with 2-5.
+        cache.edit(2, 3, 5);
+
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(5).is_none());
+        assert!(cache.get(6).is_some());
+        assert!(cache.get(11).is_some());
+        assert!(cache.get(12).is_none());
+
+        // Join lines 6 and 7.
+        cache.edit(6, 7, 6);
+
+        assert!(cache.get(5).is_none());
+        assert!(cache.get(6).is_none());
+        assert!(cache.get(7).is_some());
+        assert!(cache.get(10).is_some());
+        assert!(cache.get(11).is_none());
+    }
+
+    #[test]
+    fn invalidate() {
+        let mut cache = cached(0..10);
+
+        cache.invalidate(3..5);
+        assert_eq!(cache.missing_rows(0..10), Some(3..5));
+
+        cache.invalidate_from(8);
+        assert_eq!(cache.missing_rows(0..10), Some(3..10));
+    }
+}
@@ -0,0 +1,206 @@
+//! Parsing entire buffers, in the background if they are large.
+//!
+//! Parsing a multi-megabyte buffer can take long enough to noticeably delay input. Instead of
+//! blocking the render path, large buffers are parsed on a separate thread. In the meantime, the
+//! previous tree is updated with any edits and used for highlighting, so highlights may be slightly
+//! out of date until the new tree arrives.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use futures::channel::oneshot;
+use log::*;
+use tree_sitter::{InputEdit, Language, Parser, Tree};
+
+/// Buffers at least this many bytes long are parsed in the background.
+pub const BACKGROUND_PARSE_THRESHOLD: usize = 512 * 1024;
+
+/// The maximum time that a background parse may take before it is abandoned.
+const PARSE_TIMEOUT_MICROS: u64 = 10_000_000;
+
+/// The state of parsing an entire buffer.
+#[derive(Default)]
+pub struct ParseState {
+    /// The most recent tree, updated with any edits made since it was parsed.
+    pub tree: Option<Tree>,
+
+    /// Whether the buffer has been edited since the tree was parsed.
+    pub stale: bool,
+
+    /// A parse running in the background, if any.
+    pub pending: Option<PendingParse>,
+}
+
+impl ParseState {
+    /// Returns whether the buffer needs to be parsed again.
+    pub fn needs_parse(&self) -> bool {
+        self.tree.is_none() || self.stale
+    }
+
+    /// Applies an edit to the current tree, and to the tree of the pending parse once it finishes.
+    pub fn edit(&mut self, edit: InputEdit) {
+        if let Some(tree) = &mut self.tree {
+            tree.edit(&edit);
+        }
+
+        if let Some(pending) = &mut self.pending {
+            pending.edits.push(edit);
+        }
+
+        self.stale = true;
+    }
+
+    /// Replaces the current tree with the result of a synchronous parse.
+    ///
+    /// Any parse in progress is canceled, since its result would be older.
+    pub fn set_tree(&mut self, tree: Tree) {
+        self.tree = Some(tree);
+        self.stale = false;
+        self.pending = None;
+    }
+
+    /// Checks whether the pending parse has finished without waiting for it.
+    ///
+    /// Returns `true` if the tree was replaced.
+    pub fn poll(&mut self) -> bool {
+        let result = match &mut self.pending {
+            Some(pending) => match pending.result.try_recv() {
+                Ok(Some(tree)) => tree,
+                Ok(None) => return false,
+                Err(oneshot::Canceled) => None,
+            },
+            None => return false,
+        };
+
+        self.finish(result)
+    }
+
+    /// Installs the result of the pending parse, updated with any edits made while it was running.
+    ///
+    /// Returns `true` if the tree was replaced.
+    pub fn finish(&mut self, tree: Option<Tree>) -> bool {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return false,
+        };
+
+        match tree {
+            Some(mut tree) => {
+                for edit in &pending.edits {
+                    tree.edit(edit);
+                }
+
+                self.tree = Some(tree);
+                self.stale = !pending.edits.is_empty();
+                true
+            }
+            None => {
+                warn!("background parse timed out or was canceled");
+                false
+            }
+        }
+    }
+}
+
+/// A parse running on a separate thread.
+///
+/// The parse is canceled when this is dropped.
+pub struct PendingParse {
+    /// Edits made to the buffer since the parse started.
+    edits: Vec<InputEdit>,
+
+    cancellation_flag: Arc<AtomicUsize>,
+
+    /// Receives the new tree, or `None` if parsing timed out or was canceled.
+    pub result: oneshot::Receiver<Option<Tree>>,
+}
+
+impl PendingParse {
+    /// Starts parsing a snapshot of the buffer text, reusing the old tree if there is one.
+    pub fn start(language: Language, text: String, old_tree: Option<Tree>) -> Self {
+        let (sender, result) = oneshot::channel();
+        let cancellation_flag = Arc::new(AtomicUsize::new(0));
+        let thread_cancellation_flag = Arc::clone(&cancellation_flag);
+
+        info!("parsing {} bytes in the background", text.len());
+
+        thread::spawn(move || {
+            let mut parser = Parser::new();
+            if let Err(e) = parser.set_language(language) {
+                error!("unable to parse in the background: {}", e);
+                return;
+            }
+
+            parser.set_timeout_micros(PARSE_TIMEOUT_MICROS);
+
+            // SAFETY: The flag outlives the parser, which is dropped at the end of this closure.
+            unsafe {
+                parser.set_cancellation_flag(Some(&thread_cancellation_flag));
+            }
+
+            let tree = parser.parse(&text, old_tree.as_ref());
+
+            // The highlighter may have been dropped in the meantime.
+            let _ = sender.send(tree);
+        });
+
+        PendingParse {
+            edits: vec![],
+            cancellation_flag,
+            result,
+        }
+    }
+}
+
+impl Drop for PendingParse {
+    fn drop(&mut self) {
+        self.cancellation_flag.store(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(test, feature = "lang-rust"))]
+mod tests {
+    use futures::executor;
+    use tree_sitter::{InputEdit, Point};
+
+    use crate::grammar::Grammar;
+    use crate::syntax::Syntax;
+
+    use super::{ParseState, PendingParse};
+
+    fn language() -> tree_sitter::Language {
+        Grammar::load(Syntax::Rust).unwrap().language
+    }
+
+    #[test]
+    fn finish_applies_edits() {
+        let mut state = ParseState::default();
+        state.pending = Some(PendingParse::start(
+            language(),
+            String::from("fn main() {}\n"),
+            None,
+        ));
+
+        // Insert "x" at the start of the buffer while the parse is running.
+        state.edit(InputEdit {
+            start_byte: 0,
+            old_end_byte: 0,
+            new_end_byte: 1,
+            start_position: Point::new(0, 0),
+            old_end_position: Point::new(0, 0),
+            new_end_position: Point::new(0, 1),
+        });
+        assert!(state.needs_parse());
+
+        let result = executor::block_on(&mut state.pending.as_mut().unwrap().result).unwrap();
+        assert!(state.finish(result));
+
+        assert!(state.pending.is_none());
+        assert!(state.stale);
+
+        let tree = state.tree.unwrap();
+        assert!(tree.root_node().has_changes());
+        assert_eq!(tree.root_node().end_byte(), 14);
+    }
+}
@@ -0,0 +1,193 @@
+//! Detecting how a file is indented, so that new lines are indented the same way.
+//!
+//! When a file is opened, the leading whitespace of its lines decides whether it's indented with
+//! tabs or spaces. For spaces, the width of an indentation level is the increase in indentation
+//! between consecutive lines that's most common. The detected style overrides `expandtab` and
+//! `tabstop`.
+
+use std::fmt::{self, Display};
+
+use crate::options::BufferOptions;
+
+use super::Buffer;
+
+/// The number of lines at the start of a file that are examined.
+const MAX_LINES: usize = 1000;
+
+/// The widest indentation level that's detected. Larger increases are usually alignment.
+const MAX_WIDTH: usize = 8;
+
+/// How a file is indented.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Indent {
+    Tabs,
+
+    /// Each level is indented with the given number of spaces.
+    Spaces(usize),
+}
+
+impl Indent {
+    /// Detects the indentation of lines, or returns `None` if they aren't indented.
+    pub fn detect<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut tabs = 0;
+        let mut spaces = 0;
+
+        // How often each increase in spaces from one indented line to the next occurs.
+        let mut increases = [0; MAX_WIDTH + 1];
+        let mut previous = 0;
+
+        for line in lines.into_iter().take(MAX_LINES) {
+            let content = line.trim_start_matches(|c| c == ' ' || c == '\t');
+            if content.is_empty() {
+                continue;
+            }
+
+            let indent = &line[..line.len() - content.len()];
+            if indent.starts_with('\t') {
+                tabs += 1;
+                continue;
+            }
+
+            // Lines that mix tabs into their spaces don't say how wide a level is.
+            if indent.contains('\t') {
+                continue;
+            }
+
+            let width = indent.len();
+            if width > 0 {
+                spaces += 1;
+            }
+
+            // A single space is usually the continuation of a block comment, like ` * `.
+            if width > previous + 1 && width - previous <= MAX_WIDTH {
+                increases[width - previous] += 1;
+            }
+            previous = width;
+        }
+
+        if tabs == 0 && spaces == 0 {
+            return None;
+        }
+
+        if tabs >= spaces {
+            return Some(Indent::Tabs);
+        }
+
+        // Ties go to the narrower width.
+        let (width, count) = increases
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, count)| count)
+            .expect("increases should not be empty");
+
+        if *count == 0 {
+            return None;
+        }
+
+        Some(Indent::Spaces(width))
+    }
+
+    /// Overrides the options that decide how lines are indented.
+    pub fn apply(self, options: &mut BufferOptions) {
+        match self {
+            Indent::Tabs => options.expandtab = false,
+            Indent::Spaces(width) => {
+                options.expandtab = true;
+                options.tabstop = width;
+            }
+        }
+    }
+}
+
+impl Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indent::Tabs => f.write_str("tabs"),
+            Indent::Spaces(width) => write!(f, "spaces:{}", width),
+        }
+    }
+}
+
+impl Buffer {
+    /// Returns how the buffer's file was indented when it was opened, if it was indented.
+    pub fn indent(&self) -> Option<Indent> {
+        self.indent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::options::BufferOptions;
+
+    use super::Indent;
+
+    fn detect(text: &str) -> Option<Indent> {
+        Indent::detect(text.lines())
+    }
+
+    #[test]
+    fn detect_spaces() {
+        let text = indoc! {"
+            fn main() {
+                if true {
+                    println!();
+                }
+            }
+        "};
+        assert_eq!(detect(text), Some(Indent::Spaces(4)));
+
+        let text = indoc! {"
+            def f():
+              return [
+                      1,
+              ]
+        "};
+        assert_eq!(detect(text), Some(Indent::Spaces(2)));
+    }
+
+    #[test]
+    fn detect_tabs() {
+        assert_eq!(
+            detect("int main() {\n\tif (x) {\n\t\treturn 0;\n\t}\n}\n"),
+            Some(Indent::Tabs)
+        );
+    }
+
+    #[test]
+    fn ignore_comments_and_blank_lines() {
+        let text = indoc! {"
+            /*
+             * A comment.
+             */
+            int x;
+        "};
+        assert_eq!(detect(text), None);
+
+        assert_eq!(detect("a\n\n   \nb\n"), None);
+    }
+
+    #[test]
+    fn apply() {
+        let mut options = BufferOptions {
+            tabstop: 8,
+            expandtab: false,
+        };
+
+        Indent::Spaces(2).apply(&mut options);
+        assert_eq!(
+            options,
+            BufferOptions {
+                tabstop: 2,
+                expandtab: true,
+            }
+        );
+
+        Indent::Tabs.apply(&mut options);
+        assert!(!options.expandtab);
+        assert_eq!(Indent::Tabs.to_string(), "tabs");
+        assert_eq!(Indent::Spaces(2).to_string(), "spaces:2");
+    }
+}
@@ -0,0 +1,122 @@
+//! Line numbers, shown in a gutter to the left of the buffer when `number` or `relativenumber` is
+//! set.
+
+use std::cmp;
+
+use crate::theme;
+use crate::ui::{Context, Coordinates};
+
+use super::Buffer;
+
+/// The number of digits that the gutter always has room for, like Vim's `numberwidth`.
+const MIN_DIGITS: usize = 3;
+
+impl Buffer {
+    /// Returns the width of the gutter that shows line numbers, which is zero if they are hidden.
+    ///
+    /// The gutter is wide enough for the number of the last line, followed by a space.
+    pub fn gutter_width(&self) -> u16 {
+        if !self.window.number && !self.window.relativenumber {
+            return 0;
+        }
+
+        let digits = self.storage.lines().to_string().len();
+        (cmp::max(digits, MIN_DIGITS) + 1) as u16
+    }
+
+    /// Draws the numbers of the lines within the viewport.
+    ///
+    /// If `relativenumber` is set, each line shows its distance from the cursor's line. The
+    /// cursor's line shows its own number if `number` is also set, or zero otherwise.
+    pub fn draw_line_numbers(&self, ctx: &mut Context<'_>) {
+        let width = usize::from(ctx.bounds.width());
+        if width == 0 {
+            return;
+        }
+
+        let cursor = self.cursor.y();
+
+        for (row, display_line) in self.display_lines().iter().enumerate() {
+            // Only the first display line of a wrapped line is numbered.
+            if display_line.continued {
+                continue;
+            }
+
+            let line = display_line.line;
+            let number = if self.window.relativenumber && !(self.window.number && line == cursor) {
+                cmp::max(line, cursor) - cmp::min(line, cursor)
+            } else {
+                line + 1
+            };
+
+            ctx.screen.write(
+                Coordinates::new(ctx.bounds.min.x, ctx.bounds.min.y + row as u16),
+                &format!("{:>1$} ", number, width - 1),
+            );
+        }
+
+        if let Some(style) = theme::current().style("line-number") {
+            ctx.screen.apply_style(ctx.bounds, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::rect;
+    use indoc::indoc;
+
+    use crate::buffer::{Buffer, Cursor};
+    use crate::options::WindowOptions;
+    use crate::ui::{Bounds, Context, Screen, Size};
+
+    fn draw(buffer: &Buffer) -> Vec<String> {
+        let size = Size::new(buffer.gutter_width(), 3);
+        let mut screen = Screen::new(size);
+        buffer.draw_line_numbers(&mut Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        });
+
+        (0..size.height)
+            .map(|y| {
+                (0..size.width)
+                    .map(|x| screen[(y, x)].c.unwrap_or(' '))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn draw_line_numbers() {
+        let mut buffer = Buffer::from(indoc! {"
+            a
+            b
+            c
+            d
+        "});
+        buffer.viewport = Some(rect(0, 1, 1, 3));
+        buffer.cursor = Cursor::at(0, 2);
+        assert_eq!(buffer.gutter_width(), 0);
+
+        buffer.set_window_options(WindowOptions {
+            number: true,
+            ..WindowOptions::default()
+        });
+        assert_eq!(buffer.gutter_width(), 4);
+        assert_eq!(draw(&buffer), vec!["  2 ", "  3 ", "  4 "]);
+
+        buffer.set_window_options(WindowOptions {
+            relativenumber: true,
+            ..WindowOptions::default()
+        });
+        assert_eq!(draw(&buffer), vec!["  1 ", "  0 ", "  1 "]);
+
+        buffer.set_window_options(WindowOptions {
+            number: true,
+            relativenumber: true,
+            ..WindowOptions::default()
+        });
+        assert_eq!(draw(&buffer), vec!["  1 ", "  3 ", "  1 "]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Reading large files in the background.
+//!
+//! Only the first lines of a large file are read before its buffer is opened, so that it can be
+//! drawn right away. A task reads the rest of the file in chunks of lines, which are appended to
+//! the buffer as they arrive. The buffer can be moved around in and edited meanwhile, but it can't
+//! be written until the whole file has been read, since the file would be cut short.
+//!
+//! Large files are read a line at a time, rather than being decoded all at once, so that only one
+//! copy of them is held in memory. Encodings aren't detected; the file is assumed to be UTF-8.
+
+use std::path::PathBuf;
+
+use futures::channel::mpsc;
+use futures::future;
+use futures::{SinkExt, StreamExt};
+use log::*;
+use tokio::fs::File;
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+
+use super::{Buffer, FileFormat};
+
+/// The number of lines that are read before the buffer is opened, which is enough to fill the
+/// screen.
+const FIRST_LINES: usize = 1024;
+
+/// The number of lines that are read at a time after the buffer is opened.
+const CHUNK_LINES: usize = 64 * 1024;
+
+/// The rest of a large file, which is being read in the background.
+pub(super) struct Loader {
+    chunks: mpsc::Receiver<io::Result<Chunk>>,
+
+    /// The error that stopped the file from being read, if any.
+    error: Option<String>,
+}
+
+/// Lines read from a file.
+struct Chunk {
+    lines: Vec<String>,
+
+    /// Whether the last line ended with a newline.
+    eol: bool,
+
+    /// Whether the end of the file was reached.
+    done: bool,
+}
+
+impl Buffer {
+    /// Opens a large file, reading the rest of it in the background.
+    pub(super) async fn open_large(path: PathBuf) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(&path).await?);
+        let (first, file_format) = read_chunk(&mut reader, FIRST_LINES).await?;
+        info!(
+            "read the first {} lines of {}",
+            first.lines.len(),
+            path.display()
+        );
+
+        let loader = if first.done {
+            None
+        } else {
+            let (mut sender, chunks) = mpsc::channel(1);
+            tokio::spawn(async move {
+                loop {
+                    let chunk = read_chunk(&mut reader, CHUNK_LINES).await;
+                    let done = chunk.as_ref().map_or(true, |(chunk, _)| chunk.done);
+                    if sender.send(chunk.map(|(chunk, _)| chunk)).await.is_err() || done {
+                        return;
+                    }
+                }
+            });
+
+            Some(Loader {
+                chunks,
+                error: None,
+            })
+        };
+
+        Ok(Buffer {
+            storage: first.lines.into(),
+            path: Some(path),
+            file_format,
+            eol: first.eol,
+            large: true,
+            loader,
+            ..Buffer::new()
+        })
+    }
+
+    /// Returns an error if the buffer's file hasn't been read completely, so that writing the
+    /// buffer would cut the file short.
+    pub(super) fn check_loaded(&self) -> io::Result<()> {
+        match &self.loader {
+            Some(Loader {
+                error: Some(error), ..
+            }) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("the file couldn't be read completely: {}", error),
+            )),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the file is still being read",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for the next chunk of the buffer's file to be read, and appends it to the buffer.
+    ///
+    /// Never completes if the file isn't being read.
+    pub(super) async fn load_chunk(&mut self) {
+        let loader = match &mut self.loader {
+            Some(loader) if loader.error.is_none() => loader,
+            _ => return future::pending().await,
+        };
+
+        match loader.chunks.next().await {
+            Some(Ok(chunk)) => {
+                if !chunk.lines.is_empty() {
+                    self.storage.push_lines(chunk.lines);
+                    self.eol = chunk.eol;
+                }
+                if chunk.done {
+                    info!("finished reading {} lines", self.storage.lines());
+                    self.loader = None;
+                }
+            }
+            Some(Err(e)) => {
+                error!("unable to read the rest of the file: {}", e);
+                loader.error = Some(e.to_string());
+            }
+            None => loader.error = Some(String::from("the reading task stopped")),
+        }
+    }
+}
+
+/// Reads up to a number of lines from a file, returning them along with the file's line ending.
+///
+/// The line ending is detected from the first line that is read. If the file ends exactly after
+/// the last line of the chunk, the end is only noticed by the next chunk, which is empty.
+async fn read_chunk(
+    reader: &mut BufReader<File>,
+    max_lines: usize,
+) -> io::Result<(Chunk, FileFormat)> {
+    let mut chunk = Chunk {
+        lines: vec![],
+        eol: true,
+        done: false,
+    };
+    let mut file_format = FileFormat::Unix;
+    let mut line = vec![];
+
+    while chunk.lines.len() < max_lines {
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            chunk.done = true;
+            break;
+        }
+
+        chunk.eol = line.last() == Some(&b'\n');
+        if chunk.eol {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+
+                if chunk.lines.is_empty() {
+                    file_format = FileFormat::Dos;
+                }
+            }
+        }
+
+        chunk
+            .lines
+            .push(String::from_utf8_lossy(&line).into_owned());
+        line.clear();
+    }
+
+    Ok((chunk, file_format))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use crate::buffer::{Buffer, Buffers, FileFormat};
+    use crate::ui::{Bounds, Size};
+
+    use super::{read_chunk, FIRST_LINES};
+
+    #[tokio::test]
+    async fn read_large_file_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.log");
+
+        std::fs::write(&path, b"a\r\nb\xff\n\nc").unwrap();
+        let mut reader = tokio::io::BufReader::new(tokio::fs::File::open(&path).await.unwrap());
+        let (chunk, file_format) = read_chunk(&mut reader, 10).await.unwrap();
+        assert_eq!(chunk.lines, vec!["a", "b\u{fffd}", "", "c"]);
+        assert!(!chunk.eol);
+        assert!(chunk.done);
+        assert_eq!(file_format, FileFormat::Dos);
+
+        std::fs::write(&path, "a\nb\n").unwrap();
+        let mut reader = tokio::io::BufReader::new(tokio::fs::File::open(&path).await.unwrap());
+        let (chunk, file_format) = read_chunk(&mut reader, 1).await.unwrap();
+        assert_eq!(chunk.lines, vec!["a"]);
+        assert!(!chunk.done);
+        assert_eq!(file_format, FileFormat::Unix);
+
+        let (chunk, _) = read_chunk(&mut reader, 1).await.unwrap();
+        assert_eq!(chunk.lines, vec!["b"]);
+        assert!(chunk.eol);
+        assert!(!chunk.done);
+
+        let (chunk, _) = read_chunk(&mut reader, 1).await.unwrap();
+        assert!(chunk.lines.is_empty());
+        assert!(chunk.done);
+    }
+
+    #[tokio::test]
+    async fn load_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.log");
+
+        let lines = FIRST_LINES + 10;
+        let text = (0..lines).map(|i| format!("{}\n", i)).collect::<String>();
+        std::fs::write(&path, &text).unwrap();
+
+        let mut buffer = Buffer::open_large(path.clone()).await.unwrap();
+        assert_eq!(buffer.storage.lines(), FIRST_LINES);
+        assert!(buffer.save().await.is_err());
+
+        let mut buffers = Buffers::from_paths(vec![], Bounds::from_size(Size::new(4, 3)), false)
+            .await
+            .unwrap();
+        buffers.push(buffer);
+        buffers.background_work().await;
+        let buffer = buffers.current_mut();
+        assert_eq!(buffer.text(), text);
+        assert!(buffer.loader.is_none());
+        assert!(!buffer.is_modified());
+
+        // Nothing is left to read.
+        assert!(buffers.background_work().now_or_never().is_none());
+    }
+}
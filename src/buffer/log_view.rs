@@ -0,0 +1,138 @@
+//! Buffers that follow the editor's own log, opened with `:log`.
+//!
+//! The editor reads the lines written to the log file since it was last read every so often, and
+//! appends them to the buffer. If the cursor is on the last line, it moves to the new last line.
+//! Log buffers can't be edited.
+
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use tokio::fs::File;
+use tokio::io::{self, AsyncReadExt, AsyncSeekExt};
+
+use super::{Buffer, Buffers, Position, Storage};
+
+/// The log file that a buffer follows.
+pub(super) struct LogFollower {
+    path: PathBuf,
+
+    /// The number of bytes of the file that have been read into the buffer.
+    offset: u64,
+}
+
+impl Buffer {
+    /// Creates a buffer following a log file, with the cursor on its last line.
+    pub async fn open_log(path: PathBuf) -> io::Result<Self> {
+        let mut buffer = Buffer {
+            log: Some(LogFollower { path, offset: 0 }),
+            ..Buffer::new()
+        };
+
+        buffer.follow_log().await?;
+        buffer.jump_to(Position::new(0, usize::MAX));
+        Ok(buffer)
+    }
+
+    /// Returns the path of the log file that the buffer follows, if any.
+    pub fn log_path(&self) -> Option<&Path> {
+        self.log.as_ref().map(|log| log.path.as_path())
+    }
+
+    /// Appends the lines written to the log file since it was last read, if the buffer follows
+    /// one. Once the log has been rotated, the buffer is replaced with the new file.
+    pub async fn follow_log(&mut self) -> io::Result<()> {
+        let log = match &mut self.log {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+
+        let mut file = File::open(&log.path).await?;
+        if file.metadata().await?.len() < log.offset {
+            log.offset = 0;
+            self.storage = Storage::new();
+        }
+
+        file.seek(SeekFrom::Start(log.offset)).await?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).await?;
+
+        // A partly written line is read once it's finished.
+        let len = match bytes.iter().rposition(|&b| b == b'\n') {
+            Some(index) => index + 1,
+            None => return Ok(()),
+        };
+        log.offset += len as u64;
+
+        let following = self.cursor.y() + 1 == self.storage.lines();
+        let text = String::from_utf8_lossy(&bytes[..len]);
+        self.storage.append_lines(text.lines().map(String::from));
+
+        if following {
+            self.jump_to(Position::new(0, usize::MAX));
+        }
+
+        Ok(())
+    }
+}
+
+impl Buffers {
+    /// Returns whether any buffer follows a log file.
+    pub fn has_log(&self) -> bool {
+        self.buffers.iter().any(|buffer| buffer.log.is_some())
+    }
+
+    /// Reads new lines into the buffers that follow log files.
+    pub async fn follow_logs(&mut self) -> io::Result<()> {
+        for buffer in &mut self.buffers {
+            buffer.follow_log().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes the buffer following a log file the active buffer, opening it if there isn't one
+    /// already.
+    pub async fn focus_log(&mut self, path: PathBuf) -> io::Result<()> {
+        match self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.log_path() == Some(&path))
+        {
+            Some(index) => self.focus_index(index),
+            None => self.push(Buffer::open_log(path).await?),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    use super::Buffer;
+
+    #[tokio::test]
+    async fn follow_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("editor.log");
+        fs::write(&path, "INFO first\nINFO sec").unwrap();
+
+        let mut buffer = Buffer::open_log(path.clone()).await.unwrap();
+        assert_eq!(buffer.storage.to_string(), "INFO first\n");
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"ond\nINFO third\n").unwrap();
+        buffer.follow_log().await.unwrap();
+        assert_eq!(
+            buffer.storage.to_string(),
+            "INFO first\nINFO second\nINFO third\n"
+        );
+        assert_eq!(buffer.cursor.y(), 2);
+
+        fs::write(&path, "INFO rotated\n").unwrap();
+        buffer.follow_log().await.unwrap();
+        assert_eq!(buffer.storage.to_string(), "INFO rotated\n");
+    }
+}
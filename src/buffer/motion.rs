@@ -2,14 +2,77 @@
 
 use std::cmp;
 use std::convert::TryFrom;
+use std::iter;
 
 use euclid::vec2;
 use log::*;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+use super::columns::{byte_of_column, column_of};
+use super::wrap::line_breaks;
 use super::{Buffer, Offset, Position};
 
-/// The amount of padding that the cursor will maintain opposite the viewport.
-const SCROLLOFF: usize = 5;
+/// A motion whose destination depends on the contents of the buffer.
+///
+/// Motions may also be used as the target of an operator, which then acts on the text between the
+/// cursor and the destination.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Motion {
+    /// The start of the next function or class.
+    NextDefinition,
+
+    /// The start of the previous function or class.
+    PreviousDefinition,
+
+    /// The first column of the cursor's line.
+    LineStart,
+
+    /// The last character of the cursor's line. Moving up or down afterwards stays at the end of
+    /// each line.
+    LineEnd,
+
+    /// The first non-blank character of a line, counting from zero, or of the last line if the
+    /// buffer is shorter.
+    Line(usize),
+
+    /// The first non-blank character of the last line.
+    LastLine,
+}
+
+/// A change to the part of the buffer shown in the viewport.
+///
+/// The cursor is moved as little as possible to stay in the viewport, outside of the `scrolloff`
+/// lines at its edges.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scroll {
+    /// Forward a line.
+    LineDown,
+
+    /// Backward a line.
+    LineUp,
+
+    /// Forward a page, keeping two lines of the previous page visible.
+    PageDown,
+
+    /// Backward a page, keeping two lines of the previous page visible.
+    PageUp,
+
+    /// Forward half a page, moving the cursor the same number of lines.
+    HalfPageDown,
+
+    /// Backward half a page, moving the cursor the same number of lines.
+    HalfPageUp,
+
+    /// Puts the cursor's line at the top of the viewport.
+    CursorToTop,
+
+    /// Puts the cursor's line in the middle of the viewport.
+    CursorToCenter,
+
+    /// Puts the cursor's line at the bottom of the viewport.
+    CursorToBottom,
+}
 
 /// A cursor for an individual buffer.
 #[derive(Debug, Default, Copy, Clone)]
@@ -17,10 +80,11 @@ pub struct Cursor {
     /// Position of the cursor.
     pos: Position,
 
-    /// The column that the cursor should snap to if possible.
+    /// The screen column that the cursor should snap to if possible, which is remembered while
+    /// the cursor moves vertically.
     ///
-    /// See the `snap` method for more detail.
-    desired_col: usize,
+    /// See the `snap_cursor` method for more detail.
+    desired_col: Option<usize>,
 }
 
 impl Cursor {
@@ -34,7 +98,7 @@ impl Cursor {
 
     pub fn set_x(&mut self, x: usize) {
         self.pos.x = x;
-        self.desired_col = x;
+        self.desired_col = None;
     }
 
     pub fn set_y(&mut self, y: usize) {
@@ -51,7 +115,7 @@ impl Cursor {
             self.pos.x += n;
         }
 
-        self.desired_col = self.pos.x;
+        self.desired_col = None;
     }
 
     /// Move the cursor up or down a number of lines.
@@ -65,27 +129,12 @@ impl Cursor {
         }
     }
 
-    /// "Snaps" the cursor to be within a given distance.
-    ///
-    /// This method is used to support vim-like end-of-line behavior. If the cursor is moved
-    /// vertically to a line of text that is shorter than the current X-coordinate, the cursor
-    /// moves left to be within the text. The cursor remembers the desired line length. If the
-    /// cursor is moved back to a line that is longer, it will be snapped back as close to the
-    /// desired coordinate as possible, even if the line is still too short.
-    pub fn snap(&mut self, line_length: usize) {
-        if self.desired_col != self.pos.x {
-            self.pos.x = cmp::min(self.desired_col, line_length)
-        } else if self.pos.x > line_length {
-            self.pos.x = line_length
-        }
-    }
-
     #[cfg(test)]
     /// Creates a cursor at a particular position.
     pub fn at(x: usize, y: usize) -> Cursor {
         Cursor {
             pos: Position::new(x, y),
-            desired_col: x,
+            desired_col: None,
         }
     }
 }
@@ -94,33 +143,178 @@ impl Buffer {
     pub fn move_offset(&mut self, offset: Offset) {
         let (x_offset, y_offset) = offset.to_tuple();
 
+        if y_offset != 0 {
+            self.remember_column();
+        }
+
         if x_offset != 0 {
             self.cursor.move_x(x_offset);
         }
 
         if y_offset != 0 {
             self.cursor.move_y(y_offset);
-            self.cursor.snap(self.storage.line_width(self.cursor.y()));
+            self.snap_cursor();
         }
 
+        self.scroll_to_cursor();
+    }
+
+    /// Scrolls the viewport as little as possible to show the cursor, outside of the `scrolloff`
+    /// lines.
+    fn scroll_to_cursor(&mut self) {
+        // The topmost line that shows the cursor's line and the scrolloff lines below it, if
+        // lines are wrapped and may take up more than one display line each.
+        let wrapped_top = self.wrap_width().and(self.viewport).map(|viewport| {
+            let scrolloff = cmp::min(
+                self.window.scrolloff,
+                viewport.height().saturating_sub(1) / 2,
+            );
+            let bottom = cmp::min(self.cursor.y() + scrolloff, self.storage.lines() - 1);
+            self.top_for_bottom(bottom, self.cursor.y())
+        });
+
         if let Some(viewport) = &mut self.viewport {
-            if self.cursor.y() > SCROLLOFF && self.cursor.y() > viewport.max_y() - SCROLLOFF {
-                let max_y = cmp::min(self.cursor.y() + SCROLLOFF, self.storage.lines());
-                viewport.origin.y = max_y - viewport.height();
-            } else if self.cursor.y() < viewport.min_y() + SCROLLOFF {
-                viewport.origin.y = self.cursor.y().saturating_sub(SCROLLOFF);
+            // The lines kept visible around the cursor, which can't reach past the middle of the
+            // viewport.
+            let scrolloff = cmp::min(
+                self.window.scrolloff,
+                viewport.height().saturating_sub(1) / 2,
+            );
+
+            if self.cursor.y() > scrolloff && self.cursor.y() > viewport.max_y() - scrolloff {
+                let max_y = cmp::min(self.cursor.y() + scrolloff, self.storage.lines());
+                viewport.origin.y = max_y.saturating_sub(viewport.height());
+            } else if self.cursor.y() < viewport.min_y() + scrolloff {
+                viewport.origin.y = self.cursor.y().saturating_sub(scrolloff);
+            }
+
+            // Wrapped lines are never scrolled horizontally.
+            if let Some(top) = wrapped_top {
+                viewport.origin.y = cmp::max(viewport.origin.y, top);
+                viewport.origin.x = 0;
+                debug!("cursor moved to {:?}", self.cursor.pos);
+                return;
             }
 
-            if self.cursor.x() >= viewport.max_x() {
-                viewport.origin.x = self.cursor.x() + 1 - viewport.width();
-            } else if self.cursor.x() < viewport.min_x() {
-                viewport.origin.x = self.cursor.x();
+            // Wide characters must fit in the viewport entirely.
+            let line = self.storage.line(self.cursor.y());
+            let column = column_of(line, self.cursor.x());
+            let width = line[self.cursor.x()..]
+                .graphemes(true)
+                .next()
+                .map_or(1, |grapheme| cmp::max(grapheme.width(), 1));
+
+            if column + width > viewport.max_x() {
+                viewport.origin.x = (column + width).saturating_sub(viewport.width());
+            } else if column < viewport.min_x() {
+                viewport.origin.x = column;
             }
         }
 
         debug!("cursor moved to {:?}", self.cursor.pos);
     }
 
+    /// Returns the first line that the viewport can start at while still showing every display
+    /// line of `bottom`, but no later than `limit`.
+    fn top_for_bottom(&self, bottom: usize, limit: usize) -> usize {
+        let height = self.viewport.map_or(0, |viewport| viewport.height());
+        let mut rows = 0;
+        let mut top = bottom + 1;
+
+        while top > 0 {
+            rows += self.display_height(top - 1);
+            if rows > height {
+                break;
+            }
+            top -= 1;
+        }
+
+        cmp::min(top, limit)
+    }
+
+    /// Returns the last line whose display lines are all shown when the viewport starts at `top`,
+    /// or `top` itself if it's taller than the viewport.
+    fn bottom_for_top(&self, top: usize) -> usize {
+        let height = self.viewport.map_or(0, |viewport| viewport.height());
+        let mut rows = self.display_height(top);
+        let mut bottom = top;
+
+        while bottom + 1 < self.storage.lines() {
+            rows += self.display_height(bottom + 1);
+            if rows > height {
+                break;
+            }
+            bottom += 1;
+        }
+
+        bottom
+    }
+
+    /// Moves the cursor to a position within the buffer.
+    fn move_to(&mut self, pos: Position) {
+        // The column that the cursor remembers from earlier vertical moves no longer applies.
+        self.cursor.pos = pos;
+        self.cursor.desired_col = None;
+        self.snap_cursor();
+
+        self.scroll_to_cursor();
+    }
+
+    /// Moves the cursor to a position, or as close to it as the contents of the buffer allow.
+    pub fn jump_to(&mut self, pos: Position) {
+        let y = cmp::min(pos.y, self.storage.lines() - 1);
+        let x = floor_grapheme_boundary(self.storage.line(y), cmp::min(pos.x, self.max_x(y)));
+        self.move_to(Position::new(x, y));
+    }
+
+    /// Returns the position that a motion would move the cursor to, if any.
+    pub fn motion_target(&self, motion: Motion) -> Option<Position> {
+        let cursor = self.cursor.pos;
+        let is_before_cursor = |pos: &Position| (pos.y, pos.x) < (cursor.y, cursor.x);
+        let last_line = self.storage.lines() - 1;
+
+        match motion {
+            Motion::NextDefinition => self
+                .highlighter
+                .as_ref()?
+                .definitions(self)
+                .into_iter()
+                .find(|pos| !is_before_cursor(pos) && *pos != cursor),
+            Motion::PreviousDefinition => self
+                .highlighter
+                .as_ref()?
+                .definitions(self)
+                .into_iter()
+                .rev()
+                .find(is_before_cursor),
+            Motion::LineStart => Some(Position::new(0, cursor.y)),
+            Motion::LineEnd => Some(Position::new(self.max_x(cursor.y), cursor.y)),
+            Motion::Line(y) => Some(self.first_non_blank(cmp::min(y, last_line))),
+            Motion::LastLine => Some(self.first_non_blank(last_line)),
+        }
+    }
+
+    /// Moves the cursor according to a motion. If the motion has no target, the cursor stays put.
+    pub fn apply_motion(&mut self, motion: Motion) {
+        if let Some(target) = self.motion_target(motion) {
+            self.move_to(target);
+
+            if motion == Motion::LineEnd {
+                self.cursor.desired_col = Some(usize::MAX);
+            }
+        }
+    }
+
+    /// Returns the position of the first character of a line that isn't whitespace, or the end of
+    /// the line if it's blank.
+    fn first_non_blank(&self, y: usize) -> Position {
+        let line = self.storage.line(y);
+        let x = line
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(line.len());
+        Position::new(cmp::min(x, self.max_x(y)), y)
+    }
+
     /// Move the cursor down a single line.
     pub fn move_down(&mut self) {
         if self.at_last_line() {
@@ -130,13 +324,20 @@ impl Buffer {
         self.move_offset(vec2(0, 1));
     }
 
-    /// Move the cursor right a single column.
+    /// Move the cursor right a single character.
+    ///
+    /// Characters are grapheme clusters, so the cursor moves past combining characters together
+    /// with the character that they modify.
     pub fn move_right(&mut self) {
         if self.at_end_of_line() {
             return;
         }
 
-        self.move_offset(vec2(1, 0));
+        let line = self.storage.line(self.cursor.y());
+        if let Some(grapheme) = line[self.cursor.x()..].graphemes(true).next() {
+            let offset = isize::try_from(grapheme.len()).expect("grapheme too long");
+            self.move_offset(vec2(offset, 0));
+        }
     }
 
     /// Move the cursor up a single line.
@@ -148,13 +349,220 @@ impl Buffer {
         self.move_offset(vec2(0, -1));
     }
 
-    /// Move the cursor left a single column.
+    /// Move the cursor left a single character.
+    ///
+    /// Like `move_right`, characters are grapheme clusters.
     pub fn move_left(&mut self) {
         if self.at_beginning_of_line() {
             return;
         }
 
-        self.move_offset(vec2(-1, 0));
+        let line = self.storage.line(self.cursor.y());
+        if let Some(grapheme) = line[..self.cursor.x()].graphemes(true).next_back() {
+            let offset = isize::try_from(grapheme.len()).expect("grapheme too long");
+            self.move_offset(vec2(-offset, 0));
+        }
+    }
+
+    /// Moves the cursor down a display line, to the next part of a wrapped line or to the next
+    /// line. Without `wrap`, this is the same as `move_down`.
+    pub fn move_display_down(&mut self) {
+        let width = match self.wrap_width() {
+            Some(width) => width,
+            None => return self.move_down(),
+        };
+
+        let (row, offset) = self.display_offset(width);
+        let y = self.cursor.y();
+        if row + 1 < line_breaks(self.storage.line(y), width, usize::MAX).len() {
+            self.move_to_display_line(width, y, row + 1, offset);
+        } else if !self.at_last_line() {
+            self.move_to_display_line(width, y + 1, 0, offset);
+        }
+    }
+
+    /// Moves the cursor up a display line, to the previous part of a wrapped line or to the
+    /// previous line. Without `wrap`, this is the same as `move_up`.
+    pub fn move_display_up(&mut self) {
+        let width = match self.wrap_width() {
+            Some(width) => width,
+            None => return self.move_up(),
+        };
+
+        let (row, offset) = self.display_offset(width);
+        let y = self.cursor.y();
+        if row > 0 {
+            self.move_to_display_line(width, y, row - 1, offset);
+        } else if !self.at_first_line() {
+            let rows = line_breaks(self.storage.line(y - 1), width, usize::MAX).len();
+            self.move_to_display_line(width, y - 1, rows - 1, offset);
+        }
+    }
+
+    /// Returns which display line of its line the cursor is on, and the screen column that it
+    /// should stay in when it moves to another display line.
+    fn display_offset(&mut self, width: usize) -> (usize, usize) {
+        self.remember_column();
+
+        let column = self.cursor_column();
+        let breaks = line_breaks(self.storage.line(self.cursor.y()), width, usize::MAX);
+        let row = breaks
+            .iter()
+            .rposition(|&start| start <= column)
+            .unwrap_or(0);
+
+        let desired_col = self.cursor.desired_col.unwrap_or(column);
+        let offset = cmp::min(desired_col.saturating_sub(breaks[row]), width - 1);
+        (row, offset)
+    }
+
+    /// Moves the cursor to a column of a display line of a line, or as close to it as the display
+    /// line allows.
+    fn move_to_display_line(&mut self, width: usize, y: usize, row: usize, offset: usize) {
+        let breaks = line_breaks(self.storage.line(y), width, usize::MAX);
+        let mut column = breaks[row] + offset;
+        if let Some(&end) = breaks.get(row + 1) {
+            column = cmp::min(column, end - 1);
+        }
+
+        self.cursor.set_y(y);
+        self.cursor.desired_col = Some(column);
+        self.snap_cursor();
+
+        self.scroll_to_cursor();
+    }
+
+    /// Scrolls the viewport, moving the cursor to keep it visible. Does nothing if the buffer is
+    /// hidden.
+    pub fn scroll(&mut self, scroll: Scroll) {
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => return,
+        };
+
+        let height = viewport.height();
+        let top = viewport.min_y();
+        let last_line = self.storage.lines() - 1;
+        let scrolloff = cmp::min(self.window.scrolloff, height.saturating_sub(1) / 2);
+        let cursor_y = self.cursor.y();
+        self.remember_column();
+
+        let page = cmp::max(height.saturating_sub(2), 1);
+        let half_page = cmp::max(height / 2, 1);
+
+        let top = match scroll {
+            Scroll::LineDown => top + 1,
+            Scroll::LineUp => top.saturating_sub(1),
+            Scroll::PageDown => top + page,
+            Scroll::PageUp => top.saturating_sub(page),
+            Scroll::HalfPageDown => {
+                self.cursor.set_y(cmp::min(cursor_y + half_page, last_line));
+                // Stop scrolling once the last line is visible.
+                cmp::max(
+                    top,
+                    cmp::min(top + half_page, self.storage.lines().saturating_sub(height)),
+                )
+            }
+            Scroll::HalfPageUp => {
+                self.cursor.set_y(cursor_y.saturating_sub(half_page));
+                top.saturating_sub(half_page)
+            }
+            Scroll::CursorToTop => cursor_y.saturating_sub(scrolloff),
+            Scroll::CursorToCenter => cursor_y.saturating_sub(height.saturating_sub(1) / 2),
+            Scroll::CursorToBottom => (cursor_y + scrolloff + 1).saturating_sub(height),
+        };
+        let top = cmp::min(top, last_line);
+
+        if let Some(viewport) = &mut self.viewport {
+            viewport.origin.y = top;
+        }
+
+        // Keep the cursor outside of the scrolloff lines, unless they are the edges of the buffer.
+        let min_y = if top == 0 { 0 } else { top + scrolloff };
+        let max_y = if top + height > last_line {
+            last_line
+        } else {
+            top + height - 1 - scrolloff
+        };
+        let mut y = cmp::max(cmp::min(self.cursor.y(), max_y), min_y);
+
+        // Wrapped lines may leave room for fewer lines than the viewport has rows.
+        if self.wrap_width().is_some() {
+            let bottom = self.bottom_for_top(top);
+            let max_y = if bottom == last_line {
+                bottom
+            } else {
+                cmp::max(bottom.saturating_sub(scrolloff), top)
+            };
+            y = cmp::min(y, max_y);
+        }
+
+        self.cursor.set_y(cmp::min(y, last_line));
+        self.snap_cursor();
+
+        debug!("scrolled to line {}, cursor at {:?}", top, self.cursor.pos);
+    }
+
+    /// Moves the cursor back within the bounds of the buffer, if necessary.
+    ///
+    /// This is useful after edits that may have removed the text underneath the cursor.
+    pub fn clamp_cursor(&mut self) {
+        let last_line = self.storage.lines() - 1;
+        if self.cursor.y() > last_line {
+            self.cursor.set_y(last_line);
+        }
+
+        self.snap_cursor();
+    }
+
+    /// Allows or prevents the cursor from being after the last character of its line, moving it
+    /// back onto the last character if necessary.
+    pub fn set_cursor_past_end(&mut self, allowed: bool) {
+        self.cursor_past_end = allowed;
+        if !allowed {
+            self.clamp_cursor();
+        }
+    }
+
+    /// Remembers the screen column of the cursor before it moves vertically, unless a column is
+    /// already remembered from an earlier vertical move.
+    fn remember_column(&mut self) {
+        if self.cursor.desired_col.is_none() {
+            self.cursor.desired_col = Some(self.cursor_column());
+        }
+    }
+
+    /// "Snaps" the cursor to be within its line, keeping it on a grapheme cluster boundary.
+    ///
+    /// This method is used to support vim-like end-of-line behavior. If the cursor is moved
+    /// vertically to a line of text that is shorter than the current column, the cursor moves
+    /// left to be within the text. The cursor remembers the desired column. If the cursor is moved
+    /// back to a line that is longer, it will be snapped back as close to the desired column as
+    /// possible, even if the line is still too short. Columns are screen columns, so the cursor
+    /// stays in place visually when moving across lines with wide characters.
+    fn snap_cursor(&mut self) {
+        let max_x = self.max_x(self.cursor.y());
+        let line = self.storage.line(self.cursor.y());
+
+        let x = match self.cursor.desired_col {
+            Some(column) => byte_of_column(line, column),
+            None => self.cursor.x(),
+        };
+        self.cursor.pos.x = floor_grapheme_boundary(line, cmp::min(x, max_x));
+    }
+
+    /// Returns the rightmost position of the cursor on a line: after the last character if the
+    /// cursor may be past the end, or on the last character otherwise.
+    fn max_x(&self, y: usize) -> usize {
+        let line = self.storage.line(y);
+
+        if self.cursor_past_end {
+            line.len()
+        } else {
+            line.grapheme_indices(true)
+                .next_back()
+                .map_or(0, |(index, _)| index)
+        }
     }
 
     /// Returns true if the cursor is on the first line of the buffer.
@@ -172,21 +580,37 @@ impl Buffer {
         self.cursor.x() == 0
     }
 
-    /// Returns true if the cursor is in the rightmost column for the given line.
+    /// Returns true if the cursor is as far right as it may be on its line.
     fn at_end_of_line(&self) -> bool {
-        self.cursor.x() >= self.storage.line_width(self.cursor.y())
+        self.cursor.x() >= self.max_x(self.cursor.y())
     }
 }
 
+/// Returns the last grapheme cluster boundary of a line at or before a byte offset.
+///
+/// Offsets past the end of the line are clamped to the end of the line.
+fn floor_grapheme_boundary(line: &str, x: usize) -> usize {
+    line.grapheme_indices(true)
+        .map(|(index, _)| index)
+        .chain(iter::once(line.len()))
+        .take_while(|&index| index <= x)
+        .last()
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::Buffer;
 
-    use euclid::{rect, size2};
+    use euclid::{point2, rect, size2};
     use indoc::indoc;
     use itertools::Itertools;
 
     use crate::buffer::{Cursor, Position, Span};
+    #[cfg(any(feature = "lang-python", feature = "lang-rust"))]
+    use crate::syntax::Syntax;
+
+    use super::{Motion, Scroll};
 
     #[test]
     fn move_single_character_empty_buffer() {
@@ -215,6 +639,106 @@ mod tests {
         assert_eq!(buffer.cursor.pos, Position::new(4, 0));
     }
 
+    #[test]
+    fn move_by_grapheme_cluster() {
+        let mut buffer = Buffer::from("e\u{301}台x");
+
+        buffer.move_right();
+        assert_eq!(buffer.cursor.pos, Position::new(3, 0));
+        buffer.move_right();
+        assert_eq!(buffer.cursor.pos, Position::new(6, 0));
+
+        buffer.move_left();
+        assert_eq!(buffer.cursor.pos, Position::new(3, 0));
+        buffer.move_left();
+        assert_eq!(buffer.cursor.pos, Position::new(0, 0));
+    }
+
+    #[test]
+    fn move_vertically_onto_grapheme_boundary() {
+        let mut buffer = Buffer::from(indoc! {"
+            abcd
+            台北
+        "});
+        buffer.cursor = Cursor::at(2, 0);
+
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(3, 1));
+
+        buffer.move_up();
+        assert_eq!(buffer.cursor.pos, Position::new(2, 0));
+    }
+
+    #[test]
+    fn move_vertically_by_screen_column() {
+        let mut buffer = Buffer::from(indoc! {"
+            台北市
+            abcdef
+            e\u{301}e\u{301}e\u{301}e\u{301}
+        "});
+        buffer.set_cursor_past_end(false);
+        buffer.cursor = Cursor::at(6, 0);
+        assert_eq!(buffer.cursor_column(), 4);
+
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(4, 1));
+
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(9, 2));
+        assert_eq!(buffer.cursor_column(), 3);
+
+        buffer.move_up();
+        buffer.move_up();
+        assert_eq!(buffer.cursor.pos, Position::new(6, 0));
+    }
+
+    #[test]
+    fn move_by_display_line() {
+        let mut buffer = Buffer::from(indoc! {"
+            abcdefgh
+            ij
+            klmnop
+        "});
+        buffer.viewport = Some(rect(0, 0, 3, 10));
+        buffer.cursor = Cursor::at(1, 0);
+
+        // Without wrap, display lines are lines.
+        buffer.move_display_down();
+        assert_eq!(buffer.cursor.pos, Position::new(1, 1));
+        buffer.move_display_up();
+        assert_eq!(buffer.cursor.pos, Position::new(1, 0));
+
+        buffer.window.wrap = true;
+        buffer.move_display_down();
+        assert_eq!(buffer.cursor.pos, Position::new(4, 0));
+        buffer.move_display_down();
+        assert_eq!(buffer.cursor.pos, Position::new(7, 0));
+        buffer.move_display_down();
+        assert_eq!(buffer.cursor.pos, Position::new(1, 1));
+        buffer.move_display_down();
+        assert_eq!(buffer.cursor.pos, Position::new(1, 2));
+
+        buffer.move_display_up();
+        assert_eq!(buffer.cursor.pos, Position::new(1, 1));
+        buffer.move_display_up();
+        assert_eq!(buffer.cursor.pos, Position::new(7, 0));
+    }
+
+    #[test]
+    fn scroll_to_wrapped_line() {
+        let mut buffer = Buffer::from("abcdef\nabcdef\nabcdef\nabcdef\n");
+        buffer.window.wrap = true;
+        buffer.window.scrolloff = 0;
+        buffer.viewport = Some(rect(2, 0, 3, 4));
+
+        // Two lines fill the viewport, so moving to the third scrolls down a line.
+        buffer.move_down();
+        assert_eq!(buffer.viewport.unwrap().origin, point2(0, 0));
+        buffer.move_down();
+        assert_eq!(buffer.viewport.unwrap().origin, point2(0, 1));
+        assert_eq!(buffer.cursor_position(), Position::new(0, 2));
+    }
+
     #[test]
     fn move_up() {
         let mut buffer = Buffer::from(indoc! {"
@@ -251,6 +775,37 @@ mod tests {
         assert_eq!(buffer.cursor.pos, Position::new(6, 0));
     }
 
+    #[test]
+    fn clamp_to_last_character() {
+        let mut buffer = Buffer::from(indoc! {"
+            abcdef
+            a
+            é
+            abc
+        "});
+        buffer.cursor = Cursor::at(6, 0);
+
+        buffer.set_cursor_past_end(false);
+        assert_eq!(buffer.cursor.pos, Position::new(5, 0));
+
+        buffer.move_right();
+        assert_eq!(buffer.cursor.pos, Position::new(5, 0));
+
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(0, 1));
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(0, 2));
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(2, 3));
+
+        buffer.jump_to(Position::new(10, 0));
+        assert_eq!(buffer.cursor.pos, Position::new(5, 0));
+
+        buffer.set_cursor_past_end(true);
+        buffer.move_right();
+        assert_eq!(buffer.cursor.pos, Position::new(6, 0));
+    }
+
     #[test]
     fn move_down_out_of_bounds() {
         let mut buffer = Buffer::from(indoc! {"
@@ -371,6 +926,26 @@ mod tests {
         assert_eq!(buffer.viewport.unwrap().min_x(), 1);
     }
 
+    #[test]
+    fn viewport_motion_wide_characters() {
+        let mut buffer = Buffer::from("台北市");
+
+        buffer.viewport = Some(rect(0, 0, 3, 1));
+
+        buffer.move_right();
+        assert_eq!(buffer.cursor.pos, Position::new(3, 0));
+        assert_eq!(buffer.viewport.unwrap().min_x(), 1);
+        assert_eq!(buffer.cursor_position(), Position::new(1, 0));
+
+        buffer.move_right();
+        assert_eq!(buffer.viewport.unwrap().min_x(), 3);
+        assert_eq!(buffer.cursor_position(), Position::new(1, 0));
+
+        buffer.move_left();
+        buffer.move_left();
+        assert_eq!(buffer.viewport.unwrap().min_x(), 0);
+    }
+
     #[test]
     fn scrolloff_larger_than_viewport() {
         let mut buffer = Buffer::from(indoc! {"
@@ -385,4 +960,176 @@ mod tests {
         buffer.move_up();
         assert_eq!(buffer.viewport.unwrap().origin, Position::zero());
     }
+
+    #[test]
+    fn scroll() {
+        let mut buffer = Buffer::from((0..100).join("\n").as_str());
+        buffer.viewport = Some(rect(0, 0, 10, 20));
+        buffer.window.scrolloff = 2;
+
+        let mut scroll = |scroll| {
+            buffer.scroll(scroll);
+            (buffer.viewport.unwrap().min_y(), buffer.cursor.y())
+        };
+
+        assert_eq!(scroll(Scroll::PageDown), (18, 20));
+        assert_eq!(scroll(Scroll::HalfPageDown), (28, 30));
+        assert_eq!(scroll(Scroll::CursorToCenter), (21, 30));
+        assert_eq!(scroll(Scroll::CursorToTop), (28, 30));
+        assert_eq!(scroll(Scroll::CursorToBottom), (13, 30));
+        assert_eq!(scroll(Scroll::HalfPageUp), (3, 20));
+        assert_eq!(scroll(Scroll::PageUp), (0, 17));
+        assert_eq!(scroll(Scroll::PageUp), (0, 17));
+
+        for _ in 0..7 {
+            scroll(Scroll::HalfPageDown);
+        }
+        assert_eq!(scroll(Scroll::HalfPageDown), (80, 97));
+        assert_eq!(scroll(Scroll::HalfPageDown), (80, 99));
+        assert_eq!(scroll(Scroll::PageDown), (98, 99));
+    }
+
+    #[test]
+    fn scroll_by_lines() {
+        let mut buffer = Buffer::from((0..100).join("\n").as_str());
+        buffer.viewport = Some(rect(0, 0, 10, 20));
+        buffer.window.scrolloff = 2;
+
+        let mut scroll = |scroll| {
+            buffer.scroll(scroll);
+            (buffer.viewport.unwrap().min_y(), buffer.cursor.y())
+        };
+
+        assert_eq!(scroll(Scroll::LineUp), (0, 0));
+        assert_eq!(scroll(Scroll::LineDown), (1, 3));
+        assert_eq!(scroll(Scroll::LineUp), (0, 3));
+
+        // Scrolling up pulls the cursor up with the bottom of the viewport.
+        assert_eq!(scroll(Scroll::PageDown), (18, 20));
+        for _ in 0..15 {
+            scroll(Scroll::LineUp);
+        }
+        assert_eq!(scroll(Scroll::LineUp), (2, 19));
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn move_between_definitions() {
+        let mut buffer = Buffer::from(indoc! {"
+            use std::fmt;
+
+            struct Foo;
+
+            impl Foo {
+                fn new() -> Self {
+                    let f = |x| x;
+                    Foo
+                }
+            }
+
+            fn main() {}
+        "});
+        buffer.set_syntax(Some(Syntax::Rust));
+
+        let mut stops = vec![];
+        for _ in 0..5 {
+            buffer.apply_motion(Motion::NextDefinition);
+            stops.push(buffer.cursor.pos);
+        }
+
+        assert_eq!(
+            stops,
+            vec![
+                Position::new(0, 2),
+                Position::new(0, 4),
+                Position::new(4, 5),
+                Position::new(0, 11),
+                Position::new(0, 11),
+            ]
+        );
+
+        buffer.apply_motion(Motion::PreviousDefinition);
+        assert_eq!(buffer.cursor.pos, Position::new(4, 5));
+
+        buffer.cursor = Cursor::at(8, 6);
+        buffer.apply_motion(Motion::PreviousDefinition);
+        assert_eq!(buffer.cursor.pos, Position::new(4, 5));
+    }
+
+    #[cfg(feature = "lang-python")]
+    #[test]
+    fn move_between_decorated_definitions() {
+        let mut buffer = Buffer::from(indoc! {"
+            @dataclass
+            class Point:
+                x: int
+
+                @property
+                def norm(self):
+                    return abs(self.x)
+        "});
+        buffer.set_syntax(Some(Syntax::Python));
+
+        buffer.apply_motion(Motion::NextDefinition);
+        assert_eq!(buffer.cursor.pos, Position::new(4, 4));
+
+        buffer.apply_motion(Motion::PreviousDefinition);
+        assert_eq!(buffer.cursor.pos, Position::zero());
+    }
+
+    #[test]
+    fn jump_to() {
+        let mut buffer = Buffer::from("abc\nde\n");
+
+        buffer.jump_to(Position::new(1, 1));
+        assert_eq!(buffer.cursor.pos, Position::new(1, 1));
+
+        buffer.jump_to(Position::new(10, 0));
+        assert_eq!(buffer.cursor.pos, Position::new(3, 0));
+
+        buffer.jump_to(Position::new(2, 10));
+        assert_eq!(buffer.cursor.pos, Position::new(2, 1));
+    }
+
+    #[test]
+    fn line_motions() {
+        let mut buffer = Buffer::from(indoc! {"
+            abc
+              defgh
+            ij
+        "});
+        buffer.set_cursor_past_end(false);
+        buffer.cursor = Cursor::at(1, 0);
+
+        buffer.apply_motion(Motion::LineEnd);
+        assert_eq!(buffer.cursor.pos, Position::new(2, 0));
+
+        // The cursor stays at the end of each line.
+        buffer.move_down();
+        assert_eq!(buffer.cursor.pos, Position::new(6, 1));
+
+        buffer.apply_motion(Motion::LineStart);
+        assert_eq!(buffer.cursor.pos, Position::new(0, 1));
+
+        buffer.apply_motion(Motion::LastLine);
+        assert_eq!(buffer.cursor.pos, Position::new(0, 2));
+
+        buffer.apply_motion(Motion::Line(1));
+        assert_eq!(buffer.cursor.pos, Position::new(2, 1));
+
+        buffer.apply_motion(Motion::Line(0));
+        assert_eq!(buffer.cursor.pos, Position::new(0, 0));
+
+        buffer.apply_motion(Motion::Line(10));
+        assert_eq!(buffer.cursor.pos, Position::new(0, 2));
+    }
+
+    #[test]
+    fn no_definitions_without_syntax() {
+        let mut buffer = Buffer::from("fn main() {}\n");
+
+        assert_eq!(buffer.motion_target(Motion::NextDefinition), None);
+        buffer.apply_motion(Motion::NextDefinition);
+        assert_eq!(buffer.cursor.pos, Position::zero());
+    }
 }
@@ -0,0 +1,208 @@
+//! Searching the text of a buffer for a pattern, with `:/`, `:?`, `n`, and `N`.
+//!
+//! Patterns are matched literally.
+
+use super::{Buffer, Position};
+
+/// The direction that a search moves the cursor in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+impl Direction {
+    pub fn reverse(self) -> Self {
+        match self {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        }
+    }
+}
+
+/// How a pattern is searched for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Whether uppercase and lowercase letters match each other.
+    pub ignore_case: bool,
+
+    /// Whether the search continues from the other end of the buffer when it reaches the end.
+    pub wrap: bool,
+}
+
+/// Where a search found its pattern.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SearchResult {
+    Found(Position),
+
+    /// The pattern was found after continuing from the other end of the buffer.
+    Wrapped(Position),
+
+    NotFound,
+}
+
+impl Buffer {
+    /// Searches for the next match of a pattern before or after the cursor. The cursor doesn't
+    /// move.
+    pub fn search(
+        &self,
+        pattern: &str,
+        direction: Direction,
+        options: SearchOptions,
+    ) -> SearchResult {
+        if pattern.is_empty() {
+            return SearchResult::NotFound;
+        }
+
+        let cursor = Position::new(self.cursor.x(), self.cursor.y());
+        let lines = self.storage.lines();
+        let matches = |y: usize| find_matches(self.storage.line(y), pattern, options.ignore_case);
+
+        match direction {
+            Direction::Forward => {
+                let after_cursor = matches(cursor.y).into_iter().find(|&x| x > cursor.x);
+                let found = after_cursor
+                    .map(|x| Position::new(x, cursor.y))
+                    .or_else(|| {
+                        (cursor.y + 1..lines)
+                            .find_map(|y| matches(y).first().map(|&x| Position::new(x, y)))
+                    });
+                if let Some(pos) = found {
+                    return SearchResult::Found(pos);
+                }
+
+                if options.wrap {
+                    let wrapped = (0..=cursor.y)
+                        .find_map(|y| matches(y).first().map(|&x| Position::new(x, y)));
+                    if let Some(pos) = wrapped {
+                        return SearchResult::Wrapped(pos);
+                    }
+                }
+            }
+            Direction::Backward => {
+                let before_cursor = matches(cursor.y).into_iter().rev().find(|&x| x < cursor.x);
+                let found = before_cursor
+                    .map(|x| Position::new(x, cursor.y))
+                    .or_else(|| {
+                        (0..cursor.y)
+                            .rev()
+                            .find_map(|y| matches(y).last().map(|&x| Position::new(x, y)))
+                    });
+                if let Some(pos) = found {
+                    return SearchResult::Found(pos);
+                }
+
+                if options.wrap {
+                    let wrapped = (cursor.y..lines)
+                        .rev()
+                        .find_map(|y| matches(y).last().map(|&x| Position::new(x, y)));
+                    if let Some(pos) = wrapped {
+                        return SearchResult::Wrapped(pos);
+                    }
+                }
+            }
+        }
+
+        SearchResult::NotFound
+    }
+}
+
+/// Returns the byte columns where the pattern starts in a line, in order.
+pub(super) fn find_matches(line: &str, pattern: &str, ignore_case: bool) -> Vec<usize> {
+    line.char_indices()
+        .map(|(x, _)| x)
+        .filter(|&x| matches_at(&line[x..], pattern, ignore_case))
+        .collect()
+}
+
+/// Returns whether the text starts with the pattern.
+fn matches_at(text: &str, pattern: &str, ignore_case: bool) -> bool {
+    if !ignore_case {
+        return text.starts_with(pattern);
+    }
+
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| text.next() == Some(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, Position};
+
+    use super::{find_matches, Direction, SearchOptions, SearchResult};
+
+    const WRAP: SearchOptions = SearchOptions {
+        ignore_case: false,
+        wrap: true,
+    };
+
+    #[test]
+    fn find_matches_in_line() {
+        assert_eq!(find_matches("foo bar foo", "foo", false), vec![0, 8]);
+        assert!(find_matches("Foo bar FOO", "foo", false).is_empty());
+        assert_eq!(find_matches("Foo bar FOO", "foo", true), vec![0, 8]);
+        assert_eq!(find_matches("aaa", "aa", false), vec![0, 1]);
+        assert_eq!(find_matches("é foo", "FOO", true), vec![3]);
+    }
+
+    #[test]
+    fn search_forward() {
+        let mut buffer = Buffer::from("foo\nbar foo\nbaz\n");
+
+        assert_eq!(
+            buffer.search("foo", Direction::Forward, WRAP),
+            SearchResult::Found(Position::new(4, 1))
+        );
+
+        buffer.jump_to(Position::new(4, 1));
+        assert_eq!(
+            buffer.search("foo", Direction::Forward, WRAP),
+            SearchResult::Wrapped(Position::new(0, 0))
+        );
+        assert_eq!(
+            buffer.search(
+                "foo",
+                Direction::Forward,
+                SearchOptions {
+                    wrap: false,
+                    ..WRAP
+                }
+            ),
+            SearchResult::NotFound
+        );
+        assert_eq!(
+            buffer.search("qux", Direction::Forward, WRAP),
+            SearchResult::NotFound
+        );
+    }
+
+    #[test]
+    fn search_backward() {
+        let mut buffer = Buffer::from("foo\nbar foo\nbaz\n");
+
+        buffer.jump_to(Position::new(4, 1));
+        assert_eq!(
+            buffer.search("foo", Direction::Backward, WRAP),
+            SearchResult::Found(Position::new(0, 0))
+        );
+
+        buffer.jump_to(Position::new(0, 0));
+        assert_eq!(
+            buffer.search("foo", Direction::Backward, WRAP),
+            SearchResult::Wrapped(Position::new(4, 1))
+        );
+    }
+
+    #[test]
+    fn search_only_match() {
+        let buffer = Buffer::from("foo\n");
+
+        assert_eq!(
+            buffer.search("foo", Direction::Forward, WRAP),
+            SearchResult::Wrapped(Position::new(0, 0))
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! The text selected in visual mode.
+//!
+//! A selection is made of the anchor, where visual mode was entered, and the cursor. It includes
+//! the characters under both. When the cursor is past the end of its line, or the line is empty,
+//! the newline is selected too.
+
+use std::cmp;
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::buffer::units::ByteIndex;
+use crate::theme;
+use crate::ui::Context;
+
+use super::{Buffer, Position};
+
+impl Buffer {
+    /// Starts selecting text at the cursor.
+    pub fn start_selection(&mut self) {
+        self.selection_anchor = Some(Position::new(self.cursor.x(), self.cursor.y()));
+    }
+
+    /// Deselects the selected text.
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// Returns the range of the selected text, or `None` if nothing is selected.
+    pub fn selection(&self) -> Option<Range<Position>> {
+        let anchor = self.clamp_position(self.selection_anchor?);
+        let cursor = self.clamp_position(Position::new(self.cursor.x(), self.cursor.y()));
+
+        let (start, last) = if (anchor.y, anchor.x) <= (cursor.y, cursor.x) {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+
+        let line = self.storage.line(last.y);
+        let end = match line[last.x..].graphemes(true).next() {
+            Some(grapheme) => Position::new(last.x + grapheme.len(), last.y),
+            None if last.y + 1 < self.storage.lines() => Position::new(0, last.y + 1),
+            None => Position::new(line.len(), last.y),
+        };
+
+        Some(start..end)
+    }
+
+    /// Returns the byte range of the selected text, or `None` if nothing is selected.
+    pub(super) fn selection_bytes(&self) -> Option<Range<ByteIndex>> {
+        let Range { start, end } = self.selection()?;
+
+        Some(
+            self.storage.line_to_byte(start.y) + ByteIndex::new(start.x)
+                ..self.storage.line_to_byte(end.y) + ByteIndex::new(end.x),
+        )
+    }
+
+    /// Highlights the selected text. The selection is drawn over the syntax highlighting.
+    pub(super) fn draw_selection(&self, ctx: &mut Context<'_>) {
+        let selection = match self.selection() {
+            Some(selection) => selection,
+            None => return,
+        };
+
+        if let Some(style) = theme::current().style("visual") {
+            self.style_range(ctx, selection, style);
+        }
+    }
+
+    /// Moves a position that may have been left behind by an edit back inside the text.
+    fn clamp_position(&self, pos: Position) -> Position {
+        let y = cmp::min(pos.y, self.storage.lines() - 1);
+        let line = self.storage.line(y);
+
+        let mut x = cmp::min(pos.x, line.len());
+        while !line.is_char_boundary(x) {
+            x -= 1;
+        }
+
+        Position::new(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::buffer::{Buffer, Cursor, Position};
+
+    #[test]
+    fn selection() {
+        let mut buffer = Buffer::from(indoc! {"
+            Lorem ipsum
+
+            dolor é
+        "});
+        assert_eq!(buffer.selection(), None);
+
+        buffer.cursor = Cursor::at(6, 0);
+        buffer.start_selection();
+        assert_eq!(
+            buffer.selection(),
+            Some(Position::new(6, 0)..Position::new(7, 0))
+        );
+
+        // The selection may be made backwards, and includes the newline of an empty line.
+        buffer.cursor = Cursor::at(0, 1);
+        buffer.start_selection();
+        buffer.cursor = Cursor::at(2, 0);
+        assert_eq!(
+            buffer.selection(),
+            Some(Position::new(2, 0)..Position::new(0, 2))
+        );
+
+        buffer.cursor = Cursor::at(6, 2);
+        assert_eq!(
+            buffer.selection(),
+            Some(Position::new(0, 1)..Position::new(8, 2))
+        );
+
+        buffer.clear_selection();
+        assert_eq!(buffer.selection(), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Shading the columns of the buffer in `colorcolumn`, such as at the maximum line length, and the
+//! line and column of the cursor when `cursorline` and `cursorcolumn` are set.
+
+use crate::theme;
+use crate::ui::{Bounds, Context, Coordinates, Style};
+
+use super::Buffer;
+
+impl Buffer {
+    /// Shades the line and column of the cursor, if `cursorline` or `cursorcolumn` is set, and the
+    /// columns in `colorcolumn`, on the lines within the viewport.
+    ///
+    /// The shading is drawn before the text, which is drawn over it with its own colors.
+    pub(super) fn draw_shading(&self, ctx: &mut Context<'_>) {
+        if self.window.cursorline {
+            self.draw_cursor_line(ctx);
+        }
+
+        self.draw_color_columns(ctx);
+
+        if self.window.cursorcolumn {
+            self.draw_cursor_column(ctx);
+        }
+    }
+
+    fn draw_cursor_line(&self, ctx: &mut Context<'_>) {
+        let style = match theme::current().style("cursorline") {
+            Some(style) => style,
+            None => return,
+        };
+
+        let rows = self
+            .display_lines()
+            .iter()
+            .enumerate()
+            .filter(|(_, display_line)| display_line.line == self.cursor.y())
+            .map(|(row, _)| row)
+            .collect::<Vec<_>>();
+
+        for row in rows {
+            let y = ctx.bounds.min.y + row as u16;
+            if y >= ctx.bounds.max.y {
+                break;
+            }
+
+            ctx.screen.apply_style(
+                Bounds::new(
+                    Coordinates::new(ctx.bounds.min.x, y),
+                    Coordinates::new(ctx.bounds.max.x, y + 1),
+                ),
+                style,
+            );
+        }
+    }
+
+    fn draw_cursor_column(&self, ctx: &mut Context<'_>) {
+        if let Some(style) = theme::current().style("cursorcolumn") {
+            self.draw_column(ctx, self.cursor_column(), style);
+        }
+    }
+
+    /// Shades the columns in `colorcolumn` on the lines within the viewport.
+    fn draw_color_columns(&self, ctx: &mut Context<'_>) {
+        if let Some(style) = theme::current().style("colorcolumn") {
+            for column in self.window.colorcolumn.iter() {
+                self.draw_column(ctx, column - 1, style);
+            }
+        }
+    }
+
+    /// Shades a screen column on the lines within the viewport, where it's visible.
+    fn draw_column(&self, ctx: &mut Context<'_>, x: usize, style: Style) {
+        let origin = ctx.bounds.min;
+
+        for (row, display_line) in self.display_lines().iter().enumerate() {
+            if !display_line.columns.contains(&x) {
+                continue;
+            }
+
+            let x = origin.x + (x - display_line.columns.start) as u16;
+            let y = origin.y + row as u16;
+            let bounds = Bounds::new(Coordinates::new(x, y), Coordinates::new(x + 1, y + 1))
+                .intersection(&ctx.bounds);
+
+            if !bounds.is_empty() {
+                ctx.screen.apply_style(bounds, style);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::rect;
+
+    use crate::buffer::{Buffer, Position};
+    use crate::theme;
+    use crate::ui::{Bounds, Context, Screen, Size};
+
+    #[test]
+    fn draw_color_columns() {
+        let mut buffer = Buffer::from("abcdef\nghijkl\n");
+        buffer.window.colorcolumn = "2,4,20".parse().unwrap();
+        buffer.viewport = Some(rect(1, 0, 4, 3));
+
+        let size = Size::new(4, 3);
+        let mut screen = Screen::new(size);
+        buffer.draw_shading(&mut Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        });
+
+        let shaded = theme::current().style("colorcolumn").unwrap().background;
+        assert_eq!(screen[(0, 0)].background, shaded);
+        assert_eq!(screen[(1, 2)].background, shaded);
+        assert_eq!(screen[(0, 1)].background, None);
+
+        // Rows past the end of the buffer aren't shaded.
+        assert_eq!(screen[(2, 0)].background, None);
+    }
+
+    #[test]
+    fn draw_cursor_line_and_column() {
+        let mut buffer = Buffer::from("abc\ndef\nghi\n");
+        buffer.window.cursorline = true;
+        buffer.window.cursorcolumn = true;
+        buffer.viewport = Some(rect(0, 0, 3, 3));
+        buffer.jump_to(Position::new(1, 1));
+
+        let size = Size::new(3, 3);
+        let mut screen = Screen::new(size);
+        buffer.draw_shading(&mut Context {
+            bounds: Bounds::from_size(size),
+            screen: &mut screen,
+        });
+
+        let line = theme::current().style("cursorline").unwrap().background;
+        let column = theme::current().style("cursorcolumn").unwrap().background;
+        assert_eq!(screen[(1, 0)].background, line);
+        assert_eq!(screen[(1, 2)].background, line);
+        assert_eq!(screen[(0, 1)].background, column);
+        assert_eq!(screen[(1, 1)].background, column);
+        assert_eq!(screen[(0, 0)].background, None);
+    }
+}
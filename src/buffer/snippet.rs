@@ -0,0 +1,413 @@
+//! Snippets from language servers, and the tab stops of the snippet being filled in.
+//!
+//! Completions may be snippets, where `$1` or `${1:placeholder}` mark the tab stops and `$0` marks
+//! where the cursor ends up. A snippet is inserted as its text with the placeholders filled in,
+//! and `<Tab>` in insert mode then moves the cursor to each tab stop in order. Moving to a
+//! placeholder selects it, so that typing replaces it.
+//!
+//! The tab stops are byte ranges of the buffer, which are moved by each edit, like the syntax
+//! tree. They're forgotten when the last one is visited or insert mode is left.
+
+use std::ops::Range;
+
+use crate::buffer::units::ByteIndex;
+use crate::theme;
+use crate::ui::Context;
+
+use super::{edit::Edit, Buffer};
+
+/// The text of a snippet, and the tab stops within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+
+    /// The byte ranges of the tab stops in the text, in the order that they're visited. The last
+    /// is where the cursor ends up, which is the end of the text unless the snippet has a `$0`.
+    pub tab_stops: Vec<Range<usize>>,
+}
+
+impl Snippet {
+    /// Parses a snippet in the syntax of the language server protocol.
+    ///
+    /// The editor doesn't know the values of variables such as `$TM_FILENAME`, so they're replaced
+    /// by their default, if any. Choices are replaced by their first option. Text that isn't valid
+    /// snippet syntax is kept as it's written.
+    pub fn parse(snippet: &str) -> Self {
+        let mut parser = Parser {
+            input: snippet,
+            pos: 0,
+            text: String::new(),
+            tab_stops: vec![],
+        };
+        parser.parse(false);
+
+        // Tab stops that share a number mirror each other, but only the first is visited.
+        let mut tab_stops = parser.tab_stops;
+        tab_stops.sort_by_key(|&(number, _)| (number == 0, number));
+        tab_stops.dedup_by_key(|&mut (number, _)| number);
+
+        if tab_stops.last().map_or(true, |&(number, _)| number != 0) {
+            let end = parser.text.len();
+            tab_stops.push((0, end..end));
+        }
+
+        Snippet {
+            text: parser.text,
+            tab_stops: tab_stops.into_iter().map(|(_, range)| range).collect(),
+        }
+    }
+}
+
+/// Fills in the placeholders of a snippet.
+struct Parser<'a> {
+    input: &'a str,
+
+    /// The byte offset in the input of the next character to parse.
+    pos: usize,
+
+    text: String,
+
+    /// The number and the range in the text of each tab stop, in the order that they appear.
+    tab_stops: Vec<(u32, Range<usize>)>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    /// Parses text up to the end of the input, or up to the `}` that closes a placeholder if
+    /// `nested` is set.
+    fn parse(&mut self, nested: bool) {
+        while let Some(c) = self.rest().chars().next() {
+            match c {
+                '}' if nested => return,
+                '\\' => match self.rest()[1..].chars().next() {
+                    Some(escaped) if matches!(escaped, '$' | '}' | '\\') => {
+                        self.text.push(escaped);
+                        self.pos += 2;
+                    }
+                    _ => {
+                        self.text.push('\\');
+                        self.pos += 1;
+                    }
+                },
+                '$' if self.parse_dollar() => (),
+                c => {
+                    self.text.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Parses the tab stop, placeholder, choice, or variable that starts with the `$` at the
+    /// current position. Returns `false` without parsing anything if the `$` doesn't start one.
+    fn parse_dollar(&mut self) -> bool {
+        let rest = &self.rest()[1..];
+        let braced = rest.starts_with('{');
+        let rest = if braced { &rest[1..] } else { rest };
+        let header = if braced { 2 } else { 1 };
+
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits > 0 {
+            let number = match rest[..digits].parse() {
+                Ok(number) => number,
+                Err(_) => return false,
+            };
+
+            let start = self.text.len();
+            if !braced {
+                self.pos += header + digits;
+                self.tab_stops.push((number, start..start));
+                return true;
+            }
+
+            let parsed = match rest[digits..].chars().next() {
+                Some('}') => {
+                    self.pos += header + digits + 1;
+                    true
+                }
+                Some(':') => self.parse_nested(header + digits + 1),
+                Some('|') => self.parse_choice(header + digits + 1),
+                _ => false,
+            };
+            if parsed {
+                self.tab_stops.push((number, start..self.text.len()));
+            }
+            return parsed;
+        }
+
+        let name = rest
+            .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+            .unwrap_or(rest.len());
+        if name == 0 {
+            return false;
+        }
+
+        if !braced {
+            self.pos += header + name;
+            return true;
+        }
+
+        match rest[name..].chars().next() {
+            Some('}') => {
+                self.pos += header + name + 1;
+                true
+            }
+            Some(':') => self.parse_nested(header + name + 1),
+            Some('/') => self.skip_transform(header + name + 1),
+            _ => false,
+        }
+    }
+
+    /// Parses the text of a placeholder or a variable's default, which starts `skip` bytes after
+    /// the current position, up to its closing `}`. If it isn't closed, nothing is parsed.
+    fn parse_nested(&mut self, skip: usize) -> bool {
+        let (pos, len, tab_stops) = (self.pos, self.text.len(), self.tab_stops.len());
+
+        self.pos += skip;
+        self.parse(true);
+
+        if self.rest().starts_with('}') {
+            self.pos += 1;
+            true
+        } else {
+            self.pos = pos;
+            self.text.truncate(len);
+            self.tab_stops.truncate(tab_stops);
+            false
+        }
+    }
+
+    /// Parses the options of a choice, which start `skip` bytes after the current position, and
+    /// fills in the first.
+    fn parse_choice(&mut self, skip: usize) -> bool {
+        let choices = &self.rest()[skip..];
+        let mut first = String::new();
+        let mut in_first = true;
+        let mut escaped = false;
+
+        for (index, c) in choices.char_indices() {
+            match c {
+                _ if escaped => {
+                    escaped = false;
+                    if in_first {
+                        first.push(c);
+                    }
+                }
+                '\\' => escaped = true,
+                '|' if choices[index + 1..].starts_with('}') => {
+                    self.text.push_str(&first);
+                    self.pos += skip + index + 2;
+                    return true;
+                }
+                ',' => in_first = false,
+                _ if in_first => first.push(c),
+                _ => (),
+            }
+        }
+
+        false
+    }
+
+    /// Skips over the regular expression and format of a variable's transform, which start
+    /// `skip` bytes after the current position, since the variable's value isn't known.
+    fn skip_transform(&mut self, skip: usize) -> bool {
+        let transform = &self.rest()[skip..];
+        let mut depth = 0;
+        let mut escaped = false;
+
+        for (index, c) in transform.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                '}' => {
+                    self.pos += skip + index + 1;
+                    return true;
+                }
+                _ => (),
+            }
+        }
+
+        false
+    }
+}
+
+/// The tab stops of the snippet being filled in.
+#[derive(Debug, Default)]
+pub(super) struct TabStops {
+    /// The tab stops that haven't been visited yet, in the order that they're visited.
+    remaining: Vec<Range<ByteIndex>>,
+
+    /// The placeholder that the cursor was moved to, until the next edit.
+    selected: Option<Range<ByteIndex>>,
+}
+
+impl TabStops {
+    /// Moves the tab stops to where their text is after an edit.
+    ///
+    /// Tab stops after the edit are moved by the change in length, and tab stops that the edit
+    /// was made in grow or shrink. Text inserted at the start of a tab stop is before it.
+    pub(super) fn edit(&mut self, edit: &Edit) {
+        self.selected = None;
+
+        let start = edit.range.start.0;
+        let old_end = edit.range.end.0;
+        let new_end = edit.new_end().0;
+
+        for range in &mut self.remaining {
+            let (range_start, range_end) = (range.start.0, range.end.0);
+
+            *range = if old_end <= range_start {
+                ByteIndex::new(range_start - old_end + new_end)
+                    ..ByteIndex::new(range_end - old_end + new_end)
+            } else if start <= range_end {
+                ByteIndex::new(range_start.min(start))
+                    ..ByteIndex::new(range_end.max(old_end) - old_end + new_end)
+            } else {
+                continue;
+            };
+        }
+    }
+}
+
+impl Buffer {
+    /// Starts filling in the tab stops of a snippet that an edit inserted, forgetting those of any
+    /// other snippet. The tab stops are byte ranges of the edit's new text.
+    pub fn set_tab_stops(&mut self, edit: &Edit, tab_stops: &[Range<usize>]) {
+        let start = edit.range.start;
+
+        self.tab_stops = TabStops {
+            remaining: tab_stops
+                .iter()
+                .map(|range| start + ByteIndex::new(range.start)..start + ByteIndex::new(range.end))
+                .collect(),
+            selected: None,
+        };
+    }
+
+    /// Forgets the tab stops of the snippet being filled in.
+    pub fn clear_tab_stops(&mut self) {
+        self.tab_stops = TabStops::default();
+    }
+
+    /// Moves the cursor to the next tab stop of the snippet being filled in, selecting its
+    /// placeholder. Returns `false` if there are no tab stops left.
+    pub fn next_tab_stop(&mut self) -> bool {
+        if self.tab_stops.remaining.is_empty() {
+            return false;
+        }
+
+        let range = self.tab_stops.remaining.remove(0);
+        self.jump_to(self.storage.position_of_byte(range.start).cast_unit());
+
+        if range.start < range.end {
+            self.tab_stops.selected = Some(range);
+        }
+
+        true
+    }
+
+    /// Returns the byte range of the placeholder that the cursor was moved to, if the cursor is
+    /// still at its start and nothing has been edited since.
+    pub(super) fn selected_placeholder(&self) -> Option<Range<ByteIndex>> {
+        let cursor = self.storage.line_to_byte(self.cursor.y()) + ByteIndex::new(self.cursor.x());
+
+        self.tab_stops
+            .selected
+            .clone()
+            .filter(|range| range.start == cursor)
+    }
+
+    /// Highlights the placeholder that the cursor was moved to.
+    pub(super) fn draw_placeholder(&self, ctx: &mut Context<'_>) {
+        let range = match self.selected_placeholder() {
+            Some(range) => range,
+            None => return,
+        };
+
+        let range = self.storage.position_of_byte(range.start).cast_unit()
+            ..self.storage.position_of_byte(range.end).cast_unit();
+        if let Some(style) = theme::current().style("placeholder") {
+            self.style_range(ctx, range, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, Position};
+
+    use super::Snippet;
+
+    #[test]
+    fn parse_tab_stops() {
+        let snippet = Snippet::parse("fn ${1:name}($2) {\n\t$0\n}");
+        assert_eq!(snippet.text, "fn name() {\n\t\n}");
+        assert_eq!(snippet.tab_stops, vec![3..7, 8..8, 13..13]);
+
+        let snippet = Snippet::parse("foo($1, $2)");
+        assert_eq!(snippet.text, "foo(, )");
+        assert_eq!(snippet.tab_stops, vec![4..4, 6..6, 7..7]);
+
+        let snippet = Snippet::parse("${2:b} ${1:a} $2");
+        assert_eq!(snippet.text, "b a ");
+        assert_eq!(snippet.tab_stops, vec![2..3, 0..1, 4..4]);
+
+        let snippet = Snippet::parse("${1:foo ${2:bar}}");
+        assert_eq!(snippet.text, "foo bar");
+        assert_eq!(snippet.tab_stops, vec![0..7, 4..7, 7..7]);
+    }
+
+    #[test]
+    fn parse_choices_and_variables() {
+        let snippet = Snippet::parse("${1|one,t\\,wo|}");
+        assert_eq!(snippet.text, "one");
+        assert_eq!(snippet.tab_stops, vec![0..3, 3..3]);
+
+        let snippet = Snippet::parse("$TM_FILENAME ${TM_X:default} ${TM_Y/(.*)/${1:/upcase}/}.");
+        assert_eq!(snippet.text, " default .");
+        assert_eq!(snippet.tab_stops, vec![10..10]);
+    }
+
+    #[test]
+    fn parse_invalid_syntax() {
+        let snippet = Snippet::parse("\\$1 \\} $ ${x");
+        assert_eq!(snippet.text, "$1 } $ ${x");
+        assert_eq!(snippet.tab_stops, vec![10..10]);
+
+        let snippet = Snippet::parse("${1:foo");
+        assert_eq!(snippet.text, "${1:foo");
+        assert_eq!(snippet.tab_stops, vec![7..7]);
+    }
+
+    #[test]
+    fn visit_tab_stops() {
+        let mut buffer = Buffer::new();
+
+        let snippet = Snippet::parse("foo(${1:a}, ${2:b})$0");
+        let edit = buffer.replace_before_cursor(0, &snippet.text);
+        buffer.set_tab_stops(&edit, &snippet.tab_stops);
+
+        assert!(buffer.next_tab_stop());
+        assert_eq!(buffer.cursor(), Position::new(4, 0));
+        assert!(buffer.delete_placeholder().is_some());
+        buffer.insert_str("xyz");
+        assert_eq!(buffer.storage.to_string(), "foo(xyz, b)\n");
+
+        // Moving away from a placeholder keeps it from being replaced.
+        assert!(buffer.next_tab_stop());
+        assert_eq!(buffer.cursor(), Position::new(9, 0));
+        buffer.move_left();
+        assert!(buffer.delete_placeholder().is_none());
+
+        assert!(buffer.next_tab_stop());
+        assert_eq!(buffer.cursor(), Position::new(11, 0));
+        assert!(!buffer.next_tab_stop());
+    }
+}
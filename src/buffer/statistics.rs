@@ -0,0 +1,160 @@
+//! Counts of the lines, words, and bytes of a buffer and its selection, for `g<C-g>`.
+
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use super::Buffer;
+
+/// The position of the cursor in a buffer, counted in columns, lines, words, and bytes from one,
+/// along with the totals of each.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Statistics {
+    pub column: usize,
+    pub columns: usize,
+    pub line: usize,
+    pub lines: usize,
+    pub word: usize,
+    pub words: usize,
+    pub byte: usize,
+    pub bytes: usize,
+
+    /// The counts of the selected text, if any.
+    pub selected: Option<Selected>,
+}
+
+/// The number of lines, words, and bytes of the text selected in visual mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Selected {
+    pub lines: usize,
+    pub words: usize,
+    pub bytes: usize,
+}
+
+impl Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(selected) = self.selected {
+            return write!(
+                f,
+                "Selected {} of {} Lines; {} of {} Words; {} of {} Bytes",
+                selected.lines, self.lines, selected.words, self.words, selected.bytes, self.bytes
+            );
+        }
+
+        write!(
+            f,
+            "Col {} of {}; Line {} of {}; Word {} of {}; Byte {} of {}",
+            self.column,
+            self.columns,
+            self.line,
+            self.lines,
+            self.word,
+            self.words,
+            self.byte,
+            self.bytes
+        )
+    }
+}
+
+impl Buffer {
+    /// Counts the lines, words, and bytes of the buffer, and where the cursor is among them.
+    ///
+    /// Words are separated by whitespace. The cursor is in the last word that starts at or before
+    /// it. In visual mode, the selected text is counted too.
+    pub fn statistics(&self) -> Statistics {
+        let (x, y) = (self.cursor.x(), self.cursor.y());
+        let cursor_byte = self.storage.line_to_byte(y).0 + x;
+
+        let mut word = 0;
+        let mut words = 0;
+        for (y, line) in self.storage.iter_lines().enumerate() {
+            let line_start = self.storage.line_to_byte(y).0;
+
+            for x in word_starts(line) {
+                words += 1;
+                if line_start + x <= cursor_byte {
+                    word = words;
+                }
+            }
+        }
+
+        Statistics {
+            column: x + 1,
+            columns: self.storage.line(y).len(),
+            line: y + 1,
+            lines: self.storage.lines(),
+            word,
+            words,
+            byte: cursor_byte + 1,
+            bytes: self.storage.len(),
+            selected: self.selected(),
+        }
+    }
+
+    /// Counts the lines, words, and bytes of the selected text. A selection that ends with a
+    /// newline doesn't include the line after it.
+    fn selected(&self) -> Option<Selected> {
+        let Range { start, end } = self.selection()?;
+        let bytes = self.selection_bytes()?;
+        let text = self.storage.text_in(bytes.start.0..bytes.end.0);
+
+        Some(Selected {
+            lines: end.y - start.y + usize::from(end.x > 0 || end.y == start.y),
+            words: text.split_whitespace().count(),
+            bytes: text.len(),
+        })
+    }
+}
+
+/// Returns the byte columns where the words of a line start.
+fn word_starts(line: &str) -> impl Iterator<Item = usize> + '_ {
+    line.char_indices()
+        .scan(true, |after_space, (x, c)| {
+            let start = *after_space && !c.is_whitespace();
+            *after_space = c.is_whitespace();
+            Some((x, start))
+        })
+        .filter(|&(_, start)| start)
+        .map(|(x, _)| x)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, Position};
+
+    use super::word_starts;
+
+    #[test]
+    fn find_word_starts() {
+        assert_eq!(
+            word_starts("foo  bar\tbaz").collect::<Vec<_>>(),
+            vec![0, 5, 9]
+        );
+        assert_eq!(word_starts("  foo").collect::<Vec<_>>(), vec![2]);
+        assert_eq!(word_starts("").count(), 0);
+    }
+
+    #[test]
+    fn statistics() {
+        let mut buffer = Buffer::from("one two\nthree\n\nfour five six");
+        buffer.jump_to(Position::new(2, 1));
+
+        assert_eq!(
+            buffer.statistics().to_string(),
+            "Col 3 of 5; Line 2 of 4; Word 3 of 6; Byte 11 of 29"
+        );
+
+        buffer.jump_to(Position::new(4, 0));
+        buffer.start_selection();
+        buffer.jump_to(Position::new(1, 1));
+        assert_eq!(
+            buffer.statistics().to_string(),
+            "Selected 2 of 4 Lines; 2 of 6 Words; 6 of 29 Bytes"
+        );
+
+        buffer.jump_to(Position::new(0, 2));
+        assert_eq!(
+            buffer.statistics().to_string(),
+            "Selected 3 of 4 Lines; 2 of 6 Words; 11 of 29 Bytes"
+        );
+    }
+}
@@ -3,54 +3,118 @@ use std::fmt;
 use std::iter;
 use std::ops::{Index, Range};
 
-use unicode_width::UnicodeWidthStr;
-
 use crate::buffer::units::{ByteIndex, BytePosition, CharPosition};
 
+/// The most lines that a block holds.
+///
+/// An edit rebuilds the line index of the block that it changes, and then moves the blocks after
+/// it, so its cost depends on the size and number of blocks rather than the length of the buffer.
+const BLOCK_LINES: usize = 1024;
+
 /// Underlying storage for the buffer contents.
 ///
-/// The storage contains at least one (empty) line.
-#[derive(Debug, PartialEq, Eq)]
+/// The storage contains at least one (empty) line. The lines are kept in blocks of consecutive
+/// lines, so that large files can be edited without moving or reindexing every line after the
+/// edit.
+#[derive(Debug)]
 pub struct Storage {
-    /// The contents of the storage.
+    /// The blocks of lines, in order. There is at least one block, and no block is empty.
+    blocks: Vec<Block>,
+}
+
+/// Consecutive lines of the storage.
+#[derive(Debug)]
+struct Block {
+    /// The contents of the block.
     ///
     /// Unix-style newlines ("\n") are implicitly inserted between each line. Lines themselves
     /// cannot contain `\n`.
     lines: Vec<String>,
+
+    /// The byte offset of the start of each line from the start of the block.
+    ///
+    /// Lookups between byte indices and positions would otherwise walk every preceding line. The
+    /// index is updated from the first changed line after each edit.
+    line_starts: Vec<usize>,
+
+    /// The index of the first line of the block in the storage.
+    first_line: usize,
+
+    /// The byte index of the start of the block in the storage.
+    start: usize,
+}
+
+impl Block {
+    fn new(lines: Vec<String>) -> Self {
+        let mut block = Block {
+            lines,
+            line_starts: vec![],
+            first_line: 0,
+            start: 0,
+        };
+        block.update_line_starts(0);
+        block
+    }
+
+    /// Returns the length of the block in bytes, including the newline after each line.
+    fn len(&self) -> usize {
+        match (self.lines.last(), self.line_starts.last()) {
+            (Some(line), Some(start)) => start + line.len() + 1,
+            _ => 0,
+        }
+    }
+
+    /// Recomputes the start of each line from a line onwards.
+    fn update_line_starts(&mut self, from: usize) {
+        self.line_starts.truncate(from);
+
+        let mut start = match from.checked_sub(1) {
+            Some(previous) => self.line_starts[previous] + self.lines[previous].len() + 1,
+            None => 0,
+        };
+
+        for line in &self.lines[from..] {
+            self.line_starts.push(start);
+            start += line.len() + 1;
+        }
+    }
 }
 
 impl Storage {
     /// Returns a new `Storage` with a single empty line.
     pub fn new() -> Self {
-        Self {
-            lines: vec![String::new()],
-        }
+        Self::from(vec![String::new()])
     }
 
     /// Returns the number of lines.
     pub fn lines(&self) -> usize {
-        self.lines.len()
+        let last = self.last_block();
+        last.first_line + last.lines.len()
     }
 
     /// Returns the total byte length of the buffer.
     pub fn len(&self) -> usize {
-        let mut len = 0;
-
-        for line in &self.lines {
-            len += line.len() + 1
-        }
+        let last = self.last_block();
+        last.start + last.len()
+    }
 
-        len
+    /// Returns the byte index of the start of a line.
+    pub fn line_to_byte(&self, line: usize) -> ByteIndex {
+        let block = &self.blocks[self.block_of_line(line)];
+        ByteIndex::new(block.start + block.line_starts[line - block.first_line])
     }
 
-    /// Returns width of a given line in columns.
-    pub fn line_width(&self, line: usize) -> usize {
-        self.lines[line].width()
+    /// Returns the text of a line, without its newline.
+    pub fn line(&self, line: usize) -> &str {
+        let block = &self.blocks[self.block_of_line(line)];
+        &block.lines[line - block.first_line]
     }
 
     /// Returns an iterator over the lines of the storage.
     pub fn iter_lines(&self) -> impl Iterator<Item = &str> {
-        self.lines.iter().map(|line| &**line)
+        self.blocks
+            .iter()
+            .flat_map(|block| block.lines.iter().map(|line| &**line))
     }
 
     /// Return a slice of the underlying text starting at the given position.
@@ -61,7 +125,7 @@ impl Storage {
             return "";
         }
 
-        let line = &self.lines[pos.y];
+        let line = self.line(pos.y);
 
         if pos.x == line.len() {
             "\n"
@@ -70,11 +134,32 @@ impl Storage {
         }
     }
 
+    /// Returns the text within a byte range, which may span several lines.
+    pub fn text_in(&self, range: Range<usize>) -> String {
+        let mut text = String::with_capacity(range.len());
+        let mut line = self.row_of_byte(range.start);
+        let mut offset = range.start - self.line_to_byte(line).0;
+
+        while text.len() < range.len() {
+            let rest = &self.line(line)[offset..];
+            let len = cmp::min(rest.len(), range.len() - text.len());
+            text.push_str(&rest[..len]);
+
+            if text.len() < range.len() {
+                text.push('\n');
+                line += 1;
+                offset = 0;
+            }
+        }
+
+        text
+    }
+
     /// Returns the character position of a given byte. The byte must lie on a character boundary.
     pub fn byte_to_char_position(&self, byte: ByteIndex) -> CharPosition {
         let byte_position = self.position_of_byte(byte);
 
-        let line = &self.lines[byte_position.y];
+        let line = self.line(byte_position.y);
 
         assert!(line.is_char_boundary(byte_position.x));
         let char_index = line
@@ -93,110 +178,213 @@ impl Storage {
         CharPosition::new(char_index, byte_position.y)
     }
 
+    /// Returns the byte index of a character position.
+    ///
+    /// Positions past the end of a line are clamped to the end of that line, and positions past
+    /// the last line are clamped to the end of the storage.
+    pub fn char_to_byte(&self, pos: CharPosition) -> ByteIndex {
+        if pos.y >= self.lines() {
+            return ByteIndex::new(self.len() - 1);
+        }
+
+        let line_start = self.line_to_byte(pos.y).0;
+
+        let line = self.line(pos.y);
+        let offset = line
+            .char_indices()
+            .nth(pos.x)
+            .map_or(line.len(), |(offset, _)| offset);
+
+        ByteIndex::new(line_start + offset)
+    }
+
     /// Returns the row and column of a byte index.
     pub fn position_of_byte(&self, byte: ByteIndex) -> BytePosition {
         let byte = byte.0;
         assert!(byte < self.len());
 
-        let mut remaining = byte;
+        let row = self.row_of_byte(byte);
+        BytePosition::new(byte - self.line_to_byte(row).0, row)
+    }
 
-        for (row, line) in self.iter_lines().enumerate() {
-            if remaining <= line.len() {
-                return BytePosition::new(remaining, row);
-            }
+    /// Returns the row containing a byte index, which may be the index of the newline at the end
+    /// of the row.
+    fn row_of_byte(&self, byte: usize) -> usize {
+        let block = match self.blocks.binary_search_by_key(&byte, |block| block.start) {
+            Ok(index) => &self.blocks[index],
+            Err(index) => &self.blocks[index - 1],
+        };
+
+        let row = match block.line_starts.binary_search(&(byte - block.start)) {
+            Ok(row) => row,
+            Err(row) => row - 1,
+        };
+        block.first_line + row
+    }
 
-            remaining -= line.len() + 1;
+    /// Returns the index of the block containing a line. Lines past the end are in the last block.
+    fn block_of_line(&self, line: usize) -> usize {
+        match self
+            .blocks
+            .binary_search_by_key(&line, |block| block.first_line)
+        {
+            Ok(index) => index,
+            Err(index) => index - 1,
         }
+    }
 
-        unreachable!();
+    fn last_block(&self) -> &Block {
+        self.blocks.last().expect("storage should have a block")
     }
 
-    /// Replace a byte range in the buffer with a replacement string, like
-    /// [`String::replace_range`].
-    pub fn replace_range(&mut self, range: Range<usize>, replacement: &str) {
-        // Find the line containing the start of the byte range, and the byte offset from the
-        // start of the line.
-        let mut line_no = 0;
-        let mut byte_offset = range.start;
-        while byte_offset > self.lines[line_no].len() {
-            byte_offset -= self.lines[line_no].len() + 1;
-            line_no += 1;
+    /// Recomputes the first line and start of each block from a block onwards.
+    fn update_block_positions(&mut self, from: usize) {
+        let (mut first_line, mut start) = match from.checked_sub(1) {
+            Some(previous) => {
+                let previous = &self.blocks[previous];
+                (
+                    previous.first_line + previous.lines.len(),
+                    previous.start + previous.len(),
+                )
+            }
+            None => (0, 0),
+        };
+
+        for block in &mut self.blocks[from..] {
+            block.first_line = first_line;
+            block.start = start;
+            first_line += block.lines.len();
+            start += block.len();
         }
+    }
 
-        // Delete any text that is inside the range.
-        let mut bytes_to_consume = range.len();
-
-        while bytes_to_consume > 0 {
-            let bytes_to_remove =
-                cmp::min(self.lines[line_no][byte_offset..].len(), bytes_to_consume);
-            self.lines[line_no].replace_range(byte_offset..(byte_offset + bytes_to_remove), "");
-
-            bytes_to_consume -= bytes_to_remove;
+    /// Replaces a range of lines with other lines, rebuilding the blocks that held them.
+    fn splice_lines(&mut self, rows: Range<usize>, new_lines: impl IntoIterator<Item = String>) {
+        let first_block = self.block_of_line(rows.start);
+        let last_block = cmp::max(self.block_of_line(rows.end.saturating_sub(1)), first_block);
+        let first_line = self.blocks[first_block].first_line;
+
+        let mut lines = self
+            .blocks
+            .drain(first_block..=last_block)
+            .flat_map(|block| block.lines)
+            .collect::<Vec<_>>();
+        lines.splice(rows.start - first_line..rows.end - first_line, new_lines);
+
+        // The lines are divided evenly, so that a block that has grown too large doesn't leave a
+        // tiny block behind.
+        let count = (lines.len() + BLOCK_LINES - 1) / BLOCK_LINES;
+        let mut blocks = vec![];
+        let mut lines = lines.into_iter().peekable();
+        while lines.peek().is_some() {
+            let size = (lines.len() + count - blocks.len() - 1) / (count - blocks.len());
+            blocks.push(Block::new(lines.by_ref().take(size).collect()));
+        }
 
-            if bytes_to_consume > 0 {
-                // Remove the newline.
-                let next_line = self.lines.remove(line_no + 1);
-                self.lines[line_no].insert_str(byte_offset, &next_line);
-                bytes_to_consume -= 1;
-            }
+        self.blocks.splice(first_block..first_block, blocks);
+        if self.blocks.is_empty() {
+            self.blocks.push(Block::new(vec![String::new()]));
         }
 
-        // Insert the new text.
-        if !replacement.contains('\n') {
-            // Fast path. Just insert the new text into the current line.
-            self.lines[line_no].insert_str(byte_offset, replacement);
-        } else {
-            // We're going to add at least one new line into the underlying lines array. Start by
-            // splitting the current line into two at the insertion point.
-            let end = self.lines[line_no].split_off(byte_offset);
-            self.lines.insert(line_no + 1, end);
+        self.update_block_positions(first_block);
+    }
 
-            let mut new_lines = replacement.lines().peekable();
+    /// Adds lines after the last line. If the storage is a single empty line, the lines replace it.
+    pub fn append_lines(&mut self, lines: impl IntoIterator<Item = String>) {
+        if self.lines() == 1 && self.line(0).is_empty() {
+            self.splice_lines(0..1, lines);
+        } else {
+            self.push_lines(lines);
+        }
+    }
 
-            // The first new line is appended at the insertion point.
-            let first_new_line = new_lines
-                .next()
-                .expect("checked replacement text contains newline above");
-            self.lines[line_no].push_str(first_new_line);
+    /// Adds lines after the last line, even if it's empty.
+    pub fn push_lines(&mut self, lines: impl IntoIterator<Item = String>) {
+        let end = self.lines();
+        self.splice_lines(end..end, lines);
+    }
 
-            while let Some(new_line) = new_lines.next() {
-                line_no += 1;
+    /// Replace a byte range in the buffer with a replacement string, like
+    /// [`String::replace_range`].
+    pub fn replace_range(&mut self, range: Range<usize>, replacement: &str) {
+        // Find the lines containing the ends of the byte range, and the byte offsets from the
+        // start of each line.
+        let first_line = self.row_of_byte(range.start);
+        let start_offset = range.start - self.line_to_byte(first_line).0;
+        let last_line = self.row_of_byte(range.end);
+        let end_offset = range.end - self.line_to_byte(last_line).0;
+
+        if first_line == last_line && !replacement.contains('\n') {
+            // Fast path. Just replace the text within the line.
+            let index = self.block_of_line(first_line);
+            let block = &mut self.blocks[index];
+            let row = first_line - block.first_line;
+
+            block.lines[row].replace_range(start_offset..end_offset, replacement);
+            block.update_line_starts(row + 1);
+            self.update_block_positions(index + 1);
+            return;
+        }
 
-                if new_lines.peek().is_some() {
-                    // Middle new lines, if any, are inserted as their own lines.
-                    self.lines.insert(line_no, new_line.to_owned());
-                } else {
-                    // The last new line is prepended to line split after the insertion point.
-                    self.lines[line_no].insert_str(0, new_line);
+        // Unlike `str::lines`, splitting keeps the empty line after a trailing newline, which is
+        // the start of the line after the range.
+        let new_lines = replacement
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect::<Vec<_>>();
+        let last = new_lines.len() - 1;
+
+        // The first new line follows the text before the range, and the last new line is followed
+        // by the text after it.
+        let before = &self.line(first_line)[..start_offset];
+        let after = &self.line(last_line)[end_offset..];
+        let new_lines = new_lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let mut new_line = String::new();
+                if i == 0 {
+                    new_line.push_str(before);
                 }
-            }
-        }
+                new_line.push_str(line);
+                if i == last {
+                    new_line.push_str(after);
+                }
+                new_line
+            })
+            .collect::<Vec<_>>();
+
+        self.splice_lines(first_line..last_line + 1, new_lines);
     }
 }
 
 impl From<Vec<String>> for Storage {
     fn from(lines: Vec<String>) -> Self {
-        Self {
-            lines: if lines.is_empty() {
-                vec![String::new()]
-            } else {
-                lines
-            },
-        }
+        let mut storage = Self {
+            blocks: vec![Block::new(vec![String::new()])],
+        };
+        storage.splice_lines(0..1, lines);
+        storage
     }
 }
 
 impl<'a> From<&'a str> for Storage {
     fn from(s: &str) -> Self {
-        Self {
-            lines: s.lines().map(|line| line.to_owned()).collect(),
-        }
+        Self::from(s.lines().map(|line| line.to_owned()).collect::<Vec<_>>())
     }
 }
 
+impl PartialEq for Storage {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter_lines().eq(other.iter_lines())
+    }
+}
+
+impl Eq for Storage {}
+
 impl fmt::Display for Storage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for line in &self.lines {
+        for line in self.iter_lines() {
             writeln!(f, "{}", line)?;
         }
 
@@ -213,7 +401,7 @@ impl Index<Range<BytePosition>> for Storage {
             "cannot index across rows: {:?}",
             start..end
         );
-        &self.lines[start.y][start.x..end.x]
+        &self.line(start.y)[start.x..end.x]
     }
 }
 
@@ -221,27 +409,12 @@ impl Index<Range<BytePosition>> for Storage {
 mod tests {
     use indoc::indoc;
 
-    use super::{ByteIndex, BytePosition, Storage};
+    use super::{ByteIndex, BytePosition, CharPosition, Storage, BLOCK_LINES};
 
     #[test]
     fn from_empty_lines() {
         let storage = Storage::from(vec![]);
-        assert_eq!(storage.lines, vec![String::new()]);
-    }
-
-    #[test]
-    fn line_width() {
-        let storage = Storage::from(indoc! {"
-            aeioucsz
-            áéíóúčšž
-            台北1234
-            ＱＲＳ12
-            ｱｲｳ12345
-        "});
-
-        for line in 0..storage.lines() {
-            assert_eq!(storage.line_width(line), 8);
-        }
+        assert_eq!(storage.iter_lines().collect::<Vec<_>>(), vec![""]);
     }
 
     #[test]
@@ -271,6 +444,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn char_to_byte() {
+        let storage = Storage::from("aé\nb");
+
+        assert_eq!(
+            storage.char_to_byte(CharPosition::new(0, 0)),
+            ByteIndex::new(0)
+        );
+        assert_eq!(
+            storage.char_to_byte(CharPosition::new(2, 0)),
+            ByteIndex::new(3)
+        );
+        assert_eq!(
+            storage.char_to_byte(CharPosition::new(0, 1)),
+            ByteIndex::new(4)
+        );
+        assert_eq!(
+            storage.char_to_byte(CharPosition::new(10, 0)),
+            ByteIndex::new(3)
+        );
+        assert_eq!(
+            storage.char_to_byte(CharPosition::new(0, 2)),
+            ByteIndex::new(5)
+        );
+    }
+
+    #[test]
+    fn text_in() {
+        let storage = Storage::from(indoc! {"
+            Lorem ipsum
+            dolor
+            sit amet
+        "});
+
+        assert_eq!(storage.text_in(6..11), "ipsum");
+        assert_eq!(storage.text_in(6..14), "ipsum\ndo");
+        assert_eq!(storage.text_in(12..27), "dolor\nsit amet\n");
+        assert_eq!(storage.text_in(3..3), "");
+    }
+
     #[test]
     fn replace_range_deletion() {
         let mut storage = Storage::from("Goodbye, cruel world!");
@@ -310,6 +523,15 @@ mod tests {
         assert_eq!(storage.to_string(), "ab\nc\nde\n");
     }
 
+    #[test]
+    fn replace_range_replacement_ends_with_newline() {
+        let mut storage = Storage::from("ad");
+
+        storage.replace_range(1..1, "b\nc\n");
+
+        assert_eq!(storage.to_string(), "ab\nc\nd\n");
+    }
+
     #[test]
     fn replace_range_at_end_of_line() {
         let mut storage = Storage::from("a\n");
@@ -318,4 +540,57 @@ mod tests {
 
         assert_eq!(storage.to_string(), "ab\n");
     }
+
+    #[test]
+    fn line_starts_after_edits() {
+        let mut storage = Storage::from("one\ntwo\nthree\nfour");
+
+        storage.replace_range(5..5, "x\ny\n");
+        storage.replace_range(0..4, "");
+        storage.replace_range(10..12, "");
+
+        assert_eq!(storage.to_string(), "tx\ny\nwo\nthe\nfour\n");
+        assert_eq!(storage, Storage::from(storage.to_string().as_str()));
+        assert_eq!(storage.line_to_byte(2), ByteIndex::new(5));
+        assert_eq!(storage.len(), 17);
+        assert_eq!(
+            storage.position_of_byte(ByteIndex::new(11)),
+            BytePosition::new(3, 3)
+        );
+    }
+
+    #[test]
+    fn edits_across_blocks() {
+        let mut text = (0..BLOCK_LINES * 3)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let mut storage = Storage::from(text.as_str());
+        assert_eq!(storage.blocks.len(), 3);
+
+        let start = storage.line_to_byte(BLOCK_LINES - 2).0 + 2;
+        let end = storage.line_to_byte(BLOCK_LINES * 2 + 1).0 + 3;
+        storage.replace_range(start..end, "x\ny");
+        text.replace_range(start..end, "x\ny");
+        assert_eq!(storage.to_string(), text);
+
+        let lines = (0..BLOCK_LINES * 2)
+            .map(|i| format!("new {}\n", i))
+            .collect::<String>();
+        storage.replace_range(4..4, &lines);
+        text.replace_range(4..4, &lines);
+        assert_eq!(storage.to_string(), text);
+
+        storage.append_lines(vec![String::from("last")]);
+        text.push_str("last\n");
+        assert_eq!(storage.to_string(), text);
+
+        assert_eq!(storage.lines(), text.lines().count());
+        assert_eq!(storage.len(), text.len());
+        for (row, line) in text.lines().enumerate().step_by(97) {
+            let start = storage.line_to_byte(row);
+            assert_eq!(storage.line(row), line);
+            assert_eq!(&text[start.0..start.0 + line.len()], line);
+            assert_eq!(storage.position_of_byte(start), BytePosition::new(0, row));
+        }
+    }
 }
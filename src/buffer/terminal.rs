@@ -0,0 +1,226 @@
+//! Buffers that show a program running in a terminal, opened with `:terminal`.
+//!
+//! The program's output is fed to a terminal emulator, and the buffer's contents are replaced with
+//! the emulator's screen whenever it changes, with the cursor where the program left it. In
+//! terminal mode, keys are sent to the program instead of being handled by the editor. Terminal
+//! buffers can't be edited otherwise.
+
+use std::process::ExitStatus;
+
+use tokio::io;
+use unicode_width::UnicodeWidthChar;
+
+use crate::pty::Pty;
+use crate::term::Key;
+use crate::ui::Size;
+
+use super::{Buffer, Position, Storage};
+
+/// A terminal emulator and the program running in it.
+pub(super) struct Emulator {
+    /// Identifies the buffer in the output of its program.
+    id: usize,
+
+    /// The command line that was run, shown in the status line.
+    title: String,
+
+    /// `None` once the program has exited.
+    pty: Option<Pty>,
+
+    parser: vt100::Parser,
+}
+
+impl Buffer {
+    /// Creates a buffer showing a program running in a pseudoterminal of a size.
+    pub fn from_terminal(id: usize, pty: Pty, size: Size) -> Self {
+        let mut buffer = Buffer {
+            terminal: Some(Emulator {
+                id,
+                title: pty.command_line().to_owned(),
+                pty: Some(pty),
+                parser: vt100::Parser::new(size.height, size.width, 0),
+            }),
+            ..Buffer::new()
+        };
+
+        buffer.update_terminal();
+        buffer
+    }
+
+    /// Returns the ID of the terminal program that the buffer shows, if any.
+    pub fn terminal_id(&self) -> Option<usize> {
+        self.terminal.as_ref().map(|terminal| terminal.id)
+    }
+
+    /// Returns the command line that the buffer's terminal is running, if any.
+    pub fn terminal_title(&self) -> Option<&str> {
+        self.terminal
+            .as_ref()
+            .map(|terminal| terminal.title.as_str())
+    }
+
+    /// Returns whether the buffer shows a terminal whose program is still running.
+    pub fn is_terminal_running(&self) -> bool {
+        matches!(&self.terminal, Some(Emulator { pty: Some(_), .. }))
+    }
+
+    /// Feeds output from the terminal's program to the emulator.
+    pub fn terminal_output(&mut self, output: &[u8]) {
+        if let Some(terminal) = &mut self.terminal {
+            terminal.parser.process(output);
+            self.update_terminal();
+        }
+    }
+
+    /// Marks the terminal's program as exited, showing its exit status at the end of the buffer.
+    pub fn terminal_exited(&mut self, status: &io::Result<ExitStatus>) {
+        if let Some(terminal) = &mut self.terminal {
+            terminal.pty = None;
+
+            let status = match status {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            let output = format!("\r\n[process exited: {}]", status);
+            terminal.parser.process(output.as_bytes());
+            self.update_terminal();
+        }
+    }
+
+    /// Sends a key to the terminal's program, if it's still running.
+    pub fn send_key(&mut self, key: Key) -> io::Result<()> {
+        let application_cursor = match &self.terminal {
+            Some(terminal) => terminal.parser.screen().application_cursor(),
+            None => return Ok(()),
+        };
+
+        self.write_to_terminal(&encode_key(key, application_cursor))
+    }
+
+    /// Sends text to the terminal's program as if it were typed, if it's still running.
+    pub fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_to_terminal(text.as_bytes())
+    }
+
+    fn write_to_terminal(&mut self, input: &[u8]) -> io::Result<()> {
+        match &mut self.terminal {
+            Some(Emulator { pty: Some(pty), .. }) => pty.write(input),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resizes the terminal to fill the buffer's viewport, if the buffer is visible and the size
+    /// changed.
+    pub fn fit_terminal(&mut self) -> io::Result<()> {
+        let size = match self.viewport {
+            Some(viewport) => Size::new(viewport.width() as u16, viewport.height() as u16),
+            None => return Ok(()),
+        };
+
+        if let Some(terminal) = &mut self.terminal {
+            if terminal.parser.screen().size() == (size.height, size.width) {
+                return Ok(());
+            }
+
+            terminal.parser.set_size(size.height, size.width);
+            if let Some(pty) = &terminal.pty {
+                pty.resize(size)?;
+            }
+            self.update_terminal();
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the contents of the buffer with the emulator's screen, moving the cursor to the
+    /// emulator's cursor.
+    fn update_terminal(&mut self) {
+        let screen = match &self.terminal {
+            Some(terminal) => terminal.parser.screen(),
+            None => return,
+        };
+
+        let (_, width) = screen.size();
+        let (row, column) = screen.cursor_position();
+        let mut lines = screen.rows(0, width).collect::<Vec<_>>();
+
+        // Trailing blanks aren't part of the rows, but the cursor may be after them.
+        let cursor_line = &mut lines[usize::from(row)];
+        let line_width = cursor_line
+            .chars()
+            .map(|c| c.width().unwrap_or(0))
+            .sum::<usize>();
+        let padding = usize::from(column).saturating_sub(line_width);
+        cursor_line.push_str(&" ".repeat(padding));
+        let x = byte_at_column(cursor_line, usize::from(column));
+
+        self.storage = Storage::from(lines);
+        self.jump_to(Position::new(x, usize::from(row)));
+    }
+}
+
+/// Returns the byte index of the character at a screen column of a line.
+fn byte_at_column(line: &str, column: usize) -> usize {
+    let mut width = 0;
+
+    for (i, c) in line.char_indices() {
+        if width >= column {
+            return i;
+        }
+        width += c.width().unwrap_or(0);
+    }
+
+    line.len()
+}
+
+/// Returns the bytes that a terminal sends to a program for a key.
+///
+/// If the program asked for application cursor keys, the arrow keys are sent as `SS3` sequences.
+fn encode_key(key: Key, application_cursor: bool) -> Vec<u8> {
+    let arrow = |c: u8| {
+        let prefix = if application_cursor { b'O' } else { b'[' };
+        vec![b'\x1b', prefix, c]
+    };
+
+    match key {
+        Key::ArrowUp => arrow(b'A'),
+        Key::ArrowDown => arrow(b'B'),
+        Key::ArrowRight => arrow(b'C'),
+        Key::ArrowLeft => arrow(b'D'),
+        // Keys are read from the terminal one byte at a time, so characters up to U+00FF are
+        // bytes of UTF-8 sequences that are passed through as they are.
+        Key::Char(c) if u32::from(c) <= 0xff => vec![u32::from(c) as u8],
+        Key::Char(c) => c.to_string().into_bytes(),
+        Key::Ctrl(c) => vec![(c as u8) & 0x1f],
+        Key::Backspace => vec![b'\x7f'],
+        Key::Return => vec![b'\r'],
+        Key::Esc => vec![b'\x1b'],
+        Key::FocusGained | Key::FocusLost | Key::PasteStart | Key::PasteEnd => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::term::Key;
+
+    use super::{byte_at_column, encode_key};
+
+    #[test]
+    fn encode_keys() {
+        assert_eq!(encode_key(Key::Char('a'), false), b"a");
+        assert_eq!(encode_key(Key::Char('\u{c3}'), false), b"\xc3");
+        assert_eq!(encode_key(Key::Ctrl('c'), false), b"\x03");
+        assert_eq!(encode_key(Key::Return, false), b"\r");
+        assert_eq!(encode_key(Key::ArrowUp, false), b"\x1b[A");
+        assert_eq!(encode_key(Key::ArrowUp, true), b"\x1bOA");
+        assert!(encode_key(Key::FocusLost, false).is_empty());
+    }
+
+    #[test]
+    fn columns_to_bytes() {
+        assert_eq!(byte_at_column("$ ls", 2), 2);
+        assert_eq!(byte_at_column("é|", 1), 2);
+        assert_eq!(byte_at_column("日本", 2), 3);
+        assert_eq!(byte_at_column("ab", 5), 2);
+    }
+}
@@ -0,0 +1,113 @@
+//! Finding URLs in the text of a buffer, for `gx`.
+
+use super::Buffer;
+
+impl Buffer {
+    /// Returns the URL under the cursor, if any.
+    pub fn url_at_cursor(&self) -> Option<&str> {
+        let line = self.storage.iter_lines().nth(self.cursor.y())?;
+        url_at(line, self.cursor.x())
+    }
+}
+
+/// Returns the URL containing a byte column of a line, if any.
+///
+/// A URL is a scheme followed by `://` and the characters up to the next whitespace or quote.
+/// Trailing punctuation and unbalanced closing brackets are not part of the URL, so that URLs
+/// in prose and in parentheses are found correctly.
+fn url_at(line: &str, column: usize) -> Option<&str> {
+    let mut search_start = 0;
+
+    while let Some(index) = line[search_start..].find("://") {
+        let separator = search_start + index;
+
+        let end = line[separator..]
+            .find(|c: char| c.is_whitespace() || "<>\"'`".contains(c))
+            .map_or(line.len(), |index| separator + index);
+        search_start = end;
+
+        let scheme_start = line[..separator]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || "+.-".contains(c)))
+            .map_or(0, |index| index + 1);
+        let scheme_start =
+            match line[scheme_start..separator].find(|c: char| c.is_ascii_alphabetic()) {
+                Some(index) => scheme_start + index,
+                None => continue,
+            };
+
+        let url = trim_url(&line[scheme_start..end]);
+        if url.len() > separator + "://".len() - scheme_start
+            && (scheme_start..scheme_start + url.len()).contains(&column)
+        {
+            return Some(url);
+        }
+    }
+
+    None
+}
+
+/// Removes trailing punctuation and unbalanced closing brackets from a URL.
+fn trim_url(mut url: &str) -> &str {
+    loop {
+        let trimmed = url.trim_end_matches(|c| ".,;:!?".contains(c));
+        let trimmed = match trimmed.chars().last() {
+            Some(')') if trimmed.matches('(').count() < trimmed.matches(')').count() => {
+                &trimmed[..trimmed.len() - 1]
+            }
+            Some(']') if trimmed.matches('[').count() < trimmed.matches(']').count() => {
+                &trimmed[..trimmed.len() - 1]
+            }
+            _ => trimmed,
+        };
+
+        if trimmed.len() == url.len() {
+            return url;
+        }
+
+        url = trimmed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::url_at;
+
+    #[test]
+    fn find_url() {
+        let line = "See https://example.com/docs?q=1#top for details.";
+
+        assert_eq!(url_at(line, 4), Some("https://example.com/docs?q=1#top"));
+        assert_eq!(url_at(line, 20), Some("https://example.com/docs?q=1#top"));
+        assert_eq!(url_at(line, 3), None);
+        assert_eq!(url_at(line, 37), None);
+    }
+
+    #[test]
+    fn trim_punctuation() {
+        assert_eq!(
+            url_at(
+                "(see https://en.wikipedia.org/wiki/Rust_(programming_language)).",
+                10
+            ),
+            Some("https://en.wikipedia.org/wiki/Rust_(programming_language)")
+        );
+        assert_eq!(
+            url_at("[link](http://example.com/a.html), then", 10),
+            Some("http://example.com/a.html")
+        );
+        assert_eq!(
+            url_at("<a href=\"file:///tmp/index.html\">", 12),
+            Some("file:///tmp/index.html")
+        );
+    }
+
+    #[test]
+    fn multiple_urls() {
+        let line = "http://a.example and http://b.example";
+
+        assert_eq!(url_at(line, 0), Some("http://a.example"));
+        assert_eq!(url_at(line, 25), Some("http://b.example"));
+        assert_eq!(url_at("a :// b", 2), None);
+        assert_eq!(url_at("http:// b", 2), None);
+    }
+}
@@ -0,0 +1,229 @@
+//! Soft wrapping of lines that are wider than the viewport, when `wrap` is set.
+//!
+//! A wrapped line is shown on several display lines, each as wide as the viewport. Lines are
+//! broken between grapheme clusters, so a wide character that doesn't fit at the end of a display
+//! line moves to the next one. Without `wrap`, each line is a single display line that scrolls
+//! horizontally with the viewport.
+//!
+//! The viewport's top is always the start of a line. A line that is taller than the viewport is
+//! cut off at the bottom.
+
+use std::cmp;
+use std::ops::Range;
+use std::slice;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{Buffer, Position};
+
+/// A line of the buffer, or the part of one, that is shown on a row of the viewport.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayLine {
+    /// The line of the buffer.
+    pub line: usize,
+
+    /// The screen columns of the line that the row shows. The first is drawn at the left edge of
+    /// the viewport.
+    pub columns: Range<usize>,
+
+    /// Whether the row continues the line from the row above, rather than starting it.
+    pub continued: bool,
+}
+
+/// The display lines shown in the viewport, from the top.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DisplayLines(Vec<DisplayLine>);
+
+impl DisplayLines {
+    pub fn iter(&self) -> slice::Iter<'_, DisplayLine> {
+        self.0.iter()
+    }
+
+    /// Returns the number of rows that show text.
+    pub fn rows(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns the rows of the viewport that show a range of screen columns of a line, along with
+    /// the columns of each row, counted from the left edge of the viewport, that they're drawn in.
+    pub fn spans(
+        &self,
+        line: usize,
+        columns: Range<usize>,
+    ) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(move |(_, display)| display.line == line)
+            .filter_map(move |(row, display)| {
+                let start = cmp::max(columns.start, display.columns.start);
+                let end = cmp::min(columns.end, display.columns.end);
+                if start < end {
+                    let offset = display.columns.start;
+                    Some((row, start - offset..end - offset))
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Returns where a screen column of a line is drawn, relative to the viewport, if it's visible.
+    pub fn position(&self, line: usize, column: usize) -> Option<Position> {
+        self.spans(line, column..column + 1)
+            .next()
+            .map(|(row, columns)| Position::new(columns.start, row))
+    }
+}
+
+impl Buffer {
+    /// Returns the display lines shown in the viewport, which is empty if the buffer is hidden.
+    pub fn display_lines(&self) -> DisplayLines {
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => return DisplayLines::default(),
+        };
+
+        let height = viewport.height();
+        let mut rows = Vec::with_capacity(height);
+
+        for line in viewport.min_y()..self.storage.lines() {
+            if rows.len() >= height {
+                break;
+            }
+
+            if !self.window.wrap {
+                rows.push(DisplayLine {
+                    line,
+                    columns: viewport.min_x()..viewport.max_x(),
+                    continued: false,
+                });
+                continue;
+            }
+
+            let width = viewport.width();
+            let breaks = line_breaks(self.storage.line(line), width, height - rows.len());
+            for (i, &start) in breaks.iter().enumerate() {
+                let end = breaks.get(i + 1).copied().unwrap_or(start + width);
+                rows.push(DisplayLine {
+                    line,
+                    columns: start..end,
+                    continued: i > 0,
+                });
+            }
+        }
+
+        DisplayLines(rows)
+    }
+
+    /// Returns the width that lines are wrapped to, or `None` if `wrap` isn't set or the buffer is
+    /// hidden.
+    pub(super) fn wrap_width(&self) -> Option<usize> {
+        match self.viewport {
+            Some(viewport) if self.window.wrap && viewport.width() > 0 => Some(viewport.width()),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of display lines that a line takes up, which is one unless it's wrapped.
+    /// Lines taller than the viewport count as its height.
+    pub(super) fn display_height(&self, line: usize) -> usize {
+        match (self.wrap_width(), self.viewport) {
+            (Some(width), Some(viewport)) => {
+                line_breaks(self.storage.line(line), width, viewport.height()).len()
+            }
+            _ => 1,
+        }
+    }
+}
+
+/// Returns the screen columns that the display lines of a line start at, when it's wrapped to a
+/// width. There is always at least one display line, starting at column zero.
+///
+/// At most `max_rows` display lines are returned, so that only the visible part of a long line is
+/// examined.
+pub fn line_breaks(line: &str, width: usize, max_rows: usize) -> Vec<usize> {
+    let mut breaks = vec![0];
+    let mut row_start = 0;
+    let mut column = 0;
+
+    for grapheme in line.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if column + grapheme_width > row_start + width && column > row_start {
+            if breaks.len() >= max_rows {
+                break;
+            }
+
+            breaks.push(column);
+            row_start = column;
+        }
+
+        column += grapheme_width;
+    }
+
+    breaks
+}
+
+#[cfg(test)]
+mod tests {
+    use euclid::rect;
+
+    use crate::buffer::{Buffer, Position};
+
+    use super::{line_breaks, DisplayLine};
+
+    #[test]
+    fn break_lines() {
+        assert_eq!(line_breaks("", 4, 10), vec![0]);
+        assert_eq!(line_breaks("abcd", 4, 10), vec![0]);
+        assert_eq!(line_breaks("abcdefghij", 4, 10), vec![0, 4, 8]);
+        assert_eq!(line_breaks("abcdefghij", 4, 2), vec![0, 4]);
+
+        // Wide characters aren't split across display lines.
+        assert_eq!(line_breaks("abc台北", 4, 10), vec![0, 3]);
+    }
+
+    #[test]
+    fn display_lines() {
+        let mut buffer = Buffer::from("abcdefghij\nkl\nmnop\n");
+        buffer.viewport = Some(rect(1, 0, 4, 4));
+
+        let lines = buffer.display_lines();
+        assert_eq!(lines.rows(), 3);
+        assert_eq!(lines.position(0, 2), Some(Position::new(1, 0)));
+        assert_eq!(lines.position(0, 6), None);
+
+        buffer.window.wrap = true;
+        let lines = buffer.display_lines();
+        assert_eq!(
+            lines.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                DisplayLine {
+                    line: 0,
+                    columns: 0..4,
+                    continued: false,
+                },
+                DisplayLine {
+                    line: 0,
+                    columns: 4..8,
+                    continued: true,
+                },
+                DisplayLine {
+                    line: 0,
+                    columns: 8..12,
+                    continued: true,
+                },
+                DisplayLine {
+                    line: 1,
+                    columns: 0..4,
+                    continued: false,
+                },
+            ]
+        );
+        assert_eq!(lines.position(0, 6), Some(Position::new(2, 1)));
+        assert_eq!(
+            lines.spans(0, 3..9).collect::<Vec<_>>(),
+            vec![(0, 3..4), (1, 0..4), (2, 0..1)]
+        );
+    }
+}
@@ -0,0 +1,710 @@
+//! Ex-style commands entered in command-line mode.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::buffer::Direction;
+use crate::options::{OptionName, Setting, Value};
+use crate::quickfix::ListKind;
+use crate::ui::{Context, Coordinates, Drawable};
+
+/// A command that can be entered at the command-line prompt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Check the editor's environment and open the report in a scratch buffer.
+    CheckHealth,
+
+    /// Open a file, or switch to its buffer if it's already open. Directories are opened as a
+    /// listing of their entries.
+    Edit(PathBuf),
+
+    /// Change the working directory of the editor.
+    ChangeDirectory(PathBuf),
+
+    /// Change the working directory of the current buffer only.
+    ///
+    /// There is only one window, so the directory belongs to the buffer rather than the window.
+    ChangeLocalDirectory(PathBuf),
+
+    /// Show the working directory of the current buffer.
+    PrintDirectory,
+
+    /// Write the current buffer to its file.
+    Write,
+
+    /// Exit the editor.
+    Quit,
+
+    /// Switch to the next buffer, or the first after the last.
+    NextBuffer,
+
+    /// Switch to the previous buffer, or the last before the first.
+    PreviousBuffer,
+
+    /// Rename the current buffer's file on disk, letting language servers update references.
+    Rename(PathBuf),
+
+    /// Write the current buffer to a new file and continue editing it there, letting language
+    /// servers update references. The original file is left in place.
+    SaveAs(PathBuf),
+
+    /// Switch to the named color scheme, or show the active color scheme if no name is given.
+    ColorScheme(Option<String>),
+
+    /// Read the config files again and apply their settings.
+    ReloadConfig,
+
+    /// Change the records that are logged, or show the current filter if none is given.
+    LogLevel(Option<String>),
+
+    /// Open the editor's log in a buffer that follows it.
+    Log,
+
+    /// Open the buffers that were modified when the editor crashed, with the text they had.
+    Recover,
+
+    /// Run the linter configured for the current buffer's language.
+    Lint,
+
+    /// Run the build command with additional arguments, collecting its errors into the quickfix
+    /// list.
+    Make(Vec<String>),
+
+    /// Search the project for a pattern, collecting the matches into the quickfix list.
+    Grep(String),
+
+    /// Search the project for a pattern and preview replacing it, then replace the matches that
+    /// are kept. Both are matched and inserted literally.
+    Replace {
+        pattern: String,
+        replacement: String,
+    },
+
+    /// Search the current buffer for a pattern, or the last pattern searched for if none is given,
+    /// and move the cursor to the match.
+    Search(Option<String>, Direction),
+
+    /// Show a list in a split at the bottom of the screen.
+    OpenList(ListKind),
+
+    /// Close the split showing a list.
+    CloseList(ListKind),
+
+    /// Jump to the next entry of a list.
+    NextEntry(ListKind),
+
+    /// Jump to the previous entry of a list.
+    PreviousEntry(ListKind),
+
+    /// Fill the location list with the diagnostics of the current buffer and show it.
+    Diagnostics,
+
+    /// Choose a function or class of the current buffer to jump to.
+    Symbols,
+
+    /// Choose an open buffer to switch to or close.
+    Buffers,
+
+    /// Choose a recently edited file to open.
+    RecentFiles,
+
+    /// Choose recently yanked or deleted text to paste.
+    Yanks,
+
+    /// Show the commit that last changed the cursor's line in the status line.
+    Blame,
+
+    /// Toggle whether the current buffer's file is written with a byte order mark.
+    ToggleBom,
+
+    /// Change or show the values of options.
+    Set(Vec<Setting>),
+
+    /// Show the hunks that differ between the current buffer and the index, or a revision if one
+    /// is given.
+    Diff(Option<String>),
+
+    /// Show the hunks that differ between the current buffer and a file.
+    DiffSplit(PathBuf),
+
+    /// Mark the current buffer to be compared with. Once another buffer is marked, show the hunks
+    /// that differ between it and the first.
+    DiffThis,
+
+    /// Forget the buffer marked by `:diffthis`, and close the diff.
+    DiffOff,
+
+    /// Open the help for a topic, or the help's table of contents if no topic is given.
+    Help(Option<String>),
+
+    /// Run a program in a terminal buffer, or the user's shell if none is given.
+    Terminal(Vec<String>),
+
+    /// Jump to the definition of a tag in the tags file.
+    Tag(String),
+
+    /// Jump back to where the last tag jump was made from.
+    Pop,
+
+    /// Jump to a line, counting from one.
+    GoToLine(usize),
+
+    /// Jump to the last line.
+    GoToLastLine,
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("not an editor command: {0}")]
+    UnknownCommand(String),
+
+    #[error("argument required")]
+    MissingArgument,
+
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+}
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        // Like Vim's `:/pattern/` and `:?pattern?` ranges, the closing delimiter is optional.
+        for &(delimiter, direction) in &[('/', Direction::Forward), ('?', Direction::Backward)] {
+            if let Some(pattern) = s.strip_prefix(delimiter) {
+                let pattern = pattern.strip_suffix(delimiter).unwrap_or(pattern);
+                return Ok(Command::Search(
+                    Some(pattern)
+                        .filter(|pattern| !pattern.is_empty())
+                        .map(String::from),
+                    direction,
+                ));
+            }
+        }
+        let (name, args) = match s.find(char::is_whitespace) {
+            Some(index) => (&s[..index], s[index..].trim_start()),
+            None => (s, ""),
+        };
+
+        let required_arg = || {
+            if args.is_empty() {
+                Err(ParseError::MissingArgument)
+            } else {
+                Ok(args)
+            }
+        };
+
+        match name {
+            "checkhealth" => Ok(Command::CheckHealth),
+            "edit" | "e" => Ok(Command::Edit(PathBuf::from(required_arg()?))),
+            "cd" => Ok(Command::ChangeDirectory(PathBuf::from(required_arg()?))),
+            "lcd" => Ok(Command::ChangeLocalDirectory(
+                PathBuf::from(required_arg()?),
+            )),
+            "pwd" => Ok(Command::PrintDirectory),
+            "write" | "w" => Ok(Command::Write),
+            "quit" | "q" => Ok(Command::Quit),
+            "bnext" | "bn" => Ok(Command::NextBuffer),
+            "bprevious" | "bprev" | "bp" => Ok(Command::PreviousBuffer),
+            "rename" => Ok(Command::Rename(PathBuf::from(required_arg()?))),
+            "saveas" | "sav" => Ok(Command::SaveAs(PathBuf::from(required_arg()?))),
+            "colorscheme" => Ok(Command::ColorScheme(
+                Some(args).filter(|args| !args.is_empty()).map(String::from),
+            )),
+            "config-reload" => Ok(Command::ReloadConfig),
+            "log" => Ok(Command::Log),
+            "recover" | "rec" => Ok(Command::Recover),
+            "log-level" => Ok(Command::LogLevel(
+                Some(args).filter(|args| !args.is_empty()).map(String::from),
+            )),
+            "lint" => Ok(Command::Lint),
+            "make" => Ok(Command::Make(
+                args.split_whitespace().map(String::from).collect(),
+            )),
+            "grep" => Ok(Command::Grep(required_arg()?.to_owned())),
+            "replace" => parse_replace(required_arg()?),
+            "copen" => Ok(Command::OpenList(ListKind::Quickfix)),
+            "cclose" => Ok(Command::CloseList(ListKind::Quickfix)),
+            "cnext" | "cn" => Ok(Command::NextEntry(ListKind::Quickfix)),
+            "cprevious" | "cprev" | "cp" => Ok(Command::PreviousEntry(ListKind::Quickfix)),
+            "lopen" => Ok(Command::OpenList(ListKind::Location)),
+            "lclose" => Ok(Command::CloseList(ListKind::Location)),
+            "lnext" => Ok(Command::NextEntry(ListKind::Location)),
+            "lprevious" | "lprev" => Ok(Command::PreviousEntry(ListKind::Location)),
+            "diagnostics" => Ok(Command::Diagnostics),
+            "symbols" => Ok(Command::Symbols),
+            "buffers" | "ls" => Ok(Command::Buffers),
+            "oldfiles" | "ol" => Ok(Command::RecentFiles),
+            "yanks" => Ok(Command::Yanks),
+            "blame" => Ok(Command::Blame),
+            "bomb" => Ok(Command::ToggleBom),
+            "set" | "se" => Ok(Command::Set(
+                required_arg()?
+                    .split_whitespace()
+                    .map(parse_setting)
+                    .collect::<Result<_, _>>()?,
+            )),
+            "diff" => Ok(Command::Diff(
+                Some(args).filter(|args| !args.is_empty()).map(String::from),
+            )),
+            "diffsplit" | "diffs" => Ok(Command::DiffSplit(PathBuf::from(required_arg()?))),
+            "diffthis" | "difft" => Ok(Command::DiffThis),
+            "diffoff" | "diffo" => Ok(Command::DiffOff),
+            "help" | "h" => Ok(Command::Help(
+                Some(args).filter(|args| !args.is_empty()).map(String::from),
+            )),
+            "terminal" | "term" => Ok(Command::Terminal(
+                args.split_whitespace().map(String::from).collect(),
+            )),
+            "tag" | "ta" => Ok(Command::Tag(required_arg()?.to_owned())),
+            "pop" | "po" => Ok(Command::Pop),
+            "$" => Ok(Command::GoToLastLine),
+            _ => match name.parse() {
+                Ok(line) if args.is_empty() => Ok(Command::GoToLine(line)),
+                _ => Err(ParseError::UnknownCommand(s.to_owned())),
+            },
+        }
+    }
+}
+
+/// Converts a command-line argument that starts with `+` to the command that it runs at startup,
+/// like Vim's: `+N` jumps to line N, `+` jumps to the last line, and `+cmd` runs `cmd`.
+///
+/// Returns `None` if the argument doesn't start with `+`.
+pub fn startup_command(arg: &str) -> Option<String> {
+    match arg.strip_prefix('+')? {
+        "" => Some(String::from("$")),
+        command => Some(command.to_owned()),
+    }
+}
+
+/// Parses the argument of `:replace`, like the argument of Vim's `:s`: `/pattern/replacement/`,
+/// where the first character is the delimiter, and the last delimiter is optional.
+fn parse_replace(arg: &str) -> Result<Command, ParseError> {
+    let delimiter = arg.chars().next().ok_or(ParseError::MissingArgument)?;
+    let mut parts = arg[delimiter.len_utf8()..].splitn(2, delimiter);
+
+    match (parts.next(), parts.next()) {
+        (Some(pattern), Some(replacement)) if !pattern.is_empty() => Ok(Command::Replace {
+            pattern: pattern.to_owned(),
+            replacement: replacement
+                .strip_suffix(delimiter)
+                .unwrap_or(replacement)
+                .to_owned(),
+        }),
+        _ => Err(ParseError::InvalidArgument(arg.to_owned())),
+    }
+}
+
+/// Parses an argument of `:set`, like Vim's.
+///
+/// `name` turns an option on, or shows its value if it isn't turned on and off. `noname` turns an
+/// option off, `name!` and `invname` toggle it, `name=value` sets its value, and `name?` shows it.
+fn parse_setting(arg: &str) -> Result<Setting, ParseError> {
+    let lookup = |name: &str| {
+        OptionName::from_name(name).ok_or_else(|| ParseError::UnknownOption(name.to_owned()))
+    };
+
+    let lookup_bool = |name: &str| match lookup(name)? {
+        option if option.is_bool() => Ok(option),
+        _ => Err(ParseError::InvalidArgument(arg.to_owned())),
+    };
+
+    if let Some(index) = arg.find('=') {
+        let option = lookup(&arg[..index])?;
+        let value = &arg[index + 1..];
+        return match option.parse_value(value) {
+            Some(value) => Ok(Setting::Set(option, value)),
+            None => Err(ParseError::InvalidArgument(value.to_owned())),
+        };
+    }
+
+    if let Some(name) = arg.strip_suffix('?') {
+        return Ok(Setting::Show(lookup(name)?));
+    }
+
+    if let Some(name) = arg.strip_suffix('!') {
+        return Ok(Setting::Toggle(lookup_bool(name)?));
+    }
+
+    if let Some(option) = OptionName::from_name(arg) {
+        return Ok(if option.is_bool() {
+            Setting::Set(option, Value::Bool(true))
+        } else {
+            Setting::Show(option)
+        });
+    }
+
+    if let Some(name) = arg.strip_prefix("no") {
+        return Ok(Setting::Set(lookup_bool(name)?, Value::Bool(false)));
+    }
+
+    if let Some(name) = arg.strip_prefix("inv") {
+        return Ok(Setting::Toggle(lookup_bool(name)?));
+    }
+
+    Err(ParseError::UnknownOption(arg.to_owned()))
+}
+
+/// The text entered at the command-line prompt.
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    text: String,
+
+    /// The command lines that were entered, from oldest to newest.
+    history: Vec<String>,
+
+    /// The index of the history entry being shown, if the history is being browsed.
+    history_index: Option<usize>,
+}
+
+impl CommandLine {
+    /// Creates an empty command line that remembers the command lines entered before.
+    pub fn with_history(history: Vec<String>) -> Self {
+        CommandLine {
+            history,
+            ..CommandLine::default()
+        }
+    }
+
+    /// Returns the command lines that were entered, from oldest to newest.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    /// Replaces the command line with the start of a command for the user to finish.
+    pub fn prefill(&mut self, text: &str) {
+        self.history_index = None;
+        self.text = text.to_owned();
+    }
+
+    /// Removes the last character of the command line.
+    pub fn pop(&mut self) {
+        self.text.pop();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Clears the command line, returning its contents.
+    pub fn take(&mut self) -> String {
+        self.history_index = None;
+        std::mem::take(&mut self.text)
+    }
+
+    /// Clears the command line, returning its contents and adding them to the history.
+    pub fn submit(&mut self) -> String {
+        let text = self.take();
+
+        if !text.trim().is_empty() {
+            self.history.retain(|line| *line != text);
+            self.history.push(text.clone());
+        }
+
+        text
+    }
+
+    /// Replaces the command line with the previous entry of the history.
+    pub fn previous(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.history_index = Some(index);
+        self.text = self.history[index].clone();
+    }
+
+    /// Replaces the command line with the next entry of the history, or clears it after the last
+    /// entry.
+    pub fn next(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index + 1,
+            None => return,
+        };
+
+        if index < self.history.len() {
+            self.history_index = Some(index);
+            self.text = self.history[index].clone();
+        } else {
+            self.take();
+        }
+    }
+
+    /// Returns the column of the cursor within the prompt.
+    pub fn cursor_column(&self) -> usize {
+        1 + self.text.chars().count()
+    }
+}
+
+impl Drawable for CommandLine {
+    fn draw(&self, ctx: &mut Context<'_>) {
+        ctx.screen.write(
+            Coordinates::new(0, ctx.bounds.min.y),
+            &format!(":{}", self.text),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::buffer::{Direction, FileFormat};
+    use crate::options::{OptionName, Setting, Value};
+    use crate::quickfix::ListKind;
+
+    use super::{startup_command, Command, CommandLine, ParseError};
+
+    #[test]
+    fn parse_command() {
+        assert_eq!("checkhealth".parse(), Ok(Command::CheckHealth));
+        assert_eq!("  checkhealth  ".parse(), Ok(Command::CheckHealth));
+        assert_eq!("symbols".parse(), Ok(Command::Symbols));
+        assert_eq!("config-reload".parse(), Ok(Command::ReloadConfig));
+        assert_eq!("log".parse(), Ok(Command::Log));
+        assert_eq!(
+            "log-level editor::lsp=trace".parse(),
+            Ok(Command::LogLevel(Some(String::from("editor::lsp=trace"))))
+        );
+        assert_eq!("ls".parse(), Ok(Command::Buffers));
+        assert_eq!("oldfiles".parse(), Ok(Command::RecentFiles));
+        assert_eq!("yanks".parse(), Ok(Command::Yanks));
+        assert_eq!("blame".parse(), Ok(Command::Blame));
+        assert_eq!("w".parse(), Ok(Command::Write));
+        assert_eq!("q".parse(), Ok(Command::Quit));
+        assert_eq!("bn".parse(), Ok(Command::NextBuffer));
+        assert_eq!("bprevious".parse(), Ok(Command::PreviousBuffer));
+        assert_eq!("bomb".parse(), Ok(Command::ToggleBom));
+        assert_eq!("diff".parse(), Ok(Command::Diff(None)));
+        assert_eq!(
+            "diff HEAD~1".parse(),
+            Ok(Command::Diff(Some(String::from("HEAD~1"))))
+        );
+        assert_eq!(
+            "diffsplit old.rs".parse(),
+            Ok(Command::DiffSplit(PathBuf::from("old.rs")))
+        );
+        assert_eq!("diffthis".parse(), Ok(Command::DiffThis));
+        assert_eq!("help".parse(), Ok(Command::Help(None)));
+        assert_eq!(
+            "h :set".parse(),
+            Ok(Command::Help(Some(String::from(":set"))))
+        );
+        assert_eq!("ta main".parse(), Ok(Command::Tag(String::from("main"))));
+        assert_eq!("pop".parse(), Ok(Command::Pop));
+        assert_eq!("42".parse(), Ok(Command::GoToLine(42)));
+        assert_eq!("$".parse(), Ok(Command::GoToLastLine));
+        assert_eq!(
+            "42 foo".parse::<Command>(),
+            Err(ParseError::UnknownCommand(String::from("42 foo")))
+        );
+    }
+
+    #[test]
+    fn parse_replace_command() {
+        assert_eq!(
+            "replace /foo/bar/".parse(),
+            Ok(Command::Replace {
+                pattern: String::from("foo"),
+                replacement: String::from("bar"),
+            })
+        );
+        assert_eq!(
+            "replace #a/b#".parse(),
+            Ok(Command::Replace {
+                pattern: String::from("a/b"),
+                replacement: String::new(),
+            })
+        );
+        assert_eq!(
+            "replace /foo".parse::<Command>(),
+            Err(ParseError::InvalidArgument(String::from("/foo")))
+        );
+        assert_eq!(
+            "replace //bar/".parse::<Command>(),
+            Err(ParseError::InvalidArgument(String::from("//bar/")))
+        );
+        assert_eq!(
+            "replace".parse::<Command>(),
+            Err(ParseError::MissingArgument)
+        );
+    }
+
+    #[test]
+    fn parse_search_command() {
+        assert_eq!(
+            "/foo bar".parse(),
+            Ok(Command::Search(
+                Some(String::from("foo bar")),
+                Direction::Forward
+            ))
+        );
+        assert_eq!(
+            "?foo?".parse(),
+            Ok(Command::Search(
+                Some(String::from("foo")),
+                Direction::Backward
+            ))
+        );
+        assert_eq!("/".parse(), Ok(Command::Search(None, Direction::Forward)));
+    }
+
+    #[test]
+    fn parse_startup_command() {
+        assert_eq!(startup_command("file.txt"), None);
+        assert_eq!(startup_command("+"), Some(String::from("$")));
+        assert_eq!(startup_command("+12"), Some(String::from("12")));
+        assert_eq!(startup_command("+set nu"), Some(String::from("set nu")));
+    }
+
+    #[test]
+    fn parse_set_command() {
+        assert_eq!(
+            "set ff".parse(),
+            Ok(Command::Set(vec![Setting::Show(OptionName::FileFormat)]))
+        );
+        assert_eq!(
+            "set fileformat=dos".parse(),
+            Ok(Command::Set(vec![Setting::Set(
+                OptionName::FileFormat,
+                Value::FileFormat(FileFormat::Dos)
+            )]))
+        );
+        assert_eq!(
+            "se nu norelativenumber so=3 invbomb tm?".parse(),
+            Ok(Command::Set(vec![
+                Setting::Set(OptionName::Number, Value::Bool(true)),
+                Setting::Set(OptionName::RelativeNumber, Value::Bool(false)),
+                Setting::Set(OptionName::ScrollOff, Value::Number(3)),
+                Setting::Toggle(OptionName::Bomb),
+                Setting::Show(OptionName::TimeoutLen),
+            ]))
+        );
+        assert_eq!(
+            "set noscrolloff".parse::<Command>(),
+            Err(ParseError::InvalidArgument(String::from("noscrolloff")))
+        );
+        assert_eq!(
+            "set nu=1".parse::<Command>(),
+            Err(ParseError::InvalidArgument(String::from("1")))
+        );
+        assert_eq!(
+            "set ff=mac".parse::<Command>(),
+            Err(ParseError::InvalidArgument(String::from("mac")))
+        );
+        assert_eq!(
+            "set spell".parse::<Command>(),
+            Err(ParseError::UnknownOption(String::from("spell")))
+        );
+        assert_eq!("set".parse::<Command>(), Err(ParseError::MissingArgument));
+    }
+
+    #[test]
+    fn history() {
+        let mut command_line = CommandLine::with_history(vec![String::from("make")]);
+        for c in "grep foo".chars() {
+            command_line.push(c);
+        }
+        assert_eq!(command_line.submit(), "grep foo");
+
+        command_line.push('m');
+        command_line.take();
+        command_line.submit();
+        assert_eq!(command_line.history(), &["make", "grep foo"]);
+
+        command_line.previous();
+        assert_eq!(command_line.text, "grep foo");
+        command_line.previous();
+        command_line.previous();
+        assert_eq!(command_line.text, "make");
+        command_line.next();
+        assert_eq!(command_line.text, "grep foo");
+        command_line.next();
+        assert!(command_line.is_empty());
+
+        command_line.previous();
+        assert_eq!(command_line.submit(), "grep foo");
+        assert_eq!(command_line.history(), &["make", "grep foo"]);
+    }
+
+    #[test]
+    fn parse_list_command() {
+        assert_eq!("cn".parse(), Ok(Command::NextEntry(ListKind::Quickfix)));
+        assert_eq!(
+            "lprevious".parse(),
+            Ok(Command::PreviousEntry(ListKind::Location))
+        );
+    }
+
+    #[test]
+    fn parse_command_with_argument() {
+        assert_eq!(
+            "rename  src/new name.rs".parse(),
+            Ok(Command::Rename(PathBuf::from("src/new name.rs")))
+        );
+        assert_eq!(
+            "rename".parse::<Command>(),
+            Err(ParseError::MissingArgument)
+        );
+        assert_eq!("e src/".parse(), Ok(Command::Edit(PathBuf::from("src/"))));
+        assert_eq!(
+            "lcd ..".parse(),
+            Ok(Command::ChangeLocalDirectory(PathBuf::from("..")))
+        );
+        assert_eq!(
+            "saveas ../copy.rs".parse(),
+            Ok(Command::SaveAs(PathBuf::from("../copy.rs")))
+        );
+    }
+
+    #[test]
+    fn parse_command_with_optional_argument() {
+        assert_eq!(
+            "colorscheme light".parse(),
+            Ok(Command::ColorScheme(Some(String::from("light"))))
+        );
+        assert_eq!("colorscheme ".parse(), Ok(Command::ColorScheme(None)));
+    }
+
+    #[test]
+    fn parse_command_with_arguments() {
+        assert_eq!("make".parse(), Ok(Command::Make(vec![])));
+        assert_eq!(
+            "make  check -j4".parse(),
+            Ok(Command::Make(vec![
+                String::from("check"),
+                String::from("-j4")
+            ]))
+        );
+        assert_eq!("term".parse(), Ok(Command::Terminal(vec![])));
+        assert_eq!(
+            "terminal python3 -q".parse(),
+            Ok(Command::Terminal(vec![
+                String::from("python3"),
+                String::from("-q")
+            ]))
+        );
+    }
+
+    #[test]
+    fn parse_unknown_command() {
+        assert_eq!(
+            "frobnicate now".parse::<Command>(),
+            Err(ParseError::UnknownCommand(String::from("frobnicate now")))
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! Completing the word before the cursor in insert mode, with `<C-n>` and `<C-p>`.
+//!
+//! Like Vim's keyword completion, the candidates are the words in the open buffers that start with
+//! the text before the cursor: first the words of the current buffer, starting from the cursor's
+//! line, and then the words of the other buffers. Each press of `<C-n>` or `<C-p>` replaces the
+//! text before the cursor with the next or previous candidate, and cycling past either end brings
+//! back what was typed. The candidates are shown in a menu next to the cursor while completing.
+//!
+//! In buffers with a language server, `<C-Space>` or typing one of the server's trigger
+//! characters asks the server for completions instead. Its candidates are shown in the menu
+//! without replacing what was typed, and are inserted with `<C-n>` and `<C-p>` like words.
+//! Candidates that are snippets are inserted with their placeholders filled in, and `<Tab>` then
+//! moves to each of their tab stops.
+
+use std::cmp;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use lsp_types::{CompletionItem, CompletionTextEdit, InsertTextFormat};
+
+use crate::buffer::{Buffer, Buffers, Position, Snippet};
+
+/// A completion in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// The text that was typed before the cursor when completion started.
+    prefix: String,
+
+    candidates: Vec<String>,
+
+    /// The text shown in the menu for each candidate.
+    labels: Vec<String>,
+
+    /// The byte ranges of the tab stops in each candidate that is a snippet.
+    tab_stops: Vec<Vec<Range<usize>>>,
+
+    /// The index of the candidate that replaced the prefix, or `None` if the prefix is shown.
+    selected: Option<usize>,
+
+    /// The version of the buffer and the position of the cursor after the last candidate was
+    /// inserted. Typing anything else ends the completion.
+    version: u32,
+    cursor: Position,
+}
+
+impl Completion {
+    /// Starts completing a prefix, unless there are no candidates.
+    pub fn new(prefix: &str, candidates: Vec<String>, buffer: &Buffer) -> Option<Self> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        Some(Completion {
+            prefix: prefix.to_owned(),
+            labels: candidates.clone(),
+            tab_stops: vec![vec![]; candidates.len()],
+            candidates,
+            selected: None,
+            version: buffer.version(),
+            cursor: buffer.cursor(),
+        })
+    }
+
+    /// Starts completing with the items that a language server suggested at the cursor, unless
+    /// there are none.
+    ///
+    /// Each item replaces the text between the start of its edit and the cursor. Items may start
+    /// at different columns, so the prefix is the text from the leftmost start, and the text
+    /// between that and each item's start is kept in its candidate. Items without an edit replace
+    /// the identifier before the cursor. Snippets are expanded, but a snippet whose only tab stop
+    /// is its end has nothing to fill in, so it has no tab stops.
+    pub fn from_items(mut items: Vec<CompletionItem>, buffer: &Buffer) -> Option<Self> {
+        let cursor = buffer.cursor();
+        let line = buffer.lines_from(cursor.y).next().unwrap_or_default();
+        let word_start = cursor.x - buffer.identifier_before_cursor().len();
+
+        items.sort_by_cached_key(|item| {
+            item.sort_text.clone().unwrap_or_else(|| item.label.clone())
+        });
+
+        let items = items
+            .into_iter()
+            .map(|item| {
+                let label = item.label;
+                let (start, text) = match item.text_edit {
+                    Some(CompletionTextEdit::Edit(edit))
+                        if edit.range.start.line as usize == cursor.y =>
+                    {
+                        let start = buffer.position_from_lsp(edit.range.start);
+                        (cmp::min(start.x, cursor.x), edit.new_text)
+                    }
+                    _ => (
+                        word_start,
+                        item.insert_text.unwrap_or_else(|| label.clone()),
+                    ),
+                };
+
+                let snippet = match item.insert_text_format {
+                    Some(InsertTextFormat::Snippet) => Snippet::parse(&text),
+                    _ => Snippet {
+                        text,
+                        tab_stops: vec![],
+                    },
+                };
+                (start, snippet, label)
+            })
+            .collect::<Vec<_>>();
+
+        let start = items.iter().map(|&(start, _, _)| start).min()?;
+        let mut candidates = vec![];
+        let mut labels = vec![];
+        let mut tab_stops = vec![];
+        for (item_start, snippet, label) in items {
+            let kept = &line[start..item_start];
+            let end = snippet.text.len();
+
+            candidates.push(format!("{}{}", kept, snippet.text));
+            labels.push(label);
+            tab_stops.push(match snippet.tab_stops.as_slice() {
+                [range] if *range == (end..end) => vec![],
+                ranges => ranges
+                    .iter()
+                    .map(|range| kept.len() + range.start..kept.len() + range.end)
+                    .collect(),
+            });
+        }
+
+        let mut completion = Completion::new(&line[start..cursor.x], candidates, buffer)?;
+        completion.labels = labels;
+        completion.tab_stops = tab_stops;
+        Some(completion)
+    }
+
+    /// Returns whether the completion is still in progress in a buffer: nothing has been typed and
+    /// the cursor hasn't moved since the last candidate was inserted.
+    pub fn is_active(&self, buffer: &Buffer) -> bool {
+        buffer.version() == self.version && buffer.cursor() == self.cursor
+    }
+
+    /// Remembers the state of the buffer after the selected candidate was inserted.
+    pub fn inserted(&mut self, buffer: &Buffer) {
+        self.version = buffer.version();
+        self.cursor = buffer.cursor();
+    }
+
+    /// Returns the text that currently replaces the prefix.
+    pub fn text(&self) -> &str {
+        match self.selected {
+            Some(index) => &self.candidates[index],
+            None => &self.prefix,
+        }
+    }
+
+    /// Returns the byte ranges of the tab stops in the text that currently replaces the prefix, if
+    /// it's a snippet.
+    pub fn tab_stops(&self) -> &[Range<usize>] {
+        match self.selected {
+            Some(index) => &self.tab_stops[index],
+            None => &[],
+        }
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Selects the next candidate, or the prefix after the last candidate.
+    pub fn next(&mut self) {
+        self.selected = match self.selected {
+            None => Some(0),
+            Some(index) if index + 1 < self.candidates.len() => Some(index + 1),
+            Some(_) => None,
+        };
+    }
+
+    /// Selects the previous candidate, or the prefix before the first candidate.
+    pub fn previous(&mut self) {
+        self.selected = match self.selected {
+            None => Some(self.candidates.len() - 1),
+            Some(0) => None,
+            Some(index) => Some(index - 1),
+        };
+    }
+}
+
+/// Returns the words in the open buffers that start with a prefix, without duplicates.
+///
+/// The words of the current buffer come first, starting from the cursor's line and wrapping
+/// around, followed by the words of the other buffers in order.
+pub fn candidates(buffers: &Buffers, prefix: &str) -> Vec<String> {
+    let current = buffers.current();
+    let cursor_line = current.cursor().y;
+
+    let lines = current
+        .lines_from(cursor_line)
+        .chain(current.lines_from(0).take(cursor_line))
+        .chain(
+            buffers
+                .into_iter()
+                .filter(|buffer| !std::ptr::eq(*buffer, current))
+                .flat_map(|buffer| buffer.lines_from(0)),
+        );
+
+    let mut seen = HashSet::new();
+    lines
+        .flat_map(words)
+        .filter(|word| word.starts_with(prefix) && *word != prefix)
+        .filter(|word| seen.insert(*word))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Returns the words of a line: runs of alphanumeric characters and underscores.
+fn words(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{CompletionItem, CompletionTextEdit, InsertTextFormat, Range, TextEdit};
+
+    use crate::buffer::{Buffer, Buffers, Position};
+    use crate::ui::{Bounds, Size};
+
+    use super::{candidates, words, Completion};
+
+    #[test]
+    fn split_words() {
+        assert_eq!(
+            words("let foo_bar = baz::qux(é1);").collect::<Vec<_>>(),
+            vec!["let", "foo_bar", "baz", "qux", "é1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn find_candidates() {
+        let mut buffers = Buffers::from_paths(vec![], Bounds::from_size(Size::new(80, 24)), false)
+            .await
+            .unwrap();
+        buffers.push(Buffer::from("format\nforeign\n"));
+        buffers.push(Buffer::from("for fold\nfoo\nfor form\n"));
+        buffers.current_mut().jump_to(Position::new(0, 1));
+
+        assert_eq!(
+            candidates(&buffers, "fo"),
+            vec!["foo", "for", "form", "fold", "format", "foreign"]
+        );
+        assert_eq!(candidates(&buffers, "form"), vec!["format"]);
+        assert!(candidates(&buffers, "x").is_empty());
+    }
+
+    #[test]
+    fn cycle() {
+        let buffer = Buffer::from("fo");
+        let candidates = vec![String::from("foo"), String::from("for")];
+        let mut completion = Completion::new("fo", candidates, &buffer).unwrap();
+
+        assert_eq!(completion.text(), "fo");
+        completion.next();
+        assert_eq!(completion.text(), "foo");
+        completion.next();
+        assert_eq!(completion.text(), "for");
+        completion.next();
+        assert_eq!(completion.text(), "fo");
+        completion.previous();
+        assert_eq!(completion.text(), "for");
+
+        assert!(Completion::new("x", vec![], &buffer).is_none());
+    }
+
+    #[test]
+    fn language_server_items() {
+        let mut buffer = Buffer::from("foo.ba\n");
+        buffer.set_cursor_past_end(true);
+        buffer.jump_to(Position::new(6, 0));
+
+        let edit = |start, text: &str| {
+            Some(CompletionTextEdit::Edit(TextEdit::new(
+                Range::new(
+                    lsp_types::Position::new(0, start),
+                    lsp_types::Position::new(0, 6),
+                ),
+                String::from(text),
+            )))
+        };
+        let items = vec![
+            CompletionItem {
+                label: String::from("bar(…)"),
+                sort_text: Some(String::from("b")),
+                text_edit: edit(4, "bar"),
+                ..CompletionItem::default()
+            },
+            CompletionItem {
+                label: String::from("baz"),
+                sort_text: Some(String::from("a")),
+                ..CompletionItem::default()
+            },
+            CompletionItem {
+                label: String::from("?.bat"),
+                sort_text: Some(String::from("c")),
+                text_edit: edit(3, "?.bat"),
+                ..CompletionItem::default()
+            },
+        ];
+
+        let mut completion = Completion::from_items(items, &buffer).unwrap();
+        assert_eq!(completion.labels(), ["baz", "bar(…)", "?.bat"]);
+        assert_eq!(completion.text(), ".ba");
+        completion.next();
+        assert_eq!(completion.text(), ".baz");
+        completion.next();
+        assert_eq!(completion.text(), ".bar");
+        completion.next();
+        assert_eq!(completion.text(), "?.bat");
+
+        assert!(Completion::from_items(vec![], &buffer).is_none());
+    }
+
+    #[test]
+    fn language_server_snippets() {
+        let mut buffer = Buffer::from("x.\n");
+        buffer.set_cursor_past_end(true);
+        buffer.jump_to(Position::new(2, 0));
+
+        let items = vec![
+            CompletionItem {
+                label: String::from("push(…)"),
+                sort_text: Some(String::from("a")),
+                insert_text: Some(String::from("push(${1:value})$0")),
+                insert_text_format: Some(InsertTextFormat::Snippet),
+                ..CompletionItem::default()
+            },
+            CompletionItem {
+                label: String::from("len()"),
+                sort_text: Some(String::from("b")),
+                insert_text: Some(String::from("len()")),
+                insert_text_format: Some(InsertTextFormat::Snippet),
+                ..CompletionItem::default()
+            },
+        ];
+
+        let mut completion = Completion::from_items(items, &buffer).unwrap();
+        assert!(completion.tab_stops().is_empty());
+        completion.next();
+        assert_eq!(completion.text(), "push(value)");
+        assert_eq!(completion.tab_stops(), [5..10, 11..11]);
+        completion.next();
+        assert_eq!(completion.text(), "len()");
+        assert!(completion.tab_stops().is_empty());
+    }
+
+    #[test]
+    fn language_server_items_count_characters() {
+        let mut buffer = Buffer::from("é.ba\n");
+        buffer.set_cursor_past_end(true);
+        buffer.jump_to(Position::new(5, 0));
+
+        let items = vec![CompletionItem {
+            label: String::from("bar"),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                Range::new(
+                    lsp_types::Position::new(0, 2),
+                    lsp_types::Position::new(0, 4),
+                ),
+                String::from("bar"),
+            ))),
+            ..CompletionItem::default()
+        }];
+
+        let mut completion = Completion::from_items(items, &buffer).unwrap();
+        assert_eq!(completion.text(), "ba");
+        completion.next();
+        assert_eq!(completion.text(), "bar");
+    }
+}
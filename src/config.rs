@@ -2,15 +2,25 @@
 
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use log::*;
-use serde::de::{self, Deserializer};
+use serde::de::{self, DeserializeOwned, Deserializer};
 use serde::Deserialize;
 use tokio::fs;
 use tokio::io;
+use toml::value::{Table, Value};
 
+use crate::event::EventKind;
+use crate::keymap::{Action, Chord};
+use crate::options::{LanguageOptions, Options};
 use crate::syntax::Syntax;
+use crate::term::Key;
+
+/// The name of the project config file, which is read from the editor's working directory.
+const PROJECT_CONFIG_FILE: &str = ".editor.toml";
 
 /// Configuration supplied by the user.
 #[derive(Debug, Default, PartialEq, Eq, Deserialize)]
@@ -19,6 +29,74 @@ pub struct Config {
     #[serde(default)]
     #[serde(rename = "language-server")]
     pub language_server_config: HashMap<Syntax, LanguageServerConfig>,
+
+    /// Mappings from file name patterns to language names, such as `"*.jsx" = "javascript"`.
+    #[serde(default)]
+    pub filetypes: HashMap<String, String>,
+
+    #[serde(default)]
+    #[serde(rename = "linter")]
+    pub linter_config: HashMap<Syntax, LinterConfig>,
+
+    #[serde(default)]
+    #[serde(rename = "make")]
+    pub make_config: MakeConfig,
+
+    #[serde(default)]
+    #[serde(rename = "grep")]
+    pub grep_config: GrepConfig,
+
+    #[serde(default)]
+    #[serde(rename = "save")]
+    pub save_config: SaveConfig,
+
+    #[serde(default)]
+    #[serde(rename = "yank")]
+    pub yank_config: YankConfig,
+
+    #[serde(default)]
+    #[serde(rename = "keys")]
+    pub key_config: KeyConfig,
+
+    #[serde(default)]
+    #[serde(rename = "log")]
+    pub log_config: LogConfig,
+
+    /// The name of the color scheme to use, such as `light`.
+    pub colorscheme: Option<String>,
+
+    /// The initial values of options, such as `number = true`.
+    #[serde(default)]
+    pub options: Options,
+
+    /// Buffer-local options that override `options` for buffers of a language, such as
+    /// `[language.python]`.
+    #[serde(default)]
+    #[serde(rename = "language")]
+    pub language_options: HashMap<Syntax, LanguageOptions>,
+
+    /// Commands that run when events happen, given as `[[hook]]` tables.
+    #[serde(default)]
+    #[serde(rename = "hook")]
+    pub hooks: Vec<HookConfig>,
+}
+
+/// Configuration specific to a project, read from the root of the project.
+///
+/// Settings in the project config take precedence over the same settings in the user's config.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    #[serde(rename = "make")]
+    pub make_config: ProjectMakeConfig,
+}
+
+/// The project's build command, which is used for buffers of any language.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct ProjectMakeConfig {
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_optional_command")]
+    command: Option<Vec<String>>,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -26,6 +104,11 @@ pub struct LanguageServerConfig {
     /// The program name and arguments used to launch the language server.
     #[serde(deserialize_with = "validate_command")]
     command: Vec<String>,
+
+    /// Settings sent to the server with `workspace/didChangeConfiguration`, whose meaning is
+    /// specific to the server.
+    #[serde(default)]
+    settings: Option<serde_json::Value>,
 }
 
 impl LanguageServerConfig {
@@ -34,6 +117,301 @@ impl LanguageServerConfig {
             .split_first()
             .expect("command should not be empty")
     }
+
+    pub fn settings(&self) -> Option<&serde_json::Value> {
+        self.settings.as_ref()
+    }
+}
+
+/// An external program that checks buffers of a language for problems.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LinterConfig {
+    /// The program name and arguments used to run the linter.
+    ///
+    /// The contents of the buffer are written to the linter's standard input.
+    #[serde(deserialize_with = "validate_command")]
+    command: Vec<String>,
+
+    /// The format of the linter's output.
+    #[serde(default)]
+    pub format: LintFormat,
+}
+
+impl LinterConfig {
+    pub fn command(&self) -> (&String, &[String]) {
+        self.command
+            .split_first()
+            .expect("command should not be empty")
+    }
+}
+
+/// Output formats that linters may produce.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintFormat {
+    /// One problem per line, like `file:line:column: severity: message`, as printed by GCC and
+    /// many other tools.
+    #[default]
+    Gcc,
+
+    /// A JSON array of objects with `line`, `column`, `endLine`, `endColumn`, `level`, and
+    /// `message` fields, as printed by `shellcheck --format=json`.
+    Json,
+}
+
+/// The build command run by `:make`.
+///
+/// The command is run in the editor's working directory, which is usually the root of the project.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MakeConfig {
+    /// The program name and arguments used to build the project.
+    #[serde(default = "default_make_command")]
+    #[serde(deserialize_with = "validate_command")]
+    command: Vec<String>,
+
+    /// Commands that override the default for buffers of particular languages, such as
+    /// `rust = ["cargo", "build"]`.
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_language_commands")]
+    language: HashMap<Syntax, Vec<String>>,
+
+    /// The command from the project config, which overrides the others.
+    #[serde(skip)]
+    project: Option<Vec<String>>,
+}
+
+impl MakeConfig {
+    /// Returns the build command for a buffer of the given language.
+    ///
+    /// The project's command is preferred, followed by the command for the language, and then the
+    /// default command.
+    pub fn command(&self, syntax: Option<Syntax>) -> (&String, &[String]) {
+        self.project
+            .as_ref()
+            .or_else(|| syntax.and_then(|syntax| self.language.get(&syntax)))
+            .unwrap_or(&self.command)
+            .split_first()
+            .expect("command should not be empty")
+    }
+}
+
+impl Default for MakeConfig {
+    fn default() -> Self {
+        MakeConfig {
+            command: default_make_command(),
+            language: HashMap::new(),
+            project: None,
+        }
+    }
+}
+
+/// The search program run by `:grep`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GrepConfig {
+    /// The program name and arguments used to search. The pattern is appended after `--`.
+    ///
+    /// Each match should be printed as `path:line:column:text` or `path:line:text`.
+    #[serde(deserialize_with = "validate_command")]
+    command: Vec<String>,
+}
+
+impl GrepConfig {
+    pub fn command(&self) -> (&String, &[String]) {
+        self.command
+            .split_first()
+            .expect("command should not be empty")
+    }
+}
+
+impl Default for GrepConfig {
+    fn default() -> Self {
+        GrepConfig {
+            command: vec![String::from("rg"), String::from("--vimgrep")],
+        }
+    }
+}
+
+/// Changes made to buffers when they are written. All are off by default.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SaveConfig {
+    /// Remove whitespace from the end of each line.
+    #[serde(default)]
+    trim_trailing_whitespace: bool,
+
+    /// Make sure that the file ends with a newline.
+    #[serde(default)]
+    final_newline: bool,
+
+    /// Settings that override the others for buffers of particular languages, such as
+    /// `markdown = { trim-trailing-whitespace = false }`.
+    #[serde(default)]
+    language: HashMap<Syntax, LanguageSaveConfig>,
+}
+
+/// Changes made to buffers of a language when they are written. Unset changes fall back to the
+/// global setting.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LanguageSaveConfig {
+    trim_trailing_whitespace: Option<bool>,
+    final_newline: Option<bool>,
+
+    /// The program name and arguments of a formatter, such as
+    /// `["prettier", "--stdin-filepath", "{file}"]`. The contents of the buffer are written to its
+    /// standard input, and replaced with its output if it succeeds.
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_optional_command")]
+    format: Option<Vec<String>>,
+}
+
+impl SaveConfig {
+    /// Returns whether trailing whitespace is removed from buffers of the given language.
+    pub fn trim_trailing_whitespace(&self, syntax: Option<Syntax>) -> bool {
+        self.language(syntax)
+            .and_then(|language| language.trim_trailing_whitespace)
+            .unwrap_or(self.trim_trailing_whitespace)
+    }
+
+    /// Returns whether a final newline is added to buffers of the given language.
+    pub fn final_newline(&self, syntax: Option<Syntax>) -> bool {
+        self.language(syntax)
+            .and_then(|language| language.final_newline)
+            .unwrap_or(self.final_newline)
+    }
+
+    /// Returns the command of the formatter for buffers of the given language, if any.
+    pub fn formatter(&self, syntax: Option<Syntax>) -> Option<&[String]> {
+        self.language(syntax)
+            .and_then(|language| language.format.as_deref())
+    }
+
+    fn language(&self, syntax: Option<Syntax>) -> Option<&LanguageSaveConfig> {
+        syntax.and_then(|syntax| self.language.get(&syntax))
+    }
+}
+
+/// Feedback for yanking text.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct YankConfig {
+    /// How long yanked text is highlighted for, in milliseconds. Zero disables the highlight.
+    #[serde(default = "default_highlight_duration")]
+    highlight_duration: u64,
+}
+
+impl YankConfig {
+    /// Returns how long yanked text is highlighted for, or `None` if it isn't highlighted.
+    pub fn highlight_duration(&self) -> Option<Duration> {
+        Some(Duration::from_millis(self.highlight_duration))
+            .filter(|duration| *duration != Duration::from_millis(0))
+    }
+}
+
+impl Default for YankConfig {
+    fn default() -> Self {
+        YankConfig {
+            highlight_duration: default_highlight_duration(),
+        }
+    }
+}
+
+/// A command that runs when an event happens, like a Vim autocommand.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct HookConfig {
+    pub event: EventKind,
+
+    /// A glob pattern that the subject of the event must match, such as `*.rs` for the path of a
+    /// buffer or `insert:*` for a mode change. Hooks without a pattern run for every event of
+    /// their kind.
+    #[serde(default)]
+    pub pattern: Option<String>,
+
+    /// The command line to run, such as `make`.
+    pub command: String,
+}
+
+/// Where to log, and what.
+///
+/// The log is set up before the editor starts, so changes take effect after a restart. The filter
+/// may be changed while the editor runs with `:log-level`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LogConfig {
+    /// The log file, which is `editor.log` in the state directory by default.
+    pub file: Option<PathBuf>,
+
+    /// The records to log, written like `RUST_LOG`, such as `info` or `editor::lsp=debug`.
+    pub level: Option<String>,
+
+    /// The size in bytes that the log file may grow to before it's moved aside and a new one is
+    /// started. Only one old log file is kept.
+    pub max_size: Option<u64>,
+}
+
+/// Key bindings that change the defaults, such as `"<C-s>" = "normal-mode"`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KeyConfig {
+    #[serde(default)]
+    pub normal: HashMap<Chord, Action>,
+
+    #[serde(default)]
+    pub insert: HashMap<Chord, Action>,
+
+    #[serde(default)]
+    pub visual: HashMap<Chord, Action>,
+
+    /// The key that `<leader>` stands for in chords.
+    #[serde(default = "default_leader")]
+    #[serde(deserialize_with = "validate_leader")]
+    pub leader: Key,
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        KeyConfig {
+            normal: HashMap::new(),
+            insert: HashMap::new(),
+            visual: HashMap::new(),
+            leader: default_leader(),
+        }
+    }
+}
+
+fn default_leader() -> Key {
+    Key::Char('\\')
+}
+
+fn default_highlight_duration() -> u64 {
+    150
+}
+
+fn default_make_command() -> Vec<String> {
+    vec![String::from("make")]
+}
+
+fn validate_language_commands<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<Syntax, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let commands = <HashMap<Syntax, Vec<String>>>::deserialize(deserializer)?;
+    if commands.values().any(Vec::is_empty) {
+        return Err(de::Error::invalid_length(0, &"at least a program name"));
+    }
+
+    Ok(commands)
+}
+
+fn validate_leader<'de, D>(deserializer: D) -> Result<Key, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Chord::deserialize(deserializer)?
+        .single_key()
+        .ok_or_else(|| de::Error::custom("the leader must be a single key"))
 }
 
 fn validate_command<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
@@ -48,42 +426,122 @@ where
     Ok(command)
 }
 
+fn validate_optional_command<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    validate_command(deserializer).map(Some)
+}
+
 impl Config {
-    /// Read the configuration from a file path. If no path is supplied, the default configuration
-    /// is returned.
-    pub async fn read(path: Option<PathBuf>) -> anyhow::Result<Config> {
-        // If the file doesn't exist, return the default config.
-        let path = match path {
-            Some(path) => path,
-            None => {
-                info!("could not determine config directory");
-                return Ok(Config::default());
-            }
-        };
+    /// Read the configuration from config files, from lowest to highest precedence. Settings in
+    /// later files override the same settings in earlier ones, and tables are merged. Files that
+    /// don't exist are skipped, so if none exist, the default configuration is returned.
+    pub async fn read(paths: &[PathBuf]) -> anyhow::Result<Config> {
+        let mut table = Table::new();
 
-        info!("reading config from {}", path.display());
+        for path in paths {
+            info!("reading config from {}", path.display());
+            merge_tables(&mut table, read_toml(path).await?);
+        }
 
-        let config = match fs::read(path).await {
-            Ok(bytes) => toml::from_slice(&bytes)?,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                info!("config file not found");
-                return Ok(Config::default());
-            }
-            Err(e) => return Err(e.into()),
-        };
+        Ok(Value::Table(table).try_into()?)
+    }
 
-        Ok(config)
+    /// Applies the settings of a project config, which take precedence over the user's.
+    pub fn merge_project(&mut self, project: ProjectConfig) {
+        if let Some(command) = project.make_config.command {
+            self.make_config.project = Some(command);
+        }
     }
 
-    /// Returns the path of the config file.
+    /// Returns the path of the user's config file.
     ///
     /// Respects `XDG_CONFIG_HOME`.
     pub fn config_path() -> Option<PathBuf> {
+        Some(Config::config_dir()?.join("config.toml"))
+    }
+
+    /// Returns the paths of the config files, from lowest to highest precedence: the system-wide
+    /// files in `XDG_CONFIG_DIRS`, which provide defaults, then the user's file.
+    pub fn config_paths() -> Vec<PathBuf> {
+        let mut paths = system_config_dirs(env::var_os("XDG_CONFIG_DIRS"))
+            .into_iter()
+            .rev()
+            .map(|dir| dir.join("editor/config.toml"))
+            .collect::<Vec<_>>();
+
+        match Config::config_path() {
+            Some(path) => paths.push(path),
+            None => info!("could not determine config directory"),
+        }
+
+        paths
+    }
+
+    /// Returns the directory containing the config file and other user-supplied files.
+    ///
+    /// Respects `XDG_CONFIG_HOME`.
+    pub fn config_dir() -> Option<PathBuf> {
         let config_dir = env::var_os("XDG_CONFIG_HOME")
             .map(PathBuf::from)
             .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
 
-        Some(config_dir.join("editor/config.toml"))
+        Some(config_dir.join("editor"))
+    }
+}
+
+impl ProjectConfig {
+    /// Reads the project config from the root directory of a project. If the project has no
+    /// config file, the default configuration is returned.
+    pub async fn read(root: &Path) -> anyhow::Result<ProjectConfig> {
+        let path = root.join(PROJECT_CONFIG_FILE);
+
+        info!("reading project config from {}", path.display());
+
+        read_toml(&path).await
+    }
+}
+
+/// Returns the system-wide config directories in `XDG_CONFIG_DIRS`, from most to least important.
+fn system_config_dirs(var: Option<OsString>) -> Vec<PathBuf> {
+    let dirs = var
+        .map(|var| {
+            env::split_paths(&var)
+                .filter(|dir| dir.is_absolute())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if dirs.is_empty() {
+        vec![PathBuf::from("/etc/xdg")]
+    } else {
+        dirs
+    }
+}
+
+/// Merges the settings of a config file on top of the settings of another, merging tables that
+/// both contain rather than replacing them.
+fn merge_tables(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overrides)) => merge_tables(base, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Reads a TOML config file, returning the default configuration if it doesn't exist.
+async fn read_toml<T: DeserializeOwned + Default>(path: &Path) -> anyhow::Result<T> {
+    match fs::read(path).await {
+        Ok(bytes) => Ok(toml::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!("config file not found");
+            Ok(T::default())
+        }
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -91,16 +549,25 @@ impl Config {
 mod tests {
     use std::error::Error;
     use std::ops::Deref;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
     use indoc::indoc;
     use maplit::hashmap;
+    use serde_json::json;
     use tempfile::NamedTempFile;
     use tokio::fs::File;
     use tokio::io::AsyncWriteExt;
 
+    use crate::event::EventKind;
+    use crate::keymap::Action;
     use crate::syntax::Syntax;
+    use crate::term::Key;
 
-    use super::{Config, LanguageServerConfig};
+    use super::{
+        Config, HookConfig, LanguageServerConfig, LintFormat, LinterConfig, MakeConfig,
+        ProjectConfig, PROJECT_CONFIG_FILE,
+    };
 
     #[test]
     fn deserialize_empty_config() -> Result<(), Box<dyn Error>> {
@@ -115,6 +582,10 @@ mod tests {
             "
             [language-server.rust]
             command = ['rust-analyzer']
+
+            [language-server.python]
+            command = ['pylsp']
+            settings = { pylsp = { plugins = { pycodestyle = { enabled = false } } } }
             "
         ))?;
         assert_eq!(
@@ -123,13 +594,301 @@ mod tests {
                 language_server_config: hashmap! {
                     Syntax::Rust => LanguageServerConfig {
                         command: vec![String::from("rust-analyzer")],
+                        settings: None,
+                    },
+                    Syntax::Python => LanguageServerConfig {
+                        command: vec![String::from("pylsp")],
+                        settings: Some(json!({
+                            "pylsp": { "plugins": { "pycodestyle": { "enabled": false } } },
+                        })),
                     },
-                }
+                },
+                ..Config::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_hooks() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            r#"
+            [[hook]]
+            event = "buf-write-post"
+            pattern = "*.rs"
+            command = "make"
+
+            [[hook]]
+            event = "focus-lost"
+            command = "w"
+            "#
+        ))?;
+        assert_eq!(
+            config.hooks,
+            vec![
+                HookConfig {
+                    event: EventKind::BufWritePost,
+                    pattern: Some(String::from("*.rs")),
+                    command: String::from("make"),
+                },
+                HookConfig {
+                    event: EventKind::FocusLost,
+                    pattern: None,
+                    command: String::from("w"),
+                },
+            ]
+        );
+
+        assert!(
+            toml::from_str::<Config>("[[hook]]\nevent = \"bufenter\"\ncommand = \"w\"").is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_options() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            "
+            [options]
+            expandtab = true
+
+            [language.go]
+            expandtab = false
+            tabstop = 4
+            "
+        ))?;
+        assert!(config.options.buffer.expandtab);
+
+        let mut options = config.options.buffer;
+        config.language_options[&Syntax::Go].apply(&mut options);
+        assert!(!options.expandtab);
+        assert_eq!(options.tabstop, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_filetypes() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            r#"
+            [filetypes]
+            "*.jsx" = "javascript"
+            "Justfile" = "make"
+            "#
+        ))?;
+        assert_eq!(
+            config.filetypes,
+            hashmap! {
+                String::from("*.jsx") => String::from("javascript"),
+                String::from("Justfile") => String::from("make"),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_linter() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            "
+            [linter.javascript]
+            command = ['lint-js', '--json']
+            format = 'json'
+
+            [linter.python]
+            command = ['flake8', '-']
+            "
+        ))?;
+        assert_eq!(
+            config.linter_config,
+            hashmap! {
+                Syntax::JavaScript => LinterConfig {
+                    command: vec![String::from("lint-js"), String::from("--json")],
+                    format: LintFormat::Json,
+                },
+                Syntax::Python => LinterConfig {
+                    command: vec![String::from("flake8"), String::from("-")],
+                    format: LintFormat::Gcc,
+                },
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_make() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            "
+            [make.language]
+            rust = ['cargo', 'build']
+            "
+        ))?;
+        assert_eq!(
+            config.make_config,
+            MakeConfig {
+                command: vec![String::from("make")],
+                language: hashmap! {
+                    Syntax::Rust => vec![String::from("cargo"), String::from("build")],
+                },
+                project: None,
             }
         );
+
+        let make = &config.make_config;
+        assert_eq!(make.command(Some(Syntax::Rust)).0, "cargo");
+        assert_eq!(make.command(Some(Syntax::C)).0, "make");
+        assert_eq!(make.command(None).0, "make");
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_save() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            r#"
+            [save]
+            trim-trailing-whitespace = true
+
+            [save.language]
+            markdown = { trim-trailing-whitespace = false, final-newline = true }
+            javascript = { format = ["prettier", "--stdin-filepath", "{file}"] }
+            "#
+        ))?;
+
+        let save = &config.save_config;
+        assert!(save.trim_trailing_whitespace(Some(Syntax::Rust)));
+        assert!(save.trim_trailing_whitespace(None));
+        assert!(!save.trim_trailing_whitespace(Some(Syntax::Markdown)));
+        assert!(!save.final_newline(Some(Syntax::Rust)));
+        assert!(save.final_newline(Some(Syntax::Markdown)));
+        assert_eq!(save.formatter(Some(Syntax::Rust)), None);
+        assert_eq!(
+            save.formatter(Some(Syntax::JavaScript)),
+            Some(
+                &[
+                    String::from("prettier"),
+                    String::from("--stdin-filepath"),
+                    String::from("{file}")
+                ][..]
+            )
+        );
+
+        assert!(toml::from_str::<Config>("[save.language]\nrust = { format = [] }").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_yank() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>("")?;
+        assert_eq!(
+            config.yank_config.highlight_duration(),
+            Some(Duration::from_millis(150))
+        );
+
+        let config = toml::from_str::<Config>(indoc!(
+            "
+            [yank]
+            highlight-duration = 0
+            "
+        ))?;
+        assert_eq!(config.yank_config.highlight_duration(), None);
         Ok(())
     }
 
+    #[test]
+    fn deserialize_keys() -> Result<(), Box<dyn Error>> {
+        let config = toml::from_str::<Config>(indoc!(
+            r#"
+            [keys.normal]
+            "<C-q>" = "quit"
+
+            [keys.insert]
+            jk = "normal-mode"
+            "#
+        ))?;
+        assert_eq!(
+            config.key_config.normal,
+            hashmap! { "<C-q>".parse()? => Action::Quit }
+        );
+        assert_eq!(
+            config.key_config.insert,
+            hashmap! { "jk".parse()? => Action::NormalMode }
+        );
+        assert_eq!(config.key_config.leader, Key::Char('\\'));
+
+        let config = toml::from_str::<Config>(indoc!(
+            r#"
+            [keys]
+            leader = "<Space>"
+            "#
+        ))?;
+        assert_eq!(config.key_config.leader, Key::Char(' '));
+
+        assert!(toml::from_str::<Config>(indoc!(
+            r#"
+            [keys]
+            leader = "<leader>"
+            "#
+        ))
+        .is_err());
+
+        assert!(toml::from_str::<Config>(indoc!(
+            r#"
+            [keys.normal]
+            "<F1>" = "quit"
+            "#
+        ))
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn project_make_command_precedence() -> Result<(), Box<dyn Error>> {
+        let mut config = toml::from_str::<Config>(indoc!(
+            "
+            [make.language]
+            rust = ['cargo', 'build']
+            "
+        ))?;
+
+        config.merge_project(toml::from_str::<ProjectConfig>("")?);
+        assert_eq!(config.make_config.command(Some(Syntax::Rust)).0, "cargo");
+
+        config.merge_project(toml::from_str::<ProjectConfig>(indoc!(
+            "
+            [make]
+            command = ['just', 'build']
+            "
+        ))?);
+
+        let make = &config.make_config;
+        assert_eq!(
+            make.command(Some(Syntax::Rust)),
+            (&String::from("just"), &[String::from("build")][..])
+        );
+        assert_eq!(make.command(Some(Syntax::C)).0, "just");
+        assert_eq!(make.command(None).0, "just");
+
+        assert!(toml::from_str::<ProjectConfig>("make = { command = [] }").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_project_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            ProjectConfig::read(dir.path()).await.unwrap(),
+            ProjectConfig::default()
+        );
+
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILE),
+            "[make]\ncommand = ['ninja']\n",
+        )
+        .unwrap();
+
+        let mut config = Config::default();
+        config.merge_project(ProjectConfig::read(dir.path()).await.unwrap());
+        assert_eq!(config.make_config.command(None).0, "ninja");
+    }
+
     #[test]
     fn deserialize_language_server_command_empty() {
         let err = toml::from_str::<Config>(indoc!(
@@ -145,14 +904,12 @@ mod tests {
 
     #[tokio::test]
     async fn read_no_config_dir() {
-        assert_eq!(Config::read(None).await.unwrap(), Config::default());
+        assert_eq!(Config::read(&[]).await.unwrap(), Config::default());
     }
 
     #[tokio::test]
     async fn read_nonexistent_file() {
-        let config = Config::read(Some("i-dont-exist.toml".into()))
-            .await
-            .unwrap();
+        let config = Config::read(&["i-dont-exist.toml".into()]).await.unwrap();
         assert_eq!(config, Config::default());
     }
 
@@ -161,7 +918,34 @@ mod tests {
         let (file, path) = NamedTempFile::new().unwrap().into_parts();
         let mut file = File::from_std(file);
         file.write_all(b"I am not TOML").await.unwrap();
-        assert!(Config::read(Some(path.deref().into())).await.is_err());
+        assert!(Config::read(&[path.deref().into()]).await.is_err());
         drop(path);
     }
+
+    #[tokio::test]
+    async fn read_layered_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.toml");
+        let user = dir.path().join("user.toml");
+        std::fs::write(
+            &system,
+            "colorscheme = 'light'\n[options]\nnumber = true\nscrolloff = 3\n",
+        )
+        .unwrap();
+        std::fs::write(&user, "[options]\nscrolloff = 8\n").unwrap();
+
+        let config = Config::read(&[system, user]).await.unwrap();
+        assert_eq!(config.colorscheme.as_deref(), Some("light"));
+        assert!(config.options.window.number);
+        assert_eq!(config.options.window.scrolloff, 8);
+    }
+
+    #[test]
+    fn system_config_dirs() {
+        assert_eq!(super::system_config_dirs(None), [PathBuf::from("/etc/xdg")]);
+        assert_eq!(
+            super::system_config_dirs(Some("/opt/xdg:relative:/etc/xdg".into())),
+            [PathBuf::from("/opt/xdg"), PathBuf::from("/etc/xdg")]
+        );
+    }
 }
@@ -0,0 +1,1050 @@
+//! Comparing a buffer with the version of its file in git, with `:diff`, with another file, with
+//! `:diffsplit` or `editor -d old new`, or with another buffer, with `:diffthis`.
+//!
+//! The diff view lists the hunks that differ between the buffer and a base, which is the index, a
+//! revision such as `HEAD`, another file, or another buffer. Hunks can be reverted, which edits the
+//! buffer to match the base, and hunks of a diff with the index can be staged, which copies the
+//! buffer's version of the hunk into the index.
+//!
+//! Diffs with git are shown as a unified diff. Two files are shown side by side instead, with the
+//! base on the left, and the lines that were added, changed or deleted highlighted in both. Both
+//! sides scroll together.
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use futures::channel::mpsc;
+use futures::{future, SinkExt};
+use log::*;
+use lsp_types::TextEdit;
+use similar::{Algorithm, DiffTag};
+use thiserror::Error;
+use tokio::fs;
+use tokio::io::{self, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::task;
+
+use crate::theme;
+use crate::ui::picker::truncate;
+use crate::ui::{Bounds, Context, Coordinates, Drawable};
+
+/// The number of unchanged lines shown around each hunk.
+const CONTEXT_LINES: usize = 3;
+
+/// The character between the two sides of a side-by-side diff.
+const SEPARATOR: &str = "│";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to run git: {0}")]
+    Io(#[source] io::Error),
+
+    #[error("git failed: {0}")]
+    Failed(String),
+
+    #[error("hunks can only be staged when comparing with the index")]
+    StageRevision,
+
+    #[error("hunks can't be staged when comparing two files")]
+    StageFile,
+
+    #[error("unable to read {}: {}", .0.display(), .1)]
+    ReadFile(PathBuf, #[source] io::Error),
+}
+
+/// The version of a file that a buffer is compared with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base {
+    /// The version of the file that is staged for the next commit.
+    Index,
+
+    /// The version of the file in a commit, such as `HEAD`.
+    Revision(String),
+
+    /// Another file.
+    File(PathBuf),
+
+    /// The contents of the buffer of another file, which may have unsaved changes.
+    Buffer(PathBuf),
+}
+
+impl Display for Base {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Base::Index => write!(f, "index"),
+            Base::Revision(revision) => write!(f, "{}", revision),
+            Base::File(path) | Base::Buffer(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A range of changed lines.
+///
+/// Both ranges are zero-indexed. An empty range is the position where lines were inserted or
+/// deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The lines of the base that were replaced.
+    pub old: Range<usize>,
+
+    /// The lines of the buffer that replaced them.
+    pub new: Range<usize>,
+}
+
+impl Hunk {
+    /// Returns the hunk header of a unified diff without context, such as `@@ -3,2 +3 @@`.
+    ///
+    /// The new side starts at `new_start`. A patch of a single hunk passes the base's line, since
+    /// none of the other hunks are applied.
+    fn header(&self, new_start: usize) -> String {
+        let side = |start: usize, len: usize| match len {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, len),
+        };
+
+        format!(
+            "@@ -{} +{} @@",
+            side(self.old.start, self.old.len()),
+            side(new_start, self.new.len())
+        )
+    }
+}
+
+/// A row of the side-by-side view, with a line of the base and the line of the buffer beside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row<'a> {
+    /// The line of the base, or `None` where the buffer has more lines in a hunk.
+    old: Option<&'a str>,
+
+    /// The line of the buffer, or `None` where the base has more lines in a hunk.
+    new: Option<&'a str>,
+
+    /// The index of the hunk that the row belongs to, or `None` if the lines are unchanged.
+    hunk: Option<usize>,
+}
+
+impl<'a> Row<'a> {
+    fn unchanged(old: &'a str, new: &'a str) -> Self {
+        Row {
+            old: Some(old),
+            new: Some(new),
+            hunk: None,
+        }
+    }
+
+    /// Returns the name of the row's style, depending on whether its line was added, changed or
+    /// deleted.
+    fn style(&self) -> Option<&'static str> {
+        match (self.hunk, self.old, self.new) {
+            (None, _, _) => None,
+            (Some(_), None, _) => Some("diff.added"),
+            (Some(_), _, None) => Some("diff.deleted"),
+            (Some(_), Some(_), Some(_)) => Some("diff.changed"),
+        }
+    }
+}
+
+/// Returns the hunks that turn the `old` lines into the `new` lines.
+pub fn hunks(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = vec![];
+
+    for op in similar::capture_diff_slices(Algorithm::Myers, old, new) {
+        let (tag, old, new) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+
+        // A replacement is reported as a deletion followed by an insertion.
+        match hunks.last_mut() {
+            Some(hunk) if hunk.old.end == old.start && hunk.new.end == new.start => {
+                hunk.old.end = old.end;
+                hunk.new.end = new.end;
+            }
+            _ => hunks.push(Hunk { old, new }),
+        }
+    }
+
+    hunks
+}
+
+/// Returns a patch that applies a single hunk to the base, for `git apply --unidiff-zero`.
+fn patch(name: &str, hunk: &Hunk, old: &[&str], new: &[&str]) -> String {
+    let mut patch = format!(
+        "--- a/{}\n+++ b/{}\n{}\n",
+        name,
+        name,
+        hunk.header(hunk.old.start)
+    );
+
+    for line in &old[hunk.old.clone()] {
+        patch.push('-');
+        patch.push_str(line);
+        patch.push('\n');
+    }
+
+    for line in &new[hunk.new.clone()] {
+        patch.push('+');
+        patch.push_str(line);
+        patch.push('\n');
+    }
+
+    patch
+}
+
+/// Returns an edit that replaces the buffer's lines of a hunk with the base's lines.
+///
+/// The buffer always ends with a newline, so a hunk at the end of the buffer replaces the text
+/// after the end of the previous line instead.
+fn revert_edit(hunk: &Hunk, old: &[&str], new: &[&str]) -> TextEdit {
+    let position = |line: usize, character: usize| {
+        lsp_types::Position::new(
+            u64::try_from(line).expect("line out of range"),
+            u64::try_from(character).expect("character out of range"),
+        )
+    };
+
+    let old_lines = &old[hunk.old.clone()];
+
+    if hunk.new.end < new.len() || new.is_empty() {
+        let new_text = old_lines.iter().map(|line| format!("{}\n", line)).collect();
+        return TextEdit::new(
+            lsp_types::Range::new(position(hunk.new.start, 0), position(hunk.new.end, 0)),
+            new_text,
+        );
+    }
+
+    let last = new.len() - 1;
+    let end = position(last, new[last].chars().count());
+
+    match hunk.new.start.checked_sub(1) {
+        Some(previous) => TextEdit::new(
+            lsp_types::Range::new(position(previous, new[previous].chars().count()), end),
+            old_lines.iter().map(|line| format!("\n{}", line)).collect(),
+        ),
+        None => TextEdit::new(
+            lsp_types::Range::new(position(0, 0), end),
+            old_lines.join("\n"),
+        ),
+    }
+}
+
+/// A diff that was computed in the background.
+pub struct Report {
+    /// The version of the buffer that was diffed.
+    pub version: u32,
+
+    pub view: Result<DiffView, Error>,
+}
+
+/// Diffs a buffer with a base in the background, sending a report once it's done.
+///
+/// `text` is the current contents of the buffer. `base_text` is the contents of the base if the
+/// editor knows them, such as when the base is another buffer. Otherwise, the base is read.
+pub fn spawn(
+    path: PathBuf,
+    version: u32,
+    base: Base,
+    base_text: Option<String>,
+    text: String,
+    mut report_sender: mpsc::Sender<Report>,
+) {
+    tokio::spawn(async move {
+        let base_text = match base_text {
+            Some(base_text) => Ok(base_text),
+            None => read_base(&path, &base).await,
+        };
+
+        // Diffing large files takes long enough to hold up the other tasks on the worker.
+        let view = match base_text {
+            Ok(base_text) => {
+                let view = task::spawn_blocking(move || DiffView::new(path, base, base_text, text));
+                Ok(view.await.expect("diffing panicked"))
+            }
+            Err(e) => Err(e),
+        };
+
+        if report_sender.send(Report { version, view }).await.is_err() {
+            info!("editor exited before diff finished");
+        }
+    });
+}
+
+/// Reads the base version of a file.
+///
+/// The contents of buffers are only known to the editor, so a buffer base is read from its file.
+pub async fn read_base(path: &Path, base: &Base) -> Result<String, Error> {
+    let object = match base {
+        Base::Index => format!(":./{}", file_name(path)),
+        Base::Revision(revision) => format!("{}:./{}", revision, file_name(path)),
+        Base::File(path) | Base::Buffer(path) => {
+            return fs::read_to_string(path)
+                .await
+                .map_err(|e| Error::ReadFile(path.clone(), e))
+        }
+    };
+
+    git(path, &["show", &object], "").await
+}
+
+/// Runs git in the directory containing `path`, returning its standard output.
+async fn git(path: &Path, args: &[&str], input: &str) -> Result<String, Error> {
+    let mut command = Command::new("git");
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(dir) = path.parent() {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn().map_err(Error::Io)?;
+
+    let mut stdin = child.stdin.take().expect("stdin was not piped");
+    let write_stdin = async move {
+        if let Err(e) = stdin.write_all(input.as_bytes()).await {
+            warn!("unable to write to git: {}", e);
+        }
+    };
+
+    let (_, output) = future::join(write_stdin, child.wait_with_output()).await;
+    let output = output.map_err(Error::Io)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .next()
+            .map(String::from)
+            .unwrap_or_else(|| output.status.to_string());
+        return Err(Error::Failed(reason));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The hunks between a buffer and its base, shown over the buffer while the diff is open.
+#[derive(Debug)]
+pub struct DiffView {
+    path: PathBuf,
+    base: Base,
+    base_text: String,
+
+    /// The contents of the buffer when the hunks were computed.
+    text: String,
+
+    hunks: Vec<Hunk>,
+
+    /// The index of the selected hunk.
+    current: usize,
+
+    /// The first row that is shown, or `None` if the view follows the selected hunk.
+    scroll: Option<usize>,
+}
+
+impl DiffView {
+    /// Compares the contents of the buffer at `path` with the text of its base.
+    pub fn new(path: PathBuf, base: Base, base_text: String, text: String) -> Self {
+        let mut view = DiffView {
+            path,
+            base,
+            base_text,
+            text: String::new(),
+            hunks: vec![],
+            current: 0,
+            scroll: None,
+        };
+        view.update(None, text);
+        view
+    }
+
+    /// Computes the hunks again after the buffer or its base changed, keeping the selection in
+    /// place where possible.
+    pub fn update(&mut self, base_text: Option<String>, text: String) {
+        if let Some(base_text) = base_text {
+            self.base_text = base_text;
+        }
+        self.text = text;
+
+        let old = self.base_text.lines().collect::<Vec<_>>();
+        let new = self.text.lines().collect::<Vec<_>>();
+        self.hunks = hunks(&old, &new);
+        self.current = cmp::min(self.current, self.hunks.len().saturating_sub(1));
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn base(&self) -> &Base {
+        &self.base
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Returns the selected hunk, if there are any hunks.
+    pub fn current(&self) -> Option<&Hunk> {
+        self.hunks.get(self.current)
+    }
+
+    /// Selects the next hunk. Returns `false` if the last hunk was already selected.
+    pub fn next(&mut self) -> bool {
+        if self.current + 1 < self.hunks.len() {
+            self.current += 1;
+            self.scroll = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Selects the previous hunk. Returns `false` if the first hunk was already selected.
+    pub fn previous(&mut self) -> bool {
+        if self.current > 0 {
+            self.current -= 1;
+            self.scroll = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Scrolls the view by `delta` rows, moving both sides of a side-by-side diff together. The
+    /// view follows the selected hunk again once another hunk is selected.
+    pub fn scroll(&mut self, delta: isize) {
+        let (len, first) = self.first_row();
+        let first = if delta < 0 {
+            first.saturating_sub(-delta as usize)
+        } else {
+            first + delta as usize
+        };
+        self.scroll = Some(cmp::min(first, len.saturating_sub(1)));
+    }
+
+    /// Returns the number of rows in the view and the first row that is shown.
+    fn first_row(&self) -> (usize, usize) {
+        let (len, current) = if self.is_side_by_side() {
+            let (rows, current) = self.rows();
+            (rows.len(), current.saturating_sub(CONTEXT_LINES))
+        } else {
+            let (lines, current) = self.lines();
+            (lines.len(), current)
+        };
+
+        let first = self.scroll.unwrap_or(current);
+        (len, cmp::min(first, len.saturating_sub(1)))
+    }
+
+    /// Returns whether the diff compares two files, which are shown side by side.
+    fn is_side_by_side(&self) -> bool {
+        matches!(self.base, Base::File(_) | Base::Buffer(_))
+    }
+
+    /// Returns a description of the selection, such as `hunk 2 of 3 (index)`.
+    pub fn describe_current(&self) -> String {
+        match self.hunks.len() {
+            0 => format!("no changes against {}", self.base),
+            len => format!("hunk {} of {} ({})", self.current + 1, len, self.base),
+        }
+    }
+
+    /// Stages the buffer's version of the selected hunk.
+    pub async fn stage_current(&self) -> Result<(), Error> {
+        match self.base {
+            Base::Index => {}
+            Base::Revision(_) => return Err(Error::StageRevision),
+            Base::File(_) | Base::Buffer(_) => return Err(Error::StageFile),
+        }
+
+        let hunk = match self.current() {
+            Some(hunk) => hunk,
+            None => return Ok(()),
+        };
+
+        let old = self.base_text.lines().collect::<Vec<_>>();
+        let new = self.text.lines().collect::<Vec<_>>();
+        let patch = patch(&file_name(&self.path), hunk, &old, &new);
+
+        git(
+            &self.path,
+            &["apply", "--cached", "--unidiff-zero", "-"],
+            &patch,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns an edit that reverts the selected hunk in the buffer.
+    pub fn revert_current(&self) -> Option<TextEdit> {
+        let hunk = self.current()?;
+
+        let old = self.base_text.lines().collect::<Vec<_>>();
+        let new = self.text.lines().collect::<Vec<_>>();
+        Some(revert_edit(hunk, &old, &new))
+    }
+
+    /// Returns the lines of the unified diff, with the names of their styles, and the index of the
+    /// selected hunk's header.
+    fn lines(&self) -> (Vec<(String, Option<&'static str>)>, usize) {
+        let old = self.base_text.lines().collect::<Vec<_>>();
+        let new = self.text.lines().collect::<Vec<_>>();
+
+        let mut lines = vec![];
+        let mut current = 0;
+
+        // The end of the trailing context of the previous hunk.
+        let mut previous_end = 0;
+
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            let context_start =
+                cmp::max(previous_end, hunk.new.start.saturating_sub(CONTEXT_LINES));
+            let context_end = match self.hunks.get(index + 1) {
+                Some(next) => cmp::max(hunk.new.end, next.new.start.saturating_sub(CONTEXT_LINES)),
+                None => new.len(),
+            };
+            let context_end = cmp::min(context_end, hunk.new.end + CONTEXT_LINES);
+
+            if index == self.current {
+                current = lines.len();
+            }
+            lines.push((hunk.header(hunk.new.start), Some("diff.hunk")));
+
+            for line in &new[context_start..hunk.new.start] {
+                lines.push((format!(" {}", line), None));
+            }
+            for line in &old[hunk.old.clone()] {
+                lines.push((format!("-{}", line), Some("diff.minus")));
+            }
+            for line in &new[hunk.new.clone()] {
+                lines.push((format!("+{}", line), Some("diff.plus")));
+            }
+            for line in &new[hunk.new.end..context_end] {
+                lines.push((format!(" {}", line), None));
+            }
+
+            previous_end = context_end;
+        }
+
+        (lines, current)
+    }
+
+    /// Returns the rows of the side-by-side view, and the index of the first row of the selected
+    /// hunk.
+    ///
+    /// The lines of each hunk are paired up, and the shorter side is padded with empty lines, so
+    /// that the unchanged lines after the hunk line up again.
+    fn rows(&self) -> (Vec<Row<'_>>, usize) {
+        let old = self.base_text.lines().collect::<Vec<_>>();
+        let new = self.text.lines().collect::<Vec<_>>();
+
+        let mut rows = vec![];
+        let mut current = 0;
+
+        // The ends of the previous hunk.
+        let (mut old_end, mut new_end) = (0, 0);
+
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            rows.extend(
+                old[old_end..hunk.old.start]
+                    .iter()
+                    .zip(&new[new_end..hunk.new.start])
+                    .map(|(old, new)| Row::unchanged(old, new)),
+            );
+
+            if index == self.current {
+                current = rows.len();
+            }
+
+            for line in 0..cmp::max(hunk.old.len(), hunk.new.len()) {
+                rows.push(Row {
+                    old: old[hunk.old.clone()].get(line).copied(),
+                    new: new[hunk.new.clone()].get(line).copied(),
+                    hunk: Some(index),
+                });
+            }
+
+            old_end = hunk.old.end;
+            new_end = hunk.new.end;
+        }
+
+        rows.extend(
+            old[old_end..]
+                .iter()
+                .zip(&new[new_end..])
+                .map(|(old, new)| Row::unchanged(old, new)),
+        );
+
+        (rows, current)
+    }
+
+    /// Draws the base and the buffer side by side, under their names. Unless the view was scrolled,
+    /// the selected hunk is shown after a few unchanged lines.
+    fn draw_side_by_side(&self, ctx: &mut Context<'_>) {
+        let bounds = ctx.bounds;
+        if bounds.width() < 3 || bounds.height() == 0 {
+            return;
+        }
+
+        let (rows, _) = self.rows();
+        let (_, first) = self.first_row();
+        let scheme = theme::current();
+
+        let old_width = (bounds.width() - 1) / 2;
+        let separator_x = bounds.min.x + old_width;
+        let new_x = separator_x + 1;
+        let new_width = bounds.max.x - new_x;
+
+        // Styles one side of a row.
+        let style_side =
+            |ctx: &mut Context<'_>, x: u16, width: u16, y: u16, style: &str, bold: bool| {
+                if let Some(style) = scheme.style(style) {
+                    let style = if bold { style.bold() } else { style };
+                    let bounds =
+                        Bounds::new(Coordinates::new(x, y), Coordinates::new(x + width, y + 1));
+                    ctx.screen.apply_style(bounds, style);
+                }
+            };
+
+        let names = [
+            (bounds.min.x, old_width, self.base.to_string()),
+            (new_x, new_width, self.path.display().to_string()),
+        ];
+        for (x, width, name) in &names {
+            let y = bounds.min.y;
+            ctx.screen
+                .write(Coordinates::new(*x, y), truncate(name, usize::from(*width)));
+            style_side(ctx, *x, *width, y, "diff.hunk", true);
+        }
+        ctx.screen
+            .write(Coordinates::new(separator_x, bounds.min.y), SEPARATOR);
+
+        for (offset, row) in rows
+            .iter()
+            .skip(first)
+            .take(usize::from(bounds.height() - 1))
+            .enumerate()
+        {
+            let y = bounds.min.y + 1 + offset as u16;
+
+            ctx.screen
+                .write(Coordinates::new(separator_x, y), SEPARATOR);
+
+            if let Some(line) = row.old {
+                ctx.screen.write(
+                    Coordinates::new(bounds.min.x, y),
+                    truncate(line, usize::from(old_width)),
+                );
+            }
+            if let Some(line) = row.new {
+                ctx.screen.write(
+                    Coordinates::new(new_x, y),
+                    truncate(line, usize::from(new_width)),
+                );
+            }
+
+            if let Some(style) = row.style() {
+                let bold = row.hunk == Some(self.current);
+                style_side(ctx, bounds.min.x, old_width, y, style, bold);
+                style_side(ctx, new_x, new_width, y, style, bold);
+            }
+        }
+    }
+}
+
+impl Drawable for DiffView {
+    /// Draws the diff, scrolled so that the selected hunk is at the top unless the view was
+    /// scrolled. Diffs between two files are drawn side by side.
+    fn draw(&self, ctx: &mut Context<'_>) {
+        if self.is_side_by_side() {
+            self.draw_side_by_side(ctx);
+            return;
+        }
+
+        let (lines, current) = self.lines();
+        let (_, first) = self.first_row();
+        let scheme = theme::current();
+
+        for (row, (line, style)) in lines
+            .iter()
+            .skip(first)
+            .take(usize::from(ctx.bounds.height()))
+            .enumerate()
+        {
+            let y = ctx.bounds.min.y + row as u16;
+            let bounds = Bounds::new(
+                Coordinates::new(ctx.bounds.min.x, y),
+                Coordinates::new(ctx.bounds.max.x, y + 1),
+            );
+
+            ctx.screen.write(bounds.min, line);
+
+            if let Some(style) = style.and_then(|style| scheme.style(style)) {
+                let style = if first + row == current {
+                    style.bold()
+                } else {
+                    style
+                };
+                ctx.screen.apply_style(bounds, style);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::Command;
+
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+
+    use crate::buffer::Buffer;
+    use crate::ui::{Bounds, Context, Drawable, Screen, Size};
+
+    use super::{hunks, patch, read_base, revert_edit, spawn, Base, DiffView, Hunk, Row};
+
+    const OLD: &[&str] = &["a", "b", "c", "d", "e"];
+    const NEW: &[&str] = &["a", "B", "c", "e", "f"];
+
+    #[test]
+    fn compute_hunks() {
+        assert_eq!(
+            hunks(OLD, NEW),
+            vec![
+                Hunk {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Hunk {
+                    old: 3..4,
+                    new: 3..3
+                },
+                Hunk {
+                    old: 5..5,
+                    new: 4..5
+                },
+            ]
+        );
+        assert_eq!(hunks(OLD, OLD), vec![]);
+    }
+
+    #[test]
+    fn hunk_patch() {
+        let hunks = hunks(OLD, NEW);
+
+        assert_eq!(
+            patch("file.txt", &hunks[0], OLD, NEW),
+            "--- a/file.txt\n+++ b/file.txt\n@@ -2 +2 @@\n-b\n+B\n"
+        );
+        assert_eq!(
+            patch("file.txt", &hunks[1], OLD, NEW),
+            "--- a/file.txt\n+++ b/file.txt\n@@ -4 +3,0 @@\n-d\n"
+        );
+        assert_eq!(
+            patch("file.txt", &hunks[2], OLD, NEW),
+            "--- a/file.txt\n+++ b/file.txt\n@@ -5,0 +6 @@\n+f\n"
+        );
+    }
+
+    #[test]
+    fn revert_hunks() {
+        for hunk in hunks(OLD, NEW) {
+            let mut buffer = Buffer::from("a\nB\nc\ne\nf\n");
+            buffer.apply_text_edits(vec![revert_edit(&hunk, OLD, NEW)]);
+
+            let text = buffer.text();
+            let lines = text.lines().collect::<Vec<_>>();
+            assert_eq!(hunks(OLD, &lines).len(), 2, "reverting {:?}", hunk);
+        }
+
+        let mut buffer = Buffer::from("a\nnew\nb\n");
+        let hunk = &hunks(&["a", "old", "lines", "b"], &["a", "new", "b"])[0];
+        buffer.apply_text_edits(vec![revert_edit(
+            hunk,
+            &["a", "old", "lines", "b"],
+            &["a", "new", "b"],
+        )]);
+        assert_eq!(buffer.text(), "a\nold\nlines\nb\n");
+
+        let mut buffer = Buffer::from("new\n");
+        let hunk = &hunks(&["old", "lines"], &["new"])[0];
+        buffer.apply_text_edits(vec![revert_edit(hunk, &["old", "lines"], &["new"])]);
+        assert_eq!(buffer.text(), "old\nlines\n");
+    }
+
+    #[test]
+    fn navigate_hunks() {
+        let mut view = DiffView::new(
+            "/file.txt".into(),
+            Base::Index,
+            String::from("a\nb\nc\nd\ne\n"),
+            String::from("a\nB\nc\ne\nf\n"),
+        );
+
+        assert_eq!(view.describe_current(), "hunk 1 of 3 (index)");
+        assert!(!view.previous());
+        assert!(view.next());
+        assert!(view.next());
+        assert!(!view.next());
+        assert_eq!(
+            view.current(),
+            Some(&Hunk {
+                old: 5..5,
+                new: 4..5
+            })
+        );
+
+        view.update(None, String::from("a\nb\nc\nd\ne\nf\n"));
+        assert_eq!(view.describe_current(), "hunk 1 of 1 (index)");
+
+        view.update(None, String::from("a\nb\nc\nd\ne\n"));
+        assert!(view.is_empty());
+        assert_eq!(view.describe_current(), "no changes against index");
+    }
+
+    #[test]
+    fn diff_lines() {
+        let view = DiffView::new(
+            "/file.txt".into(),
+            Base::Revision(String::from("HEAD")),
+            String::from("1\n2\n3\n4\n5\n6\n7\n8\n9\n"),
+            String::from("1\n2\nthree\n4\n5\n6\n7\n8\n9\nten\n"),
+        );
+
+        let (lines, current) = view.lines();
+        let lines = lines.into_iter().map(|(line, _)| line).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "@@ -3 +3 @@",
+                " 1",
+                " 2",
+                "-3",
+                "+three",
+                " 4",
+                " 5",
+                " 6",
+                "@@ -9,0 +10 @@",
+                " 7",
+                " 8",
+                " 9",
+                "+ten",
+            ]
+        );
+        assert_eq!(current, 0);
+    }
+
+    #[test]
+    fn side_by_side_rows() {
+        let mut view = DiffView::new(
+            "/new.txt".into(),
+            Base::File("/old.txt".into()),
+            String::from("a\nb\nc\nd\ne\n"),
+            String::from("a\nB\nc\ne\nf\n"),
+        );
+        view.next();
+
+        let row = |old, new, hunk| Row { old, new, hunk };
+        let (rows, current) = view.rows();
+        assert_eq!(
+            rows,
+            vec![
+                row(Some("a"), Some("a"), None),
+                row(Some("b"), Some("B"), Some(0)),
+                row(Some("c"), Some("c"), None),
+                row(Some("d"), None, Some(1)),
+                row(Some("e"), Some("e"), None),
+                row(None, Some("f"), Some(2)),
+            ]
+        );
+        assert_eq!(current, 3);
+
+        let styles = rows.iter().map(Row::style).collect::<Vec<_>>();
+        assert_eq!(
+            styles,
+            vec![
+                None,
+                Some("diff.changed"),
+                None,
+                Some("diff.deleted"),
+                None,
+                Some("diff.added"),
+            ]
+        );
+    }
+
+    #[test]
+    fn scroll_together() {
+        let mut view = DiffView::new(
+            "/new.txt".into(),
+            Base::File("/old.txt".into()),
+            String::from("1\n2\n3\n4\n5\n6\n7\n8\n"),
+            String::from("1\n2\n3\n4\nfive\n6\n7\n8\n"),
+        );
+        assert_eq!(view.first_row(), (8, 1));
+
+        view.scroll(2);
+        assert_eq!(view.first_row(), (8, 3));
+        view.scroll(-5);
+        assert_eq!(view.first_row(), (8, 0));
+        view.scroll(20);
+        assert_eq!(view.first_row(), (8, 7));
+
+        let mut screen = Screen::new(Size::new(9, 2));
+        view.draw(&mut Context {
+            bounds: Bounds::from_size(screen.size),
+            screen: &mut screen,
+        });
+        assert_eq!(screen[(1, 0)].c, Some('8'));
+        assert_eq!(screen[(1, 5)].c, Some('8'));
+    }
+
+    #[test]
+    fn draw_side_by_side() {
+        let mut screen = Screen::new(Size::new(21, 5));
+
+        let view = DiffView::new(
+            "/new.txt".into(),
+            Base::File("/old.txt".into()),
+            String::from("a\nb\nc\n"),
+            String::from("a\nB\nb\nc\n"),
+        );
+        view.draw(&mut Context {
+            bounds: Bounds::from_size(screen.size),
+            screen: &mut screen,
+        });
+
+        let row = |y| {
+            (0..21)
+                .map(|x| screen[(y, x)].c.unwrap_or(' '))
+                .collect::<String>()
+                .trim_end()
+                .to_owned()
+        };
+
+        assert_eq!(row(0), "/old.txt  │/new.txt");
+        assert_eq!(row(1), "a         │a");
+        assert_eq!(row(2), "          │B");
+        assert_eq!(row(3), "b         │b");
+        assert_eq!(row(4), "c         │c");
+        assert!(screen[(2, 11)].bold);
+        assert!(!screen[(3, 11)].bold);
+    }
+
+    #[tokio::test]
+    async fn stage_hunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let path = dir.path().join("src/file.txt");
+        std::fs::write(&path, "a\nb\nc\nd\ne\n").unwrap();
+        git(&["init", "--quiet"]);
+        git(&["add", "src/file.txt"]);
+
+        let base = read_base(&path, &Base::Index).await.unwrap();
+        assert_eq!(base, "a\nb\nc\nd\ne\n");
+
+        let mut view = DiffView::new(
+            path.clone(),
+            Base::Index,
+            base,
+            String::from("a\nB\nc\ne\nf\n"),
+        );
+        assert!(view.next());
+        view.stage_current().await.unwrap();
+
+        assert_eq!(git(&["show", ":src/file.txt"]), "a\nb\nc\ne\n");
+
+        let view = DiffView::new(
+            path.clone(),
+            Base::Revision(String::from("HEAD")),
+            String::new(),
+            String::from("a\n"),
+        );
+        assert!(view.stage_current().await.is_err());
+
+        let view = DiffView::new(
+            path.clone(),
+            Base::File(dir.path().join("other.txt")),
+            String::new(),
+            String::from("a\n"),
+        );
+        assert_eq!(
+            view.stage_current().await.unwrap_err().to_string(),
+            "hunks can't be staged when comparing two files"
+        );
+        assert!(read_base(Path::new("/nonexistent/file.txt"), &Base::Index)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn read_base_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.txt");
+        std::fs::write(&old, "a\nb\n").unwrap();
+
+        let new = dir.path().join("new.txt");
+        let base = Base::File(old);
+        assert_eq!(read_base(&new, &base).await.unwrap(), "a\nb\n");
+
+        let base = Base::File(dir.path().join("missing.txt"));
+        assert!(read_base(&new, &base).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn diff_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.txt");
+        std::fs::write(&old, "a\nb\n").unwrap();
+        let new = dir.path().join("new.txt");
+
+        let (tx, mut rx) = mpsc::channel(1);
+        spawn(
+            new.clone(),
+            3,
+            Base::File(old),
+            None,
+            String::from("a\nc\n"),
+            tx.clone(),
+        );
+        let report = rx.next().await.unwrap();
+        assert_eq!(report.version, 3);
+        assert_eq!(report.view.unwrap().hunks.len(), 1);
+
+        spawn(
+            new,
+            3,
+            Base::Buffer(dir.path().join("other.txt")),
+            Some(String::from("a\nc\n")),
+            String::from("a\nc\n"),
+            tx,
+        );
+        assert!(rx.next().await.unwrap().view.unwrap().is_empty());
+    }
+}
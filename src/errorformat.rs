@@ -0,0 +1,243 @@
+//! Parsing of file locations from the output of compilers and linters, in the spirit of vim's
+//! `errorformat`.
+//!
+//! Two common formats are recognized: single lines of the form `file:line:column: severity:
+//! message`, as printed by GCC, Clang, and many linters, and rustc's multi-line format, in which a
+//! `severity: message` header is followed by a ` --> file:line:column` pointer. Other lines are
+//! ignored.
+
+use lsp_types::DiagnosticSeverity;
+
+/// A problem reported at a location in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// The path of the file, as printed.
+    pub path: String,
+
+    /// The zero-indexed line.
+    pub line: usize,
+
+    /// The zero-indexed column. Zero if the output didn't include a column.
+    pub column: usize,
+
+    pub severity: Option<DiagnosticSeverity>,
+
+    pub message: String,
+}
+
+/// Parses all problems from the output of a command.
+pub fn parse(output: &str) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    // The severity and message of a rustc-style header that is waiting for its location.
+    let mut header = None;
+
+    for line in output.lines() {
+        if let Some(location) = line.trim_start().strip_prefix("--> ") {
+            if let (Some((severity, message)), Some((path, row, column))) =
+                (header.take(), parse_location(location))
+            {
+                problems.push(Problem {
+                    path: path.to_owned(),
+                    line: row,
+                    column,
+                    severity,
+                    message,
+                });
+            }
+        } else if let Some(problem) = parse_line(line) {
+            header = None;
+            problems.push(problem);
+        } else if let Some(parsed_header) = parse_header(line) {
+            header = Some(parsed_header);
+        }
+    }
+
+    problems
+}
+
+/// Parses a line of the form `file:line:column: severity: message`, where the column and severity
+/// are optional.
+fn parse_line(line: &str) -> Option<Problem> {
+    let mut fields = line.splitn(3, ':');
+
+    let path = fields.next()?;
+    if path.is_empty() || path.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let row = fields.next()?.trim().parse::<usize>().ok()?;
+    let mut rest = fields.next()?;
+
+    let mut column = 1;
+    if let Some(index) = rest.find(':') {
+        if let Ok(col) = rest[..index].trim().parse::<usize>() {
+            column = col;
+            rest = &rest[index + 1..];
+        }
+    }
+
+    let (severity, message) = parse_message(rest);
+    if message.is_empty() {
+        return None;
+    }
+
+    Some(Problem {
+        path: path.to_owned(),
+        line: row.saturating_sub(1),
+        column: column.saturating_sub(1),
+        severity,
+        message,
+    })
+}
+
+/// Parses a rustc-style header, such as `error[E0425]: cannot find value`.
+fn parse_header(line: &str) -> Option<(Option<DiagnosticSeverity>, String)> {
+    let index = line.find(':')?;
+    let severity = parse_severity(&line[..index])?;
+    Some((Some(severity), line[index + 1..].trim().to_owned()))
+}
+
+/// Parses the `file:line:column` of a rustc-style location.
+fn parse_location(location: &str) -> Option<(&str, usize, usize)> {
+    let mut fields = location.trim().rsplitn(3, ':');
+    let column = fields.next()?.parse::<usize>().ok()?;
+    let row = fields.next()?.parse::<usize>().ok()?;
+    let path = fields.next()?;
+
+    Some((path, row.saturating_sub(1), column.saturating_sub(1)))
+}
+
+/// Splits a message into its severity, if it has one, and the rest of the message.
+fn parse_message(message: &str) -> (Option<DiagnosticSeverity>, String) {
+    let message = message.trim();
+
+    if let Some(index) = message.find(':') {
+        if let Some(severity) = parse_severity(&message[..index]) {
+            return (Some(severity), message[index + 1..].trim_start().to_owned());
+        }
+    }
+
+    (None, message.to_owned())
+}
+
+/// Parses the severity names used by common compilers and linters.
+///
+/// Error codes in brackets after the name, such as `error[E0425]`, are ignored.
+pub fn parse_severity(s: &str) -> Option<DiagnosticSeverity> {
+    let name = s.split('[').next().unwrap_or_default();
+
+    match name.trim().to_lowercase().as_str() {
+        "error" | "fatal error" | "fatal" => Some(DiagnosticSeverity::Error),
+        "warning" => Some(DiagnosticSeverity::Warning),
+        "info" | "information" | "note" => Some(DiagnosticSeverity::Information),
+        "hint" | "style" => Some(DiagnosticSeverity::Hint),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use lsp_types::DiagnosticSeverity;
+
+    use super::{parse, Problem};
+
+    fn problem(
+        path: &str,
+        line: usize,
+        column: usize,
+        severity: Option<DiagnosticSeverity>,
+        message: &str,
+    ) -> Problem {
+        Problem {
+            path: String::from(path),
+            line,
+            column,
+            severity,
+            message: String::from(message),
+        }
+    }
+
+    #[test]
+    fn parse_gcc() {
+        let output = indoc! {"
+            main.c: In function 'main':
+            main.c:3:5: error: expected ';' before '}' token
+                3 | }
+                  | ^
+            main.c:10: warning: unused variable 'x'
+            stdin:1:1: F401 'os' imported but unused
+            1 error generated.
+        "};
+
+        assert_eq!(
+            parse(output),
+            vec![
+                problem(
+                    "main.c",
+                    2,
+                    4,
+                    Some(DiagnosticSeverity::Error),
+                    "expected ';' before '}' token"
+                ),
+                problem(
+                    "main.c",
+                    9,
+                    0,
+                    Some(DiagnosticSeverity::Warning),
+                    "unused variable 'x'"
+                ),
+                problem("stdin", 0, 0, None, "F401 'os' imported but unused"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rustc() {
+        let output = indoc! {"
+               Compiling editor v0.1.0 (/src/editor)
+            error[E0425]: cannot find value `x` in this scope
+              --> src/lib.rs:10:5
+               |
+            10 |     x
+               |     ^ not found in this scope
+
+            warning: unused import: `std::fs`
+             --> src/main.rs:1:5
+              |
+            1 | use std::fs;
+              |     ^^^^^^^
+
+            error: aborting due to previous error
+            src/short.rs:4:1: error[E0308]: mismatched types
+        "};
+
+        assert_eq!(
+            parse(output),
+            vec![
+                problem(
+                    "src/lib.rs",
+                    9,
+                    4,
+                    Some(DiagnosticSeverity::Error),
+                    "cannot find value `x` in this scope"
+                ),
+                problem(
+                    "src/main.rs",
+                    0,
+                    4,
+                    Some(DiagnosticSeverity::Warning),
+                    "unused import: `std::fs`"
+                ),
+                problem(
+                    "src/short.rs",
+                    3,
+                    0,
+                    Some(DiagnosticSeverity::Error),
+                    "mismatched types"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Events that happen while editing, which parts of the editor and the config's hooks respond to.
+//!
+//! Like Vim's autocommands, hooks from the config run a command when an event happens, such as
+//! running `make` after a Rust file is written. Scripts may respond to events too. The editor's
+//! own responses to an event, such as trimming trailing whitespace before a buffer is written,
+//! happen as soon as the event is emitted. The requests of hooks and scripts are carried out once
+//! the key or message that caused the event has been handled. Events caused by those requests
+//! don't run hooks or scripts, so that they can't trigger each other forever.
+
+use std::ops::Range;
+use std::path::PathBuf;
+
+use glob::Pattern;
+use log::*;
+use serde::Deserialize;
+
+use crate::buffer::Position;
+use crate::config::HookConfig;
+use crate::Mode;
+
+/// The kind of an event, which hooks are registered for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    BufOpen,
+    BufWritePre,
+    BufWritePost,
+    ModeChanged,
+    TextYanked,
+    CursorHold,
+    FocusLost,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A file was opened in a buffer.
+    BufOpen(PathBuf),
+
+    /// The current buffer is about to be written to a file.
+    BufWritePre(PathBuf),
+
+    /// The current buffer was written to a file.
+    BufWritePost(PathBuf),
+
+    /// The mode changed from the first mode to the second.
+    ModeChanged(Mode, Mode),
+
+    /// Text was yanked from the current buffer.
+    TextYanked(Range<Position>),
+
+    /// No key was pressed in normal mode for `updatetime` milliseconds.
+    CursorHold,
+
+    /// The terminal lost focus.
+    FocusLost,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::BufOpen(_) => EventKind::BufOpen,
+            Event::BufWritePre(_) => EventKind::BufWritePre,
+            Event::BufWritePost(_) => EventKind::BufWritePost,
+            Event::ModeChanged(..) => EventKind::ModeChanged,
+            Event::TextYanked(_) => EventKind::TextYanked,
+            Event::CursorHold => EventKind::CursorHold,
+            Event::FocusLost => EventKind::FocusLost,
+        }
+    }
+
+    /// Returns what the patterns of hooks are matched against: the path of the file for buffer
+    /// events, and the names of the old and new modes separated by a colon for mode changes.
+    pub fn subject(&self) -> Option<String> {
+        match self {
+            Event::BufOpen(path) | Event::BufWritePre(path) | Event::BufWritePost(path) => {
+                Some(path.display().to_string())
+            }
+            Event::ModeChanged(old, new) => Some(format!("{}:{}", old.name(), new.name())),
+            Event::TextYanked(_) | Event::CursorHold | Event::FocusLost => None,
+        }
+    }
+}
+
+/// Something that a hook or a script asks the editor to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Run a command line.
+    Command(String),
+
+    /// Insert text at the cursor.
+    Insert(String),
+
+    /// Insert a snippet at the cursor and start filling in its tab stops.
+    Snippet(String),
+
+    /// Show a message.
+    Message(String),
+}
+
+/// The hooks from the config, and the requests of hooks and scripts that are waiting to be
+/// carried out.
+#[derive(Debug, Default)]
+pub struct Hooks {
+    hooks: Vec<HookConfig>,
+
+    /// Requests whose events have happened, in order.
+    pending: Vec<Request>,
+
+    /// Whether requests are being carried out, so events don't run hooks.
+    running: bool,
+}
+
+impl Hooks {
+    pub fn new(hooks: Vec<HookConfig>) -> Self {
+        Hooks {
+            hooks,
+            ..Hooks::default()
+        }
+    }
+
+    /// Queues the commands of the hooks that match an event.
+    pub fn queue(&mut self, event: &Event) {
+        if self.running {
+            return;
+        }
+
+        let subject = event.subject();
+
+        for hook in &self.hooks {
+            if hook.event != event.kind() {
+                continue;
+            }
+
+            let matches = match (&hook.pattern, &subject) {
+                (None, _) => true,
+                (Some(pattern), Some(subject)) => match Pattern::new(pattern) {
+                    Ok(pattern) => pattern.matches(subject),
+                    Err(e) => {
+                        warn!("invalid hook pattern {}: {}", pattern, e);
+                        false
+                    }
+                },
+                (Some(_), None) => false,
+            };
+
+            if matches {
+                self.pending.push(Request::Command(hook.command.clone()));
+            }
+        }
+    }
+
+    /// Queues requests from a script.
+    pub fn extend(&mut self, requests: Vec<Request>) {
+        self.pending.extend(requests);
+    }
+
+    /// Returns whether requests are being carried out, so events shouldn't be responded to.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Takes the requests that are waiting to be carried out. Events don't run hooks until
+    /// `finish` is called.
+    pub fn start(&mut self) -> Vec<Request> {
+        self.running = !self.pending.is_empty();
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Lets events run hooks again, once the requests taken by `start` have been carried out.
+    pub fn finish(&mut self) {
+        self.running = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::buffer::Position;
+    use crate::config::HookConfig;
+    use crate::Mode;
+
+    use super::{Event, EventKind, Hooks, Request};
+
+    fn hook(event: EventKind, pattern: Option<&str>, command: &str) -> HookConfig {
+        HookConfig {
+            event,
+            pattern: pattern.map(String::from),
+            command: String::from(command),
+        }
+    }
+
+    #[test]
+    fn queue_matching_hooks() {
+        let mut hooks = Hooks::new(vec![
+            hook(EventKind::BufWritePost, Some("*.rs"), "make"),
+            hook(EventKind::BufWritePost, None, "lint"),
+            hook(EventKind::ModeChanged, Some("insert:*"), "w"),
+            hook(EventKind::TextYanked, Some("*"), "yanks"),
+        ]);
+
+        hooks.queue(&Event::BufWritePost(PathBuf::from("/src/lib.rs")));
+        hooks.queue(&Event::BufWritePre(PathBuf::from("/src/lib.rs")));
+        hooks.queue(&Event::BufWritePost(PathBuf::from("/README.md")));
+        hooks.queue(&Event::ModeChanged(Mode::Insert, Mode::Normal));
+        hooks.queue(&Event::ModeChanged(Mode::Normal, Mode::Insert));
+        hooks.queue(&Event::TextYanked(Position::zero()..Position::new(1, 0)));
+
+        assert_eq!(
+            hooks.start(),
+            vec![
+                Request::Command(String::from("make")),
+                Request::Command(String::from("lint")),
+                Request::Command(String::from("lint")),
+                Request::Command(String::from("w")),
+            ]
+        );
+
+        // Events caused by the commands of hooks don't run hooks.
+        hooks.queue(&Event::BufWritePost(PathBuf::from("/src/lib.rs")));
+        hooks.finish();
+        assert!(hooks.start().is_empty());
+
+        hooks.queue(&Event::BufWritePost(PathBuf::from("/src/lib.rs")));
+        assert_eq!(hooks.start().len(), 2);
+    }
+}
@@ -0,0 +1,116 @@
+//! Formatting buffers with external programs before they are written.
+//!
+//! Formatters are configured per language with `format` in the `save.language` table of the
+//! config file. The contents of a buffer are written to the formatter's standard input, and what
+//! it prints replaces them if it exits successfully.
+
+use std::path::Path;
+use std::process::Stdio;
+
+use futures::future;
+use log::*;
+use thiserror::Error;
+use tokio::io::{self, AsyncWriteExt};
+use tokio::process::Command;
+
+/// Replaced by the path of the buffer's file in the arguments of formatters, for formatters that
+/// need it to find their configuration, like `prettier --stdin-filepath {file}`.
+const FILE_PLACEHOLDER: &str = "{file}";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to run {0}: {1}")]
+    Io(String, #[source] io::Error),
+
+    #[error("{0} failed: {1}")]
+    Failed(String, String),
+
+    #[error("{0} printed invalid UTF-8")]
+    Utf8(String),
+}
+
+/// Runs a formatter over the text of the buffer at `path`, returning the formatted text.
+///
+/// Like linters, the formatter is run in the buffer's directory.
+pub async fn run(command: &[String], text: String, path: &Path) -> Result<String, Error> {
+    let (prog, args) = command.split_first().expect("command should not be empty");
+    let file = path.to_string_lossy();
+
+    let mut command = Command::new(prog);
+    command
+        .args(args.iter().map(|arg| arg.replace(FILE_PLACEHOLDER, &file)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn().map_err(|e| Error::Io(prog.clone(), e))?;
+
+    let mut stdin = child.stdin.take().expect("stdin was not piped");
+    let write_stdin = async move {
+        if let Err(e) = stdin.write_all(text.as_bytes()).await {
+            warn!("unable to write to formatter: {}", e);
+        }
+    };
+
+    let (_, output) = future::join(write_stdin, child.wait_with_output()).await;
+    let output = output.map_err(|e| Error::Io(prog.clone(), e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .map_or_else(|| output.status.to_string(), str::to_owned);
+        return Err(Error::Failed(prog.clone(), reason));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| Error::Utf8(prog.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{run, Error};
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[tokio::test]
+    async fn format() {
+        let path = Path::new("/tmp/main.rs");
+
+        let formatted = run(
+            &command(&["tr", "a-z", "A-Z"]),
+            String::from("hello\n"),
+            path,
+        )
+        .await
+        .unwrap();
+        assert_eq!(formatted, "HELLO\n");
+
+        let formatted = run(
+            &command(&["sh", "-c", "cat; echo {file}"]),
+            String::from("hello\n"),
+            path,
+        )
+        .await
+        .unwrap();
+        assert_eq!(formatted, "hello\n/tmp/main.rs\n");
+
+        let error = run(
+            &command(&["sh", "-c", "echo 'syntax error' >&2; exit 1"]),
+            String::from("hello\n"),
+            path,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, Error::Failed(_, reason) if reason == "syntax error"));
+    }
+}
@@ -0,0 +1,590 @@
+//! Loading tree-sitter grammars and their queries.
+//!
+//! Grammars are bundled with the editor by the `tree-sitter-languages` crate. Additionally, users
+//! may supply their own compiled grammars in the config directory, which take precedence over the
+//! bundled grammars:
+//!
+//! ```text
+//! ~/.config/editor/grammars/<language>/
+//! ├── parser.so
+//! └── queries/
+//!     ├── highlights.scm
+//!     ├── injections.scm
+//!     ├── locals.scm
+//!     ├── folds.scm
+//!     └── textobjects.scm
+//! ```
+//!
+//! The shared library must export a function named `tree_sitter_<language>`, which is the
+//! convention for grammars generated by the tree-sitter CLI.
+//!
+//! The queries of any grammar, bundled or not, may be overridden by placing them in
+//! `~/.config/editor/queries/<language>/`. A query that starts with a `; extends` comment is
+//! appended to the grammar's query instead of replacing it.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use libloading::{Library, Symbol};
+use log::*;
+use thiserror::Error;
+use tree_sitter::{Language, LanguageError, Parser, Query, QueryError};
+
+use crate::config::Config;
+use crate::syntax::Syntax;
+
+lazy_static! {
+    /// Names of languages that have been discovered in the user's grammar directory.
+    static ref USER_LANGUAGES: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+
+    /// Grammar libraries that have been loaded, by path, and the languages that they define.
+    ///
+    /// Languages refer to static data within their library, so libraries are never unloaded.
+    static ref LIBRARIES: Mutex<HashMap<PathBuf, (Library, Language)>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("no grammar available")]
+    NotFound,
+
+    #[error("unable to read query: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("unable to load grammar library: {0}")]
+    Library(#[from] libloading::Error),
+
+    #[error("invalid {0} query: {1:?}")]
+    Query(&'static str, QueryError),
+
+    #[error("{0}")]
+    Incompatible(LanguageError),
+}
+
+/// A tree-sitter language and the queries used to highlight it.
+pub struct Grammar {
+    pub language: Language,
+    pub highlights: Query,
+    pub injections: Option<Query>,
+
+    /// Query identifying scopes, definitions and references, used to highlight local variables.
+    pub locals: Option<Query>,
+
+    /// Query identifying regions of the buffer that may be folded.
+    pub folds: Option<Query>,
+
+    /// Query identifying functions and classes, used for structural navigation.
+    pub textobjects: Option<Query>,
+}
+
+/// The sources of a grammar's queries.
+struct QuerySources<S> {
+    highlights: S,
+    injections: Option<S>,
+    locals: Option<S>,
+    folds: Option<S>,
+    textobjects: Option<S>,
+}
+
+impl Grammar {
+    /// Loads the grammar for a syntax.
+    ///
+    /// Grammars in the user's grammar directory are preferred. If a user grammar fails to load, the
+    /// bundled grammar is used instead, if there is one. In either case, the queries may be
+    /// overridden by the user's query directory.
+    pub fn load(syntax: Syntax) -> Result<Self, LoadError> {
+        let name = syntax.into_language_id();
+
+        let user_grammar = user_grammar_dir(name).map(|dir| {
+            let grammar = Grammar::load_dynamic(name, &dir);
+            if let Err(e) = &grammar {
+                error!("unable to load grammar from {}: {}", dir.display(), e);
+            }
+            grammar
+        });
+
+        let (language, sources) = match (user_grammar, Grammar::bundled(syntax)) {
+            (Some(Ok(grammar)), _) => grammar,
+            (_, Some((language, sources))) => (
+                language,
+                QuerySources {
+                    highlights: sources.highlights.to_owned(),
+                    injections: sources.injections.map(str::to_owned),
+                    locals: sources.locals.map(str::to_owned),
+                    folds: sources.folds.map(str::to_owned),
+                    textobjects: sources.textobjects.map(str::to_owned),
+                },
+            ),
+            (Some(Err(e)), None) => return Err(e),
+            (None, None) => return Err(LoadError::NotFound),
+        };
+
+        Ok(Grammar {
+            language,
+            highlights: compile_query(name, language, "highlights", Some(&sources.highlights))?
+                .ok_or(LoadError::NotFound)?,
+            injections: compile_query(name, language, "injections", sources.injections.as_deref())?,
+            locals: compile_query(name, language, "locals", sources.locals.as_deref())?,
+            folds: compile_query(name, language, "folds", sources.folds.as_deref())?,
+            textobjects: compile_query(
+                name,
+                language,
+                "textobjects",
+                sources.textobjects.as_deref(),
+            )?,
+        })
+    }
+
+    /// Loads a grammar from a shared library and query files in the given directory.
+    ///
+    /// Returns the language and the sources of its queries.
+    fn load_dynamic(name: &str, dir: &Path) -> Result<(Language, QuerySources<String>), LoadError> {
+        let library_path = dir
+            .join("parser")
+            .with_extension(env::consts::DLL_EXTENSION);
+        let language = load_library(name, &library_path)?;
+
+        Parser::new()
+            .set_language(language)
+            .map_err(LoadError::Incompatible)?;
+
+        let queries = dir.join("queries");
+        let highlights = fs::read_to_string(queries.join("highlights.scm"))?;
+        let injections = read_optional(&queries.join("injections.scm"))?;
+        let locals = read_optional(&queries.join("locals.scm"))?;
+        let folds = read_optional(&queries.join("folds.scm"))?;
+        let textobjects = read_optional(&queries.join("textobjects.scm"))?;
+
+        Ok((
+            language,
+            QuerySources {
+                highlights,
+                injections,
+                locals,
+                folds,
+                textobjects,
+            },
+        ))
+    }
+
+    /// Returns the language and query sources of the grammar bundled with the editor, if any.
+    fn bundled(syntax: Syntax) -> Option<(Language, QuerySources<&'static str>)> {
+        match syntax {
+            #[cfg(feature = "lang-bash")]
+            Syntax::Bash => Some((
+                tree_sitter_languages::bash(),
+                QuerySources {
+                    highlights: tree_sitter_languages::BASH_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::BASH_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::BASH_LOCALS_QUERY,
+                    folds: tree_sitter_languages::BASH_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::BASH_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-c")]
+            Syntax::C => Some((
+                tree_sitter_languages::c(),
+                QuerySources {
+                    highlights: tree_sitter_languages::C_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::C_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::C_LOCALS_QUERY,
+                    folds: tree_sitter_languages::C_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::C_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-cpp")]
+            Syntax::Cpp => Some((
+                tree_sitter_languages::cpp(),
+                QuerySources {
+                    highlights: tree_sitter_languages::CPP_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::CPP_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::CPP_LOCALS_QUERY,
+                    folds: tree_sitter_languages::CPP_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::CPP_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-css")]
+            Syntax::Css => Some((
+                tree_sitter_languages::css(),
+                QuerySources {
+                    highlights: tree_sitter_languages::CSS_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::CSS_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::CSS_LOCALS_QUERY,
+                    folds: tree_sitter_languages::CSS_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::CSS_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-go")]
+            Syntax::Go => Some((
+                tree_sitter_languages::go(),
+                QuerySources {
+                    highlights: tree_sitter_languages::GO_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::GO_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::GO_LOCALS_QUERY,
+                    folds: tree_sitter_languages::GO_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::GO_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-html")]
+            Syntax::Html => Some((
+                tree_sitter_languages::html(),
+                QuerySources {
+                    highlights: tree_sitter_languages::HTML_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::HTML_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::HTML_LOCALS_QUERY,
+                    folds: tree_sitter_languages::HTML_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::HTML_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-javascript")]
+            Syntax::JavaScript => Some((
+                tree_sitter_languages::javascript(),
+                QuerySources {
+                    highlights: tree_sitter_languages::JAVASCRIPT_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::JAVASCRIPT_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::JAVASCRIPT_LOCALS_QUERY,
+                    folds: tree_sitter_languages::JAVASCRIPT_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::JAVASCRIPT_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-json")]
+            Syntax::Json => Some((
+                tree_sitter_languages::json(),
+                QuerySources {
+                    highlights: tree_sitter_languages::JSON_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::JSON_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::JSON_LOCALS_QUERY,
+                    folds: tree_sitter_languages::JSON_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::JSON_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-markdown")]
+            Syntax::Markdown => Some((
+                tree_sitter_languages::markdown(),
+                QuerySources {
+                    highlights: tree_sitter_languages::MARKDOWN_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::MARKDOWN_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::MARKDOWN_LOCALS_QUERY,
+                    folds: tree_sitter_languages::MARKDOWN_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::MARKDOWN_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-python")]
+            Syntax::Python => Some((
+                tree_sitter_languages::python(),
+                QuerySources {
+                    highlights: tree_sitter_languages::PYTHON_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::PYTHON_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::PYTHON_LOCALS_QUERY,
+                    folds: tree_sitter_languages::PYTHON_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::PYTHON_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-rust")]
+            Syntax::Rust => Some((
+                tree_sitter_languages::rust(),
+                QuerySources {
+                    highlights: tree_sitter_languages::RUST_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::RUST_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::RUST_LOCALS_QUERY,
+                    folds: tree_sitter_languages::RUST_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::RUST_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-toml")]
+            Syntax::Toml => Some((
+                tree_sitter_languages::toml(),
+                QuerySources {
+                    highlights: tree_sitter_languages::TOML_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::TOML_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::TOML_LOCALS_QUERY,
+                    folds: tree_sitter_languages::TOML_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::TOML_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-typescript")]
+            Syntax::TypeScript => Some((
+                tree_sitter_languages::typescript(),
+                QuerySources {
+                    highlights: tree_sitter_languages::TYPESCRIPT_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::TYPESCRIPT_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::TYPESCRIPT_LOCALS_QUERY,
+                    folds: tree_sitter_languages::TYPESCRIPT_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::TYPESCRIPT_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-typescript")]
+            Syntax::Tsx => Some((
+                tree_sitter_languages::tsx(),
+                QuerySources {
+                    highlights: tree_sitter_languages::TSX_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::TSX_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::TSX_LOCALS_QUERY,
+                    folds: tree_sitter_languages::TSX_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::TSX_TEXTOBJECTS_QUERY,
+                },
+            )),
+            #[cfg(feature = "lang-yaml")]
+            Syntax::Yaml => Some((
+                tree_sitter_languages::yaml(),
+                QuerySources {
+                    highlights: tree_sitter_languages::YAML_HIGHLIGHTS_QUERY,
+                    injections: tree_sitter_languages::YAML_INJECTIONS_QUERY,
+                    locals: tree_sitter_languages::YAML_LOCALS_QUERY,
+                    folds: tree_sitter_languages::YAML_FOLDS_QUERY,
+                    textobjects: tree_sitter_languages::YAML_TEXTOBJECTS_QUERY,
+                },
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Loads the language defined by a grammar library, or returns it if the library was already
+/// loaded.
+fn load_library(name: &str, library_path: &Path) -> Result<Language, LoadError> {
+    let mut libraries = LIBRARIES.lock().expect("grammar library cache poisoned");
+
+    if let Some((_, language)) = libraries.get(library_path) {
+        return Ok(*language);
+    }
+
+    info!("loading grammar from {}", library_path.display());
+
+    let symbol = format!("tree_sitter_{}", name.replace('-', "_"));
+
+    let (library, language) = unsafe {
+        let library = Library::new(library_path)?;
+        let language_fn: Symbol<unsafe extern "C" fn() -> Language> =
+            library.get(symbol.as_bytes())?;
+        let language = language_fn();
+        (library, language)
+    };
+
+    libraries.insert(library_path.to_owned(), (library, language));
+
+    Ok(language)
+}
+
+/// Compiles a query, taking the user's query directory into account.
+///
+/// If the user has a query of the same kind for the language, it replaces the default query, unless
+/// it begins with a `; extends` comment, in which case it is appended to the default query. If the
+/// user's query is invalid, the default query is used instead.
+fn compile_query(
+    name: &str,
+    language: Language,
+    kind: &'static str,
+    default: Option<&str>,
+) -> Result<Option<Query>, LoadError> {
+    let user_query = match user_query_path(name, kind) {
+        Some(path) => {
+            let query = read_optional(&path)?;
+            if query.is_some() {
+                info!("using user query from {}", path.display());
+            }
+            query
+        }
+        None => None,
+    };
+
+    if let Some(source) = merge_user_query(default, user_query) {
+        match Query::new(language, &source) {
+            Ok(query) => return Ok(Some(query)),
+            Err(e) => error!("invalid user {} query for {}: {:?}", kind, name, e),
+        }
+    }
+
+    default
+        .map(|source| Query::new(language, source).map_err(|e| LoadError::Query(kind, e)))
+        .transpose()
+}
+
+/// Combines a default query with a user-supplied query, if any.
+///
+/// Returns `None` if there is no user query.
+fn merge_user_query(default: Option<&str>, user: Option<String>) -> Option<String> {
+    let user = user?;
+
+    let extends = user
+        .lines()
+        .next()
+        .map(|line| line.trim_start_matches(';').trim() == "extends")
+        .unwrap_or(false);
+
+    match default {
+        Some(default) if extends => Some(format!("{}\n{}", default, user)),
+        _ => Some(user),
+    }
+}
+
+/// Reads a file to a string, returning `None` if it doesn't exist.
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Verifies that the grammar for a syntax can be loaded and is compatible with the tree-sitter
+/// runtime.
+///
+/// On success, returns the number of captures defined by the grammar's highlight query.
+pub fn check_grammar(syntax: Syntax) -> Result<usize, LoadError> {
+    let grammar = Grammar::load(syntax)?;
+    Parser::new()
+        .set_language(grammar.language)
+        .map_err(LoadError::Incompatible)?;
+    Ok(grammar.highlights.capture_names().len())
+}
+
+/// Returns the name of a language in the user's grammar directory, if it exists.
+///
+/// The returned name lives for the rest of the program so that it may be used in a
+/// [`Syntax::Dynamic`].
+pub fn user_language(name: &str) -> Option<&'static str> {
+    user_grammar_dir(name)?;
+
+    let mut user_languages = USER_LANGUAGES.lock().expect("user languages lock poisoned");
+
+    match user_languages.get(name) {
+        Some(name) => Some(name),
+        None => {
+            let name = Box::leak(name.to_owned().into_boxed_str());
+            user_languages.insert(name);
+            Some(name)
+        }
+    }
+}
+
+/// Returns the path of the user's query of a given kind for a language.
+fn user_query_path(name: &str, kind: &str) -> Option<PathBuf> {
+    if !is_valid_name(name) {
+        return None;
+    }
+
+    Some(
+        Config::config_dir()?
+            .join("queries")
+            .join(name)
+            .join(kind)
+            .with_extension("scm"),
+    )
+}
+
+/// Returns whether a language name is safe to use as a directory name.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(std::path::is_separator) && !name.starts_with('.')
+}
+
+/// Returns the directory containing the user's grammar for a language, if it exists.
+fn user_grammar_dir(name: &str) -> Option<PathBuf> {
+    grammar_dir(Config::config_dir()?, name)
+}
+
+fn grammar_dir(config_dir: PathBuf, name: &str) -> Option<PathBuf> {
+    // Don't allow names to escape the grammars directory.
+    if !is_valid_name(name) {
+        return None;
+    }
+
+    let dir = config_dir.join("grammars").join(name);
+
+    if dir.is_dir() {
+        Some(dir)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    #[cfg(feature = "lang-rust")]
+    use crate::syntax::Syntax;
+
+    use super::{grammar_dir, load_library, merge_user_query, Grammar, LoadError};
+
+    #[test]
+    fn grammar_dir_exists() {
+        let config_dir = TempDir::new().unwrap();
+        fs::create_dir_all(config_dir.path().join("grammars/zig")).unwrap();
+
+        assert_eq!(
+            grammar_dir(config_dir.path().to_owned(), "zig"),
+            Some(config_dir.path().join("grammars/zig"))
+        );
+        assert_eq!(grammar_dir(config_dir.path().to_owned(), "nim"), None);
+        assert_eq!(grammar_dir(config_dir.path().to_owned(), ".."), None);
+        assert_eq!(grammar_dir(config_dir.path().to_owned(), "zig/.."), None);
+    }
+
+    #[test]
+    fn load_dynamic_missing_library() {
+        let dir = TempDir::new().unwrap();
+
+        assert!(matches!(
+            Grammar::load_dynamic("zig", dir.path()),
+            Err(LoadError::Library(_))
+        ));
+    }
+
+    #[test]
+    fn load_library_once() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("parser.c");
+        let library = dir.path().join("parser.so");
+        fs::write(&source, "void *tree_sitter_fake(void) { return 0; }\n").unwrap();
+
+        let status = std::process::Command::new("cc")
+            .args(["-shared", "-fPIC", "-o"])
+            .arg(&library)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        assert!(load_library("fake", &library).is_ok());
+
+        // The cached library is used, so the file isn't needed anymore.
+        fs::remove_file(&library).unwrap();
+        assert!(load_library("fake", &library).is_ok());
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn load_bundled() {
+        assert!(Grammar::load(Syntax::Rust).is_ok());
+        assert!(Grammar::bundled(Syntax::Dynamic("zig")).is_none());
+    }
+
+    #[test]
+    fn user_query_replaces_default() {
+        assert_eq!(
+            merge_user_query(Some("(a) @a"), Some(String::from("(b) @b"))),
+            Some(String::from("(b) @b"))
+        );
+        assert_eq!(
+            merge_user_query(None, Some(String::from("(b) @b"))),
+            Some(String::from("(b) @b"))
+        );
+        assert_eq!(merge_user_query(Some("(a) @a"), None), None);
+    }
+
+    #[test]
+    fn user_query_extends_default() {
+        assert_eq!(
+            merge_user_query(Some("(a) @a"), Some(String::from(";; extends\n(b) @b"))),
+            Some(String::from("(a) @a\n;; extends\n(b) @b"))
+        );
+    }
+}
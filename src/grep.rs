@@ -0,0 +1,331 @@
+//! Project-wide search with `:grep`.
+//!
+//! The search program, ripgrep by default, is run asynchronously in the editor's working
+//! directory. Matches are streamed to the editor in batches as they're found, so that the first
+//! results can be visited while the search continues.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use log::*;
+use thiserror::Error;
+use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::LinesStream;
+
+use crate::buffer::Position;
+use crate::quickfix::Entry;
+
+/// The maximum number of matches sent to the editor at once.
+const BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to run {0}: {1}")]
+    Io(String, #[source] io::Error),
+
+    #[error("{0} failed: {1}")]
+    Failed(String, String),
+}
+
+/// Progress of a search.
+#[derive(Debug)]
+pub struct Update {
+    /// Identifies the search, so that updates from a search that has been superseded by a newer
+    /// one can be ignored.
+    pub id: u64,
+
+    pub event: Event,
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /// Matches were found.
+    Matches(Vec<Entry>),
+
+    /// The search finished.
+    Finished(Result<(), Error>),
+}
+
+/// Searches for a pattern in the background, sending updates as matches are found. If
+/// `fixed_strings` is set, the pattern is matched literally, which both `grep` and ripgrep are told
+/// with `-F`.
+///
+/// Aborting the returned task stops the search and kills the search program.
+pub fn spawn(
+    id: u64,
+    (prog, args): (&String, &[String]),
+    pattern: String,
+    fixed_strings: bool,
+    dir: PathBuf,
+    mut update_sender: mpsc::Sender<Update>,
+) -> JoinHandle<()> {
+    let mut command = Command::new(prog);
+    command.args(args);
+    if fixed_strings {
+        command.arg("-F");
+    }
+    command
+        .arg("--")
+        .arg(pattern)
+        .current_dir(&dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let prog = prog.clone();
+
+    tokio::spawn(async move {
+        let result = search(command, &prog, &dir, |entries| {
+            let mut update_sender = update_sender.clone();
+            async move {
+                update_sender
+                    .send(Update {
+                        id,
+                        event: Event::Matches(entries),
+                    })
+                    .await
+                    .is_ok()
+            }
+        })
+        .await;
+
+        let update = Update {
+            id,
+            event: Event::Finished(result),
+        };
+
+        if update_sender.send(update).await.is_err() {
+            info!("editor exited before search finished");
+        }
+    })
+}
+
+/// Runs a search command, passing batches of matches to `on_matches` as they're found.
+///
+/// The search stops early if `on_matches` returns `false`.
+async fn search<F, Fut>(
+    mut command: Command,
+    prog: &str,
+    dir: &Path,
+    mut on_matches: F,
+) -> Result<(), Error>
+where
+    F: FnMut(Vec<Entry>) -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let mut child = command.spawn().map_err(|e| Error::Io(prog.to_owned(), e))?;
+
+    // Standard error is read concurrently, so that the search doesn't block if it fills the pipe.
+    let mut stderr = child.stderr.take().expect("stderr was not piped");
+    let stderr = tokio::spawn(async move {
+        let mut output = vec![];
+        stderr.read_to_end(&mut output).await.map(|_| output)
+    });
+
+    let stdout = child.stdout.take().expect("stdout was not piped");
+    let mut batches = LinesStream::new(BufReader::new(stdout).lines()).ready_chunks(BATCH_SIZE);
+
+    while let Some(lines) = batches.next().await {
+        let entries = lines
+            .into_iter()
+            .filter_map(|line| match line {
+                Ok(line) => parse_match(&line, dir),
+                Err(e) => {
+                    warn!("unable to read search output: {}", e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if !entries.is_empty() && !on_matches(entries).await {
+            return Ok(());
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| Error::Io(prog.to_owned(), e))?;
+
+    // grep and ripgrep exit with 1 if there were no matches, and 2 if there was an error.
+    match status.code() {
+        Some(0) | Some(1) => Ok(()),
+        _ => {
+            let stderr = match stderr.await {
+                Ok(stderr) => stderr,
+                Err(e) => Err(e.into()),
+            }
+            .unwrap_or_else(|e: io::Error| {
+                warn!("unable to read search errors: {}", e);
+                vec![]
+            });
+            let reason = String::from_utf8_lossy(&stderr)
+                .lines()
+                .next()
+                .map(String::from)
+                .unwrap_or_else(|| status.to_string());
+            Err(Error::Failed(prog.to_owned(), reason))
+        }
+    }
+}
+
+/// Parses a match in the form `path:line:column:text`, as printed by `rg --vimgrep`, or
+/// `path:line:text`, as printed by `grep -n`.
+///
+/// The text of the matching line is kept as the entry's message, as a preview of the match.
+fn parse_match(line: &str, dir: &Path) -> Option<Entry> {
+    let mut fields = line.splitn(3, ':');
+    let path = fields.next()?;
+    let row = fields.next()?.parse::<usize>().ok()?;
+    let mut text = fields.next()?;
+
+    let mut column = 1;
+    if let Some(index) = text.find(':') {
+        if let Ok(col) = text[..index].parse::<usize>() {
+            column = col;
+            text = &text[index + 1..];
+        }
+    }
+
+    Some(Entry {
+        path: dir.join(path),
+        position: Position::new(column.saturating_sub(1), row.saturating_sub(1)),
+        severity: None,
+        message: text.trim().to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use tokio::process::Command;
+
+    use crate::buffer::Position;
+    use crate::quickfix::Entry;
+
+    use super::{parse_match, search, spawn, Event};
+
+    #[test]
+    fn parse_vimgrep() {
+        assert_eq!(
+            parse_match("src/lib.rs:12:5:    let x = 1;", Path::new("/project")),
+            Some(Entry {
+                path: Path::new("/project/src/lib.rs").to_owned(),
+                position: Position::new(4, 11),
+                severity: None,
+                message: String::from("let x = 1;"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_grep() {
+        assert_eq!(
+            parse_match("README.md:3:see: the docs", Path::new("/project")),
+            Some(Entry {
+                path: Path::new("/project/README.md").to_owned(),
+                position: Position::new(0, 2),
+                severity: None,
+                message: String::from("see: the docs"),
+            })
+        );
+
+        assert_eq!(parse_match("Binary file x matches", Path::new("/")), None);
+    }
+
+    #[tokio::test]
+    async fn search_in_batches() {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "echo a.txt:1:1:foo; echo b.txt:2:3:bar"])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        let mut found = vec![];
+        search(command, "sh", Path::new("/project"), |entries| {
+            found.extend(entries);
+            async { true }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            found
+                .iter()
+                .map(|entry| entry.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["foo", "bar"]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_failure() {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", "echo 'regex parse error' >&2; exit 2"])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        let err = search(command, "rg", Path::new("/"), |_| async { true })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "rg failed: regex parse error");
+    }
+
+    #[tokio::test]
+    async fn search_with_noisy_stderr() {
+        let mut command = Command::new("sh");
+        command
+            .args([
+                "-c",
+                "yes 'permission denied' | head -c 200000 >&2; echo a.txt:1:1:foo",
+            ])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        let mut found = vec![];
+        search(command, "sh", Path::new("/project"), |entries| {
+            found.extend(entries);
+            async { true }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(found.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn abort_search() {
+        let (tx, mut rx) = mpsc::channel(1);
+
+        let task = spawn(
+            1,
+            (
+                &String::from("sh"),
+                &[
+                    String::from("-c"),
+                    String::from("echo a.txt:1:foo; sleep 10"),
+                    String::from("sh"),
+                ],
+            ),
+            String::from("foo"),
+            false,
+            Path::new("/").to_owned(),
+            tx,
+        );
+
+        let update = rx.next().await.unwrap();
+        assert!(matches!(update.event, Event::Matches(_)));
+
+        task.abort();
+        assert!(rx.next().await.is_none());
+    }
+}
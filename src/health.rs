@@ -0,0 +1,249 @@
+//! Diagnostics for the editor's environment, in the spirit of vim's `:checkhealth`.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use strum::IntoEnumIterator;
+use terminfo::capability as cap;
+
+use crate::config::Config;
+use crate::grammar::check_grammar;
+use crate::syntax::Syntax;
+use crate::term::Terminal;
+
+/// Outcome of a single health check.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Error => "ERROR",
+        })
+    }
+}
+
+/// A human-readable health report, organized into sections.
+#[derive(Debug, Default)]
+struct Report {
+    text: String,
+}
+
+impl Report {
+    fn section(&mut self, name: &str) {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+
+        let _ = writeln!(self.text, "## {}", name);
+    }
+
+    fn report(&mut self, status: Status, message: impl Display) {
+        let _ = writeln!(self.text, "- {}: {}", status, message);
+    }
+}
+
+/// Checks the editor's environment and returns a report suitable for displaying in a buffer.
+pub async fn check(term: &Terminal, config_paths: &[PathBuf]) -> String {
+    let mut report = Report::default();
+
+    let config = check_config(&mut report, config_paths).await;
+    check_language_servers(&mut report, &config);
+    check_terminal(&mut report, term);
+    check_grammars(&mut report);
+
+    report.text
+}
+
+async fn check_config(report: &mut Report, paths: &[PathBuf]) -> Config {
+    report.section("Configuration");
+
+    if Config::config_dir().is_none() {
+        report.report(
+            Status::Warning,
+            "could not determine config directory: neither XDG_CONFIG_HOME nor HOME is set",
+        );
+    }
+
+    let existing = paths
+        .iter()
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+    if existing.is_empty() {
+        report.report(Status::Ok, "no config file, using defaults");
+    }
+    for path in existing {
+        report.report(Status::Ok, format!("config file: {}", path.display()));
+    }
+
+    match Config::read(paths).await {
+        Ok(config) => {
+            report.report(Status::Ok, "config parsed successfully");
+            config
+        }
+        Err(e) => {
+            report.report(Status::Error, format!("unable to read config: {}", e));
+            Config::default()
+        }
+    }
+}
+
+fn check_language_servers(report: &mut Report, config: &Config) {
+    report.section("Language servers");
+
+    for syntax in Syntax::iter() {
+        let name = syntax.into_language_id();
+
+        let server_config = match config.language_server_config.get(&syntax) {
+            Some(server_config) => server_config,
+            None => {
+                report.report(Status::Ok, format!("{}: no server configured", name));
+                continue;
+            }
+        };
+
+        let (program, _) = server_config.command();
+
+        match find_executable(program, env::var_os("PATH")) {
+            Some(path) => report.report(
+                Status::Ok,
+                format!("{}: {} found at {}", name, program, path.display()),
+            ),
+            None => report.report(
+                Status::Error,
+                format!("{}: {} is not executable or not on PATH", name, program),
+            ),
+        }
+    }
+}
+
+fn check_terminal(report: &mut Report, term: &Terminal) {
+    report.section("Terminal");
+
+    let terminfo = term.terminfo();
+    report.report(Status::Ok, format!("terminfo entry: {}", terminfo.name()));
+
+    let capabilities = [
+        ("smcup", terminfo.get::<cap::EnterCaMode>().is_some()),
+        ("rmcup", terminfo.get::<cap::ExitCaMode>().is_some()),
+        ("cup", terminfo.get::<cap::CursorAddress>().is_some()),
+        ("civis", terminfo.get::<cap::CursorInvisible>().is_some()),
+        ("cnorm", terminfo.get::<cap::CursorNormal>().is_some()),
+        ("clear", terminfo.get::<cap::ClearScreen>().is_some()),
+        ("sgr0", terminfo.get::<cap::ExitAttributeMode>().is_some()),
+    ];
+
+    for (name, present) in &capabilities {
+        if *present {
+            report.report(Status::Ok, format!("capability {} is supported", name));
+        } else {
+            report.report(Status::Error, format!("capability {} is missing", name));
+        }
+    }
+
+    let capabilities = term.capabilities();
+    if capabilities.truecolor {
+        report.report(Status::Ok, "truecolor is supported");
+    } else {
+        report.report(
+            Status::Warning,
+            "truecolor support not detected, colors are approximated (set COLORTERM=truecolor if your terminal supports it)",
+        );
+    }
+
+    if capabilities.italic {
+        report.report(Status::Ok, "italics are supported");
+    } else {
+        report.report(Status::Warning, "italics support not detected");
+    }
+
+    if capabilities.undercurl {
+        report.report(Status::Ok, "undercurl is supported");
+    } else {
+        report.report(Status::Warning, "undercurl support not detected");
+    }
+}
+
+fn check_grammars(report: &mut Report) {
+    report.section("Grammars");
+
+    for syntax in Syntax::iter() {
+        let name = syntax.into_language_id();
+
+        match check_grammar(syntax) {
+            Ok(captures) => report.report(
+                Status::Ok,
+                format!("{}: grammar loaded ({} highlight captures)", name, captures),
+            ),
+            Err(e) => report.report(Status::Error, format!("{}: {}", name, e)),
+        }
+    }
+}
+
+/// Searches for an executable program, similar to `which`.
+///
+/// Programs containing a path separator are checked directly. Otherwise, each directory in `path`
+/// is searched in order.
+fn find_executable(program: &str, path: Option<impl AsRef<OsStr>>) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    };
+
+    if program.contains('/') {
+        let program = PathBuf::from(program);
+        return if is_executable(&program) {
+            Some(program)
+        } else {
+            None
+        };
+    }
+
+    env::split_paths(&path?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_executable, Report, Status};
+
+    #[test]
+    fn find_executable_on_path() {
+        let path = find_executable("sh", Some("/nonexistent:/bin:/usr/bin")).unwrap();
+        assert!(path.ends_with("sh"));
+
+        assert_eq!(find_executable("sh", Some("/nonexistent")), None);
+        assert_eq!(find_executable("sh", None::<&str>), None);
+    }
+
+    #[test]
+    fn find_executable_with_separator() {
+        assert!(find_executable("/bin/sh", None::<&str>).is_some());
+        assert!(find_executable("/etc/hostname-does-not-exist", None::<&str>).is_none());
+    }
+
+    #[test]
+    fn report_sections() {
+        let mut report = Report::default();
+        report.section("First");
+        report.report(Status::Ok, "good");
+        report.section("Second");
+        report.report(Status::Error, "bad");
+
+        assert_eq!(
+            report.text,
+            "## First\n- OK: good\n\n## Second\n- ERROR: bad\n"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! The help documents that ship with the editor, opened with `:help`.
+//!
+//! Like Vim's help, documents define tags between stars, such as `*:write*`, and refer to tags
+//! between bars, such as `|:write|`. `:help` opens the document that defines the tag for a topic at
+//! the line that defines it, and pressing Enter on a reference in a help buffer follows it.
+
+/// A help document.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Doc {
+    pub name: &'static str,
+    pub text: &'static str,
+}
+
+/// The documents, in the order that their tags are searched.
+const DOCS: &[Doc] = &[
+    Doc {
+        name: "editor.txt",
+        text: include_str!("../doc/editor.txt"),
+    },
+    Doc {
+        name: "keys.txt",
+        text: include_str!("../doc/keys.txt"),
+    },
+    Doc {
+        name: "commands.txt",
+        text: include_str!("../doc/commands.txt"),
+    },
+    Doc {
+        name: "options.txt",
+        text: include_str!("../doc/options.txt"),
+    },
+];
+
+/// The topic of `:help` without an argument.
+pub const DEFAULT_TOPIC: &str = "help";
+
+/// Where a tag is defined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub doc: Doc,
+
+    /// The zero-indexed line that defines the tag.
+    pub line: usize,
+}
+
+/// Finds the tag for a topic.
+///
+/// Like in Vim, the topic may leave out the `:` of a command or the quotes of an option, so that
+/// `:help set` finds `:set`. Tags that match exactly are preferred. Otherwise, the shortest tag that
+/// starts with the topic is used, so that `:help diffs` finds `:diffsplit`.
+pub fn find(topic: &str) -> Option<Location> {
+    let tags = DOCS
+        .iter()
+        .flat_map(|doc| tags(doc.text).map(move |(tag, line)| (tag, Location { doc: *doc, line })))
+        .collect::<Vec<_>>();
+
+    let command = format!(":{}", topic);
+    let option = format!("'{}'", topic);
+    let names = [topic, &command, &option];
+    let prefixes = [topic, &command, &option[..option.len() - 1]];
+
+    names
+        .iter()
+        .find_map(|name| tags.iter().find(|(tag, _)| tag == name))
+        .or_else(|| {
+            prefixes.iter().find_map(|prefix| {
+                tags.iter()
+                    .filter(|(tag, _)| tag.starts_with(prefix))
+                    .min_by_key(|(tag, _)| tag.len())
+            })
+        })
+        .map(|&(_, location)| location)
+}
+
+/// Returns the tags defined in a document, with the lines that define them.
+fn tags(text: &str) -> impl Iterator<Item = (&str, usize)> {
+    text.lines().enumerate().flat_map(|(line, text)| {
+        text.split_whitespace()
+            .filter_map(|word| word.strip_prefix('*')?.strip_suffix('*'))
+            .filter(|tag| !tag.is_empty() && !tag.contains('*'))
+            .map(move |tag| (tag, line))
+    })
+}
+
+/// Returns the tag referred to at a byte column of a line, if the column is between bars.
+pub fn reference_at(line: &str, column: usize) -> Option<&str> {
+    let mut search_start = 0;
+
+    while let Some(index) = line[search_start..].find('|') {
+        let start = search_start + index + 1;
+        let end = start + line[start..].find('|')?;
+        search_start = end + 1;
+
+        let tag = &line[start..end];
+        if tag.is_empty() || tag.contains(char::is_whitespace) {
+            // The closing bar may start the next reference.
+            search_start = end;
+            continue;
+        }
+
+        if (start - 1..=end).contains(&column) {
+            return Some(tag);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, reference_at, tags, DEFAULT_TOPIC, DOCS};
+
+    #[test]
+    fn find_tags() {
+        let location = find(DEFAULT_TOPIC).unwrap();
+        assert_eq!(location.doc.name, "editor.txt");
+
+        let location = find(":w").unwrap();
+        assert_eq!(location.doc.name, "commands.txt");
+        assert!(location
+            .doc
+            .text
+            .lines()
+            .nth(location.line)
+            .unwrap()
+            .contains("*:w*"));
+
+        assert_eq!(find("set"), find(":set"));
+        assert_eq!(find("diffs"), find(":diffsplit"));
+        assert_eq!(find("expandtab"), find("'expandtab'"));
+        assert_eq!(find("tabs"), find("'tabstop'"));
+        assert_eq!(find("frobnicate"), None);
+    }
+
+    #[test]
+    fn references_resolve() {
+        for doc in DOCS {
+            for line in doc.text.lines() {
+                for (column, _) in line.match_indices('|') {
+                    if let Some(tag) = reference_at(line, column) {
+                        assert!(
+                            DOCS.iter()
+                                .flat_map(|doc| tags(doc.text))
+                                .any(|(t, _)| t == tag),
+                            "{} refers to unknown tag {}",
+                            doc.name,
+                            tag
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_tags() {
+        assert_eq!(
+            tags("*a.txt*\tTitle\n\n  *:e* *:edit* 2 * 3 = *6*\n").collect::<Vec<_>>(),
+            vec![("a.txt", 0), (":e", 2), (":edit", 2), ("6", 2)]
+        );
+    }
+
+    #[test]
+    fn find_references() {
+        let line = "See |:set| or |'number'|, a || b.";
+        assert_eq!(reference_at(line, 4), Some(":set"));
+        assert_eq!(reference_at(line, 7), Some(":set"));
+        assert_eq!(reference_at(line, 9), Some(":set"));
+        assert_eq!(reference_at(line, 11), None);
+        assert_eq!(reference_at(line, 16), Some("'number'"));
+        assert_eq!(reference_at(line, 27), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Showing what the language server knows about the symbol under the cursor, with `K`.
+//!
+//! Servers usually describe a symbol in Markdown, with its signature in a code block followed by
+//! its documentation. The popup that shows it can't style text, so the Markdown is shown as it's
+//! written, except that the fences around code blocks and the markers of headings are removed.
+
+use itertools::Itertools;
+use lsp_types::{Hover, HoverContents, MarkedString, MarkupKind};
+
+/// Returns the text to show for a hover, or `None` if it's blank.
+pub fn text(hover: Hover) -> Option<String> {
+    let sections = match hover.contents {
+        HoverContents::Scalar(marked) => vec![marked_text(marked)],
+        HoverContents::Array(marked) => marked.into_iter().map(marked_text).collect(),
+        HoverContents::Markup(markup) => match markup.kind {
+            MarkupKind::Markdown => vec![plain(&markup.value)],
+            MarkupKind::PlainText => vec![markup.value],
+        },
+    };
+
+    let text = sections
+        .iter()
+        .map(|section| section.trim())
+        .filter(|section| !section.is_empty())
+        .join("\n\n");
+
+    Some(text).filter(|text| !text.is_empty())
+}
+
+fn marked_text(marked: MarkedString) -> String {
+    match marked {
+        MarkedString::String(markdown) => plain(&markdown),
+        MarkedString::LanguageString(code) => code.value,
+    }
+}
+
+/// Removes the code fences and heading markers from Markdown. Lines inside code blocks are left
+/// alone, since they may start with `#` too.
+fn plain(markdown: &str) -> String {
+    let mut in_code_block = false;
+
+    markdown
+        .lines()
+        .filter_map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+                return None;
+            }
+
+            let heading = line.trim_start_matches('#');
+            if !in_code_block && heading.len() < line.len() && heading.starts_with(' ') {
+                Some(heading.trim_start())
+            } else {
+                Some(line)
+            }
+        })
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use lsp_types::{
+        Hover, HoverContents, LanguageString, MarkedString, MarkupContent, MarkupKind,
+    };
+
+    use super::{plain, text};
+
+    #[test]
+    fn markdown_to_plain_text() {
+        let markdown = indoc! {"
+            ```rust
+            #[derive(Debug)]
+            pub struct Foo
+            ```
+
+            ## Examples
+
+            A *foo*.
+        "};
+
+        assert_eq!(
+            plain(markdown),
+            "#[derive(Debug)]\npub struct Foo\n\nExamples\n\nA *foo*."
+        );
+    }
+
+    #[test]
+    fn hover_text() {
+        let hover = |contents| Hover {
+            contents,
+            range: None,
+        };
+
+        assert_eq!(
+            text(hover(HoverContents::Array(vec![
+                MarkedString::LanguageString(LanguageString {
+                    language: String::from("rust"),
+                    value: String::from("fn foo()"),
+                }),
+                MarkedString::String(String::from("# Foo\nDoes foo.\n")),
+            ]))),
+            Some(String::from("fn foo()\n\nFoo\nDoes foo."))
+        );
+
+        assert_eq!(
+            text(hover(HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::PlainText,
+                value: String::from("# not a heading"),
+            }))),
+            Some(String::from("# not a heading"))
+        );
+
+        assert_eq!(
+            text(hover(HoverContents::Scalar(MarkedString::String(
+                String::from("  \n")
+            )))),
+            None
+        );
+    }
+}
@@ -0,0 +1,644 @@
+//! Key bindings for normal, insert, and visual mode.
+//!
+//! A binding maps a chord, a sequence of one or more keys, to an action. Chords are written in
+//! Vim's key notation: printable characters stand for themselves, and other keys are written in
+//! angle brackets, such as `<Esc>`, `<CR>`, `<BS>`, `<Tab>`, `<Up>`, or `<C-w>`. The default
+//! bindings can be changed in the `[keys.normal]`, `[keys.insert]`, and `[keys.visual]` sections
+//! of the config, like `"<C-s>" = "insert-mode"`. Binding a chord to `nop` disables it.
+//!
+//! `<leader>` in a chord stands for the leader key, which is `\` unless it is changed with
+//! `leader` in the `[keys]` section. This leaves room for user mappings like `<leader>ff` that
+//! don't shadow the defaults.
+//!
+//! The bindings of a mode are stored in a trie, so that the keys typed so far can be matched as
+//! they arrive. When the keys are both bound and the start of a longer chord, such as `g` when
+//! `gcc` is also bound, the editor waits for the next key until the timeout elapses, and then
+//! performs the shorter binding.
+//!
+//! In normal and visual mode, digits typed before a chord are its count, like the `42` of `42G`.
+//! `0` is only part of a count after another digit, so that it can be bound on its own.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use maplit::hashmap;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::config::KeyConfig;
+use crate::term::Key;
+
+/// Something that a key binding does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Does nothing. Used to remove a default binding.
+    Nop,
+
+    Quit,
+    CommandMode,
+    InsertMode,
+    NormalMode,
+
+    /// Starts selecting text at the cursor.
+    VisualMode,
+
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveRight,
+
+    /// Moves down or up a display line, which is part of a line when lines are wrapped.
+    MoveDisplayDown,
+    MoveDisplayUp,
+
+    NextFold,
+    PreviousFold,
+    NextDefinition,
+    PreviousDefinition,
+    LineStart,
+    LineEnd,
+
+    /// Moves to the first line, or to the line of the count if one was typed.
+    FirstLine,
+
+    /// Moves to the last line, or to the line of the count if one was typed.
+    LastLine,
+
+    /// Moves to the next match of the last search, in the same or the opposite direction.
+    SearchNext,
+    SearchPrevious,
+
+    ScrollLineDown,
+    ScrollLineUp,
+    ScrollPageDown,
+    ScrollPageUp,
+    ScrollHalfPageDown,
+    ScrollHalfPageUp,
+    ScrollCursorToTop,
+    ScrollCursorToCenter,
+    ScrollCursorToBottom,
+
+    InsertNewline,
+
+    /// Moves to the next tab stop of a completed snippet, or inserts a tab, or spaces if
+    /// `expandtab` is set.
+    InsertTab,
+
+    /// Completes the word before the cursor with the next or previous word from the open buffers.
+    CompleteNext,
+    CompletePrevious,
+
+    /// Asks the buffer's language server for completions at the cursor.
+    RequestCompletion,
+
+    /// Shows what the buffer's language server knows about the symbol under the cursor.
+    Hover,
+
+    DeleteCharBackward,
+    DeleteCharForward,
+
+    /// Deletes the word before the cursor.
+    DeleteWordBackward,
+
+    /// Deletes the text between the start of the line and the cursor.
+    DeleteToLineStart,
+
+    DeleteToNextDefinition,
+    DeleteToPreviousDefinition,
+
+    /// Deletes, yanks, or changes the text selected in visual mode.
+    DeleteSelection,
+    YankSelection,
+    ChangeSelection,
+
+    /// Deletes the cursor's line.
+    DeleteLine,
+
+    YankLine,
+    YankToNextDefinition,
+    YankToPreviousDefinition,
+
+    /// Pastes the most recent yank before or after the cursor, or above or below the cursor's line
+    /// if it's made of whole lines.
+    Paste,
+    PasteAfter,
+
+    /// Opens the directory entry, or follows the help tag, under the cursor.
+    OpenEntry,
+
+    /// Opens the URL under the cursor.
+    OpenUrl,
+
+    /// Shows the cursor's column, line, word, and byte, and the totals of each.
+    ShowStatistics,
+
+    /// Jumps to the definition of the identifier under the cursor in the tags file.
+    JumpToTag,
+
+    /// Jumps back to where the last tag jump was made from.
+    PopTag,
+
+    /// Writes the buffer to its file, or asks for a file name if it has none.
+    Write,
+
+    /// Switches to the next or previous buffer, like `:bnext` and `:bprevious`.
+    NextBuffer,
+    PreviousBuffer,
+
+    /// Calls the function or runs the command bound by a script, by the order it was bound in.
+    #[serde(skip)]
+    Script(usize),
+}
+
+impl Action {
+    /// Returns whether the action changes the buffer, or enters a mode that does.
+    pub fn edits(self) -> bool {
+        matches!(
+            self,
+            Action::InsertMode
+                | Action::InsertNewline
+                | Action::InsertTab
+                | Action::CompleteNext
+                | Action::CompletePrevious
+                | Action::DeleteCharBackward
+                | Action::DeleteCharForward
+                | Action::DeleteWordBackward
+                | Action::DeleteToLineStart
+                | Action::DeleteToNextDefinition
+                | Action::DeleteToPreviousDefinition
+                | Action::DeleteLine
+                | Action::DeleteSelection
+                | Action::ChangeSelection
+                | Action::Paste
+                | Action::PasteAfter
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("invalid key chord: {0}")]
+pub struct InvalidChord(String);
+
+/// A key in a chord.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum ChordKey {
+    Key(Key),
+
+    /// The leader key, which is configurable.
+    Leader,
+}
+
+/// A sequence of keys that triggers an action.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Chord(Vec<ChordKey>);
+
+impl Chord {
+    /// Returns the key of a chord made of a single key, other than the leader.
+    pub fn single_key(&self) -> Option<Key> {
+        match self.0.as_slice() {
+            [ChordKey::Key(key)] => Some(*key),
+            _ => None,
+        }
+    }
+
+    /// Returns the keys of the chord, given the leader key.
+    fn keys(&self, leader: Key) -> Vec<Key> {
+        self.0
+            .iter()
+            .map(|key| match key {
+                ChordKey::Key(key) => *key,
+                ChordKey::Leader => leader,
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Chord {
+    type Err = InvalidChord;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidChord(s.to_owned());
+
+        let mut keys = vec![];
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '<' {
+                keys.push(ChordKey::Key(Key::Char(c)));
+                continue;
+            }
+
+            let name = chars.by_ref().take_while(|&c| c != '>').collect::<String>();
+            let key = match name.to_lowercase().as_str() {
+                "leader" => ChordKey::Leader,
+                "esc" => ChordKey::Key(Key::Esc),
+                "cr" | "enter" | "return" => ChordKey::Key(Key::Return),
+                "bs" | "backspace" => ChordKey::Key(Key::Backspace),
+                "tab" => ChordKey::Key(Key::Ctrl('i')),
+                "up" => ChordKey::Key(Key::ArrowUp),
+                "down" => ChordKey::Key(Key::ArrowDown),
+                "left" => ChordKey::Key(Key::ArrowLeft),
+                "right" => ChordKey::Key(Key::ArrowRight),
+                "space" => ChordKey::Key(Key::Char(' ')),
+                "lt" => ChordKey::Key(Key::Char('<')),
+                "c-space" | "c-@" => ChordKey::Key(Key::Ctrl('@')),
+                lower => {
+                    let mut rest = lower.strip_prefix("c-").ok_or_else(invalid)?.chars();
+                    match (rest.next(), rest.next()) {
+                        (Some(c), None) if c.is_ascii_lowercase() || "\\]^_".contains(c) => {
+                            ChordKey::Key(Key::Ctrl(c))
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+            };
+            keys.push(key);
+        }
+
+        if keys.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Chord(keys))
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = InvalidChord;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The result of looking up the keys typed so far.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// The keys are bound to an action, and aren't the start of a longer chord.
+    Action(Action),
+
+    /// The keys are the start of at least one longer chord, so more keys are needed.
+    Pending,
+
+    /// No chord starts with the keys.
+    Unbound,
+}
+
+/// A node of the trie of bindings, reached by typing the keys on the path from the root.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Node {
+    action: Option<Action>,
+    children: HashMap<Key, Node>,
+}
+
+impl Node {
+    fn get(&self, keys: &[Key]) -> Option<&Node> {
+        match keys.split_first() {
+            Some((key, rest)) => self.children.get(key)?.get(rest),
+            None => Some(self),
+        }
+    }
+
+    fn insert(&mut self, keys: &[Key], action: Action) {
+        match keys.split_first() {
+            Some((key, rest)) => self.children.entry(*key).or_default().insert(rest, action),
+            None => self.action = Some(action),
+        }
+    }
+
+    /// Removes the binding of some keys, along with any nodes that no longer lead to a binding.
+    fn remove(&mut self, keys: &[Key]) {
+        match keys.split_first() {
+            Some((key, rest)) => {
+                if let Some(child) = self.children.get_mut(key) {
+                    child.remove(rest);
+                    if child.action.is_none() && child.children.is_empty() {
+                        self.children.remove(key);
+                    }
+                }
+            }
+            None => self.action = None,
+        }
+    }
+}
+
+/// The key bindings of a mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    root: Node,
+
+    /// The key that `<leader>` stands for.
+    leader: Key,
+}
+
+impl Keymap {
+    /// Returns the default normal mode bindings, changed by the bindings from the config.
+    pub fn normal(config: &KeyConfig) -> Self {
+        Keymap::new(
+            hashmap! {
+                "q" => Action::Quit,
+                ":" => Action::CommandMode,
+                "i" => Action::InsertMode,
+                "v" => Action::VisualMode,
+                "h" => Action::MoveLeft,
+                "j" => Action::MoveDown,
+                "k" => Action::MoveUp,
+                "l" => Action::MoveRight,
+                "gj" => Action::MoveDisplayDown,
+                "gk" => Action::MoveDisplayUp,
+                "zj" => Action::NextFold,
+                "zk" => Action::PreviousFold,
+                "]f" => Action::NextDefinition,
+                "[f" => Action::PreviousDefinition,
+                "0" => Action::LineStart,
+                "$" => Action::LineEnd,
+                "gg" => Action::FirstLine,
+                "G" => Action::LastLine,
+                "n" => Action::SearchNext,
+                "N" => Action::SearchPrevious,
+                "<C-e>" => Action::ScrollLineDown,
+                "<C-y>" => Action::ScrollLineUp,
+                "<C-f>" => Action::ScrollPageDown,
+                "<C-b>" => Action::ScrollPageUp,
+                "<C-d>" => Action::ScrollHalfPageDown,
+                "<C-u>" => Action::ScrollHalfPageUp,
+                "zt" => Action::ScrollCursorToTop,
+                "zz" => Action::ScrollCursorToCenter,
+                "zb" => Action::ScrollCursorToBottom,
+                "x" => Action::DeleteCharForward,
+                "d]f" => Action::DeleteToNextDefinition,
+                "d[f" => Action::DeleteToPreviousDefinition,
+                "dd" => Action::DeleteLine,
+                "yy" => Action::YankLine,
+                "y]f" => Action::YankToNextDefinition,
+                "y[f" => Action::YankToPreviousDefinition,
+                "p" => Action::PasteAfter,
+                "P" => Action::Paste,
+                "<CR>" => Action::OpenEntry,
+                "gx" => Action::OpenUrl,
+                "g<C-g>" => Action::ShowStatistics,
+                "<C-]>" => Action::JumpToTag,
+                "<C-t>" => Action::PopTag,
+                "K" => Action::Hover,
+                "<C-s>" => Action::Write,
+                "gt" => Action::NextBuffer,
+                "gT" => Action::PreviousBuffer,
+            },
+            &config.normal,
+            config.leader,
+        )
+    }
+
+    /// Returns the default insert mode bindings, changed by the bindings from the config.
+    ///
+    /// Printable characters that aren't bound are inserted.
+    pub fn insert(config: &KeyConfig) -> Self {
+        Keymap::new(
+            hashmap! {
+                "<Esc>" => Action::NormalMode,
+                "<BS>" => Action::DeleteCharBackward,
+                "<CR>" => Action::InsertNewline,
+                "<Tab>" => Action::InsertTab,
+                "<C-w>" => Action::DeleteWordBackward,
+                "<C-u>" => Action::DeleteToLineStart,
+                "<Left>" => Action::MoveLeft,
+                "<Down>" => Action::MoveDown,
+                "<Up>" => Action::MoveUp,
+                "<Right>" => Action::MoveRight,
+                "<C-n>" => Action::CompleteNext,
+                "<C-p>" => Action::CompletePrevious,
+                "<C-Space>" => Action::RequestCompletion,
+            },
+            &config.insert,
+            config.leader,
+        )
+    }
+
+    /// Returns the default visual mode bindings, changed by the bindings from the config.
+    ///
+    /// Motions extend the selection from where visual mode was entered to the cursor.
+    pub fn visual(config: &KeyConfig) -> Self {
+        Keymap::new(
+            hashmap! {
+                "<Esc>" => Action::NormalMode,
+                "v" => Action::NormalMode,
+                "h" => Action::MoveLeft,
+                "j" => Action::MoveDown,
+                "k" => Action::MoveUp,
+                "l" => Action::MoveRight,
+                "gj" => Action::MoveDisplayDown,
+                "gk" => Action::MoveDisplayUp,
+                "zj" => Action::NextFold,
+                "zk" => Action::PreviousFold,
+                "]f" => Action::NextDefinition,
+                "[f" => Action::PreviousDefinition,
+                "0" => Action::LineStart,
+                "$" => Action::LineEnd,
+                "gg" => Action::FirstLine,
+                "G" => Action::LastLine,
+                "n" => Action::SearchNext,
+                "N" => Action::SearchPrevious,
+                "<C-e>" => Action::ScrollLineDown,
+                "<C-y>" => Action::ScrollLineUp,
+                "<C-f>" => Action::ScrollPageDown,
+                "<C-b>" => Action::ScrollPageUp,
+                "<C-d>" => Action::ScrollHalfPageDown,
+                "<C-u>" => Action::ScrollHalfPageUp,
+                "zt" => Action::ScrollCursorToTop,
+                "zz" => Action::ScrollCursorToCenter,
+                "zb" => Action::ScrollCursorToBottom,
+                "d" => Action::DeleteSelection,
+                "x" => Action::DeleteSelection,
+                "y" => Action::YankSelection,
+                "c" => Action::ChangeSelection,
+                "g<C-g>" => Action::ShowStatistics,
+            },
+            &config.visual,
+            config.leader,
+        )
+    }
+
+    fn new(defaults: HashMap<&str, Action>, config: &HashMap<Chord, Action>, leader: Key) -> Self {
+        let mut root = Node::default();
+
+        for (chord, action) in defaults {
+            let chord = chord
+                .parse::<Chord>()
+                .expect("default chord should be valid");
+            root.insert(&chord.keys(leader), action);
+        }
+
+        for (chord, &action) in config {
+            let keys = chord.keys(leader);
+            if action == Action::Nop {
+                root.remove(&keys);
+            } else {
+                root.insert(&keys, action);
+            }
+        }
+
+        Keymap { root, leader }
+    }
+
+    /// Binds a chord to an action, replacing any existing binding.
+    pub fn bind(&mut self, chord: &Chord, action: Action) {
+        self.root.insert(&chord.keys(self.leader), action);
+    }
+
+    /// Looks up the keys typed so far.
+    pub fn resolve(&self, keys: &[Key]) -> Resolution {
+        match self.root.get(keys) {
+            Some(node) if !node.children.is_empty() => Resolution::Pending,
+            Some(Node {
+                action: Some(action),
+                ..
+            }) => Resolution::Action(*action),
+            _ => Resolution::Unbound,
+        }
+    }
+
+    /// Returns the longest bound chord that the keys start with, as its length and action.
+    pub fn longest_prefix(&self, keys: &[Key]) -> Option<(usize, Action)> {
+        let mut node = &self.root;
+        let mut longest = None;
+
+        for (i, key) in keys.iter().enumerate() {
+            node = match node.children.get(key) {
+                Some(node) => node,
+                None => break,
+            };
+
+            if let Some(action) = node.action {
+                longest = Some((i + 1, action));
+            }
+        }
+
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use crate::config::KeyConfig;
+    use crate::term::Key;
+
+    use super::{Action, Chord, ChordKey, Keymap, Resolution};
+
+    #[test]
+    fn parse_chord() {
+        assert_eq!(
+            "d]f".parse(),
+            Ok(Chord(vec![
+                ChordKey::Key(Key::Char('d')),
+                ChordKey::Key(Key::Char(']')),
+                ChordKey::Key(Key::Char('f'))
+            ]))
+        );
+        assert_eq!(
+            "<C-w><lt><Leader>x<cr>".parse(),
+            Ok(Chord(vec![
+                ChordKey::Key(Key::Ctrl('w')),
+                ChordKey::Key(Key::Char('<')),
+                ChordKey::Leader,
+                ChordKey::Key(Key::Char('x')),
+                ChordKey::Key(Key::Return)
+            ]))
+        );
+        assert!("".parse::<Chord>().is_err());
+        assert!("<F1>".parse::<Chord>().is_err());
+        assert!("<C-ab>".parse::<Chord>().is_err());
+        assert_eq!(
+            "<C-]>".parse(),
+            Ok(Chord(vec![ChordKey::Key(Key::Ctrl(']'))]))
+        );
+        assert_eq!(
+            "<C-Space>".parse(),
+            Ok(Chord(vec![ChordKey::Key(Key::Ctrl('@'))]))
+        );
+    }
+
+    #[test]
+    fn resolve() {
+        let keymap = Keymap::normal(&KeyConfig::default());
+        assert_eq!(keymap.resolve(&[Key::Char('d')]), Resolution::Pending);
+        assert_eq!(
+            keymap.resolve(&[Key::Char('d'), Key::Char(']'), Key::Char('f')]),
+            Resolution::Action(Action::DeleteToNextDefinition)
+        );
+        assert_eq!(
+            keymap.resolve(&[Key::Char('d'), Key::Char('x')]),
+            Resolution::Unbound
+        );
+        assert_eq!(
+            keymap.resolve(&[Key::Return]),
+            Resolution::Action(Action::OpenEntry)
+        );
+    }
+
+    #[test]
+    fn override_defaults() {
+        let config = KeyConfig {
+            insert: hashmap! {
+                "jk".parse().unwrap() => Action::NormalMode,
+                "<Esc>".parse().unwrap() => Action::Nop,
+            },
+            ..KeyConfig::default()
+        };
+
+        let keymap = Keymap::insert(&config);
+        assert_eq!(keymap.resolve(&[Key::Char('j')]), Resolution::Pending);
+        assert_eq!(
+            keymap.resolve(&[Key::Char('j'), Key::Char('k')]),
+            Resolution::Action(Action::NormalMode)
+        );
+        assert_eq!(keymap.resolve(&[Key::Esc]), Resolution::Unbound);
+        assert_eq!(
+            keymap.resolve(&[Key::Backspace]),
+            Resolution::Action(Action::DeleteCharBackward)
+        );
+    }
+
+    #[test]
+    fn leader_and_ambiguous_chords() {
+        let config = KeyConfig {
+            normal: hashmap! {
+                "<leader>ff".parse().unwrap() => Action::Quit,
+                "g".parse().unwrap() => Action::MoveDown,
+                "zj".parse().unwrap() => Action::Nop,
+            },
+            leader: Key::Char(','),
+            ..KeyConfig::default()
+        };
+
+        let keymap = Keymap::normal(&config);
+        let keys = [Key::Char(','), Key::Char('f'), Key::Char('f')];
+        assert_eq!(keymap.resolve(&keys[..2]), Resolution::Pending);
+        assert_eq!(keymap.resolve(&keys), Resolution::Action(Action::Quit));
+
+        // `g` waits for the `x` of `gx`, and is performed on its own otherwise.
+        assert_eq!(keymap.resolve(&[Key::Char('g')]), Resolution::Pending);
+        assert_eq!(
+            keymap.longest_prefix(&[Key::Char('g'), Key::Char('q')]),
+            Some((1, Action::MoveDown))
+        );
+        assert_eq!(
+            keymap.longest_prefix(&[Key::Char('y'), Key::Char('y'), Key::Char('j')]),
+            Some((2, Action::YankLine))
+        );
+        assert_eq!(
+            keymap.longest_prefix(&[Key::Char('d'), Key::Char(']')]),
+            None
+        );
+
+        assert_eq!(keymap.resolve(&[Key::Char('z')]), Resolution::Pending);
+        assert_eq!(
+            keymap.resolve(&[Key::Char('z'), Key::Char('j')]),
+            Resolution::Unbound
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! The most recently yanked and deleted text.
+//!
+//! Unlike Vim's single unnamed register, every yank and deletion is kept in a ring of recent
+//! entries. Pasting uses the most recent entry, like the unnamed register, and older entries can be
+//! chosen from a picker, which moves them back to the front of the ring. Entries remember whether
+//! they are whole lines, so that `p` and `P` paste them below or above the cursor's line.
+
+use std::collections::VecDeque;
+
+/// The maximum number of entries in the kill ring.
+const CAPACITY: usize = 32;
+
+/// Text that was yanked or deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Yank {
+    pub text: String,
+
+    /// Whether the text is made of whole lines, which are pasted above the cursor's line rather
+    /// than at the cursor.
+    pub linewise: bool,
+}
+
+impl Yank {
+    pub fn new(text: impl Into<String>) -> Self {
+        Yank {
+            text: text.into(),
+            linewise: false,
+        }
+    }
+
+    pub fn lines(text: impl Into<String>) -> Self {
+        Yank {
+            text: text.into(),
+            linewise: true,
+        }
+    }
+}
+
+/// A ring of recent yanks and deletions, from most to least recent.
+#[derive(Debug, Default)]
+pub struct KillRing {
+    entries: VecDeque<Yank>,
+}
+
+impl KillRing {
+    /// Adds an entry to the front of the ring, forgetting the oldest entry if the ring is full.
+    /// Empty text is ignored.
+    pub fn push(&mut self, yank: Yank) {
+        if yank.text.is_empty() {
+            return;
+        }
+
+        self.entries.push_front(yank);
+        self.entries.truncate(CAPACITY);
+    }
+
+    /// Returns the most recent entry.
+    pub fn front(&self) -> Option<&Yank> {
+        self.entries.front()
+    }
+
+    /// Moves an entry to the front of the ring, returning it.
+    pub fn promote(&mut self, index: usize) -> Option<&Yank> {
+        let yank = self.entries.remove(index)?;
+        self.entries.push_front(yank);
+        self.entries.front()
+    }
+
+    /// Returns an iterator over the entries, from most to least recent.
+    pub fn iter(&self) -> impl Iterator<Item = &Yank> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KillRing, Yank, CAPACITY};
+
+    #[test]
+    fn push_and_promote() {
+        let mut ring = KillRing::default();
+        assert_eq!(ring.front(), None);
+
+        ring.push(Yank::new("a"));
+        ring.push(Yank::new(""));
+        ring.push(Yank::lines("b\n"));
+        assert_eq!(ring.front(), Some(&Yank::lines("b\n")));
+
+        assert_eq!(ring.promote(1), Some(&Yank::new("a")));
+        assert_eq!(
+            ring.iter().collect::<Vec<_>>(),
+            vec![&Yank::new("a"), &Yank::lines("b\n")]
+        );
+        assert_eq!(ring.promote(2), None);
+
+        for i in 0..CAPACITY {
+            ring.push(Yank::new(i.to_string()));
+        }
+        assert_eq!(ring.iter().count(), CAPACITY);
+        assert_eq!(ring.iter().last(), Some(&Yank::new("0")));
+    }
+}
@@ -7,55 +7,213 @@
 // workaround for rust-lang/rust#55779
 extern crate serde;
 
+use std::cmp;
 use std::convert::TryFrom;
 use std::env;
+use std::io::{self, ErrorKind};
+use std::iter;
+use std::ops::Range;
 use std::os::unix::io::AsRawFd;
 use std::panic;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::Error;
 use futures::channel::mpsc;
-use futures::{select, StreamExt};
+use futures::{future, select, FutureExt, Stream, StreamExt};
 use if_chain::if_chain;
 use log::*;
+use lsp_types::notification::{LogMessage, Notification as _, PublishDiagnostics, TelemetryEvent};
+use lsp_types::{
+    CompletionResponse, DocumentChangeOperation, DocumentChanges, MessageType,
+    PublishDiagnosticsParams, TextEdit, WorkspaceEdit,
+};
+use maplit::hashmap;
 use nix::sys::termios::{self, SetArg};
 use structopt::StructOpt;
+use tokio::fs;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
 use tokio_stream::wrappers::SignalStream;
+use unicode_width::UnicodeWidthStr;
 
+mod blame;
 mod buffer;
+mod command;
+mod completion;
 mod config;
+mod diff;
+mod errorformat;
+mod event;
+mod format;
+mod grammar;
+mod grep;
+mod health;
+mod help;
+mod hover;
+mod keymap;
+mod kill_ring;
+mod lint;
+mod load;
 mod logger;
 mod lsp;
+mod make;
+mod opener;
+mod options;
+mod pty;
+mod quickfix;
+mod recording;
+mod recovery;
+mod replace;
+mod script;
+mod state;
+mod status_line;
 mod syntax;
+mod tags;
 mod term;
+mod theme;
 mod ui;
 
-use buffer::Buffers;
-use config::Config;
-use lsp::{LanguageServerBridge, Message, Response};
+use buffer::{
+    Buffer, Buffers, Direction, Edit, Motion, Position, Scroll, SearchOptions, SearchResult,
+    Snippet,
+};
+use command::{Command, CommandLine};
+use completion::Completion;
+use config::{Config, GrepConfig, LogConfig, MakeConfig, ProjectConfig, SaveConfig, YankConfig};
+use diff::{Base, DiffView};
+use event::{Event, Hooks, Request};
+use keymap::{Action, Keymap, Resolution};
+use kill_ring::{KillRing, Yank};
+use lint::Linters;
+use lsp::{FileRename, LanguageServerBridge, Message, Notification, Response, ToUri, Uri};
+use options::{GlobalOptions, OptionName, VirtualEdit};
+use pty::Pty;
+use quickfix::{Entry, ListKind, ListView, QuickfixList, LIST_HEIGHT};
+use replace::{Hit, Replacement};
+use script::{Scripts, Snapshot};
+use state::State;
+use status_line::StatusLine;
 use term::{Key, Stdin, Terminal};
 use tokio::signal::unix::{signal, SignalKind};
-use ui::{Bounds, Coordinates, Drawable};
+use ui::menu::Menu;
+use ui::picker::{self, Picker};
+use ui::popup::Popup;
+use ui::{Bounds, Coordinates, Drawable, Screen, Size, Style};
 
-pub use logger::Logger;
+use logger::Logger;
+
+/// The most keys of a pending chord that are shown in the command-line area.
+const SHOWCMD_WIDTH: usize = 10;
+
+/// How often the log buffer reads the lines that have been logged since.
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The source that diagnostics published by a buffer's language server are stored under.
+const LANGUAGE_SERVER_SOURCE: &str = "language server";
 
 /// Command-line options.
 #[derive(Debug, StructOpt)]
 pub struct Options {
     /// A list of filenames to edit.
+    ///
+    /// Arguments that start with `+` are commands to run once the first file is loaded, like
+    /// Vim's: `+N` jumps to line N, `+` jumps to the last line, and `+cmd` runs `cmd`.
     pub files: Vec<PathBuf>,
+
+    /// Show two files side by side, with the lines that differ highlighted: the second file is
+    /// opened, and compared with the first. Use `]c` and `[c` to move between the hunks.
+    #[structopt(short, long)]
+    pub diff: bool,
+
+    /// Open the files in horizontal splits. The editor only has one window, so the files are
+    /// opened as buffers instead.
+    #[structopt(short = "o", conflicts_with = "vsplit")]
+    pub split: bool,
+
+    /// Open the files in vertical splits. Like `-o`, the files are opened as buffers instead.
+    #[structopt(short = "O", conflicts_with = "split")]
+    pub vsplit: bool,
+
+    /// A command to run once the first file is loaded, after the `+` commands. May be given more
+    /// than once.
+    #[structopt(short = "c", number_of_values = 1)]
+    pub commands: Vec<String>,
+
+    /// Edit the files as hex dumps, even if they look like text.
+    #[structopt(short, long)]
+    pub binary: bool,
+
+    /// Read the config from this file instead of the system-wide and user config files.
+    #[structopt(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Write the log to this file instead of `editor.log` in the state directory.
+    #[structopt(long, parse(from_os_str))]
+    pub log_file: Option<PathBuf>,
+
+    /// The records to log, such as `info` or `editor::lsp=debug`. Overrides `RUST_LOG`.
+    #[structopt(long)]
+    pub log_level: Option<String>,
+
+    /// Record the keys that are typed, and when, to this file.
+    #[structopt(long, parse(from_os_str))]
+    pub record: Option<PathBuf>,
+
+    /// Type the keys recorded in this file with `--record`, before reading the keyboard.
+    #[structopt(long, parse(from_os_str))]
+    pub replay: Option<PathBuf>,
 }
 
 pub async fn run(options: Options) -> Result<(), Error> {
+    let current_dir = env::current_dir()?;
+
+    let config_paths = match &options.config {
+        Some(path) => vec![current_dir.join(path)],
+        None => Config::config_paths(),
+    };
+
+    // The config is read before the log is set up, since it may say where to log.
+    let config = Config::read(&config_paths).await;
+    let log_path = init_logger(
+        &options,
+        config
+            .as_ref()
+            .map(|config| config.log_config.clone())
+            .unwrap_or_default(),
+    );
+
+    // The recording is read before the terminal is set up, so that an error can be printed.
+    let replayed = match &options.replay {
+        Some(path) => recording::read(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("unable to read recording {}: {}", path.display(), e))?,
+        None => vec![],
+    };
+    let mut recorder = options
+        .record
+        .as_ref()
+        .map(|path| recording::Recorder::create(path))
+        .transpose()?;
+
     let stdin = Stdin::new()?;
     let term = Terminal::new().await?;
 
-    set_panic_hook(&stdin, &term);
+    let backups = recovery::Backups::default();
+    set_panic_hook(&stdin, &term, backups.clone());
 
-    let Config {
-        language_server_config,
-    } = match Config::read(Config::config_path()).await {
+    let input = recording::replay(replayed)
+        .chain(stdin)
+        .inspect(move |key| {
+            if let (Some(recorder), Ok(key)) = (&mut recorder, key) {
+                if let Err(e) = recorder.record(*key) {
+                    error!("unable to record key: {}", e);
+                }
+            }
+        });
+
+    let mut config = match config {
         Ok(config) => config,
         Err(e) => {
             // TODO: Report error to user
@@ -64,71 +222,479 @@ pub async fn run(options: Options) -> Result<(), Error> {
         }
     };
 
+    match ProjectConfig::read(&current_dir).await {
+        Ok(project_config) => config.merge_project(project_config),
+        Err(e) => info!("unable to read project config file: {}", e),
+    }
+
+    let Config {
+        language_server_config,
+        filetypes,
+        linter_config,
+        make_config,
+        grep_config,
+        save_config,
+        yank_config,
+        key_config,
+        log_config: _,
+        colorscheme,
+        options: config_options,
+        language_options,
+        hooks,
+    } = config;
+
+    syntax::set_filetypes(&filetypes);
+    options::set_buffer_defaults(config_options.buffer, language_options);
+
+    if let Some(name) = colorscheme {
+        if let Err(e) = theme::set_current(&name) {
+            info!("unable to set color scheme: {}", e);
+        }
+    }
+
+    let state = match State::path() {
+        Ok(path) => State::read(&path).await,
+        Err(e) => Err(e),
+    };
+    let state = state.unwrap_or_else(|e| {
+        info!("unable to read state file: {}", e);
+        State::default()
+    });
+
     let (ls_tx, ls_rx) = mpsc::channel(10);
+    let (lint_tx, lint_rx) = mpsc::channel(10);
+    let (make_tx, make_rx) = mpsc::channel(1);
+    let (grep_tx, grep_rx) = mpsc::channel(10);
+    let (blame_tx, blame_rx) = mpsc::channel(1);
+    let (load_tx, load_rx) = mpsc::channel(1);
+    let (diff_tx, diff_rx) = mpsc::channel(1);
+    let (terminal_tx, terminal_rx) = mpsc::channel(10);
+
+    let mut commands = vec![];
+    let mut paths = vec![];
+    for file in &options.files {
+        match file.to_str().and_then(command::startup_command) {
+            Some(command) => commands.push(command),
+            None => paths.push(current_dir.join(file)),
+        }
+    }
+    commands.extend(options.commands.iter().cloned());
+
+    // In diff mode, the first file is the base that the second is compared with.
+    let diff_base = match (options.diff, paths.len()) {
+        (true, 2) => Some(paths.remove(0)),
+        (true, _) => return Err(anyhow::anyhow!("--diff requires exactly two files")),
+        (false, _) => None,
+    };
 
-    let screen_size = term.size();
-    let buffers =
-        Buffers::from_paths(options.files.clone(), Bounds::from_size(screen_size)).await?;
+    // There's only one window, so files that should be opened in splits are opened as buffers,
+    // with a message saying so.
+    let split = match (options.split, options.vsplit) {
+        (true, _) => Some("horizontal"),
+        (_, true) => Some("vertical"),
+        _ => None,
+    };
+    let split_message = split.filter(|_| paths.len() > 1).map(|split| {
+        format!(
+            "{} splits aren't supported yet, so the {} files were opened as buffers",
+            split,
+            paths.len()
+        )
+    });
+
+    // Only the first file is opened before the editor starts. The rest are opened in the
+    // background.
+    let mut paths = paths.into_iter();
+    let layout = Layout::new(Bounds::from_size(term.size()), 0, 0);
+    let mut buffers = Buffers::from_paths(
+        paths.next().into_iter().collect(),
+        layout.text,
+        options.binary,
+    )
+    .await?;
+    buffers
+        .current_mut()
+        .set_window_options(config_options.window);
+    let paths = paths.collect::<Vec<_>>();
+    let loading = paths.len();
+    load::spawn(paths, options.binary, load_tx.clone());
 
     let mut editor = Editor {
-        current_dir: env::current_dir()?,
+        current_dir,
+        config_paths,
         buffers,
         ls_bridge: LanguageServerBridge::new(language_server_config, ls_tx),
         language_server_messages: ls_rx,
+        linters: Linters::new(linter_config, lint_tx),
+        lint_reports: lint_rx,
+        make_config,
+        make_sender: make_tx,
+        make_reports: make_rx,
+        grep_config,
+        grep_sender: grep_tx,
+        grep_updates: grep_rx,
+        grep_id: 0,
+        grep_task: None,
+        replacement: None,
+        blame_sender: blame_tx,
+        blame_reports: blame_rx,
+        blame: None,
+        save_config,
+        load_sender: load_tx,
+        load_reports: load_rx,
+        loading,
+        opening: None,
+        terminal_sender: terminal_tx,
+        terminal_output: terminal_rx,
+        terminal_id: 0,
+        tag_stack: vec![],
+        completion: None,
+        hover: None,
+        quickfix: QuickfixList::default(),
+        location_list: QuickfixList::default(),
+        open_list: None,
+        picker: None,
+        diff: None,
+        diff_sender: diff_tx,
+        diff_reports: diff_rx,
+        diff_this: None,
         mode: Mode::Normal,
+        command_line: CommandLine::with_history(state.command_history().to_vec()),
+        message: None,
+        normal_keymap: Keymap::normal(&key_config),
+        insert_keymap: Keymap::insert(&key_config),
+        visual_keymap: Keymap::visual(&key_config),
+        options: config_options.global,
+        pending_keys: vec![],
+        count: None,
+        pending_timeout: None,
+        kill_ring: KillRing::default(),
+        yank_config,
+        flash: None,
+        last_search: None,
+        hooks: Hooks::new(hooks),
+        scripts: Scripts::new()?,
+        cursor_hold: None,
+        log_path,
+        log_poll: None,
+        state,
+        backups,
     };
 
-    for buffer in &editor.buffers {
-        if_chain! {
-            if let Some(syntax) = buffer.syntax;
-            if let Some(server) = editor.ls_bridge.get_or_init(editor.current_dir.clone(), lsp::Context { syntax }).await;
-            if let Some(text_document_item) = buffer.to_text_document_item();
-            then {
-                server.did_open_text_document(text_document_item).await?;
-            }
+    editor.state.restore_cursor(editor.buffers.current_mut());
+    editor.run_init_script().await;
+    editor.offer_recovery().await;
+
+    let opened = (&editor.buffers)
+        .into_iter()
+        .filter_map(Buffer::path)
+        .map(Path::to_owned)
+        .collect::<Vec<_>>();
+    for path in opened {
+        editor.emit(Event::BufOpen(path)).await?;
+    }
+
+    if let Some(base) = diff_base {
+        editor.open_diff(Base::File(base));
+    }
+
+    if split_message.is_some() {
+        editor.message = split_message;
+    }
+
+    for command in commands {
+        if let ControlFlow::Break = editor.execute_command(&command, &term).await? {
+            editor.save_state().await;
+            return Ok(());
         }
     }
 
-    editor.run(stdin, term).await
+    editor.run(input, term).await
 }
 
 /// Core editor state.
 pub struct Editor {
+    /// The working directory of the editor, changed with `:cd`.
+    ///
+    /// Buffers may have their own working directory, which takes precedence.
     current_dir: PathBuf,
+
+    /// The config files that were read, from lowest to highest precedence.
+    config_paths: Vec<PathBuf>,
     buffers: Buffers,
     ls_bridge: LanguageServerBridge,
 
     /// Receiver for requests and notifications from language servers.
     language_server_messages: mpsc::Receiver<(lsp::Context, lsp::Message)>,
 
+    linters: Linters,
+
+    /// Receiver for the problems found by linters running in the background.
+    lint_reports: mpsc::Receiver<lint::Report>,
+
+    make_config: MakeConfig,
+
+    /// Cloneable sender for the results of builds running in the background.
+    make_sender: mpsc::Sender<make::Report>,
+
+    /// Receiver for the results of builds running in the background.
+    make_reports: mpsc::Receiver<make::Report>,
+
+    grep_config: GrepConfig,
+
+    /// Cloneable sender for the progress of searches running in the background.
+    grep_sender: mpsc::Sender<grep::Update>,
+
+    /// Receiver for the progress of searches running in the background.
+    grep_updates: mpsc::Receiver<grep::Update>,
+
+    /// Identifies the most recent search. Updates from earlier searches are ignored.
+    grep_id: u64,
+
+    /// The most recent search, which is stopped when a new search starts.
+    grep_task: Option<JoinHandle<()>>,
+
+    /// The replacement that the most recent search was started for by `:replace`, which is
+    /// previewed once the search finishes.
+    replacement: Option<Replacement>,
+
+    /// Cloneable sender for the results of `git blame` running in the background.
+    blame_sender: mpsc::Sender<blame::Report>,
+
+    /// Receiver for the results of `git blame` running in the background.
+    blame_reports: mpsc::Receiver<blame::Report>,
+
+    /// The most recent blame, shown in the status line while the cursor is on the blamed line.
+    blame: Option<blame::Report>,
+
+    /// Changes made to buffers when they are written.
+    save_config: SaveConfig,
+
+    /// Cloneable sender for the files that are opened in the background.
+    load_sender: mpsc::Sender<load::Report>,
+
+    /// Receiver for the files that are opened in the background, including the files given on the
+    /// command line.
+    load_reports: mpsc::Receiver<load::Report>,
+
+    /// The number of files given on the command line that are still being opened.
+    loading: usize,
+
+    /// The file that the user opened most recently, if it's still being opened, and what to do
+    /// once it's shown.
+    opening: Option<(PathBuf, AfterOpen)>,
+
+    /// Cloneable sender for the output of programs running in terminal buffers.
+    terminal_sender: mpsc::Sender<pty::Output>,
+
+    /// Receiver for the output of programs running in terminal buffers.
+    terminal_output: mpsc::Receiver<pty::Output>,
+
+    /// The ID of the most recently opened terminal buffer.
+    terminal_id: usize,
+
+    /// Where each tag jump was made from, from oldest to newest.
+    tag_stack: Vec<(PathBuf, Position)>,
+
+    /// The completion of the word before the cursor in insert mode, if one is in progress.
+    completion: Option<Completion>,
+
+    /// What the language server said about the symbol under the cursor, shown in a popup until
+    /// the next key.
+    hover: Option<String>,
+
+    /// Locations collected from the output of commands such as `:make`.
+    quickfix: QuickfixList,
+
+    /// The location list of the window.
+    ///
+    /// There is only one window, so the location list follows it between buffers.
+    location_list: QuickfixList,
+
+    /// The list shown in a split at the bottom of the screen, if any.
+    open_list: Option<ListKind>,
+
+    /// The picker shown over the buffer in picker mode, and what choosing an item does.
+    picker: Option<(Picker, Pick)>,
+
+    /// The diff shown over the buffer in diff mode.
+    diff: Option<DiffView>,
+
+    /// Cloneable sender for diffs computed in the background.
+    diff_sender: mpsc::Sender<diff::Report>,
+
+    /// Receiver for diffs computed in the background.
+    diff_reports: mpsc::Receiver<diff::Report>,
+
+    /// The buffer marked by `:diffthis`, which the next buffer marked is compared with.
+    diff_this: Option<PathBuf>,
+
     mode: Mode,
+
+    /// The command being entered in command-line mode.
+    command_line: CommandLine,
+
+    /// A message to display in the command-line area, such as an error from the last command.
+    message: Option<String>,
+
+    normal_keymap: Keymap,
+    insert_keymap: Keymap,
+    visual_keymap: Keymap,
+
+    /// The values of global options.
+    options: GlobalOptions,
+
+    /// The keys typed so far of a chord that isn't complete, such as the `d]` of `d]f`.
+    pending_keys: Vec<Key>,
+
+    /// The count typed before a chord in normal or visual mode, like the `42` of `42G`.
+    count: Option<usize>,
+
+    /// When to stop waiting for the rest of the pending chord.
+    pending_timeout: Option<Instant>,
+
+    /// Recently yanked and deleted text.
+    kill_ring: KillRing,
+
+    yank_config: YankConfig,
+
+    /// Text that was just yanked, which is highlighted until the flash ends.
+    flash: Option<Flash>,
+
+    /// The pattern and direction of the last search in a buffer, which `n` and `N` repeat.
+    last_search: Option<(String, Direction)>,
+
+    /// Commands that run when events happen.
+    hooks: Hooks,
+
+    /// The Lua state of `init.lua`.
+    scripts: Scripts,
+
+    /// When the `cursor-hold` event happens, unless a key is pressed first.
+    cursor_hold: Option<Instant>,
+
+    /// The file that the editor logs to, if logging was set up.
+    log_path: Option<PathBuf>,
+
+    /// When to read the lines logged since the log buffer was last updated, if one is open.
+    log_poll: Option<Instant>,
+
+    /// State that persists between sessions, such as the cursor position in each file.
+    state: State,
+
+    /// Copies of the modified buffers, which are saved if the editor panics.
+    backups: recovery::Backups,
 }
 
 impl Editor {
-    async fn run(mut self, stdin: Stdin, mut term: Terminal) -> Result<(), Error> {
-        let mut stdin = stdin.fuse();
+    async fn run(
+        &mut self,
+        input: impl Stream<Item = io::Result<Key>>,
+        mut term: Terminal,
+    ) -> Result<(), Error> {
+        let mut stdin = Box::pin(input).fuse();
         let mut sigwinch = SignalStream::new(signal(SignalKind::window_change())?).fuse();
+        let mut sigusr1 = SignalStream::new(signal(SignalKind::user_defined1())?).fuse();
 
         loop {
+            self.run_hooks(&term).await?;
+            self.update_cursor_past_end();
+
             // TODO: Move to default?
+            // The width of the line numbers may have changed since the last redraw.
+            self.buffers.set_bounds(self.layout(term.size()).text);
+            if let Err(e) = self.buffers.current_mut().fit_terminal() {
+                info!("unable to resize terminal: {}", e);
+            }
             self.redraw(&mut term).await?;
+            self.backups.update(&self.buffers);
+
+            let mode = self.mode;
+            let flash_end = self.flash.as_ref().map(|flash| flash.end);
+            let pending_timeout = self.pending_timeout;
+            let cursor_hold = self.cursor_hold;
+            let log_poll = self.log_poll;
 
             select! {
                 _ = sigwinch.next() => {
+                    // The layout and the viewport are fit to the new size at the start of the next
+                    // iteration, before the redraw.
                     let size = term.refresh_size()?;
                     info!("received SIGWINCH, new size: {}", size);
-                    self.redraw(&mut term).await?;
+                }
+
+                _ = sigusr1.next() => {
+                    info!("received SIGUSR1, reloading config");
+                    self.reload_config().await;
                 }
 
                 input = stdin.next() => {
                     let key = match input {
                         Some(key) => key.unwrap(),
-                        None => return Ok(()),
+                        None => break,
                     };
 
                     info!("read key: {:?}", key);
 
-                    if let ControlFlow::Break = self.handle_key(key).await? {
+                    match key {
+                        Key::FocusLost => self.emit(Event::FocusLost).await?,
+                        Key::FocusGained | Key::PasteEnd => (),
+                        Key::PasteStart => {
+                            // The whole paste is read before the next redraw, so that it's applied
+                            // and highlighted once, instead of a key at a time.
+                            let mut keys = vec![];
+                            while let Some(key) = stdin.next().await {
+                                match key? {
+                                    Key::PasteEnd => break,
+                                    key => keys.push(key),
+                                }
+                            }
+
+                            info!("read paste of {} keys", keys.len());
+                            self.cursor_hold = Some(Instant::now() + self.options.update_time());
+
+                            if let ControlFlow::Break = self.handle_paste(keys, &term).await? {
+                                break;
+                            }
+                        }
+                        key => {
+                            self.cursor_hold = Some(Instant::now() + self.options.update_time());
+
+                            if let ControlFlow::Break = self.handle_key(key, &term).await? {
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                // Redraw with the new highlights once the buffer has been parsed, or as more of a
+                // large file is read.
+                _ = self.buffers.background_work().fuse() => {}
+
+                _ = sleep_until(flash_end).fuse() => self.flash = None,
+
+                _ = sleep_until(cursor_hold).fuse() => {
+                    self.cursor_hold = None;
+
+                    if let Mode::Normal = self.mode {
+                        self.emit(Event::CursorHold).await?;
+                    }
+                }
+
+                _ = sleep_until(log_poll).fuse() => {
+                    if let Err(e) = self.buffers.follow_logs().await {
+                        info!("unable to read log: {}", e);
+                    }
+
+                    self.log_poll = if self.buffers.has_log() {
+                        Some(Instant::now() + LOG_POLL_INTERVAL)
+                    } else {
+                        None
+                    };
+                }
+
+                _ = sleep_until(pending_timeout).fuse() => {
+                    if let ControlFlow::Break = self.resolve_pending_keys(true).await? {
                         break;
                     }
                 }
@@ -146,124 +712,3139 @@ impl Editor {
                                 server.respond(Response::method_not_found(req.id)).await?;
                             }
                         }
-                        Message::Notification(not) => {
-                            info!("unhandled notification: {:?}", not);
+                        Message::Notification(notification) => {
+                            self.handle_notification(&ctx, notification);
                         }
                         Message::Response(_) => panic!("responses should be handled in the lsp module"),
                     }
                 }
+
+                lint_report = self.lint_reports.next() => {
+                    if let Some(report) = lint_report {
+                        self.handle_lint_report(report);
+                    }
+                }
+
+                make_report = self.make_reports.next() => {
+                    if let Some(report) = make_report {
+                        self.handle_make_report(report).await?;
+                    }
+                }
+
+                grep_update = self.grep_updates.next() => {
+                    if let Some(update) = grep_update {
+                        self.handle_grep_update(update).await?;
+                    }
+                }
+
+                blame_report = self.blame_reports.next() => {
+                    if let Some(report) = blame_report {
+                        self.handle_blame_report(report);
+                    }
+                }
+
+                load_report = self.load_reports.next() => {
+                    if let Some(report) = load_report {
+                        self.handle_load_report(report).await?;
+                    }
+                }
+
+                diff_report = self.diff_reports.next() => {
+                    if let Some(report) = diff_report {
+                        self.handle_diff_report(report);
+                    }
+                }
+
+                terminal_output = self.terminal_output.next() => {
+                    if let Some(output) = terminal_output {
+                        self.handle_terminal_output(output);
+                    }
+                }
+            }
+
+            if self.mode != mode {
+                self.emit(Event::ModeChanged(mode, self.mode)).await?;
             }
         }
 
         info!("terminating");
 
+        self.save_state().await;
+
+        Ok(())
+    }
+
+    /// Writes the cursor positions of the open buffers and the command-line history to the state
+    /// file.
+    ///
+    /// Failing to save the state doesn't prevent the editor from exiting, so errors are only logged.
+    async fn save_state(&mut self) {
+        // The current buffer is remembered last, so that it becomes the most recent file.
+        for buffer in &self.buffers {
+            if buffer.path() != self.buffers.current().path() {
+                self.state.remember_cursor(buffer);
+            }
+        }
+        self.state.remember_cursor(self.buffers.current());
+
+        self.state
+            .set_command_history(self.command_line.history().to_vec());
+
+        let state = std::mem::take(&mut self.state);
+        let result = match State::path() {
+            Ok(path) => state.write(&path).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            info!("unable to write state file: {}", e);
+        }
+    }
+
+    /// Tells the user if there are buffers to recover from a crash.
+    async fn offer_recovery(&mut self) {
+        let files = match recovery::dir() {
+            Ok(dir) => recovery::files(&dir).await.unwrap_or_default(),
+            Err(_) => return,
+        };
+
+        if !files.is_empty() {
+            self.message = Some(format!(
+                "{} modified buffers were saved when the editor crashed, restore them with :recover",
+                files.len()
+            ));
+        }
+    }
+
+    /// Opens the buffers that were saved when the editor crashed with their recovered text, and
+    /// removes the recovery files.
+    async fn recover(&mut self) -> Result<(), Error> {
+        let files = recovery::files(&recovery::dir()?).await?;
+        if files.is_empty() {
+            self.message = Some(String::from("no buffers to recover"));
+            return Ok(());
+        }
+
+        for file in files {
+            let recovered = match recovery::read(&file).await {
+                Ok(recovered) => recovered,
+                Err(e) => {
+                    self.message = Some(format!("unable to read {}: {}", file.display(), e));
+                    continue;
+                }
+            };
+
+            match recovered.path {
+                Some(path) => {
+                    // The file is opened right away, rather than in the background, since its text
+                    // is replaced.
+                    match self.buffers.focus(&path).await {
+                        Ok((_, true)) => self.emit(Event::BufOpen(path.clone())).await?,
+                        Ok((_, false)) => (),
+                        Err(e) => {
+                            self.message =
+                                Some(format!("unable to open {}: {}", path.display(), e));
+                            continue;
+                        }
+                    }
+
+                    let buffer = self.buffers.current_mut();
+                    let edit = buffer.replace_text(&recovered.text);
+
+                    if_chain! {
+                        if let Some(edit) = edit;
+                        if let Some(syntax) = buffer.syntax;
+                        if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+                        if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+                        then {
+                            server.did_change_text_document(
+                                versioned_identifier,
+                                vec![edit.to_text_document_content_change_event()],
+                            ).await?;
+                        }
+                    }
+                }
+                None => {
+                    let mut buffer = Buffer::new();
+                    buffer.replace_text(&recovered.text);
+                    self.buffers.push(buffer);
+                }
+            }
+
+            fs::remove_file(&file).await?;
+        }
+
         Ok(())
     }
 
     /// Handles user-supplied key input.
-    async fn handle_key(&mut self, key: Key) -> Result<ControlFlow, Error> {
+    async fn handle_key(&mut self, key: Key, term: &Terminal) -> Result<ControlFlow, Error> {
         use Mode::*;
 
+        // Any key dismisses the hover popup, without doing anything else.
+        if self.hover.take().is_some() {
+            return Ok(ControlFlow::Continue);
+        }
+
         match (self.mode, key) {
-            (Normal, Key::Char('q')) => return Ok(ControlFlow::Break),
-            (Normal, Key::Char('h')) => self.buffers.current_mut().move_left(),
-            (Normal, Key::Char('i')) => self.mode = Insert,
-            (Normal, Key::Char('j')) => self.buffers.current_mut().move_down(),
-            (Normal, Key::Char('k')) => self.buffers.current_mut().move_up(),
-            (Normal, Key::Char('l')) => self.buffers.current_mut().move_right(),
-            (Insert, Key::Esc) => self.mode = Normal,
-            (Insert, Key::Backspace) => self.delete_char().await?,
-            (Insert, Key::Char(c)) => self.insert_char(c).await?,
-            (Insert, Key::Return) => self.insert_char('\n').await?,
+            (Normal, key) | (Insert, key) | (Visual, key) => {
+                return self.handle_bound_key(key).await
+            }
+            (Command, Key::Esc) => {
+                self.command_line.take();
+                self.mode = Normal;
+            }
+            (Command, Key::Backspace) if self.command_line.is_empty() => self.mode = Normal,
+            (Command, Key::Backspace) => {
+                self.command_line.pop();
+            }
+            (Command, Key::Char(c)) => self.command_line.push(c),
+            (Command, Key::ArrowUp) => self.command_line.previous(),
+            (Command, Key::ArrowDown) => self.command_line.next(),
+            (Command, Key::Return) => {
+                self.mode = Normal;
+                let command_line = self.command_line.submit();
+                return self.execute_command(&command_line, term).await;
+            }
+            (Picker, key) => self.handle_picker_key(key, term).await?,
+            (Diff, key) => self.handle_diff_key(key).await?,
+            (Terminal, key) => self.handle_terminal_key(key),
             _ => (),
         }
 
         Ok(ControlFlow::Continue)
     }
 
-    async fn delete_char(&mut self) -> Result<(), Error> {
-        let buffer = self.buffers.current_mut();
-        let edit = buffer.delete();
+    /// Handles the keys of text that was pasted into the terminal.
+    ///
+    /// In normal and insert mode, the text is inserted at the cursor as a single edit, without
+    /// running the bindings of its keys, and is sent to the program in terminal mode. Other modes
+    /// handle the keys as if they were typed.
+    async fn handle_paste(
+        &mut self,
+        keys: Vec<Key>,
+        term: &Terminal,
+    ) -> Result<ControlFlow, Error> {
+        self.hover = None;
 
-        if_chain! {
-            if let Some(edit) = edit;
-            if let Some(syntax) = buffer.syntax;
-            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
-            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
-            then {
-                server.did_change_text_document(
-                    versioned_identifier,
-                    vec![edit.to_text_document_content_change_event()],
-                ).await?;
+        match self.mode {
+            Mode::Normal | Mode::Insert | Mode::Terminal => {
+                let text = pasted_text(&keys);
+                self.pending_keys.clear();
+                self.count = None;
+                self.insert_text(&text, Buffer::insert_str).await?;
+            }
+            _ => {
+                for key in keys {
+                    if let ControlFlow::Break = self.handle_key(key, term).await? {
+                        return Ok(ControlFlow::Break);
+                    }
+                }
             }
         }
 
-        Ok(())
+        Ok(ControlFlow::Continue)
     }
 
-    /// Insert a character into the active buffer.
-    async fn insert_char(&mut self, c: char) -> Result<(), Error> {
-        let buffer = self.buffers.current_mut();
-        let edit = buffer.insert(c);
-
+    /// Handles a key in a mode with key bindings.
+    async fn handle_bound_key(&mut self, key: Key) -> Result<ControlFlow, Error> {
         if_chain! {
-            if let Some(syntax) = buffer.syntax;
-            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
-            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            if matches!(self.mode, Mode::Normal | Mode::Visual);
+            if self.pending_keys.is_empty();
+            if let Key::Char(c) = key;
+            if let Some(digit) = c.to_digit(10);
+            if digit != 0 || self.count.is_some();
             then {
-                server.did_change_text_document(
-                    versioned_identifier,
-                    vec![edit.to_text_document_content_change_event()],
-                ).await?;
+                let count = self.count.unwrap_or(0);
+                self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+                return Ok(ControlFlow::Continue);
             }
         }
 
-        Ok(())
+        self.pending_keys.push(key);
+        self.resolve_pending_keys(false).await
     }
 
-    async fn redraw(&self, term: &mut Terminal) -> Result<(), Error> {
-        let bounds = Bounds::from_size(term.size());
-
-        let mut ctx = ui::Context {
-            bounds,
-            screen: term.screen(),
-        };
+    /// Performs the actions of the chords that have been typed so far, waiting for more keys if
+    /// the last chord may not be complete. If the wait has timed out, the longest bound chord is
+    /// performed instead.
+    ///
+    /// Keys that aren't part of a chord are discarded in normal mode and typed in insert mode.
+    async fn resolve_pending_keys(&mut self, mut timed_out: bool) -> Result<ControlFlow, Error> {
+        let mut keys = std::mem::take(&mut self.pending_keys);
+        self.pending_timeout = None;
 
-        ctx.screen.clear();
+        while !keys.is_empty() {
+            let keymap = match self.mode {
+                Mode::Insert => &self.insert_keymap,
+                Mode::Visual => &self.visual_keymap,
+                _ => &self.normal_keymap,
+            };
 
-        let current_buffer = self.buffers.current();
-        current_buffer.draw(&mut ctx);
+            let (len, action) = match keymap.resolve(&keys) {
+                Resolution::Pending if !timed_out => {
+                    self.pending_keys = keys;
+                    self.pending_timeout = Some(Instant::now() + self.options.timeout());
+                    break;
+                }
+                Resolution::Action(action) => (keys.len(), Some(action)),
+                _ => match keymap.longest_prefix(&keys) {
+                    Some((len, action)) => (len, Some(action)),
+                    None => (1, None),
+                },
+            };
 
-        let cursor_position = current_buffer.cursor_position();
-        term.cursor = Coordinates::new(
-            u16::try_from(cursor_position.x).expect("cursor outside screen bounds"),
-            u16::try_from(cursor_position.y).expect("cursor outside screen bounds"),
-        );
+            // Any remaining keys were typed after the timeout started, so they wait again.
+            timed_out = false;
 
-        term.refresh().await?;
+            let chord = keys.drain(..len).collect::<Vec<_>>();
+            let count = self.count.take();
+            match (action, self.mode, chord.as_slice()) {
+                (Some(action), _, _) => {
+                    if let ControlFlow::Break = self.perform(action, count).await? {
+                        return Ok(ControlFlow::Break);
+                    }
+                }
+                (None, Mode::Insert, &[Key::Char(c)]) => self.insert_char(c).await?,
+                (None, _, _) => (),
+            }
+        }
 
-        Ok(())
+        Ok(ControlFlow::Continue)
     }
-}
 
-/// Editing mode.
-#[derive(Debug, Copy, Clone)]
-enum Mode {
-    Normal,
-    Insert,
-}
+    /// Performs the action of a key binding, with the count typed before it.
+    async fn perform(
+        &mut self,
+        action: Action,
+        count: Option<usize>,
+    ) -> Result<ControlFlow, Error> {
+        if action.edits() && self.buffers.current().is_directory() {
+            self.message = Some(String::from("directory listings cannot be edited"));
+            return Ok(ControlFlow::Continue);
+        }
 
-impl Default for Mode {
-    fn default() -> Self {
-        Mode::Normal
-    }
-}
+        if action.edits() && self.buffers.current().help_doc().is_some() {
+            self.message = Some(String::from("help documents cannot be edited"));
+            return Ok(ControlFlow::Continue);
+        }
+
+        if action.edits() && self.buffers.current().log_path().is_some() {
+            self.message = Some(String::from("the log cannot be edited"));
+            return Ok(ControlFlow::Continue);
+        }
+
+        if action.edits() && self.buffers.current().terminal_id().is_some() {
+            // Insert mode sends keys to the terminal's program instead, and pasting sends the
+            // text.
+            match action {
+                Action::InsertMode if self.buffers.current().is_terminal_running() => {
+                    self.mode = Mode::Terminal;
+                    return Ok(ControlFlow::Continue);
+                }
+                Action::Paste | Action::PasteAfter => (),
+                _ => {
+                    self.message = Some(String::from("terminal buffers cannot be edited"));
+                    return Ok(ControlFlow::Continue);
+                }
+            }
+        }
+
+        let buffer = self.buffers.current_mut();
+
+        match action {
+            Action::Nop => (),
+            Action::Quit => return Ok(ControlFlow::Break),
+            Action::CommandMode => {
+                self.message = None;
+                self.mode = Mode::Command;
+            }
+            Action::InsertMode => self.mode = Mode::Insert,
+            Action::NormalMode => {
+                self.completion = None;
+                buffer.clear_tab_stops();
+                self.mode = Mode::Normal;
+            }
+            Action::VisualMode => {
+                buffer.start_selection();
+                self.mode = Mode::Visual;
+            }
+            Action::MoveLeft => buffer.move_left(),
+            Action::MoveDown if self.options.wrapmotion => buffer.move_display_down(),
+            Action::MoveUp if self.options.wrapmotion => buffer.move_display_up(),
+            Action::MoveDown => buffer.move_down(),
+            Action::MoveUp => buffer.move_up(),
+            Action::MoveDisplayDown => buffer.move_display_down(),
+            Action::MoveDisplayUp => buffer.move_display_up(),
+            Action::MoveRight => buffer.move_right(),
+            Action::NextFold => buffer.move_to_next_fold(),
+            Action::PreviousFold => buffer.move_to_previous_fold(),
+            Action::NextDefinition => buffer.apply_motion(Motion::NextDefinition),
+            Action::PreviousDefinition => buffer.apply_motion(Motion::PreviousDefinition),
+            Action::LineStart => buffer.apply_motion(Motion::LineStart),
+            Action::LineEnd => buffer.apply_motion(Motion::LineEnd),
+            Action::FirstLine => buffer.apply_motion(Motion::Line(
+                count.map_or(0, |count| count.saturating_sub(1)),
+            )),
+            Action::LastLine => match count {
+                Some(count) => buffer.apply_motion(Motion::Line(count.saturating_sub(1))),
+                None => buffer.apply_motion(Motion::LastLine),
+            },
+            Action::SearchNext => self.repeat_search(false),
+            Action::SearchPrevious => self.repeat_search(true),
+            Action::ScrollLineDown => buffer.scroll(Scroll::LineDown),
+            Action::ScrollLineUp => buffer.scroll(Scroll::LineUp),
+            Action::ScrollPageDown => buffer.scroll(Scroll::PageDown),
+            Action::ScrollPageUp => buffer.scroll(Scroll::PageUp),
+            Action::ScrollHalfPageDown => buffer.scroll(Scroll::HalfPageDown),
+            Action::ScrollHalfPageUp => buffer.scroll(Scroll::HalfPageUp),
+            Action::ScrollCursorToTop => buffer.scroll(Scroll::CursorToTop),
+            Action::ScrollCursorToCenter => buffer.scroll(Scroll::CursorToCenter),
+            Action::ScrollCursorToBottom => buffer.scroll(Scroll::CursorToBottom),
+            Action::InsertNewline => self.insert_char('\n').await?,
+            Action::InsertTab => self.insert_tab().await?,
+            Action::CompleteNext => self.complete(true).await?,
+            Action::CompletePrevious => self.complete(false).await?,
+            Action::RequestCompletion => self.request_completion(None).await?,
+            Action::Hover => self.show_hover().await?,
+            Action::DeleteCharBackward => self.delete_char().await?,
+            Action::DeleteCharForward => self.delete_char_forward().await?,
+            Action::DeleteWordBackward => self.delete_word_backward().await?,
+            Action::DeleteToLineStart => self.delete_to_line_start().await?,
+            Action::DeleteToNextDefinition => self.delete_motion(Motion::NextDefinition).await?,
+            Action::DeleteToPreviousDefinition => {
+                self.delete_motion(Motion::PreviousDefinition).await?
+            }
+            Action::DeleteLine => self.delete_line().await?,
+            Action::DeleteSelection => {
+                self.delete_selection().await?;
+                self.mode = Mode::Normal;
+            }
+            Action::YankSelection => {
+                if let Some((text, range)) = buffer.yank_selection() {
+                    self.kill_ring.push(Yank::new(text));
+                    self.emit(Event::TextYanked(range)).await?;
+                }
+                self.mode = Mode::Normal;
+            }
+            Action::ChangeSelection => {
+                self.delete_selection().await?;
+                self.mode = Mode::Insert;
+            }
+            Action::YankLine => {
+                let (text, range) = buffer.yank_line();
+                self.kill_ring.push(Yank::lines(text));
+                self.emit(Event::TextYanked(range)).await?;
+            }
+            Action::YankToNextDefinition => self.yank_motion(Motion::NextDefinition).await?,
+            Action::YankToPreviousDefinition => {
+                self.yank_motion(Motion::PreviousDefinition).await?
+            }
+            Action::Paste | Action::PasteAfter => match self.kill_ring.front().cloned() {
+                Some(yank) => self.paste(yank, action == Action::PasteAfter).await?,
+                None => self.message = Some(String::from("nothing to paste")),
+            },
+            Action::OpenEntry => {
+                if let Some(path) = buffer.entry_at_cursor() {
+                    self.open_path(&path, AfterOpen::Show);
+                } else if let Some(tag) = buffer.tag_at_cursor() {
+                    let tag = tag.to_owned();
+                    self.open_help(&tag);
+                }
+            }
+            Action::OpenUrl => self.open_url_at_cursor(),
+            Action::ShowStatistics => self.message = Some(buffer.statistics().to_string()),
+            Action::JumpToTag => match buffer.identifier_at_cursor() {
+                Some(name) => {
+                    let name = name.to_owned();
+                    self.jump_to_tag(&name).await?;
+                }
+                None => self.message = Some(String::from("no identifier under cursor")),
+            },
+            Action::PopTag => self.pop_tag(),
+            Action::Write => self.write().await?,
+            Action::NextBuffer => self.buffers.focus_next(),
+            Action::PreviousBuffer => self.buffers.focus_previous(),
+            Action::Script(i) => {
+                let snapshot = self.snapshot();
+                if let Err(e) = self.scripts.call_mapping(i, snapshot) {
+                    self.message = Some(format!("script error: {}", e));
+                }
+                self.hooks.extend(self.scripts.take_requests());
+            }
+        }
+
+        Ok(ControlFlow::Continue)
+    }
+
+    /// Searches the current buffer for a pattern and moves the cursor to the match, remembering
+    /// the search for `n` and `N`. Without a pattern, the last pattern is searched for again.
+    fn search(&mut self, pattern: Option<String>, direction: Direction) {
+        let last_pattern = self.last_search.take().map(|(pattern, _)| pattern);
+        let pattern = match pattern.or(last_pattern) {
+            Some(pattern) => pattern,
+            None => {
+                self.message = Some(String::from("no previous search pattern"));
+                return;
+            }
+        };
+
+        self.find(&pattern, direction);
+        self.last_search = Some((pattern, direction));
+    }
+
+    /// Repeats the last search, in the opposite direction if `reverse` is set.
+    fn repeat_search(&mut self, reverse: bool) {
+        let (pattern, direction) = match &self.last_search {
+            Some((pattern, direction)) => (pattern.clone(), *direction),
+            None => {
+                self.message = Some(String::from("no previous search pattern"));
+                return;
+            }
+        };
+
+        let direction = if reverse {
+            direction.reverse()
+        } else {
+            direction
+        };
+        self.find(&pattern, direction);
+    }
+
+    /// Moves the cursor to the next match of a pattern, following the search options.
+    fn find(&mut self, pattern: &str, direction: Direction) {
+        let options = SearchOptions {
+            ignore_case: self.options.ignores_case(pattern),
+            wrap: self.options.wrapscan,
+        };
+
+        let buffer = self.buffers.current_mut();
+        self.message = match buffer.search(pattern, direction, options) {
+            SearchResult::Found(position) => {
+                buffer.jump_to(position);
+                None
+            }
+            SearchResult::Wrapped(position) => {
+                buffer.jump_to(position);
+                Some(String::from(match direction {
+                    Direction::Forward => "search hit BOTTOM, continuing at TOP",
+                    Direction::Backward => "search hit TOP, continuing at BOTTOM",
+                }))
+            }
+            SearchResult::NotFound if options.wrap => {
+                Some(format!("pattern not found: {}", pattern))
+            }
+            SearchResult::NotFound => Some(format!(
+                "search hit {} without match for: {}",
+                match direction {
+                    Direction::Forward => "BOTTOM",
+                    Direction::Backward => "TOP",
+                },
+                pattern
+            )),
+        };
+    }
+
+    /// Opens the URL under the cursor with the platform's default application.
+    fn open_url_at_cursor(&mut self) {
+        let url = match self.buffers.current().url_at_cursor() {
+            Some(url) => url.to_owned(),
+            None => {
+                self.message = Some(String::from("no URL under cursor"));
+                return;
+            }
+        };
+
+        self.message = Some(match opener::open(&url) {
+            Ok(()) => format!("opening {}", url),
+            Err(e) => format!("unable to open {}: {}", url, e),
+        });
+    }
+
+    /// Parses and executes a command entered at the command-line prompt.
+    ///
+    /// Errors in the command itself are reported to the user rather than returned.
+    async fn execute_command(
+        &mut self,
+        command_line: &str,
+        term: &Terminal,
+    ) -> Result<ControlFlow, Error> {
+        if command_line.trim().is_empty() {
+            return Ok(ControlFlow::Continue);
+        }
+
+        let command = match command_line.parse::<Command>() {
+            Ok(command) => command,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return Ok(ControlFlow::Continue);
+            }
+        };
+
+        info!("executing command: {:?}", command);
+
+        match command {
+            Command::CheckHealth => {
+                let report = health::check(term, &self.config_paths).await;
+                self.buffers.push(Buffer::from(report.as_str()));
+            }
+            Command::Edit(path) => {
+                let path = self.working_dir().join(path);
+                self.open_path(&path, AfterOpen::Show);
+            }
+            Command::ChangeDirectory(path) => {
+                if let Some(dir) = self.resolve_dir(path).await {
+                    self.current_dir = dir;
+                    self.message = Some(self.current_dir.display().to_string());
+                }
+            }
+            Command::ChangeLocalDirectory(path) => {
+                if let Some(dir) = self.resolve_dir(path).await {
+                    self.message = Some(dir.display().to_string());
+                    self.buffers.current_mut().set_local_dir(dir);
+                }
+            }
+            Command::PrintDirectory => {
+                self.message = Some(self.working_dir().display().to_string());
+            }
+            Command::Write => self.write().await?,
+            Command::Quit => return Ok(ControlFlow::Break),
+            Command::NextBuffer => {
+                self.buffers.focus_next();
+                self.buffers.set_bounds(self.layout(term.size()).text);
+            }
+            Command::PreviousBuffer => {
+                self.buffers.focus_previous();
+                self.buffers.set_bounds(self.layout(term.size()).text);
+            }
+            Command::Rename(path) => self.move_file(path, FileMove::Rename).await?,
+            Command::SaveAs(path) => self.move_file(path, FileMove::SaveAs).await?,
+            Command::ColorScheme(Some(name)) => {
+                if let Err(e) = theme::set_current(&name) {
+                    self.message = Some(e.to_string());
+                }
+            }
+            Command::ColorScheme(None) => {
+                self.message = Some(String::from(theme::current().name));
+            }
+            Command::ReloadConfig => self.reload_config().await,
+            Command::LogLevel(Some(filter)) => {
+                if let Err(e) = logger::set_filter(&filter) {
+                    self.message = Some(e.to_string());
+                }
+            }
+            Command::LogLevel(None) => self.message = Some(logger::filter()),
+            Command::Lint => {
+                if !self.linters.lint(self.buffers.current()) {
+                    self.message = Some(String::from("no linter configured for this buffer"));
+                }
+            }
+            Command::Make(args) => self.make(args),
+            Command::Terminal(command) => self.open_terminal(command, term),
+            Command::Tag(name) => self.jump_to_tag(&name).await?,
+            Command::Pop => self.pop_tag(),
+            Command::Grep(pattern) => {
+                self.replacement = None;
+                self.grep(pattern, false);
+            }
+            Command::Replace {
+                pattern,
+                replacement,
+            } => {
+                self.replacement = Some(Replacement {
+                    pattern: pattern.clone(),
+                    replacement,
+                });
+                self.grep(pattern, true);
+            }
+            Command::Search(pattern, direction) => self.search(pattern, direction),
+            Command::OpenList(kind) => {
+                self.open_list = Some(kind);
+                self.buffers.set_bounds(self.layout(term.size()).text);
+            }
+            Command::CloseList(kind) => {
+                if self.open_list == Some(kind) {
+                    self.open_list = None;
+                    self.buffers.set_bounds(self.layout(term.size()).text);
+                }
+            }
+            Command::NextEntry(kind) => self.navigate_list(kind, QuickfixList::next).await?,
+            Command::PreviousEntry(kind) => {
+                self.navigate_list(kind, QuickfixList::previous).await?
+            }
+            Command::Symbols => {
+                let buffer = self.buffers.current();
+                let symbols = buffer.symbols();
+
+                if symbols.is_empty() {
+                    self.message = Some(String::from("no symbols in this buffer"));
+                    return Ok(ControlFlow::Continue);
+                }
+
+                let preview_height = usize::from(self.layout(term.size()).buffer.height());
+                let items = symbols
+                    .iter()
+                    .map(|symbol| {
+                        picker::Item::new(format!("{}{}", "  ".repeat(symbol.depth), symbol.name))
+                            .with_preview(
+                                buffer
+                                    .lines_from(symbol.position.y)
+                                    .take(preview_height)
+                                    .map(String::from)
+                                    .collect(),
+                            )
+                    })
+                    .collect();
+
+                self.open_picker(
+                    Picker::new("symbol: ", items),
+                    Pick::Symbol(symbols.into_iter().map(|symbol| symbol.position).collect()),
+                );
+            }
+            Command::Blame => {
+                let buffer = self.buffers.current();
+
+                match buffer.path() {
+                    Some(path) => {
+                        self.message = Some(String::from("running git blame..."));
+                        blame::spawn(
+                            path.to_owned(),
+                            buffer.version(),
+                            buffer.cursor_line(),
+                            buffer.text(),
+                            self.blame_sender.clone(),
+                        );
+                    }
+                    None => self.message = Some(String::from("buffer has no file name")),
+                }
+            }
+            Command::ToggleBom => {
+                let buffer = self.buffers.current_mut();
+                let bom = !buffer.has_bom();
+                buffer.set_bom(bom);
+
+                self.message = Some(String::from(if bom {
+                    "the byte order mark will be written"
+                } else {
+                    "the byte order mark will be removed"
+                }));
+            }
+            Command::Set(settings) => {
+                let shown = settings
+                    .into_iter()
+                    .filter_map(|setting| {
+                        setting.apply(&mut self.options, self.buffers.current_mut())
+                    })
+                    .map(|shown| shown.to_string())
+                    .collect::<Vec<_>>();
+
+                if !shown.is_empty() {
+                    self.message = Some(shown.join(" "));
+                }
+            }
+            Command::GoToLine(line) => self
+                .buffers
+                .current_mut()
+                .jump_to(Position::new(0, line.saturating_sub(1))),
+            // The line is clamped to the last line of the buffer.
+            Command::GoToLastLine => self
+                .buffers
+                .current_mut()
+                .jump_to(Position::new(0, usize::MAX)),
+            Command::Diff(revision) => {
+                let base = match revision {
+                    Some(revision) => Base::Revision(revision),
+                    None => Base::Index,
+                };
+
+                self.open_diff(base);
+            }
+            Command::DiffSplit(path) => {
+                let path = self.working_dir().join(path);
+                self.open_diff(Base::File(path));
+            }
+            Command::DiffThis => {
+                let path = match self.buffers.current().path() {
+                    Some(path) => path.to_owned(),
+                    None => {
+                        self.message = Some(String::from("buffer has no file name"));
+                        return Ok(ControlFlow::Continue);
+                    }
+                };
+
+                match self.diff_this.take() {
+                    Some(other) if other != path => self.open_diff(Base::Buffer(other)),
+                    _ => {
+                        self.diff_this = Some(path);
+                        self.message = Some(String::from(
+                            "run :diffthis in another buffer to compare it with this one",
+                        ));
+                    }
+                }
+            }
+            Command::DiffOff => {
+                self.diff_this = None;
+                self.close_diff();
+            }
+            Command::Recover => self.recover().await?,
+            Command::Log => match self.log_path.clone() {
+                Some(path) => match self.buffers.focus_log(path).await {
+                    Ok(()) => self.log_poll = Some(Instant::now() + LOG_POLL_INTERVAL),
+                    Err(e) => self.message = Some(format!("unable to open log: {}", e)),
+                },
+                None => self.message = Some(String::from("not logging")),
+            },
+            Command::Help(topic) => {
+                self.open_help(topic.as_deref().unwrap_or(help::DEFAULT_TOPIC));
+            }
+            Command::Buffers => {
+                let items = self.buffer_items(term);
+                self.open_picker(Picker::new("buffer: ", items), Pick::Buffer);
+            }
+            Command::Yanks => {
+                if self.kill_ring.is_empty() {
+                    self.message = Some(String::from("nothing has been yanked or deleted"));
+                    return Ok(ControlFlow::Continue);
+                }
+
+                let preview_height = usize::from(self.layout(term.size()).buffer.height());
+                let items = self
+                    .kill_ring
+                    .iter()
+                    .map(|yank| {
+                        let lines = yank.text.lines().collect::<Vec<_>>();
+                        let detail = match lines.len() {
+                            0 | 1 => String::new(),
+                            n => format!("{} lines", n),
+                        };
+
+                        picker::Item::new(lines.first().copied().unwrap_or_default())
+                            .with_detail(detail)
+                            .with_preview(
+                                lines
+                                    .into_iter()
+                                    .take(preview_height)
+                                    .map(String::from)
+                                    .collect(),
+                            )
+                    })
+                    .collect();
+
+                self.open_picker(Picker::new("paste: ", items), Pick::Yank);
+            }
+            Command::RecentFiles => {
+                let paths = self.state.recent_files().to_vec();
+                let items = paths
+                    .iter()
+                    .map(|path| {
+                        picker::Item::new(
+                            path.strip_prefix(self.working_dir())
+                                .unwrap_or(path)
+                                .display()
+                                .to_string(),
+                        )
+                    })
+                    .collect();
+
+                self.open_picker(Picker::new("recent file: ", items), Pick::RecentFile(paths));
+            }
+            Command::Diagnostics => {
+                let buffer = self.buffers.current();
+
+                let path = match buffer.path() {
+                    Some(path) => path,
+                    None => {
+                        self.message = Some(String::from("buffer has no file name"));
+                        return Ok(ControlFlow::Continue);
+                    }
+                };
+
+                let mut entries = buffer
+                    .diagnostics()
+                    .map(|diagnostic| Entry::from_diagnostic(path, diagnostic))
+                    .collect::<Vec<_>>();
+                entries.sort_by_key(|entry| (entry.position.y, entry.position.x));
+
+                self.location_list = QuickfixList::new(entries);
+                self.open_list = Some(ListKind::Location);
+                self.buffers.set_bounds(self.layout(term.size()).text);
+            }
+        }
+
+        Ok(ControlFlow::Continue)
+    }
+
+    fn open_picker(&mut self, picker: Picker, pick: Pick) {
+        self.picker = Some((picker, pick));
+        self.mode = Mode::Picker;
+    }
+
+    /// Returns picker items for the open buffers, in order.
+    ///
+    /// Each item shows whether the buffer has unsaved changes and how many diagnostics it has.
+    fn buffer_items(&self, term: &Terminal) -> Vec<picker::Item> {
+        let preview_height = usize::from(self.layout(term.size()).buffer.height());
+
+        self.buffers
+            .into_iter()
+            .map(|buffer| {
+                let label = match buffer.path() {
+                    Some(path) => path
+                        .strip_prefix(self.working_dir())
+                        .unwrap_or(path)
+                        .display()
+                        .to_string(),
+                    None => String::from("[No Name]"),
+                };
+
+                let mut detail = vec![];
+                if buffer.is_modified() {
+                    detail.push(String::from("[+]"));
+                }
+                match buffer.diagnostics().count() {
+                    0 => (),
+                    1 => detail.push(String::from("1 diagnostic")),
+                    count => detail.push(format!("{} diagnostics", count)),
+                }
+
+                picker::Item::new(label)
+                    .with_detail(detail.join(" "))
+                    .with_preview(
+                        buffer
+                            .lines_from(0)
+                            .take(preview_height)
+                            .map(String::from)
+                            .collect(),
+                    )
+            })
+            .collect()
+    }
+
+    /// Passes a key to the open picker, acting on the chosen item once the picker is closed.
+    async fn handle_picker_key(&mut self, key: Key, term: &Terminal) -> Result<(), Error> {
+        let (picker, pick) = self.picker.as_mut().expect("picker mode without a picker");
+
+        let index = match (picker.handle_key(key), pick) {
+            (picker::Action::Continue, _) => return Ok(()),
+            (picker::Action::Delete(index), Pick::Buffer) => {
+                self.close_buffer(index).await?;
+
+                let items = self.buffer_items(term);
+                let (picker, _) = self.picker.as_mut().expect("picker mode without a picker");
+                picker.set_items(items);
+                return Ok(());
+            }
+            (picker::Action::Delete(index), Pick::Replace(replacement, hits)) => {
+                hits.remove(index);
+
+                let (replacement, hits) = (replacement.clone(), hits.clone());
+                let items = self.replacement_items(&replacement, &hits);
+                let (picker, _) = self.picker.as_mut().expect("picker mode without a picker");
+                picker.set_items(items);
+                return Ok(());
+            }
+            (picker::Action::Delete(_), _) => return Ok(()),
+            (picker::Action::Cancel, _) => None,
+            (picker::Action::Accept(index), _) => Some(index),
+        };
+
+        self.mode = Mode::Normal;
+        let (_, pick) = self.picker.take().expect("picker mode without a picker");
+
+        if let Some(index) = index {
+            match pick {
+                Pick::Symbol(positions) => {
+                    self.buffers.current_mut().jump_to(positions[index]);
+                }
+                Pick::Buffer => {
+                    self.buffers.focus_index(index);
+                    self.buffers.set_bounds(self.layout(term.size()).text);
+                }
+                Pick::RecentFile(paths) => {
+                    self.open_path(&paths[index], AfterOpen::Show);
+                }
+                Pick::Yank => {
+                    if let Some(yank) = self.kill_ring.promote(index).cloned() {
+                        self.paste(yank, false).await?;
+                    }
+                }
+                Pick::Replace(replacement, hits) => self.replace(replacement, hits).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts diffing the current buffer with a base in the background. The hunks that differ are
+    /// shown once the diff is done.
+    fn open_diff(&mut self, base: Base) {
+        let buffer = self.buffers.current();
+
+        let path = match buffer.path() {
+            Some(path) => path.to_owned(),
+            None => {
+                self.message = Some(String::from("buffer has no file name"));
+                return;
+            }
+        };
+
+        let base_text = match &base {
+            Base::Buffer(other) => (&self.buffers)
+                .into_iter()
+                .find(|buffer| buffer.path() == Some(other.as_path()))
+                .map(Buffer::text),
+            _ => None,
+        };
+
+        diff::spawn(
+            path,
+            buffer.version(),
+            base,
+            base_text,
+            buffer.text(),
+            self.diff_sender.clone(),
+        );
+    }
+
+    /// Shows the hunks of a diff that was computed in the background, or reports why it failed.
+    ///
+    /// The diff is discarded if the buffer was edited or isn't shown anymore, or if the editor
+    /// isn't in normal mode.
+    fn handle_diff_report(&mut self, report: diff::Report) {
+        let view = match report.view {
+            Ok(view) => view,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return;
+            }
+        };
+
+        let buffer = self.buffers.current();
+        if buffer.path() != Some(view.path())
+            || buffer.version() != report.version
+            || self.mode != Mode::Normal
+        {
+            info!("discarding outdated diff of {}", view.path().display());
+            return;
+        }
+
+        self.message = Some(view.describe_current());
+
+        if !view.is_empty() {
+            self.diff = Some(view);
+            self.mode = Mode::Diff;
+        }
+    }
+
+    /// Handles a key in diff mode, which moves between the hunks of the diff and stages or reverts
+    /// them.
+    async fn handle_diff_key(&mut self, key: Key) -> Result<(), Error> {
+        // `]c` and `[c` move between hunks, like in Vim's diff mode.
+        let pending = std::mem::take(&mut self.pending_keys);
+        let key = match (pending.as_slice(), key) {
+            ([], Key::Char(']')) | ([], Key::Char('[')) => {
+                self.pending_keys.push(key);
+                return Ok(());
+            }
+            ([Key::Char(']')], Key::Char('c')) => Key::Char('n'),
+            ([Key::Char('[')], Key::Char('c')) => Key::Char('p'),
+            ([], key) => key,
+            _ => return Ok(()),
+        };
+
+        let view = self.diff.as_mut().expect("diff mode without a diff");
+
+        match key {
+            Key::Char('n') => {
+                view.next();
+            }
+            Key::Char('p') => {
+                view.previous();
+            }
+            Key::Char('j') | Key::ArrowDown | Key::Ctrl('e') => view.scroll(1),
+            Key::Char('k') | Key::ArrowUp | Key::Ctrl('y') => view.scroll(-1),
+            Key::Char('s') => {
+                let staged = match view.stage_current().await {
+                    Ok(()) => diff::read_base(view.path(), view.base()).await,
+                    Err(e) => Err(e),
+                };
+
+                match staged {
+                    Ok(base_text) => view.update(Some(base_text), self.buffers.current().text()),
+                    Err(e) => {
+                        self.message = Some(e.to_string());
+                        return Ok(());
+                    }
+                }
+            }
+            Key::Char('r') => {
+                if let Some(text_edit) = view.revert_current() {
+                    let changes = hashmap! { view.path().to_uri() => vec![text_edit] };
+                    self.apply_workspace_edit(WorkspaceEdit::new(changes))
+                        .await?;
+
+                    let text = self.buffers.current().text();
+                    let view = self.diff.as_mut().expect("diff mode without a diff");
+                    view.update(None, text);
+                }
+            }
+            Key::Return => {
+                if let Some(hunk) = view.current() {
+                    let position = Position::new(0, hunk.new.start);
+                    self.buffers.current_mut().jump_to(position);
+                }
+                self.close_diff();
+                return Ok(());
+            }
+            Key::Char('q') | Key::Esc => {
+                self.close_diff();
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+
+        let view = self.diff.as_ref().expect("diff mode without a diff");
+        self.message = Some(view.describe_current());
+        if view.is_empty() {
+            self.close_diff();
+        }
+
+        Ok(())
+    }
+
+    /// Jumps to the first definition of a tag in the tags file, remembering where the jump was made
+    /// from.
+    async fn jump_to_tag(&mut self, name: &str) -> Result<(), Error> {
+        let tags = match tags::lookup(self.working_dir(), name).await {
+            Ok(tags) => tags,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return Ok(());
+            }
+        };
+
+        let tag = match tags.first() {
+            Some(tag) => tag,
+            None => {
+                self.message = Some(format!("tag not found: {}", name));
+                return Ok(());
+            }
+        };
+
+        let current = self.buffers.current();
+        let origin = current
+            .path()
+            .map(|path| (path.to_owned(), current.cursor()));
+
+        let then = AfterOpen::Tag {
+            name: name.to_owned(),
+            address: tag.address.clone(),
+            count: tags.len(),
+            origin,
+        };
+        self.open_path(&tag.path, then);
+
+        Ok(())
+    }
+
+    /// Jumps back to where the last tag jump was made from.
+    fn pop_tag(&mut self) {
+        match self.tag_stack.pop() {
+            Some((path, position)) => self.open_path(&path, AfterOpen::Jump(position)),
+            None => self.message = Some(String::from("tag stack is empty")),
+        }
+    }
+
+    /// Opens the help document that defines the tag for a topic, at the tag.
+    fn open_help(&mut self, topic: &str) {
+        match help::find(topic) {
+            Some(location) => {
+                let buffer = self.buffers.focus_help(location.doc);
+                buffer.jump_to(Position::new(0, location.line));
+            }
+            None => self.message = Some(format!("no help for {}", topic)),
+        }
+    }
+
+    /// Runs a program in a new terminal buffer, or the user's shell if no command is given, and
+    /// starts sending keys to it.
+    fn open_terminal(&mut self, command: Vec<String>, term: &Terminal) {
+        let size = self.layout(term.size()).text.size();
+        self.terminal_id += 1;
+
+        match Pty::spawn(
+            self.terminal_id,
+            &command,
+            self.working_dir(),
+            size,
+            self.terminal_sender.clone(),
+        ) {
+            Ok(pty) => {
+                self.buffers
+                    .push(Buffer::from_terminal(self.terminal_id, pty, size));
+                self.mode = Mode::Terminal;
+            }
+            Err(e) => self.message = Some(format!("unable to start terminal: {}", e)),
+        }
+    }
+
+    /// Sends a key to the program in the current terminal buffer. Like in Vim, `<C-\><C-n>`
+    /// returns to normal mode instead.
+    fn handle_terminal_key(&mut self, key: Key) {
+        let pending = std::mem::take(&mut self.pending_keys);
+        match (pending.as_slice(), key) {
+            ([], Key::Ctrl('\\')) => {
+                self.pending_keys.push(key);
+                return;
+            }
+            ([_], Key::Ctrl('n')) => {
+                self.mode = Mode::Normal;
+                return;
+            }
+            _ => (),
+        }
+
+        let buffer = self.buffers.current_mut();
+        if !buffer.is_terminal_running() {
+            self.mode = Mode::Normal;
+            return;
+        }
+
+        for key in pending.into_iter().chain(iter::once(key)) {
+            if let Err(e) = buffer.send_key(key) {
+                self.message = Some(format!("unable to write to terminal: {}", e));
+                break;
+            }
+        }
+    }
+
+    /// Updates a terminal buffer with the output of its program.
+    fn handle_terminal_output(&mut self, output: pty::Output) {
+        let id = match &output {
+            pty::Output::Data(id, _) | pty::Output::Exited(id, _) => *id,
+        };
+
+        let buffer = match (&mut self.buffers)
+            .into_iter()
+            .find(|buffer| buffer.terminal_id() == Some(id))
+        {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        match output {
+            pty::Output::Data(_, data) => buffer.terminal_output(&data),
+            pty::Output::Exited(_, status) => {
+                buffer.terminal_exited(&status);
+
+                if self.mode == Mode::Terminal && !self.buffers.current().is_terminal_running() {
+                    self.mode = Mode::Normal;
+                }
+            }
+        }
+    }
+
+    fn close_diff(&mut self) {
+        self.diff = None;
+        self.mode = Mode::Normal;
+        self.pending_keys.clear();
+    }
+
+    /// Closes the buffer at an index, unless it has unsaved changes.
+    async fn close_buffer(&mut self, index: usize) -> Result<(), Error> {
+        let buffer = match (&self.buffers).into_iter().nth(index) {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+
+        if buffer.is_modified() {
+            self.message = Some(String::from("buffer has unsaved changes"));
+            return Ok(());
+        }
+
+        let buffer = self.buffers.remove(index);
+        self.state.remember_cursor(&buffer);
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            if let Some(identifier) = buffer.to_text_document_identifier();
+            then {
+                server.did_close_text_document(identifier).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows the result of `git blame` in the status line, or reports why it failed.
+    fn handle_blame_report(&mut self, report: blame::Report) {
+        match &report.blame {
+            Ok(_) => {
+                self.message = None;
+                self.blame = Some(report);
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    /// Adds a buffer for a file that was opened in the background.
+    ///
+    /// Files given on the command line are added without being shown. Files that the user opened
+    /// are shown, unless another file was opened since. The buffer is discarded if the file was
+    /// opened some other way in the meantime.
+    async fn handle_load_report(&mut self, report: load::Report) -> Result<(), Error> {
+        let then = if report.show {
+            match self.opening.take() {
+                Some((path, then)) if path == report.path => Some(then),
+                opening => {
+                    info!(
+                        "discarding {}, another file was opened",
+                        report.path.display()
+                    );
+                    self.opening = opening;
+                    return Ok(());
+                }
+            }
+        } else {
+            self.loading -= 1;
+            None
+        };
+
+        let mut buffer = match report.buffer {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                self.message = Some(format!("unable to open {}: {}", report.path.display(), e));
+                return Ok(());
+            }
+        };
+
+        // Directories are opened with `.` components removed, so the buffer's path is used.
+        let path = buffer.path().map(Path::to_owned);
+        if let Some(index) = path.as_deref().and_then(|path| self.buffers.index_of(path)) {
+            if let Some(then) = then {
+                self.buffers.focus_index(index);
+                self.after_open(then);
+            }
+            return Ok(());
+        }
+
+        self.state.restore_cursor(&mut buffer);
+
+        let large = buffer.is_large();
+        match then {
+            Some(_) => self.buffers.push(buffer),
+            None => {
+                self.buffers.add(buffer);
+            }
+        }
+
+        if let Some(path) = path {
+            self.emit(Event::BufOpen(path)).await?;
+        }
+
+        if let Some(then) = then {
+            if large {
+                self.message = Some(String::from(
+                    "file is large: highlighting and language servers are disabled",
+                ));
+            }
+
+            self.after_open(then);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the blame of the cursor's line, if it was blamed since the buffer was last edited.
+    fn current_blame(&self) -> Option<&blame::Blame> {
+        let report = self.blame.as_ref()?;
+        let buffer = self.buffers.current();
+
+        if buffer.path() == Some(report.path.as_path())
+            && buffer.version() == report.version
+            && buffer.cursor_line() == report.line
+        {
+            report.blame.as_ref().ok()
+        } else {
+            None
+        }
+    }
+
+    /// Updates the diagnostics of a buffer with the problems found by a linter.
+    ///
+    /// Reports for outdated versions of the buffer are discarded.
+    fn handle_lint_report(&mut self, report: lint::Report) {
+        let buffer = match self.buffers.get_mut(&report.path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        if buffer.version() != report.version {
+            info!(
+                "discarding lint report for outdated version of {}",
+                report.path.display()
+            );
+            return;
+        }
+
+        match report.diagnostics {
+            Ok(diagnostics) => {
+                info!(
+                    "{} found {} problems in {}",
+                    report.source,
+                    diagnostics.len(),
+                    report.path.display()
+                );
+                buffer.set_diagnostics(&report.source, diagnostics);
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    /// Handles a notification from a language server.
+    ///
+    /// Log messages are written to the editor's log at the matching level. Telemetry is meant to be
+    /// collected by the client, so it's ignored.
+    fn handle_notification(&mut self, ctx: &lsp::Context, notification: Notification) {
+        match notification.method.as_str() {
+            LogMessage::METHOD => match notification.params::<LogMessage>() {
+                Ok(params) => {
+                    let level = match params.typ {
+                        MessageType::Error => Level::Error,
+                        MessageType::Warning => Level::Warn,
+                        MessageType::Info => Level::Info,
+                        MessageType::Log => Level::Debug,
+                    };
+                    log!(
+                        level,
+                        "{:?} language server: {}",
+                        ctx.syntax,
+                        params.message
+                    );
+                }
+                Err(e) => info!("invalid log message notification: {}", e),
+            },
+            PublishDiagnostics::METHOD => match notification.params::<PublishDiagnostics>() {
+                Ok(params) => self.handle_published_diagnostics(ctx, params),
+                Err(e) => info!("invalid diagnostics notification: {}", e),
+            },
+            TelemetryEvent::METHOD => trace!("telemetry event: {:?}", notification.params),
+            _ => info!("unhandled notification: {:?}", notification),
+        }
+    }
+
+    /// Replaces the diagnostics of a buffer with those published by its language server.
+    ///
+    /// The server publishes every diagnostic of a file each time, so they replace the server's
+    /// earlier diagnostics without affecting those from linters. Diagnostics for files that aren't
+    /// open, or for outdated versions of a buffer, are discarded.
+    fn handle_published_diagnostics(
+        &mut self,
+        ctx: &lsp::Context,
+        params: PublishDiagnosticsParams,
+    ) {
+        let path = match params.uri.to_file_path() {
+            Ok(path) => path,
+            Err(()) => {
+                warn!("diagnostics published for non-file URI: {}", params.uri);
+                return;
+            }
+        };
+
+        let buffer = match self.buffers.get_mut(&path) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        if let Some(version) = params.version {
+            if version != i64::from(buffer.version()) {
+                info!(
+                    "discarding diagnostics for outdated version of {}",
+                    path.display()
+                );
+                return;
+            }
+        }
+
+        info!(
+            "{:?} language server found {} problems in {}",
+            ctx.syntax,
+            params.diagnostics.len(),
+            path.display()
+        );
+        buffer.set_diagnostics(LANGUAGE_SERVER_SOURCE, params.diagnostics);
+    }
+
+    /// Runs the build command in the background.
+    fn make(&mut self, extra_args: Vec<String>) {
+        let (prog, args) = self.make_config.command(self.buffers.current().syntax);
+        let prog = prog.clone();
+        let args = args.iter().cloned().chain(extra_args).collect::<Vec<_>>();
+
+        info!("running {} {:?}", prog, args);
+        self.message = Some(format!("running {}...", prog));
+
+        make::spawn(
+            prog,
+            args,
+            self.working_dir().to_owned(),
+            self.make_sender.clone(),
+        );
+    }
+
+    /// Fills the quickfix list with the problems reported by a build, and jumps to the first one.
+    async fn handle_make_report(&mut self, report: make::Report) -> Result<(), Error> {
+        self.quickfix = QuickfixList::new(report.to_entries());
+
+        if !self.quickfix.is_empty() {
+            self.jump_to_list_entry(ListKind::Quickfix);
+            return Ok(());
+        }
+
+        self.message = Some(match report.status {
+            Ok(status) if status.success() => format!("{}: no errors", report.command),
+            Ok(status) => format!("{} failed: {}", report.command, status),
+            Err(e) => format!("unable to run {}: {}", report.command, e),
+        });
+
+        Ok(())
+    }
+
+    fn list(&self, kind: ListKind) -> &QuickfixList {
+        match kind {
+            ListKind::Quickfix => &self.quickfix,
+            ListKind::Location => &self.location_list,
+        }
+    }
+
+    fn list_mut(&mut self, kind: ListKind) -> &mut QuickfixList {
+        match kind {
+            ListKind::Quickfix => &mut self.quickfix,
+            ListKind::Location => &mut self.location_list,
+        }
+    }
+
+    /// Moves to another entry of a list with `advance`, then jumps to it.
+    async fn navigate_list(
+        &mut self,
+        kind: ListKind,
+        advance: fn(&mut QuickfixList) -> bool,
+    ) -> Result<(), Error> {
+        let list = self.list_mut(kind);
+
+        if list.is_empty() {
+            self.message = Some(format!("{} is empty", <&str>::from(kind)));
+            return Ok(());
+        }
+
+        if !advance(list) {
+            self.message = Some(String::from("no more items"));
+            return Ok(());
+        }
+
+        self.jump_to_list_entry(kind);
+        Ok(())
+    }
+
+    /// Searches the working directory for a pattern in the background, replacing the quickfix list
+    /// with the matches. Any search that's still running is stopped.
+    fn grep(&mut self, pattern: String, fixed_strings: bool) {
+        if let Some(task) = self.grep_task.take() {
+            task.abort();
+        }
+
+        self.grep_id += 1;
+        self.quickfix = QuickfixList::default();
+        self.message = Some(format!("searching for {}...", pattern));
+
+        self.grep_task = Some(grep::spawn(
+            self.grep_id,
+            self.grep_config.command(),
+            pattern,
+            fixed_strings,
+            self.working_dir().to_owned(),
+            self.grep_sender.clone(),
+        ));
+    }
+
+    /// Adds the matches found by a search to the quickfix list, jumping to the first match.
+    ///
+    /// If the search was started by `:replace`, the cursor stays put, and the replacement is
+    /// previewed once the search finishes.
+    async fn handle_grep_update(&mut self, update: grep::Update) -> Result<(), Error> {
+        if update.id != self.grep_id {
+            return Ok(());
+        }
+
+        match update.event {
+            grep::Event::Matches(entries) => {
+                let first_matches = self.quickfix.is_empty();
+                self.quickfix.extend(entries);
+
+                if first_matches && self.replacement.is_none() {
+                    self.jump_to_list_entry(ListKind::Quickfix);
+                }
+            }
+            grep::Event::Finished(Ok(())) => {
+                self.message = Some(match self.quickfix.len() {
+                    0 => String::from("no matches"),
+                    1 => String::from("1 match"),
+                    n => format!("{} matches", n),
+                });
+
+                if let Some(replacement) = self.replacement.take() {
+                    let hits = replace::hits(self.quickfix.entries());
+                    if !hits.is_empty() {
+                        let items = self.replacement_items(&replacement, &hits);
+                        self.open_picker(
+                            Picker::new("replace: ", items),
+                            Pick::Replace(replacement, hits),
+                        );
+                    }
+                }
+            }
+            grep::Event::Finished(Err(e)) => {
+                self.replacement = None;
+                self.message = Some(e.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns picker items that preview replacing the matches on each line.
+    fn replacement_items(&self, replacement: &Replacement, hits: &[Hit]) -> Vec<picker::Item> {
+        hits.iter()
+            .map(|hit| replacement.item(hit, self.working_dir()))
+            .collect()
+    }
+
+    /// Replaces the matches on the lines that were kept in the preview of `:replace`.
+    ///
+    /// Files that aren't open are opened in hidden buffers, which are left unsaved.
+    async fn replace(&mut self, replacement: Replacement, hits: Vec<Hit>) -> Result<(), Error> {
+        let mut matches = 0;
+        let mut files = 0;
+
+        for (path, lines) in replace::by_file(&hits) {
+            let replaced = self
+                .edit_file(path, |buffer| {
+                    buffer.replace_in_lines(&lines, &replacement.pattern, &replacement.replacement)
+                })
+                .await?;
+
+            if replaced > 0 {
+                matches += replaced;
+                files += 1;
+            }
+        }
+
+        self.message = Some(format!(
+            "replaced {} {} in {} {}",
+            matches,
+            if matches == 1 { "match" } else { "matches" },
+            files,
+            if files == 1 { "file" } else { "files" },
+        ));
+
+        Ok(())
+    }
+
+    /// Opens the file of the current entry of a list and moves the cursor to its location.
+    fn jump_to_list_entry(&mut self, kind: ListKind) {
+        if let Some(entry) = self.list(kind).current().cloned() {
+            self.message = self.list(kind).describe_current();
+            self.open_path(&entry.path, AfterOpen::Jump(entry.position));
+        }
+    }
+
+    /// Returns the directory that relative paths are resolved against: the current buffer's
+    /// working directory if it has one, or the editor's.
+    fn working_dir(&self) -> &Path {
+        self.buffers
+            .current()
+            .local_dir()
+            .unwrap_or(&self.current_dir)
+    }
+
+    /// Resolves the argument of `:cd` or `:lcd` against the working directory.
+    ///
+    /// Returns `None` if the path is not a directory, after reporting why to the user.
+    async fn resolve_dir(&mut self, path: PathBuf) -> Option<PathBuf> {
+        let dir = self.working_dir().join(path);
+
+        let result = match fs::canonicalize(&dir).await {
+            Ok(dir) => fs::metadata(&dir).await.map(|metadata| (dir, metadata)),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok((dir, metadata)) if metadata.is_dir() => Some(dir),
+            Ok((dir, _)) => {
+                self.message = Some(format!("not a directory: {}", dir.display()));
+                None
+            }
+            Err(e) => {
+                self.message = Some(format!(
+                    "unable to change directory to {}: {}",
+                    dir.display(),
+                    e
+                ));
+                None
+            }
+        }
+    }
+
+    /// Makes the buffer for a path the active buffer, and then does what's left to do with it.
+    ///
+    /// If there isn't a buffer for the path, the file is opened in the background, so that keys are
+    /// handled while it's read. It's shown once it's open, unless another file was opened since.
+    fn open_path(&mut self, path: &Path, then: AfterOpen) {
+        match self.buffers.index_of(path) {
+            Some(index) => {
+                self.opening = None;
+                self.buffers.focus_index(index);
+                self.after_open(then);
+            }
+            None => {
+                self.opening = Some((path.to_owned(), then));
+                load::open(path.to_owned(), self.load_sender.clone());
+            }
+        }
+    }
+
+    /// Does what's left to do with a buffer that the user opened, once it's the active buffer.
+    fn after_open(&mut self, then: AfterOpen) {
+        let buffer = self.buffers.current_mut();
+
+        match then {
+            AfterOpen::Show => (),
+            AfterOpen::Jump(position) => buffer.jump_to(position),
+            AfterOpen::Tag {
+                name,
+                address,
+                count,
+                origin,
+            } => {
+                self.tag_stack.extend(origin);
+
+                match address.find(&buffer.text()) {
+                    Some(line) => {
+                        buffer.jump_to(Position::new(0, line));
+
+                        if count > 1 {
+                            self.message = Some(format!("tag 1 of {}", count));
+                        }
+                    }
+                    None => self.message = Some(format!("definition of {} not found", name)),
+                }
+            }
+        }
+    }
+
+    /// Writes the current buffer to its file.
+    async fn write(&mut self) -> Result<(), Error> {
+        let path = match self.buffers.current().path() {
+            Some(path) => path.to_owned(),
+            None => {
+                // The file name is asked for on the command line.
+                self.message = None;
+                self.command_line.prefill("saveas ");
+                self.mode = Mode::Command;
+                return Ok(());
+            }
+        };
+
+        // Problems found before writing, such as a failing formatter, are reported with the write.
+        self.message = None;
+        self.emit(Event::BufWritePre(path.clone())).await?;
+
+        match self.buffers.current_mut().save().await {
+            Ok(()) => {
+                self.message = Some(match self.message.take() {
+                    Some(problem) => format!("wrote {}; {}", path.display(), problem),
+                    None => format!("wrote {}", path.display()),
+                });
+                self.notify_saved().await?;
+                self.emit(Event::BufWritePost(path)).await?;
+            }
+            Err(e) => self.message = Some(format!("unable to write {}: {}", path.display(), e)),
+        }
+
+        Ok(())
+    }
+
+    /// Makes the changes that the config asks for before the current buffer is written to a path,
+    /// such as formatting it or trimming trailing whitespace.
+    ///
+    /// The changes are made as a single transaction and sent to the language server like any other
+    /// edit. If the formatter fails, the buffer is written unformatted and the error is left in the
+    /// message.
+    async fn apply_save_hooks(&mut self, path: &Path) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+
+        // Hex dumps aren't text, so they are written as they are.
+        if buffer.is_binary() {
+            return Ok(());
+        }
+
+        let formatted = match self.save_config.formatter(buffer.syntax) {
+            Some(command) => match format::run(command, buffer.text(), path).await {
+                Ok(formatted) => Some(formatted),
+                Err(e) => {
+                    self.message = Some(format!("unable to format: {}", e));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let trim_trailing_whitespace = self.save_config.trim_trailing_whitespace(buffer.syntax);
+        let final_newline = self.save_config.final_newline(buffer.syntax);
+
+        let edits = buffer.transaction(|buffer| {
+            let mut edits = vec![];
+
+            if let Some(formatted) = formatted {
+                edits.extend(buffer.replace_text(&formatted));
+            }
+
+            if trim_trailing_whitespace {
+                edits.extend(buffer.trim_trailing_whitespace());
+            }
+
+            if final_newline {
+                edits.extend(buffer.ensure_final_newline());
+            }
+
+            edits
+        });
+
+        if_chain! {
+            if !edits.is_empty();
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    edits.iter().map(|edit| edit.to_text_document_content_change_event()).collect(),
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the current buffer to a new file, either by renaming its file or by writing it to the
+    /// new path.
+    ///
+    /// Before the file is moved on disk, the language server is given the opportunity to update
+    /// other files that refer to it, such as imports.
+    async fn move_file(&mut self, path: PathBuf, how: FileMove) -> Result<(), Error> {
+        let new_path = self.working_dir().join(path);
+
+        let old_path = match self.buffers.current().path() {
+            Some(path) => path.to_owned(),
+            None if matches!(how, FileMove::SaveAs) => return self.write_new_file(new_path).await,
+            None => {
+                self.message = Some(String::from("buffer has no file name"));
+                return Ok(());
+            }
+        };
+
+        let files = vec![FileRename {
+            old_uri: old_path.to_uri(),
+            new_uri: new_path.to_uri(),
+        }];
+
+        let old_syntax = self.buffers.current().syntax;
+
+        if_chain! {
+            if let Some(syntax) = old_syntax;
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                match server.will_rename_files(files.clone()).await {
+                    Ok(Some(edit)) => self.apply_workspace_edit(edit).await?,
+                    Ok(None) => (),
+                    Err(e) => info!("unable to request edits before rename: {}", e),
+                }
+            }
+        }
+
+        let result = match how {
+            FileMove::Rename => match fs::rename(&old_path, &new_path).await {
+                // The file has not been written yet, so there is nothing to rename on disk.
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+            FileMove::SaveAs => {
+                self.emit(Event::BufWritePre(new_path.clone())).await?;
+                self.buffers.current_mut().write_to(&new_path).await
+            }
+        };
+
+        if let Err(e) = result {
+            self.message = Some(match how {
+                FileMove::Rename => format!(
+                    "unable to rename {} to {}: {}",
+                    old_path.display(),
+                    new_path.display(),
+                    e
+                ),
+                FileMove::SaveAs => format!("unable to write {}: {}", new_path.display(), e),
+            });
+            return Ok(());
+        }
+
+        info!("moved {} to {}", old_path.display(), new_path.display());
+
+        let root = self.working_dir().to_owned();
+        let buffer = self.buffers.current_mut();
+
+        if_chain! {
+            if let Some(syntax) = old_syntax;
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            if let Some(identifier) = buffer.to_text_document_identifier();
+            then {
+                server.did_rename_files(files).await?;
+                server.did_close_text_document(identifier).await?;
+            }
+        }
+
+        buffer.set_path(new_path.clone());
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(server) = self.ls_bridge.get_or_init(root, lsp::Context { syntax }).await;
+            if let Some(text_document_item) = buffer.to_text_document_item();
+            then {
+                server.did_open_text_document(text_document_item).await?;
+            }
+        }
+
+        if let FileMove::SaveAs = how {
+            self.notify_saved().await?;
+            self.emit(Event::BufWritePost(new_path)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the current buffer, which has no file, to a new file that it edits from then on.
+    async fn write_new_file(&mut self, path: PathBuf) -> Result<(), Error> {
+        self.message = None;
+        self.emit(Event::BufWritePre(path.clone())).await?;
+
+        let root = self.working_dir().to_owned();
+        let buffer = self.buffers.current_mut();
+
+        if let Err(e) = buffer.write_to(&path).await {
+            self.message = Some(format!("unable to write {}: {}", path.display(), e));
+            return Ok(());
+        }
+
+        buffer.set_path(path.clone());
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(server) = self.ls_bridge.get_or_init(root, lsp::Context { syntax }).await;
+            if let Some(text_document_item) = buffer.to_text_document_item();
+            then {
+                server.did_open_text_document(text_document_item).await?;
+            }
+        }
+
+        self.message = Some(match self.message.take() {
+            Some(problem) => format!("wrote {}; {}", path.display(), problem),
+            None => format!("wrote {}", path.display()),
+        });
+        self.notify_saved().await?;
+        self.emit(Event::BufWritePost(path)).await?;
+
+        Ok(())
+    }
+
+    /// Tells the language server of the current buffer that the buffer was written to its file.
+    async fn notify_saved(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current();
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(identifier) = buffer.to_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_save_text_document(identifier).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a workspace edit from a language server to the affected buffers.
+    ///
+    /// Files that are not already open are opened in hidden buffers, so the changes are not
+    /// written to disk until the user saves them.
+    async fn apply_workspace_edit(&mut self, edit: WorkspaceEdit) -> Result<(), Error> {
+        let mut changes: Vec<(Uri, Vec<TextEdit>)> =
+            edit.changes.unwrap_or_default().into_iter().collect();
+
+        match edit.document_changes {
+            Some(DocumentChanges::Edits(edits)) => changes.extend(
+                edits
+                    .into_iter()
+                    .map(|edit| (edit.text_document.uri, edit.edits)),
+            ),
+            Some(DocumentChanges::Operations(operations)) => {
+                for operation in operations {
+                    match operation {
+                        DocumentChangeOperation::Edit(edit) => {
+                            changes.push((edit.text_document.uri, edit.edits))
+                        }
+                        DocumentChangeOperation::Op(op) => {
+                            warn!("unsupported resource operation: {:?}", op)
+                        }
+                    }
+                }
+            }
+            None => (),
+        }
+
+        for (uri, text_edits) in changes {
+            let path = match uri.to_file_path() {
+                Ok(path) => path,
+                Err(()) => {
+                    warn!("unable to apply edit to non-file URI: {}", uri);
+                    continue;
+                }
+            };
+
+            self.edit_file(&path, |buffer| buffer.apply_text_edits(text_edits))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Edits the buffer of a file, opening a hidden buffer for it if there isn't one, and tells its
+    /// language server about the edits. Returns the number of edits.
+    ///
+    /// If the file can't be opened, it isn't edited, and the reason is logged.
+    async fn edit_file<F>(&mut self, path: &Path, edit: F) -> Result<usize, Error>
+    where
+        F: FnOnce(&mut Buffer) -> Vec<Edit>,
+    {
+        let (buffer, opened) = match self.buffers.get_or_open(path).await {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("unable to open {} to apply edit: {}", path.display(), e);
+                return Ok(0);
+            }
+        };
+
+        let edits = edit(buffer);
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                if opened {
+                    if let Some(text_document_item) = buffer.to_text_document_item() {
+                        server.did_open_text_document(text_document_item).await?;
+                    }
+                } else if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier() {
+                    server.did_change_text_document(
+                        versioned_identifier,
+                        edits.iter().map(|edit| edit.to_text_document_content_change_event()).collect(),
+                    ).await?;
+                }
+            }
+        }
+
+        Ok(edits.len())
+    }
+
+    async fn delete_char(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+        let edit = buffer.delete();
+
+        if_chain! {
+            if let Some(edit) = edit;
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the word before the cursor in the active buffer.
+    async fn delete_word_backward(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+        let edit = buffer.delete_word_backward();
+
+        if_chain! {
+            if let Some(edit) = edit;
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the text before the cursor on its line in the active buffer.
+    async fn delete_to_line_start(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+        let edit = buffer.delete_to_line_start();
+
+        if_chain! {
+            if let Some(edit) = edit;
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the character under the cursor in the active buffer.
+    async fn delete_char_forward(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+        let edit = buffer.delete_forward();
+
+        if let Some(edit) = &edit {
+            self.kill_ring.push(Yank::new(edit.old_text.clone()));
+        }
+
+        if_chain! {
+            if let Some(edit) = edit;
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the text between the cursor and the target of a motion in the active buffer.
+    async fn delete_motion(&mut self, motion: Motion) -> Result<(), Error> {
+        match self.buffers.current_mut().delete_motion(motion) {
+            Some(edit) => self.kill(Yank::new(edit.old_text.clone()), edit).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Deletes the text selected in visual mode, adding it to the kill ring.
+    async fn delete_selection(&mut self) -> Result<(), Error> {
+        match self.buffers.current_mut().delete_selection() {
+            Some(edit) => self.kill(Yank::new(edit.old_text.clone()), edit).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Deletes the cursor's line in the active buffer, adding it to the kill ring as a whole line.
+    async fn delete_line(&mut self) -> Result<(), Error> {
+        let (text, edit) = self.buffers.current_mut().delete_line();
+        self.kill(Yank::lines(text), edit).await
+    }
+
+    /// Adds the text removed by a deletion to the kill ring, and tells the language server about
+    /// the deletion.
+    async fn kill(&mut self, yank: Yank, edit: Edit) -> Result<(), Error> {
+        let buffer = self.buffers.current();
+        self.kill_ring.push(yank);
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the text between the cursor and the target of a motion to the kill ring.
+    async fn yank_motion(&mut self, motion: Motion) -> Result<(), Error> {
+        if let Some((text, range)) = self.buffers.current().yank_motion(motion) {
+            self.kill_ring.push(Yank::new(text));
+            self.emit(Event::TextYanked(range)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Responds to an event, and queues the commands of the hooks for it.
+    async fn emit(&mut self, event: Event) -> Result<(), Error> {
+        debug!("event: {:?}", event);
+
+        match &event {
+            Event::BufOpen(path) => self.buffer_opened(path).await?,
+            Event::BufWritePre(path) => self.apply_save_hooks(path).await?,
+            Event::ModeChanged(Mode::Insert, _) => {
+                self.linters.lint(self.buffers.current());
+            }
+            Event::ModeChanged(Mode::Visual, _) => self.buffers.current_mut().clear_selection(),
+            Event::TextYanked(range) => self.flash(range.clone()),
+            Event::BufWritePost(_)
+            | Event::ModeChanged(..)
+            | Event::CursorHold
+            | Event::FocusLost => (),
+        }
+
+        self.hooks.queue(&event);
+
+        if !self.hooks.is_running() && self.scripts.handles(event.kind()) {
+            let snapshot = self.snapshot();
+            if let Err(e) = self.scripts.dispatch(&event, snapshot) {
+                self.message = Some(format!("script error: {}", e));
+            }
+            self.hooks.extend(self.scripts.take_requests());
+        }
+
+        Ok(())
+    }
+
+    /// Carries out the requests of the hooks and scripts for the events that happened since they
+    /// last ran.
+    async fn run_hooks(&mut self, term: &Terminal) -> Result<(), Error> {
+        let requests = self.hooks.start();
+        if requests.is_empty() {
+            return Ok(());
+        }
+
+        let mode = self.mode;
+        for request in requests {
+            match request {
+                Request::Command(command) => {
+                    // Hooks run in the middle of handling other events, so they can't stop the
+                    // editor.
+                    if let ControlFlow::Break = self.execute_command(&command, term).await? {
+                        self.message = Some(String::from("hooks cannot quit the editor"));
+                    }
+                }
+                Request::Insert(text) => self.paste(Yank::new(text), false).await?,
+                Request::Snippet(snippet) => self.insert_snippet(&snippet).await?,
+                Request::Message(message) => self.message = Some(message),
+            }
+        }
+
+        if self.mode != mode {
+            self.emit(Event::ModeChanged(mode, self.mode)).await?;
+        }
+
+        self.hooks.finish();
+
+        Ok(())
+    }
+
+    /// Runs `init.lua` from the config directory, binding the chords that it maps.
+    async fn run_init_script(&mut self) {
+        let config_dir = match Config::config_dir() {
+            Some(config_dir) => config_dir,
+            None => return,
+        };
+
+        let snapshot = self.snapshot();
+        if let Err(e) = self.scripts.run_init(&config_dir, snapshot).await {
+            self.message = Some(format!("script error: {}", e));
+        }
+
+        for (i, mapping) in self.scripts.mappings().into_iter().enumerate() {
+            let keymap = match mapping.mode {
+                Mode::Insert => &mut self.insert_keymap,
+                Mode::Visual => &mut self.visual_keymap,
+                _ => &mut self.normal_keymap,
+            };
+            keymap.bind(&mapping.chord, Action::Script(i));
+        }
+
+        self.hooks.extend(self.scripts.take_requests());
+    }
+
+    /// Returns the state of the editor that scripts can read.
+    fn snapshot(&self) -> Snapshot {
+        let buffer = self.buffers.current();
+
+        Snapshot {
+            path: buffer.path().map(Path::to_owned),
+            syntax: buffer.syntax,
+            text: buffer.text(),
+            cursor: buffer.cursor(),
+            mode: self.mode,
+            options: OptionName::ALL
+                .iter()
+                .map(|option| (*option, option.get(&self.options, buffer)))
+                .collect(),
+        }
+    }
+
+    /// Tells the language server and the linter about a buffer that was just opened.
+    async fn buffer_opened(&mut self, path: &Path) -> Result<(), Error> {
+        let root = self.working_dir().to_owned();
+        let buffer = match (&self.buffers)
+            .into_iter()
+            .find(|buffer| buffer.path() == Some(path))
+        {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(server) = self.ls_bridge.get_or_init(root, lsp::Context { syntax }).await;
+            if let Some(text_document_item) = buffer.to_text_document_item();
+            then {
+                server.did_open_text_document(text_document_item).await?;
+            }
+        }
+
+        self.linters.lint(buffer);
+
+        Ok(())
+    }
+
+    /// Highlights a range of the current buffer for the configured duration.
+    fn flash(&mut self, range: Range<Position>) {
+        let duration = match self.yank_config.highlight_duration() {
+            Some(duration) => duration,
+            None => return,
+        };
+
+        let buffer = self.buffers.current();
+        self.flash = Some(Flash {
+            path: buffer.path().map(Path::to_owned),
+            version: buffer.version(),
+            range,
+            end: Instant::now() + duration,
+        });
+    }
+
+    /// Pastes yanked or deleted text into the active buffer, before the cursor or after it.
+    async fn paste(&mut self, yank: Yank, after: bool) -> Result<(), Error> {
+        let linewise = yank.linewise;
+        self.insert_text(&yank.text, |buffer, text| {
+            if after {
+                buffer.paste_after(text, linewise)
+            } else {
+                buffer.paste(text, linewise)
+            }
+        })
+        .await
+    }
+
+    /// Inserts text into the active buffer with a function that returns the edit, or sends it to
+    /// the program if the buffer is a terminal. Buffers that can't be edited are left alone.
+    async fn insert_text(
+        &mut self,
+        text: &str,
+        insert: impl FnOnce(&mut Buffer, &str) -> Edit,
+    ) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+
+        if buffer.is_directory() {
+            self.message = Some(String::from("directory listings cannot be edited"));
+            return Ok(());
+        }
+
+        if buffer.help_doc().is_some() {
+            self.message = Some(String::from("help documents cannot be edited"));
+            return Ok(());
+        }
+
+        if buffer.log_path().is_some() {
+            self.message = Some(String::from("the log cannot be edited"));
+            return Ok(());
+        }
+
+        if buffer.terminal_id().is_some() {
+            if let Err(e) = buffer.send_text(text) {
+                self.message = Some(format!("unable to write to terminal: {}", e));
+            }
+            return Ok(());
+        }
+
+        let edit = insert(buffer, text);
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert a character into the active buffer.
+    /// Reads the config files again and applies their settings, as if the editor were restarted.
+    ///
+    /// Options are set to their initial values again, including the buffer-local options of every
+    /// buffer.
+    async fn reload_config(&mut self) {
+        let mut config = match Config::read(&self.config_paths).await {
+            Ok(config) => config,
+            Err(e) => {
+                self.message = Some(format!("unable to read config file: {}", e));
+                return;
+            }
+        };
+
+        match ProjectConfig::read(&self.current_dir).await {
+            Ok(project_config) => config.merge_project(project_config),
+            Err(e) => {
+                self.message = Some(format!("unable to read project config file: {}", e));
+                return;
+            }
+        }
+
+        let Config {
+            language_server_config,
+            filetypes,
+            linter_config,
+            make_config,
+            grep_config,
+            save_config,
+            yank_config,
+            key_config,
+            log_config: _,
+            colorscheme,
+            options: config_options,
+            language_options,
+            hooks,
+        } = config;
+
+        syntax::set_filetypes(&filetypes);
+        options::set_buffer_defaults(config_options.buffer, language_options);
+
+        self.hooks = Hooks::new(hooks);
+        self.ls_bridge.set_config(language_server_config).await;
+        self.linters.set_config(linter_config);
+        self.make_config = make_config;
+        self.grep_config = grep_config;
+        self.save_config = save_config;
+        self.yank_config = yank_config;
+
+        self.normal_keymap = Keymap::normal(&key_config);
+        self.insert_keymap = Keymap::insert(&key_config);
+        self.visual_keymap = Keymap::visual(&key_config);
+        self.pending_keys.clear();
+        self.count = None;
+        self.pending_timeout = None;
+
+        self.scripts = match Scripts::new() {
+            Ok(scripts) => scripts,
+            Err(e) => {
+                self.message = Some(format!("script error: {}", e));
+                return;
+            }
+        };
+
+        self.options = config_options.global;
+        self.buffers
+            .current_mut()
+            .set_window_options(config_options.window);
+        for buffer in &mut self.buffers {
+            buffer.set_buffer_options(options::buffer_defaults(buffer.syntax));
+        }
+
+        self.message = Some(String::from("config reloaded"));
+
+        if let Some(name) = colorscheme {
+            if let Err(e) = theme::set_current(&name) {
+                self.message = Some(e.to_string());
+            }
+        }
+
+        self.run_init_script().await;
+    }
+
+    /// Inserts a character at the cursor, replacing the placeholder of a snippet that the cursor
+    /// was just moved to. If it's one of the language server's trigger characters, the server is
+    /// asked for completions.
+    async fn insert_char(&mut self, c: char) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+        let edits = buffer.transaction(|buffer| {
+            let mut edits = buffer.delete_placeholder().into_iter().collect::<Vec<_>>();
+            edits.push(buffer.insert(c));
+            edits
+        });
+        let mut is_trigger = false;
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    edits.iter().map(|edit| edit.to_text_document_content_change_event()).collect(),
+                ).await?;
+                is_trigger = server.is_completion_trigger(c);
+            }
+        }
+
+        if is_trigger {
+            self.request_completion(Some(c)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a snippet at the cursor and moves to its first tab stop in insert mode.
+    async fn insert_snippet(&mut self, snippet: &str) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+
+        if buffer.is_directory() || buffer.help_doc().is_some() || buffer.terminal_id().is_some() {
+            self.message = Some(String::from("snippets can only be inserted into text"));
+            return Ok(());
+        }
+
+        let snippet = Snippet::parse(snippet);
+        let edit = buffer.replace_before_cursor(0, &snippet.text);
+        buffer.set_tab_stops(&edit, &snippet.tab_stops);
+        buffer.next_tab_stop();
+        self.mode = Mode::Insert;
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves to the next tab stop of the snippet being filled in, or inserts a tab at the cursor if
+    /// there are none left.
+    async fn insert_tab(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current_mut();
+        if buffer.next_tab_stop() {
+            return Ok(());
+        }
+
+        let edit = buffer.insert_tab();
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Asks the language server of the current buffer for completions at the cursor, and shows
+    /// them in a menu. `trigger` is the trigger character that was just typed, if any.
+    ///
+    /// Completions that were asked for with a trigger character are expected to be missing at
+    /// times, so there's no message when there are none.
+    async fn request_completion(&mut self, trigger: Option<char>) -> Result<(), Error> {
+        let buffer = self.buffers.current();
+        let server = match buffer.syntax {
+            Some(syntax) => self.ls_bridge.get(lsp::Context { syntax }),
+            None => None,
+        };
+
+        let items = match (server, buffer.to_text_document_position()) {
+            (Some(server), Some(position)) => match server.completion(position, trigger).await {
+                Ok(Some(CompletionResponse::Array(items))) => items,
+                Ok(Some(CompletionResponse::List(list))) => list.items,
+                Ok(None) => vec![],
+                Err(e) => {
+                    info!("unable to request completions: {}", e);
+                    vec![]
+                }
+            },
+            _ => {
+                if trigger.is_none() {
+                    self.message = Some(String::from("no language server"));
+                }
+                return Ok(());
+            }
+        };
+
+        self.completion = Completion::from_items(items, self.buffers.current());
+        if self.completion.is_none() && trigger.is_none() {
+            self.message = Some(String::from("no completions"));
+        }
+
+        Ok(())
+    }
+
+    /// Asks the language server of the current buffer about the symbol under the cursor, and
+    /// shows its answer in a popup. The cursor is sent as a character offset into its line, so
+    /// that servers find the right symbol on lines with non-ASCII text.
+    async fn show_hover(&mut self) -> Result<(), Error> {
+        let buffer = self.buffers.current();
+        let server = match buffer.syntax {
+            Some(syntax) => self.ls_bridge.get(lsp::Context { syntax }),
+            None => None,
+        };
+
+        let (server, position) = match (server, buffer.to_text_document_position()) {
+            (Some(server), Some(position)) => (server, position),
+            _ => {
+                self.message = Some(String::from("no language server"));
+                return Ok(());
+            }
+        };
+
+        match server.hover(position).await {
+            Ok(response) => match response.and_then(hover::text) {
+                Some(text) => self.hover = Some(text),
+                None => self.message = Some(String::from("no information")),
+            },
+            Err(e) => {
+                info!("unable to request hover: {}", e);
+                self.message = Some(e.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the word before the cursor with the next or previous completion from the open
+    /// buffers, starting a new completion if none is in progress.
+    async fn complete(&mut self, forward: bool) -> Result<(), Error> {
+        let buffer = self.buffers.current();
+        let completion = match self.completion.take() {
+            Some(completion) if completion.is_active(buffer) => Some(completion),
+            _ => {
+                let prefix = buffer.identifier_before_cursor();
+                let candidates = completion::candidates(&self.buffers, prefix);
+                Completion::new(prefix, candidates, buffer)
+            }
+        };
+
+        let mut completion = match completion {
+            Some(completion) => completion,
+            None => {
+                self.message = Some(String::from("no completions"));
+                return Ok(());
+            }
+        };
+
+        let len = completion.text().len();
+        let had_tab_stops = !completion.tab_stops().is_empty();
+        if forward {
+            completion.next();
+        } else {
+            completion.previous();
+        }
+
+        // Words don't replace the tab stops of a snippet that's being filled in, unless they
+        // replace a snippet that was completed.
+        let buffer = self.buffers.current_mut();
+        let edit = buffer.replace_before_cursor(len, completion.text());
+        if had_tab_stops || !completion.tab_stops().is_empty() {
+            buffer.set_tab_stops(&edit, completion.tab_stops());
+        }
+        completion.inserted(buffer);
+        self.completion = Some(completion);
+
+        if_chain! {
+            if let Some(syntax) = buffer.syntax;
+            if let Some(versioned_identifier) = buffer.to_versioned_text_document_identifier();
+            if let Some(server) = self.ls_bridge.get(lsp::Context { syntax });
+            then {
+                server.did_change_text_document(
+                    versioned_identifier,
+                    vec![edit.to_text_document_content_change_event()],
+                ).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lets the cursor of the active buffer be after the end of its line in insert and terminal
+    /// modes, or if `virtualedit` allows it.
+    fn update_cursor_past_end(&mut self) {
+        let allowed = matches!(self.mode, Mode::Insert | Mode::Terminal)
+            || self.options.virtualedit == VirtualEdit::OneMore;
+        self.buffers.current_mut().set_cursor_past_end(allowed);
+    }
+
+    /// Shows the keys of a chord that is being typed at the right of the command line, like Vim's
+    /// `showcmd`. Only the last few keys are shown if there are many.
+    fn draw_pending_keys(&self, ctx: &mut ui::Context<'_>) {
+        let count = self
+            .count
+            .map(|count| count.to_string())
+            .unwrap_or_default();
+        let keys = iter::once(count)
+            .chain(self.pending_keys.iter().map(ToString::to_string))
+            .collect::<String>();
+        if keys.is_empty() {
+            return;
+        }
+
+        let skip = keys.chars().count().saturating_sub(SHOWCMD_WIDTH);
+        let keys = keys.chars().skip(skip).collect::<String>();
+        let width = u16::try_from(keys.width()).expect("pending keys are truncated");
+        let x = cmp::max(ctx.bounds.min.x, ctx.bounds.max.x.saturating_sub(width + 1));
+
+        ctx.screen
+            .write(Coordinates::new(x, ctx.bounds.min.y), &keys);
+    }
+
+    /// Returns the areas of a screen of the given size.
+    fn layout(&self, size: Size) -> Layout {
+        let list_height = match self.open_list {
+            Some(_) => LIST_HEIGHT,
+            None => 0,
+        };
+
+        Layout::new(
+            Bounds::from_size(size),
+            list_height,
+            self.buffers.current().gutter_width(),
+        )
+    }
+
+    async fn redraw(&self, term: &mut Terminal) -> Result<(), Error> {
+        term.cursor = self.draw(term.screen());
+        term.refresh().await?;
+
+        Ok(())
+    }
+
+    /// Draws a frame without a terminal, such as for comparing with a snapshot of the expected
+    /// screen. See `Screen::to_text` and `Screen::to_ansi`.
+    pub fn render(&self, size: Size) -> Screen {
+        let mut screen = Screen::new(size);
+        self.draw(&mut screen);
+        screen
+    }
+
+    /// Draws the editor to a screen, returning where the cursor should be shown.
+    fn draw(&self, screen: &mut Screen) -> Coordinates {
+        let layout = self.layout(screen.size);
+        let command_line_bounds = layout.command_line;
+
+        let mut ctx = ui::Context {
+            bounds: layout.buffer,
+            screen,
+        };
+
+        ctx.screen.clear();
+
+        let normal = theme::current().normal;
+        if normal != Style::default() {
+            ctx.screen
+                .apply_style(Bounds::from_size(ctx.screen.size), normal);
+        }
+
+        // The picker and the diff cover the buffer, so the buffer is only drawn while neither is
+        // open.
+        let current_buffer = self.buffers.current();
+        match (&self.picker, &self.diff) {
+            (Some((picker, _)), _) => picker.draw(&mut ctx),
+            (None, Some(diff)) => diff.draw(&mut ctx),
+            (None, None) => {
+                ctx.bounds = layout.gutter;
+                current_buffer.draw_line_numbers(&mut ctx);
+
+                ctx.bounds = layout.text;
+                current_buffer.draw(&mut ctx);
+
+                if_chain! {
+                    if let Some(flash) = &self.flash;
+                    if current_buffer.path() == flash.path.as_deref();
+                    if current_buffer.version() == flash.version;
+                    if let Some(style) = theme::current().style("yank");
+                    then {
+                        current_buffer.style_range(&mut ctx, flash.range.clone(), style);
+                    }
+                }
+
+                if_chain! {
+                    if self.mode == Mode::Insert;
+                    if let Some(completion) = &self.completion;
+                    if completion.is_active(current_buffer);
+                    then {
+                        let menu = Menu {
+                            items: completion.labels(),
+                            selected: completion.selected(),
+                        };
+                        // The menu is aligned with the start of the word being completed.
+                        let cursor = current_buffer.cursor_position();
+                        let column = cursor.x.saturating_sub(completion.text().width());
+                        let anchor = layout.text.min
+                            + Coordinates::new(
+                                u16::try_from(column).expect("cursor outside screen bounds"),
+                                u16::try_from(cursor.y).expect("cursor outside screen bounds"),
+                            )
+                            .to_vector();
+                        ctx.bounds = menu.bounds(layout.text, anchor);
+                        menu.draw(&mut ctx);
+                    }
+                }
+
+                if let Some(hover) = &self.hover {
+                    let popup = Popup::new(hover, layout.text.width());
+                    let cursor = current_buffer.cursor_position();
+                    let anchor = layout.text.min
+                        + Coordinates::new(
+                            u16::try_from(cursor.x).expect("cursor outside screen bounds"),
+                            u16::try_from(cursor.y).expect("cursor outside screen bounds"),
+                        )
+                        .to_vector();
+                    ctx.bounds = popup.bounds(layout.text, anchor);
+                    popup.draw(&mut ctx);
+                }
+            }
+        }
+
+        ctx.bounds = layout.status_line;
+
+        StatusLine {
+            path: current_buffer
+                .path()
+                .map(|path| path.strip_prefix(self.working_dir()).unwrap_or(path))
+                .or_else(|| current_buffer.help_doc().map(Path::new))
+                .or_else(|| current_buffer.terminal_title().map(Path::new))
+                .or_else(|| current_buffer.log_path()),
+            bom: current_buffer.has_bom(),
+            binary: current_buffer.is_binary(),
+            indent: current_buffer.indent(),
+            loading: self.loading,
+            breadcrumbs: current_buffer.breadcrumbs(),
+            blame: self.current_blame().map(ToString::to_string),
+        }
+        .draw(&mut ctx);
+
+        if let Some(kind) = self.open_list {
+            ctx.bounds = layout.list;
+
+            ListView {
+                list: self.list(kind),
+                current_dir: self.working_dir(),
+            }
+            .draw(&mut ctx);
+        }
+
+        ctx.bounds = command_line_bounds;
+
+        match self.mode {
+            Mode::Command => self.command_line.draw(&mut ctx),
+            _ => {
+                if let Some(message) = &self.message {
+                    ctx.screen.write(command_line_bounds.min, message);
+                } else if let Some(diagnostic) = current_buffer.diagnostic_at_cursor() {
+                    let message = diagnostic.message.lines().next().unwrap_or_default();
+                    let message = match &diagnostic.source {
+                        Some(source) => format!("{}: {}", source, message),
+                        None => message.to_owned(),
+                    };
+                    ctx.screen.write(command_line_bounds.min, &message);
+                }
+
+                self.draw_pending_keys(&mut ctx);
+            }
+        }
+
+        match (self.mode, &self.picker) {
+            (Mode::Command, _) => Coordinates::new(
+                u16::try_from(self.command_line.cursor_column())
+                    .expect("cursor outside screen bounds"),
+                command_line_bounds.min.y,
+            ),
+            (Mode::Picker, Some((picker, _))) => Coordinates::new(
+                u16::try_from(picker.cursor_column()).expect("cursor outside screen bounds"),
+                layout.buffer.min.y,
+            ),
+            (Mode::Diff, _) => layout.buffer.min,
+            _ => {
+                let cursor_position = current_buffer.cursor_position();
+                layout.text.min
+                    + Coordinates::new(
+                        u16::try_from(cursor_position.x).expect("cursor outside screen bounds"),
+                        u16::try_from(cursor_position.y).expect("cursor outside screen bounds"),
+                    )
+                    .to_vector()
+            }
+        }
+    }
+}
+
+/// Editing mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Insert,
+    Command,
+
+    /// Selecting text, from where the mode was entered to the cursor.
+    Visual,
+
+    /// Choosing an item from a picker.
+    Picker,
+
+    /// Moving between the hunks of a diff.
+    Diff,
+
+    /// Sending keys to the program in a terminal buffer.
+    Terminal,
+}
+
+impl Mode {
+    /// Returns the name of the mode, which `mode-changed` hooks match against.
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Command => "command",
+            Mode::Visual => "visual",
+            Mode::Picker => "picker",
+            Mode::Diff => "diff",
+            Mode::Terminal => "terminal",
+        }
+    }
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Normal
+    }
+}
+
+/// What choosing an item in a picker does.
+enum Pick {
+    /// Move the cursor to the start of a symbol in the current buffer.
+    Symbol(Vec<Position>),
+
+    /// Switch to the buffer at the chosen index. Buffers may be closed from the picker.
+    Buffer,
+
+    /// Open a recently edited file.
+    RecentFile(Vec<PathBuf>),
+
+    /// Paste the kill ring entry at the chosen index, moving it to the front of the ring.
+    Yank,
+
+    /// Replace the pattern on every line that's left, whichever is chosen. Lines are removed from
+    /// the picker to leave them alone.
+    Replace(Replacement, Vec<Hit>),
+}
+
+/// What to do with a file that the user opened, once it's shown.
+enum AfterOpen {
+    /// Nothing but showing it.
+    Show,
+
+    /// Move the cursor to a position.
+    Jump(Position),
+
+    /// Move the cursor to the definition of a tag, remembering where the jump was made from.
+    Tag {
+        name: String,
+        address: tags::Address,
+
+        /// The number of definitions that the tag has.
+        count: usize,
+
+        origin: Option<(PathBuf, Position)>,
+    },
+}
+
+/// Text that is highlighted briefly after it is yanked.
+struct Flash {
+    /// The path of the buffer that the text was yanked from.
+    path: Option<PathBuf>,
+
+    /// The version of the buffer when the text was yanked. The highlight is hidden if the buffer
+    /// is edited.
+    version: u32,
+
+    range: Range<Position>,
+
+    /// When the highlight disappears.
+    end: Instant,
+}
+
+/// Waits until a deadline, or forever if there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => future::pending().await,
+    }
+}
+
+/// Returns the text of the keys of a paste.
+///
+/// Keys are read a byte at a time, so characters up to U+00FF are bytes of UTF-8 sequences.
+/// Terminals send line breaks in pastes as carriage returns, which are read as `Return`.
+fn pasted_text(keys: &[Key]) -> String {
+    let mut bytes = vec![];
+    for &key in keys {
+        match key {
+            Key::Char(c) if u32::from(c) <= 0xff => bytes.push(u32::from(c) as u8),
+            Key::Char(c) => bytes.extend_from_slice(c.to_string().as_bytes()),
+            Key::Return | Key::Ctrl('j') => bytes.push(b'\n'),
+            Key::Ctrl('i') => bytes.push(b'\t'),
+            _ => (),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// How the current buffer is moved to a new file.
+#[derive(Debug, Copy, Clone)]
+enum FileMove {
+    /// The file is renamed on disk.
+    Rename,
+
+    /// The buffer is written to the new file, leaving the old file in place.
+    SaveAs,
+}
 
 enum ControlFlow {
     Continue,
     Break,
 }
 
+/// The areas of the screen.
+struct Layout {
+    /// The area available to buffers.
+    buffer: Bounds,
+
+    /// The line numbers on the left of the buffer area, which is empty if they are hidden.
+    gutter: Bounds,
+
+    /// The rest of the buffer area, where the text of the buffer is shown.
+    text: Bounds,
+
+    status_line: Bounds,
+
+    /// The split showing a list, which is empty if no list is open.
+    list: Bounds,
+
+    /// The command line, on the last row.
+    command_line: Bounds,
+}
+
+impl Layout {
+    /// Splits the screen from the bottom up, giving `list_height` rows to the split showing a
+    /// list, then splits the buffer area into the line numbers and the text.
+    ///
+    /// The list never takes the last row available to buffers, and the line numbers never take the
+    /// last column.
+    fn new(bounds: Bounds, list_height: u16, gutter_width: u16) -> Self {
+        let (rest, command_line) = split_last_rows(bounds, 1);
+        let list_height = cmp::min(list_height, rest.height().saturating_sub(2));
+        let (rest, list) = split_last_rows(rest, list_height);
+        let (buffer, status_line) = split_last_rows(rest, 1);
+
+        let split = cmp::min(
+            buffer.min.x + gutter_width,
+            cmp::max(buffer.min.x, buffer.max.x.saturating_sub(1)),
+        );
+        let gutter = Bounds::new(buffer.min, Coordinates::new(split, buffer.max.y));
+        let text = Bounds::new(Coordinates::new(split, buffer.min.y), buffer.max);
+
+        Layout {
+            buffer,
+            gutter,
+            text,
+            status_line,
+            list,
+            command_line,
+        }
+    }
+}
+
+/// Splits an area into the last `rows` rows and the rows above them.
+fn split_last_rows(bounds: Bounds, rows: u16) -> (Bounds, Bounds) {
+    let split = cmp::max(bounds.min.y, bounds.max.y.saturating_sub(rows));
+
+    (
+        Bounds::new(bounds.min, Coordinates::new(bounds.max.x, split)),
+        Bounds::new(Coordinates::new(bounds.min.x, split), bounds.max),
+    )
+}
+
+/// Starts logging to the file and the records given on the command line, in the environment, or in
+/// the config, in that order of precedence. Returns the file, if logging started.
+fn init_logger(options: &Options, config: LogConfig) -> Option<PathBuf> {
+    let filter = options
+        .log_level
+        .clone()
+        .or_else(|| env::var("RUST_LOG").ok())
+        .or(config.level)
+        .unwrap_or_default();
+
+    let path = match options.log_file.clone().or(config.file) {
+        Some(path) => path,
+        None => match state::state_dir() {
+            Ok(dir) => dir.join("editor.log"),
+            Err(e) => {
+                eprintln!("not logging: {}", e);
+                return None;
+            }
+        },
+    };
+
+    let max_size = config.max_size.unwrap_or(logger::DEFAULT_MAX_SIZE);
+    match Logger::init(&filter, &path, max_size) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            eprintln!("unable to open log file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
 /// Sets a panic hook that restores the terminal to its initial state and prints the panic message
 /// to standard error.
 ///
@@ -275,7 +3856,10 @@ enum ControlFlow {
 ///
 /// Normally the destructors of `Stdin` and `Terminal` handle restoring the terminal for us, but
 /// they run after this hook, so the panic message would be lost.
-fn set_panic_hook(stdin: &Stdin, term: &Terminal) {
+///
+/// The copies of the modified buffers are also saved, before the panic message is printed, so
+/// that they can be recovered on the next start.
+fn set_panic_hook(stdin: &Stdin, term: &Terminal, backups: recovery::Backups) {
     // Termios is !Send, but we need to be able to send it to the panic hook.
     let old_termios = Arc::new(Mutex::new(stdin.old_termios.clone()));
     let restore_sequence = term.restore_sequence();
@@ -294,6 +3878,26 @@ fn set_panic_hook(stdin: &Stdin, term: &Terminal) {
             let _ = termios::tcsetattr(stdin.as_raw_fd(), SetArg::TCSAFLUSH, &termios);
         }
 
+        dump_modified_buffers(&backups);
+
         eprintln!("fatal error: {}", panic_info);
     }));
 }
+
+/// Writes the copies of the modified buffers to the recovery directory after a crash, telling the
+/// user where they went.
+fn dump_modified_buffers(backups: &recovery::Backups) {
+    let result = recovery::dir()
+        .map_err(Error::from)
+        .and_then(|dir| Ok((backups.dump(&dir)?, dir)));
+
+    match result {
+        Ok((0, _)) => (),
+        Ok((written, dir)) => eprintln!(
+            "{} modified buffers were saved to {}, restore them with :recover",
+            written,
+            dir.display()
+        ),
+        Err(e) => eprintln!("unable to save modified buffers: {}", e),
+    }
+}
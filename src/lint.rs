@@ -0,0 +1,414 @@
+//! Integration with external linters.
+//!
+//! Linters are configured per language in the `linter` table of the config file. The contents of a
+//! buffer are written to the linter's standard input, and the problems that it reports are parsed
+//! into diagnostics, which are displayed like those from a language server.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use futures::channel::mpsc;
+use futures::{future, SinkExt};
+use log::*;
+use lsp_types::{Diagnostic, NumberOrString, Position, Range};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::io::{self, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::buffer::Buffer;
+use crate::config::{LintFormat, LinterConfig};
+use crate::errorformat::{self, parse_severity};
+use crate::syntax::Syntax;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to run {0}: {1}")]
+    Io(String, #[source] io::Error),
+
+    #[error("could not parse output of {0}: {1}")]
+    Json(String, #[source] serde_json::Error),
+}
+
+/// The problems found by a linter in a buffer.
+#[derive(Debug)]
+pub struct Report {
+    /// The path of the linted buffer.
+    pub path: PathBuf,
+
+    /// The version of the buffer that was linted.
+    pub version: u32,
+
+    /// The name of the linter.
+    pub source: String,
+
+    pub diagnostics: Result<Vec<Diagnostic>, Error>,
+}
+
+/// Runs the linters configured for each language.
+pub struct Linters {
+    config: HashMap<Syntax, LinterConfig>,
+
+    /// Cloneable sender for the reports of finished linters.
+    report_sender: mpsc::Sender<Report>,
+}
+
+impl Linters {
+    pub fn new(config: HashMap<Syntax, LinterConfig>, report_sender: mpsc::Sender<Report>) -> Self {
+        Linters {
+            config,
+            report_sender,
+        }
+    }
+
+    /// Replaces the configured linters. Linters that are already running are unaffected.
+    pub fn set_config(&mut self, config: HashMap<Syntax, LinterConfig>) {
+        self.config = config;
+    }
+
+    /// Lints a buffer in the background, sending a report once the linter exits.
+    ///
+    /// Returns `false` if there is no linter for the buffer's language, or if the buffer has no
+    /// path.
+    pub fn lint(&self, buffer: &Buffer) -> bool {
+        let (config, path) = match (
+            buffer.syntax.and_then(|syntax| self.config.get(&syntax)),
+            buffer.path(),
+        ) {
+            (Some(config), Some(path)) => (config.clone(), path.to_owned()),
+            _ => return false,
+        };
+
+        let text = buffer.text();
+        let version = buffer.version();
+        let mut report_sender = self.report_sender.clone();
+
+        tokio::spawn(async move {
+            let source = linter_name(&config);
+
+            info!("linting {} with {}", path.display(), source);
+
+            let diagnostics = run(&config, text, &path).await;
+
+            let report = Report {
+                path,
+                version,
+                source,
+                diagnostics,
+            };
+
+            if report_sender.send(report).await.is_err() {
+                info!("editor exited before linter finished");
+            }
+        });
+
+        true
+    }
+}
+
+/// Returns the name of a linter's program, without any leading directories.
+fn linter_name(config: &LinterConfig) -> String {
+    let (prog, _) = config.command();
+
+    Path::new(prog)
+        .file_name()
+        .map_or_else(|| prog.clone(), |name| name.to_string_lossy().into_owned())
+}
+
+/// Runs a linter over the text of the buffer at `path`, returning the problems that it found.
+///
+/// The linter is run in the buffer's directory so that it can find project-specific
+/// configuration. Problems reported in other files are ignored.
+async fn run(config: &LinterConfig, text: String, path: &Path) -> Result<Vec<Diagnostic>, Error> {
+    let source = linter_name(config);
+    let (prog, args) = config.command();
+
+    let mut command = Command::new(prog);
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn().map_err(|e| Error::Io(source.clone(), e))?;
+
+    let mut stdin = child.stdin.take().expect("stdin was not piped");
+    let write_stdin = async move {
+        // Linters that only check files on disk may exit without reading their input.
+        if let Err(e) = stdin.write_all(text.as_bytes()).await {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                warn!("unable to write to linter: {}", e);
+            }
+        }
+    };
+
+    // Linters exit unsuccessfully when they find problems, so the status is ignored.
+    let (_, output) = future::join(write_stdin, child.wait_with_output()).await;
+    let output = output.map_err(|e| Error::Io(source.clone(), e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let mut diagnostics = match config.format {
+        LintFormat::Gcc => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            parse_gcc(&stdout, path)
+                .chain(parse_gcc(&stderr, path))
+                .collect()
+        }
+        LintFormat::Json => {
+            parse_json(&stdout, path).map_err(|e| Error::Json(source.clone(), e))?
+        }
+    };
+
+    for diagnostic in &mut diagnostics {
+        diagnostic.source = Some(source.clone());
+    }
+
+    Ok(diagnostics)
+}
+
+/// Returns whether a path printed by a linter refers to the linted buffer at `path`.
+///
+/// Linters read the buffer from standard input, so they usually print a placeholder such as `-`
+/// for its path. Linters that are told the buffer's file name print it relative to their working
+/// directory, which is the buffer's directory.
+fn is_linted_path(printed: &str, path: &Path) -> bool {
+    match printed {
+        "" | "-" | "stdin" | "<stdin>" | "(stdin)" => true,
+        printed => match path.parent() {
+            Some(dir) => dir.join(printed) == path,
+            None => Path::new(printed) == path,
+        },
+    }
+}
+
+/// Parses the problems in the buffer at `path` from output in a format recognized by the
+/// `errorformat` module.
+fn parse_gcc<'a>(output: &str, path: &'a Path) -> impl Iterator<Item = Diagnostic> + 'a {
+    errorformat::parse(output)
+        .into_iter()
+        .filter(move |problem| is_linted_path(&problem.path, path))
+        .map(|problem| {
+            let start = Position::new(problem.line as u64, problem.column as u64);
+
+            Diagnostic {
+                range: Range::new(start, start),
+                severity: problem.severity,
+                message: problem.message,
+                ..Diagnostic::default()
+            }
+        })
+}
+
+/// Output in the format of `shellcheck --format=json` or `--format=json1`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonOutput {
+    Problems(Vec<JsonProblem>),
+    Comments { comments: Vec<JsonProblem> },
+}
+
+/// A problem reported in JSON. Lines and columns are one-indexed, and the end is exclusive.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonProblem {
+    file: Option<String>,
+    line: u64,
+    column: Option<u64>,
+    end_line: Option<u64>,
+    end_column: Option<u64>,
+    #[serde(alias = "severity")]
+    level: Option<String>,
+    message: String,
+    code: Option<NumberOrString>,
+}
+
+/// Parses the problems in the buffer at `path` from JSON output.
+fn parse_json(output: &str, path: &Path) -> serde_json::Result<Vec<Diagnostic>> {
+    if output.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let problems = match serde_json::from_str(output)? {
+        JsonOutput::Problems(problems) => problems,
+        JsonOutput::Comments { comments } => comments,
+    };
+
+    Ok(problems
+        .into_iter()
+        .filter(|problem| match &problem.file {
+            Some(file) => is_linted_path(file, path),
+            None => true,
+        })
+        .map(|problem| {
+            let start = Position::new(
+                problem.line.saturating_sub(1),
+                problem.column.unwrap_or(1).saturating_sub(1),
+            );
+            let end = match problem.end_line {
+                Some(end_line) => Position::new(
+                    end_line.saturating_sub(1),
+                    problem.end_column.unwrap_or(1).saturating_sub(1),
+                ),
+                None => start,
+            };
+
+            Diagnostic {
+                range: Range::new(start, end),
+                severity: problem.level.as_deref().and_then(parse_severity),
+                code: problem.code,
+                message: problem.message,
+                ..Diagnostic::default()
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+    use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+    use crate::config::LinterConfig;
+
+    use super::{is_linted_path, parse_gcc, parse_json, run};
+
+    fn diagnostic(
+        start: (u64, u64),
+        end: (u64, u64),
+        severity: Option<DiagnosticSeverity>,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            severity,
+            message: String::from(message),
+            ..Diagnostic::default()
+        }
+    }
+
+    #[test]
+    fn parse_gcc_output() {
+        let output = indoc! {"
+            main.c:3:5: error: expected ';' before '}' token
+            main.c:10: warning: unused variable 'x'
+            stdin:1:1: F401 'os' imported but unused
+                3 | }
+            include/util.h:1:1: error: unknown type name 'size_t'
+            1 error generated.
+        "};
+
+        assert_eq!(
+            parse_gcc(output, Path::new("/project/main.c")).collect::<Vec<_>>(),
+            vec![
+                diagnostic(
+                    (2, 4),
+                    (2, 4),
+                    Some(DiagnosticSeverity::Error),
+                    "expected ';' before '}' token"
+                ),
+                diagnostic(
+                    (9, 0),
+                    (9, 0),
+                    Some(DiagnosticSeverity::Warning),
+                    "unused variable 'x'"
+                ),
+                diagnostic((0, 0), (0, 0), None, "F401 'os' imported but unused"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_json_output() {
+        let output = r#"[{"file":"-","line":2,"endLine":2,"column":6,"endColumn":8,"level":"info","code":2086,"message":"Double quote to prevent globbing and word splitting."}]"#;
+
+        assert_eq!(
+            parse_json(output, Path::new("/project/build.sh")).unwrap(),
+            vec![Diagnostic {
+                code: Some(NumberOrString::Number(2086)),
+                ..diagnostic(
+                    (1, 5),
+                    (1, 7),
+                    Some(DiagnosticSeverity::Information),
+                    "Double quote to prevent globbing and word splitting."
+                )
+            }]
+        );
+
+        let output = r#"{"comments":[{"line":1,"column":1,"level":"error","message":"oops"}]}"#;
+        assert_eq!(
+            parse_json(output, Path::new("/project/build.sh")).unwrap(),
+            vec![diagnostic(
+                (0, 0),
+                (0, 0),
+                Some(DiagnosticSeverity::Error),
+                "oops"
+            )]
+        );
+
+        let output = r#"[{"file":"lib.sh","line":1,"column":1,"level":"error","message":"oops"}]"#;
+        assert!(parse_json(output, Path::new("/project/build.sh"))
+            .unwrap()
+            .is_empty());
+
+        assert!(parse_json("", Path::new("build.sh")).unwrap().is_empty());
+        assert!(parse_json("not json", Path::new("build.sh")).is_err());
+    }
+
+    #[tokio::test]
+    async fn run_linter() {
+        let config = toml::from_str::<LinterConfig>(
+            r#"command = ["sh", "-c", "grep -n TODO | sed 's/^\\([0-9]*\\):.*/-:\\1: warning: todo/'; echo 'lib.rs:1: error: elsewhere'"]"#,
+        )
+        .unwrap();
+
+        let diagnostics = run(
+            &config,
+            String::from("fn main() {\n    // TODO\n}\n"),
+            Path::new("main.rs"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                source: Some(String::from("sh")),
+                ..diagnostic((1, 0), (1, 0), Some(DiagnosticSeverity::Warning), "todo")
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_missing_linter() {
+        let config = toml::from_str::<LinterConfig>(r#"command = ["does-not-exist"]"#).unwrap();
+
+        let err = run(&config, String::new(), Path::new("main.rs"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("unable to run does-not-exist"));
+    }
+
+    #[test]
+    fn linted_path() {
+        let path = Path::new("/project/src/main.rs");
+
+        assert!(is_linted_path("-", path));
+        assert!(is_linted_path("<stdin>", path));
+        assert!(is_linted_path("main.rs", path));
+        assert!(is_linted_path("./main.rs", path));
+        assert!(is_linted_path("/project/src/main.rs", path));
+        assert!(!is_linted_path("lib.rs", path));
+        assert!(!is_linted_path("src/main.rs", path));
+
+        assert!(is_linted_path("main.rs", Path::new("main.rs")));
+    }
+}
@@ -0,0 +1,131 @@
+//! Opening files in the background.
+//!
+//! Only the first file given on the command line is opened before the editor starts, so that it
+//! can be shown as soon as possible. The rest are read concurrently, and are added to the buffer
+//! list in the order that they were given as they finish. Files that are opened later, such as
+//! with `:edit`, are shown once they've been read, and keys are handled in the meantime.
+
+use std::path::PathBuf;
+
+use futures::channel::mpsc;
+use futures::{stream, SinkExt, StreamExt};
+use log::*;
+use tokio::io;
+
+use crate::buffer::Buffer;
+
+/// The maximum number of files that are read at once.
+const CONCURRENT_LOADS: usize = 8;
+
+/// A file that has finished opening.
+pub struct Report {
+    pub path: PathBuf,
+    pub buffer: io::Result<Buffer>,
+
+    /// Whether the user opened the file to be shown, rather than giving it on the command line.
+    pub show: bool,
+}
+
+/// Opens files in the background, sending a report as each one finishes. The paths must be
+/// absolute. If `binary` is true, the files are shown as hex dumps.
+pub fn spawn(paths: Vec<PathBuf>, binary: bool, mut report_sender: mpsc::Sender<Report>) {
+    if paths.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut reports = stream::iter(paths)
+            .map(|path| async {
+                let buffer = if binary {
+                    Buffer::open_binary(path.clone()).await
+                } else {
+                    Buffer::open(path.clone()).await
+                };
+                Report {
+                    path,
+                    buffer,
+                    show: false,
+                }
+            })
+            .buffered(CONCURRENT_LOADS);
+
+        while let Some(report) = reports.next().await {
+            if report_sender.send(report).await.is_err() {
+                info!("editor exited before files finished loading");
+                return;
+            }
+        }
+    });
+}
+
+/// Opens a file that the user asked to see in the background, sending a report once it's open.
+/// The path must be absolute.
+pub fn open(path: PathBuf, mut report_sender: mpsc::Sender<Report>) {
+    tokio::spawn(async move {
+        let buffer = Buffer::open(path.clone()).await;
+        let report = Report {
+            path,
+            buffer,
+            show: true,
+        };
+
+        if report_sender.send(report).await.is_err() {
+            info!("editor exited before a file finished opening");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+
+    use super::{open, spawn};
+
+    #[tokio::test]
+    async fn load_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = (0..20)
+            .map(|i| {
+                let path = dir.path().join(format!("{}.txt", i));
+                fs::write(&path, i.to_string()).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let (tx, rx) = mpsc::channel(1);
+        spawn(paths.clone(), false, tx);
+
+        let reports = rx.collect::<Vec<_>>().await;
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| &report.path)
+                .collect::<Vec<_>>(),
+            paths.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(reports[3].buffer.as_ref().unwrap().text(), "3");
+        assert!(reports.iter().all(|report| !report.show));
+    }
+
+    #[tokio::test]
+    async fn open_to_show() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "a").unwrap();
+
+        let (tx, mut rx) = mpsc::channel(1);
+        open(path.clone(), tx.clone());
+        let report = rx.next().await.unwrap();
+        assert_eq!(report.path, path);
+        assert_eq!(report.buffer.unwrap().text(), "a");
+        assert!(report.show);
+
+        // Files that don't exist yet are opened as empty buffers.
+        open(dir.path().join("missing"), tx);
+        let report = rx.next().await.unwrap();
+        assert_eq!(report.buffer.unwrap().text(), "\n");
+    }
+}
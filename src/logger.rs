@@ -1,69 +1,216 @@
-use std::env;
-use std::ffi::OsStr;
-use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::path::Path;
-use std::sync::Mutex;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
 
 use env_logger::filter::{Builder, Filter};
+use lazy_static::lazy_static;
+use log::{LevelFilter, Log, Metadata, Record};
+use thiserror::Error;
 
-use log::{Log, Metadata, Record};
+/// The size that the log file may grow to before it is rotated, if the config doesn't say.
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
 
-/// Small `env_logger`-like logger that reads filters from an environment variable and logs to a
-/// provided file.
+lazy_static! {
+    /// The records to log, which may be changed while the editor is running.
+    static ref FILTER: RwLock<(String, Filter)> =
+        RwLock::new((String::new(), Builder::new().build()));
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("invalid log filter: {0}")]
+pub struct InvalidFilter(String);
+
+/// Small `env_logger`-like logger that logs to a provided file.
 pub struct Logger {
-    file: Mutex<File>,
-    filter: Filter,
+    file: Mutex<LogFile>,
+}
+
+/// The file that records are written to, and how much has been written to it.
+struct LogFile {
+    file: File,
+    path: PathBuf,
+    len: u64,
+
+    /// Once the file is larger than this, it's moved aside to a file with `.1` appended to its
+    /// name, replacing the previous one, and a new file is started.
+    max_size: u64,
 }
 
 impl Logger {
-    pub fn init(env_var: impl AsRef<OsStr>, path: impl AsRef<Path>) {
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path)
-            .expect("could not open log file");
-
-        let mut filter_builder = Builder::new();
-
-        if let Ok(filter) = env::var(env_var) {
-            filter_builder.parse(&filter);
+    /// Logs the records that match a filter, written like `RUST_LOG`, to a file.
+    ///
+    /// The file is truncated, and only the user may read it, since the log may contain the
+    /// contents of buffers.
+    pub fn init(filter: &str, path: &Path, max_size: u64) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
 
-        let filter = filter_builder.build();
-        let max_level = filter.filter();
+        let file = LogFile {
+            file: create(path)?,
+            path: path.to_owned(),
+            len: 0,
+            max_size,
+        };
+
+        // An invalid filter from the command line or the config logs nothing instead.
+        if set_filter(filter).is_err() {
+            set_filter("off").expect("off is a valid filter");
+        }
 
         log::set_boxed_logger(Box::new(Logger {
             file: Mutex::new(file),
-            filter,
         }))
-        .map(|()| log::set_max_level(max_level))
         .expect("could not initialize logger");
+
+        Ok(())
+    }
+}
+
+/// Changes the records that are logged, given a filter written like `RUST_LOG`.
+pub fn set_filter(spec: &str) -> Result<(), InvalidFilter> {
+    validate(spec)?;
+
+    let filter = Builder::new().parse(spec).build();
+    log::set_max_level(filter.filter());
+    *FILTER.write().expect("log filter lock poisoned") = (spec.to_owned(), filter);
+
+    Ok(())
+}
+
+/// Returns the filter of the records that are logged, as it was written.
+pub fn filter() -> String {
+    FILTER.read().expect("log filter lock poisoned").0.clone()
+}
+
+/// Checks that the levels of a filter are valid, since `env_logger` prints invalid directives to
+/// standard error, which would garble the screen.
+fn validate(spec: &str) -> Result<(), InvalidFilter> {
+    let invalid = || InvalidFilter(spec.to_owned());
+
+    let directives = spec.split('/').next().unwrap_or_default();
+    if spec.matches('/').count() > 1 {
+        return Err(invalid());
+    }
+
+    for directive in directives.split(',').map(str::trim) {
+        let mut parts = directive.split('=');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(_), None, None) => (),
+            (Some(_), Some(level), None) if level.parse::<LevelFilter>().is_ok() => (),
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates or truncates a log file that only the user may read.
+fn create(path: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+
+    // The mode only applies to new files.
+    file.set_permissions(Permissions::from_mode(0o600))?;
+
+    Ok(file)
+}
+
+impl LogFile {
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if self.len > 0 && self.len + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.len += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Moves the file aside and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+
+        self.file.flush()?;
+        fs::rename(&self.path, rotated)?;
+        self.file = create(&self.path)?;
+        self.len = 0;
+
+        Ok(())
     }
 }
 
 #[allow(clippy::unwrap_used)]
 impl Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.filter.enabled(metadata)
+        FILTER.read().unwrap().1.enabled(metadata)
     }
 
     fn log(&self, record: &Record) {
-        if self.filter.matches(record) {
-            let mut file = self.file.lock().unwrap();
-
-            let _ = writeln!(
-                file,
-                "{} {:5} {}",
+        if FILTER.read().unwrap().1.matches(record) {
+            let line = format!(
+                "{} {:5} {}\n",
                 record.level(),
                 record.target(),
                 record.args()
             );
+
+            let _ = self.file.lock().unwrap().write(&line);
         }
     }
 
     fn flush(&self) {
-        let _ = self.file.lock().unwrap().flush();
+        let _ = self.file.lock().unwrap().file.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{create, validate, InvalidFilter, LogFile};
+
+    #[test]
+    fn validate_filters() {
+        assert_eq!(validate(""), Ok(()));
+        assert_eq!(validate("info"), Ok(()));
+        assert_eq!(validate("editor::lsp=debug,warn"), Ok(()));
+        assert_eq!(validate("editor=trace/lsp"), Ok(()));
+        assert_eq!(
+            validate("editor=loud"),
+            Err(InvalidFilter(String::from("editor=loud")))
+        );
+        assert!(validate("a=b=c").is_err());
+    }
+
+    #[test]
+    fn rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("editor.log");
+
+        let mut file = LogFile {
+            file: create(&path).unwrap(),
+            path: path.clone(),
+            len: 0,
+            max_size: 10,
+        };
+
+        file.write("first\n").unwrap();
+        file.write("second\n").unwrap();
+        file.write("third\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("editor.log.1")).unwrap(),
+            "second\n"
+        );
     }
 }
@@ -3,6 +3,7 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::env;
+use std::iter;
 use std::num::Wrapping;
 use std::path::{Path, PathBuf};
 use std::process::{self, Stdio};
@@ -13,13 +14,19 @@ use futures::lock::Mutex;
 use futures::{future, SinkExt, TryStreamExt};
 use log::*;
 use lsp_types::notification::{
-    DidChangeTextDocument, DidOpenTextDocument, Initialized, Notification as LspTypesNotification,
+    DidChangeConfiguration, DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    DidSaveTextDocument, Initialized, Notification as LspTypesNotification,
 };
-use lsp_types::request::{Initialize, Request as LspTypesRequest};
+use lsp_types::request::{Completion, HoverRequest, Initialize, Request as LspTypesRequest};
 use lsp_types::{
-    ClientCapabilities, ClientInfo, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
-    InitializeParams, InitializeResult, InitializedParams, ServerInfo,
-    TextDocumentContentChangeEvent, TextDocumentItem, VersionedTextDocumentIdentifier,
+    ClientCapabilities, ClientInfo, CompletionCapability, CompletionContext,
+    CompletionItemCapability, CompletionParams, CompletionResponse, CompletionTriggerKind,
+    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, Hover, HoverCapability, HoverParams,
+    InitializeParams, InitializeResult, InitializedParams, MarkupKind, ServerInfo,
+    SynchronizationCapability, TextDocumentClientCapabilities, TextDocumentContentChangeEvent,
+    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    VersionedTextDocumentIdentifier, WorkspaceEdit,
 };
 use serde::Deserialize;
 use thiserror::Error;
@@ -31,10 +38,16 @@ use tokio_util::codec::{FramedRead, FramedWrite};
 use crate::config::LanguageServerConfig;
 use crate::syntax::Syntax;
 
+mod file_operations;
 mod protocol;
 
+use file_operations::{
+    DidRenameFiles, FileOperationsServerCapabilities, RenameFilesParams, WillRenameFiles,
+};
 use protocol::{Id, LspCodec, ResponseError};
 
+pub use file_operations::FileRename;
+
 pub use protocol::{Message, Notification, Request, Response};
 
 pub type Uri = lsp_types::Url;
@@ -86,6 +99,37 @@ impl LanguageServerBridge {
         }
     }
 
+    /// Replaces the configuration of the language servers, sending the new settings to the servers
+    /// that are running.
+    ///
+    /// Running servers keep their command until the editor is restarted.
+    pub async fn set_config(&mut self, config: HashMap<Syntax, LanguageServerConfig>) {
+        for (syntax, server) in &mut self.language_to_server {
+            let old = self.config.get(syntax);
+            let new = config.get(syntax);
+
+            if old.map(LanguageServerConfig::command) != new.map(LanguageServerConfig::command) {
+                info!(
+                    "the {:?} language server will change after a restart",
+                    syntax
+                );
+            }
+
+            let settings = new.and_then(LanguageServerConfig::settings);
+            if old.and_then(LanguageServerConfig::settings) != settings {
+                let settings = settings.cloned().unwrap_or_default();
+                if let Err(e) = server.did_change_configuration(settings).await {
+                    info!(
+                        "unable to send settings to the {:?} language server: {}",
+                        syntax, e
+                    );
+                }
+            }
+        }
+
+        self.config = config;
+    }
+
     pub fn get(&mut self, ctx: Context) -> Option<&mut LanguageServer> {
         self.language_to_server.get_mut(&ctx.syntax)
     }
@@ -98,7 +142,8 @@ impl LanguageServerBridge {
         match self.language_to_server.entry(ctx.syntax) {
             Entry::Occupied(entry) => Some(entry.into_mut()),
             Entry::Vacant(entry) => {
-                let (prog, args) = self.config.get(&ctx.syntax)?.command();
+                let config = self.config.get(&ctx.syntax)?;
+                let (prog, args) = config.command();
                 let mut command = Command::new(prog);
                 command.args(args);
 
@@ -135,6 +180,12 @@ impl LanguageServerBridge {
                 );
                 server.initialized().await.ok()?;
 
+                if let Some(settings) = config.settings() {
+                    if let Err(e) = server.did_change_configuration(settings.clone()).await {
+                        info!("unable to send settings to {}: {}", prog, e);
+                    }
+                }
+
                 Some(entry.insert(server))
             }
         }
@@ -145,6 +196,12 @@ pub struct LanguageServer {
     next_request_id: Wrapping<u64>,
     pending_responses: Arc<Mutex<HashMap<Id, oneshot::Sender<protocol::Response>>>>,
     stdin: FramedWrite<ChildStdin, LspCodec>,
+
+    /// The file operations that the server wants to be notified about.
+    file_operations: FileOperationsServerCapabilities,
+
+    /// The characters that ask the server for completions when they're typed.
+    completion_triggers: Vec<String>,
 }
 
 impl LanguageServer {
@@ -174,7 +231,8 @@ impl LanguageServer {
                 .expect("error reading stderr from server");
         });
 
-        // TODO: Should be able to remove these Arc/Mutexes, we're using the single-threaded runtime.
+        // The task that reads the server's messages may run on another worker thread than the
+        // editor, so the state that they share is behind Arc/Mutexes.
         let pending_responses = Arc::new(Mutex::new(HashMap::new()));
         let server_pending_responses = Arc::clone(&pending_responses);
         let server_message_sender = Arc::new(Mutex::new(message_sender));
@@ -224,6 +282,8 @@ impl LanguageServer {
             next_request_id: Wrapping(0),
             pending_responses,
             stdin: FramedWrite::new(stdin, LspCodec),
+            file_operations: FileOperationsServerCapabilities::default(),
+            completion_triggers: vec![],
         })
     }
 
@@ -249,7 +309,119 @@ impl LanguageServer {
         .await
     }
 
+    pub async fn did_change_configuration(&mut self, settings: serde_json::Value) -> Result<()> {
+        self.notify::<DidChangeConfiguration>(DidChangeConfigurationParams { settings })
+            .await
+    }
+
+    pub async fn did_close_text_document(
+        &mut self,
+        text_document: TextDocumentIdentifier,
+    ) -> Result<()> {
+        self.notify::<DidCloseTextDocument>(DidCloseTextDocumentParams { text_document })
+            .await
+    }
+
+    pub async fn did_save_text_document(
+        &mut self,
+        text_document: TextDocumentIdentifier,
+    ) -> Result<()> {
+        self.notify::<DidSaveTextDocument>(DidSaveTextDocumentParams { text_document })
+            .await
+    }
+
+    /// Returns whether typing a character should ask the server for completions.
+    pub fn is_completion_trigger(&self, c: char) -> bool {
+        self.completion_triggers
+            .iter()
+            .any(|trigger| trigger.chars().eq(iter::once(c)))
+    }
+
+    /// Asks the server for completions at a position. `trigger` is the character that was just
+    /// typed, if it's one of the server's trigger characters.
+    pub async fn completion(
+        &mut self,
+        text_document_position: TextDocumentPositionParams,
+        trigger: Option<char>,
+    ) -> Result<Option<CompletionResponse>> {
+        let context = match trigger {
+            Some(c) => CompletionContext {
+                trigger_kind: CompletionTriggerKind::TriggerCharacter,
+                trigger_character: Some(c.to_string()),
+            },
+            None => CompletionContext {
+                trigger_kind: CompletionTriggerKind::Invoked,
+                trigger_character: None,
+            },
+        };
+
+        self.request::<Completion>(CompletionParams {
+            text_document_position,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(context),
+        })
+        .await
+    }
+
+    /// Asks the server about the symbol at a position.
+    pub async fn hover(
+        &mut self,
+        text_document_position_params: TextDocumentPositionParams,
+    ) -> Result<Option<Hover>> {
+        self.request::<HoverRequest>(HoverParams {
+            text_document_position_params,
+            work_done_progress_params: Default::default(),
+        })
+        .await
+    }
+
+    /// Asks the server for edits to apply before files are renamed, such as updated imports.
+    ///
+    /// Only files that the server registered interest in are sent. If there are none, the server
+    /// is not asked.
+    pub async fn will_rename_files(
+        &mut self,
+        mut files: Vec<FileRename>,
+    ) -> Result<Option<WorkspaceEdit>> {
+        match &self.file_operations.will_rename {
+            Some(options) => files.retain(|file| options.matches(&file.old_uri)),
+            None => files.clear(),
+        }
+
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        self.request::<WillRenameFiles>(RenameFilesParams { files })
+            .await
+    }
+
+    /// Notifies the server that files were renamed, if it registered interest in them.
+    pub async fn did_rename_files(&mut self, mut files: Vec<FileRename>) -> Result<()> {
+        match &self.file_operations.did_rename {
+            Some(options) => files.retain(|file| options.matches(&file.old_uri)),
+            None => files.clear(),
+        }
+
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        self.notify::<DidRenameFiles>(RenameFilesParams { files })
+            .await
+    }
+
     async fn request<Req: LspTypesRequest>(&mut self, params: Req::Params) -> Result<Req::Result> {
+        let res = self.request_value::<Req>(params).await?;
+        <_>::deserialize(res).map_err(Into::into)
+    }
+
+    /// Sends a request, returning the result without deserializing it.
+    async fn request_value<Req: LspTypesRequest>(
+        &mut self,
+        params: Req::Params,
+    ) -> Result<serde_json::Value> {
         let id = self.next_request_id();
 
         let (response_tx, response_rx) = oneshot::channel();
@@ -261,8 +433,7 @@ impl LanguageServer {
         let req = Message::request::<Req>(id, params);
         self.stdin.send(req).await?;
 
-        let res = response_rx.await?.result?;
-        <_>::deserialize(res).map_err(Into::into)
+        Ok(response_rx.await?.result?)
     }
 
     async fn notify<N: LspTypesNotification>(&mut self, params: N::Params) -> Result<()> {
@@ -285,7 +456,20 @@ impl LanguageServer {
             workspace_folders: None,
         };
 
-        self.request::<Initialize>(params).await
+        // The result is deserialized by hand, since `lsp-types` doesn't know about some of the
+        // server capabilities yet.
+        let result = self.request_value::<Initialize>(params).await?;
+        self.file_operations = FileOperationsServerCapabilities::from_initialize_result(&result);
+
+        let result = InitializeResult::deserialize(result)?;
+        self.completion_triggers = result
+            .capabilities
+            .completion_provider
+            .as_ref()
+            .and_then(|options| options.trigger_characters.clone())
+            .unwrap_or_default();
+
+        Ok(result)
     }
 
     async fn initialized(&mut self) -> Result<()> {
@@ -320,7 +504,28 @@ fn client_info() -> ClientInfo {
 }
 
 fn client_capabilities() -> ClientCapabilities {
-    ClientCapabilities::default()
+    ClientCapabilities {
+        text_document: Some(TextDocumentClientCapabilities {
+            synchronization: Some(SynchronizationCapability {
+                did_save: Some(true),
+                ..SynchronizationCapability::default()
+            }),
+            completion: Some(CompletionCapability {
+                completion_item: Some(CompletionItemCapability {
+                    snippet_support: Some(true),
+                    ..CompletionItemCapability::default()
+                }),
+                context_support: Some(true),
+                ..CompletionCapability::default()
+            }),
+            hover: Some(HoverCapability {
+                content_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+                ..HoverCapability::default()
+            }),
+            ..TextDocumentClientCapabilities::default()
+        }),
+        ..ClientCapabilities::default()
+    }
 }
 
 #[cfg(test)]
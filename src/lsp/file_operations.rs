@@ -0,0 +1,229 @@
+//! File operation requests and notifications introduced in LSP 3.16.
+//!
+//! These are not yet available in `lsp-types`, so they are defined here.
+
+use glob::{MatchOptions, Pattern};
+use lsp_types::notification::Notification;
+use lsp_types::request::Request;
+use lsp_types::WorkspaceEdit;
+use serde::{Deserialize, Serialize};
+
+use super::Uri;
+
+/// The `workspace/willRenameFiles` request, sent before files are renamed by the client.
+#[derive(Debug)]
+pub enum WillRenameFiles {}
+
+impl Request for WillRenameFiles {
+    type Params = RenameFilesParams;
+    type Result = Option<WorkspaceEdit>;
+    const METHOD: &'static str = "workspace/willRenameFiles";
+}
+
+/// The `workspace/didRenameFiles` notification, sent after files were renamed by the client.
+#[derive(Debug)]
+pub enum DidRenameFiles {}
+
+impl Notification for DidRenameFiles {
+    type Params = RenameFilesParams;
+    const METHOD: &'static str = "workspace/didRenameFiles";
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct RenameFilesParams {
+    /// An array of all files/folders renamed in this operation.
+    pub files: Vec<FileRename>,
+}
+
+/// Represents information on a file/folder rename.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRename {
+    pub old_uri: Uri,
+    pub new_uri: Uri,
+}
+
+/// The file operations that a server wants to be notified about, advertised in the
+/// `workspace.fileOperations` server capability.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileOperationsServerCapabilities {
+    pub will_rename: Option<FileOperationRegistrationOptions>,
+    pub did_rename: Option<FileOperationRegistrationOptions>,
+}
+
+impl FileOperationsServerCapabilities {
+    /// Reads the capability from the result of an `initialize` request. Servers that don't
+    /// advertise the capability aren't interested in any file operations.
+    pub fn from_initialize_result(result: &serde_json::Value) -> Self {
+        result
+            .pointer("/capabilities/workspace/fileOperations")
+            .and_then(|capability| Self::deserialize(capability).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The files that a server is interested in for a file operation.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileOperationRegistrationOptions {
+    filters: Vec<FileOperationFilter>,
+}
+
+impl FileOperationRegistrationOptions {
+    /// Returns whether the server is interested in an operation on the file at a URI.
+    pub fn matches(&self, uri: &Uri) -> bool {
+        self.filters.iter().any(|filter| filter.matches(uri))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FileOperationFilter {
+    scheme: Option<String>,
+    pattern: FileOperationPattern,
+}
+
+impl FileOperationFilter {
+    fn matches(&self, uri: &Uri) -> bool {
+        if matches!(&self.scheme, Some(scheme) if scheme != uri.scheme()) {
+            return false;
+        }
+
+        // Only files are renamed, so filters that are limited to folders never match.
+        if let Some(FileOperationPatternKind::Folder) = self.pattern.matches {
+            return false;
+        }
+
+        let options = MatchOptions {
+            case_sensitive: !self.pattern.options.ignore_case,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        expand_braces(&self.pattern.glob)
+            .iter()
+            .filter_map(|glob| Pattern::new(glob).ok())
+            .any(|pattern| pattern.matches_with(uri.path(), options))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FileOperationPattern {
+    glob: String,
+    matches: Option<FileOperationPatternKind>,
+    #[serde(default)]
+    options: FileOperationPatternOptions,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FileOperationPatternKind {
+    File,
+    Folder,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileOperationPatternOptions {
+    #[serde(default)]
+    ignore_case: bool,
+}
+
+/// Expands the `{a,b}` alternatives of an LSP glob pattern, which the `glob` crate doesn't
+/// support, into separate patterns.
+fn expand_braces(glob: &str) -> Vec<String> {
+    let (start, end) = match (glob.find('{'), glob.find('}')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return vec![glob.to_owned()],
+    };
+
+    glob[start + 1..end]
+        .split(',')
+        .flat_map(|alternative| {
+            expand_braces(&format!(
+                "{}{}{}",
+                &glob[..start],
+                alternative,
+                &glob[end + 1..]
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use serde_json::json;
+
+    use crate::lsp::Uri;
+
+    use super::{expand_braces, FileOperationsServerCapabilities, FileRename, RenameFilesParams};
+
+    #[test]
+    fn serialize_rename_files_params() -> Result<(), Box<dyn Error>> {
+        let params = RenameFilesParams {
+            files: vec![FileRename {
+                old_uri: Uri::parse("file:///foo.rs")?,
+                new_uri: Uri::parse("file:///bar.rs")?,
+            }],
+        };
+
+        assert_eq!(
+            serde_json::to_value(params)?,
+            json!({
+                "files": [{
+                    "oldUri": "file:///foo.rs",
+                    "newUri": "file:///bar.rs",
+                }]
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn expand_glob_braces() {
+        assert_eq!(expand_braces("**/*.rs"), vec!["**/*.rs"]);
+        assert_eq!(expand_braces("**/*.{ts,tsx}"), vec!["**/*.ts", "**/*.tsx"]);
+    }
+
+    #[test]
+    fn file_operations_capability() -> Result<(), Box<dyn Error>> {
+        let result = json!({
+            "capabilities": {
+                "workspace": {
+                    "fileOperations": {
+                        "willRename": {
+                            "filters": [
+                                {
+                                    "scheme": "file",
+                                    "pattern": { "glob": "**/*.{rs,toml}", "matches": "file" }
+                                },
+                                {
+                                    "pattern": { "glob": "**/SRC", "options": { "ignoreCase": true } }
+                                }
+                            ]
+                        }
+                    }
+                }
+            }
+        });
+
+        let capabilities = FileOperationsServerCapabilities::from_initialize_result(&result);
+        assert!(capabilities.did_rename.is_none());
+
+        let will_rename = capabilities.will_rename.unwrap();
+        assert!(will_rename.matches(&Uri::parse("file:///project/src/lib.rs")?));
+        assert!(will_rename.matches(&Uri::parse("file:///project/Cargo.toml")?));
+        assert!(will_rename.matches(&Uri::parse("file:///project/src")?));
+        assert!(!will_rename.matches(&Uri::parse("file:///project/README.md")?));
+        assert!(!will_rename.matches(&Uri::parse("untitled:///project/main.rs")?));
+
+        let none = FileOperationsServerCapabilities::from_initialize_result(&json!({
+            "capabilities": {}
+        }));
+        assert!(none.will_rename.is_none());
+
+        Ok(())
+    }
+}
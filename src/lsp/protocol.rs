@@ -6,7 +6,7 @@ use atoi::atoi;
 use bytes::{Buf, BufMut, BytesMut};
 use httparse::{Status, EMPTY_HEADER};
 use log::*;
-use serde::de::{self, Unexpected, Visitor};
+use serde::de::{self, DeserializeOwned, Unexpected, Visitor};
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
@@ -281,6 +281,17 @@ pub struct Notification {
     pub params: Option<Value>,
 }
 
+impl Notification {
+    /// Deserializes the parameters of the notification as the parameters of a known method.
+    pub fn params<N>(&self) -> Result<N::Params, serde_json::Error>
+    where
+        N: lsp_types::notification::Notification,
+        <N as lsp_types::notification::Notification>::Params: DeserializeOwned,
+    {
+        serde_json::from_value(self.params.clone().unwrap_or(Value::Null))
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum LspError {
     #[error("I/O error: {0}")]
@@ -391,6 +402,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn notification_params() -> Result<(), Box<dyn Error>> {
+        let notification = Notification {
+            method: String::from("window/logMessage"),
+            params: Some(json!({ "type": 4, "message": "indexing" })),
+        };
+
+        let params = notification.params::<lsp_notification!("window/logMessage")>()?;
+        assert_eq!(params.typ, MessageType::Log);
+        assert_eq!(params.message, "indexing");
+
+        assert!(notification
+            .params::<lsp_notification!("textDocument/publishDiagnostics")>()
+            .is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn serialize_notification() -> Result<(), Box<dyn Error>> {
         let notification =
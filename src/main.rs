@@ -6,11 +6,15 @@ use editor::Options;
 use structopt::StructOpt;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    editor::Logger::init("RUST_LOG", "/tmp/editor.log");
-
     let options = Options::from_args();
 
-    let runtime = Builder::new_current_thread().enable_io().build()?;
+    // The editor runs on the main thread, while the tasks that it spawns to read files, search,
+    // and run git are scheduled on worker threads, so that they can't delay input.
+    let runtime = Builder::new_multi_thread()
+        .thread_name("editor-worker")
+        .enable_io()
+        .enable_time()
+        .build()?;
     runtime.block_on(editor::run(options))?;
 
     Ok(())
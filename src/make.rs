@@ -0,0 +1,154 @@
+//! Running builds with `:make`.
+//!
+//! The build command is run asynchronously in the editor's working directory, so the UI stays
+//! responsive while it runs. Once it exits, the locations in its output are collected into the
+//! quickfix list.
+
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+use log::*;
+use tokio::io;
+use tokio::process::Command;
+
+use crate::buffer::Position;
+use crate::errorformat::{self, Problem};
+use crate::quickfix::Entry;
+
+/// The outcome of a build.
+#[derive(Debug)]
+pub struct Report {
+    /// The command line that was run, for display.
+    pub command: String,
+
+    /// The directory that the build was run in.
+    pub dir: PathBuf,
+
+    pub status: io::Result<ExitStatus>,
+
+    /// The problems reported by the build, with paths relative to its working directory.
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    /// Converts the reported problems into quickfix entries.
+    ///
+    /// Relative paths are resolved against the directory that the build was run in.
+    pub fn to_entries(&self) -> Vec<Entry> {
+        self.problems
+            .iter()
+            .map(|problem| Entry {
+                path: self.dir.join(&problem.path),
+                position: Position::new(problem.column, problem.line),
+                severity: problem.severity,
+                message: problem.message.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Runs a build command in the background, sending a report once it exits.
+pub fn spawn(
+    prog: String,
+    args: Vec<String>,
+    dir: PathBuf,
+    mut report_sender: mpsc::Sender<Report>,
+) {
+    tokio::spawn(async move {
+        let report = run(prog, args, dir).await;
+
+        if report_sender.send(report).await.is_err() {
+            info!("editor exited before build finished");
+        }
+    });
+}
+
+/// Runs a build command in a directory, parsing its output once it exits.
+async fn run(prog: String, args: Vec<String>, dir: PathBuf) -> Report {
+    let command_line = std::iter::once(&prog)
+        .chain(&args)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let output = Command::new(&prog)
+        .args(&args)
+        .current_dir(&dir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await;
+
+    match output {
+        Ok(output) => {
+            // Compilers usually report problems on standard error.
+            let mut problems = errorformat::parse(&String::from_utf8_lossy(&output.stderr));
+            problems.extend(errorformat::parse(&String::from_utf8_lossy(&output.stdout)));
+
+            Report {
+                command: command_line,
+                dir,
+                status: Ok(output.status),
+                problems,
+            }
+        }
+        Err(e) => Report {
+            command: command_line,
+            dir,
+            status: Err(e),
+            problems: vec![],
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    use lsp_types::DiagnosticSeverity;
+
+    use crate::buffer::Position;
+    use crate::quickfix::Entry;
+
+    use super::run;
+
+    #[tokio::test]
+    async fn run_build() {
+        let report = run(
+            String::from("sh"),
+            vec![
+                String::from("-c"),
+                String::from("echo 'src/main.c:3:5: error: oops' >&2; exit 2"),
+            ],
+            PathBuf::from("/"),
+        )
+        .await;
+
+        assert_eq!(report.status.as_ref().unwrap().code(), Some(2));
+        assert_eq!(
+            report.to_entries(),
+            vec![Entry {
+                path: Path::new("/src/main.c").to_owned(),
+                position: Position::new(4, 2),
+                severity: Some(DiagnosticSeverity::Error),
+                message: String::from("oops"),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_missing_program() {
+        let report = run(
+            String::from("does-not-exist"),
+            vec![],
+            env::current_dir().unwrap(),
+        )
+        .await;
+
+        assert!(report.status.is_err());
+        assert!(report.problems.is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+//! Opening URLs with the platform's default application, for `gx`.
+
+use std::process::Stdio;
+
+use log::*;
+use tokio::io;
+use tokio::process::Command;
+
+/// The program that opens URLs with the default application.
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+
+/// The program that opens URLs with the default application.
+#[cfg(not(target_os = "macos"))]
+const OPENER: &str = "xdg-open";
+
+/// Opens a URL in the background.
+///
+/// The opener is detached from the terminal: its standard streams are closed so that it can't
+/// draw over the editor, and it runs in a new session so that it has no controlling terminal.
+pub fn open(url: &str) -> io::Result<()> {
+    let mut command = Command::new(OPENER);
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // SAFETY: `setsid` is async-signal-safe, and creating an error from errno doesn't allocate.
+    unsafe {
+        command.pre_exec(|| match nix::unistd::setsid() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::last_os_error()),
+        });
+    }
+
+    let mut child = command.spawn()?;
+
+    // Wait for the opener in the background, so that it doesn't linger as a zombie process.
+    let url = url.to_owned();
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) if status.success() => (),
+            Ok(status) => warn!("{} {} exited with {}", OPENER, url, status),
+            Err(e) => warn!("unable to wait for {}: {}", OPENER, e),
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,723 @@
+//! Options that change how the editor behaves, like Vim's.
+//!
+//! Options are set in the `[options]` section of the config, such as `scrolloff = 3`, and changed
+//! at runtime with `:set`. Each option has a scope:
+//!
+//! - Global options apply everywhere, and are stored by the editor.
+//! - Window-local options apply to the window. There is only one window, so they are stored with
+//!   the buffer that it shows and move to the next buffer along with the viewport.
+//! - Buffer-local options apply to a single buffer, and are stored with it.
+//!
+//! Buffer-local options may be overridden for buffers of a language in a `[language.<name>]`
+//! section of the config, such as `[language.python]`. The overrides are applied whenever a
+//! buffer's syntax is identified, like a filetype plugin in Vim.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use log::*;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::buffer::{Buffer, FileFormat};
+use crate::syntax::Syntax;
+
+lazy_static! {
+    /// The initial values of buffer-local options, and the overrides for particular languages.
+    static ref BUFFER_DEFAULTS: RwLock<(BufferOptions, HashMap<Syntax, LanguageOptions>)> =
+        RwLock::new(Default::default());
+}
+
+/// The initial values of options.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    #[serde(flatten)]
+    pub global: GlobalOptions,
+
+    #[serde(flatten)]
+    pub window: WindowOptions,
+
+    #[serde(flatten)]
+    pub buffer: BufferOptions,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct GlobalOptions {
+    /// How long to wait for the rest of a key chord, in milliseconds.
+    pub timeoutlen: usize,
+
+    /// How long the cursor must be held still before the `cursor-hold` event, in milliseconds.
+    pub updatetime: usize,
+
+    /// Where the cursor may be moved beyond the text.
+    pub virtualedit: VirtualEdit,
+
+    /// Ignore case when searching.
+    pub ignorecase: bool,
+
+    /// If `ignorecase` is set, don't ignore case when the pattern contains an uppercase letter.
+    pub smartcase: bool,
+
+    /// Continue searching from the other end of the buffer when a search reaches the end.
+    pub wrapscan: bool,
+
+    /// Move `j` and `k` by display lines, like `gj` and `gk`, when lines are wrapped.
+    pub wrapmotion: bool,
+}
+
+impl GlobalOptions {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeoutlen as u64)
+    }
+
+    pub fn update_time(&self) -> Duration {
+        Duration::from_millis(self.updatetime as u64)
+    }
+
+    /// Returns whether a search for the pattern should ignore case.
+    pub fn ignores_case(&self, pattern: &str) -> bool {
+        self.ignorecase && !(self.smartcase && pattern.chars().any(char::is_uppercase))
+    }
+}
+
+impl Default for GlobalOptions {
+    fn default() -> Self {
+        GlobalOptions {
+            timeoutlen: 1000,
+            updatetime: 4000,
+            virtualedit: VirtualEdit::None,
+            ignorecase: false,
+            smartcase: false,
+            wrapscan: true,
+            wrapmotion: false,
+        }
+    }
+}
+
+/// Where the cursor may be moved beyond the text, in addition to after the end of the line in
+/// insert mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum VirtualEdit {
+    /// Nowhere: in normal mode, the cursor stays on the last character of the line.
+    #[serde(rename = "none")]
+    None,
+
+    /// After the end of the line, in any mode.
+    #[serde(rename = "onemore")]
+    OneMore,
+}
+
+impl Display for VirtualEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VirtualEdit::None => "none",
+            VirtualEdit::OneMore => "onemore",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("unknown virtualedit value: {0}")]
+pub struct UnknownVirtualEdit(String);
+
+impl FromStr for VirtualEdit {
+    type Err = UnknownVirtualEdit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "none" => Ok(VirtualEdit::None),
+            "onemore" => Ok(VirtualEdit::OneMore),
+            _ => Err(UnknownVirtualEdit(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct WindowOptions {
+    /// Show the number of each line.
+    pub number: bool,
+
+    /// Show the distance from the cursor's line to each line. If `number` is also set, the cursor's
+    /// line shows its own number.
+    pub relativenumber: bool,
+
+    /// The number of lines to keep visible above and below the cursor.
+    pub scrolloff: usize,
+
+    /// The columns to shade, such as at the maximum line length.
+    pub colorcolumn: ColorColumns,
+
+    /// Shade the line that the cursor is on.
+    pub cursorline: bool,
+
+    /// Shade the column that the cursor is in.
+    pub cursorcolumn: bool,
+
+    /// Wrap lines that are wider than the window onto the next screen lines, instead of scrolling
+    /// horizontally.
+    pub wrap: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        WindowOptions {
+            number: false,
+            relativenumber: false,
+            scrolloff: 5,
+            colorcolumn: ColorColumns::default(),
+            cursorline: false,
+            cursorcolumn: false,
+            wrap: false,
+        }
+    }
+}
+
+/// The most columns that `colorcolumn` may shade.
+const MAX_COLOR_COLUMNS: usize = 8;
+
+/// Columns to shade, numbered from 1 and written like `80,100`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ColorColumns {
+    columns: [usize; MAX_COLOR_COLUMNS],
+    len: usize,
+}
+
+impl ColorColumns {
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.columns[..self.len].iter().copied()
+    }
+}
+
+impl Display for ColorColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, column) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", column)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("invalid colorcolumn value: {0}")]
+pub struct InvalidColorColumns(String);
+
+impl FromStr for ColorColumns {
+    type Err = InvalidColorColumns;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidColorColumns(s.to_owned());
+
+        let mut columns = ColorColumns::default();
+        if s.is_empty() {
+            return Ok(columns);
+        }
+
+        for column in s.split(',') {
+            let column = match column.trim().parse() {
+                Ok(0) | Err(_) => return Err(invalid()),
+                Ok(column) => column,
+            };
+
+            if columns.len == MAX_COLOR_COLUMNS {
+                return Err(invalid());
+            }
+
+            columns.columns[columns.len] = column;
+            columns.len += 1;
+        }
+
+        Ok(columns)
+    }
+}
+
+impl TryFrom<String> for ColorColumns {
+    type Error = InvalidColorColumns;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct BufferOptions {
+    /// The number of columns between tab stops.
+    pub tabstop: usize,
+
+    /// Insert spaces up to the next tab stop, instead of a tab, when Tab is pressed.
+    pub expandtab: bool,
+}
+
+impl Default for BufferOptions {
+    fn default() -> Self {
+        BufferOptions {
+            tabstop: 8,
+            expandtab: false,
+        }
+    }
+}
+
+/// Buffer-local options that override the defaults for buffers of a language. Unset options are
+/// left alone.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub struct LanguageOptions {
+    tabstop: Option<usize>,
+    expandtab: Option<bool>,
+}
+
+impl LanguageOptions {
+    /// Overrides the options that are set.
+    pub fn apply(&self, options: &mut BufferOptions) {
+        if let Some(tabstop) = self.tabstop {
+            options.tabstop = tabstop;
+        }
+
+        if let Some(expandtab) = self.expandtab {
+            options.expandtab = expandtab;
+        }
+    }
+}
+
+/// Sets the initial values of buffer-local options, and the options that override them for buffers
+/// of particular languages.
+pub fn set_buffer_defaults(defaults: BufferOptions, languages: HashMap<Syntax, LanguageOptions>) {
+    *BUFFER_DEFAULTS
+        .write()
+        .expect("buffer defaults lock poisoned") = (defaults, languages);
+}
+
+/// Returns the initial values of the buffer-local options of a buffer of the given language.
+pub fn buffer_defaults(syntax: Option<Syntax>) -> BufferOptions {
+    let mut options = BUFFER_DEFAULTS
+        .read()
+        .expect("buffer defaults lock poisoned")
+        .0;
+    apply_language(syntax, &mut options);
+    options
+}
+
+/// Overrides buffer-local options with the options set for the given language.
+pub fn apply_language(syntax: Option<Syntax>, options: &mut BufferOptions) {
+    let defaults = BUFFER_DEFAULTS
+        .read()
+        .expect("buffer defaults lock poisoned");
+
+    if let Some(language) = syntax.and_then(|syntax| defaults.1.get(&syntax)) {
+        language.apply(options);
+    }
+}
+
+/// An option that can be changed with `:set`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OptionName {
+    TimeoutLen,
+    UpdateTime,
+    VirtualEdit,
+    IgnoreCase,
+    SmartCase,
+    WrapScan,
+    WrapMotion,
+    Number,
+    RelativeNumber,
+    ScrollOff,
+    ColorColumn,
+    CursorLine,
+    CursorColumn,
+    Wrap,
+    FileFormat,
+    Bomb,
+    TabStop,
+    ExpandTab,
+}
+
+impl OptionName {
+    pub const ALL: &'static [OptionName] = &[
+        OptionName::TimeoutLen,
+        OptionName::UpdateTime,
+        OptionName::VirtualEdit,
+        OptionName::IgnoreCase,
+        OptionName::SmartCase,
+        OptionName::WrapScan,
+        OptionName::WrapMotion,
+        OptionName::Number,
+        OptionName::RelativeNumber,
+        OptionName::ScrollOff,
+        OptionName::ColorColumn,
+        OptionName::CursorLine,
+        OptionName::CursorColumn,
+        OptionName::Wrap,
+        OptionName::FileFormat,
+        OptionName::Bomb,
+        OptionName::TabStop,
+        OptionName::ExpandTab,
+    ];
+
+    /// Looks up an option by its full or abbreviated name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "timeoutlen" | "tm" => OptionName::TimeoutLen,
+            "updatetime" | "ut" => OptionName::UpdateTime,
+            "virtualedit" | "ve" => OptionName::VirtualEdit,
+            "ignorecase" | "ic" => OptionName::IgnoreCase,
+            "smartcase" | "scs" => OptionName::SmartCase,
+            "wrapscan" | "ws" => OptionName::WrapScan,
+            "wrapmotion" => OptionName::WrapMotion,
+            "number" | "nu" => OptionName::Number,
+            "relativenumber" | "rnu" => OptionName::RelativeNumber,
+            "scrolloff" | "so" => OptionName::ScrollOff,
+            "colorcolumn" | "cc" => OptionName::ColorColumn,
+            "cursorline" | "cul" => OptionName::CursorLine,
+            "cursorcolumn" | "cuc" => OptionName::CursorColumn,
+            "wrap" => OptionName::Wrap,
+            "fileformat" | "ff" => OptionName::FileFormat,
+            "bomb" => OptionName::Bomb,
+            "tabstop" | "ts" => OptionName::TabStop,
+            "expandtab" | "et" => OptionName::ExpandTab,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OptionName::TimeoutLen => "timeoutlen",
+            OptionName::UpdateTime => "updatetime",
+            OptionName::VirtualEdit => "virtualedit",
+            OptionName::IgnoreCase => "ignorecase",
+            OptionName::SmartCase => "smartcase",
+            OptionName::WrapScan => "wrapscan",
+            OptionName::WrapMotion => "wrapmotion",
+            OptionName::Number => "number",
+            OptionName::RelativeNumber => "relativenumber",
+            OptionName::ScrollOff => "scrolloff",
+            OptionName::ColorColumn => "colorcolumn",
+            OptionName::CursorLine => "cursorline",
+            OptionName::CursorColumn => "cursorcolumn",
+            OptionName::Wrap => "wrap",
+            OptionName::FileFormat => "fileformat",
+            OptionName::Bomb => "bomb",
+            OptionName::TabStop => "tabstop",
+            OptionName::ExpandTab => "expandtab",
+        }
+    }
+
+    /// Returns whether the option is turned on and off, rather than set to a value.
+    pub fn is_bool(self) -> bool {
+        match self {
+            OptionName::IgnoreCase
+            | OptionName::SmartCase
+            | OptionName::WrapScan
+            | OptionName::WrapMotion
+            | OptionName::Number
+            | OptionName::RelativeNumber
+            | OptionName::CursorLine
+            | OptionName::CursorColumn
+            | OptionName::Wrap
+            | OptionName::Bomb
+            | OptionName::ExpandTab => true,
+            OptionName::TimeoutLen
+            | OptionName::UpdateTime
+            | OptionName::VirtualEdit
+            | OptionName::ScrollOff
+            | OptionName::ColorColumn
+            | OptionName::FileFormat
+            | OptionName::TabStop => false,
+        }
+    }
+
+    /// Parses a value of the option that isn't turned on and off.
+    pub fn parse_value(self, value: &str) -> Option<Value> {
+        match self {
+            OptionName::TimeoutLen | OptionName::UpdateTime | OptionName::ScrollOff => {
+                value.parse().ok().map(Value::Number)
+            }
+            OptionName::TabStop => match value.parse() {
+                Ok(0) | Err(_) => None,
+                Ok(n) => Some(Value::Number(n)),
+            },
+            OptionName::VirtualEdit => value.parse().ok().map(Value::VirtualEdit),
+            OptionName::FileFormat => value.parse().ok().map(Value::FileFormat),
+            OptionName::ColorColumn => value.parse().ok().map(Value::ColorColumns),
+            OptionName::IgnoreCase
+            | OptionName::SmartCase
+            | OptionName::WrapScan
+            | OptionName::WrapMotion
+            | OptionName::Number
+            | OptionName::RelativeNumber
+            | OptionName::CursorLine
+            | OptionName::CursorColumn
+            | OptionName::Wrap
+            | OptionName::Bomb
+            | OptionName::ExpandTab => None,
+        }
+    }
+
+    /// Returns the value of the option.
+    pub fn get(self, global: &GlobalOptions, buffer: &Buffer) -> Value {
+        let window = buffer.window_options();
+        let local = buffer.buffer_options();
+
+        match self {
+            OptionName::TimeoutLen => Value::Number(global.timeoutlen),
+            OptionName::UpdateTime => Value::Number(global.updatetime),
+            OptionName::VirtualEdit => Value::VirtualEdit(global.virtualedit),
+            OptionName::IgnoreCase => Value::Bool(global.ignorecase),
+            OptionName::SmartCase => Value::Bool(global.smartcase),
+            OptionName::WrapScan => Value::Bool(global.wrapscan),
+            OptionName::WrapMotion => Value::Bool(global.wrapmotion),
+            OptionName::Number => Value::Bool(window.number),
+            OptionName::RelativeNumber => Value::Bool(window.relativenumber),
+            OptionName::ScrollOff => Value::Number(window.scrolloff),
+            OptionName::ColorColumn => Value::ColorColumns(window.colorcolumn),
+            OptionName::CursorLine => Value::Bool(window.cursorline),
+            OptionName::CursorColumn => Value::Bool(window.cursorcolumn),
+            OptionName::Wrap => Value::Bool(window.wrap),
+            OptionName::FileFormat => Value::FileFormat(buffer.file_format()),
+            OptionName::Bomb => Value::Bool(buffer.has_bom()),
+            OptionName::TabStop => Value::Number(local.tabstop),
+            OptionName::ExpandTab => Value::Bool(local.expandtab),
+        }
+    }
+
+    /// Changes the value of the option. Values of the wrong type are ignored.
+    pub fn set(self, value: Value, global: &mut GlobalOptions, buffer: &mut Buffer) {
+        let mut window = buffer.window_options();
+        let mut local = buffer.buffer_options();
+
+        match (self, value) {
+            (OptionName::TimeoutLen, Value::Number(n)) => global.timeoutlen = n,
+            (OptionName::UpdateTime, Value::Number(n)) => global.updatetime = n,
+            (OptionName::VirtualEdit, Value::VirtualEdit(virtualedit)) => {
+                global.virtualedit = virtualedit
+            }
+            (OptionName::IgnoreCase, Value::Bool(b)) => global.ignorecase = b,
+            (OptionName::SmartCase, Value::Bool(b)) => global.smartcase = b,
+            (OptionName::WrapScan, Value::Bool(b)) => global.wrapscan = b,
+            (OptionName::WrapMotion, Value::Bool(b)) => global.wrapmotion = b,
+            (OptionName::Number, Value::Bool(b)) => window.number = b,
+            (OptionName::RelativeNumber, Value::Bool(b)) => window.relativenumber = b,
+            (OptionName::ScrollOff, Value::Number(n)) => window.scrolloff = n,
+            (OptionName::ColorColumn, Value::ColorColumns(columns)) => window.colorcolumn = columns,
+            (OptionName::CursorLine, Value::Bool(b)) => window.cursorline = b,
+            (OptionName::CursorColumn, Value::Bool(b)) => window.cursorcolumn = b,
+            (OptionName::Wrap, Value::Bool(b)) => window.wrap = b,
+            (OptionName::FileFormat, Value::FileFormat(file_format)) => {
+                buffer.set_file_format(file_format)
+            }
+            (OptionName::Bomb, Value::Bool(b)) => buffer.set_bom(b),
+            (OptionName::TabStop, Value::Number(n)) => local.tabstop = n,
+            (OptionName::ExpandTab, Value::Bool(b)) => local.expandtab = b,
+            (option, value) => warn!("invalid value for {}: {:?}", option.name(), value),
+        }
+
+        buffer.set_window_options(window);
+        buffer.set_buffer_options(local);
+    }
+}
+
+/// The value of an option.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Number(usize),
+    FileFormat(FileFormat),
+    VirtualEdit(VirtualEdit),
+    ColorColumns(ColorColumns),
+}
+
+/// A change made to an option with `:set`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Setting {
+    /// Show the value of the option.
+    Show(OptionName),
+
+    Set(OptionName, Value),
+
+    /// Turn the option on if it's off, or off if it's on.
+    Toggle(OptionName),
+}
+
+impl Setting {
+    /// Applies the setting, returning the option and its value if it should be shown to the user.
+    pub fn apply(self, global: &mut GlobalOptions, buffer: &mut Buffer) -> Option<Shown> {
+        match self {
+            Setting::Show(option) => {
+                return Some(Shown {
+                    option,
+                    value: option.get(global, buffer),
+                })
+            }
+            Setting::Set(option, value) => option.set(value, global, buffer),
+            Setting::Toggle(option) => {
+                if let Value::Bool(b) = option.get(global, buffer) {
+                    option.set(Value::Bool(!b), global, buffer);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// An option and its value, displayed like `number`, `nonumber`, or `scrolloff=5`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Shown {
+    option: OptionName,
+    value: Value,
+}
+
+impl Display for Shown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.option.name();
+
+        match self.value {
+            Value::Bool(true) => write!(f, "{}", name),
+            Value::Bool(false) => write!(f, "no{}", name),
+            Value::Number(n) => write!(f, "{}={}", name, n),
+            Value::FileFormat(file_format) => write!(f, "{}={}", name, file_format),
+            Value::VirtualEdit(virtualedit) => write!(f, "{}={}", name, virtualedit),
+            Value::ColorColumns(columns) => write!(f, "{}={}", name, columns),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, FileFormat};
+
+    use super::{
+        BufferOptions, ColorColumns, GlobalOptions, LanguageOptions, OptionName, Options, Setting,
+        Value, VirtualEdit,
+    };
+
+    #[test]
+    fn deserialize() {
+        let options =
+            toml::from_str::<Options>("number = true\ntimeoutlen = 500\nexpandtab = true").unwrap();
+        assert_eq!(options.global.virtualedit, VirtualEdit::None);
+        assert!(options.window.number);
+        assert!(!options.window.relativenumber);
+        assert_eq!(options.window.scrolloff, 5);
+        assert_eq!(options.global.timeoutlen, 500);
+        assert!(options.buffer.expandtab);
+        assert_eq!(options.buffer.tabstop, 8);
+    }
+
+    #[test]
+    fn parse_virtualedit() {
+        assert_eq!(
+            OptionName::VirtualEdit.parse_value("onemore"),
+            Some(Value::VirtualEdit(VirtualEdit::OneMore))
+        );
+        assert_eq!(
+            OptionName::VirtualEdit.parse_value(""),
+            Some(Value::VirtualEdit(VirtualEdit::None))
+        );
+        assert_eq!(OptionName::VirtualEdit.parse_value("all"), None);
+
+        let options = toml::from_str::<Options>("virtualedit = \"onemore\"").unwrap();
+        assert_eq!(options.global.virtualedit, VirtualEdit::OneMore);
+    }
+
+    #[test]
+    fn parse_colorcolumn() {
+        let columns = "80,100".parse::<ColorColumns>().unwrap();
+        assert_eq!(columns.iter().collect::<Vec<_>>(), vec![80, 100]);
+        assert_eq!(columns.to_string(), "80,100");
+
+        assert_eq!("".parse::<ColorColumns>(), Ok(ColorColumns::default()));
+        assert!("0".parse::<ColorColumns>().is_err());
+        assert!("80,".parse::<ColorColumns>().is_err());
+        assert!("1,2,3,4,5,6,7,8,9".parse::<ColorColumns>().is_err());
+
+        let options = toml::from_str::<Options>("colorcolumn = \"80\"").unwrap();
+        assert_eq!(
+            options.window.colorcolumn.iter().collect::<Vec<_>>(),
+            vec![80]
+        );
+    }
+
+    #[test]
+    fn smartcase() {
+        let mut options = GlobalOptions::default();
+        assert!(!options.ignores_case("foo"));
+
+        options.ignorecase = true;
+        assert!(options.ignores_case("foo"));
+        assert!(options.ignores_case("Foo"));
+
+        options.smartcase = true;
+        assert!(options.ignores_case("foo"));
+        assert!(!options.ignores_case("Foo"));
+    }
+
+    #[test]
+    fn apply_language_options() {
+        let language = toml::from_str::<LanguageOptions>("tabstop = 4").unwrap();
+
+        let mut options = BufferOptions {
+            tabstop: 8,
+            expandtab: true,
+        };
+        language.apply(&mut options);
+        assert_eq!(
+            options,
+            BufferOptions {
+                tabstop: 4,
+                expandtab: true,
+            }
+        );
+    }
+
+    #[test]
+    fn apply_settings() {
+        let mut global = GlobalOptions::default();
+        let mut buffer = Buffer::from("");
+
+        Setting::Set(OptionName::Number, Value::Bool(true)).apply(&mut global, &mut buffer);
+        Setting::Toggle(OptionName::RelativeNumber).apply(&mut global, &mut buffer);
+        Setting::Set(OptionName::ScrollOff, Value::Number(2)).apply(&mut global, &mut buffer);
+        Setting::Set(OptionName::FileFormat, Value::FileFormat(FileFormat::Dos))
+            .apply(&mut global, &mut buffer);
+        Setting::Set(OptionName::TimeoutLen, Value::Number(200)).apply(&mut global, &mut buffer);
+        Setting::Set(OptionName::Wrap, Value::Bool(true)).apply(&mut global, &mut buffer);
+
+        let window = buffer.window_options();
+        assert!(window.number);
+        assert!(window.relativenumber);
+        assert!(window.wrap);
+        assert_eq!(window.scrolloff, 2);
+        assert_eq!(buffer.file_format(), FileFormat::Dos);
+        assert_eq!(global.timeoutlen, 200);
+
+        let show = |option, global: &mut GlobalOptions, buffer: &mut Buffer| {
+            Setting::Show(option)
+                .apply(global, buffer)
+                .map(|shown| shown.to_string())
+        };
+        assert_eq!(
+            show(OptionName::Bomb, &mut global, &mut buffer).as_deref(),
+            Some("nobomb")
+        );
+        assert_eq!(
+            show(OptionName::ScrollOff, &mut global, &mut buffer).as_deref(),
+            Some("scrolloff=2")
+        );
+        assert_eq!(
+            show(OptionName::FileFormat, &mut global, &mut buffer).as_deref(),
+            Some("fileformat=dos")
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Running programs in pseudoterminals, for terminal buffers.
+//!
+//! The program runs in a new session with the pseudoterminal as its controlling terminal, so shells
+//! and REPLs behave as they would in a terminal emulator. Its output is read in the background and
+//! sent to the editor, which feeds it to the terminal buffer's emulator.
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+use log::*;
+use nix::fcntl::{self, FcntlArg, FdFlag};
+use nix::pty::{self, Winsize};
+use nix::sys::signal::{self, Signal};
+use nix::unistd::{self, Pid};
+use nix::{ioctl_none_bad, ioctl_write_ptr_bad};
+use tokio::io::{self, AsyncReadExt};
+use tokio::process::Command;
+
+use crate::ui::Size;
+
+/// The terminal type that programs are told they are running in. The emulator understands most
+/// xterm escape sequences.
+const TERM: &str = "xterm";
+
+/// The shell that is run when no program is given and `SHELL` is unset.
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// Something that happened to the program in a terminal buffer, identified by the buffer's ID.
+#[derive(Debug)]
+pub enum Output {
+    /// The program wrote to the terminal.
+    Data(usize, Vec<u8>),
+
+    /// The program exited.
+    Exited(usize, io::Result<ExitStatus>),
+}
+
+/// The editor's end of a pseudoterminal that a program is running in.
+#[derive(Debug)]
+pub struct Pty {
+    /// The master side of the pseudoterminal, which input is written to.
+    master: File,
+
+    /// The program's process ID, so it can be hung up on when the pty is closed.
+    pid: Pid,
+
+    /// The command line that was run.
+    command_line: String,
+}
+
+impl Pty {
+    /// Runs a program in a new pseudoterminal of a size, sending its output until it exits.
+    ///
+    /// If the command is empty, the user's shell is run.
+    pub fn spawn(
+        id: usize,
+        command: &[String],
+        dir: &Path,
+        size: Size,
+        mut output_sender: mpsc::Sender<Output>,
+    ) -> io::Result<Self> {
+        let shell = env::var("SHELL").unwrap_or_else(|_| String::from(DEFAULT_SHELL));
+        let (prog, args) = command.split_first().unwrap_or((&shell, &[]));
+        let command_line = std::iter::once(prog)
+            .chain(args)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let pty = pty::openpty(&winsize(size), None).map_err(nix_to_io)?;
+        // The program's own children shouldn't inherit the editor's end.
+        fcntl::fcntl(pty.master, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map_err(nix_to_io)?;
+        // SAFETY: `openpty` returned new file descriptors that nothing else owns.
+        let (master, slave) =
+            unsafe { (File::from_raw_fd(pty.master), File::from_raw_fd(pty.slave)) };
+
+        let mut command = Command::new(prog);
+        command
+            .args(args)
+            .current_dir(dir)
+            .env("TERM", TERM)
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+
+        // SAFETY: `setsid` and `ioctl` are async-signal-safe, and creating an error from errno
+        // doesn't allocate.
+        unsafe {
+            command.pre_exec(|| {
+                ioctl_none_bad!(tiocsctty, libc::TIOCSCTTY);
+
+                if unistd::setsid().is_err() || tiocsctty(libc::STDIN_FILENO).is_err() {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+
+        let mut child = command.spawn()?;
+        let pid = Pid::from_raw(child.id().expect("child should not have been polled") as i32);
+        let mut reader = tokio::fs::File::from_std(master.try_clone()?);
+
+        tokio::spawn(async move {
+            let mut buf = vec![0; 4096];
+
+            // Reading fails with EIO once the program exits and the slave side is closed.
+            loop {
+                match reader.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => {
+                        let output = Output::Data(id, buf[..len].to_vec());
+                        if output_sender.send(output).await.is_err() {
+                            info!("editor exited before terminal program");
+                            return;
+                        }
+                    }
+                }
+            }
+
+            let status = child.wait().await;
+            if output_sender
+                .send(Output::Exited(id, status))
+                .await
+                .is_err()
+            {
+                info!("editor exited before terminal program");
+            }
+        });
+
+        Ok(Pty {
+            master,
+            pid,
+            command_line,
+        })
+    }
+
+    /// Returns the command line that was run.
+    pub fn command_line(&self) -> &str {
+        &self.command_line
+    }
+
+    /// Writes input to the program.
+    pub fn write(&mut self, input: &[u8]) -> io::Result<()> {
+        self.master.write_all(input)
+    }
+
+    /// Changes the size of the terminal, which tells the program that it was resized.
+    pub fn resize(&self, size: Size) -> io::Result<()> {
+        ioctl_write_ptr_bad!(tiocswinsz, libc::TIOCSWINSZ, Winsize);
+
+        // SAFETY: The master file descriptor is open for as long as `self` is.
+        unsafe { tiocswinsz(self.master.as_raw_fd(), &winsize(size)) }.map_err(nix_to_io)?;
+        Ok(())
+    }
+}
+
+impl Drop for Pty {
+    /// Hangs up on the program, like closing a terminal emulator's window.
+    fn drop(&mut self) {
+        if let Err(e) = signal::kill(self.pid, Signal::SIGHUP) {
+            debug!("unable to hang up on terminal program: {}", e);
+        }
+    }
+}
+
+fn winsize(size: Size) -> Winsize {
+    Winsize {
+        ws_row: size.height,
+        ws_col: size.width,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+/// Converts an error from a system call to an I/O error.
+fn nix_to_io(error: nix::Error) -> io::Error {
+    let errno = error.as_errno().map_or(libc::EIO, |errno| errno as i32);
+    io::Error::from_raw_os_error(errno)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+
+    use crate::ui::Size;
+
+    use super::{Output, Pty};
+
+    #[tokio::test]
+    async fn run_program() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let command = vec![
+            String::from("sh"),
+            String::from("-c"),
+            String::from("stty size; tty >/dev/null && echo tty"),
+        ];
+        let _pty = Pty::spawn(0, &command, &env::temp_dir(), Size::new(80, 24), tx).unwrap();
+
+        let mut output = vec![];
+        loop {
+            match rx.next().await.unwrap() {
+                Output::Data(0, data) => output.extend(data),
+                Output::Exited(0, status) => {
+                    assert!(status.unwrap().success());
+                    break;
+                }
+                output => panic!("unexpected output: {:?}", output),
+            }
+        }
+
+        assert_eq!(String::from_utf8(output).unwrap(), "24 80\r\ntty\r\n");
+    }
+}
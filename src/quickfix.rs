@@ -0,0 +1,286 @@
+//! Quickfix and location lists: locations collected from commands such as `:make`, which can be
+//! visited in turn.
+//!
+//! There is a single, global quickfix list. Each window also has a location list, which is usually
+//! filled with results that are specific to its buffer, such as diagnostics. Either list can be
+//! shown in a split at the bottom of the screen.
+
+use std::cmp;
+use std::path::{Path, PathBuf};
+
+use lsp_types::{Diagnostic, DiagnosticSeverity};
+use strum::IntoStaticStr;
+
+use crate::buffer::Position;
+use crate::ui::{Bounds, Context, Coordinates, Drawable, Style};
+
+/// The number of rows of the split that shows a list.
+pub const LIST_HEIGHT: u16 = 10;
+
+/// Identifies the quickfix list or the location list.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, IntoStaticStr)]
+pub enum ListKind {
+    #[strum(serialize = "quickfix list")]
+    Quickfix,
+
+    #[strum(serialize = "location list")]
+    Location,
+}
+
+/// A location in a list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The absolute path of the file.
+    pub path: PathBuf,
+
+    pub position: Position,
+
+    pub severity: Option<DiagnosticSeverity>,
+
+    pub message: String,
+}
+
+impl Entry {
+    /// Creates an entry for a diagnostic in the file at `path`.
+    pub fn from_diagnostic(path: &Path, diagnostic: &Diagnostic) -> Self {
+        let start = diagnostic.range.start;
+
+        Entry {
+            path: path.to_owned(),
+            position: Position::new(start.character as usize, start.line as usize),
+            severity: diagnostic.severity,
+            message: diagnostic.message.clone(),
+        }
+    }
+
+    fn severity_name(&self) -> Option<&'static str> {
+        match self.severity? {
+            DiagnosticSeverity::Error => Some("error"),
+            DiagnosticSeverity::Warning => Some("warning"),
+            DiagnosticSeverity::Information => Some("note"),
+            DiagnosticSeverity::Hint => Some("hint"),
+        }
+    }
+}
+
+/// A list of locations, and the entry that the user most recently visited.
+#[derive(Debug, Default)]
+pub struct QuickfixList {
+    entries: Vec<Entry>,
+
+    /// The index of the current entry.
+    current: usize,
+}
+
+impl QuickfixList {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        QuickfixList {
+            entries,
+            current: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Adds entries to the end of the list.
+    pub fn extend(&mut self, entries: Vec<Entry>) {
+        self.entries.extend(entries);
+    }
+
+    /// Returns the current entry, or `None` if the list is empty.
+    pub fn current(&self) -> Option<&Entry> {
+        self.entries.get(self.current)
+    }
+
+    /// Makes the following entry current. Returns `false` if the current entry is the last.
+    pub fn next(&mut self) -> bool {
+        if self.current + 1 < self.len() {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Makes the preceding entry current. Returns `false` if the current entry is the first.
+    pub fn previous(&mut self) -> bool {
+        if self.current > 0 && !self.is_empty() {
+            self.current -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Describes the current entry for display in the command-line area, such as `(2 of 5): error:
+    /// expected ';'`.
+    pub fn describe_current(&self) -> Option<String> {
+        let entry = self.current()?;
+
+        let severity = match entry.severity_name() {
+            Some(name) => format!("{}: ", name),
+            None => String::new(),
+        };
+
+        Some(format!(
+            "({} of {}): {}{}",
+            self.current + 1,
+            self.len(),
+            severity,
+            entry.message
+        ))
+    }
+}
+
+/// Draws a list in the bottom split.
+///
+/// Each entry is shown on a single line in the form `path|line col column severity| message`, with
+/// paths relative to the working directory. The list scrolls to keep the current entry visible.
+pub struct ListView<'a> {
+    pub list: &'a QuickfixList,
+
+    /// The directory that paths are shown relative to.
+    pub current_dir: &'a Path,
+}
+
+impl ListView<'_> {
+    fn line(&self, entry: &Entry) -> String {
+        let path = entry
+            .path
+            .strip_prefix(self.current_dir)
+            .unwrap_or(&entry.path);
+
+        let mut location = format!(
+            "{}|{} col {}",
+            path.display(),
+            entry.position.y + 1,
+            entry.position.x + 1
+        );
+
+        if let Some(severity) = entry.severity_name() {
+            location.push(' ');
+            location.push_str(severity);
+        }
+
+        let message = entry.message.lines().next().unwrap_or_default();
+
+        format!("{}| {}", location, message)
+    }
+}
+
+impl Drawable for ListView<'_> {
+    fn draw(&self, ctx: &mut Context<'_>) {
+        let height = usize::from(ctx.bounds.height());
+        if height == 0 {
+            return;
+        }
+
+        let first = (self.list.current + 1).saturating_sub(height);
+        let last = cmp::min(first + height, self.list.len());
+
+        for (row, index) in (first..last).enumerate() {
+            let y = ctx.bounds.min.y + row as u16;
+
+            ctx.screen.write(
+                Coordinates::new(ctx.bounds.min.x, y),
+                &self.line(&self.list.entries[index]),
+            );
+
+            if index == self.list.current {
+                let bounds = Bounds::new(
+                    Coordinates::new(ctx.bounds.min.x, y),
+                    Coordinates::new(ctx.bounds.max.x, y + 1),
+                );
+                ctx.screen.apply_style(bounds, Style::default().bold());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use lsp_types::DiagnosticSeverity;
+
+    use crate::buffer::Position;
+    use crate::ui::{Bounds, Context, Coordinates, Drawable, Screen, Size};
+
+    use super::{Entry, ListView, QuickfixList};
+
+    fn list() -> QuickfixList {
+        QuickfixList::new(vec![
+            Entry {
+                path: PathBuf::from("/src/main.c"),
+                position: Position::new(4, 2),
+                severity: Some(DiagnosticSeverity::Error),
+                message: String::from("expected ';'"),
+            },
+            Entry {
+                path: PathBuf::from("/src/main.c"),
+                position: Position::new(0, 9),
+                severity: None,
+                message: String::from("unused variable"),
+            },
+        ])
+    }
+
+    #[test]
+    fn describe_current() {
+        assert_eq!(
+            list().describe_current().as_deref(),
+            Some("(1 of 2): error: expected ';'")
+        );
+
+        assert!(QuickfixList::default().describe_current().is_none());
+    }
+
+    #[test]
+    fn navigate() {
+        let mut list = list();
+
+        assert!(!list.previous());
+        assert!(list.next());
+        assert_eq!(list.current().unwrap().message, "unused variable");
+        assert!(!list.next());
+        assert!(list.previous());
+        assert_eq!(list.current().unwrap().message, "expected ';'");
+
+        let mut empty = QuickfixList::default();
+        assert!(!empty.next());
+        assert!(!empty.previous());
+    }
+
+    #[test]
+    fn draw_list() {
+        let mut list = list();
+        list.next();
+
+        let mut screen = Screen::new(Size::new(40, 2));
+        ListView {
+            list: &list,
+            current_dir: Path::new("/src"),
+        }
+        .draw(&mut Context {
+            bounds: Bounds::new(Coordinates::new(0, 1), Coordinates::new(40, 2)),
+            screen: &mut screen,
+        });
+
+        let row = |y| (0..40).filter_map(|x| screen[(y, x)].c).collect::<String>();
+
+        // Only the current entry fits.
+        assert_eq!(row(0), "");
+        assert_eq!(row(1), "main.c|10 col 1| unused variable");
+        assert!(screen[(1, 0)].bold);
+    }
+}
@@ -0,0 +1,170 @@
+//! Recording the keys that are typed and replaying them, so that a bug can be reproduced from a
+//! recording attached to its report, with `--record` and `--replay`.
+//!
+//! A recording has a line for each key: the number of milliseconds since the previous key, and
+//! the key in the notation of key chords, such as `250 <C-w>`. Replayed keys arrive with the same
+//! delays, so that timeouts behave the same way as when the keys were recorded.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use futures::stream::{self, Stream, StreamExt};
+use thiserror::Error;
+use tokio::fs;
+use tokio::time::{self, Instant};
+
+use crate::keymap::Chord;
+use crate::term::Key;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid recording on line {0}")]
+    InvalidLine(usize),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Writes the keys that are typed to a recording.
+pub struct Recorder {
+    file: BufWriter<File>,
+
+    /// When the previous key was typed, or when recording started.
+    last: Instant,
+}
+
+impl Recorder {
+    /// Starts recording to a file, replacing it if it exists.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Recorder {
+            file: BufWriter::new(File::create(path)?),
+            last: Instant::now(),
+        })
+    }
+
+    /// Records a key that was just typed.
+    ///
+    /// The recording is flushed after each key, so that it's complete even if the editor crashes.
+    pub fn record(&mut self, key: Key) -> io::Result<()> {
+        let now = Instant::now();
+        let delay = now.duration_since(self.last);
+        self.last = now;
+
+        writeln!(self.file, "{} {}", delay.as_millis(), encode(key))?;
+        self.file.flush()
+    }
+}
+
+/// Reads the keys of a recording, and the delay before each.
+pub async fn read(path: &Path) -> Result<Vec<(Duration, Key)>, Error> {
+    let text = fs::read_to_string(path).await?;
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| parse_line(line).ok_or(Error::InvalidLine(i + 1)))
+        .collect()
+}
+
+/// Returns a stream of recorded keys, each arriving after its delay.
+pub fn replay(keys: Vec<(Duration, Key)>) -> impl Stream<Item = io::Result<Key>> {
+    stream::iter(keys).then(|(delay, key)| async move {
+        time::sleep(delay).await;
+        Ok(key)
+    })
+}
+
+fn parse_line(line: &str) -> Option<(Duration, Key)> {
+    let index = line.find(' ')?;
+    let millis = line[..index].parse().ok()?;
+    let key = decode(&line[index + 1..])?;
+
+    Some((Duration::from_millis(millis), key))
+}
+
+/// Writes a key in the notation of key chords. Focus changes and the ends of pastes aren't part of
+/// chords, so they have their own names.
+fn encode(key: Key) -> String {
+    match key {
+        Key::FocusGained => String::from("<FocusGained>"),
+        Key::FocusLost => String::from("<FocusLost>"),
+        Key::PasteStart => String::from("<PasteStart>"),
+        Key::PasteEnd => String::from("<PasteEnd>"),
+        key => key.to_string(),
+    }
+}
+
+fn decode(s: &str) -> Option<Key> {
+    match s {
+        "<FocusGained>" => Some(Key::FocusGained),
+        "<FocusLost>" => Some(Key::FocusLost),
+        "<PasteStart>" => Some(Key::PasteStart),
+        "<PasteEnd>" => Some(Key::PasteEnd),
+        s => s.parse::<Chord>().ok()?.single_key(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::TryStreamExt;
+
+    use crate::term::Key;
+
+    use super::{decode, encode, parse_line, read, replay, Recorder};
+
+    #[test]
+    fn encode_and_decode() {
+        let keys = [
+            Key::Char('a'),
+            Key::Char('<'),
+            Key::Char(' '),
+            Key::Ctrl('w'),
+            Key::Ctrl(']'),
+            Key::Ctrl('i'),
+            Key::Return,
+            Key::Esc,
+            Key::Backspace,
+            Key::ArrowLeft,
+            Key::FocusLost,
+            Key::PasteStart,
+        ];
+
+        for &key in &keys {
+            assert_eq!(decode(&encode(key)), Some(key));
+        }
+    }
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(
+            parse_line("250 <C-w>"),
+            Some((Duration::from_millis(250), Key::Ctrl('w')))
+        );
+        assert_eq!(parse_line("250"), None);
+        assert_eq!(parse_line("soon a"), None);
+        assert_eq!(parse_line("0 ab"), None);
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys");
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.record(Key::Char('i')).unwrap();
+        recorder.record(Key::Esc).unwrap();
+
+        let recorded = read(&path).await.unwrap();
+        assert_eq!(
+            recorded.iter().map(|&(_, key)| key).collect::<Vec<_>>(),
+            vec![Key::Char('i'), Key::Esc]
+        );
+
+        let replayed = replay(recorded).try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(replayed, vec![Key::Char('i'), Key::Esc]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Recovery of modified buffers after a crash.
+//!
+//! If the editor panics, the text of each modified buffer is written to a file in the recovery
+//! directory, in the state directory, before the editor exits. On the next start, the editor says
+//! that there are buffers to recover, and `:recover` opens them again with the recovered text.
+
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex, TryLockError};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::Buffer;
+use crate::state::{self, Error};
+
+/// The text of a buffer that was modified when the editor crashed.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recovered {
+    /// The buffer's file, or `None` for a scratch buffer.
+    pub path: Option<PathBuf>,
+
+    pub text: String,
+}
+
+/// Returns the directory that buffers are written to when the editor crashes.
+pub fn dir() -> Result<PathBuf, Error> {
+    Ok(state::state_dir()?.join("recovery"))
+}
+
+/// Copies of the text of the modified buffers, which the panic hook writes to the recovery
+/// directory.
+///
+/// The panic hook can't borrow the editor's buffers, which may be half-changed when it panics, so
+/// the editor updates these copies before each redraw instead. A buffer's text is only copied
+/// again after it changes. Large files aren't copied, since copying them after every change would
+/// be slow.
+#[derive(Debug, Default, Clone)]
+pub struct Backups(Arc<Mutex<Vec<Backup>>>);
+
+/// The copy of a modified buffer.
+#[derive(Debug)]
+struct Backup {
+    /// The position of the buffer in the buffer list.
+    index: usize,
+
+    /// The version of the buffer that the text was copied from.
+    version: u32,
+
+    recovered: Recovered,
+}
+
+impl Backups {
+    /// Copies the text of the buffers that changed since the last update, and forgets the buffers
+    /// that are no longer modified.
+    pub fn update<'a>(&self, buffers: impl IntoIterator<Item = &'a Buffer>) {
+        let mut backups = match self.0.lock() {
+            Ok(backups) => backups,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let mut old = mem::take(&mut *backups);
+        for (index, buffer) in buffers.into_iter().enumerate() {
+            if !is_recoverable(buffer) {
+                continue;
+            }
+
+            let path = buffer.path().map(Path::to_owned);
+            let version = buffer.version();
+            let unchanged = old.iter().position(|backup| {
+                backup.index == index && backup.version == version && backup.recovered.path == path
+            });
+
+            backups.push(match unchanged {
+                Some(i) => old.swap_remove(i),
+                None => Backup {
+                    index,
+                    version,
+                    recovered: Recovered {
+                        path,
+                        text: buffer.text(),
+                    },
+                },
+            });
+        }
+    }
+
+    /// Writes the copies to the recovery directory, returning how many were written.
+    ///
+    /// This runs while the editor is panicking, so the files are written synchronously, and a copy
+    /// that can't be written doesn't stop the others from being written. Nothing is written if the
+    /// panic happened while the copies were being updated.
+    pub fn dump(&self, dir: &Path) -> io::Result<usize> {
+        let backups = match self.0.try_lock() {
+            Ok(backups) => backups,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "the buffers were being copied",
+                ))
+            }
+        };
+
+        if backups.is_empty() {
+            return Ok(0);
+        }
+
+        fs::create_dir_all(dir)?;
+
+        let mut written = 0;
+        for (i, backup) in backups.iter().enumerate() {
+            // The process ID keeps the files of editors that crash at the same time apart.
+            let path = dir.join(format!("{}-{}.json", process::id(), i));
+            let result = serde_json::to_vec(&backup.recovered)
+                .map_err(io::Error::from)
+                .and_then(|json| fs::write(&path, json));
+
+            match result {
+                Ok(()) => written += 1,
+                Err(e) => error!("unable to write {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+/// Returns whether a buffer has changes that would be lost in a crash.
+fn is_recoverable(buffer: &Buffer) -> bool {
+    buffer.is_modified()
+        && !buffer.is_large()
+        && buffer.log_path().is_none()
+        && buffer.terminal_id().is_none()
+        && buffer.help_doc().is_none()
+        && !buffer.is_directory()
+}
+
+/// Returns the recovery files in the recovery directory, in order.
+pub async fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut files = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Reads a recovery file.
+pub async fn read(path: &Path) -> Result<Recovered, Error> {
+    let bytes = tokio::fs::read(path).await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::buffer::Buffer;
+
+    use super::{files, read, Backups, Recovered};
+
+    #[tokio::test]
+    async fn dump_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let recovery_dir = dir.path().join("recovery");
+
+        let unmodified = Buffer::from("unmodified");
+        let mut modified = Buffer::from("");
+        modified.insert_str("typed text");
+
+        let backups = Backups::default();
+        backups.update(vec![&unmodified, &modified]);
+        modified.insert_str("more ");
+
+        // The text is copied again after the buffer changes.
+        backups.update(vec![&unmodified, &modified]);
+
+        let written = backups.dump(&recovery_dir).unwrap();
+        assert_eq!(written, 1);
+
+        let files = files(&recovery_dir).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            read(&files[0]).await.unwrap(),
+            Recovered {
+                path: None,
+                text: modified.text(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn no_recovery_dir() {
+        let files = files(&PathBuf::from("/nonexistent/recovery"))
+            .await
+            .unwrap();
+        assert!(files.is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+//! Replacing a pattern across the project with `:replace`.
+//!
+//! The project is searched like `:grep`, except that the pattern is matched literally. Once the
+//! search finishes, each matching line is shown in a picker as it would be after the replacement,
+//! grouped by file. Lines removed from the picker are left alone, and choosing a line replaces the
+//! matches on every line that's left. The lines of each file are replaced in a single transaction,
+//! so the replacements in a file can be undone at once.
+
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::quickfix::Entry;
+use crate::ui::picker::Item;
+
+/// A pattern to replace, and its replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+/// A line that contains the pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    /// The absolute path of the file.
+    pub path: PathBuf,
+
+    /// The line, counting from zero.
+    pub line: usize,
+
+    /// The text of the line when it was searched, without surrounding whitespace.
+    pub text: String,
+}
+
+impl Replacement {
+    /// Returns the picker item that previews replacing the matches on a line.
+    pub fn item(&self, hit: &Hit, current_dir: &Path) -> Item {
+        let path = hit.path.strip_prefix(current_dir).unwrap_or(&hit.path);
+        let replaced = hit.text.replace(&self.pattern, &self.replacement);
+
+        Item::new(format!("{}:{}: {}", path.display(), hit.line + 1, replaced))
+            .with_preview(vec![format!("- {}", hit.text), format!("+ {}", replaced)])
+    }
+}
+
+/// Returns the lines of the search results, ordered by file and line. Matches on the same line
+/// are combined, since a line's matches are replaced together.
+pub fn hits(entries: &[Entry]) -> Vec<Hit> {
+    let mut hits = entries
+        .iter()
+        .map(|entry| Hit {
+            path: entry.path.clone(),
+            line: entry.position.y,
+            text: entry.message.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    hits.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    hits.dedup_by(|a, b| a.path == b.path && a.line == b.line);
+    hits
+}
+
+/// Groups the lines to replace by file, in order.
+pub fn by_file(hits: &[Hit]) -> Vec<(&Path, Vec<usize>)> {
+    hits.iter()
+        .group_by(|hit| hit.path.as_path())
+        .into_iter()
+        .map(|(path, hits)| (path, hits.map(|hit| hit.line).collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::buffer::Position;
+    use crate::quickfix::Entry;
+
+    use super::{by_file, hits, Hit, Replacement};
+
+    fn entry(path: &str, x: usize, y: usize, message: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            position: Position::new(x, y),
+            severity: None,
+            message: String::from(message),
+        }
+    }
+
+    fn hit(path: &str, line: usize, text: &str) -> Hit {
+        Hit {
+            path: PathBuf::from(path),
+            line,
+            text: String::from(text),
+        }
+    }
+
+    #[test]
+    fn combine_matches_by_line() {
+        let entries = vec![
+            entry("/src/main.rs", 4, 9, "foo(foo)"),
+            entry("/src/lib.rs", 0, 2, "foo"),
+            entry("/src/main.rs", 0, 9, "foo(foo)"),
+            entry("/src/main.rs", 0, 1, "let foo;"),
+        ];
+
+        assert_eq!(
+            hits(&entries),
+            vec![
+                hit("/src/lib.rs", 2, "foo"),
+                hit("/src/main.rs", 1, "let foo;"),
+                hit("/src/main.rs", 9, "foo(foo)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_by_file() {
+        let hits = vec![
+            hit("/src/lib.rs", 2, "foo"),
+            hit("/src/main.rs", 1, "let foo;"),
+            hit("/src/main.rs", 9, "foo(foo)"),
+        ];
+
+        assert_eq!(
+            by_file(&hits),
+            vec![
+                (Path::new("/src/lib.rs"), vec![2]),
+                (Path::new("/src/main.rs"), vec![1, 9]),
+            ]
+        );
+    }
+
+    #[test]
+    fn preview_replacement() {
+        let replacement = Replacement {
+            pattern: String::from("foo"),
+            replacement: String::from("bar"),
+        };
+
+        let item = replacement.item(&hit("/src/main.rs", 9, "foo(foo)"), Path::new("/src"));
+        assert_eq!(item.label, "main.rs:10: bar(bar)");
+        assert_eq!(item.preview, vec!["- foo(foo)", "+ bar(bar)"]);
+    }
+}
@@ -0,0 +1,540 @@
+//! Extending the editor with Lua scripts.
+//!
+//! `init.lua` in the config directory is run when the editor starts and when the config is
+//! reloaded. Scripts use the `editor` table to read the current buffer and options, run commands,
+//! bind keys, and respond to events:
+//!
+//! ```lua
+//! editor.set("number", true)
+//! editor.map("normal", "<leader>w", "write")
+//! editor.on("buf-write-post", "*.rs", function(path)
+//!     editor.message("wrote " .. path)
+//! end)
+//! ```
+//!
+//! Scripts run while the editor is in the middle of handling a key or an event, so functions that
+//! change the editor, such as `editor.command`, make requests that are carried out afterwards, like
+//! the commands of hooks. Functions that read the editor see a snapshot taken before the script was
+//! called.
+
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+use mlua::{AppDataRefMut, Function, Lua, RegistryKey, Table};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::fs;
+use tokio::io;
+
+use crate::buffer::Position;
+use crate::event::{Event, EventKind, Request};
+use crate::keymap::Chord;
+use crate::options::{OptionName, Value};
+use crate::syntax::Syntax;
+use crate::Mode;
+
+/// The name of the script that is run at startup, in the config directory.
+pub const INIT_SCRIPT: &str = "init.lua";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to read {0}: {1}")]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("{}", first_line(.0))]
+    Lua(#[from] mlua::Error),
+}
+
+/// Returns the first line of a Lua error, without the stack traceback.
+fn first_line(error: &mlua::Error) -> String {
+    error
+        .to_string()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// The state of the editor that scripts can read.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub path: Option<PathBuf>,
+    pub syntax: Option<Syntax>,
+    pub text: String,
+    pub cursor: Position,
+    pub mode: Mode,
+    pub options: Vec<(OptionName, Value)>,
+}
+
+/// A chord bound by a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub mode: Mode,
+    pub chord: Chord,
+}
+
+/// What a chord bound by a script does.
+enum Binding {
+    Command(String),
+    Function(RegistryKey),
+}
+
+/// A function that a script registered for an event.
+struct Handler {
+    event: EventKind,
+    pattern: Option<Pattern>,
+    function: RegistryKey,
+}
+
+/// The state shared between the editor and the `editor` table, kept in the Lua state.
+#[derive(Default)]
+struct State {
+    snapshot: Snapshot,
+    requests: Vec<Request>,
+    handlers: Vec<Handler>,
+    mappings: Vec<(Mapping, Binding)>,
+}
+
+/// The Lua state that scripts run in.
+pub struct Scripts {
+    lua: Lua,
+}
+
+impl Scripts {
+    pub fn new() -> Result<Self, Error> {
+        let lua = Lua::new();
+        lua.set_app_data(State::default());
+        install_api(&lua)?;
+
+        Ok(Scripts { lua })
+    }
+
+    /// Runs the init script in a config directory, if there is one.
+    pub async fn run_init(&self, config_dir: &Path, snapshot: Snapshot) -> Result<(), Error> {
+        let path = config_dir.join(INIT_SCRIPT);
+
+        let source = match fs::read_to_string(&path).await {
+            Ok(source) => source,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(Error::Io(path, e)),
+        };
+
+        self.run(&source, &path.display().to_string(), snapshot)
+    }
+
+    /// Runs the source of a script.
+    pub fn run(&self, source: &str, name: &str, snapshot: Snapshot) -> Result<(), Error> {
+        self.state().snapshot = snapshot;
+        self.lua.load(source).set_name(name).exec()?;
+        Ok(())
+    }
+
+    /// Returns the chords bound by scripts. The action of each is `Action::Script` with its index.
+    pub fn mappings(&self) -> Vec<Mapping> {
+        self.state()
+            .mappings
+            .iter()
+            .map(|(mapping, _)| mapping.clone())
+            .collect()
+    }
+
+    /// Returns whether any script responds to events of a kind.
+    pub fn handles(&self, kind: EventKind) -> bool {
+        self.state()
+            .handlers
+            .iter()
+            .any(|handler| handler.event == kind)
+    }
+
+    /// Calls the functions that scripts registered for an event, with the subject of the event.
+    pub fn dispatch(&self, event: &Event, snapshot: Snapshot) -> Result<(), Error> {
+        let subject = event.subject();
+
+        let functions = {
+            let mut state = self.state();
+            state.snapshot = snapshot;
+
+            state
+                .handlers
+                .iter()
+                .filter(|handler| handler.event == event.kind())
+                .filter(|handler| match (&handler.pattern, &subject) {
+                    (None, _) => true,
+                    (Some(pattern), Some(subject)) => pattern.matches(subject),
+                    (Some(_), None) => false,
+                })
+                .map(|handler| self.lua.registry_value::<Function>(&handler.function))
+                .collect::<mlua::Result<Vec<_>>>()?
+        };
+
+        for function in functions {
+            function.call::<_, ()>(subject.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Carries out the binding of the chord bound at an index.
+    pub fn call_mapping(&self, index: usize, snapshot: Snapshot) -> Result<(), Error> {
+        let function = {
+            let mut state = self.state();
+            state.snapshot = snapshot;
+
+            match state.mappings.get(index) {
+                Some((_, Binding::Command(command))) => {
+                    let request = Request::Command(command.clone());
+                    state.requests.push(request);
+                    return Ok(());
+                }
+                Some((_, Binding::Function(key))) => self.lua.registry_value::<Function>(key)?,
+                None => return Ok(()),
+            }
+        };
+
+        function.call::<_, ()>(())?;
+
+        Ok(())
+    }
+
+    /// Takes the requests that scripts made since they were last taken.
+    pub fn take_requests(&self) -> Vec<Request> {
+        std::mem::take(&mut self.state().requests)
+    }
+
+    fn state(&self) -> AppDataRefMut<'_, State> {
+        state(&self.lua)
+    }
+}
+
+fn state(lua: &Lua) -> AppDataRefMut<'_, State> {
+    lua.app_data_mut::<State>()
+        .expect("script state should be set")
+}
+
+fn runtime_error(message: String) -> mlua::Error {
+    mlua::Error::RuntimeError(message)
+}
+
+/// Creates the `editor` table.
+fn install_api(lua: &Lua) -> mlua::Result<()> {
+    let api = lua.create_table()?;
+
+    api.set(
+        "command",
+        lua.create_function(|lua, command: String| {
+            state(lua).requests.push(Request::Command(command));
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "insert",
+        lua.create_function(|lua, text: String| {
+            state(lua).requests.push(Request::Insert(text));
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "snippet",
+        lua.create_function(|lua, snippet: String| {
+            state(lua).requests.push(Request::Snippet(snippet));
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "message",
+        lua.create_function(|lua, message: String| {
+            state(lua).requests.push(Request::Message(message));
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "option",
+        lua.create_function(|lua, name: String| {
+            let option = OptionName::from_name(&name)
+                .ok_or_else(|| runtime_error(format!("unknown option: {}", name)))?;
+            let value = state(lua)
+                .snapshot
+                .options
+                .iter()
+                .find(|(o, _)| *o == option)
+                .map(|(_, value)| *value);
+
+            Ok(match value {
+                Some(Value::Bool(b)) => mlua::Value::Boolean(b),
+                Some(Value::Number(n)) => mlua::Value::Integer(n as mlua::Integer),
+                Some(Value::FileFormat(file_format)) => {
+                    mlua::Value::String(lua.create_string(file_format.to_string())?)
+                }
+                Some(Value::VirtualEdit(virtualedit)) => {
+                    mlua::Value::String(lua.create_string(virtualedit.to_string())?)
+                }
+                Some(Value::ColorColumns(columns)) => {
+                    mlua::Value::String(lua.create_string(columns.to_string())?)
+                }
+                None => mlua::Value::Nil,
+            })
+        })?,
+    )?;
+
+    api.set(
+        "set",
+        lua.create_function(|lua, (name, value): (String, mlua::Value)| {
+            if OptionName::from_name(&name).is_none() {
+                return Err(runtime_error(format!("unknown option: {}", name)));
+            }
+
+            let setting = match value {
+                mlua::Value::Boolean(true) => name,
+                mlua::Value::Boolean(false) => format!("no{}", name),
+                mlua::Value::Integer(n) => format!("{}={}", name, n),
+                mlua::Value::String(s) => format!("{}={}", name, s.to_str()?),
+                _ => return Err(runtime_error(format!("invalid value for {}", name))),
+            };
+
+            state(lua)
+                .requests
+                .push(Request::Command(format!("set {}", setting)));
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "mode",
+        lua.create_function(|lua, ()| Ok(state(lua).snapshot.mode.name()))?,
+    )?;
+
+    api.set("buffer", lua.create_function(buffer_table)?)?;
+
+    api.set(
+        "on",
+        lua.create_function(
+            |lua, (event, pattern, function): (String, mlua::Value, Option<Function>)| {
+                let kind = EventKind::deserialize(event.as_str().into_deserializer())
+                    .map_err(|e: serde::de::value::Error| runtime_error(e.to_string()))?;
+
+                let (pattern, function) = match (pattern, function) {
+                    (mlua::Value::Function(function), None) => (None, function),
+                    (mlua::Value::String(pattern), Some(function)) => {
+                        let pattern = Pattern::new(pattern.to_str()?)
+                            .map_err(|e| runtime_error(e.to_string()))?;
+                        (Some(pattern), function)
+                    }
+                    _ => return Err(runtime_error(String::from("expected a function"))),
+                };
+
+                let function = lua.create_registry_value(function)?;
+                state(lua).handlers.push(Handler {
+                    event: kind,
+                    pattern,
+                    function,
+                });
+                Ok(())
+            },
+        )?,
+    )?;
+
+    api.set(
+        "map",
+        lua.create_function(
+            |lua, (mode, chord, binding): (String, String, mlua::Value)| {
+                let mode = match mode.as_str() {
+                    "normal" => Mode::Normal,
+                    "insert" => Mode::Insert,
+                    "visual" => Mode::Visual,
+                    _ => return Err(runtime_error(format!("cannot map keys in {} mode", mode))),
+                };
+                let chord = chord
+                    .parse::<Chord>()
+                    .map_err(|e| runtime_error(e.to_string()))?;
+
+                let binding = match binding {
+                    mlua::Value::String(command) => Binding::Command(command.to_str()?.to_owned()),
+                    mlua::Value::Function(function) => {
+                        Binding::Function(lua.create_registry_value(function)?)
+                    }
+                    _ => {
+                        return Err(runtime_error(String::from(
+                            "expected a command or a function",
+                        )))
+                    }
+                };
+
+                state(lua).mappings.push((Mapping { mode, chord }, binding));
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set("editor", api)
+}
+
+/// Returns a table describing the current buffer: its `path`, `syntax`, `lines`, and `cursor`,
+/// with the line and column counting from one.
+fn buffer_table(lua: &Lua, _: ()) -> mlua::Result<Table<'_>> {
+    let state = state(lua);
+    let snapshot = &state.snapshot;
+
+    let buffer = lua.create_table()?;
+    buffer.set(
+        "path",
+        snapshot
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string()),
+    )?;
+    buffer.set("syntax", snapshot.syntax.map(<&str>::from))?;
+    buffer.set("lines", lua.create_sequence_from(snapshot.text.lines())?)?;
+
+    let cursor = lua.create_table()?;
+    cursor.set("line", snapshot.cursor.y + 1)?;
+    cursor.set("column", snapshot.cursor.x + 1)?;
+    buffer.set("cursor", cursor)?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::buffer::Position;
+    use crate::event::{Event, EventKind, Request};
+    use crate::options::{OptionName, Value};
+    use crate::syntax::Syntax;
+    use crate::Mode;
+
+    use super::{Mapping, Scripts, Snapshot};
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            path: Some(PathBuf::from("/src/main.rs")),
+            syntax: Some(Syntax::Rust),
+            text: String::from("fn main() {\n}\n"),
+            cursor: Position::new(3, 1),
+            mode: Mode::Normal,
+            options: vec![(OptionName::TabStop, Value::Number(4))],
+        }
+    }
+
+    #[test]
+    fn requests() {
+        let scripts = Scripts::new().unwrap();
+        scripts
+            .run(
+                r#"
+                local buffer = editor.buffer()
+                editor.message(buffer.path .. ":" .. buffer.cursor.line .. ":" .. buffer.lines[1])
+                editor.set("expandtab", editor.option("ts") == 4)
+                editor.set("scrolloff", 3)
+                editor.insert(editor.mode())
+                editor.snippet("fn ${1:name}() {}")
+                editor.command("make")
+                "#,
+                "test",
+                snapshot(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            scripts.take_requests(),
+            vec![
+                Request::Message(String::from("/src/main.rs:2:fn main() {")),
+                Request::Command(String::from("set expandtab")),
+                Request::Command(String::from("set scrolloff=3")),
+                Request::Insert(String::from("normal")),
+                Request::Snippet(String::from("fn ${1:name}() {}")),
+                Request::Command(String::from("make")),
+            ]
+        );
+        assert!(scripts.take_requests().is_empty());
+
+        let error = scripts
+            .run("editor.set('frobnicate', true)", "test", snapshot())
+            .unwrap_err();
+        assert!(error.to_string().contains("unknown option: frobnicate"));
+    }
+
+    #[test]
+    fn events() {
+        let scripts = Scripts::new().unwrap();
+        scripts
+            .run(
+                r#"
+                editor.on("buf-write-post", "*.rs", function(path) editor.command("make " .. path) end)
+                editor.on("focus-lost", function() editor.command("w") end)
+                "#,
+                "test",
+                snapshot(),
+            )
+            .unwrap();
+
+        assert!(scripts.handles(EventKind::BufWritePost));
+        assert!(!scripts.handles(EventKind::BufOpen));
+
+        let event = Event::BufWritePost(PathBuf::from("/src/main.rs"));
+        scripts.dispatch(&event, snapshot()).unwrap();
+        let event = Event::BufWritePost(PathBuf::from("/README.md"));
+        scripts.dispatch(&event, snapshot()).unwrap();
+        scripts.dispatch(&Event::FocusLost, snapshot()).unwrap();
+
+        assert_eq!(
+            scripts.take_requests(),
+            vec![
+                Request::Command(String::from("make /src/main.rs")),
+                Request::Command(String::from("w")),
+            ]
+        );
+
+        assert!(scripts
+            .run("editor.on('bufenter', function() end)", "test", snapshot())
+            .is_err());
+    }
+
+    #[test]
+    fn mappings() {
+        let scripts = Scripts::new().unwrap();
+        scripts
+            .run(
+                r#"
+                editor.map("normal", "<leader>w", "write")
+                editor.map("insert", "jk", function() editor.insert("!") end)
+                "#,
+                "test",
+                snapshot(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            scripts.mappings(),
+            vec![
+                Mapping {
+                    mode: Mode::Normal,
+                    chord: "<leader>w".parse().unwrap(),
+                },
+                Mapping {
+                    mode: Mode::Insert,
+                    chord: "jk".parse().unwrap(),
+                },
+            ]
+        );
+
+        scripts.call_mapping(0, snapshot()).unwrap();
+        scripts.call_mapping(1, snapshot()).unwrap();
+        assert_eq!(
+            scripts.take_requests(),
+            vec![
+                Request::Command(String::from("write")),
+                Request::Insert(String::from("!")),
+            ]
+        );
+
+        assert!(scripts
+            .run("editor.map('picker', 'x', 'w')", "test", snapshot())
+            .is_err());
+    }
+}
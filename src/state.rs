@@ -0,0 +1,250 @@
+//! Editor state that persists between sessions, like Vim's viminfo.
+//!
+//! The state file remembers the cursor position in each file that was edited, the most recently
+//! edited files, and the history of the command line. It is read when the editor starts and
+//! written when it exits. Other instances of the editor may have written the file in the meantime,
+//! so it is read again before writing and this session's state is merged on top of it.
+
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+use tokio::io;
+
+use crate::buffer::{Buffer, Position};
+
+/// The maximum number of files whose cursor positions are remembered.
+const MAX_FILES: usize = 100;
+
+/// The maximum number of command lines that are remembered.
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("could not determine state directory: neither XDG_STATE_HOME nor HOME is set")]
+    NoStateDir,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Returns the directory containing the state file and the log.
+///
+/// Respects `XDG_STATE_HOME`.
+pub fn state_dir() -> Result<PathBuf, Error> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .ok_or(Error::NoStateDir)?;
+
+    Ok(state_dir.join("editor"))
+}
+
+/// A remembered cursor position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Cursor {
+    line: usize,
+    column: usize,
+}
+
+/// State that persists between sessions.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// The last cursor position in each file, by absolute path.
+    cursors: HashMap<PathBuf, Cursor>,
+
+    /// The most recently edited files, from most to least recent.
+    recent_files: Vec<PathBuf>,
+
+    /// The command lines that were entered, from oldest to newest.
+    command_history: Vec<String>,
+}
+
+impl State {
+    /// Reads the state file, returning empty state if it doesn't exist.
+    pub async fn read(path: &Path) -> Result<State, Error> {
+        info!("reading state from {}", path.display());
+
+        match fs::read(path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the state file, merging this state on top of the state that is already there.
+    pub async fn write(mut self, path: &Path) -> Result<(), Error> {
+        info!("writing state to {}", path.display());
+
+        // A corrupt state file is replaced rather than preventing the state from being saved.
+        if let Ok(on_disk) = State::read(path).await {
+            self = on_disk.merge(self);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(path, serde_json::to_vec(&self)?).await?;
+
+        Ok(())
+    }
+
+    /// Returns the path of the state file.
+    ///
+    /// Respects `XDG_STATE_HOME`.
+    pub fn path() -> Result<PathBuf, Error> {
+        Ok(state_dir()?.join("state.json"))
+    }
+
+    /// Returns the remembered cursor position of a file.
+    pub fn cursor(&self, path: &Path) -> Option<Position> {
+        let cursor = self.cursors.get(path)?;
+        Some(Position::new(cursor.column, cursor.line))
+    }
+
+    /// Moves the cursor of a buffer to the remembered position of its file, if any.
+    pub fn restore_cursor(&self, buffer: &mut Buffer) {
+        if let Some(position) = buffer.path().and_then(|path| self.cursor(path)) {
+            buffer.jump_to(position);
+        }
+    }
+
+    /// Remembers the cursor position of a buffer's file, if it has one.
+    pub fn remember_cursor(&mut self, buffer: &Buffer) {
+        if let Some(path) = buffer.path() {
+            self.remember(path, buffer.cursor());
+        }
+    }
+
+    /// Remembers the cursor position of a file, making it the most recently edited file.
+    pub fn remember(&mut self, path: &Path, position: Position) {
+        self.cursors.insert(
+            path.to_owned(),
+            Cursor {
+                line: position.y,
+                column: position.x,
+            },
+        );
+
+        self.recent_files.retain(|file| file != path);
+        self.recent_files.insert(0, path.to_owned());
+
+        for forgotten in self
+            .recent_files
+            .drain(MAX_FILES.min(self.recent_files.len())..)
+        {
+            self.cursors.remove(&forgotten);
+        }
+    }
+
+    /// Returns the most recently edited files, from most to least recent.
+    pub fn recent_files(&self) -> &[PathBuf] {
+        &self.recent_files
+    }
+
+    /// Returns the command lines that were entered, from oldest to newest.
+    pub fn command_history(&self) -> &[String] {
+        &self.command_history
+    }
+
+    /// Replaces the history of the command line.
+    pub fn set_command_history(&mut self, history: Vec<String>) {
+        let excess = history.len().saturating_sub(MAX_HISTORY);
+        self.command_history = history.into_iter().skip(excess).collect();
+    }
+
+    /// Merges newer state on top of this state.
+    fn merge(mut self, newer: State) -> State {
+        for path in newer.recent_files.iter().rev() {
+            if let Some(cursor) = newer.cursors.get(path) {
+                self.remember(path, Position::new(cursor.column, cursor.line));
+            }
+        }
+
+        if !newer.command_history.is_empty() {
+            let mut history = std::mem::take(&mut self.command_history);
+            history.retain(|line| !newer.command_history.contains(line));
+            history.extend(newer.command_history);
+            self.set_command_history(history);
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::buffer::Position;
+
+    use super::State;
+
+    #[test]
+    fn remember() {
+        let mut state = State::default();
+        state.remember(Path::new("/a"), Position::new(1, 2));
+        state.remember(Path::new("/b"), Position::new(3, 4));
+        state.remember(Path::new("/a"), Position::new(5, 6));
+
+        assert_eq!(state.cursor(Path::new("/a")), Some(Position::new(5, 6)));
+        assert_eq!(state.cursor(Path::new("/c")), None);
+        assert_eq!(
+            state.recent_files(),
+            &[Path::new("/a").to_owned(), Path::new("/b").to_owned()]
+        );
+
+        for i in 0..100 {
+            state.remember(&Path::new("/").join(i.to_string()), Position::zero());
+        }
+        assert_eq!(state.recent_files().len(), 100);
+        assert_eq!(state.cursor(Path::new("/a")), None);
+    }
+
+    #[test]
+    fn merge() {
+        let mut older = State::default();
+        older.remember(Path::new("/a"), Position::new(1, 1));
+        older.remember(Path::new("/b"), Position::new(2, 2));
+        older.set_command_history(vec![String::from("make"), String::from("grep foo")]);
+
+        let mut newer = State::default();
+        newer.remember(Path::new("/a"), Position::new(3, 3));
+        newer.set_command_history(vec![String::from("make")]);
+
+        let merged = older.merge(newer);
+        assert_eq!(merged.cursor(Path::new("/a")), Some(Position::new(3, 3)));
+        assert_eq!(merged.cursor(Path::new("/b")), Some(Position::new(2, 2)));
+        assert_eq!(merged.recent_files()[0], Path::new("/a"));
+        assert_eq!(merged.command_history(), &["grep foo", "make"]);
+    }
+
+    #[tokio::test]
+    async fn read_and_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("editor/state.json");
+
+        assert_eq!(State::read(&path).await.unwrap(), State::default());
+
+        let mut state = State::default();
+        state.remember(Path::new("/a"), Position::new(1, 2));
+        state.write(&path).await.unwrap();
+
+        let mut other = State::default();
+        other.remember(Path::new("/b"), Position::new(3, 4));
+        other.write(&path).await.unwrap();
+
+        let state = State::read(&path).await.unwrap();
+        assert_eq!(state.cursor(Path::new("/a")), Some(Position::new(1, 2)));
+        assert_eq!(state.cursor(Path::new("/b")), Some(Position::new(3, 4)));
+    }
+}
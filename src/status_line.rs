@@ -0,0 +1,226 @@
+//! The status line, which describes the current buffer.
+
+use std::path::Path;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::buffer::Indent;
+use crate::ui::{Context, Coordinates, Drawable, Style};
+
+/// Separates the path of the buffer and the names of the definitions enclosing the cursor.
+const BREADCRUMB_SEPARATOR: &str = " › ";
+
+/// Information about the current buffer, shown above the command line.
+pub struct StatusLine<'a> {
+    /// The path of the buffer's file, if any.
+    pub path: Option<&'a Path>,
+
+    /// Whether the buffer's file is written with a byte order mark.
+    pub bom: bool,
+
+    /// Whether the buffer shows its file as a hex dump.
+    pub binary: bool,
+
+    /// How the buffer's file was indented when it was opened.
+    pub indent: Option<Indent>,
+
+    /// The number of files that are still being opened in the background.
+    pub loading: usize,
+
+    /// The names of the functions and classes enclosing the cursor, from outermost to innermost.
+    pub breadcrumbs: Vec<String>,
+
+    /// The commit that last changed the cursor's line, shown at the right edge.
+    pub blame: Option<String>,
+}
+
+impl StatusLine<'_> {
+    fn text(&self) -> String {
+        let mut text = match self.path {
+            Some(path) => path.display().to_string(),
+            None => String::from("[No Name]"),
+        };
+
+        if self.bom {
+            text.push_str(" [BOM]");
+        }
+
+        if self.binary {
+            text.push_str(" [hex]");
+        }
+
+        if let Some(indent) = self.indent {
+            text.push_str(&format!(" [{}]", indent));
+        }
+
+        match self.loading {
+            0 => (),
+            1 => text.push_str(" [loading 1 file]"),
+            n => text.push_str(&format!(" [loading {} files]", n)),
+        }
+
+        for breadcrumb in &self.breadcrumbs {
+            text.push_str(BREADCRUMB_SEPARATOR);
+            text.push_str(breadcrumb);
+        }
+
+        text
+    }
+}
+
+impl Drawable for StatusLine<'_> {
+    fn draw(&self, ctx: &mut Context<'_>) {
+        if ctx.bounds.is_empty() {
+            return;
+        }
+
+        let text = self.text();
+        ctx.screen.write(ctx.bounds.min, &text);
+
+        // The blame is only shown if it fits beside the rest of the status line.
+        if let Some(blame) = &self.blame {
+            let used = text.width() + 1;
+            let blame_width = blame.width();
+            let width = usize::from(ctx.bounds.width());
+
+            if used + blame_width <= width {
+                let x = ctx.bounds.min.x + (width - blame_width) as u16;
+                ctx.screen
+                    .write(Coordinates::new(x, ctx.bounds.min.y), blame);
+            }
+        }
+
+        ctx.screen.apply_style(ctx.bounds, Style::default().bold());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::buffer::Indent;
+    use crate::ui::{Bounds, Context, Coordinates, Drawable, Screen, Size};
+
+    use super::StatusLine;
+
+    #[test]
+    fn text() {
+        let status_line = StatusLine {
+            path: Some(Path::new("src/lib.rs")),
+            bom: false,
+            binary: false,
+            indent: None,
+            loading: 0,
+            breadcrumbs: vec![String::from("Editor"), String::from("run")],
+            blame: None,
+        };
+        assert_eq!(status_line.text(), "src/lib.rs › Editor › run");
+
+        let status_line = StatusLine {
+            path: None,
+            bom: false,
+            binary: false,
+            indent: None,
+            loading: 0,
+            breadcrumbs: vec![],
+            blame: None,
+        };
+        assert_eq!(status_line.text(), "[No Name]");
+
+        let status_line = StatusLine {
+            path: Some(Path::new("notes.txt")),
+            bom: true,
+            binary: false,
+            indent: None,
+            loading: 0,
+            breadcrumbs: vec![String::from("Notes")],
+            blame: None,
+        };
+        assert_eq!(status_line.text(), "notes.txt [BOM] › Notes");
+
+        let status_line = StatusLine {
+            path: Some(Path::new("data.bin")),
+            bom: false,
+            binary: true,
+            indent: None,
+            loading: 0,
+            breadcrumbs: vec![],
+            blame: None,
+        };
+        assert_eq!(status_line.text(), "data.bin [hex]");
+
+        let status_line = StatusLine {
+            path: Some(Path::new("notes.txt")),
+            bom: false,
+            binary: false,
+            indent: None,
+            loading: 2,
+            breadcrumbs: vec![],
+            blame: None,
+        };
+        assert_eq!(status_line.text(), "notes.txt [loading 2 files]");
+
+        let status_line = StatusLine {
+            path: Some(Path::new("main.c")),
+            bom: false,
+            binary: false,
+            indent: Some(Indent::Tabs),
+            loading: 0,
+            breadcrumbs: vec![],
+            blame: None,
+        };
+        assert_eq!(status_line.text(), "main.c [tabs]");
+    }
+
+    #[test]
+    fn draw() {
+        let mut screen = Screen::new(Size::new(8, 2));
+        let mut ctx = Context {
+            bounds: Bounds::new(Coordinates::new(0, 1), Coordinates::new(8, 2)),
+            screen: &mut screen,
+        };
+
+        StatusLine {
+            path: Some(Path::new("main.rs")),
+            bom: false,
+            binary: false,
+            indent: None,
+            loading: 0,
+            breadcrumbs: vec![String::from("main")],
+            blame: None,
+        }
+        .draw(&mut ctx);
+
+        assert_eq!(screen[(0, 0)].c, None);
+        assert_eq!(screen[(1, 0)].c, Some('m'));
+        assert_eq!(screen[(1, 7)].c, Some(' '));
+        assert!(screen[(1, 7)].bold);
+    }
+
+    #[test]
+    fn draw_blame() {
+        let row = |blame: &str, width| {
+            let mut screen = Screen::new(Size::new(width, 1));
+            StatusLine {
+                path: Some(Path::new("main.rs")),
+                bom: false,
+                binary: false,
+                indent: None,
+                loading: 0,
+                breadcrumbs: vec![],
+                blame: Some(String::from(blame)),
+            }
+            .draw(&mut Context {
+                bounds: Bounds::from_size(screen.size),
+                screen: &mut screen,
+            });
+
+            (0..width)
+                .map(|x| screen[(0, x)].c.unwrap_or(' '))
+                .collect::<String>()
+        };
+
+        assert_eq!(row("abc Jane", 16), "main.rs abc Jane");
+        assert_eq!(row("abc Jane", 15), "main.rs        ");
+    }
+}
@@ -1,46 +1,325 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::RwLock;
 
+use glob::Pattern;
+use lazy_static::lazy_static;
+use log::*;
 use serde::{de, Deserialize, Deserializer};
-use strum::{EnumString, IntoStaticStr};
+use strum::{EnumIter, EnumString, IntoStaticStr};
+
+use crate::grammar;
+
+/// The number of lines at the start of a file that are inspected to identify its syntax.
+const SNIFF_LINES: usize = 50;
+
+lazy_static! {
+    /// File name patterns and the names of the languages that the user has mapped them to, in
+    /// order of precedence.
+    static ref FILETYPES: RwLock<Vec<(Pattern, String)>> = RwLock::new(vec![]);
+}
 
 /// Programming language or file format being edited in a buffer.
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, EnumString, IntoStaticStr)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, EnumIter, EnumString, IntoStaticStr)]
 pub enum Syntax {
+    #[strum(serialize = "bash")]
+    Bash,
+
+    #[strum(serialize = "c")]
+    C,
+
+    #[strum(serialize = "cpp")]
+    Cpp,
+
+    #[strum(serialize = "css")]
+    Css,
+
+    #[strum(serialize = "go")]
+    Go,
+
+    #[strum(serialize = "html")]
+    Html,
+
     #[strum(serialize = "javascript")]
     JavaScript,
 
+    #[strum(serialize = "json")]
+    Json,
+
+    #[strum(serialize = "markdown")]
+    Markdown,
+
+    #[strum(serialize = "python")]
+    Python,
+
     #[strum(serialize = "rust")]
     Rust,
+
+    #[strum(serialize = "toml")]
+    Toml,
+
+    #[strum(serialize = "typescript")]
+    TypeScript,
+
+    /// TypeScript with JSX.
+    #[strum(serialize = "typescriptreact")]
+    Tsx,
+
+    #[strum(serialize = "yaml")]
+    Yaml,
+
+    /// A language provided by a grammar in the user's config directory.
+    #[strum(disabled)]
+    Dynamic(&'static str),
 }
 
 impl Syntax {
-    /// Attempts to identify the syntax for a given file.
+    /// Attempts to identify the syntax for a given file from its path and the beginning of its
+    /// contents.
+    ///
+    /// The extension is preferred, but the contents are consulted when it's ambiguous (such as C
+    /// and C++ headers) or missing, as is common for scripts with a shebang line.
     ///
     /// If the syntax is unknown or unsupported, `None` is returned.
-    pub fn identify(path: impl AsRef<Path>) -> Option<Self> {
+    pub fn identify<'a>(
+        path: impl AsRef<Path>,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Option<Self> {
+        let filetypes = FILETYPES.read().expect("filetypes lock poisoned");
+        Syntax::identify_with(&filetypes, path, lines)
+    }
+
+    /// Identifies the syntax of a file like [`Syntax::identify`], using the given file type
+    /// mappings instead of the ones set by [`set_filetypes`].
+    fn identify_with<'a>(
+        filetypes: &[(Pattern, String)],
+        path: impl AsRef<Path>,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Option<Self> {
         let path = path.as_ref();
+        let lines = lines.into_iter().take(SNIFF_LINES).collect::<Vec<_>>();
+
+        if let Some(syntax) = Syntax::from_filetypes(filetypes, path) {
+            return Some(syntax);
+        }
 
-        if let Some(ext) = path.extension().and_then(OsStr::to_str) {
-            match ext {
-                "js" => return Some(Syntax::JavaScript),
-                "rs" => return Some(Syntax::Rust),
-                _ => (),
+        // Some well-known files don't have a meaningful extension.
+        match path.file_name().and_then(OsStr::to_str) {
+            Some("Cargo.lock") | Some("Pipfile") | Some("poetry.lock") => {
+                return Some(Syntax::Toml)
             }
+            _ => (),
+        }
+
+        let by_extension = path.extension().and_then(OsStr::to_str).and_then(|ext| {
+            match Syntax::from_extension(ext) {
+                Some(Syntax::C) if ext == "h" && looks_like_cpp(&lines) => Some(Syntax::Cpp),
+                Some(syntax) => Some(syntax),
+                None => grammar::user_language(ext).map(Syntax::Dynamic),
+            }
+        });
+
+        by_extension.or_else(|| Syntax::from_contents(&lines))
+    }
+
+    /// Attempts to identify the syntax using file type mappings, such as those in the user's
+    /// config.
+    fn from_filetypes(filetypes: &[(Pattern, String)], path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?;
+
+        let (pattern, name) = filetypes.iter().find(|(pattern, _)| {
+            // Patterns containing a separator match against the entire path.
+            if pattern.as_str().contains('/') {
+                pattern.matches_path(path)
+            } else {
+                pattern.matches_path(Path::new(file_name))
+            }
+        })?;
+
+        let syntax = Syntax::from_name(name);
+        if syntax.is_none() {
+            warn!("{} is mapped to unknown language: {}", pattern, name);
+        }
+
+        syntax
+    }
+
+    /// Attempts to identify the syntax from the first lines of a file, using its shebang line or
+    /// an Emacs-style mode line.
+    fn from_contents(lines: &[&str]) -> Option<Self> {
+        let first_line = lines.first()?.trim();
+
+        if let Some(shebang) = first_line.strip_prefix("#!") {
+            return Syntax::from_interpreter(interpreter(shebang)?);
+        }
+
+        if let Some(mode) = emacs_mode(first_line) {
+            return Syntax::from_name(mode);
+        }
+
+        if first_line.to_lowercase().starts_with("<!doctype html") {
+            return Some(Syntax::Html);
         }
 
         None
     }
 
+    /// Attempts to identify the syntax of scripts run by an interpreter, such as `python3`.
+    fn from_interpreter(interpreter: &str) -> Option<Self> {
+        // Strip version numbers, such as in `python3.9`.
+        let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        match name {
+            "python" | "pypy" => Some(Syntax::Python),
+            "node" | "nodejs" => Some(Syntax::JavaScript),
+            "deno" | "ts-node" => Some(Syntax::TypeScript),
+            "sh" | "bash" | "dash" | "ksh" | "zsh" => Some(Syntax::Bash),
+            _ => grammar::user_language(name).map(Syntax::Dynamic),
+        }
+    }
+
+    /// Attempts to identify the syntax from a language name, such as the info string of a fenced
+    /// code block in Markdown.
+    ///
+    /// Both language identifiers and common file extensions are accepted.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+
+        if let Ok(syntax) = name.parse() {
+            return Some(syntax);
+        }
+
+        match name.as_str() {
+            "c++" => Some(Syntax::Cpp),
+            "golang" => Some(Syntax::Go),
+            "shell" | "shellscript" => Some(Syntax::Bash),
+            _ => Syntax::from_extension(&name)
+                .or_else(|| grammar::user_language(&name).map(Syntax::Dynamic)),
+        }
+    }
+
+    fn from_extension(ext: &str) -> Option<Self> {
+        let syntax = match ext {
+            "sh" | "bash" => Syntax::Bash,
+            "c" | "h" => Syntax::C,
+            "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => Syntax::Cpp,
+            "css" => Syntax::Css,
+            "go" => Syntax::Go,
+            "htm" | "html" => Syntax::Html,
+            "js" => Syntax::JavaScript,
+            "json" => Syntax::Json,
+            "md" | "markdown" => Syntax::Markdown,
+            "py" => Syntax::Python,
+            "rs" => Syntax::Rust,
+            "toml" => Syntax::Toml,
+            "ts" => Syntax::TypeScript,
+            "tsx" => Syntax::Tsx,
+            "yaml" | "yml" => Syntax::Yaml,
+            _ => return None,
+        };
+
+        Some(syntax)
+    }
+
     /// Converts returns a syntax to a [LSP-compatible language identifier][language id].
     ///
     /// [language id]: https://microsoft.github.io/language-server-protocol/specifications/specification-current/#textDocumentItem
     pub fn into_language_id(self) -> &'static str {
-        self.into()
+        match self {
+            Syntax::Dynamic(name) => name,
+            _ => self.into(),
+        }
     }
 }
 
+/// Sets the user's mappings from file name patterns, such as `*.jsx` or `Justfile`, to language
+/// names. These take precedence over the built-in file types.
+///
+/// Exact file names are preferred over glob patterns, and longer patterns over shorter ones.
+pub fn set_filetypes(filetypes: &HashMap<String, String>) {
+    *FILETYPES.write().expect("filetypes lock poisoned") = filetype_patterns(filetypes);
+}
+
+/// Compiles file type mappings into patterns, in order of precedence.
+fn filetype_patterns(filetypes: &HashMap<String, String>) -> Vec<(Pattern, String)> {
+    let mut patterns = filetypes
+        .iter()
+        .filter_map(|(pattern, name)| match Pattern::new(pattern) {
+            Ok(pattern) => Some((pattern, name.clone())),
+            Err(e) => {
+                error!("invalid file type pattern {:?}: {}", pattern, e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    patterns.sort_by_key(|(pattern, _)| {
+        let pattern = pattern.as_str();
+        let is_glob = pattern.contains(&['*', '?', '['][..]);
+        (is_glob, Reverse(pattern.len()), pattern.to_owned())
+    });
+
+    patterns
+}
+
+/// Returns the name of the interpreter of a shebang line, without the leading `#!`.
+///
+/// Interpreters run through `env`, such as `/usr/bin/env node`, are resolved to the program that
+/// `env` runs.
+fn interpreter(shebang: &str) -> Option<&str> {
+    let mut args = shebang.split_whitespace();
+    let program = file_name(args.next()?);
+
+    if program == "env" {
+        args.find(|arg| !arg.starts_with('-') && !arg.contains('='))
+            .map(file_name)
+    } else {
+        Some(program)
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Returns the mode of an Emacs file variables line, such as `# -*- mode: python -*-`.
+fn emacs_mode(line: &str) -> Option<&str> {
+    let start = line.find("-*-")? + 3;
+    let end = start + line[start..].find("-*-")?;
+    let variables = line[start..end].trim();
+
+    if !variables.contains(':') {
+        // The entire line is the mode, as in `-*- python -*-`.
+        return Some(variables);
+    }
+
+    variables.split(';').find_map(|variable| {
+        let (name, value) = variable.split_at(variable.find(':')?);
+        if name.trim().eq_ignore_ascii_case("mode") {
+            Some(value[1..].trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns whether the contents of a header file appear to be C++ rather than C.
+fn looks_like_cpp(lines: &[&str]) -> bool {
+    lines.iter().map(|line| line.trim_start()).any(|line| {
+        line.starts_with("class ")
+            || line.starts_with("namespace ")
+            || line.starts_with("template")
+            || line.starts_with("public:")
+            || line.starts_with("private:")
+            || line.starts_with("protected:")
+            || line.contains("std::")
+            || (line.starts_with("#include <") && !line.contains(".h>"))
+    })
+}
+
 /// Used for deserializing [`crate::config::Config`].
 impl<'de> Deserialize<'de> for Syntax {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -48,6 +327,170 @@ impl<'de> Deserialize<'de> for Syntax {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        FromStr::from_str(&s).map_err(de::Error::custom)
+        FromStr::from_str(&s)
+            .ok()
+            .or_else(|| grammar::user_language(&s).map(Syntax::Dynamic))
+            .ok_or_else(|| de::Error::custom(format!("unknown language: {}", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use super::{filetype_patterns, Syntax};
+
+    #[test]
+    fn identify_by_extension() {
+        assert_eq!(Syntax::identify("main.rs", None), Some(Syntax::Rust));
+        assert_eq!(Syntax::identify("index.js", None), Some(Syntax::JavaScript));
+        assert_eq!(Syntax::identify("setup.py", None), Some(Syntax::Python));
+        assert_eq!(Syntax::identify("main.go", None), Some(Syntax::Go));
+        assert_eq!(Syntax::identify("stdio.h", None), Some(Syntax::C));
+        assert_eq!(Syntax::identify("main.c", None), Some(Syntax::C));
+        assert_eq!(Syntax::identify("vector.hpp", None), Some(Syntax::Cpp));
+        assert_eq!(Syntax::identify("main.cc", None), Some(Syntax::Cpp));
+        assert_eq!(Syntax::identify("index.ts", None), Some(Syntax::TypeScript));
+        assert_eq!(Syntax::identify("App.tsx", None), Some(Syntax::Tsx));
+        assert_eq!(Syntax::identify("README.md", None), Some(Syntax::Markdown));
+        assert_eq!(Syntax::identify("index.html", None), Some(Syntax::Html));
+        assert_eq!(Syntax::identify("style.css", None), Some(Syntax::Css));
+        assert_eq!(Syntax::identify("install.sh", None), Some(Syntax::Bash));
+        assert_eq!(Syntax::identify("README", None), None);
+    }
+
+    #[test]
+    fn identify_config_formats() {
+        assert_eq!(
+            Syntax::identify("/home/user/.config/editor/config.toml", None),
+            Some(Syntax::Toml)
+        );
+        assert_eq!(Syntax::identify("Cargo.lock", None), Some(Syntax::Toml));
+        assert_eq!(Syntax::identify("package.json", None), Some(Syntax::Json));
+        assert_eq!(Syntax::identify(".travis.yml", None), Some(Syntax::Yaml));
+        assert_eq!(
+            Syntax::identify("docker-compose.yaml", None),
+            Some(Syntax::Yaml)
+        );
+    }
+
+    #[test]
+    fn identify_by_name() {
+        assert_eq!(Syntax::from_name("rust"), Some(Syntax::Rust));
+        assert_eq!(Syntax::from_name("rs"), Some(Syntax::Rust));
+        assert_eq!(Syntax::from_name("JavaScript"), Some(Syntax::JavaScript));
+        assert_eq!(Syntax::from_name("c++"), Some(Syntax::Cpp));
+        assert_eq!(Syntax::from_name("brainfuck"), None);
+    }
+
+    #[test]
+    fn identify_by_shebang() {
+        assert_eq!(
+            Syntax::identify("bin/serve", Some("#!/usr/bin/env node")),
+            Some(Syntax::JavaScript)
+        );
+        assert_eq!(
+            Syntax::identify("manage", Some("#!/usr/bin/python3.9 -u")),
+            Some(Syntax::Python)
+        );
+        assert_eq!(
+            Syntax::identify("script", Some("#!/usr/bin/env -S deno run")),
+            Some(Syntax::TypeScript)
+        );
+        assert_eq!(Syntax::identify("configure", Some("#!/bin/perl")), None);
+
+        for shebang in &[
+            "#!/bin/sh",
+            "#!/usr/bin/env sh",
+            "#!/bin/dash -e",
+            "#!/usr/bin/env bash",
+            "#!/bin/bash",
+        ] {
+            assert_eq!(
+                Syntax::identify("install", Some(*shebang)),
+                Some(Syntax::Bash),
+                "{}",
+                shebang
+            );
+        }
+
+        // Extensions take precedence.
+        assert_eq!(
+            Syntax::identify("main.rs", Some("#!/usr/bin/env python")),
+            Some(Syntax::Rust)
+        );
+    }
+
+    #[test]
+    fn identify_by_contents() {
+        assert_eq!(
+            Syntax::identify("build", Some("# -*- mode: python; coding: utf-8 -*-")),
+            Some(Syntax::Python)
+        );
+        assert_eq!(
+            Syntax::identify("page", Some("<!DOCTYPE html>")),
+            Some(Syntax::Html)
+        );
+        assert_eq!(Syntax::identify("README", Some("# Title")), None);
+    }
+
+    #[test]
+    fn identify_header() {
+        assert_eq!(
+            Syntax::identify(
+                "stdio.h",
+                vec!["#include <stddef.h>", "int puts(const char *);"]
+            ),
+            Some(Syntax::C)
+        );
+        assert_eq!(
+            Syntax::identify("vector.h", vec!["#include <memory>", "namespace std {"]),
+            Some(Syntax::Cpp)
+        );
+    }
+
+    #[test]
+    fn language_ids() {
+        assert_eq!(Syntax::Cpp.into_language_id(), "cpp");
+        assert_eq!("python".parse(), Ok(Syntax::Python));
+        assert_eq!(Syntax::Tsx.into_language_id(), "typescriptreact");
+        assert_eq!("typescript".parse(), Ok(Syntax::TypeScript));
+        assert_eq!(Syntax::Dynamic("zig").into_language_id(), "zig");
+    }
+
+    #[test]
+    fn identify_by_filetypes() {
+        let filetypes = filetype_patterns(&hashmap! {
+            String::from("*.jsx") => String::from("javascript"),
+            String::from("*.conf") => String::from("toml"),
+            String::from("special.conf") => String::from("json"),
+            String::from("**/.config/app/*.rs") => String::from("python"),
+            String::from("Justfile") => String::from("make"),
+            String::from("[invalid") => String::from("rust"),
+        });
+
+        assert_eq!(
+            Syntax::identify_with(&filetypes, "App.jsx", None),
+            Some(Syntax::JavaScript)
+        );
+        assert_eq!(
+            Syntax::identify_with(&filetypes, "/etc/app.conf", None),
+            Some(Syntax::Toml)
+        );
+        assert_eq!(
+            Syntax::identify_with(&filetypes, "/etc/special.conf", None),
+            Some(Syntax::Json)
+        );
+        assert_eq!(
+            Syntax::identify_with(&filetypes, "/home/user/.config/app/init.rs", None),
+            Some(Syntax::Python)
+        );
+        assert_eq!(
+            Syntax::identify_with(&filetypes, "/home/user/init.rs", None),
+            Some(Syntax::Rust)
+        );
+
+        // Unknown languages fall back to the built-in file types.
+        assert_eq!(Syntax::identify_with(&filetypes, "Justfile", None), None);
     }
 }
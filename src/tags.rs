@@ -0,0 +1,235 @@
+//! Jumping to definitions with tags files generated by ctags, for `<C-]>` and `:tag`.
+//!
+//! Tags files list one tag per line: the name, the file that defines it, and an address that
+//! finds the definition in the file, separated by tabs. The address is either a line number or a
+//! search pattern like `/^fn main() {$/`. Anything after `;"` is extra information that is
+//! ignored. Paths are relative to the directory of the tags file.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use tokio::fs;
+use tokio::io;
+use tokio::task;
+
+/// The name of tags files, which are searched for in the working directory and its ancestors.
+const TAGS_FILE: &str = "tags";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no tags file")]
+    NoTagsFile,
+
+    #[error("unable to read {0}: {1}")]
+    Io(PathBuf, #[source] io::Error),
+}
+
+/// Where a tag is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub path: PathBuf,
+    pub address: Address,
+}
+
+/// How to find a tag's definition in its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// A zero-indexed line.
+    Line(usize),
+
+    /// A line matching a pattern.
+    Pattern(Pattern),
+}
+
+impl Address {
+    /// Returns the zero-indexed line that the address refers to in the text of its file.
+    pub fn find(&self, text: &str) -> Option<usize> {
+        match self {
+            Address::Line(line) => Some(*line),
+            Address::Pattern(pattern) => text.lines().position(|line| pattern.matches(line)),
+        }
+    }
+}
+
+/// A ctags search pattern. These are literal text, optionally anchored to the start and end of the
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    text: String,
+    start: bool,
+    end: bool,
+}
+
+impl Pattern {
+    /// Parses a pattern between `/` or `?` delimiters, unescaping the delimiter and backslashes.
+    fn parse(s: &str) -> Option<Self> {
+        let delimiter = s.chars().next().filter(|&c| c == '/' || c == '?')?;
+        let s = s[1..].strip_suffix(delimiter)?;
+
+        let (s, start) = match s.strip_prefix('^') {
+            Some(s) => (s, true),
+            None => (s, false),
+        };
+
+        let mut text = String::new();
+        let mut end = false;
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some(&escaped)) => {
+                    text.push(escaped);
+                    chars.next();
+                }
+                ('$', None) => end = true,
+                (c, _) => text.push(c),
+            }
+        }
+
+        Some(Pattern { text, start, end })
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        match (self.start, self.end) {
+            (true, true) => line == self.text,
+            (true, false) => line.starts_with(&self.text),
+            (false, true) => line.ends_with(&self.text),
+            (false, false) => line.contains(&self.text),
+        }
+    }
+}
+
+/// Returns the nearest tags file in a directory or its ancestors.
+pub async fn find_file(dir: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let path = dir.join(TAGS_FILE);
+        if let Ok(metadata) = fs::metadata(&path).await {
+            if metadata.is_file() {
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+/// Looks up the definitions of a tag in the nearest tags file to a directory.
+pub async fn lookup(dir: &Path, name: &str) -> Result<Vec<Tag>, Error> {
+    let path = find_file(dir).await.ok_or(Error::NoTagsFile)?;
+    let text = fs::read_to_string(&path)
+        .await
+        .map_err(|e| Error::Io(path.clone(), e))?;
+    let dir = path
+        .parent()
+        .expect("tags file should be in a directory")
+        .to_owned();
+    let name = name.to_owned();
+
+    // Tags files of large projects can be tens of megabytes.
+    let tags = task::spawn_blocking(move || parse(&text, &name, &dir))
+        .await
+        .expect("parsing tags file panicked");
+
+    Ok(tags)
+}
+
+/// Returns the definitions of a tag in the text of a tags file, resolving paths against a
+/// directory.
+fn parse(text: &str, name: &str, dir: &Path) -> Vec<Tag> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            if fields.next()? != name {
+                return None;
+            }
+
+            let path = dir.join(fields.next()?);
+            let address = fields.next()?;
+            let address = address
+                .find(";\"\t")
+                .map_or_else(|| address.trim_end_matches(";\""), |end| &address[..end]);
+
+            let address = match address.parse::<usize>() {
+                Ok(line) => Address::Line(line.saturating_sub(1)),
+                Err(_) => Address::Pattern(Pattern::parse(address)?),
+            };
+
+            Some(Tag { path, address })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::{lookup, parse, Address, Pattern, Tag};
+
+    const TAGS: &str = indoc!(
+        r#"
+        !_TAG_FILE_FORMAT	2	/extended format/
+        Buffer	src/buffer.rs	/^pub struct Buffer {$/;"	s
+        main	src/main.rs	/^fn main() {$/;"	f
+        main	tests/main.rs	12;"	f
+        path	src/lib.rs	/path\/to\/file/
+        "#
+    );
+
+    #[test]
+    fn parse_tags() {
+        let dir = Path::new("/project");
+
+        assert_eq!(
+            parse(TAGS, "main", dir),
+            vec![
+                Tag {
+                    path: dir.join("src/main.rs"),
+                    address: Address::Pattern(Pattern {
+                        text: String::from("fn main() {"),
+                        start: true,
+                        end: true,
+                    }),
+                },
+                Tag {
+                    path: dir.join("tests/main.rs"),
+                    address: Address::Line(11),
+                },
+            ]
+        );
+        assert_eq!(parse(TAGS, "mai", dir), vec![]);
+
+        let tags = parse(TAGS, "path", dir);
+        assert_eq!(
+            tags[0].address,
+            Address::Pattern(Pattern {
+                text: String::from("path/to/file"),
+                start: false,
+                end: false,
+            })
+        );
+    }
+
+    #[test]
+    fn find_definition() {
+        let text = "use std::io;\n\nfn main() {\n}\n";
+
+        let tags = parse(TAGS, "main", Path::new("/"));
+        assert_eq!(tags[0].address.find(text), Some(2));
+        assert_eq!(tags[1].address.find(text), Some(11));
+
+        let tags = parse(TAGS, "Buffer", Path::new("/"));
+        assert_eq!(tags[0].address.find(text), None);
+    }
+
+    #[tokio::test]
+    async fn lookup_in_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("tags"), TAGS).unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+
+        let tags = lookup(&dir.path().join("src"), "Buffer").await.unwrap();
+        assert_eq!(tags[0].path, dir.path().join("src/buffer.rs"));
+    }
+}
@@ -5,22 +5,37 @@ use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::thread;
 
 use anyhow::{Context, Error};
-use libc::STDOUT_FILENO;
+use libc::{STDIN_FILENO, STDOUT_FILENO};
 use log::*;
 use nix::ioctl_read_bad;
 use terminfo::{capability as cap, expand};
 use tokio::fs::File;
 use tokio::io::{self, AsyncWriteExt, BufWriter};
+use unicode_width::UnicodeWidthChar;
 
-use crate::ui::{Coordinates, Screen, Size};
+use crate::ui::{Color, Coordinates, Screen, Size, Style};
 
+mod capabilities;
 mod input;
 
+pub use capabilities::Capabilities;
 pub use input::{Key, Stdin};
 
+/// Asks the terminal to report when it gains and loses focus.
+const ENABLE_FOCUS_REPORTING: &[u8] = b"\x1b[?1004h";
+
+const DISABLE_FOCUS_REPORTING: &[u8] = b"\x1b[?1004l";
+
+/// Asks the terminal to mark the start and end of pasted text, so that a paste can be applied all
+/// at once instead of as typed keys.
+const ENABLE_BRACKETED_PASTE: &[u8] = b"\x1b[?2004h";
+
+const DISABLE_BRACKETED_PASTE: &[u8] = b"\x1b[?2004l";
+
 pub struct Terminal {
     terminfo: terminfo::Database,
     stdout: BufWriter<File>,
+    capabilities: Capabilities,
     /// The screen that should be drawn on the next refresh.
     back: Screen,
     pub cursor: Coordinates,
@@ -32,15 +47,27 @@ impl Terminal {
 
         let terminfo = terminfo::Database::from_env().context("failed to initialize terminfo")?;
 
+        // The terminal is asked before anything else is written, so that its replies aren't
+        // mixed up with other output.
+        let mut capabilities = Capabilities::from_env(&terminfo);
+        if let Err(e) = capabilities.query(STDIN_FILENO, STDOUT_FILENO) {
+            info!("unable to query terminal capabilities: {}", e);
+        }
+        info!("terminal capabilities: {:?}", capabilities);
+
         if let Some(smcup) = terminfo.get::<cap::EnterCaMode>() {
             stdout.write_all(smcup.as_ref()).await?;
         }
 
+        stdout.write_all(ENABLE_FOCUS_REPORTING).await?;
+        stdout.write_all(ENABLE_BRACKETED_PASTE).await?;
+
         let size = get_size(stdout.as_raw_fd())?;
 
         Ok(Terminal {
             terminfo,
             stdout: BufWriter::new(stdout),
+            capabilities,
             back: Screen::new(size),
             cursor: Coordinates::zero(),
         })
@@ -49,7 +76,8 @@ impl Terminal {
     /// Returns a sequence of bytes that can be used to restore the terminal to its original state.
     /// This does *not* include the TTY settings, `input::Stdin` is responsible for that.
     pub fn restore_sequence(&self) -> Vec<u8> {
-        let mut seq = vec![];
+        let mut seq = DISABLE_FOCUS_REPORTING.to_vec();
+        seq.extend_from_slice(DISABLE_BRACKETED_PASTE);
 
         if let Some(rmcup) = self.terminfo.get::<cap::ExitCaMode>() {
             seq.extend_from_slice(rmcup.as_ref());
@@ -66,6 +94,16 @@ impl Terminal {
         seq
     }
 
+    /// Returns the terminfo database describing the terminal.
+    pub fn terminfo(&self) -> &terminfo::Database {
+        &self.terminfo
+    }
+
+    /// Returns the colors and text attributes that the terminal supports.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
     pub fn screen(&mut self) -> &mut Screen {
         &mut self.back
     }
@@ -74,8 +112,10 @@ impl Terminal {
         self.back.size
     }
 
+    /// Reads the size of the terminal again, resizing the screen to match. The screen is cleared,
+    /// so the next refresh draws everything.
     pub fn refresh_size(&mut self) -> Result<Size, Error> {
-        self.back.size = get_size(self.stdout.get_ref().as_raw_fd())?;
+        self.back = Screen::new(get_size(self.stdout.get_ref().as_raw_fd())?);
         Ok(self.size())
     }
 
@@ -86,36 +126,70 @@ impl Terminal {
             self.stdout.write_all(cl.as_ref()).await?;
         }
 
-        let mut last_color = None;
+        let mut last_style = Style::default();
 
         {
             let mut rows = self.back.iter_rows().peekable();
             while let Some(row) = rows.next() {
+                // The number of following cells that are covered by a wide character.
+                let mut covered = 0;
+
                 for col in row {
-                    if col.color != last_color {
-                        match col.color {
-                            Some(color) => {
-                                self.stdout
-                                    .write_all(
-                                        format!("\x1b[38;2;{};{};{}m", color.r, color.g, color.b)
-                                            .as_bytes(),
-                                    )
-                                    .await?;
+                    let style = col.style();
+
+                    if style != last_style {
+                        // Attributes can't be turned off individually, so start from scratch.
+                        let sgr0 = self.terminfo.get::<cap::ExitAttributeMode>().unwrap();
+                        self.stdout.write_all(sgr0.as_ref()).await?;
+
+                        if let Some(color) = style.foreground {
+                            let sgr = color_sgr(self.capabilities, 38, color);
+                            self.stdout.write_all(sgr.as_bytes()).await?;
+                        }
+
+                        if let Some(color) = style.background {
+                            let sgr = color_sgr(self.capabilities, 48, color);
+                            self.stdout.write_all(sgr.as_bytes()).await?;
+                        }
+
+                        if style.bold {
+                            if let Some(bold) = self.terminfo.get::<cap::EnterBoldMode>() {
+                                self.stdout.write_all(bold.as_ref()).await?;
                             }
-                            None => {
-                                let sgr0 = self.terminfo.get::<cap::ExitAttributeMode>().unwrap();
-                                self.stdout.write_all(sgr0.as_ref()).await?;
+                        }
+
+                        if style.italic && self.capabilities.italic {
+                            if let Some(italic) = self.terminfo.get::<cap::EnterItalicsMode>() {
+                                self.stdout.write_all(italic.as_ref()).await?;
+                            }
+                        }
+
+                        if style.underline {
+                            if let Some(underline) = self.terminfo.get::<cap::EnterUnderlineMode>()
+                            {
+                                self.stdout.write_all(underline.as_ref()).await?;
                             }
                         }
 
-                        last_color = col.color;
+                        last_style = style;
                     }
 
-                    if let Some(c) = col.c {
-                        let mut buf = [0; 4];
-                        self.stdout
-                            .write_all(c.encode_utf8(&mut buf).as_bytes())
-                            .await?;
+                    match col.c {
+                        Some(c) => {
+                            let mut buf = [0; 4];
+                            self.stdout
+                                .write_all(c.encode_utf8(&mut buf).as_bytes())
+                                .await?;
+
+                            covered = c.width().unwrap_or(0).saturating_sub(1);
+                        }
+                        // Empty cells must be written to fill them with the background color,
+                        // unless they're covered by a preceding wide character.
+                        None if covered > 0 => covered -= 1,
+                        None if style.background.is_some() => {
+                            self.stdout.write_all(b" ").await?;
+                        }
+                        None => (),
                     }
                 }
 
@@ -167,6 +241,16 @@ impl Drop for Terminal {
     }
 }
 
+/// Returns the Select Graphic Rendition sequence that sets the foreground (38) or background (48)
+/// color, approximating it with the 256-color palette if the terminal doesn't support truecolor.
+fn color_sgr(capabilities: Capabilities, parameter: u8, color: Color) -> String {
+    if capabilities.truecolor {
+        format!("\x1b[{};2;{};{};{}m", parameter, color.r, color.g, color.b)
+    } else {
+        format!("\x1b[{};5;{}m", parameter, color.to_ansi256())
+    }
+}
+
 /// Queries the terminal size on a file descriptor.
 fn get_size(fd: RawFd) -> nix::Result<Size> {
     ioctl_read_bad!(tiocgwinsz, libc::TIOCGWINSZ, libc::winsize);
@@ -0,0 +1,173 @@
+//! Detecting the colors and text attributes that the terminal supports.
+//!
+//! terminfo entries are often missing capabilities that the terminal has, so they're combined
+//! with `COLORTERM` and with the terminal's own answers. At startup, the terminal is asked about
+//! each capability with XTGETTCAP, followed by a primary device attributes (DA1) request. Every
+//! terminal answers DA1, so its answer means that there are no more replies to wait for, even from
+//! terminals that ignore XTGETTCAP.
+
+use std::env;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use log::*;
+use nix::poll::{self, PollFd, PollFlags};
+use nix::unistd;
+use terminfo::{capability as cap, Database};
+
+/// How long to wait for the terminal to answer, in case it doesn't answer DA1 either.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The terminfo names of the capabilities that the terminal is asked about.
+const QUERIED: &[&str] = &["Tc", "RGB", "sitm", "Smulx"];
+
+/// The colors and text attributes that the terminal supports.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether colors may be 24-bit. Otherwise, they are approximated with the 256-color palette.
+    pub truecolor: bool,
+
+    pub italic: bool,
+
+    /// Whether underlines may be curly.
+    pub undercurl: bool,
+}
+
+impl Capabilities {
+    /// Returns the capabilities that `COLORTERM` and the terminfo entry claim.
+    pub fn from_env(terminfo: &Database) -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+
+        Capabilities {
+            truecolor: colorterm == "truecolor"
+                || colorterm == "24bit"
+                || matches!(terminfo.get::<cap::TrueColor>(), Some(cap::TrueColor(true)))
+                || terminfo.raw("RGB").is_some(),
+            italic: terminfo.get::<cap::EnterItalicsMode>().is_some(),
+            undercurl: terminfo.raw("Smulx").is_some() || terminfo.raw("Su").is_some(),
+        }
+    }
+
+    /// Asks the terminal which capabilities it has, adding the ones that it reports.
+    ///
+    /// The input must be in raw mode, and must not be read by anything else until this returns.
+    pub fn query(&mut self, input: RawFd, output: RawFd) -> nix::Result<()> {
+        let mut request = QUERIED
+            .iter()
+            .map(|name| format!("\x1bP+q{}\x1b\\", hex(name)))
+            .collect::<String>();
+        request.push_str("\x1b[c");
+        write_all(output, request.as_bytes())?;
+
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        let mut replies = vec![];
+        while !has_device_attributes(&replies) {
+            let timeout = deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis();
+            let mut fds = [PollFd::new(input, PollFlags::POLLIN)];
+            if timeout == 0 || poll::poll(&mut fds, timeout as i32)? == 0 {
+                info!("terminal did not answer capability queries");
+                break;
+            }
+
+            let mut buf = [0; 1024];
+            let len = unistd::read(input, &mut buf)?;
+            if len == 0 {
+                break;
+            }
+            replies.extend_from_slice(&buf[..len]);
+        }
+
+        for name in supported(&replies) {
+            match name.as_str() {
+                "Tc" | "RGB" => self.truecolor = true,
+                "sitm" => self.italic = true,
+                "Smulx" => self.undercurl = true,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_all(fd: RawFd, mut bytes: &[u8]) -> nix::Result<()> {
+    while !bytes.is_empty() {
+        let written = unistd::write(fd, bytes)?;
+        bytes = &bytes[written..];
+    }
+
+    Ok(())
+}
+
+/// Returns whether the replies include the answer to DA1, like `\x1b[?62;22c`.
+fn has_device_attributes(replies: &[u8]) -> bool {
+    String::from_utf8_lossy(replies)
+        .split("\x1b[?")
+        .skip(1)
+        .any(|reply| {
+            reply
+                .trim_start_matches(|c: char| c.is_ascii_digit() || c == ';')
+                .starts_with('c')
+        })
+}
+
+/// Returns the names of the capabilities that XTGETTCAP replies say are supported. A supported
+/// capability is reported like `\x1bP1+r<name>=<value>\x1b\\`, with the name and value in hex.
+fn supported(replies: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(replies)
+        .split("\x1bP1+r")
+        .skip(1)
+        .filter_map(|reply| {
+            let end = reply.find(|c| c == '=' || c == '\x1b')?;
+            unhex(&reply[..end])
+        })
+        .collect()
+}
+
+fn hex(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<String> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    let bytes = (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_device_attributes, hex, supported, unhex};
+
+    #[test]
+    fn hex_names() {
+        assert_eq!(hex("Tc"), "5463");
+        assert_eq!(unhex("5463").as_deref(), Some("Tc"));
+        assert_eq!(unhex("546"), None);
+        assert_eq!(unhex("zz"), None);
+    }
+
+    #[test]
+    fn device_attributes() {
+        assert!(has_device_attributes(b"\x1b[?62;22c"));
+        assert!(has_device_attributes(b"\x1bP1+r5463\x1b\\\x1b[?1;2c"));
+        assert!(!has_device_attributes(b"\x1b[?62;22"));
+        assert!(!has_device_attributes(b"\x1bP1+r5463\x1b\\"));
+    }
+
+    #[test]
+    fn supported_capabilities() {
+        let replies =
+            b"\x1bP1+r5463\x1b\\\x1bP0+r524742\x1b\\\x1bP1+r7369746D=1B5B336D\x1b\\\x1b[?62c";
+
+        assert_eq!(supported(replies), vec!["Tc", "sitm"]);
+        assert!(supported(b"\x1b[?62c").is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt::{self, Display};
 use std::os::unix::io::FromRawFd;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -16,7 +17,7 @@ use tokio::fs::File;
 use tokio::io;
 use tokio_util::codec::{Decoder, FramedRead};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Key {
     ArrowUp,
     ArrowDown,
@@ -27,6 +28,39 @@ pub enum Key {
     Backspace,
     Return,
     Esc,
+
+    /// The terminal gained focus. Reported instead of a key once focus reporting is enabled.
+    FocusGained,
+
+    /// The terminal lost focus.
+    FocusLost,
+
+    /// Text is being pasted into the terminal. The keys of the text follow, then `PasteEnd`.
+    /// Reported once bracketed paste is enabled.
+    PasteStart,
+
+    PasteEnd,
+}
+
+/// Keys are displayed in the notation that key chords are written in, such as `<C-w>` or `<CR>`.
+impl Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::ArrowUp => f.write_str("<Up>"),
+            Key::ArrowDown => f.write_str("<Down>"),
+            Key::ArrowLeft => f.write_str("<Left>"),
+            Key::ArrowRight => f.write_str("<Right>"),
+            Key::Char('<') => f.write_str("<lt>"),
+            Key::Char(' ') => f.write_str("<Space>"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Ctrl('i') => f.write_str("<Tab>"),
+            Key::Ctrl(c) => write!(f, "<C-{}>", c),
+            Key::Backspace => f.write_str("<BS>"),
+            Key::Return => f.write_str("<CR>"),
+            Key::Esc => f.write_str("<Esc>"),
+            Key::FocusGained | Key::FocusLost | Key::PasteStart | Key::PasteEnd => Ok(()),
+        }
+    }
 }
 
 lazy_static! {
@@ -55,6 +89,10 @@ lazy_static! {
             b"B" => ArrowDown,
             b"C" => ArrowRight,
             b"D" => ArrowLeft,
+            b"I" => FocusGained,
+            b"O" => FocusLost,
+            b"200~" => PasteStart,
+            b"201~" => PasteEnd,
         }
     };
 }
@@ -74,8 +112,10 @@ impl KeyCodec {
         #[allow(clippy::match_overlapping_arm)] // rust-lang/rust-clippy#6603
         match byte {
             b'\x0D' => Key::Return,
+            b'\x00' => Key::Ctrl('@'),
             b'\x01'..=b'\x1A' => Key::Ctrl((byte | 0x60) as char),
             b'\x1b' => Key::Esc,
+            b'\x1c'..=b'\x1f' => Key::Ctrl((byte | 0x40) as char),
             b'\x7f' => Key::Backspace,
             _ => Key::Char(byte as char),
         }
@@ -191,6 +231,22 @@ mod tests {
 
     use super::{Key, KeyCodec};
 
+    #[test]
+    fn display_keys() {
+        let keys = [
+            Key::Char('d'),
+            Key::Char('<'),
+            Key::Char(' '),
+            Key::Ctrl('w'),
+            Key::Ctrl('i'),
+            Key::Return,
+            Key::ArrowUp,
+        ];
+        let displayed = keys.iter().map(ToString::to_string).collect::<String>();
+
+        assert_eq!(displayed, "d<lt><Space><C-w><Tab><CR><Up>");
+    }
+
     #[tokio::test]
     async fn decode_char() {
         let keys: Vec<Key> = FramedRead::new(Cursor::new(b"a"), KeyCodec)
@@ -209,6 +265,20 @@ mod tests {
             .unwrap();
 
         assert_eq!(keys, vec![Key::Ctrl('a')]);
+
+        let keys: Vec<Key> = FramedRead::new(Cursor::new(b"\x1d"), KeyCodec)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(keys, vec![Key::Ctrl(']')]);
+
+        let keys: Vec<Key> = FramedRead::new(Cursor::new(b"\x00"), KeyCodec)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(keys, vec![Key::Ctrl('@')]);
     }
 
     #[tokio::test]
@@ -221,6 +291,35 @@ mod tests {
         assert_eq!(keys, vec![Key::Esc]);
     }
 
+    #[tokio::test]
+    async fn decode_focus() {
+        let keys: Vec<Key> = FramedRead::new(Cursor::new(b"\x1b[O"), KeyCodec)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(keys, vec![Key::FocusLost]);
+    }
+
+    #[tokio::test]
+    async fn decode_paste() {
+        let keys: Vec<Key> = FramedRead::new(Cursor::new(b"\x1b[200~a\rb\x1b[201~"), KeyCodec)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            keys,
+            vec![
+                Key::PasteStart,
+                Key::Char('a'),
+                Key::Return,
+                Key::Char('b'),
+                Key::PasteEnd
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn decode_escape_seq() {
         let keys: Vec<Key> = FramedRead::new(Cursor::new(b"\x1b[A"), KeyCodec)
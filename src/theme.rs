@@ -0,0 +1,258 @@
+//! Color schemes for syntax highlighting.
+//!
+//! A color scheme maps the names of highlight query captures, such as `keyword` or
+//! `function.macro`, to styles. The active color scheme may be changed at runtime with the
+//! `:colorscheme` command.
+//!
+//! Diagnostics are styled with the `diagnostic.<severity>` names, such as `diagnostic.error`, the
+//! lines of diffs with `diff.plus`, `diff.minus` and `diff.hunk`, the lines of side-by-side diffs
+//! with `diff.added`, `diff.changed` and `diff.deleted`, text that was just yanked with `yank`,
+//! text selected in visual mode with `visual`, the snippet placeholder that the cursor was moved
+//! to with `placeholder`, and line numbers with `line-number`. The line and column of the cursor
+//! are shaded with `cursorline` and `cursorcolumn`, and the columns in the `colorcolumn` option
+//! with `colorcolumn`.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use maplit::hashmap;
+use thiserror::Error;
+
+use crate::ui::{Color, Style};
+
+/// The name of the color scheme that is active on startup.
+pub const DEFAULT_COLOR_SCHEME: &str = "default";
+
+lazy_static! {
+    static ref COLOR_SCHEMES: Vec<ColorScheme> = vec![
+        ColorScheme {
+            name: DEFAULT_COLOR_SCHEME,
+            normal: Style::default(),
+            styles: hashmap! {
+                "attribute" => Style::fg(Color::new(0xff, 0x00, 0x00)),
+                "colorcolumn" => Style::default().on(Color::new(0x3a, 0x3a, 0x3a)),
+                "comment" => Style::fg(Color::new(0x4e, 0x4e, 0x4e)),
+                "constant" => Style::fg(Color::new(0x00, 0x87, 0x87)),
+                "cursorcolumn" => Style::default().on(Color::new(0x26, 0x26, 0x26)),
+                "cursorline" => Style::default().on(Color::new(0x26, 0x26, 0x26)),
+                "diagnostic.error" => Style::fg(Color::new(0xff, 0x5f, 0x5f)).underline(),
+                "diagnostic.hint" => Style::fg(Color::new(0x8a, 0x8a, 0x8a)).underline(),
+                "diagnostic.information" => Style::fg(Color::new(0x5f, 0xaf, 0xff)).underline(),
+                "diagnostic.warning" => Style::fg(Color::new(0xff, 0xaf, 0x00)).underline(),
+                "diff.added" => Style::default().on(Color::new(0x00, 0x5f, 0x00)),
+                "diff.changed" => Style::default().on(Color::new(0x00, 0x00, 0x87)),
+                "diff.deleted" => Style::default().on(Color::new(0x5f, 0x00, 0x00)),
+                "diff.hunk" => Style::fg(Color::new(0x00, 0xaf, 0xaf)),
+                "diff.minus" => Style::fg(Color::new(0xff, 0x5f, 0x5f)),
+                "diff.plus" => Style::fg(Color::new(0x5f, 0xd7, 0x5f)),
+                "escape" => Style::fg(Color::new(0xff, 0xd7, 0x00)),
+                "function" => Style::fg(Color::new(0xff, 0x87, 0x00)),
+                "function.macro" => Style::fg(Color::new(0xff, 0x00, 0x00)),
+                "keyword" => Style::fg(Color::new(0xff, 0xff, 0x00)),
+                "label" => Style::fg(Color::new(0xff, 0xff, 0x00)),
+                "line-number" => Style::fg(Color::new(0x6c, 0x6c, 0x6c)),
+                "menu" => Style::fg(Color::new(0xd0, 0xd0, 0xd0)).on(Color::new(0x3a, 0x3a, 0x3a)),
+                "menu.selected" => {
+                    Style::fg(Color::new(0x00, 0x00, 0x00)).on(Color::new(0x87, 0xaf, 0xd7))
+                },
+                "number" => Style::fg(Color::new(0x00, 0x87, 0x87)),
+                "operator" => Style::fg(Color::new(0xff, 0xff, 0x00)),
+                "placeholder" => Style::default().on(Color::new(0x44, 0x44, 0x44)),
+                "string" => Style::fg(Color::new(0x5f, 0x87, 0xd7)),
+                "type" => Style::fg(Color::new(0x00, 0xff, 0x00)),
+                "variable.parameter" => Style::fg(Color::new(0xd7, 0x87, 0xff)),
+                "visual" => Style::default().on(Color::new(0x44, 0x44, 0x44)),
+                "yank" => Style::default().on(Color::new(0x5f, 0x5f, 0x87)),
+            },
+        },
+        ColorScheme {
+            name: "light",
+            normal: Style::fg(Color::new(0x1c, 0x1c, 0x1c)).on(Color::new(0xff, 0xff, 0xff)),
+            styles: hashmap! {
+                "attribute" => Style::fg(Color::new(0x87, 0x00, 0x00)),
+                "colorcolumn" => Style::default().on(Color::new(0xee, 0xee, 0xee)),
+                "comment" => Style::fg(Color::new(0x8a, 0x8a, 0x8a)).italic(),
+                "constant" => Style::fg(Color::new(0x00, 0x5f, 0x87)),
+                "cursorcolumn" => Style::default().on(Color::new(0xee, 0xee, 0xee)),
+                "cursorline" => Style::default().on(Color::new(0xee, 0xee, 0xee)),
+                "diagnostic.error" => Style::fg(Color::new(0xd7, 0x00, 0x00)).underline(),
+                "diagnostic.hint" => Style::fg(Color::new(0x6c, 0x6c, 0x6c)).underline(),
+                "diagnostic.information" => Style::fg(Color::new(0x00, 0x5f, 0xd7)).underline(),
+                "diagnostic.warning" => Style::fg(Color::new(0xaf, 0x87, 0x00)).underline(),
+                "diff.added" => Style::default().on(Color::new(0xd7, 0xff, 0xd7)),
+                "diff.changed" => Style::default().on(Color::new(0xd7, 0xd7, 0xff)),
+                "diff.deleted" => Style::default().on(Color::new(0xff, 0xd7, 0xd7)),
+                "diff.hunk" => Style::fg(Color::new(0x00, 0x5f, 0x87)),
+                "diff.minus" => Style::fg(Color::new(0xaf, 0x00, 0x00)),
+                "diff.plus" => Style::fg(Color::new(0x00, 0x87, 0x00)),
+                "escape" => Style::fg(Color::new(0xaf, 0x5f, 0x00)),
+                "function" => Style::fg(Color::new(0x00, 0x5f, 0xaf)),
+                "function.macro" => Style::fg(Color::new(0x87, 0x00, 0x87)),
+                "keyword" => Style::fg(Color::new(0x87, 0x00, 0xaf)).bold(),
+                "label" => Style::fg(Color::new(0x87, 0x00, 0xaf)),
+                "line-number" => Style::fg(Color::new(0xa8, 0xa8, 0xa8)),
+                "menu" => Style::fg(Color::new(0x1c, 0x1c, 0x1c)).on(Color::new(0xe4, 0xe4, 0xe4)),
+                "menu.selected" => {
+                    Style::fg(Color::new(0xff, 0xff, 0xff)).on(Color::new(0x00, 0x5f, 0xaf))
+                },
+                "number" => Style::fg(Color::new(0x00, 0x5f, 0x87)),
+                "operator" => Style::fg(Color::new(0x5f, 0x5f, 0x5f)),
+                "placeholder" => Style::default().on(Color::new(0xd0, 0xd0, 0xd0)),
+                "string" => Style::fg(Color::new(0x00, 0x87, 0x00)),
+                "type" => Style::fg(Color::new(0x00, 0x87, 0x87)),
+                "variable.parameter" => Style::fg(Color::new(0xaf, 0x5f, 0x00)),
+                "visual" => Style::default().on(Color::new(0xd0, 0xd0, 0xd0)),
+                "yank" => Style::default().on(Color::new(0xff, 0xd7, 0x87)),
+            },
+        },
+        ColorScheme {
+            name: "high-contrast",
+            normal: Style::fg(Color::new(0xff, 0xff, 0xff)).on(Color::new(0x00, 0x00, 0x00)),
+            styles: hashmap! {
+                "attribute" => Style::fg(Color::new(0xff, 0x5f, 0xff)),
+                "colorcolumn" => Style::default().on(Color::new(0x3a, 0x3a, 0x3a)),
+                "comment" => Style::fg(Color::new(0x00, 0xff, 0x00)).italic(),
+                "constant" => Style::fg(Color::new(0x00, 0xff, 0xff)).bold(),
+                "cursorcolumn" => Style::default().on(Color::new(0x26, 0x26, 0x26)),
+                "cursorline" => Style::default().on(Color::new(0x26, 0x26, 0x26)),
+                "diagnostic.error" => Style::fg(Color::new(0xff, 0x00, 0x00)).bold().underline(),
+                "diagnostic.hint" => Style::fg(Color::new(0xff, 0xff, 0xff)).underline(),
+                "diagnostic.information" => Style::fg(Color::new(0x00, 0xff, 0xff)).underline(),
+                "diagnostic.warning" => Style::fg(Color::new(0xff, 0xff, 0x00)).bold().underline(),
+                "diff.added" => Style::default().on(Color::new(0x00, 0x87, 0x00)),
+                "diff.changed" => Style::default().on(Color::new(0x00, 0x00, 0xd7)),
+                "diff.deleted" => Style::default().on(Color::new(0x87, 0x00, 0x00)),
+                "diff.hunk" => Style::fg(Color::new(0x00, 0xff, 0xff)).bold(),
+                "diff.minus" => Style::fg(Color::new(0xff, 0x00, 0x00)).bold(),
+                "diff.plus" => Style::fg(Color::new(0x00, 0xff, 0x00)).bold(),
+                "escape" => {
+                    Style::fg(Color::new(0x00, 0x00, 0x00)).on(Color::new(0xff, 0xff, 0x00))
+                },
+                "function" => Style::fg(Color::new(0x5f, 0xd7, 0xff)).bold(),
+                "function.macro" => Style::fg(Color::new(0xff, 0x5f, 0xff)).bold(),
+                "keyword" => Style::fg(Color::new(0xff, 0xff, 0x00)).bold(),
+                "label" => Style::fg(Color::new(0xff, 0xff, 0x00)),
+                "line-number" => Style::fg(Color::new(0xff, 0xff, 0x00)),
+                "menu" => Style::fg(Color::new(0xff, 0xff, 0xff)).on(Color::new(0x00, 0x00, 0x87)),
+                "menu.selected" => {
+                    Style::fg(Color::new(0x00, 0x00, 0x00)).on(Color::new(0xff, 0xff, 0x00)).bold()
+                },
+                "number" => Style::fg(Color::new(0x00, 0xff, 0xff)),
+                "operator" => Style::fg(Color::new(0xff, 0xff, 0x00)),
+                "placeholder" => {
+                    Style::fg(Color::new(0x00, 0x00, 0x00)).on(Color::new(0xff, 0xff, 0xff))
+                },
+                "string" => Style::fg(Color::new(0xff, 0xaf, 0x00)),
+                "type" => Style::fg(Color::new(0x5f, 0xff, 0x5f)).bold(),
+                "variable.parameter" => Style::fg(Color::new(0xff, 0xff, 0xff)).italic(),
+                "visual" => {
+                    Style::fg(Color::new(0x00, 0x00, 0x00)).on(Color::new(0xff, 0xff, 0xff))
+                },
+                "yank" => Style::fg(Color::new(0x00, 0x00, 0x00)).on(Color::new(0xff, 0xff, 0x00)),
+            },
+        },
+    ];
+    static ref CURRENT: RwLock<&'static ColorScheme> =
+        RwLock::new(color_scheme(DEFAULT_COLOR_SCHEME).expect("missing default color scheme"));
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("unknown color scheme: {0}")]
+pub struct UnknownColorScheme(String);
+
+/// A set of styles for highlighting.
+#[derive(Debug)]
+pub struct ColorScheme {
+    pub name: &'static str,
+
+    /// The style of text that isn't highlighted.
+    pub normal: Style,
+
+    /// Map of capture names to styles.
+    styles: HashMap<&'static str, Style>,
+}
+
+impl ColorScheme {
+    /// Returns the style for a capture name.
+    ///
+    /// If there is no style for the exact name, the name is shortened one component at a time. For
+    /// example, `function.method` falls back to `function`.
+    pub fn style(&self, name: &str) -> Option<Style> {
+        if let Some(style) = self.styles.get(name) {
+            return Some(*style);
+        }
+
+        name.rmatch_indices('.')
+            .find_map(|(pos, _)| self.styles.get(&name[..pos]))
+            .copied()
+    }
+}
+
+/// Returns the bundled color scheme with the given name.
+pub fn color_scheme(name: &str) -> Option<&'static ColorScheme> {
+    COLOR_SCHEMES.iter().find(|scheme| scheme.name == name)
+}
+
+/// Returns the active color scheme.
+pub fn current() -> &'static ColorScheme {
+    *CURRENT.read().expect("color scheme lock poisoned")
+}
+
+/// Changes the active color scheme.
+pub fn set_current(name: &str) -> Result<(), UnknownColorScheme> {
+    let scheme = color_scheme(name).ok_or_else(|| UnknownColorScheme(name.to_owned()))?;
+    *CURRENT.write().expect("color scheme lock poisoned") = scheme;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::{Color, Style};
+
+    use super::{
+        color_scheme, set_current, UnknownColorScheme, COLOR_SCHEMES, DEFAULT_COLOR_SCHEME,
+    };
+
+    #[test]
+    fn bundled_color_schemes() {
+        let names = COLOR_SCHEMES
+            .iter()
+            .map(|scheme| scheme.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![DEFAULT_COLOR_SCHEME, "light", "high-contrast"]);
+
+        // Every scheme should style the same captures.
+        let default = color_scheme(DEFAULT_COLOR_SCHEME).unwrap();
+        for name in names {
+            let scheme = color_scheme(name).unwrap();
+            for capture in default.styles.keys() {
+                assert!(
+                    scheme.style(capture).is_some(),
+                    "{} has no style for {}",
+                    name,
+                    capture
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn style_fallback() {
+        let scheme = color_scheme("high-contrast").unwrap();
+
+        assert_eq!(
+            scheme.style("keyword.control.conditional"),
+            Some(Style::fg(Color::new(0xff, 0xff, 0x00)).bold())
+        );
+        assert_eq!(scheme.style("punctuation"), None);
+    }
+
+    #[test]
+    fn set_unknown_color_scheme() {
+        assert_eq!(
+            set_current("solarized"),
+            Err(UnknownColorScheme(String::from("solarized")))
+        );
+    }
+}
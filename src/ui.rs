@@ -18,10 +18,15 @@ pub type Size = Size2D<u16, ScreenSpace>;
 pub type Bounds = Box2D<u16, ScreenSpace>;
 
 mod color;
+pub mod menu;
+pub mod picker;
+pub mod popup;
 mod screen;
+mod style;
 
 pub use color::Color;
 pub use screen::Screen;
+pub use style::Style;
 
 /// Context for the rendering of a widget.
 pub struct Context<'screen> {
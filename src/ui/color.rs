@@ -13,6 +13,28 @@ impl Color {
     }
 
     pub const BLUE: Color = Color::new(0, 0, 0xFF);
+
+    /// Returns the closest color of the xterm 256-color palette, from its color cube or its
+    /// grayscale ramp.
+    pub fn to_ansi256(self) -> u8 {
+        if self.r == self.g && self.g == self.b {
+            return match self.r {
+                0..=7 => 16,
+                249..=255 => 231,
+                // The ramp goes from 8 to 238 in steps of 10.
+                gray => 232 + ((gray - 3) / 10).min(23),
+            };
+        }
+
+        // The levels of the color cube are 0, 95, 135, 175, 215, and 255.
+        let level = |c: u8| match c {
+            0..=47 => 0,
+            48..=114 => 1,
+            c => (c - 35) / 40,
+        };
+
+        16 + 36 * level(self.r) + 6 * level(self.g) + level(self.b)
+    }
 }
 
 impl Debug for Color {
@@ -30,4 +52,14 @@ mod tests {
         assert_eq!(format!("{:?}", Color::new(0xAB, 0xCD, 0xEF)), "#abcdef");
         assert_eq!(format!("{:?}", Color::new(0x00, 0x00, 0x00)), "#000000");
     }
+
+    #[test]
+    fn to_ansi256() {
+        assert_eq!(Color::new(0x00, 0x00, 0x00).to_ansi256(), 16);
+        assert_eq!(Color::new(0xff, 0xff, 0xff).to_ansi256(), 231);
+        assert_eq!(Color::new(0xff, 0x00, 0x00).to_ansi256(), 196);
+        assert_eq!(Color::new(0x5f, 0xaf, 0xff).to_ansi256(), 75);
+        assert_eq!(Color::new(0x08, 0x08, 0x08).to_ansi256(), 232);
+        assert_eq!(Color::new(0x8a, 0x8a, 0x8a).to_ansi256(), 245);
+    }
 }
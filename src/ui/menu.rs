@@ -0,0 +1,183 @@
+//! A popup list of items drawn next to the cursor, such as completion candidates.
+//!
+//! Unlike the picker, the menu doesn't take any keys itself: whatever opened it moves the
+//! selection. Each row is padded to the width of the widest item so the menu is drawn as a solid
+//! block over the buffer, styled with the color scheme's `menu` and `menu.selected` styles.
+
+use std::cmp;
+use std::convert::TryFrom;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::theme;
+
+use super::{Bounds, Context, Coordinates, Drawable, Size};
+
+/// The most rows that a menu takes up. Menus with more items scroll to show the selection.
+const MAX_HEIGHT: u16 = 10;
+
+#[derive(Debug)]
+pub struct Menu<'a> {
+    pub items: &'a [String],
+
+    /// The index of the highlighted item, if any.
+    pub selected: Option<usize>,
+}
+
+impl Menu<'_> {
+    /// Returns the size that the menu would like to be drawn at, with a column of padding on
+    /// either side of the items.
+    pub fn size(&self) -> Size {
+        let width = self
+            .items
+            .iter()
+            .map(|item| item.width())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let height = cmp::min(self.items.len(), usize::from(MAX_HEIGHT));
+
+        Size::new(
+            u16::try_from(width).unwrap_or(u16::MAX),
+            u16::try_from(height).expect("menu height is bounded"),
+        )
+    }
+
+    /// Returns where to draw the menu within an area, next to the cell at `anchor`.
+    pub fn bounds(&self, area: Bounds, anchor: Coordinates) -> Bounds {
+        bounds_near(self.size(), area, anchor)
+    }
+}
+
+/// Returns where to draw a popup of some size within an area, below the cell at `anchor` or above
+/// it if there's more room there. The popup is shifted left or cut off to fit in the area.
+pub(super) fn bounds_near(size: Size, area: Bounds, anchor: Coordinates) -> Bounds {
+    let rows_below = area.max.y.saturating_sub(anchor.y + 1);
+    let rows_above = anchor.y.saturating_sub(area.min.y);
+    let (min_y, max_y) = if rows_below >= size.height || rows_below >= rows_above {
+        (
+            anchor.y + 1,
+            anchor.y + 1 + cmp::min(size.height, rows_below),
+        )
+    } else {
+        (anchor.y - cmp::min(size.height, rows_above), anchor.y)
+    };
+
+    let width = cmp::min(size.width, area.width());
+    let min_x = cmp::max(area.min.x, cmp::min(anchor.x, area.max.x - width));
+
+    Bounds::new(
+        Coordinates::new(min_x, min_y),
+        Coordinates::new(min_x + width, max_y),
+    )
+}
+
+/// Pads or truncates text to exactly `width` columns, with a column of padding on the left.
+pub(super) fn pad(text: &str, width: usize) -> String {
+    let mut row = String::from(" ");
+    let mut row_width = 1;
+
+    for c in text.chars() {
+        let c_width = c.width().unwrap_or(0);
+        if row_width + c_width > width {
+            break;
+        }
+        row.push(c);
+        row_width += c_width;
+    }
+
+    row.push_str(&" ".repeat(width.saturating_sub(row_width)));
+    row
+}
+
+impl Drawable for Menu<'_> {
+    fn draw(&self, ctx: &mut Context<'_>) {
+        let bounds = ctx.bounds;
+        if bounds.is_empty() {
+            return;
+        }
+
+        let height = usize::from(bounds.height());
+        let selected = self.selected.unwrap_or(0);
+        let first = (selected + 1).saturating_sub(height);
+        let last = cmp::min(first + height, self.items.len());
+
+        let scheme = theme::current();
+        let style = scheme.style("menu").unwrap_or_default();
+        let selected_style = scheme.style("menu.selected").unwrap_or_default();
+
+        for (row, index) in (first..last).enumerate() {
+            let y = bounds.min.y + row as u16;
+            let row_bounds = Bounds::new(
+                Coordinates::new(bounds.min.x, y),
+                Coordinates::new(bounds.max.x, y + 1),
+            );
+
+            ctx.screen.write(
+                row_bounds.min,
+                &pad(&self.items[index], usize::from(bounds.width())),
+            );
+
+            let style = if self.selected == Some(index) {
+                selected_style
+            } else {
+                style
+            };
+            ctx.screen.apply_style(row_bounds, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::{Bounds, Coordinates, Size};
+
+    use super::{pad, Menu};
+
+    #[test]
+    fn menu_size() {
+        let items = (0..12).map(|i| format!("item{}", i)).collect::<Vec<_>>();
+
+        let menu = Menu {
+            items: &items,
+            selected: None,
+        };
+        assert_eq!(menu.size(), Size::new(8, 10));
+
+        let menu = Menu {
+            items: &items[..2],
+            selected: None,
+        };
+        assert_eq!(menu.size(), Size::new(7, 2));
+    }
+
+    #[test]
+    fn menu_bounds() {
+        let items = vec![String::from("foo"), String::from("foobar")];
+        let menu = Menu {
+            items: &items,
+            selected: Some(1),
+        };
+        let area = Bounds::new(Coordinates::new(2, 0), Coordinates::new(20, 10));
+
+        assert_eq!(
+            menu.bounds(area, Coordinates::new(5, 3)),
+            Bounds::new(Coordinates::new(5, 4), Coordinates::new(13, 6))
+        );
+        assert_eq!(
+            menu.bounds(area, Coordinates::new(16, 9)),
+            Bounds::new(Coordinates::new(12, 7), Coordinates::new(20, 9))
+        );
+        assert_eq!(
+            menu.bounds(area, Coordinates::new(5, 8)),
+            Bounds::new(Coordinates::new(5, 6), Coordinates::new(13, 8))
+        );
+    }
+
+    #[test]
+    fn pad_rows() {
+        assert_eq!(pad("foo", 6), " foo  ");
+        assert_eq!(pad("foobar", 4), " foo");
+        assert_eq!(pad("日本", 4), " 日 ");
+    }
+}
@@ -0,0 +1,441 @@
+//! A modal list of items that can be filtered by typing a query.
+//!
+//! The picker is drawn as an overlay: a prompt on the first row, the items that match the query
+//! below it, and a preview of the selected item to the right of the list if there's room. The
+//! picker only writes its own text, so whatever is drawn beneath it should be cleared first.
+
+use std::cmp;
+
+use crate::term::Key;
+
+use super::{Bounds, Context, Coordinates, Drawable, Style};
+
+/// The minimum width of the overlay for the preview pane to be shown.
+const MIN_PREVIEW_WIDTH: u16 = 60;
+
+/// Separates the list from the preview pane.
+const PREVIEW_SEPARATOR: &str = "│";
+
+/// An item that can be chosen in a picker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// The text shown in the list, which the query is matched against.
+    pub label: String,
+
+    /// Text shown after the label, which isn't matched against the query.
+    pub detail: String,
+
+    /// Lines shown in the preview pane while the item is selected. Lines that don't fit are not
+    /// shown.
+    pub preview: Vec<String>,
+}
+
+impl Item {
+    pub fn new(label: impl Into<String>) -> Self {
+        Item {
+            label: label.into(),
+            detail: String::new(),
+            preview: vec![],
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self
+    }
+
+    pub fn with_preview(mut self, preview: Vec<String>) -> Self {
+        self.preview = preview;
+        self
+    }
+}
+
+/// The result of passing a key to a picker.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The picker is still open.
+    Continue,
+
+    /// The picker was dismissed without choosing an item.
+    Cancel,
+
+    /// The item at the given index of the picker's items was chosen.
+    Accept(usize),
+
+    /// The item at the given index of the picker's items should be deleted. The picker is still
+    /// open.
+    Delete(usize),
+}
+
+/// A prompt and a list of items filtered by the text typed at the prompt.
+///
+/// Items match the query if they contain its characters in order, ignoring case. Matches are
+/// ordered by how close together the characters are, then by their original order.
+#[derive(Debug)]
+pub struct Picker {
+    prompt: String,
+    items: Vec<Item>,
+    query: String,
+
+    /// The indices of the items that match the query, best match first.
+    matches: Vec<usize>,
+
+    /// The index of the selected item within `matches`.
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(prompt: impl Into<String>, items: Vec<Item>) -> Self {
+        let mut picker = Picker {
+            prompt: prompt.into(),
+            items,
+            matches: vec![],
+            query: String::new(),
+            selected: 0,
+        };
+        picker.filter();
+        picker
+    }
+
+    #[cfg(test)]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Returns the index of the selected item, or `None` if no items match the query.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    /// Returns the matching items, best match first.
+    #[cfg(test)]
+    pub fn matches(&self) -> impl Iterator<Item = &Item> {
+        self.matches.iter().map(move |&index| &self.items[index])
+    }
+
+    /// Replaces the items, keeping the query and the position of the selection.
+    pub fn set_items(&mut self, items: Vec<Item>) {
+        let selected = self.selected;
+
+        self.items = items;
+        self.filter();
+        self.selected = cmp::min(selected, self.matches.len().saturating_sub(1));
+    }
+
+    /// Updates the query or the selection in response to a key.
+    pub fn handle_key(&mut self, key: Key) -> Action {
+        match key {
+            Key::Esc | Key::Ctrl('c') => return Action::Cancel,
+            Key::Return => {
+                return match self.selected() {
+                    Some(index) => Action::Accept(index),
+                    None => Action::Cancel,
+                }
+            }
+            Key::Ctrl('d') => {
+                if let Some(index) = self.selected() {
+                    return Action::Delete(index);
+                }
+            }
+            Key::ArrowDown | Key::Ctrl('n') => self.select_next(),
+            Key::ArrowUp | Key::Ctrl('p') => self.select_previous(),
+            Key::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            Key::Ctrl('u') => {
+                self.query.clear();
+                self.filter();
+            }
+            Key::Char(c) => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => (),
+        }
+
+        Action::Continue
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Updates the matches for the current query, selecting the best match.
+    fn filter(&mut self) {
+        let query = self.query.to_lowercase().chars().collect::<Vec<_>>();
+
+        let mut scored = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| Some((fuzzy_score(&query, &item.label)?, index)))
+            .collect::<Vec<_>>();
+        scored.sort();
+
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+
+    /// Returns the column of the cursor within the prompt row.
+    pub fn cursor_column(&self) -> usize {
+        self.prompt.chars().count() + self.query.chars().count()
+    }
+}
+
+/// Returns the number of characters spanned by the first occurrence of the query in the text as a
+/// subsequence, or `None` if the text doesn't contain it. Lower scores are better matches.
+///
+/// The query must be lowercase.
+fn fuzzy_score(query: &[char], text: &str) -> Option<usize> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+
+    // Try each possible start of the match, keeping the tightest.
+    text.iter()
+        .enumerate()
+        .filter(|&(_, &c)| c == query[0])
+        .filter_map(|(start, _)| {
+            let mut remaining = query.iter().peekable();
+            for (offset, c) in text[start..].iter().enumerate() {
+                if remaining.peek() == Some(&c) {
+                    remaining.next();
+                    if remaining.peek().is_none() {
+                        return Some(offset + 1);
+                    }
+                }
+            }
+            None
+        })
+        .min()
+}
+
+/// Truncates text to at most `width` characters.
+pub(crate) fn truncate(text: &str, width: usize) -> &str {
+    match text.char_indices().nth(width) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
+
+impl Drawable for Picker {
+    fn draw(&self, ctx: &mut Context<'_>) {
+        let bounds = ctx.bounds;
+        if bounds.is_empty() {
+            return;
+        }
+
+        let width = usize::from(bounds.width());
+        ctx.screen.write(
+            bounds.min,
+            truncate(&format!("{}{}", self.prompt, self.query), width),
+        );
+
+        let height = usize::from(bounds.height().saturating_sub(1));
+        if height == 0 {
+            return;
+        }
+
+        let list_width = if bounds.width() >= MIN_PREVIEW_WIDTH {
+            bounds.width() / 2
+        } else {
+            bounds.width()
+        };
+
+        let first = (self.selected + 1).saturating_sub(height);
+        let last = cmp::min(first + height, self.matches.len());
+
+        for (row, index) in (first..last).enumerate() {
+            let y = bounds.min.y + 1 + row as u16;
+            let item = &self.items[self.matches[index]];
+
+            let text = if item.detail.is_empty() {
+                item.label.clone()
+            } else {
+                format!("{}  {}", item.label, item.detail)
+            };
+
+            ctx.screen.write(
+                Coordinates::new(bounds.min.x, y),
+                truncate(&text, usize::from(list_width)),
+            );
+
+            if index == self.selected {
+                let bounds = Bounds::new(
+                    Coordinates::new(bounds.min.x, y),
+                    Coordinates::new(bounds.min.x + list_width, y + 1),
+                );
+                ctx.screen.apply_style(bounds, Style::default().bold());
+            }
+        }
+
+        if list_width == bounds.width() {
+            return;
+        }
+
+        let separator_x = bounds.min.x + list_width;
+        let preview_x = separator_x + 1;
+        let preview_width = usize::from(bounds.max.x - preview_x);
+        let preview = self
+            .selected()
+            .map(|index| self.items[index].preview.as_slice())
+            .unwrap_or_default();
+
+        for row in 0..height {
+            let y = bounds.min.y + 1 + row as u16;
+
+            ctx.screen
+                .write(Coordinates::new(separator_x, y), PREVIEW_SEPARATOR);
+
+            if let Some(line) = preview.get(row) {
+                ctx.screen.write(
+                    Coordinates::new(preview_x, y),
+                    truncate(line, preview_width),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::term::Key;
+    use crate::ui::{Bounds, Context, Drawable, Screen, Size};
+
+    use super::{fuzzy_score, Action, Item, Picker};
+
+    fn picker() -> Picker {
+        Picker::new(
+            "> ",
+            vec![
+                Item::new("src/buffer.rs").with_preview(vec![String::from("//! Buffers.")]),
+                Item::new("src/lib.rs")
+                    .with_detail("[+]")
+                    .with_preview(vec![String::from("mod buffer;")]),
+                Item::new("README.md"),
+            ],
+        )
+    }
+
+    fn type_query(picker: &mut Picker, query: &str) {
+        for c in query.chars() {
+            assert_eq!(picker.handle_key(Key::Char(c)), Action::Continue);
+        }
+    }
+
+    #[test]
+    fn fuzzy_match() {
+        let query = |q: &str| q.chars().collect::<Vec<_>>();
+
+        assert_eq!(fuzzy_score(&query(""), "anything"), Some(0));
+        assert_eq!(fuzzy_score(&query("lib"), "src/lib.rs"), Some(3));
+        assert_eq!(fuzzy_score(&query("slr"), "src/lib.rs"), Some(9));
+        assert_eq!(fuzzy_score(&query("rd"), "README.md"), Some(4));
+        assert_eq!(fuzzy_score(&query("xyz"), "src/lib.rs"), None);
+    }
+
+    #[test]
+    fn filter_items() {
+        let mut picker = picker();
+        assert_eq!(picker.matches().count(), 3);
+
+        let labels = |picker: &Picker| {
+            picker
+                .matches()
+                .map(|item| item.label.clone())
+                .collect::<Vec<_>>()
+        };
+
+        type_query(&mut picker, "rs");
+        assert_eq!(labels(&picker), vec!["src/buffer.rs", "src/lib.rs"]);
+
+        picker.handle_key(Key::Backspace);
+        type_query(&mut picker, "e");
+        assert_eq!(labels(&picker), vec!["README.md", "src/buffer.rs"]);
+        assert_eq!(picker.selected(), Some(2));
+
+        picker.handle_key(Key::Ctrl('u'));
+        type_query(&mut picker, "zz");
+        assert_eq!(picker.selected(), None);
+        assert_eq!(picker.handle_key(Key::Return), Action::Cancel);
+
+        picker.handle_key(Key::Backspace);
+        picker.handle_key(Key::Backspace);
+        assert_eq!(picker.query(), "");
+        assert_eq!(picker.matches().count(), 3);
+    }
+
+    #[test]
+    fn select_and_accept() {
+        let mut picker = picker();
+
+        picker.handle_key(Key::ArrowUp);
+        assert_eq!(picker.selected(), Some(0));
+
+        picker.handle_key(Key::Ctrl('n'));
+        picker.handle_key(Key::ArrowDown);
+        picker.handle_key(Key::ArrowDown);
+        assert_eq!(picker.selected(), Some(2));
+
+        picker.handle_key(Key::Ctrl('p'));
+        assert_eq!(picker.handle_key(Key::Return), Action::Accept(1));
+        assert_eq!(picker.handle_key(Key::Esc), Action::Cancel);
+    }
+
+    #[test]
+    fn delete_items() {
+        let mut picker = picker();
+        type_query(&mut picker, "rs");
+        picker.handle_key(Key::ArrowDown);
+        assert_eq!(picker.handle_key(Key::Ctrl('d')), Action::Delete(1));
+
+        picker.set_items(vec![Item::new("src/buffer.rs"), Item::new("README.md")]);
+        assert_eq!(picker.query(), "rs");
+        assert_eq!(picker.selected(), Some(0));
+
+        picker.set_items(vec![Item::new("README.md")]);
+        assert_eq!(picker.selected(), None);
+        assert_eq!(picker.handle_key(Key::Ctrl('d')), Action::Continue);
+    }
+
+    #[test]
+    fn draw_picker() {
+        let mut screen = Screen::new(Size::new(60, 4));
+
+        let mut picker = picker();
+        type_query(&mut picker, "lib");
+        picker.handle_key(Key::Backspace);
+        picker.handle_key(Key::ArrowDown);
+
+        picker.draw(&mut Context {
+            bounds: Bounds::from_size(screen.size),
+            screen: &mut screen,
+        });
+
+        let row = |y| {
+            (0..60)
+                .map(|x| screen[(y, x)].c.unwrap_or(' '))
+                .collect::<String>()
+                .trim_end()
+                .to_owned()
+        };
+
+        assert_eq!(row(0), "> li");
+        assert_eq!(
+            row(1),
+            format!("{:30}│mod buffer;", "src/lib.rs  [+]").trim_end()
+        );
+        assert_eq!(row(2), format!("{:30}│", ""));
+        assert_eq!(row(3), format!("{:30}│", ""));
+        assert!(screen[(1, 0)].bold);
+    }
+}
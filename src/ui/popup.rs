@@ -0,0 +1,138 @@
+//! A box of text drawn next to the cursor, such as the documentation of the symbol under it.
+//!
+//! Like the menu, the popup doesn't take any keys itself, and is styled with the color scheme's
+//! `menu` style. Lines are wrapped at spaces to fit the popup's width, and lines past its height
+//! are cut off.
+
+use std::cmp;
+use std::convert::TryFrom;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::theme;
+
+use super::menu::{bounds_near, pad};
+use super::{Bounds, Context, Coordinates, Drawable, Size};
+
+/// The widest that a popup is, including its padding, so that text stays readable on wide
+/// screens.
+const MAX_WIDTH: u16 = 80;
+
+/// The most rows that a popup takes up.
+const MAX_HEIGHT: u16 = 20;
+
+#[derive(Debug)]
+pub struct Popup {
+    /// The rows of text, after wrapping.
+    rows: Vec<String>,
+}
+
+impl Popup {
+    /// Creates a popup that wraps text to fit within `max_width` columns, including a column of
+    /// padding on either side.
+    pub fn new(text: &str, max_width: u16) -> Self {
+        let width = usize::from(cmp::min(max_width, MAX_WIDTH)).saturating_sub(2);
+
+        Popup {
+            rows: text
+                .lines()
+                .flat_map(|line| wrap(line, cmp::max(width, 1)))
+                .collect(),
+        }
+    }
+
+    /// Returns the size that the popup would like to be drawn at.
+    pub fn size(&self) -> Size {
+        let width = self.rows.iter().map(|row| row.width()).max().unwrap_or(0) + 2;
+        let height = cmp::min(self.rows.len(), usize::from(MAX_HEIGHT));
+
+        Size::new(
+            u16::try_from(width).unwrap_or(u16::MAX),
+            u16::try_from(height).expect("popup height is bounded"),
+        )
+    }
+
+    /// Returns where to draw the popup within an area, next to the cell at `anchor`.
+    pub fn bounds(&self, area: Bounds, anchor: Coordinates) -> Bounds {
+        bounds_near(self.size(), area, anchor)
+    }
+}
+
+/// Splits a line into rows of at most `width` columns, breaking after spaces where possible so
+/// that words aren't split.
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut rows = vec![];
+    let mut row = String::new();
+    let mut row_width = 0;
+
+    for c in line.chars() {
+        let c_width = c.width().unwrap_or(0);
+
+        if row_width + c_width > width && !row.is_empty() {
+            if c == ' ' {
+                rows.push(row.trim_end().to_owned());
+                row.clear();
+                row_width = 0;
+                continue;
+            }
+
+            let rest = match row.rfind(' ') {
+                Some(index) if !row[..index].trim().is_empty() => row.split_off(index + 1),
+                _ => String::new(),
+            };
+
+            rows.push(row.trim_end().to_owned());
+            row = rest;
+            row_width = row.width();
+        }
+
+        row.push(c);
+        row_width += c_width;
+    }
+
+    rows.push(row.trim_end().to_owned());
+    rows
+}
+
+impl Drawable for Popup {
+    fn draw(&self, ctx: &mut Context<'_>) {
+        let bounds = ctx.bounds;
+        if bounds.is_empty() {
+            return;
+        }
+
+        for (row, text) in self.rows.iter().take(bounds.height().into()).enumerate() {
+            let position = Coordinates::new(bounds.min.x, bounds.min.y + row as u16);
+            ctx.screen
+                .write(position, &pad(text, usize::from(bounds.width())));
+        }
+
+        let style = theme::current().style("menu").unwrap_or_default();
+        ctx.screen.apply_style(bounds, style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::Size;
+
+    use super::{wrap, Popup};
+
+    #[test]
+    fn wrap_lines() {
+        assert_eq!(wrap("foo bar baz", 7), vec!["foo bar", "baz"]);
+        assert_eq!(wrap("foo bar baz", 4), vec!["foo", "bar", "baz"]);
+        assert_eq!(wrap("foobarbaz qux", 4), vec!["foob", "arba", "z", "qux"]);
+        assert_eq!(wrap("    indented", 6), vec!["    in", "dented"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+
+    #[test]
+    fn popup_size() {
+        let popup = Popup::new("fn foo()\n\nDoes foo and bar.", 12);
+        assert_eq!(popup.size(), Size::new(10, 4));
+
+        let text = "x".repeat(200);
+        assert_eq!(Popup::new(&text, 200).size(), Size::new(80, 3));
+    }
+}
@@ -4,12 +4,31 @@ use std::ops::{Index, IndexMut};
 use itertools::Itertools;
 use unicode_width::UnicodeWidthChar;
 
-use super::{Bounds, Color, Coordinates, Size};
+use super::{Bounds, Color, Coordinates, Size, Style};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cell {
     pub c: Option<char>,
+
+    /// The foreground color.
     pub color: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Cell {
+    /// Returns the visual attributes of the cell.
+    pub fn style(&self) -> Style {
+        Style {
+            foreground: self.color,
+            background: self.background,
+            bold: self.bold,
+            italic: self.italic,
+            underline: self.underline,
+        }
+    }
 }
 
 impl Default for Cell {
@@ -17,6 +36,10 @@ impl Default for Cell {
         Cell {
             c: None,
             color: None,
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
         }
     }
 }
@@ -25,7 +48,7 @@ impl From<char> for Cell {
     fn from(c: char) -> Self {
         Cell {
             c: Some(c),
-            color: None,
+            ..Cell::default()
         }
     }
 }
@@ -83,6 +106,31 @@ impl Screen {
         }
     }
 
+    /// Apply a style to cells within a rectangular region.
+    ///
+    /// Colors that the style does not specify are left unchanged.
+    pub fn apply_style(&mut self, bounds: Bounds, style: Style) {
+        debug_assert!(!bounds.is_empty());
+
+        for y in bounds.min.y..bounds.max.y {
+            for x in bounds.min.x..bounds.max.x {
+                let cell = &mut self[(y, x)];
+
+                if let Some(foreground) = style.foreground {
+                    cell.color = Some(foreground);
+                }
+
+                if let Some(background) = style.background {
+                    cell.background = Some(background);
+                }
+
+                cell.bold = style.bold;
+                cell.italic = style.italic;
+                cell.underline = style.underline;
+            }
+        }
+    }
+
     /// Returns the index in the underlying storage that corresponds to the given row and column.
     ///
     /// # Panics
@@ -111,6 +159,95 @@ impl Screen {
             *cell = Cell::default();
         }
     }
+
+    /// Returns the text of the screen, one line per row, ignoring styles. Trailing blanks are
+    /// removed from each row.
+    pub fn to_text(&self) -> String {
+        self.iter_rows()
+            .map(|row| {
+                let mut line = String::new();
+                write_row(&mut line, row, |_, _| ());
+                line.trim_end().to_owned()
+            })
+            .join("\n")
+    }
+
+    /// Returns the text of the screen with ANSI escape sequences for the styles of the cells, one
+    /// line per row. Each row starts with all attributes reset.
+    pub fn to_ansi(&self) -> String {
+        self.iter_rows()
+            .map(|row| {
+                let mut line = String::from("\x1b[0m");
+                let mut last_style = Style::default();
+
+                write_row(&mut line, row, |line, style| {
+                    if style != last_style {
+                        push_sgr(line, style);
+                        last_style = style;
+                    }
+                });
+
+                if last_style != Style::default() {
+                    line.push_str("\x1b[0m");
+                }
+                line
+            })
+            .join("\n")
+    }
+}
+
+/// Writes the characters of a row to a string, calling `on_style` before each cell with its style.
+/// Empty cells are written as spaces, unless they're covered by a preceding wide character.
+fn write_row<'a>(
+    s: &mut String,
+    row: impl Iterator<Item = &'a Cell>,
+    mut on_style: impl FnMut(&mut String, Style),
+) {
+    // The number of following cells that are covered by a wide character.
+    let mut covered = 0;
+
+    for cell in row {
+        match cell.c {
+            Some(c) => {
+                on_style(s, cell.style());
+                s.push(c);
+                covered = c.width().unwrap_or(0).saturating_sub(1);
+            }
+            None if covered > 0 => covered -= 1,
+            None => {
+                on_style(s, cell.style());
+                s.push(' ');
+            }
+        }
+    }
+}
+
+/// Writes the Select Graphic Rendition sequence that switches to a style.
+fn push_sgr(s: &mut String, style: Style) {
+    // Attributes can't be turned off individually, so start from scratch.
+    s.push_str("\x1b[0");
+
+    if let Some(color) = style.foreground {
+        let _ = write!(s, ";38;2;{};{};{}", color.r, color.g, color.b);
+    }
+
+    if let Some(color) = style.background {
+        let _ = write!(s, ";48;2;{};{};{}", color.r, color.g, color.b);
+    }
+
+    if style.bold {
+        s.push_str(";1");
+    }
+
+    if style.italic {
+        s.push_str(";3");
+    }
+
+    if style.underline {
+        s.push_str(";4");
+    }
+
+    s.push('m');
 }
 
 impl Index<(u16, u16)> for Screen {
@@ -142,7 +279,7 @@ impl Debug for Screen {
 mod tests {
     use euclid::size2;
 
-    use super::{Bounds, Cell, Color, Coordinates, Screen, Size};
+    use super::{Bounds, Cell, Color, Coordinates, Screen, Size, Style};
 
     #[test]
     fn indexing() {
@@ -209,6 +346,27 @@ mod tests {
         assert_eq!(buf[(0, 2)], Cell::from('Ｂ'));
     }
 
+    #[test]
+    fn to_text() {
+        let mut buf = Screen::new(Size::new(6, 3));
+        buf.write(Coordinates::zero(), "hello");
+        buf.write(Coordinates::new(1, 2), "ＡＢ");
+
+        assert_eq!(buf.to_text(), "hello\n\n ＡＢ");
+    }
+
+    #[test]
+    fn to_ansi() {
+        let mut buf = Screen::new(Size::new(4, 1));
+        buf.write(Coordinates::zero(), "abc");
+        buf.apply_style(
+            Bounds::new(Coordinates::new(1, 0), Coordinates::new(2, 1)),
+            Style::fg(Color::new(0xff, 0x00, 0x00)).bold(),
+        );
+
+        assert_eq!(buf.to_ansi(), "\x1b[0ma\x1b[0;38;2;255;0;0;1mb\x1b[0mc ");
+    }
+
     #[test]
     fn apply_color() {
         let mut buf = Screen::new(Size::new(3, 3));
@@ -219,4 +377,25 @@ mod tests {
         assert_eq!(buf[(1, 1)].color, Some(Color::BLUE));
         assert_eq!(buf[(1, 2)].color, None);
     }
+
+    #[test]
+    fn apply_style() {
+        let mut buf = Screen::new(Size::new(3, 1));
+        buf.apply_style(
+            Bounds::new(Coordinates::new(0, 0), Coordinates::new(3, 1)),
+            Style::default().on(Color::BLUE),
+        );
+        buf.apply_style(
+            Bounds::new(Coordinates::new(1, 0), Coordinates::new(2, 1)),
+            Style::fg(Color::new(0xff, 0x00, 0x00)).bold(),
+        );
+
+        assert_eq!(buf[(0, 0)].style(), Style::default().on(Color::BLUE));
+        assert_eq!(
+            buf[(0, 1)].style(),
+            Style::fg(Color::new(0xff, 0x00, 0x00))
+                .bold()
+                .on(Color::BLUE)
+        );
+    }
 }
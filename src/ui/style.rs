@@ -0,0 +1,49 @@
+use super::Color;
+
+/// Visual attributes of text on the screen.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /// A style that only sets the foreground color.
+    pub const fn fg(color: Color) -> Self {
+        Style {
+            foreground: Some(color),
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
+        }
+    }
+
+    pub const fn bold(self) -> Self {
+        Style { bold: true, ..self }
+    }
+
+    pub const fn italic(self) -> Self {
+        Style {
+            italic: true,
+            ..self
+        }
+    }
+
+    pub const fn underline(self) -> Self {
+        Style {
+            underline: true,
+            ..self
+        }
+    }
+
+    pub const fn on(self, background: Color) -> Self {
+        Style {
+            background: Some(background),
+            ..self
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use std::env;
 use std::error::Error;
+use std::ffi::OsStr;
 use std::fs;
-use std::path::PathBuf;
+use std::iter;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use quote::{format_ident, quote};
@@ -10,63 +12,133 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut functions = vec![];
     let mut tests = vec![];
 
-    for entry in fs::read_dir("vendor")? {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
+    let vendor = manifest_dir.join("vendor");
+
+    for entry in fs::read_dir(&vendor)? {
         let repo_path = entry?.path();
-        let language = repo_path
-            .file_name()
-            .expect("tree-sitter language repo paths must have a file name")
-            .to_str()
-            .expect("tree-sitter language repo paths must be UTF-8")
-            .trim_start_matches("tree-sitter-");
-
-        let src = repo_path.join("src");
-        let vendor_dir = format!("tree-sitter-{}", language);
-        cc::Build::new()
-            .warnings(false)
-            .include(&src)
-            .file(src.join("parser.c"))
-            .file(src.join("scanner.c"))
-            .compile(&vendor_dir);
-
-        let language_ident = format_ident!("{}", language);
-        let tree_sitter_function = format_ident!("tree_sitter_{}", language);
-        let highlight_query_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?)
-            .join("vendor")
-            .join(vendor_dir)
-            .join("queries/highlights.scm");
-        let highlight_query_path = highlight_query_path
-            .to_str()
-            .expect("expected path to be UTF-8");
-        functions.push(quote! {
-            pub fn #language_ident() -> (Language, Query) {
-                extern "C" {
-                    fn #tree_sitter_function() -> tree_sitter::Language;
-                }
 
-                let language = unsafe { #tree_sitter_function() };
-                let query = Query::new(
-                    language,
-                    include_str!(#highlight_query_path),
-                ).expect("unable to parse highlight query");
-                (language, query)
+        // Each repository is gated behind a feature of the same name.
+        let feature = format!(
+            "CARGO_FEATURE_{}",
+            repo_language(&repo_path).to_uppercase().replace('-', "_")
+        );
+        if env::var_os(feature).is_none() {
+            continue;
+        }
+
+        for (language, grammar_path) in grammars(&repo_path)? {
+            let src = grammar_path.join("src");
+            let vendor_dir = format!("tree-sitter-{}", language);
+
+            let mut build = cc::Build::new();
+            build
+                .warnings(false)
+                .include(&src)
+                .file(src.join("parser.c"));
+
+            // External scanners are optional, and may be written in either C or C++.
+            let scanner = src.join("scanner.c");
+            if scanner.exists() {
+                build.file(scanner);
             }
-        });
 
-        tests.push(quote! {
-            #[test]
-            fn #language_ident() {
-                println!("{:?}", super::#language_ident());
+            build.compile(&vendor_dir);
+
+            let cpp_scanner = src.join("scanner.cc");
+            if cpp_scanner.exists() {
+                cc::Build::new()
+                    .cpp(true)
+                    .warnings(false)
+                    .include(&src)
+                    .file(cpp_scanner)
+                    .compile(&format!("{}-scanner", vendor_dir));
             }
-        });
+
+            let language_ident = format_ident!("{}", language);
+            let tree_sitter_function = format_ident!("tree_sitter_{}", language);
+
+            let query = |kind: &str| {
+                let paths = query_paths(&manifest_dir, &repo_path, &language, kind);
+                if paths.is_empty() {
+                    quote! { None }
+                } else {
+                    quote! { Some(concat!(#(include_str!(#paths)),*)) }
+                }
+            };
+
+            let highlights_ident = format_ident!("{}_HIGHLIGHTS_QUERY", language.to_uppercase());
+            let injections_ident = format_ident!("{}_INJECTIONS_QUERY", language.to_uppercase());
+            let locals_ident = format_ident!("{}_LOCALS_QUERY", language.to_uppercase());
+            let folds_ident = format_ident!("{}_FOLDS_QUERY", language.to_uppercase());
+            let textobjects_ident = format_ident!("{}_TEXTOBJECTS_QUERY", language.to_uppercase());
+
+            let highlight_query_paths =
+                query_paths(&manifest_dir, &repo_path, &language, "highlights");
+            let injection_query = query("injections");
+            let locals_query = query("locals");
+            let folds_query = query("folds");
+            let textobjects_query = query("textobjects");
+
+            functions.push(quote! {
+                pub fn #language_ident() -> Language {
+                    extern "C" {
+                        fn #tree_sitter_function() -> tree_sitter::Language;
+                    }
+
+                    unsafe { #tree_sitter_function() }
+                }
+
+                pub const #highlights_ident: &str =
+                    concat!(#(include_str!(#highlight_query_paths)),*);
+
+                pub const #injections_ident: Option<&str> = #injection_query;
+
+                pub const #locals_ident: Option<&str> = #locals_query;
+
+                pub const #folds_ident: Option<&str> = #folds_query;
+
+                pub const #textobjects_ident: Option<&str> = #textobjects_query;
+            });
+
+            tests.push(quote! {
+                #[test]
+                fn #language_ident() {
+                    let language = super::#language_ident();
+
+                    Query::new(language, super::#highlights_ident)
+                        .expect("unable to parse highlight query");
+
+                    if let Some(injections) = super::#injections_ident {
+                        Query::new(language, injections).expect("unable to parse injection query");
+                    }
+
+                    if let Some(locals) = super::#locals_ident {
+                        Query::new(language, locals).expect("unable to parse locals query");
+                    }
+
+                    if let Some(folds) = super::#folds_ident {
+                        Query::new(language, folds).expect("unable to parse folds query");
+                    }
+
+                    if let Some(textobjects) = super::#textobjects_ident {
+                        Query::new(language, textobjects)
+                            .expect("unable to parse textobjects query");
+                    }
+                }
+            });
+        }
     }
 
     let tokens = quote! {
-        use tree_sitter::{Language, Query};
+        use tree_sitter::Language;
 
         #(#functions)*
 
         #[cfg(test)]
         mod tests {
+            use tree_sitter::Query;
+
             #(#tests)*
         }
     };
@@ -83,3 +155,94 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Returns the name of the language provided by a tree-sitter language repository.
+fn repo_language(repo_path: &Path) -> &str {
+    repo_path
+        .file_name()
+        .expect("tree-sitter language repo paths must have a file name")
+        .to_str()
+        .expect("tree-sitter language repo paths must be UTF-8")
+        .trim_start_matches("tree-sitter-")
+}
+
+/// Returns the names and paths of the grammars contained in a tree-sitter language repository.
+///
+/// Most repositories contain a single grammar at the root. Repositories that provide multiple
+/// dialects, such as TypeScript and TSX, contain one grammar per subdirectory instead.
+fn grammars(repo_path: &Path) -> Result<Vec<(String, PathBuf)>, Box<dyn Error>> {
+    if repo_path.join("src").exists() {
+        let language = repo_language(repo_path);
+        return Ok(vec![(language.to_owned(), repo_path.to_owned())]);
+    }
+
+    let mut grammars = vec![];
+
+    for entry in fs::read_dir(repo_path)? {
+        let grammar_path = entry?.path();
+        if !grammar_path.join("src/parser.c").exists() {
+            continue;
+        }
+
+        let language = grammar_path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .expect("tree-sitter grammar paths must be UTF-8")
+            .to_owned();
+        grammars.push((language, grammar_path));
+    }
+
+    Ok(grammars)
+}
+
+/// Returns the paths of the query files of the given kind for a language, such as `highlights` or
+/// `locals`.
+///
+/// The query shipped with the grammar comes first, if it has one, followed by the query in this
+/// crate's `queries` directory for grammars that don't provide one, and finally the queries of any
+/// grammars that it extends.
+fn query_paths(manifest_dir: &Path, repo_path: &Path, language: &str, kind: &str) -> Vec<String> {
+    let own_query = repo_path.join(format!("queries/{}.scm", kind));
+    let bundled_query = manifest_dir.join(format!("queries/{}/{}.scm", language, kind));
+
+    iter::once(own_query)
+        .chain(iter::once(bundled_query))
+        .filter(|path| path.exists())
+        .chain(
+            query_dependencies(language, kind)
+                .iter()
+                .map(|path| manifest_dir.join(path)),
+        )
+        .map(|path| path.to_str().expect("expected path to be UTF-8").to_owned())
+        .collect()
+}
+
+/// Returns the queries of the given kind that should be appended to a language's own query,
+/// relative to the crate root.
+///
+/// Some grammars extend another grammar, and their queries only cover the additional syntax. This
+/// mirrors the `highlights` and `locals` fields of the `tree-sitter` section of the grammar's
+/// `package.json`.
+fn query_dependencies(language: &str, kind: &str) -> &'static [&'static str] {
+    match (language, kind) {
+        ("cpp", "highlights") => &["vendor/tree-sitter-c/queries/highlights.scm"],
+        ("javascript", "highlights") => {
+            &["vendor/tree-sitter-javascript/queries/highlights-params.scm"]
+        }
+        ("typescript", "highlights") => &["vendor/tree-sitter-javascript/queries/highlights.scm"],
+        ("tsx", "highlights") => &[
+            "vendor/tree-sitter-javascript/queries/highlights-jsx.scm",
+            "vendor/tree-sitter-javascript/queries/highlights.scm",
+        ],
+        ("typescript", "locals") | ("tsx", "locals") => {
+            &["vendor/tree-sitter-javascript/queries/locals.scm"]
+        }
+        ("tsx", "folds") => &["queries/typescript/folds.scm"],
+        ("typescript", "textobjects") => &["queries/javascript/textobjects.scm"],
+        ("tsx", "textobjects") => &[
+            "queries/typescript/textobjects.scm",
+            "queries/javascript/textobjects.scm",
+        ],
+        _ => &[],
+    }
+}
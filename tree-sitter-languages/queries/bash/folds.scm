@@ -0,0 +1,11 @@
+[
+  (function_definition)
+  (compound_statement)
+  (subshell)
+  (if_statement)
+  (case_statement)
+  (for_statement)
+  (c_style_for_statement)
+  (while_statement)
+  (heredoc_body)
+] @fold
@@ -0,0 +1 @@
+(function_definition) @function.around
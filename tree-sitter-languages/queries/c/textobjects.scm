@@ -0,0 +1,7 @@
+(function_definition) @function.around
+
+[
+  (struct_specifier body: (_))
+  (union_specifier body: (_))
+  (enum_specifier body: (_))
+] @class.around
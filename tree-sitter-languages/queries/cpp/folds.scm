@@ -0,0 +1,15 @@
+[
+  (function_definition)
+  (compound_statement)
+  (class_specifier)
+  (struct_specifier)
+  (enum_specifier)
+  (union_specifier)
+  (namespace_definition)
+  (template_declaration)
+  (lambda_expression)
+  (initializer_list)
+  (preproc_if)
+  (preproc_ifdef)
+  (comment)
+] @fold
@@ -0,0 +1,8 @@
+(function_definition) @function.around
+
+[
+  (class_specifier body: (_))
+  (struct_specifier body: (_))
+  (union_specifier body: (_))
+  (enum_specifier body: (_))
+] @class.around
@@ -0,0 +1,6 @@
+[
+  (function_declaration)
+  (method_declaration)
+] @function.around
+
+(type_declaration) @class.around
@@ -0,0 +1,13 @@
+[
+  (statement_block)
+  (class_body)
+  (switch_body)
+  (object)
+  (array)
+  (arguments)
+  (formal_parameters)
+  (template_string)
+  (import_statement)
+  (jsx_element)
+  (comment)
+] @fold
@@ -0,0 +1,11 @@
+[
+  (function_declaration)
+  (generator_function_declaration)
+  (method_definition)
+] @function.around
+
+(lexical_declaration
+  (variable_declarator
+    value: [(arrow_function) (function)])) @function.around
+
+(class_declaration) @class.around
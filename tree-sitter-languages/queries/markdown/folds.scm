@@ -0,0 +1,7 @@
+[
+  (fenced_code_block)
+  (indented_code_block)
+  (html_block)
+  (block_quote)
+  (list)
+] @fold
@@ -0,0 +1,18 @@
+[
+  (function_definition)
+  (class_definition)
+  (decorated_definition)
+  (if_statement)
+  (for_statement)
+  (while_statement)
+  (with_statement)
+  (try_statement)
+  (dictionary)
+  (list)
+  (tuple)
+  (set)
+  (argument_list)
+  (parameters)
+  (import_from_statement)
+  (string)
+] @fold
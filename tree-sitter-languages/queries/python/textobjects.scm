@@ -0,0 +1,9 @@
+(function_definition) @function.around
+
+(class_definition) @class.around
+
+(decorated_definition
+  definition: (function_definition)) @function.around
+
+(decorated_definition
+  definition: (class_definition)) @class.around
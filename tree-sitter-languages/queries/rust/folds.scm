@@ -0,0 +1,21 @@
+[
+  (function_item)
+  (impl_item)
+  (trait_item)
+  (struct_item)
+  (enum_item)
+  (union_item)
+  (mod_item)
+  (macro_definition)
+  (block)
+  (match_block)
+  (use_declaration)
+  (macro_invocation)
+  (arguments)
+  (parameters)
+  (array_expression)
+  (field_initializer_list)
+  (block_comment)
+  (string_literal)
+  (raw_string_literal)
+] @fold
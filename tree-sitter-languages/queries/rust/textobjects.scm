@@ -0,0 +1,13 @@
+[
+  (function_item)
+  (macro_definition)
+] @function.around
+
+[
+  (impl_item)
+  (trait_item)
+  (struct_item)
+  (enum_item)
+  (union_item)
+  (mod_item body: (_))
+] @class.around
@@ -0,0 +1 @@
+(jsx_element) @fold
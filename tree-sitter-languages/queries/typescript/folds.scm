@@ -0,0 +1,15 @@
+[
+  (statement_block)
+  (class_body)
+  (switch_body)
+  (object_type)
+  (enum_body)
+  (internal_module)
+  (object)
+  (array)
+  (arguments)
+  (formal_parameters)
+  (template_string)
+  (import_statement)
+  (comment)
+] @fold
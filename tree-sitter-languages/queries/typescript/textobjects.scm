@@ -0,0 +1,12 @@
+[
+  (function_signature)
+  (method_signature)
+  (abstract_method_signature)
+] @function.around
+
+[
+  (abstract_class_declaration)
+  (interface_declaration)
+  (enum_declaration)
+  (internal_module)
+] @class.around